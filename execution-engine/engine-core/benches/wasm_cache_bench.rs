@@ -0,0 +1,57 @@
+#![feature(test)]
+extern crate casperlabs_engine_core;
+extern crate engine_wasm_prep;
+extern crate parking_lot;
+extern crate test;
+extern crate wabt;
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use test::black_box;
+use test::Bencher;
+
+use casperlabs_engine_core::engine_state::wasm_cache::{CachingPreprocessor, WasmCache};
+use engine_wasm_prep::wasm_costs::WasmCosts;
+use engine_wasm_prep::{Preprocessor, WasmiPreprocessor};
+
+const FUNCTION_COUNT: usize = 200;
+
+/// A module with enough functions for its preparation to take measurable time.
+fn module_bytes() -> Vec<u8> {
+    let mut wat = String::from(r#"(module (import "env" "memory" (memory 16 64))"#);
+    for i in 0..FUNCTION_COUNT {
+        wat.push_str(&format!(
+            r#"(func (export "f{0}") (param i32) (result i32)
+                (if (result i32) (i32.gt_s (get_local 0) (i32.const {0}))
+                    (then (i32.mul (get_local 0) (i32.const 3)))
+                    (else (i32.add (get_local 0) (i32.const 7)))))"#,
+            i
+        ));
+    }
+    wat.push(')');
+    wabt::wat2wasm(wat).expect("should compile wat")
+}
+
+fn caching_preprocessor(capacity: usize) -> CachingPreprocessor {
+    let preprocessor = WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap());
+    let cache = Arc::new(Mutex::new(WasmCache::new(capacity)));
+    CachingPreprocessor::new(preprocessor, 1, cache)
+}
+
+#[bench]
+fn prepare_module_cold(b: &mut Bencher) {
+    let module_bytes = module_bytes();
+    b.iter(|| {
+        let preprocessor = caching_preprocessor(1);
+        preprocessor.preprocess(black_box(&module_bytes)).unwrap()
+    });
+}
+
+#[bench]
+fn prepare_module_warm(b: &mut Bencher) {
+    let module_bytes = module_bytes();
+    let preprocessor = caching_preprocessor(1);
+    preprocessor.preprocess(&module_bytes).unwrap();
+    b.iter(|| preprocessor.preprocess(black_box(&module_bytes)).unwrap());
+}
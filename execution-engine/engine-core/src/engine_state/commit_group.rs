@@ -0,0 +1,252 @@
+//! Grouping of commits which arrive within a short window of each other into a single write
+//! transaction, so that they share the cost of syncing it to disk.
+//!
+//! The first commit of a group waits for the window to pass, then commits the group: every commit
+//! which joined it in the meantime, each on top of its own prestate.  Each commit still gets its
+//! own result, as if it had been committed alone.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex};
+
+use contract_ffi::key::Key;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_shared::transform::Transform;
+use engine_storage::global_state::{CommitResult, History};
+
+type Commit = (Blake2bHash, HashMap<Key, Transform>);
+
+/// The commits of a group, replaced by their results once the group is committed.
+enum Outcome<E> {
+    Open(Vec<Commit>),
+    Committing,
+    /// The result of each commit, in the order they joined, until it is taken by its caller
+    Committed(Vec<Option<CommitResult>>),
+    /// The transaction failed, so none of the commits were written
+    Failed(E),
+    /// Committing the group panicked
+    Abandoned,
+}
+
+impl<E> Outcome<E> {
+    fn is_pending(&self) -> bool {
+        match self {
+            Outcome::Open(_) | Outcome::Committing => true,
+            _ => false,
+        }
+    }
+}
+
+struct Group<E> {
+    outcome: Mutex<Outcome<E>>,
+    done: Condvar,
+}
+
+/// Abandons a group if committing it panics, so that the commits waiting on it do not wait
+/// forever.
+struct AbandonOnPanic<'a, E: 'a>(&'a Group<E>);
+
+impl<'a, E: 'a> Drop for AbandonOnPanic<'a, E> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            *self.0.outcome.lock() = Outcome::Abandoned;
+            self.0.done.notify_all();
+        }
+    }
+}
+
+/// Groups commits arriving within `window` of the first commit of a group.
+pub struct CommitGroups<E> {
+    window: Duration,
+    open: Mutex<Option<Arc<Group<E>>>>,
+}
+
+impl<E: Clone> CommitGroups<E> {
+    pub fn new(window: Duration) -> Self {
+        CommitGroups {
+            window,
+            open: Mutex::new(None),
+        }
+    }
+
+    /// Commits `effects` on top of `prestate_hash` to `state` as part of the open group, opening
+    /// one if there is none, and returns the result once the group is committed.
+    ///
+    /// If the group's transaction fails, nothing in it is written and every commit in it fails
+    /// with the same error.
+    pub fn commit<H>(
+        &self,
+        state: &Mutex<H>,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+    ) -> Result<CommitResult, E>
+    where
+        H: History<Error = E>,
+    {
+        let (group, index) = self.join((prestate_hash, effects));
+        if index == 0 {
+            self.commit_group(&group, state, correlation_id);
+        }
+
+        let mut outcome = group.outcome.lock();
+        while outcome.is_pending() {
+            group.done.wait(&mut outcome);
+        }
+        match *outcome {
+            Outcome::Committed(ref mut results) => Ok(results[index]
+                .take()
+                .expect("each result should be taken once")),
+            Outcome::Failed(ref error) => Err(error.clone()),
+            _ => panic!("commit group of this commit panicked"),
+        }
+    }
+
+    /// Adds `commit` to the open group, opening one if there is none, and returns the group along
+    /// with the index of `commit` in it.
+    fn join(&self, commit: Commit) -> (Arc<Group<E>>, usize) {
+        let mut open = self.open.lock();
+        if let Some(ref group) = *open {
+            if let Outcome::Open(ref mut commits) = *group.outcome.lock() {
+                commits.push(commit);
+                return (Arc::clone(group), commits.len() - 1);
+            }
+        }
+        let group = Arc::new(Group {
+            outcome: Mutex::new(Outcome::Open(vec![commit])),
+            done: Condvar::new(),
+        });
+        *open = Some(Arc::clone(&group));
+        (group, 0)
+    }
+
+    /// Waits for the window to pass, closes `group` to further commits and commits it.
+    fn commit_group<H>(&self, group: &Group<E>, state: &Mutex<H>, correlation_id: CorrelationId)
+    where
+        H: History<Error = E>,
+    {
+        thread::sleep(self.window);
+
+        let commits = {
+            let mut open = self.open.lock();
+            *open = None;
+            match mem::replace(&mut *group.outcome.lock(), Outcome::Committing) {
+                Outcome::Open(commits) => commits,
+                _ => unreachable!("a group is only committed by its first commit"),
+            }
+        };
+
+        let _abandon_on_panic = AbandonOnPanic(group);
+        let outcome = match state.lock().commit_group(correlation_id, commits) {
+            Ok(results) => Outcome::Committed(results.into_iter().map(Some).collect()),
+            Err(error) => Outcome::Failed(error),
+        };
+        *group.outcome.lock() = outcome;
+        group.done.notify_all();
+    }
+}
+
+impl<E> fmt::Debug for CommitGroups<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommitGroups")
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use parking_lot::Mutex;
+
+    use contract_ffi::key::Key;
+    use contract_ffi::value::Value;
+    use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+    use engine_shared::transform::Transform;
+    use engine_storage::global_state::in_memory::InMemoryGlobalState;
+    use engine_storage::global_state::{CommitResult, History, StateReader};
+
+    use super::CommitGroups;
+
+    #[test]
+    fn should_return_the_result_of_each_grouped_commit() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let prestate_hash = global_state.root_hash;
+        let state = Arc::new(Mutex::new(global_state));
+        let commit_groups = Arc::new(CommitGroups::new(Duration::from_millis(50)));
+
+        let handles: Vec<_> = (0..4u8)
+            .map(|i| {
+                let state = Arc::clone(&state);
+                let commit_groups = Arc::clone(&commit_groups);
+                thread::spawn(move || {
+                    let effects =
+                        vec![(Key::Hash([i; 32]), Transform::Write(Value::Int32(i.into())))]
+                            .into_iter()
+                            .collect();
+                    commit_groups.commit(&*state, correlation_id, prestate_hash, effects)
+                })
+            })
+            .collect();
+        let post_state_hashes: Vec<Blake2bHash> = handles
+            .into_iter()
+            .map(|handle| match handle.join().unwrap().unwrap() {
+                CommitResult::Success { state_root, .. } => state_root,
+                other => panic!("commit failed: {}", other),
+            })
+            .collect();
+
+        let state = state.lock();
+        for (i, post_state_hash) in post_state_hashes.into_iter().enumerate() {
+            let reader = state.checkout(post_state_hash).unwrap().unwrap();
+            let read = |key: usize| {
+                reader
+                    .read(correlation_id, &Key::Hash([key as u8; 32]))
+                    .unwrap()
+            };
+            assert_eq!(read(i), Some(Value::Int32(i as i32)));
+            // each commit is applied on top of the prestate alone
+            assert_eq!(read((i + 1) % 4), None);
+        }
+    }
+
+    #[test]
+    fn should_fail_only_the_failed_commit_of_a_group() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let prestate_hash = global_state.root_hash;
+        let state = Arc::new(Mutex::new(global_state));
+        let commit_groups = Arc::new(CommitGroups::new(Duration::from_millis(50)));
+
+        let commit = |transform: Transform| {
+            let state = Arc::clone(&state);
+            let commit_groups = Arc::clone(&commit_groups);
+            thread::spawn(move || {
+                let effects = vec![(Key::Hash([1u8; 32]), transform)]
+                    .into_iter()
+                    .collect();
+                commit_groups.commit(&*state, correlation_id, prestate_hash, effects)
+            })
+        };
+        let written = commit(Transform::Write(Value::Int32(1)));
+        let missing = commit(Transform::AddInt32(1));
+
+        match written.join().unwrap().unwrap() {
+            CommitResult::Success { .. } => (),
+            other => panic!("commit failed: {}", other),
+        }
+        match missing.join().unwrap().unwrap() {
+            CommitResult::KeyNotFound(key) => assert_eq!(key, Key::Hash([1u8; 32])),
+            other => panic!("commit should have failed, got {}", other),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! A least recently used cache of deploy results, keyed by deploy hash and prestate hash, so
+//! that a deploy sent again, e.g. after a client timeout, is not executed twice.
+//!
+//! The execution cache uses it too, with the hash of a deploy's content in place of the deploy
+//! hash, so that identical deploys from any client share a result.
+//!
+//! Results are kept serialized, in whatever form the caller chooses, as the execution engine
+//! doesn't need to read them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use engine_shared::newtypes::Blake2bHash;
+
+/// Identifies a deploy run against a particular state.  The same deploy run against a different
+/// state may have a different result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DedupKey {
+    pub deploy_hash: Vec<u8>,
+    pub prestate_hash: Blake2bHash,
+}
+
+impl DedupKey {
+    pub fn new(deploy_hash: Vec<u8>, prestate_hash: Blake2bHash) -> Self {
+        DedupKey {
+            deploy_hash,
+            prestate_hash,
+        }
+    }
+}
+
+/// Holds up to `capacity` results, evicting the least recently used one when full.
+#[derive(Debug)]
+pub struct DedupCache {
+    capacity: usize,
+    entries: HashMap<DedupKey, (Arc<Vec<u8>>, u64)>,
+    // maps the last use of each entry to its key, oldest first
+    recency: BTreeMap<u64, DedupKey>,
+    uses: u64,
+}
+
+impl DedupCache {
+    pub fn new(capacity: usize) -> Self {
+        DedupCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// Returns the result at `key` and marks it as the most recently used one.
+    pub fn get(&mut self, key: &DedupKey) -> Option<Arc<Vec<u8>>> {
+        let next_use = self.next_use();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.1);
+        self.recency.insert(next_use, key.clone());
+        entry.1 = next_use;
+        Some(Arc::clone(&entry.0))
+    }
+
+    /// Inserts `result` at `key`, evicting the least recently used result if the cache is full.
+    pub fn insert(&mut self, key: DedupKey, result: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let next_use = self.next_use();
+        if let Some((_, last_use)) = self
+            .entries
+            .insert(key.clone(), (Arc::new(result), next_use))
+        {
+            self.recency.remove(&last_use);
+        }
+        self.recency.insert(next_use, key);
+        while self.entries.len() > self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(last_use) => *last_use,
+                None => break,
+            };
+            if let Some(oldest_key) = self.recency.remove(&oldest) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(deploy: u8, prestate: u8) -> DedupKey {
+        DedupKey::new(vec![deploy; 32], [prestate; 32].into())
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        let mut cache = DedupCache::new(2);
+        cache.insert(key(1, 0), vec![1]);
+        cache.insert(key(2, 0), vec![2]);
+        assert_eq!(cache.get(&key(1, 0)), Some(Arc::new(vec![1])));
+
+        cache.insert(key(3, 0), vec![3]);
+
+        assert_eq!(cache.get(&key(2, 0)), None);
+        assert_eq!(cache.get(&key(1, 0)), Some(Arc::new(vec![1])));
+        assert_eq!(cache.get(&key(3, 0)), Some(Arc::new(vec![3])));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn should_not_conflate_prestates() {
+        let mut cache = DedupCache::new(2);
+        cache.insert(key(1, 0), vec![1]);
+        cache.insert(key(1, 1), vec![2]);
+
+        assert_eq!(cache.get(&key(1, 0)), Some(Arc::new(vec![1])));
+        assert_eq!(cache.get(&key(1, 1)), Some(Arc::new(vec![2])));
+        assert_eq!(cache.get(&key(1, 2)), None);
+    }
+
+    #[test]
+    fn should_not_hold_entries_without_capacity() {
+        let mut cache = DedupCache::new(0);
+        cache.insert(key(1, 0), vec![1]);
+        assert_eq!(cache.get(&key(1, 0)), None);
+        assert!(cache.is_empty());
+    }
+}
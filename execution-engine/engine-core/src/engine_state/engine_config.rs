@@ -1,7 +1,50 @@
+use std::time::Duration;
+
+use engine_wasm_prep::wasm_costs::WasmCosts;
+use engine_wasm_prep::{DEFAULT_MAX_MODULE_SIZE, MEM_PAGES};
+
+/// Deploy results kept to answer repeated deploys unless configured otherwise.
+pub const DEFAULT_DEDUP_CACHE_SIZE: usize = 1000;
+
+/// Deploys accepted in a single exec request unless configured otherwise; well above the number
+/// of deploys in a block.
+pub const DEFAULT_MAX_DEPLOYS_PER_BATCH: usize = 10_000;
+
+/// Runs of a deploy made to estimate its gas unless configured otherwise.
+pub const DEFAULT_ESTIMATE_GAS_MAX_TRIALS: u32 = 16;
+
+/// Names a query may follow from its base key unless configured otherwise; real queries follow a
+/// handful.
+pub const DEFAULT_MAX_QUERY_DEPTH: usize = 64;
+
+/// Trie nodes a query may visit unless configured otherwise; a single read visits at most a few
+/// dozen.
+pub const DEFAULT_MAX_QUERY_NODES: usize = 10_000;
+
 /// The runtime configuration of the execution engine
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EngineConfig {
     use_payment_code: bool,
+    read_only: bool,
+    wasm_costs: Option<WasmCosts>,
+    max_wasm_size: usize,
+    max_memory_pages: u32,
+    execution_timeout: Option<Duration>,
+    gas_profiling: bool,
+    max_gas_limit: Option<u64>,
+    default_gas_limit: Option<u64>,
+    dedup_cache_size: usize,
+    exec_cache_size: usize,
+    wasm_cache_size: usize,
+    commit_retries: u32,
+    max_deploys_per_batch: usize,
+    tls: bool,
+    metrics: bool,
+    commit_group_window: Duration,
+    estimate_gas_max_trials: u32,
+    read_timeout: Option<Duration>,
+    max_query_depth: usize,
+    max_query_nodes: usize,
 }
 
 impl EngineConfig {
@@ -15,12 +58,260 @@ impl EngineConfig {
         self.use_payment_code = arg;
         self
     }
+
+    /// Sets the `read_only` field to the given arg.
+    pub fn read_only(mut self, arg: bool) -> EngineConfig {
+        self.read_only = arg;
+        self
+    }
+
+    /// Sets the `wasm_costs` field to the given arg.
+    pub fn wasm_costs(mut self, arg: Option<WasmCosts>) -> EngineConfig {
+        self.wasm_costs = arg;
+        self
+    }
+
+    /// Returns the costs to meter wasm with in place of the protocol version's built-in costs,
+    /// if any were configured.
+    pub fn get_wasm_costs(&self) -> Option<&WasmCosts> {
+        self.wasm_costs.as_ref()
+    }
+
+    /// Sets the `max_wasm_size` field to the given arg.
+    pub fn max_wasm_size(mut self, arg: usize) -> EngineConfig {
+        self.max_wasm_size = arg;
+        self
+    }
+
+    /// Returns the largest accepted wasm module, in bytes.
+    pub fn get_max_wasm_size(&self) -> usize {
+        self.max_wasm_size
+    }
+
+    /// Sets the `max_memory_pages` field to the given arg.
+    pub fn max_memory_pages(mut self, arg: u32) -> EngineConfig {
+        self.max_memory_pages = arg;
+        self
+    }
+
+    /// Returns the most pages of linear memory a module may declare or grow to.
+    pub fn get_max_memory_pages(&self) -> u32 {
+        self.max_memory_pages
+    }
+
+    /// Sets the `execution_timeout` field to the given arg.
+    pub fn execution_timeout(mut self, arg: Option<Duration>) -> EngineConfig {
+        self.execution_timeout = arg;
+        self
+    }
+
+    /// Returns the longest a single deploy may execute for, if limited.
+    pub fn get_execution_timeout(&self) -> Option<Duration> {
+        self.execution_timeout
+    }
+
+    /// Sets the `gas_profiling` field to the given arg.
+    pub fn gas_profiling(mut self, arg: bool) -> EngineConfig {
+        self.gas_profiling = arg;
+        self
+    }
+
+    /// Returns `true` if every deploy's cost should be broken down, rather than just those of
+    /// requests asking for it.
+    pub fn is_gas_profiling(&self) -> bool {
+        self.gas_profiling
+    }
+
+    /// Sets the `max_gas_limit` field to the given arg.
+    pub fn max_gas_limit(mut self, arg: Option<u64>) -> EngineConfig {
+        self.max_gas_limit = arg;
+        self
+    }
+
+    /// Returns the highest gas limit a deploy may run with, if limited.
+    pub fn get_max_gas_limit(&self) -> Option<u64> {
+        self.max_gas_limit
+    }
+
+    /// Sets the `default_gas_limit` field to the given arg.
+    pub fn default_gas_limit(mut self, arg: Option<u64>) -> EngineConfig {
+        self.default_gas_limit = arg;
+        self
+    }
+
+    /// Returns the gas limit of a deploy which does not give a gas price, if there is one.
+    pub fn get_default_gas_limit(&self) -> Option<u64> {
+        self.default_gas_limit
+    }
+
+    /// Sets the `dedup_cache_size` field to the given arg.
+    pub fn dedup_cache_size(mut self, arg: usize) -> EngineConfig {
+        self.dedup_cache_size = arg;
+        self
+    }
+
+    /// Returns how many results of deploys with a hash are kept to answer the same deploy sent
+    /// again; 0 turns deduplication off.
+    pub fn get_dedup_cache_size(&self) -> usize {
+        self.dedup_cache_size
+    }
+
+    /// Sets the `exec_cache_size` field to the given arg.
+    pub fn exec_cache_size(mut self, arg: usize) -> EngineConfig {
+        self.exec_cache_size = arg;
+        self
+    }
+
+    /// Returns how many deploy results are kept to answer deploys with the same content run
+    /// against the same state; 0 turns the execution cache off.
+    pub fn get_exec_cache_size(&self) -> usize {
+        self.exec_cache_size
+    }
+
+    /// Sets the `wasm_cache_size` field to the given arg.
+    pub fn wasm_cache_size(mut self, arg: usize) -> EngineConfig {
+        self.wasm_cache_size = arg;
+        self
+    }
+
+    /// Returns how many prepared session modules are kept to run the same bytes again without
+    /// preparing them; 0 turns the wasm cache off.
+    pub fn get_wasm_cache_size(&self) -> usize {
+        self.wasm_cache_size
+    }
+
+    /// Sets the `commit_retries` field to the given arg.
+    pub fn commit_retries(mut self, arg: u32) -> EngineConfig {
+        self.commit_retries = arg;
+        self
+    }
+
+    /// Returns how many times a commit failing with a transient storage error is retried.
+    pub fn get_commit_retries(&self) -> u32 {
+        self.commit_retries
+    }
+
+    /// Sets the `max_deploys_per_batch` field to the given arg.
+    pub fn max_deploys_per_batch(mut self, arg: usize) -> EngineConfig {
+        self.max_deploys_per_batch = arg;
+        self
+    }
+
+    /// Returns the most deploys a single exec request may carry.
+    pub fn get_max_deploys_per_batch(&self) -> usize {
+        self.max_deploys_per_batch
+    }
+
+    /// Sets the `tls` field to the given arg.
+    pub fn tls(mut self, arg: bool) -> EngineConfig {
+        self.tls = arg;
+        self
+    }
+
+    /// Returns `true` if the server accepts connections over TLS only.
+    pub fn is_tls_enabled(&self) -> bool {
+        self.tls
+    }
+
+    /// Sets the `metrics` field to the given arg.
+    pub fn metrics(mut self, arg: bool) -> EngineConfig {
+        self.metrics = arg;
+        self
+    }
+
+    /// Returns `true` if the server serves Prometheus metrics.
+    pub fn is_metrics_enabled(&self) -> bool {
+        self.metrics
+    }
+
+    /// Sets the `commit_group_window` field to the given arg.
+    pub fn commit_group_window(mut self, arg: Duration) -> EngineConfig {
+        self.commit_group_window = arg;
+        self
+    }
+
+    /// Returns how long the first commit of a group waits for others to join it, so that they are
+    /// written in one transaction; zero commits each on its own.
+    pub fn get_commit_group_window(&self) -> Duration {
+        self.commit_group_window
+    }
+
+    /// Sets the `estimate_gas_max_trials` field to the given arg.
+    pub fn estimate_gas_max_trials(mut self, arg: u32) -> EngineConfig {
+        self.estimate_gas_max_trials = arg;
+        self
+    }
+
+    /// Returns the most times a deploy is run to search for the smallest gas limit it succeeds
+    /// with.
+    pub fn get_estimate_gas_max_trials(&self) -> u32 {
+        self.estimate_gas_max_trials
+    }
+
+    /// Sets the `read_timeout` field to the given arg.
+    pub fn read_timeout(mut self, arg: Option<Duration>) -> EngineConfig {
+        self.read_timeout = arg;
+        self
+    }
+
+    /// Returns the longest a streaming response waits for the client to take its next message
+    /// before abandoning the stream, if limited.
+    pub fn get_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Sets the `max_query_depth` field to the given arg.
+    pub fn max_query_depth(mut self, arg: usize) -> EngineConfig {
+        self.max_query_depth = arg;
+        self
+    }
+
+    /// Returns the most names a query may follow from its base key.
+    pub fn get_max_query_depth(&self) -> usize {
+        self.max_query_depth
+    }
+
+    /// Sets the `max_query_nodes` field to the given arg.
+    pub fn max_query_nodes(mut self, arg: usize) -> EngineConfig {
+        self.max_query_nodes = arg;
+        self
+    }
+
+    /// Returns the most trie nodes a query may visit, summed over the keys it reads.
+    pub fn get_max_query_nodes(&self) -> usize {
+        self.max_query_nodes
+    }
+
+    /// Returns `true` if global state must not be mutated.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 }
 
 impl Default for EngineConfig {
     fn default() -> Self {
         EngineConfig {
             use_payment_code: false,
+            read_only: false,
+            wasm_costs: None,
+            max_wasm_size: DEFAULT_MAX_MODULE_SIZE,
+            max_memory_pages: MEM_PAGES,
+            execution_timeout: None,
+            gas_profiling: false,
+            max_gas_limit: None,
+            default_gas_limit: None,
+            dedup_cache_size: DEFAULT_DEDUP_CACHE_SIZE,
+            exec_cache_size: 0,
+            wasm_cache_size: 0,
+            commit_retries: 0,
+            max_deploys_per_batch: DEFAULT_MAX_DEPLOYS_PER_BATCH,
+            tls: false,
+            metrics: false,
+            commit_group_window: Duration::from_secs(0),
+            estimate_gas_max_trials: DEFAULT_ESTIMATE_GAS_MAX_TRIALS,
+            read_timeout: None,
+            max_query_depth: DEFAULT_MAX_QUERY_DEPTH,
+            max_query_nodes: DEFAULT_MAX_QUERY_NODES,
         }
     }
 }
@@ -8,7 +8,7 @@ use execution;
 pub enum Error {
     #[fail(display = "Invalid public key length: expected {}, actual {}", _0, _1)]
     InvalidPublicKeyLength { expected: usize, actual: usize },
-    #[fail(display = "Wasm preprocessing error: {:?}", _0)]
+    #[fail(display = "Wasm preprocessing error: {}", _0)]
     WasmPreprocessingError(engine_wasm_prep::PreprocessingError),
     #[fail(display = "Wasm serialization error: {:?}", _0)]
     WasmSerializationError(parity_wasm::SerializationError),
@@ -18,6 +18,45 @@ pub enum Error {
     StorageError(engine_storage::error::Error),
     #[fail(display = "Authorization failure: not authorized.")]
     AuthorizationError,
+    #[fail(
+        display = "Overflow: {} gas at a gas price of {} costs more than fits in 64 bits",
+        gas_used, gas_price
+    )]
+    Overflow { gas_used: u64, gas_price: u64 },
+}
+
+impl Error {
+    /// Returns `true` if global state could not be read because every LMDB reader slot was
+    /// taken.  Nothing went wrong with the request itself, so it can be retried.
+    pub fn is_readers_full(&self) -> bool {
+        match self {
+            Error::StorageError(error) | Error::ExecError(execution::Error::Storage(error)) => {
+                error.is_readers_full()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if global state was read at a state root which it does not contain.
+    pub fn is_root_not_found(&self) -> bool {
+        match self {
+            Error::StorageError(error) | Error::ExecError(execution::Error::Storage(error)) => {
+                error.is_root_not_found()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if global state could not be written for a reason expected to clear up by
+    /// itself, so the write can be retried.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::StorageError(error) | Error::ExecError(execution::Error::Storage(error)) => {
+                error.is_transient()
+            }
+            _ => false,
+        }
+    }
 }
 
 impl From<engine_wasm_prep::PreprocessingError> for Error {
@@ -1,3 +1,8 @@
+use std::time::Duration;
+
+use engine_wasm_prep::gas_profile::GasProfile;
+use execution::HostCall;
+
 use super::error::Error;
 use super::execution_effect::ExecutionEffect;
 
@@ -8,9 +13,24 @@ pub enum ExecutionResult {
         error: Error,
         effect: ExecutionEffect,
         cost: u64,
+        /// What `cost` was spent on, if the execution was profiled
+        gas_profile: Option<GasProfile>,
+        /// The host functions called, if the execution was traced
+        trace: Option<Vec<HostCall>>,
+        /// What the execution used besides gas
+        stats: ExecutionStats,
     },
     /// Execution was finished successfully
-    Success { effect: ExecutionEffect, cost: u64 },
+    Success {
+        effect: ExecutionEffect,
+        cost: u64,
+        /// What `cost` was spent on, if the execution was profiled
+        gas_profile: Option<GasProfile>,
+        /// The host functions called, if the execution was traced
+        trace: Option<Vec<HostCall>>,
+        /// What the execution used besides gas
+        stats: ExecutionStats,
+    },
 }
 
 impl ExecutionResult {
@@ -22,6 +42,66 @@ impl ExecutionResult {
             error,
             effect: Default::default(),
             cost: 0,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
+        }
+    }
+
+    /// Returns the effects of the execution, whether it succeeded or not.
+    pub fn effect(&self) -> &ExecutionEffect {
+        match self {
+            ExecutionResult::Failure { effect, .. } | ExecutionResult::Success { effect, .. } => {
+                effect
+            }
+        }
+    }
+
+    /// Returns the breakdown of the execution's cost, if it was profiled.
+    pub fn gas_profile(&self) -> Option<&GasProfile> {
+        match self {
+            ExecutionResult::Failure { gas_profile, .. }
+            | ExecutionResult::Success { gas_profile, .. } => gas_profile.as_ref(),
         }
     }
+
+    /// Returns the host functions called, in order, if the execution was traced.
+    pub fn trace(&self) -> Option<&[HostCall]> {
+        match self {
+            ExecutionResult::Failure { trace, .. } | ExecutionResult::Success { trace, .. } => {
+                trace.as_ref().map(Vec::as_slice)
+            }
+        }
+    }
+
+    /// Returns what the execution used besides gas, whether it succeeded or not.
+    pub fn stats(&self) -> &ExecutionStats {
+        match self {
+            ExecutionResult::Failure { stats, .. } | ExecutionResult::Success { stats, .. } => {
+                stats
+            }
+        }
+    }
+}
+
+/// Measurements taken of every execution, which unlike a [GasProfile] are cheap enough to always
+/// take.  Executions refused before the contract ran have default (zero) stats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionStats {
+    /// Wall-clock time spent executing the deploy
+    pub duration: Duration,
+    /// The most pages of linear memory held by any one module of the execution
+    pub peak_memory_pages: u32,
+    /// Values read from global state; values already read or written by the deploy are cached
+    /// and not counted again
+    pub trie_reads: u64,
+}
+
+/// Returns what `gas_used` costs at `gas_price`, or [Error::Overflow] if that does not fit in a
+/// `u64`.
+pub fn cost(gas_used: u64, gas_price: u64) -> Result<u64, Error> {
+    gas_used.checked_mul(gas_price).ok_or(Error::Overflow {
+        gas_used,
+        gas_price,
+    })
 }
@@ -366,8 +366,8 @@ impl GenesisResult {
             CommitResult::RootNotFound => GenesisResult::RootNotFound,
             CommitResult::KeyNotFound(key) => GenesisResult::KeyNotFound(key),
             CommitResult::TypeMismatch(type_mismatch) => GenesisResult::TypeMismatch(type_mismatch),
-            CommitResult::Success(post_state_hash) => GenesisResult::Success {
-                post_state_hash,
+            CommitResult::Success { state_root, .. } => GenesisResult::Success {
+                post_state_hash: state_root,
                 effect,
             },
         }
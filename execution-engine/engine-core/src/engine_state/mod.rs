@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use parking_lot::Mutex;
 
@@ -11,29 +13,64 @@ use contract_ffi::value::{Value, U512};
 use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 use engine_shared::transform::Transform;
 use engine_state::utils::WasmiBytes;
-use engine_storage::global_state::{CommitResult, History, StateReader};
+use engine_storage::global_state::{
+    CommitResult, DiffResult, History, PruneResult, StateReader, StoreStats, VerifyResult,
+};
 use engine_wasm_prep::wasm_costs::WasmCosts;
-use engine_wasm_prep::Preprocessor;
+use engine_wasm_prep::{Preprocessor, WasmiPreprocessor};
 use execution::{self, Executor};
 use tracking_copy::TrackingCopy;
 
+use self::commit_group::CommitGroups;
+use self::dedup_cache::{DedupCache, DedupKey};
 pub use self::engine_config::EngineConfig;
 use self::error::{Error, RootNotFound};
 use self::execution_result::ExecutionResult;
 use self::genesis::{create_genesis_effects, GenesisResult};
+use self::parallel::{DeployItem, ParallelExecutionError, ParallelExecutionResult};
+use self::wasm_cache::{CachingPreprocessor, WasmCache};
 
+pub mod commit_group;
+pub mod dedup_cache;
 pub mod engine_config;
 pub mod error;
 pub mod execution_effect;
 pub mod execution_result;
 pub mod genesis;
 pub mod op;
+pub mod parallel;
 pub mod utils;
+pub mod wasm_cache;
 
 #[derive(Debug)]
-pub struct EngineState<H> {
+pub struct EngineState<H: History> {
     config: EngineConfig,
     state: Arc<Mutex<H>>,
+    /// A clone of `state` which checks states out without taking its lock, so that reads are not
+    /// held up by a commit in progress
+    reader: Arc<H>,
+    dedup_cache: Arc<Mutex<DedupCache>>,
+    exec_cache: Arc<Mutex<DedupCache>>,
+    wasm_cache: Arc<Mutex<WasmCache>>,
+    genesis_hash: Arc<Mutex<Option<Blake2bHash>>>,
+    /// Groups commits into shared transactions, if a commit group window is configured
+    commit_groups: Option<Arc<CommitGroups<H::Error>>>,
+}
+
+/// Clones share the same global state.
+impl<H: History> Clone for EngineState<H> {
+    fn clone(&self) -> Self {
+        EngineState {
+            config: self.config.clone(),
+            state: Arc::clone(&self.state),
+            reader: Arc::clone(&self.reader),
+            dedup_cache: Arc::clone(&self.dedup_cache),
+            exec_cache: Arc::clone(&self.exec_cache),
+            wasm_cache: Arc::clone(&self.wasm_cache),
+            genesis_hash: Arc::clone(&self.genesis_hash),
+            commit_groups: self.commit_groups.clone(),
+        }
+    }
 }
 
 impl<H> EngineState<H>
@@ -41,15 +78,95 @@ where
     H: History,
     H::Error: Into<execution::Error>,
 {
-    pub fn new(state: H, config: EngineConfig) -> EngineState<H> {
+    pub fn new(state: H, config: EngineConfig) -> EngineState<H>
+    where
+        H: Clone,
+    {
+        let reader = Arc::new(state.clone());
         let state = Arc::new(Mutex::new(state));
-        EngineState { config, state }
+        let dedup_cache = Arc::new(Mutex::new(DedupCache::new(config.get_dedup_cache_size())));
+        let exec_cache = Arc::new(Mutex::new(DedupCache::new(config.get_exec_cache_size())));
+        let wasm_cache = Arc::new(Mutex::new(WasmCache::new(config.get_wasm_cache_size())));
+        let commit_group_window = config.get_commit_group_window();
+        let commit_groups = if commit_group_window > Duration::from_secs(0) {
+            Some(Arc::new(CommitGroups::new(commit_group_window)))
+        } else {
+            None
+        };
+        EngineState {
+            config,
+            state,
+            reader,
+            dedup_cache,
+            exec_cache,
+            wasm_cache,
+            genesis_hash: Arc::new(Mutex::new(None)),
+            commit_groups,
+        }
     }
 
     pub fn config(&self) -> &EngineConfig {
         &self.config
     }
 
+    /// Returns the post state root of genesis, which every node of a network must agree on, if
+    /// it is known.
+    pub fn genesis_hash(&self) -> Option<Blake2bHash> {
+        *self.genesis_hash.lock()
+    }
+
+    /// Records `genesis_hash` as the post state root of genesis, replacing any known before.
+    pub fn set_genesis_hash(&self, genesis_hash: Blake2bHash) {
+        *self.genesis_hash.lock() = Some(genesis_hash);
+    }
+
+    /// Returns the serialized result stored for the deploy with `deploy_hash` run against
+    /// `prestate_hash`, if it is still cached.
+    pub fn get_deploy_result(
+        &self,
+        deploy_hash: &[u8],
+        prestate_hash: Blake2bHash,
+    ) -> Option<Arc<Vec<u8>>> {
+        let key = DedupKey::new(deploy_hash.to_vec(), prestate_hash);
+        self.dedup_cache.lock().get(&key)
+    }
+
+    /// Stores the serialized `result` of the deploy with `deploy_hash` run against
+    /// `prestate_hash`, to be returned if the same deploy is sent again.
+    pub fn put_deploy_result(
+        &self,
+        deploy_hash: &[u8],
+        prestate_hash: Blake2bHash,
+        result: Vec<u8>,
+    ) {
+        let key = DedupKey::new(deploy_hash.to_vec(), prestate_hash);
+        self.dedup_cache.lock().insert(key, result);
+    }
+
+    /// Returns the serialized result stored for a deploy whose content, together with the
+    /// request it was sent in, hashes to `content_hash`, run against `prestate_hash`, if it is
+    /// still cached.
+    pub fn get_exec_result(
+        &self,
+        content_hash: Blake2bHash,
+        prestate_hash: Blake2bHash,
+    ) -> Option<Arc<Vec<u8>>> {
+        let key = DedupKey::new(content_hash.to_vec(), prestate_hash);
+        self.exec_cache.lock().get(&key)
+    }
+
+    /// Stores the serialized `result` of a deploy whose content hashes to `content_hash`, run
+    /// against `prestate_hash`, to be returned for any deploy with the same content.
+    pub fn put_exec_result(
+        &self,
+        content_hash: Blake2bHash,
+        prestate_hash: Blake2bHash,
+        result: Vec<u8>,
+    ) {
+        let key = DedupKey::new(content_hash.to_vec(), prestate_hash);
+        self.exec_cache.lock().insert(key, result);
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn commit_genesis(
         &self,
@@ -79,6 +196,15 @@ where
             .map_err(Into::into)?;
 
         let genesis_result = GenesisResult::from_commit_result(commit_result, effects);
+        if let GenesisResult::Success {
+            post_state_hash, ..
+        } = genesis_result
+        {
+            state_guard
+                .record_genesis_root(post_state_hash)
+                .map_err(Into::into)?;
+            self.set_genesis_hash(post_state_hash);
+        }
 
         Ok(genesis_result)
     }
@@ -91,12 +217,111 @@ where
         &self,
         hash: Blake2bHash,
     ) -> Result<Option<TrackingCopy<H::Reader>>, Error> {
-        match self.state.lock().checkout(hash).map_err(Into::into)? {
+        match self.reader.checkout(hash).map_err(Into::into)? {
             Some(tc) => Ok(Some(TrackingCopy::new(tc))),
             None => Ok(None),
         }
     }
 
+    /// Like [`tracking_copy`](EngineState::tracking_copy), but fails with
+    /// [`RootNotFound`](engine_storage::error::Error::RootNotFound) if `hash` is not a state root
+    /// in global state.
+    pub fn existing_tracking_copy(
+        &self,
+        hash: Blake2bHash,
+    ) -> Result<TrackingCopy<H::Reader>, Error> {
+        self.tracking_copy(hash)?
+            .ok_or_else(|| engine_storage::error::Error::RootNotFound(hash).into())
+    }
+
+    /// Returns the root of the state holding no keys, which is the same for every global state.
+    pub fn empty_state_root(&self) -> Blake2bHash {
+        self.reader.empty_root()
+    }
+
+    pub fn store_stats(&self) -> Result<Option<StoreStats>, Error> {
+        self.reader.stats().map_err(Into::into)
+    }
+
+    pub fn state_roots(&self, offset: u64, limit: u64) -> Result<Option<Vec<Blake2bHash>>, Error> {
+        self.reader.state_roots(offset, limit).map_err(Into::into)
+    }
+
+    /// Writes a consistent copy of the global state store to the directory at `path`.  Commits
+    /// wait until the copy is complete.
+    pub fn copy_store_to(&self, path: &Path, compact: bool) -> Result<Option<u64>, Error> {
+        self.state.lock().copy_to(path, compact).map_err(Into::into)
+    }
+
+    /// Flushes everything committed so far to durable storage.
+    pub fn flush_store(&self) -> Result<(), Error> {
+        self.state.lock().flush().map_err(Into::into)
+    }
+
+    /// Returns up to `limit` keys whose values differ between the states at `before_root` and
+    /// `after_root`.
+    pub fn diff_states(
+        &self,
+        correlation_id: CorrelationId,
+        before_root: Blake2bHash,
+        after_root: Blake2bHash,
+        limit: usize,
+    ) -> Result<DiffResult<Key, Value>, Error> {
+        self.reader
+            .diff(correlation_id, before_root, after_root, limit)
+            .map_err(Into::into)
+    }
+
+    /// Calls `f` with every key/value pair of the state at `root`, ordered by key bytes, for as
+    /// long as it returns `true`.  Returns whether every pair was visited, or `None` if `root` is
+    /// not in global state.
+    pub fn export_state<F>(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+        f: F,
+    ) -> Result<Option<bool>, Error>
+    where
+        F: FnMut(Key, Value) -> bool,
+    {
+        self.reader
+            .for_each_pair(correlation_id, root, f)
+            .map_err(Into::into)
+    }
+
+    /// Checks that every node of the trie at `root` is present and stored at its own hash,
+    /// without modifying global state.
+    pub fn verify_store(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<VerifyResult, Error> {
+        self.reader.verify(correlation_id, root).map_err(Into::into)
+    }
+
+    /// Deletes every trie node which is not reachable from one of the `retain` roots, or from a
+    /// state which is still in use.  Commits and checkouts wait until it is done.
+    pub fn prune_store(
+        &self,
+        correlation_id: CorrelationId,
+        retain: &[Blake2bHash],
+    ) -> Result<Option<PruneResult>, Error> {
+        self.state
+            .lock()
+            .prune(correlation_id, retain)
+            .map_err(Into::into)
+    }
+
+    /// Wraps `preprocessor`, which is configured for `protocol_version`, so that it shares the
+    /// modules it prepares through the wasm cache.
+    pub fn caching_preprocessor(
+        &self,
+        preprocessor: WasmiPreprocessor,
+        protocol_version: u64,
+    ) -> CachingPreprocessor {
+        CachingPreprocessor::new(preprocessor, protocol_version, Arc::clone(&self.wasm_cache))
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn run_deploy<A, P: Preprocessor<A>, E: Executor<A>>(
         &self,
@@ -139,15 +364,121 @@ where
         ))
     }
 
+    /// Runs `deploys` against `prestate_hash` concurrently, on up to `threads` threads, and commits
+    /// their effects.
+    ///
+    /// Effects which do not conflict with those of earlier deploys are committed together in a
+    /// single transaction.  The conflicting deploys are then run again one by one, in the order
+    /// given, each on top of the state left by the previous one, so that deploys touching the same
+    /// keys always take effect in the order given.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_parallel<A, P, E>(
+        &self,
+        deploys: &[DeployItem],
+        prestate_hash: Blake2bHash,
+        blocktime: BlockTime,
+        protocol_version: u64,
+        correlation_id: CorrelationId,
+        executor: &E,
+        preprocessor: &P,
+        threads: usize,
+    ) -> Result<ParallelExecutionResult, ParallelExecutionError>
+    where
+        H: Send + Sync,
+        P: Preprocessor<A> + Sync,
+        E: Executor<A> + Sync,
+    {
+        if self.tracking_copy(prestate_hash)?.is_none() {
+            return Err(ParallelExecutionError::RootNotFound(prestate_hash));
+        }
+
+        let run = |deploy: &DeployItem, prestate_hash: Blake2bHash| {
+            self.run_deploy(
+                &deploy.module_bytes,
+                &deploy.args,
+                deploy.address,
+                deploy.authorized_keys.clone(),
+                blocktime,
+                deploy.nonce,
+                prestate_hash,
+                deploy.gas_limit,
+                protocol_version,
+                correlation_id,
+                executor,
+                preprocessor,
+            )
+            .map_err(|RootNotFound(hash)| ParallelExecutionError::RootNotFound(hash))
+        };
+        let commit = |prestate_hash: Blake2bHash,
+                      effects: Vec<HashMap<Key, Transform>>|
+         -> Result<Blake2bHash, ParallelExecutionError> {
+            let commit_result = self
+                .apply_effects_batch(correlation_id, prestate_hash, effects)
+                .map_err(|error| Error::from(Into::<execution::Error>::into(error)))?;
+            match commit_result {
+                CommitResult::Success { state_root, .. } => Ok(state_root),
+                failure => Err(ParallelExecutionError::Commit(failure)),
+            }
+        };
+
+        let mut execution_results =
+            parallel::run_concurrently(deploys, threads, |deploy| run(deploy, prestate_hash))
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?;
+
+        let (independent, conflicting) = parallel::partition_conflicts(&execution_results);
+
+        let independent_effects = independent
+            .into_iter()
+            .map(|index| execution_results[index].effect().transforms.clone())
+            .collect();
+        let mut post_state_hash = commit(prestate_hash, independent_effects)?;
+
+        for index in conflicting {
+            let execution_result = run(&deploys[index], post_state_hash)?;
+            let effects = execution_result.effect().transforms.clone();
+            post_state_hash = commit(post_state_hash, vec![effects])?;
+            execution_results[index] = execution_result;
+        }
+
+        Ok(ParallelExecutionResult {
+            execution_results,
+            post_state_hash,
+        })
+    }
+
+    /// Applies `effects` on top of `prestate_hash`.  If a commit group window is configured, the
+    /// commit is grouped with those arriving within the window into a single transaction.
     pub fn apply_effect(
         &self,
         correlation_id: CorrelationId,
         prestate_hash: Blake2bHash,
         effects: HashMap<Key, Transform>,
+    ) -> Result<CommitResult, H::Error>
+    where
+        H::Error: Clone,
+    {
+        match self.commit_groups {
+            Some(ref commit_groups) => {
+                commit_groups.commit(&self.state, correlation_id, prestate_hash, effects)
+            }
+            None => self
+                .state
+                .lock()
+                .commit(correlation_id, prestate_hash, effects),
+        }
+    }
+
+    /// Applies each set of effects on top of the previous one in a single commit.
+    pub fn apply_effects_batch(
+        &self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: Vec<HashMap<Key, Transform>>,
     ) -> Result<CommitResult, H::Error> {
         self.state
             .lock()
-            .commit(correlation_id, prestate_hash, effects)
+            .commit_batch(correlation_id, prestate_hash, effects)
     }
 }
 
@@ -0,0 +1,305 @@
+//! Support for [`EngineState::execute_parallel`](super::EngineState::execute_parallel), which
+//! runs the deploys of a block concurrently and only serializes those whose effects conflict.
+
+use std::cmp;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crossbeam_utils::thread;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::account::PublicKey;
+use engine_shared::newtypes::Blake2bHash;
+use engine_storage::global_state::CommitResult;
+
+use super::error::Error;
+use super::execution_result::ExecutionResult;
+use super::op::Op;
+
+/// A deploy to be run by [`EngineState::execute_parallel`](super::EngineState::execute_parallel).
+#[derive(Debug, Clone)]
+pub struct DeployItem {
+    pub module_bytes: Vec<u8>,
+    pub args: Vec<u8>,
+    pub address: Key,
+    pub authorized_keys: BTreeSet<PublicKey>,
+    pub nonce: u64,
+    pub gas_limit: u64,
+}
+
+#[derive(Debug)]
+pub struct ParallelExecutionResult {
+    /// Results of the deploys, in the order they were given
+    pub execution_results: Vec<ExecutionResult>,
+    /// State root left once the effects of all the deploys were committed
+    pub post_state_hash: Blake2bHash,
+}
+
+#[derive(Debug)]
+pub enum ParallelExecutionError {
+    RootNotFound(Blake2bHash),
+    /// Committing the effects of some of the deploys failed.  Effects committed before the
+    /// failure are left in global state, but no root including them is returned.
+    Commit(CommitResult),
+    Engine(Error),
+}
+
+impl From<Error> for ParallelExecutionError {
+    fn from(error: Error) -> Self {
+        ParallelExecutionError::Engine(error)
+    }
+}
+
+/// Calls `f` on each of `items` on up to `threads` threads, and returns the results in the order
+/// of `items`.
+pub(crate) fn run_concurrently<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = cmp::max(1, cmp::min(threads, items.len()));
+    let next_index = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|_| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::SeqCst);
+                        match items.get(index) {
+                            Some(item) => results.push((index, f(item))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("should join deploy thread"))
+            .collect()
+    })
+    .expect("should join deploy threads");
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Splits the indices of `execution_results` into those whose effects can be committed together
+/// and those which have to be run again, one after the other.
+///
+/// A result is independent if its operations commute with those of every earlier independent
+/// result.  Otherwise it conflicts, and so does every later result touching the same keys, so
+/// that conflicting deploys keep their relative order.
+pub(crate) fn partition_conflicts(
+    execution_results: &[ExecutionResult],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut independent = Vec::new();
+    let mut conflicting = Vec::new();
+    let mut independent_ops = HashMap::new();
+    let mut conflicting_ops = HashMap::new();
+
+    for (index, execution_result) in execution_results.iter().enumerate() {
+        let ops = &execution_result.effect().ops;
+        if conflicts(&independent_ops, ops) || conflicts(&conflicting_ops, ops) {
+            merge_ops(&mut conflicting_ops, ops);
+            conflicting.push(index);
+        } else {
+            merge_ops(&mut independent_ops, ops);
+            independent.push(index);
+        }
+    }
+
+    (independent, conflicting)
+}
+
+fn conflicts(ops: &HashMap<Key, Op>, other_ops: &HashMap<Key, Op>) -> bool {
+    other_ops.iter().any(|(key, other_op)| match ops.get(key) {
+        Some(op) => !commutes(op, other_op),
+        None => false,
+    })
+}
+
+fn commutes(op: &Op, other_op: &Op) -> bool {
+    match (op, other_op) {
+        (Op::NoOp, _) | (_, Op::NoOp) => true,
+        (Op::Read, Op::Read) | (Op::Add, Op::Add) => true,
+        _ => false,
+    }
+}
+
+fn merge_ops(ops: &mut HashMap<Key, Op>, other_ops: &HashMap<Key, Op>) {
+    for (key, other_op) in other_ops {
+        let op = ops.remove(key).unwrap_or(Op::NoOp) + other_op.clone();
+        ops.insert(*key, op);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use contract_ffi::value::account::BlockTime;
+    use contract_ffi::value::Value;
+    use engine_shared::newtypes::{CorrelationId, Validated};
+    use engine_storage::global_state::in_memory::InMemoryGlobalState;
+    use engine_storage::global_state::{History, StateReader};
+    use engine_wasm_prep::{PreprocessingError, Preprocessor};
+    use execution::{Error as ExecutionError, Executor};
+    use tracking_copy::TrackingCopy;
+
+    use super::*;
+    use engine_state::execution_effect::ExecutionEffect;
+    use engine_state::EngineState;
+
+    /// Passes module bytes through unchanged.
+    struct TestPreprocessor;
+
+    impl Preprocessor<Vec<u8>> for TestPreprocessor {
+        fn preprocess(&self, module_bytes: &[u8]) -> Result<Vec<u8>, PreprocessingError> {
+            Ok(module_bytes.to_vec())
+        }
+    }
+
+    /// Treats a module `[key, value]` as writing `value` to `Key::Hash([key; 32])`.
+    struct TestExecutor;
+
+    impl Executor<Vec<u8>> for TestExecutor {
+        fn exec<R: StateReader<Key, Value>>(
+            &self,
+            parity_module: Vec<u8>,
+            _args: &[u8],
+            _account: Key,
+            _authorized_keys: BTreeSet<PublicKey>,
+            _blocktime: BlockTime,
+            _nonce: u64,
+            _gas_limit: u64,
+            _protocol_version: u64,
+            _correlation_id: CorrelationId,
+            tc: Rc<RefCell<TrackingCopy<R>>>,
+        ) -> ExecutionResult
+        where
+            R::Error: Into<ExecutionError>,
+        {
+            let key = Key::Hash([parity_module[0]; 32]);
+            let value = Value::Int32(i32::from(parity_module[1]));
+            tc.borrow_mut().write(
+                Validated::new(key, Validated::valid).unwrap(),
+                Validated::new(value, Validated::valid).unwrap(),
+            );
+            ExecutionResult::Success {
+                effect: tc.borrow().effect(),
+                cost: 0,
+                gas_profile: None,
+                trace: None,
+                stats: Default::default(),
+            }
+        }
+    }
+
+    fn deploy_item(key: u8, value: u8) -> DeployItem {
+        DeployItem {
+            module_bytes: vec![key, value],
+            args: Vec::new(),
+            address: Key::Account([0u8; 32]),
+            authorized_keys: BTreeSet::new(),
+            nonce: 1,
+            gas_limit: 0,
+        }
+    }
+
+    fn execution_result(ops: Vec<(u8, Op)>) -> ExecutionResult {
+        let ops = ops
+            .into_iter()
+            .map(|(key, op)| (Key::Hash([key; 32]), op))
+            .collect();
+        ExecutionResult::Success {
+            effect: ExecutionEffect::new(ops, HashMap::new()),
+            cost: 0,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
+        }
+    }
+
+    #[test]
+    fn should_only_defer_conflicting_results() {
+        let execution_results = vec![
+            execution_result(vec![(1, Op::Write), (2, Op::Read)]),
+            execution_result(vec![(2, Op::Read), (3, Op::Add)]),
+            execution_result(vec![(3, Op::Add)]),
+            execution_result(vec![(1, Op::Read)]),
+            execution_result(vec![(4, Op::Write)]),
+            execution_result(vec![(1, Op::NoOp)]),
+        ];
+
+        let (independent, conflicting) = partition_conflicts(&execution_results);
+
+        assert_eq!(independent, vec![0, 1, 2, 4, 5]);
+        assert_eq!(conflicting, vec![3]);
+    }
+
+    #[test]
+    fn should_keep_order_of_results_conflicting_with_deferred_ones() {
+        let execution_results = vec![
+            execution_result(vec![(1, Op::Write)]),
+            execution_result(vec![(1, Op::Write), (2, Op::Write)]),
+            execution_result(vec![(2, Op::Write)]),
+        ];
+
+        let (independent, conflicting) = partition_conflicts(&execution_results);
+
+        assert_eq!(independent, vec![0]);
+        assert_eq!(conflicting, vec![1, 2]);
+    }
+
+    #[test]
+    fn should_return_results_in_order_of_items() {
+        let items: Vec<u64> = (0..100).collect();
+        let results = run_concurrently(&items, 4, |item| item * 2);
+        assert_eq!(
+            results,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_commit_writes_to_the_same_key_in_order_given() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let prestate_hash = global_state.root_hash;
+        let engine_state = EngineState::new(global_state, Default::default());
+
+        let deploys = vec![deploy_item(1, 10), deploy_item(2, 20), deploy_item(1, 11)];
+
+        for threads in 1..=deploys.len() {
+            let result = engine_state
+                .execute_parallel(
+                    &deploys,
+                    prestate_hash,
+                    BlockTime(0),
+                    1,
+                    correlation_id,
+                    &TestExecutor,
+                    &TestPreprocessor,
+                    threads,
+                )
+                .unwrap();
+
+            assert_eq!(result.execution_results.len(), deploys.len());
+
+            let reader = engine_state
+                .state()
+                .lock()
+                .checkout(result.post_state_hash)
+                .unwrap()
+                .unwrap();
+            let read = |key: u8| reader.read(correlation_id, &Key::Hash([key; 32])).unwrap();
+            assert_eq!(read(1), Some(Value::Int32(11)));
+            assert_eq!(read(2), Some(Value::Int32(20)));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! A least recently used cache of prepared wasm modules, keyed by the hash of their bytes, so that
+//! a contract deployed again and again is only validated and instrumented once.
+//!
+//! Modules are kept as the preprocessor returns them, gas counters and stack limiter included, so
+//! a cached module is ready to execute.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use parity_wasm::elements::Module;
+use parking_lot::Mutex;
+
+use engine_shared::newtypes::Blake2bHash;
+use engine_wasm_prep::{PreprocessingError, Preprocessor, WasmiPreprocessor};
+
+/// Identifies the bytes of a module prepared for a particular protocol version, whose wasm costs
+/// the module's gas counters depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WasmCacheKey {
+    pub module_hash: Blake2bHash,
+    pub protocol_version: u64,
+}
+
+impl WasmCacheKey {
+    pub fn new(module_bytes: &[u8], protocol_version: u64) -> Self {
+        WasmCacheKey {
+            module_hash: Blake2bHash::new(module_bytes),
+            protocol_version,
+        }
+    }
+}
+
+/// Holds up to `capacity` prepared modules, evicting the least recently used one when full.
+#[derive(Debug)]
+pub struct WasmCache {
+    capacity: usize,
+    entries: HashMap<WasmCacheKey, (Module, u64)>,
+    // maps the last use of each entry to its key, oldest first
+    recency: BTreeMap<u64, WasmCacheKey>,
+    uses: u64,
+}
+
+impl WasmCache {
+    pub fn new(capacity: usize) -> Self {
+        WasmCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// Returns a copy of the module at `key` and marks it as the most recently used one.
+    pub fn get(&mut self, key: &WasmCacheKey) -> Option<Module> {
+        let next_use = self.next_use();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.1);
+        self.recency.insert(next_use, *key);
+        entry.1 = next_use;
+        Some(entry.0.clone())
+    }
+
+    /// Inserts `module` at `key`, evicting the least recently used module if the cache is full.
+    pub fn insert(&mut self, key: WasmCacheKey, module: Module) {
+        if self.capacity == 0 {
+            return;
+        }
+        let next_use = self.next_use();
+        if let Some((_, last_use)) = self.entries.insert(key, (module, next_use)) {
+            self.recency.remove(&last_use);
+        }
+        self.recency.insert(next_use, key);
+        while self.entries.len() > self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(last_use) => *last_use,
+                None => break,
+            };
+            if let Some(oldest_key) = self.recency.remove(&oldest) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+}
+
+/// Prepares modules like the wrapped [`WasmiPreprocessor`], answering repeats of the same bytes
+/// from a shared [`WasmCache`].
+///
+/// Every preprocessor sharing a cache must be configured the same apart from its protocol
+/// version, as the cache cannot tell them apart otherwise.  Modules which fail to prepare are not
+/// cached.
+pub struct CachingPreprocessor {
+    preprocessor: WasmiPreprocessor,
+    protocol_version: u64,
+    cache: Arc<Mutex<WasmCache>>,
+}
+
+impl CachingPreprocessor {
+    pub fn new(
+        preprocessor: WasmiPreprocessor,
+        protocol_version: u64,
+        cache: Arc<Mutex<WasmCache>>,
+    ) -> CachingPreprocessor {
+        CachingPreprocessor {
+            preprocessor,
+            protocol_version,
+            cache,
+        }
+    }
+}
+
+impl Preprocessor<Module> for CachingPreprocessor {
+    fn preprocess(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
+        let key = WasmCacheKey::new(module_bytes, self.protocol_version);
+        if let Some(module) = self.cache.lock().get(&key) {
+            return Ok(module);
+        }
+        // prepared without holding the lock, so that other deploys can use the cache meanwhile
+        let module = self.preprocessor.preprocess(module_bytes)?;
+        self.cache.lock().insert(key, module.clone());
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_wasm_prep::wasm_costs::WasmCosts;
+
+    use super::*;
+
+    const MODULE_WAT: &str = r#"
+        (module
+            (import "env" "memory" (memory 16 64))
+            (func (export "call")
+                (drop (i32.mul (i32.const 2) (i32.const 3)))))
+    "#;
+
+    fn module(index: u8) -> Module {
+        let wat = format!(
+            r#"(module (func (export "call") (drop (i32.const {}))))"#,
+            index
+        );
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        engine_wasm_prep::deserialize(&wasm).expect("should deserialize")
+    }
+
+    fn key(index: u8, protocol_version: u64) -> WasmCacheKey {
+        WasmCacheKey::new(&[index], protocol_version)
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        let mut cache = WasmCache::new(2);
+        cache.insert(key(1, 1), module(1));
+        cache.insert(key(2, 1), module(2));
+        assert_eq!(cache.get(&key(1, 1)), Some(module(1)));
+
+        cache.insert(key(3, 1), module(3));
+
+        assert_eq!(cache.get(&key(2, 1)), None);
+        assert_eq!(cache.get(&key(1, 1)), Some(module(1)));
+        assert_eq!(cache.get(&key(3, 1)), Some(module(3)));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn should_not_conflate_protocol_versions() {
+        let mut cache = WasmCache::new(2);
+        cache.insert(key(1, 1), module(1));
+
+        assert_eq!(cache.get(&key(1, 2)), None);
+    }
+
+    #[test]
+    fn should_not_hold_entries_without_capacity() {
+        let mut cache = WasmCache::new(0);
+        cache.insert(key(1, 1), module(1));
+        assert_eq!(cache.get(&key(1, 1)), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn should_cache_the_prepared_module() {
+        let wasm = wabt::wat2wasm(MODULE_WAT).expect("should compile wat");
+        let preprocessor = WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap());
+        let expected = preprocessor.preprocess(&wasm).expect("should preprocess");
+        let cache = Arc::new(Mutex::new(WasmCache::new(1)));
+        let caching_preprocessor = CachingPreprocessor::new(preprocessor, 1, Arc::clone(&cache));
+
+        let cold = caching_preprocessor
+            .preprocess(&wasm)
+            .expect("should preprocess");
+        let warm = caching_preprocessor
+            .preprocess(&wasm)
+            .expect("should preprocess");
+
+        assert_eq!(cold, expected);
+        assert_eq!(warm, expected);
+        assert_eq!(
+            cache.lock().get(&WasmCacheKey::new(&wasm, 1)),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn should_not_cache_failures() {
+        let cache = Arc::new(Mutex::new(WasmCache::new(1)));
+        let caching_preprocessor = CachingPreprocessor::new(
+            WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap()),
+            1,
+            Arc::clone(&cache),
+        );
+
+        assert!(caching_preprocessor.preprocess(b"not wasm").is_err());
+        assert!(cache.lock().is_empty());
+    }
+}
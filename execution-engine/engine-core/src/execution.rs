@@ -1,9 +1,11 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::iter::IntoIterator;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use blake2::digest::{Input, VariableOutput};
 use blake2::VarBlake2b;
@@ -11,6 +13,7 @@ use itertools::Itertools;
 use parity_wasm::elements::{Error as ParityWasmError, Module};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
+use wasmi::memory_units::Pages;
 use wasmi::{
     Error as InterpreterError, Externals, HostError, ImportsBuilder, MemoryRef, ModuleInstance,
     ModuleRef, RuntimeArgs, RuntimeValue, Trap, TrapKind,
@@ -30,8 +33,10 @@ use contract_ffi::value::account::{
 use contract_ffi::value::{Account, Value, U512};
 use engine_shared::newtypes::{CorrelationId, Validated};
 use engine_shared::transform::TypeMismatch;
-use engine_state::execution_result::ExecutionResult;
+use engine_state::execution_result::{ExecutionResult, ExecutionStats};
 use engine_storage::global_state::StateReader;
+use engine_wasm_prep::gas_profile::{self, GasCategory, GasProfile, GasSite, ProfiledModule};
+use engine_wasm_prep::MEM_PAGES;
 use execution::Error::{KeyNotFound, URefNotFound};
 use function_index::FunctionIndex;
 use resolvers::create_module_resolver;
@@ -60,7 +65,12 @@ pub enum Error {
     URefNotFound(String),
     FunctionNotFound(String),
     ParityWasm(ParityWasmError),
-    GasLimit,
+    /// Charging gas would have taken the gas used past the limit
+    OutOfGas {
+        gas_limit: u64,
+        /// Gas used before the charge which failed
+        gas_used: u64,
+    },
     Ret(Vec<URef>),
     Rng(rand::Error),
     ResolverError(ResolverError),
@@ -76,6 +86,20 @@ pub enum Error {
     SetThresholdFailure(SetThresholdFailure),
     SystemContractError(system_contracts::error::Error),
     DeploymentAuthorizationFailure,
+    /// Execution ran past the deadline set by the executor's timeout or deadline
+    Timeout,
+    /// Linear memory would have grown past the executor's limit
+    MemoryLimitExceeded {
+        /// Size memory would have grown to, in pages
+        requested_pages: u32,
+        max_pages: u32,
+    },
+    /// A host function was imported with a signature other than its own
+    HostFunctionMisuse {
+        function: String,
+        /// How the imported signature differs
+        mismatch: String,
+    },
 }
 
 impl fmt::Display for Error {
@@ -158,6 +182,119 @@ pub struct Runtime<'a, R> {
     result: Vec<u8>,
     host_buf: Vec<u8>,
     context: RuntimeContext<'a, R>,
+    deadline: Option<Instant>,
+    gas_profiler: Option<GasProfiler>,
+    tracer: Option<ExecutionTracer>,
+    max_memory_pages: u32,
+    /// The most pages of linear memory held by this or any other module of the execution
+    peak_memory_pages: Rc<Cell<u32>>,
+}
+
+/// Accumulates what the gas charged during a profiled execution was spent on.  Clones add to
+/// the same profile, so that the gas charged by called contracts is included.
+#[derive(Clone)]
+pub struct GasProfiler {
+    sites: Rc<Vec<GasSite>>,
+    profile: Rc<RefCell<GasProfile>>,
+}
+
+impl GasProfiler {
+    /// Creates a profiler for a module instrumented with the given gas call sites.
+    pub fn new(sites: Vec<GasSite>) -> GasProfiler {
+        GasProfiler {
+            sites: Rc::new(sites),
+            profile: Default::default(),
+        }
+    }
+
+    /// Records a charge of `amount` made from the call site with index `site`.
+    fn record(&self, site: u32, amount: u64) {
+        let mut profile = self.profile.borrow_mut();
+        match self.sites.get(site as usize) {
+            Some(gas_site) => gas_site.record(amount, &mut profile),
+            None => gas_profile::add_gas(&mut profile, GasCategory::Unattributed, amount),
+        }
+    }
+
+    /// Records a charge of `amount` made by a contract which is not instrumented for profiling.
+    fn record_uninstrumented(&self, amount: u64) {
+        gas_profile::add_gas(
+            &mut self.profile.borrow_mut(),
+            GasCategory::CalledContracts,
+            amount,
+        );
+    }
+
+    /// Returns the gas recorded so far.
+    pub fn profile(&self) -> GasProfile {
+        self.profile.borrow().clone()
+    }
+}
+
+/// A call made to a host function during a traced execution.
+///
+/// Arguments and results are the raw wasm values, so the trace of a deploy only depends on the
+/// deploy and the state it runs against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    /// Name the function is imported by
+    pub function: &'static str,
+    pub args: Vec<i64>,
+    /// The value the function returned, if any
+    pub results: Vec<i64>,
+    /// `true` if the call ended the execution of the contract instead of returning, as `ret` and
+    /// `revert` do, or failed
+    pub trapped: bool,
+}
+
+/// Records the host functions called during a traced execution, in the order they are called.
+/// Clones add to the same trace, so that calls made by called contracts are included right after
+/// the `call_contract` call which made them.
+#[derive(Clone, Default)]
+pub struct ExecutionTracer {
+    calls: Rc<RefCell<Vec<HostCall>>>,
+}
+
+impl ExecutionTracer {
+    pub fn new() -> ExecutionTracer {
+        Default::default()
+    }
+
+    /// Records the start of a call to `function` and returns its index in the trace.
+    fn start(&self, function: &'static str, args: &[RuntimeValue]) -> usize {
+        let mut calls = self.calls.borrow_mut();
+        calls.push(HostCall {
+            function,
+            args: args.iter().map(runtime_value_bits).collect(),
+            results: Vec::new(),
+            trapped: false,
+        });
+        calls.len() - 1
+    }
+
+    /// Records the outcome of the call at `call_index`.
+    fn finish(&self, call_index: usize, ret: &Result<Option<RuntimeValue>, Trap>) {
+        let call = &mut self.calls.borrow_mut()[call_index];
+        match ret {
+            Ok(value) => call.results = value.iter().map(runtime_value_bits).collect(),
+            Err(_) => call.trapped = true,
+        }
+    }
+
+    /// Returns the calls recorded so far.
+    pub fn trace(&self) -> Vec<HostCall> {
+        self.calls.borrow().clone()
+    }
+}
+
+/// Returns the bits of a wasm value, sign extended if it is a 32 bit integer.
+fn runtime_value_bits(value: &RuntimeValue) -> i64 {
+    match *value {
+        RuntimeValue::I32(value) => i64::from(value),
+        RuntimeValue::I64(value) => value,
+        RuntimeValue::F32(value) => i64::from(value.to_bits() as i32),
+        RuntimeValue::F64(value) => value.to_bits() as i64,
+    }
 }
 
 /// Rename function called `name` in the `module` to `call`.
@@ -182,15 +319,86 @@ where
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(memory: MemoryRef, module: Module, context: RuntimeContext<'a, R>) -> Self {
+        let peak_memory_pages = Rc::new(Cell::new(memory.current_size().0 as u32));
         Runtime {
             memory,
             module,
             result: Vec::new(),
             host_buf: Vec::new(),
             context,
+            deadline: None,
+            gas_profiler: None,
+            tracer: None,
+            max_memory_pages: MEM_PAGES,
+            peak_memory_pages,
+        }
+    }
+
+    /// Sets the instant after which execution is aborted with [`Error::Timeout`].
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets the profiler which the gas charged by the module is recorded by.
+    pub fn with_gas_profiler(mut self, gas_profiler: Option<GasProfiler>) -> Self {
+        self.gas_profiler = gas_profiler;
+        self
+    }
+
+    /// Sets the tracer which the host functions called by the module are recorded by.
+    pub fn with_tracer(mut self, tracer: Option<ExecutionTracer>) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
+    /// Sets the most pages linear memory may grow to before execution traps with
+    /// [`Error::MemoryLimitExceeded`].
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u32) -> Self {
+        self.max_memory_pages = max_memory_pages;
+        self
+    }
+
+    /// Returns the most pages of linear memory held by any one module of the execution so far.
+    pub fn peak_memory_pages(&self) -> u32 {
+        self.peak_memory_pages.get()
+    }
+
+    /// Raises the execution's peak memory to the current size of this module's memory, if that
+    /// is larger.
+    fn record_memory_pages(&self) {
+        let current_pages = self.memory.current_size().0 as u32;
+        if current_pages > self.peak_memory_pages.get() {
+            self.peak_memory_pages.set(current_pages);
         }
     }
 
+    /// Grows linear memory by `pages`, returning its previous size in pages, or -1 if it
+    /// would exceed the maximum declared by the module.
+    fn grow_memory(&mut self, pages: u32) -> Result<i32, Trap> {
+        let current_pages = self.memory.current_size().0 as u64;
+        let requested_pages = current_pages + u64::from(pages);
+        if requested_pages > u64::from(self.max_memory_pages) {
+            return Err(Error::MemoryLimitExceeded {
+                requested_pages: requested_pages.min(u64::from(u32::max_value())) as u32,
+                max_pages: self.max_memory_pages,
+            }
+            .into());
+        }
+        match self.memory.grow(Pages(pages as usize)) {
+            Ok(previous_pages) => {
+                self.record_memory_pages();
+                Ok(previous_pages.0 as i32)
+            }
+            Err(_) => Ok(-1),
+        }
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        self.deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
     /// Charge specified amount of gas
     ///
     /// Returns false if gas limit exceeded and true if not.
@@ -208,11 +416,21 @@ where
         }
     }
 
+    /// Charges gas, and aborts execution once the deadline has passed.  Gas is charged at the
+    /// start of every metered block, including each loop iteration, so this is where a running
+    /// contract can be stopped.
     fn gas(&mut self, amount: u64) -> Result<(), Trap> {
+        if self.is_past_deadline() {
+            return Err(Error::Timeout.into());
+        }
         if self.charge_gas(amount) {
             Ok(())
         } else {
-            Err(Error::GasLimit.into())
+            Err(Error::OutOfGas {
+                gas_limit: self.context.gas_limit(),
+                gas_used: self.context.gas_counter(),
+            }
+            .into())
         }
     }
 
@@ -886,16 +1104,16 @@ fn as_usize(u: u32) -> usize {
     u as usize
 }
 
-impl<'a, R: StateReader<Key, Value>> Externals for Runtime<'a, R>
+impl<'a, R: StateReader<Key, Value>> Runtime<'a, R>
 where
     R::Error: Into<Error>,
 {
-    fn invoke_index(
+    /// Runs the host function `func` with `args`.
+    fn invoke_host_function(
         &mut self,
-        index: usize,
+        func: FunctionIndex,
         args: RuntimeArgs,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let func = FunctionIndex::try_from(index).expect("unknown function index");
         match func {
             FunctionIndex::ReadFuncIndex => {
                 // args(0) = pointer to key in Wasm memory
@@ -1096,9 +1314,30 @@ where
             FunctionIndex::GasFuncIndex => {
                 let gas: u32 = Args::parse(args)?;
                 self.gas(u64::from(gas))?;
+                if let Some(gas_profiler) = &self.gas_profiler {
+                    gas_profiler.record_uninstrumented(u64::from(gas));
+                }
+                Ok(None)
+            }
+
+            FunctionIndex::GasProfiledFuncIndex => {
+                // args(0) = amount of gas to charge
+                // args(1) = index of the gas call site
+                let (gas, site): (u32, u32) = Args::parse(args)?;
+                self.gas(u64::from(gas))?;
+                if let Some(gas_profiler) = &self.gas_profiler {
+                    gas_profiler.record(site, u64::from(gas));
+                }
                 Ok(None)
             }
 
+            FunctionIndex::GrowMemoryFuncIndex => {
+                // args(0) = number of pages to grow memory by
+                let pages: u32 = Args::parse(args)?;
+                let previous_pages = self.grow_memory(pages)?;
+                Ok(Some(RuntimeValue::I32(previous_pages)))
+            }
+
             FunctionIndex::StoreFnIndex => {
                 // args(0) = pointer to function name in Wasm memory
                 // args(1) = size of the name
@@ -1284,15 +1523,46 @@ where
     }
 }
 
+impl<'a, R: StateReader<Key, Value>> Externals for Runtime<'a, R>
+where
+    R::Error: Into<Error>,
+{
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let func = FunctionIndex::try_from(index).expect("unknown function index");
+        let tracer = match self.tracer {
+            // gas is charged by every metered block, which would drown out the calls the
+            // contract itself makes
+            Some(_) if func.is_gas() => None,
+            ref tracer => tracer.clone(),
+        };
+        match tracer {
+            Some(tracer) => {
+                let call_index = tracer.start(func.name(), args.as_ref());
+                let ret = self.invoke_host_function(func, args);
+                tracer.finish(call_index, &ret);
+                ret
+            }
+            None => self.invoke_host_function(func, args),
+        }
+    }
+}
+
 fn instance_and_memory(
     parity_module: Module,
     protocol_version: u64,
+    max_memory_pages: u32,
 ) -> Result<(ModuleRef, MemoryRef), Error> {
     let module = wasmi::Module::from_parity_wasm_module(parity_module)?;
-    let resolver = create_module_resolver(protocol_version)?;
+    let resolver = create_module_resolver(protocol_version, max_memory_pages)?;
     let mut imports = ImportsBuilder::new();
     imports.push_resolver("env", &resolver);
-    let instance = ModuleInstance::new(&module, &imports)?.assert_no_start();
+    let instance = ModuleInstance::new(&module, &imports)
+        .map_err(trap_to_error)?
+        .assert_no_start();
 
     let memory = resolver.memory_ref()?;
     Ok((instance, memory))
@@ -1312,7 +1582,11 @@ fn sub_call<R: StateReader<Key, Value>>(
 where
     R::Error: Into<Error>,
 {
-    let (instance, memory) = instance_and_memory(parity_module.clone(), protocol_version)?;
+    let (instance, memory) = instance_and_memory(
+        parity_module.clone(),
+        protocol_version,
+        current_runtime.max_memory_pages,
+    )?;
 
     let known_urefs = extract_access_rights_from_keys(refs.values().cloned().chain(extra_urefs));
 
@@ -1337,7 +1611,13 @@ where
             protocol_version,
             current_runtime.context.correlation_id(),
         ),
+        deadline: current_runtime.deadline,
+        gas_profiler: current_runtime.gas_profiler.clone(),
+        tracer: current_runtime.tracer.clone(),
+        max_memory_pages: current_runtime.max_memory_pages,
+        peak_memory_pages: Rc::clone(&current_runtime.peak_memory_pages),
     };
+    runtime.record_memory_pages();
 
     let result = instance.invoke_export("call", &[], &mut runtime);
 
@@ -1362,6 +1642,24 @@ where
                         // InterpreterError.
                         return Err(Error::Revert(*status));
                     }
+                    Error::OutOfGas {
+                        gas_limit,
+                        gas_used,
+                    } => {
+                        return Err(Error::OutOfGas {
+                            gas_limit: *gas_limit,
+                            gas_used: *gas_used,
+                        });
+                    }
+                    Error::MemoryLimitExceeded {
+                        requested_pages,
+                        max_pages,
+                    } => {
+                        return Err(Error::MemoryLimitExceeded {
+                            requested_pages: *requested_pages,
+                            max_pages: *max_pages,
+                        });
+                    }
                     _ => {}
                 }
             }
@@ -1442,6 +1740,9 @@ macro_rules! on_fail_charge {
                     error: exec_err.into(),
                     effect: Default::default(),
                     cost: $cost,
+                    gas_profile: None,
+                    trace: None,
+                    stats: Default::default(),
                 };
             }
         }
@@ -1455,6 +1756,9 @@ macro_rules! on_fail_charge {
                     error: exec_err.into(),
                     effect: $effect,
                     cost: $cost,
+                    gas_profile: None,
+                    trace: None,
+                    stats: Default::default(),
                 };
             }
         }
@@ -1480,12 +1784,62 @@ pub trait Executor<A> {
         R::Error: Into<Error>;
 }
 
-pub struct WasmiExecutor;
+pub struct WasmiExecutor {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    max_memory_pages: u32,
+    tracing: bool,
+}
 
-impl Executor<Module> for WasmiExecutor {
-    fn exec<R: StateReader<Key, Value>>(
+impl Default for WasmiExecutor {
+    fn default() -> Self {
+        WasmiExecutor {
+            timeout: None,
+            deadline: None,
+            max_memory_pages: MEM_PAGES,
+            tracing: false,
+        }
+    }
+}
+
+impl WasmiExecutor {
+    pub fn new() -> WasmiExecutor {
+        Default::default()
+    }
+
+    /// Aborts each execution that runs longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> WasmiExecutor {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Aborts each execution still running at `deadline`, even if it has not yet run for as long
+    /// as the timeout allows.
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> WasmiExecutor {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Traps each execution whose linear memory would grow past `max_memory_pages`.
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u32) -> WasmiExecutor {
+        self.max_memory_pages = max_memory_pages;
+        self
+    }
+
+    /// Records the host functions called by each execution in its result.
+    pub fn with_tracing(mut self, tracing: bool) -> WasmiExecutor {
+        self.tracing = tracing;
+        self
+    }
+
+    /// Runs `instantiated_module`, which is `parity_module` as instrumented for `gas_profiler`
+    /// when profiling.  Contracts stored by the execution are taken from `parity_module`.
+    #[allow(clippy::too_many_arguments)]
+    fn exec_module<R: StateReader<Key, Value>>(
         &self,
         parity_module: Module,
+        instantiated_module: Module,
+        gas_profiler: Option<GasProfiler>,
         args: &[u8],
         acct_key: Key,
         authorized_keys: BTreeSet<PublicKey>,
@@ -1499,8 +1853,18 @@ impl Executor<Module> for WasmiExecutor {
     where
         R::Error: Into<Error>,
     {
-        let (instance, memory) =
-            on_fail_charge!(instance_and_memory(parity_module.clone(), protocol_version));
+        let started = Instant::now();
+        let trie_reads_before = tc.borrow().trie_reads();
+        let deadline = match (self.timeout, self.deadline) {
+            (Some(timeout), Some(deadline)) => Some(cmp::min(Instant::now() + timeout, deadline)),
+            (Some(timeout), None) => Some(Instant::now() + timeout),
+            (None, deadline) => deadline,
+        };
+        let (instance, memory) = on_fail_charge!(instance_and_memory(
+            instantiated_module,
+            protocol_version,
+            self.max_memory_pages
+        ));
         #[allow(unreachable_code)]
         let validated_key = on_fail_charge!(Validated::new(acct_key, Validated::valid));
 
@@ -1605,20 +1969,149 @@ impl Executor<Module> for WasmiExecutor {
             correlation_id,
         );
 
-        let mut runtime = Runtime::new(memory, parity_module, context);
-        on_fail_charge!(
-            instance.invoke_export("call", &[], &mut runtime),
-            runtime.context.gas_counter(),
-            effects_snapshot
-        );
+        let mut runtime = Runtime::new(memory, parity_module, context)
+            .with_deadline(deadline)
+            .with_gas_profiler(gas_profiler)
+            .with_tracer(if self.tracing {
+                Some(ExecutionTracer::new())
+            } else {
+                None
+            })
+            .with_max_memory_pages(self.max_memory_pages);
+        let result = instance.invoke_export("call", &[], &mut runtime);
+        if result.is_err() && runtime.is_past_deadline() {
+            // Whether a deploy times out depends on the machine running it rather than on the
+            // deploy, so it is neither charged for nor given any effects.
+            return ExecutionResult::precondition_failure(Error::Timeout.into());
+        }
+        let gas_profile = runtime.gas_profiler.as_ref().map(GasProfiler::profile);
+        let trace = runtime.tracer.as_ref().map(ExecutionTracer::trace);
+        let stats = ExecutionStats {
+            duration: started.elapsed(),
+            peak_memory_pages: runtime.peak_memory_pages(),
+            trie_reads: runtime.context.state().borrow().trie_reads() - trie_reads_before,
+        };
+        if let Err(error) = result {
+            return ExecutionResult::Failure {
+                error: trap_to_error(error).into(),
+                effect: effects_snapshot,
+                cost: runtime.context.gas_counter(),
+                gas_profile,
+                trace,
+                stats,
+            };
+        }
 
         ExecutionResult::Success {
             effect: runtime.context.effect(),
             cost: runtime.context.gas_counter(),
+            gas_profile,
+            trace,
+            stats,
         }
     }
 }
 
+impl Executor<Module> for WasmiExecutor {
+    fn exec<R: StateReader<Key, Value>>(
+        &self,
+        parity_module: Module,
+        args: &[u8],
+        acct_key: Key,
+        authorized_keys: BTreeSet<PublicKey>,
+        blocktime: BlockTime,
+        nonce: u64,
+        gas_limit: u64,
+        protocol_version: u64,
+        correlation_id: CorrelationId,
+        tc: Rc<RefCell<TrackingCopy<R>>>,
+    ) -> ExecutionResult
+    where
+        R::Error: Into<Error>,
+    {
+        let instantiated_module = parity_module.clone();
+        self.exec_module(
+            parity_module,
+            instantiated_module,
+            None,
+            args,
+            acct_key,
+            authorized_keys,
+            blocktime,
+            nonce,
+            gas_limit,
+            protocol_version,
+            correlation_id,
+            tc,
+        )
+    }
+}
+
+impl Executor<ProfiledModule> for WasmiExecutor {
+    fn exec<R: StateReader<Key, Value>>(
+        &self,
+        profiled_module: ProfiledModule,
+        args: &[u8],
+        acct_key: Key,
+        authorized_keys: BTreeSet<PublicKey>,
+        blocktime: BlockTime,
+        nonce: u64,
+        gas_limit: u64,
+        protocol_version: u64,
+        correlation_id: CorrelationId,
+        tc: Rc<RefCell<TrackingCopy<R>>>,
+    ) -> ExecutionResult
+    where
+        R::Error: Into<Error>,
+    {
+        self.exec_module(
+            profiled_module.module,
+            profiled_module.profiled_module,
+            Some(GasProfiler::new(profiled_module.sites)),
+            args,
+            acct_key,
+            authorized_keys,
+            blocktime,
+            nonce,
+            gas_limit,
+            protocol_version,
+            correlation_id,
+            tc,
+        )
+    }
+}
+
+/// Converts the error execution trapped with, unwrapping an [`Error::OutOfGas`],
+/// [`Error::MemoryLimitExceeded`] or [`Error::HostFunctionMisuse`] raised by the host so that it
+/// is not reported as just another interpreter error.
+fn trap_to_error(error: InterpreterError) -> Error {
+    let host_error = match error
+        .as_host_error()
+        .and_then(|host_error| host_error.downcast_ref::<Error>())
+    {
+        Some(Error::OutOfGas {
+            gas_limit,
+            gas_used,
+        }) => Some(Error::OutOfGas {
+            gas_limit: *gas_limit,
+            gas_used: *gas_used,
+        }),
+        Some(Error::MemoryLimitExceeded {
+            requested_pages,
+            max_pages,
+        }) => Some(Error::MemoryLimitExceeded {
+            requested_pages: *requested_pages,
+            max_pages: *max_pages,
+        }),
+        Some(Error::HostFunctionMisuse { function, mismatch }) => Some(Error::HostFunctionMisuse {
+            function: function.clone(),
+            mismatch: mismatch.clone(),
+        }),
+        _ => None,
+    };
+    host_error.unwrap_or_else(|| Error::Interpreter(error))
+}
+
 /// Turns `key` into a `([u8; 32], AccessRights)` tuple.
 /// Returns None if `key` is not `Key::URef` as it wouldn't have `AccessRights` associated with it.
 /// Helper function for creating `known_urefs` associating addresses and corresponding `AccessRights`.
@@ -1637,12 +2130,14 @@ mod tests {
     use std::collections::{BTreeMap, BTreeSet, HashMap};
     use std::iter::{self, FromIterator};
     use std::rc::Rc;
+    use std::time::{Duration, Instant};
 
     use parity_wasm::builder::ModuleBuilder;
     use parity_wasm::elements::{External, ImportEntry, MemoryType, Module};
     use rand::RngCore;
     use rand_chacha::ChaChaRng;
 
+    use contract_ffi::bytesrepr::ToBytes;
     use contract_ffi::key::Key;
     use contract_ffi::uref::{AccessRights, URef};
     use contract_ffi::value::account::{
@@ -1653,7 +2148,10 @@ mod tests {
     use engine_state::execution_effect::ExecutionEffect;
     use engine_state::execution_result::ExecutionResult;
     use engine_storage::global_state::StateReader;
-    use execution::{create_rng, Executor, WasmiExecutor};
+    use engine_wasm_prep::gas_profile::GasCategory;
+    use engine_wasm_prep::wasm_costs::WasmCosts;
+    use engine_wasm_prep::{GasProfilingPreprocessor, Preprocessor, WasmiPreprocessor};
+    use execution::{create_rng, Executor, HostCall, WasmiExecutor};
     use tracking_copy::TrackingCopy;
 
     use super::Error;
@@ -1667,6 +2165,9 @@ mod tests {
         ExecutionResult::Success {
             effect: Default::default(),
             cost: success_cost,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
         }
     }
 
@@ -1679,7 +2180,7 @@ mod tests {
     }
     #[test]
     fn on_fail_charge_err_laziness_test() {
-        match on_fail_charge_test_helper(|| Err(Error::GasLimit) as Result<(), _>, 123, 456) {
+        match on_fail_charge_test_helper(|| Err(Error::Timeout) as Result<(), _>, 123, 456) {
             ExecutionResult::Success { .. } => panic!("Should fail"),
             ExecutionResult::Failure { cost, .. } => assert_eq!(cost, 456),
         }
@@ -1691,7 +2192,7 @@ mod tests {
         use engine_state::execution_effect::ExecutionEffect;
         use engine_state::op::Op;
         let f = || {
-            let input: Result<(), Error> = Err(Error::GasLimit);
+            let input: Result<(), Error> = Err(Error::Timeout);
             on_fail_charge!(input, 456, {
                 let mut effect = ExecutionEffect::default();
 
@@ -1705,6 +2206,9 @@ mod tests {
             ExecutionResult::Success {
                 effect: Default::default(),
                 cost: 0,
+                gas_profile: None,
+                trace: None,
+                stats: Default::default(),
             }
         };
         match f() {
@@ -1718,37 +2222,37 @@ mod tests {
         }
     }
 
+    struct DummyReader;
+    impl StateReader<Key, Value> for DummyReader {
+        type Error = ::engine_storage::error::Error;
+
+        fn read(
+            &self,
+            _correlation_id: CorrelationId,
+            key: &Key,
+        ) -> Result<Option<Value>, Self::Error> {
+            let pub_key: [u8; 32] = match key {
+                Key::Account(pub_key) => *pub_key,
+                _ => panic!("Key must be of an Account type"),
+            };
+            let acc = Account::new(
+                pub_key,
+                1,
+                BTreeMap::new(),
+                PurseId::new(URef::new([0u8; 32], AccessRights::READ_ADD_WRITE)),
+                AssociatedKeys::new(PublicKey::new(pub_key), Weight::new(1)),
+                Default::default(),
+                AccountActivity::new(BlockTime(0), BlockTime(0)),
+            );
+            Ok(Some(Value::Account(acc)))
+        }
+    }
+
     #[test]
     fn invalid_nonce_no_cost_effect() {
         let init_nonce = 1u64;
         let invalid_nonce = init_nonce + 2;
-        struct DummyReader;
-        impl StateReader<Key, Value> for DummyReader {
-            type Error = ::engine_storage::error::Error;
-
-            fn read(
-                &self,
-                _correlation_id: CorrelationId,
-                key: &Key,
-            ) -> Result<Option<Value>, Self::Error> {
-                let pub_key: [u8; 32] = match key {
-                    Key::Account(pub_key) => *pub_key,
-                    _ => panic!("Key must be of an Account type"),
-                };
-                let acc = Account::new(
-                    pub_key,
-                    1,
-                    BTreeMap::new(),
-                    PurseId::new(URef::new([0u8; 32], AccessRights::READ_ADD_WRITE)),
-                    AssociatedKeys::new(PublicKey::new(pub_key), Weight::new(1)),
-                    Default::default(),
-                    AccountActivity::new(BlockTime(0), BlockTime(0)),
-                );
-                Ok(Some(Value::Account(acc)))
-            }
-        }
-
-        let executor = WasmiExecutor;
+        let executor = WasmiExecutor::new();
         let account_address = [0u8; 32];
         let account_key: Key = Key::Account(account_address);
         let parity_module: Module = ModuleBuilder::new()
@@ -1781,6 +2285,7 @@ mod tests {
                 error,
                 effect,
                 cost,
+                ..
             } => {
                 assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
                 assert_eq!(cost, 0);
@@ -1798,6 +2303,484 @@ mod tests {
         }
     }
 
+    /// Runs a module which does nothing as a deploy with `nonce`, from an account whose nonce is 1.
+    fn exec_with_nonce(nonce: u64) -> ExecutionResult {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (func (export "call")))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let parity_module: Module =
+            parity_wasm::deserialize_buffer(&wasm).expect("should deserialize wasm");
+
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        WasmiExecutor::new().exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            nonce,
+            100u64,
+            1u64,
+            CorrelationId::new(),
+            tc,
+        )
+    }
+
+    fn assert_invalid_nonce(exec_result: ExecutionResult, nonce: u64) {
+        match exec_result {
+            ExecutionResult::Failure {
+                error:
+                    ::engine_state::error::Error::ExecError(Error::InvalidNonce {
+                        deploy_nonce,
+                        expected_nonce,
+                    }),
+                effect,
+                cost,
+                ..
+            } => {
+                assert_eq!(deploy_nonce, nonce);
+                assert_eq!(expected_nonce, 2);
+                assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
+                assert_eq!(cost, 0);
+            }
+            other => panic!("Expected InvalidNonce failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_nonce_not_above_account_nonce() {
+        assert_invalid_nonce(exec_with_nonce(0), 0);
+        // the nonce of the last deploy, as in a replay of it
+        assert_invalid_nonce(exec_with_nonce(1), 1);
+    }
+
+    #[test]
+    fn should_reject_nonce_skipping_ahead() {
+        assert_invalid_nonce(exec_with_nonce(3), 3);
+        assert_invalid_nonce(exec_with_nonce(u64::max_value()), u64::max_value());
+    }
+
+    #[test]
+    fn should_increment_account_nonce_in_effects_of_next_nonce() {
+        use engine_shared::transform::Transform;
+
+        match exec_with_nonce(2) {
+            ExecutionResult::Success { effect, .. } => {
+                match effect.transforms.get(&Key::Account([0u8; 32])) {
+                    Some(Transform::Write(Value::Account(account))) => {
+                        assert_eq!(account.nonce(), 2)
+                    }
+                    other => panic!("Expected the account to be written, got: {:?}", other),
+                }
+            }
+            other => panic!("Expected success, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_abort_execution_past_timeout() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (import "env" "gas" (func $gas (param i32)))
+                (func (export "call")
+                    (loop
+                        (call $gas (i32.const 1))
+                        (br 0))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let parity_module: Module =
+            parity_wasm::deserialize_buffer(&wasm).expect("should deserialize wasm");
+
+        let executor = WasmiExecutor::new().with_timeout(Some(Duration::from_millis(10)));
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error: ::engine_state::error::Error::ExecError(Error::Timeout),
+                effect,
+                cost,
+                ..
+            } => {
+                assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
+                assert_eq!(cost, 0);
+            }
+            other => panic!("Expected Timeout failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_abort_execution_past_deadline_before_timeout() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (import "env" "gas" (func $gas (param i32)))
+                (func (export "call")
+                    (loop
+                        (call $gas (i32.const 1))
+                        (br 0))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let parity_module: Module =
+            parity_wasm::deserialize_buffer(&wasm).expect("should deserialize wasm");
+
+        let executor = WasmiExecutor::new()
+            .with_timeout(Some(Duration::from_secs(3600)))
+            .with_deadline(Some(Instant::now() + Duration::from_millis(10)));
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error: ::engine_state::error::Error::ExecError(Error::Timeout),
+                effect,
+                cost,
+                ..
+            } => {
+                assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
+                assert_eq!(cost, 0);
+            }
+            other => panic!("Expected Timeout failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_revert_effects_when_out_of_gas() {
+        let value_bytes = Value::Int32(1).to_bytes().expect("should serialize value");
+        let escaped_value: String = value_bytes
+            .iter()
+            .map(|byte| format!("\\{:02x}", byte))
+            .collect();
+        let wat = format!(
+            r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (import "env" "new_uref" (func $new_uref (param i32 i32 i32)))
+                (import "env" "gas" (func $gas (param i32)))
+                (data (i32.const 0) "{}")
+                (func (export "call")
+                    (call $new_uref (i32.const 100) (i32.const 0) (i32.const {}))
+                    (loop
+                        (call $gas (i32.const 1))
+                        (br 0))))
+            "#,
+            escaped_value,
+            value_bytes.len()
+        );
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let parity_module: Module =
+            parity_wasm::deserialize_buffer(&wasm).expect("should deserialize wasm");
+
+        let executor = WasmiExecutor::new();
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            10u64,
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error:
+                    ::engine_state::error::Error::ExecError(Error::OutOfGas {
+                        gas_limit,
+                        gas_used,
+                    }),
+                effect,
+                cost,
+                ..
+            } => {
+                assert_eq!(gas_limit, 10);
+                assert_eq!(gas_used, 10);
+                assert_eq!(cost, 10);
+                // The uref created before running out of gas must not be written.
+                assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
+            }
+            other => panic!("Expected OutOfGas failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_name_host_function_imported_with_wrong_signature() {
+        let wat = r#"
+            (module
+                (import "env" "write" (func $write (param i32 i32 i32)))
+                (func (export "call")))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let preprocessor = WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap());
+        let parity_module: Module = preprocessor.preprocess(&wasm).expect("should preprocess");
+
+        let executor = WasmiExecutor::new();
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error:
+                    ::engine_state::error::Error::ExecError(Error::HostFunctionMisuse {
+                        ref function,
+                        ref mismatch,
+                    }),
+                ..
+            } => {
+                assert_eq!(function, "write");
+                assert_eq!(mismatch, "takes 4 arguments, but was imported taking 3");
+            }
+            other => panic!("Expected HostFunctionMisuse failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_trap_when_memory_grows_past_limit() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 1 2))
+                (func (export "call")
+                    (drop (grow_memory (i32.const 1)))
+                    (drop (grow_memory (i32.const 1)))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let preprocessor =
+            WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap()).with_max_memory_pages(2);
+        let parity_module: Module = preprocessor.preprocess(&wasm).expect("should preprocess");
+
+        let executor = WasmiExecutor::new().with_max_memory_pages(2);
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error:
+                    ::engine_state::error::Error::ExecError(Error::MemoryLimitExceeded {
+                        requested_pages: 3,
+                        max_pages: 2,
+                    }),
+                ..
+            } => (),
+            other => panic!("Expected MemoryLimitExceeded failure, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_measure_peak_memory_and_trie_reads() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 1 4))
+                (func (export "call")
+                    (drop (grow_memory (i32.const 2)))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let preprocessor = WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap());
+        let parity_module: Module = preprocessor.preprocess(&wasm).expect("should preprocess");
+
+        let executor = WasmiExecutor::new();
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Success { stats, .. } => {
+                assert_eq!(stats.peak_memory_pages, 3);
+                // Only the account is read; the contract itself reads nothing.
+                assert_eq!(stats.trie_reads, 1);
+            }
+            other => panic!("Expected success, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_break_down_gas_when_profiling() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (func (export "call")
+                    (drop (i32.mul (i32.const 2) (i32.const 3)))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let preprocessor = GasProfilingPreprocessor::new(WasmiPreprocessor::new(
+            WasmCosts::from_version(1).unwrap(),
+        ));
+        let profiled_module = preprocessor.preprocess(&wasm).expect("should preprocess");
+
+        let executor = WasmiExecutor::new();
+        let account_address = [0u8; 32];
+        let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+            Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+
+        let exec_result = executor.exec(
+            profiled_module,
+            &[],
+            Key::Account(account_address),
+            BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+            BlockTime(0),
+            2,
+            u64::max_value(),
+            1u64,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Success {
+                cost,
+                gas_profile: Some(gas_profile),
+                ..
+            } => {
+                assert_eq!(gas_profile.values().sum::<u64>(), cost);
+                assert_eq!(gas_profile.get(&GasCategory::Mul), Some(&4));
+                assert_eq!(gas_profile.get(&GasCategory::Unattributed), None);
+            }
+            other => panic!("Expected profiled success, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_trace_host_calls_when_tracing() {
+        let wat = r#"
+            (module
+                (import "env" "memory" (memory 16 64))
+                (import "env" "protocol_version" (func $protocol_version (result i64)))
+                (import "env" "get_blocktime" (func $get_blocktime (param i32)))
+                (import "env" "revert" (func $revert (param i32)))
+                (func (export "call")
+                    (drop (call $protocol_version))
+                    (call $get_blocktime (i32.const 8))
+                    (call $revert (i32.const 7))))
+        "#;
+        let wasm = wabt::wat2wasm(wat).expect("should compile wat");
+        let preprocessor = WasmiPreprocessor::new(WasmCosts::from_version(1).unwrap());
+
+        let run = |tracing: bool| {
+            let parity_module: Module = preprocessor.preprocess(&wasm).expect("should preprocess");
+            let account_address = [0u8; 32];
+            let tc: Rc<RefCell<TrackingCopy<DummyReader>>> =
+                Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+            WasmiExecutor::new().with_tracing(tracing).exec(
+                parity_module,
+                &[],
+                Key::Account(account_address),
+                BTreeSet::from_iter(iter::once(PublicKey::new(account_address))),
+                BlockTime(0),
+                2,
+                u64::max_value(),
+                1u64,
+                CorrelationId::new(),
+                tc,
+            )
+        };
+
+        let expected = vec![
+            HostCall {
+                function: "protocol_version",
+                args: vec![],
+                results: vec![1],
+                trapped: false,
+            },
+            HostCall {
+                function: "get_blocktime",
+                args: vec![8],
+                results: vec![],
+                trapped: false,
+            },
+            HostCall {
+                function: "revert",
+                args: vec![7],
+                results: vec![],
+                trapped: true,
+            },
+        ];
+        assert_eq!(run(true).trace(), Some(expected.as_slice()));
+        assert_eq!(run(true).trace(), run(true).trace());
+        assert_eq!(run(false).trace(), None);
+    }
+
     fn gen_random(rng: &mut ChaChaRng) -> [u8; 32] {
         let mut buff = [0u8; 32];
         rng.fill_bytes(&mut buff);
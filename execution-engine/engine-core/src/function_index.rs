@@ -40,6 +40,62 @@ pub enum FunctionIndex {
     TransferFromPurseToAccountIndex = 33,
     TransferFromPurseToPurseIndex = 34,
     GetBalanceIndex = 35,
+    GasProfiledFuncIndex = 36,
+    GrowMemoryFuncIndex = 37,
+}
+
+impl FunctionIndex {
+    /// Returns `true` for the functions instrumented modules call to charge gas.
+    pub fn is_gas(&self) -> bool {
+        match self {
+            FunctionIndex::GasFuncIndex | FunctionIndex::GasProfiledFuncIndex => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the name contracts import the host function by.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FunctionIndex::ReadFuncIndex => "read_value",
+            FunctionIndex::ReadLocalFuncIndex => "read_value_local",
+            FunctionIndex::SerFnFuncIndex => "serialize_function",
+            FunctionIndex::SerKnownURefs => "serialize_known_urefs",
+            FunctionIndex::WriteFuncIndex => "write",
+            FunctionIndex::WriteLocalFuncIndex => "write_local",
+            FunctionIndex::GetReadFuncIndex => "get_read",
+            FunctionIndex::GetFnFuncIndex => "get_function",
+            FunctionIndex::AddFuncIndex => "add",
+            FunctionIndex::NewFuncIndex => "new_uref",
+            FunctionIndex::LoadArgFuncIndex => "load_arg",
+            FunctionIndex::GetArgFuncIndex => "get_arg",
+            FunctionIndex::RetFuncIndex => "ret",
+            FunctionIndex::CallContractFuncIndex => "call_contract",
+            FunctionIndex::GetCallResultFuncIndex => "get_call_result",
+            FunctionIndex::GetURefFuncIndex => "get_uref",
+            FunctionIndex::HasURefFuncIndex => "has_uref_name",
+            FunctionIndex::AddURefFuncIndex => "add_uref",
+            FunctionIndex::GasFuncIndex => "gas",
+            FunctionIndex::GasProfiledFuncIndex => "gas_profiled",
+            FunctionIndex::GrowMemoryFuncIndex => "grow_memory",
+            FunctionIndex::StoreFnIndex => "store_function",
+            FunctionIndex::ProtocolVersionFuncIndex => "protocol_version",
+            FunctionIndex::IsValidFnIndex => "is_valid",
+            FunctionIndex::RevertFuncIndex => "revert",
+            FunctionIndex::AddAssociatedKeyFuncIndex => "add_associated_key",
+            FunctionIndex::RemoveAssociatedKeyFuncIndex => "remove_associated_key",
+            FunctionIndex::UpdateAssociatedKeyFuncIndex => "update_associated_key",
+            FunctionIndex::SetActionThresholdFuncIndex => "set_action_threshold",
+            FunctionIndex::ListKnownURefsIndex => "list_known_urefs",
+            FunctionIndex::RemoveURef => "remove_uref",
+            FunctionIndex::GetCallerIndex => "get_caller",
+            FunctionIndex::GetBlocktimeIndex => "get_blocktime",
+            FunctionIndex::CreatePurseIndex => "create_purse",
+            FunctionIndex::TransferToAccountIndex => "transfer_to_account",
+            FunctionIndex::TransferFromPurseToAccountIndex => "transfer_from_purse_to_account",
+            FunctionIndex::TransferFromPurseToPurseIndex => "transfer_from_purse_to_purse",
+            FunctionIndex::GetBalanceIndex => "get_balance",
+        }
+    }
 }
 
 impl Into<usize> for FunctionIndex {
@@ -74,6 +130,10 @@ mod tests {
         assert_eq!(primitive, 20usize);
     }
     #[test]
+    fn name_is_import_name() {
+        assert_eq!(FunctionIndex::CallContractFuncIndex.name(), "call_contract");
+    }
+    #[test]
     #[should_panic]
     fn invalid_index() {
         FunctionIndex::try_from(123_456_789usize).unwrap();
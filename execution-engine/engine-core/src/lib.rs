@@ -6,6 +6,7 @@ extern crate core;
 
 // third-party dependencies
 extern crate blake2;
+extern crate crossbeam_utils;
 extern crate failure;
 extern crate itertools;
 extern crate linked_hash_map;
@@ -28,6 +29,8 @@ extern crate engine_wasm_prep;
 extern crate matches;
 #[cfg(test)]
 extern crate proptest;
+#[cfg(test)]
+extern crate wabt;
 
 #[macro_use]
 extern crate num_derive;
@@ -2,29 +2,133 @@ pub mod error;
 pub mod memory_resolver;
 mod resolver_v1;
 
-use wasmi::ModuleImportResolver;
+use wasmi::{Error as InterpreterError, ModuleImportResolver, Signature, ValueType};
 
 use self::error::ResolverError;
+use execution::Error;
 use resolvers::memory_resolver::MemoryResolver;
 
 /// Creates a module resolver for given protocol version.
 ///
 /// * `protocol_version` Version of the protocol. Can't be lower than 1.
+/// * `max_memory_pages` Most pages the module's memory may be declared to grow to.
 pub fn create_module_resolver(
     protocol_version: u64,
+    max_memory_pages: u32,
 ) -> Result<impl ModuleImportResolver + MemoryResolver, ResolverError> {
     match protocol_version {
-        1 => Ok(resolver_v1::RuntimeModuleImportResolver::default()),
+        1 => Ok(resolver_v1::RuntimeModuleImportResolver::new(
+            max_memory_pages,
+        )),
         _ => Err(ResolverError::UnknownProtocolVersion(protocol_version)),
     }
 }
 
+/// Fails with [`Error::HostFunctionMisuse`] if the host function `name`, whose signature is
+/// `expected`, is imported with the signature `imported`.  The mismatch is described from the two
+/// signatures alone, so that every node reports the same error.
+fn check_signature(
+    name: &str,
+    imported: &Signature,
+    expected: &Signature,
+) -> Result<(), InterpreterError> {
+    let imported_params = imported.params();
+    let expected_params = expected.params();
+    let mismatch = if imported_params.len() != expected_params.len() {
+        format!(
+            "takes {} arguments, but was imported taking {}",
+            expected_params.len(),
+            imported_params.len()
+        )
+    } else if let Some(index) =
+        (0..expected_params.len()).find(|&index| imported_params[index] != expected_params[index])
+    {
+        format!(
+            "argument {} is {}, but was imported as {}",
+            index,
+            type_name(Some(expected_params[index])),
+            type_name(Some(imported_params[index]))
+        )
+    } else if imported.return_type() != expected.return_type() {
+        format!(
+            "returns {}, but was imported returning {}",
+            type_name(expected.return_type()),
+            type_name(imported.return_type())
+        )
+    } else {
+        return Ok(());
+    };
+    Err(InterpreterError::Host(Box::new(
+        Error::HostFunctionMisuse {
+            function: name.to_string(),
+            mismatch,
+        },
+    )))
+}
+
+/// Name of a wasm value type as written in the text format, or `nothing` for no value.
+fn type_name(value_type: Option<ValueType>) -> &'static str {
+    match value_type {
+        Some(ValueType::I32) => "i32",
+        Some(ValueType::I64) => "i64",
+        Some(ValueType::F32) => "f32",
+        Some(ValueType::F64) => "f64",
+        None => "nothing",
+    }
+}
+
 #[test]
 fn resolve_invalid_module() {
-    assert!(create_module_resolver(0).is_err());
+    assert!(create_module_resolver(0, ::engine_wasm_prep::MEM_PAGES).is_err());
 }
 
 #[test]
 fn protocol_version_1_always_resolves() {
-    assert!(create_module_resolver(1).is_ok());
+    assert!(create_module_resolver(1, ::engine_wasm_prep::MEM_PAGES).is_ok());
+}
+
+#[cfg(test)]
+fn signature_mismatch(imported: &Signature) -> Option<String> {
+    let expected = Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32));
+    match check_signature("read_value", imported, &expected) {
+        Ok(()) => None,
+        Err(error) => match error
+            .as_host_error()
+            .and_then(|host_error| host_error.downcast_ref::<Error>())
+        {
+            Some(Error::HostFunctionMisuse { function, mismatch }) => {
+                assert_eq!(function, "read_value");
+                Some(mismatch.clone())
+            }
+            _ => panic!("expected HostFunctionMisuse, got {:?}", error),
+        },
+    }
+}
+
+#[test]
+fn should_accept_matching_signature() {
+    let imported = Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32));
+    assert_eq!(signature_mismatch(&imported), None);
+}
+
+#[test]
+fn should_describe_signature_mismatches() {
+    assert_eq!(
+        signature_mismatch(&Signature::new(
+            &[ValueType::I32; 3][..],
+            Some(ValueType::I32)
+        )),
+        Some("takes 2 arguments, but was imported taking 3".to_string())
+    );
+    assert_eq!(
+        signature_mismatch(&Signature::new(
+            &[ValueType::I32, ValueType::I64][..],
+            Some(ValueType::I32)
+        )),
+        Some("argument 1 is i32, but was imported as i64".to_string())
+    );
+    assert_eq!(
+        signature_mismatch(&Signature::new(&[ValueType::I32; 2][..], None)),
+        Some("returns i32, but was imported returning nothing".to_string())
+    );
 }
@@ -6,8 +6,10 @@ use wasmi::{
 };
 use wasmi::{FuncInstance, MemoryRef, ModuleImportResolver};
 
+use super::check_signature;
 use super::error::ResolverError;
 use super::memory_resolver::MemoryResolver;
+use execution::Error;
 use function_index::FunctionIndex;
 
 pub struct RuntimeModuleImportResolver {
@@ -15,11 +17,11 @@ pub struct RuntimeModuleImportResolver {
     max_memory: u32,
 }
 
-impl Default for RuntimeModuleImportResolver {
-    fn default() -> Self {
+impl RuntimeModuleImportResolver {
+    pub fn new(max_memory: u32) -> Self {
         RuntimeModuleImportResolver {
             memory: RefCell::new(None),
-            max_memory: 64,
+            max_memory,
         }
     }
 }
@@ -34,11 +36,12 @@ impl MemoryResolver for RuntimeModuleImportResolver {
     }
 }
 
+// Functions added here must also be added to `engine_wasm_prep::imports::HOST_FUNCTIONS`.
 impl ModuleImportResolver for RuntimeModuleImportResolver {
     fn resolve_func(
         &self,
         field_name: &str,
-        _signature: &Signature,
+        signature: &Signature,
     ) -> Result<FuncRef, InterpreterError> {
         let func_ref = match field_name {
             "read_value" => FuncInstance::alloc_host(
@@ -117,6 +120,18 @@ impl ModuleImportResolver for RuntimeModuleImportResolver {
                 Signature::new(&[ValueType::I32; 1][..], None),
                 FunctionIndex::GasFuncIndex.into(),
             ),
+            // Only imported by modules instrumented for gas profiling, so it is deliberately left
+            // out of `engine_wasm_prep::imports::HOST_FUNCTIONS`.
+            "gas_profiled" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32; 2][..], None),
+                FunctionIndex::GasProfiledFuncIndex.into(),
+            ),
+            // Only imported by modules whose `grow_memory` instructions were replaced during
+            // preparation, so it is also left out of `HOST_FUNCTIONS`.
+            "grow_memory" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32; 1][..], Some(ValueType::I32)),
+                FunctionIndex::GrowMemoryFuncIndex.into(),
+            ),
             "store_function" => FuncInstance::alloc_host(
                 Signature::new(&[ValueType::I32; 5][..], None),
                 FunctionIndex::StoreFnIndex.into(),
@@ -192,6 +207,7 @@ impl ModuleImportResolver for RuntimeModuleImportResolver {
                 )));
             }
         };
+        check_signature(field_name, signature, func_ref.signature())?;
         Ok(func_ref)
     }
 
@@ -203,9 +219,12 @@ impl ModuleImportResolver for RuntimeModuleImportResolver {
         if field_name == "memory" {
             let effective_max = descriptor.maximum().unwrap_or(self.max_memory + 1);
             if descriptor.initial() > self.max_memory || effective_max > self.max_memory {
-                Err(InterpreterError::Instantiation(
-                    "Module requested too much memory".to_owned(),
-                ))
+                Err(InterpreterError::Host(Box::new(
+                    Error::MemoryLimitExceeded {
+                        requested_pages: effective_max.max(descriptor.initial()),
+                        max_pages: self.max_memory,
+                    },
+                )))
             } else {
                 // Note: each "page" is 64 KiB
                 let mem = MemoryInstance::alloc(
@@ -9,6 +9,7 @@ use engine_shared::newtypes::{CorrelationId, Validated};
 use engine_shared::transform::{self, Transform, TypeMismatch};
 use engine_storage::global_state::StateReader;
 
+use engine_state::engine_config::{DEFAULT_MAX_QUERY_DEPTH, DEFAULT_MAX_QUERY_NODES};
 use engine_state::execution_effect::ExecutionEffect;
 use engine_state::op::Op;
 use meter::heap_meter::HeapSize;
@@ -19,6 +20,35 @@ use utils::add;
 pub enum QueryResult {
     Success(Value),
     ValueNotFound(String),
+    /// The query would have gone past its [`QueryLimits`].
+    QueryTooComplex(String),
+}
+
+/// Bounds on the work done to answer a single query, so that a crafted path cannot tie up the
+/// reader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryLimits {
+    /// The most names the query may follow from its base key.
+    pub max_depth: usize,
+    /// The most trie nodes the query may visit, summed over every key it reads.
+    pub max_nodes: usize,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_depth: DEFAULT_MAX_QUERY_DEPTH,
+            max_nodes: DEFAULT_MAX_QUERY_NODES,
+        }
+    }
+}
+
+/// Reasons for a query to stop before the end of its path.
+enum QueryStop<E> {
+    /// The name at the given index could not be followed, for the given reason.
+    NotFound(usize, String),
+    TooComplex(String),
+    Storage(E),
 }
 
 /// Keeps track of already accessed keys.
@@ -86,6 +116,7 @@ pub struct TrackingCopy<R> {
     cache: TrackingCopyCache<HeapSize>,
     ops: HashMap<Key, Op>,
     fns: HashMap<Key, Transform>,
+    trie_reads: u64,
 }
 
 #[derive(Debug)]
@@ -102,9 +133,15 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
             cache: TrackingCopyCache::new(1024 * 16, HeapSize), //TODO: Should `max_cache_size` be fraction of Wasm memory limit?
             ops: HashMap::new(),
             fns: HashMap::new(),
+            trie_reads: 0,
         }
     }
 
+    /// Returns how many values have been read from global state rather than from the cache.
+    pub fn trie_reads(&self) -> u64 {
+        self.trie_reads
+    }
+
     pub fn get(
         &mut self,
         correlation_id: CorrelationId,
@@ -113,6 +150,7 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         if let Some(value) = self.cache.get(k) {
             return Ok(Some(value.to_owned()));
         }
+        self.trie_reads += 1;
         if let Some(value) = self.reader.read(correlation_id, k)? {
             self.cache.insert_read(*k, value.to_owned());
             Ok(Some(value))
@@ -125,9 +163,32 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         &mut self,
         correlation_id: CorrelationId,
         k: &Validated<Key>,
+    ) -> Result<Option<Value>, R::Error> {
+        self.read_counting_nodes(correlation_id, k, &mut 0)
+    }
+
+    /// Like `read`, but adds the number of trie nodes visited to `nodes_visited`; cached values
+    /// cost none.
+    fn read_counting_nodes(
+        &mut self,
+        correlation_id: CorrelationId,
+        k: &Validated<Key>,
+        nodes_visited: &mut usize,
     ) -> Result<Option<Value>, R::Error> {
         let k = k.normalize();
-        if let Some(value) = self.get(correlation_id, &k)? {
+        let maybe_value = match self.cache.get(&k) {
+            Some(value) => Some(value.to_owned()),
+            None => {
+                self.trie_reads += 1;
+                let (maybe_value, nodes) = self.reader.read_with_node_count(correlation_id, &k)?;
+                *nodes_visited += nodes;
+                if let Some(ref value) = maybe_value {
+                    self.cache.insert_read(k, value.to_owned());
+                }
+                maybe_value
+            }
+        };
+        if let Some(value) = maybe_value {
             add(&mut self.ops, k, Op::Read);
             add(&mut self.fns, k, Transform::Identity);
             Ok(Some(value))
@@ -193,14 +254,26 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         ExecutionEffect::new(self.ops.clone(), self.fns.clone())
     }
 
+    /// Reads the value under `base_key`, then follows each name of `path` in turn through the
+    /// named keys of the accounts and contracts on the way.  Stops with
+    /// [`QueryTooComplex`](QueryResult::QueryTooComplex) rather than go past `limits`.
     pub fn query(
         &mut self,
         correlation_id: CorrelationId,
         base_key: Key,
         path: &[String],
+        limits: QueryLimits,
     ) -> Result<QueryResult, R::Error> {
+        if path.len() > limits.max_depth {
+            return Ok(QueryResult::QueryTooComplex(format!(
+                "Path of {} names is longer than the limit of {}",
+                path.len(),
+                limits.max_depth
+            )));
+        }
+        let mut nodes_visited: usize = 0;
         let validated_key = Validated::new(base_key, Validated::valid)?;
-        match self.read(correlation_id, &validated_key)? {
+        match self.read_counting_nodes(correlation_id, &validated_key, &mut nodes_visited)? {
             None => Ok(QueryResult::ValueNotFound(self.error_path_msg(
                 base_key,
                 path,
@@ -210,44 +283,73 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
             Some(base_value) => {
                 let result = path.iter().enumerate().try_fold(
                     base_value,
-                    // We encode the two possible short-circuit conditions with
-                    // Result<(usize, String), Error>, where the Ok(_) case corresponds to
-                    // QueryResult::ValueNotFound and Err(_) corresponds to
-                    // a storage-related error. The information in the Ok(_) case is used
-                    // to build an informative error message about why the query was not successful.
-                    |curr_value, (i, name)| -> Result<Value, Result<(usize, String), R::Error>> {
+                    // The possible short-circuit conditions are encoded with QueryStop:
+                    // NotFound corresponds to QueryResult::ValueNotFound, and its information is
+                    // used to build an informative error message about why the query was not
+                    // successful; TooComplex corresponds to QueryResult::QueryTooComplex; and
+                    // Storage to a storage-related error.
+                    |curr_value, (i, name)| -> Result<Value, QueryStop<R::Error>> {
+                        if nodes_visited > limits.max_nodes {
+                            return Err(QueryStop::TooComplex(format!(
+                                "Visited {} trie nodes, more than the limit of {}, before name {}",
+                                nodes_visited, limits.max_nodes, name
+                            )));
+                        }
                         match curr_value {
                             Value::Account(account) => {
                                 if let Some(key) = account.urefs_lookup().get(name) {
                                     let validated_key = Validated::new(*key, Validated::valid)?;
-                                    self.read_key_or_stop(correlation_id, validated_key, i)
+                                    self.read_key_or_stop(
+                                        correlation_id,
+                                        validated_key,
+                                        i,
+                                        &mut nodes_visited,
+                                    )
                                 } else {
-                                    Err(Ok((i, format!("Name {} not found in Account at path:", name))))
+                                    Err(QueryStop::NotFound(
+                                        i,
+                                        format!("Name {} not found in Account at path:", name),
+                                    ))
                                 }
                             }
 
                             Value::Contract(contract) => {
                                 if let Some(key) = contract.urefs_lookup().get(name) {
                                     let validated_key = Validated::new(*key, Validated::valid)?;
-                                    self.read_key_or_stop(correlation_id, validated_key, i)
+                                    self.read_key_or_stop(
+                                        correlation_id,
+                                        validated_key,
+                                        i,
+                                        &mut nodes_visited,
+                                    )
                                 } else {
-                                    Err(Ok((i, format!("Name {} not found in Contract at path:", name))))
+                                    Err(QueryStop::NotFound(
+                                        i,
+                                        format!("Name {} not found in Contract at path:", name),
+                                    ))
                                 }
                             }
 
                             other => Err(
-                                Ok((i, format!("Name {} cannot be followed from value {:?} because it is neither an account nor contract. Value found at path:", name, other)))
-                                ),
+                                QueryStop::NotFound(i, format!("Name {} cannot be followed from value {:?} because it is neither an account nor contract. Value found at path:", name, other))
+                            ),
                         }
                     },
                 );
 
                 match result {
+                    Ok(_) if nodes_visited > limits.max_nodes => {
+                        Ok(QueryResult::QueryTooComplex(format!(
+                            "Visited {} trie nodes, more than the limit of {}",
+                            nodes_visited, limits.max_nodes
+                        )))
+                    }
                     Ok(value) => Ok(QueryResult::Success(value)),
-                    Err(Ok((i, s))) => Ok(QueryResult::ValueNotFound(
+                    Err(QueryStop::NotFound(i, s)) => Ok(QueryResult::ValueNotFound(
                         self.error_path_msg(base_key, path, s, i),
                     )),
-                    Err(Err(err)) => Err(err),
+                    Err(QueryStop::TooComplex(s)) => Ok(QueryResult::QueryTooComplex(s)),
+                    Err(QueryStop::Storage(err)) => Err(err),
                 }
             }
         }
@@ -258,14 +360,18 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         correlation_id: CorrelationId,
         key: Validated<Key>,
         i: usize,
-    ) -> Result<Value, Result<(usize, String), R::Error>> {
-        match self.read(correlation_id, &key) {
+        nodes_visited: &mut usize,
+    ) -> Result<Value, QueryStop<R::Error>> {
+        match self.read_counting_nodes(correlation_id, &key, nodes_visited) {
             // continue recursing
             Ok(Some(value)) => Ok(value),
             // key not found in the global state; stop recursing
-            Ok(None) => Err(Ok((i, format!("Name {:?} not found: ", *key)))),
+            Ok(None) => Err(QueryStop::NotFound(
+                i,
+                format!("Name {:?} not found: ", *key),
+            )),
             // global state access error; stop recursing
-            Err(error) => Err(Err(error)),
+            Err(error) => Err(QueryStop::Storage(error)),
         }
     }
 
@@ -304,7 +410,7 @@ mod tests {
     use engine_storage::global_state::in_memory::InMemoryGlobalState;
     use engine_storage::global_state::StateReader;
 
-    use super::{AddResult, QueryResult, Validated};
+    use super::{AddResult, QueryLimits, QueryResult, Validated};
     use contract_ffi::value::account::{
         AccountActivity, AssociatedKeys, BlockTime, PublicKey, PurseId, Weight, KEY_SIZE,
     };
@@ -394,6 +500,25 @@ mod tests {
         assert_eq!(db_value, 1);
     }
 
+    #[test]
+    fn tracking_copy_counts_trie_reads_but_not_cache_hits() {
+        let correlation_id = CorrelationId::new();
+        let counter = Rc::new(Cell::new(0));
+        let db = CountingDb::new(Rc::clone(&counter));
+        let mut tc = TrackingCopy::new(db);
+        let k1 = Validated::new(Key::Hash([0u8; 32]), Validated::valid).unwrap();
+        let k2 = Key::Hash([1u8; 32]);
+
+        assert_eq!(tc.trie_reads(), 0);
+        tc.read(correlation_id, &k1).unwrap();
+        tc.read(correlation_id, &k1).unwrap();
+        tc.get(correlation_id, &k2).unwrap();
+        tc.get(correlation_id, &k2).unwrap();
+
+        assert_eq!(counter.get(), 2);
+        assert_eq!(tc.trie_reads(), 2);
+    }
+
     #[test]
     fn tracking_copy_read() {
         let correlation_id = CorrelationId::new();
@@ -641,6 +766,44 @@ mod tests {
         assert_eq!(tc.ops.get(&k), Some(&Op::Write));
     }
 
+    #[test]
+    fn query_should_refuse_path_longer_than_max_depth() {
+        let correlation_id = CorrelationId::new();
+        let k = Key::Hash([1u8; 32]);
+        let gs = InMemoryGlobalState::from_pairs(correlation_id, &[(k, Value::Int32(1))]).unwrap();
+        let mut tc = TrackingCopy::new(gs);
+        let limits = QueryLimits {
+            max_depth: 2,
+            ..QueryLimits::default()
+        };
+        let path = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let result = tc.query(correlation_id, k, &path, limits);
+
+        assert_matches!(result, Ok(QueryResult::QueryTooComplex(_)));
+        // the limit is checked before anything is read
+        assert!(tc.ops.is_empty());
+    }
+
+    #[test]
+    fn query_should_stop_after_max_nodes() {
+        let correlation_id = CorrelationId::new();
+        let k = Key::Hash([1u8; 32]);
+        let gs = InMemoryGlobalState::from_pairs(correlation_id, &[(k, Value::Int32(1))]).unwrap();
+        let mut tc = TrackingCopy::new(gs);
+        let limits = QueryLimits {
+            max_nodes: 0,
+            ..QueryLimits::default()
+        };
+
+        let result = tc.query(correlation_id, k, &[], limits);
+        assert_matches!(result, Ok(QueryResult::QueryTooComplex(_)));
+
+        // the value is cached now, so reading it again visits no nodes
+        let result = tc.query(correlation_id, k, &[], limits);
+        assert_matches!(result, Ok(QueryResult::Success(Value::Int32(1))));
+    }
+
     proptest! {
         #[test]
         fn query_empty_path(k in key_arb(), missing_key in key_arb(), v in value_arb()) {
@@ -648,14 +811,24 @@ mod tests {
             let gs = InMemoryGlobalState::from_pairs(correlation_id, &[(k, v.to_owned())]).unwrap();
             let mut tc = TrackingCopy::new(gs);
             let empty_path = Vec::new();
-            if let Ok(QueryResult::Success(result)) = tc.query(correlation_id, k, &empty_path) {
+            if let Ok(QueryResult::Success(result)) = tc.query(
+                correlation_id,
+                k,
+                &empty_path,
+                QueryLimits::default(),
+            ) {
                 assert_eq!(v, result);
             } else {
                 panic!("Query failed when it should not have!");
             }
 
             if missing_key != k {
-                let result = tc.query(correlation_id, missing_key, &empty_path);
+                let result = tc.query(
+                    correlation_id,
+                    missing_key,
+                    &empty_path,
+                    QueryLimits::default(),
+                );
                 assert_matches!(result, Ok(QueryResult::ValueNotFound(_)));
             }
         }
@@ -681,14 +854,24 @@ mod tests {
             ).unwrap();
             let mut tc = TrackingCopy::new(gs);
             let path = vec!(name.clone());
-            if let Ok(QueryResult::Success(result)) = tc.query(correlation_id, contract_key, &path) {
+            if let Ok(QueryResult::Success(result)) = tc.query(
+                correlation_id,
+                contract_key,
+                &path,
+                QueryLimits::default(),
+            ) {
                 assert_eq!(v, result);
             } else {
                 panic!("Query failed when it should not have!");
             }
 
             if missing_name != name {
-                let result = tc.query(correlation_id, contract_key, &[missing_name]);
+                let result = tc.query(
+                    correlation_id,
+                    contract_key,
+                    &[missing_name],
+                    QueryLimits::default(),
+                );
                 assert_matches!(result, Ok(QueryResult::ValueNotFound(_)));
             }
         }
@@ -725,14 +908,24 @@ mod tests {
             ).unwrap();
             let mut tc = TrackingCopy::new(gs);
             let path = vec!(name.clone());
-            if let Ok(QueryResult::Success(result)) = tc.query(correlation_id, account_key, &path) {
+            if let Ok(QueryResult::Success(result)) = tc.query(
+                correlation_id,
+                account_key,
+                &path,
+                QueryLimits::default(),
+            ) {
                 assert_eq!(v, result);
             } else {
                 panic!("Query failed when it should not have!");
             }
 
             if missing_name != name {
-                let result = tc.query(correlation_id, account_key, &[missing_name]);
+                let result = tc.query(
+                    correlation_id,
+                    account_key,
+                    &[missing_name],
+                    QueryLimits::default(),
+                );
                 assert_matches!(result, Ok(QueryResult::ValueNotFound(_)));
             }
         }
@@ -779,7 +972,12 @@ mod tests {
             ]).unwrap();
             let mut tc = TrackingCopy::new(gs);
             let path = vec!(contract_name, state_name);
-            if let Ok(QueryResult::Success(result)) = tc.query(correlation_id, account_key, &path) {
+            if let Ok(QueryResult::Success(result)) = tc.query(
+                correlation_id,
+                account_key,
+                &path,
+                QueryLimits::default(),
+            ) {
                 assert_eq!(v, result);
             } else {
                 panic!("Query failed when it should not have!");
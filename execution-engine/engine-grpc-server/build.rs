@@ -1,18 +1,42 @@
 extern crate protoc_rust_grpc;
 
+use std::process::Command;
+
+/// Reported as the commit hash when the engine is not built from a git checkout.
+const UNKNOWN_GIT_COMMIT_HASH: &str = "unknown";
+
+/// Returns the hash of the commit checked out in the enclosing repository.
+fn git_commit_hash() -> String {
+    Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| UNKNOWN_GIT_COMMIT_HASH.to_string())
+}
+
 fn main() {
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+    println!("cargo:rustc-env=GIT_COMMIT_HASH={}", git_commit_hash());
+
     println!("cargo:rerun-if-changed=../../protobuf/io/casperlabs/casper/consensus/state.proto");
     println!("cargo:rerun-if-changed=../../protobuf/io/casperlabs/ipc/ipc.proto");
+    println!("cargo:rerun-if-changed=../../protobuf/grpc/health/v1/health.proto");
     protoc_rust_grpc::run(protoc_rust_grpc::Args {
         out_dir: "src/engine_server",
         input: &[
             "../../protobuf/io/casperlabs/casper/consensus/state.proto",
             "../../protobuf/io/casperlabs/ipc/ipc.proto",
+            "../../protobuf/grpc/health/v1/health.proto",
         ],
         includes: &[
             "../../protobuf/",
             "../../protobuf/io/casperlabs/casper/consensus",
             "../../protobuf/io/casperlabs/ipc",
+            "../../protobuf/grpc/health/v1",
         ],
         rust_protobuf: true,
         ..Default::default()
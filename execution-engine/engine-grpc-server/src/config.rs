@@ -0,0 +1,486 @@
+//! Server settings loaded from a TOML file.
+//!
+//! Every field mirrors a command line option of the same name.  Values given on the command line
+//! take precedence over values from the file, which in turn take precedence over the built-in
+//! defaults applied by `main`.
+//!
+//! # Example
+//!
+//! ```toml
+//! socket = ["/tmp/casperlabs/sockets/.casper-node.sock", "tcp://0.0.0.0:7777"]
+//! socket-mode = "0660"
+//! abstract-socket = false
+//! pid-file = "/run/casperlabs/engine.pid"
+//! data-dir = "/var/lib/casperlabs"
+//! loglevel = "debug"
+//! log-format = "json"
+//! log-file = "/var/log/casperlabs/engine.log"
+//! log-file-max-size = 10485760
+//! log-file-count = 5
+//! pages = 2560
+//! max-db-size = 10485760
+//! trie-cache-size = 10000
+//! use-payment-code = false
+//! gas-config = "/etc/casperlabs/gas.toml"
+//! max-wasm-size = 4194304
+//! wasm-stack-height = 65536
+//! execution-timeout-ms = 5000
+//! max-gas-limit = 100000000
+//! default-gas-limit = 1000000
+//! metrics-addr = "127.0.0.1:9090"
+//! threads = 4
+//! tls-cert = "/etc/casperlabs/engine.crt"
+//! tls-key = "/etc/casperlabs/engine.key"
+//! read-only = false
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer};
+use toml;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "could not read config file: {}", error),
+            Error::Parse(error) => write!(f, "could not parse config file: {}", error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Parse(error)
+    }
+}
+
+/// Server settings.  A `None` field means the setting was not given.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Either a single socket or a list of them, each served by its own listener.
+    #[serde(deserialize_with = "one_or_many")]
+    pub socket: Option<Vec<String>>,
+    /// Octal permission bits, kept as a string so that e.g. `"0660"` reads as written.
+    pub socket_mode: Option<String>,
+    pub abstract_socket: Option<bool>,
+    pub pid_file: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub state_subdir: Option<String>,
+    pub loglevel: Option<String>,
+    pub log_format: Option<String>,
+    pub log_timestamp_format: Option<String>,
+    pub log_file: Option<PathBuf>,
+    pub log_file_max_size: Option<u64>,
+    pub log_file_count: Option<usize>,
+    pub log_target: Option<String>,
+    pub syslog_facility: Option<String>,
+    pub syslog_ident: Option<String>,
+    pub async_logging: Option<bool>,
+    pub log_include_thread_id: Option<bool>,
+    pub pages: Option<usize>,
+    pub max_db_size: Option<usize>,
+    pub max_readers: Option<u32>,
+    pub db_open_retries: Option<u32>,
+    pub trie_cache_size: Option<usize>,
+    pub warmup: Option<bool>,
+    pub warmup_depth: Option<u32>,
+    pub key_bloom: Option<bool>,
+    pub store_name: Option<String>,
+    pub audit_log: Option<PathBuf>,
+    pub use_payment_code: Option<bool>,
+    pub read_only: Option<bool>,
+    pub restore_from: Option<PathBuf>,
+    pub restore_genesis_hash: Option<String>,
+    pub no_sync: Option<bool>,
+    pub sync_policy: Option<String>,
+    pub gas_config: Option<PathBuf>,
+    pub max_wasm_size: Option<usize>,
+    pub max_memory_pages: Option<u32>,
+    pub wasm_stack_height: Option<u32>,
+    pub execution_timeout_ms: Option<u64>,
+    pub max_gas_limit: Option<u64>,
+    pub default_gas_limit: Option<u64>,
+    pub dedup_cache_size: Option<usize>,
+    pub exec_cache_size: Option<usize>,
+    pub wasm_cache_size: Option<usize>,
+    pub commit_retries: Option<u32>,
+    pub commit_group_window_ms: Option<u64>,
+    pub estimate_gas_max_trials: Option<u32>,
+    pub max_deploys_per_batch: Option<usize>,
+    pub max_query_depth: Option<usize>,
+    pub max_query_nodes: Option<usize>,
+    pub max_rss_mb: Option<u64>,
+    pub gas_profiling: Option<bool>,
+    pub metrics_addr: Option<String>,
+    pub threads: Option<usize>,
+    pub max_concurrent_requests: Option<usize>,
+    pub socket_backlog: Option<u32>,
+    pub drain_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+}
+
+/// Reads either a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(Some(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    }))
+}
+
+impl Config {
+    /// Reads and deserializes the TOML file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let contents = fs::read_to_string(path)?;
+        Config::from_toml(&contents)
+    }
+
+    pub fn from_toml(input: &str) -> Result<Config, Error> {
+        toml::from_str(input).map_err(Into::into)
+    }
+
+    /// Returns every setting given, keyed by its name, with lists joined by commas.  Settings
+    /// which were not given are left out.
+    pub fn properties(&self) -> BTreeMap<String, String> {
+        match toml::Value::try_from(self) {
+            Ok(toml::Value::Table(table)) => table
+                .into_iter()
+                .map(|(name, value)| (name, property_value(value)))
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Returns a config where every setting present in `overrides` replaces the one in `self`.
+    pub fn merge(self, overrides: Config) -> Config {
+        Config {
+            socket: overrides.socket.or(self.socket),
+            socket_mode: overrides.socket_mode.or(self.socket_mode),
+            abstract_socket: overrides.abstract_socket.or(self.abstract_socket),
+            pid_file: overrides.pid_file.or(self.pid_file),
+            data_dir: overrides.data_dir.or(self.data_dir),
+            state_subdir: overrides.state_subdir.or(self.state_subdir),
+            loglevel: overrides.loglevel.or(self.loglevel),
+            log_format: overrides.log_format.or(self.log_format),
+            log_timestamp_format: overrides.log_timestamp_format.or(self.log_timestamp_format),
+            log_file: overrides.log_file.or(self.log_file),
+            log_file_max_size: overrides.log_file_max_size.or(self.log_file_max_size),
+            log_file_count: overrides.log_file_count.or(self.log_file_count),
+            log_target: overrides.log_target.or(self.log_target),
+            syslog_facility: overrides.syslog_facility.or(self.syslog_facility),
+            syslog_ident: overrides.syslog_ident.or(self.syslog_ident),
+            async_logging: overrides.async_logging.or(self.async_logging),
+            log_include_thread_id: overrides
+                .log_include_thread_id
+                .or(self.log_include_thread_id),
+            pages: overrides.pages.or(self.pages),
+            max_db_size: overrides.max_db_size.or(self.max_db_size),
+            max_readers: overrides.max_readers.or(self.max_readers),
+            db_open_retries: overrides.db_open_retries.or(self.db_open_retries),
+            trie_cache_size: overrides.trie_cache_size.or(self.trie_cache_size),
+            warmup: overrides.warmup.or(self.warmup),
+            warmup_depth: overrides.warmup_depth.or(self.warmup_depth),
+            key_bloom: overrides.key_bloom.or(self.key_bloom),
+            store_name: overrides.store_name.or(self.store_name),
+            audit_log: overrides.audit_log.or(self.audit_log),
+            use_payment_code: overrides.use_payment_code.or(self.use_payment_code),
+            read_only: overrides.read_only.or(self.read_only),
+            restore_from: overrides.restore_from.or(self.restore_from),
+            restore_genesis_hash: overrides.restore_genesis_hash.or(self.restore_genesis_hash),
+            no_sync: overrides.no_sync.or(self.no_sync),
+            sync_policy: overrides.sync_policy.or(self.sync_policy),
+            gas_config: overrides.gas_config.or(self.gas_config),
+            max_wasm_size: overrides.max_wasm_size.or(self.max_wasm_size),
+            max_memory_pages: overrides.max_memory_pages.or(self.max_memory_pages),
+            wasm_stack_height: overrides.wasm_stack_height.or(self.wasm_stack_height),
+            execution_timeout_ms: overrides.execution_timeout_ms.or(self.execution_timeout_ms),
+            max_gas_limit: overrides.max_gas_limit.or(self.max_gas_limit),
+            default_gas_limit: overrides.default_gas_limit.or(self.default_gas_limit),
+            dedup_cache_size: overrides.dedup_cache_size.or(self.dedup_cache_size),
+            exec_cache_size: overrides.exec_cache_size.or(self.exec_cache_size),
+            wasm_cache_size: overrides.wasm_cache_size.or(self.wasm_cache_size),
+            commit_retries: overrides.commit_retries.or(self.commit_retries),
+            commit_group_window_ms: overrides
+                .commit_group_window_ms
+                .or(self.commit_group_window_ms),
+            estimate_gas_max_trials: overrides
+                .estimate_gas_max_trials
+                .or(self.estimate_gas_max_trials),
+            max_deploys_per_batch: overrides
+                .max_deploys_per_batch
+                .or(self.max_deploys_per_batch),
+            max_query_depth: overrides.max_query_depth.or(self.max_query_depth),
+            max_query_nodes: overrides.max_query_nodes.or(self.max_query_nodes),
+            max_rss_mb: overrides.max_rss_mb.or(self.max_rss_mb),
+            gas_profiling: overrides.gas_profiling.or(self.gas_profiling),
+            metrics_addr: overrides.metrics_addr.or(self.metrics_addr),
+            threads: overrides.threads.or(self.threads),
+            max_concurrent_requests: overrides
+                .max_concurrent_requests
+                .or(self.max_concurrent_requests),
+            socket_backlog: overrides.socket_backlog.or(self.socket_backlog),
+            drain_timeout: overrides.drain_timeout.or(self.drain_timeout),
+            read_timeout: overrides.read_timeout.or(self.read_timeout),
+            tls_cert: overrides.tls_cert.or(self.tls_cert),
+            tls_key: overrides.tls_key.or(self.tls_key),
+        }
+    }
+}
+
+/// Renders a setting for [`Config::properties`]: strings as they are, lists joined by commas.
+fn property_value(value: toml::Value) -> String {
+    match value {
+        toml::Value::String(string) => string,
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(property_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Config, Error};
+
+    #[test]
+    fn should_parse_all_settings() {
+        let input = r#"
+            socket = "/tmp/ee.sock"
+            socket-mode = "0660"
+            abstract-socket = true
+            pid-file = "/tmp/ee.pid"
+            data-dir = "/tmp/data"
+            state-subdir = "state_v2"
+            loglevel = "debug"
+            log-format = "json"
+            log-timestamp-format = "rfc3339-local"
+            log-file = "/tmp/ee.log"
+            log-file-max-size = 1024
+            log-file-count = 3
+            log-target = "syslog"
+            syslog-facility = "local0"
+            syslog-ident = "ee"
+            async-logging = true
+            log-include-thread-id = true
+            pages = 2560
+            max-db-size = 10485760
+            max-readers = 512
+            db-open-retries = 10
+            trie-cache-size = 10000
+            warmup = true
+            warmup-depth = 8
+            key-bloom = true
+            store-name = "testnet"
+            audit-log = "/tmp/audit.log"
+            use-payment-code = true
+            read-only = true
+            restore-from = "/tmp/backup"
+            restore-genesis-hash = "00"
+            no-sync = true
+            sync-policy = "interval:500"
+            gas-config = "/tmp/gas.toml"
+            max-wasm-size = 1048576
+            max-memory-pages = 32
+            wasm-stack-height = 1024
+            execution-timeout-ms = 5000
+            max-gas-limit = 100000000
+            default-gas-limit = 1000000
+            dedup-cache-size = 500
+            exec-cache-size = 200
+            wasm-cache-size = 50
+            commit-retries = 3
+            commit-group-window-ms = 2
+            estimate-gas-max-trials = 8
+            max-deploys-per-batch = 500
+            max-query-depth = 16
+            max-query-nodes = 5000
+            max-rss-mb = 4096
+            gas-profiling = true
+            metrics-addr = "127.0.0.1:9090"
+            threads = 4
+            max-concurrent-requests = 64
+            socket-backlog = 1024
+            drain-timeout = 60
+            read-timeout = 120
+            tls-cert = "/tmp/ee.crt"
+            tls-key = "/tmp/ee.key"
+        "#;
+
+        let expected = Config {
+            socket: Some(vec!["/tmp/ee.sock".to_string()]),
+            socket_mode: Some("0660".to_string()),
+            abstract_socket: Some(true),
+            pid_file: Some(PathBuf::from("/tmp/ee.pid")),
+            data_dir: Some(PathBuf::from("/tmp/data")),
+            state_subdir: Some("state_v2".to_string()),
+            loglevel: Some("debug".to_string()),
+            log_format: Some("json".to_string()),
+            log_timestamp_format: Some("rfc3339-local".to_string()),
+            log_file: Some(PathBuf::from("/tmp/ee.log")),
+            log_file_max_size: Some(1024),
+            log_file_count: Some(3),
+            log_target: Some("syslog".to_string()),
+            syslog_facility: Some("local0".to_string()),
+            syslog_ident: Some("ee".to_string()),
+            async_logging: Some(true),
+            log_include_thread_id: Some(true),
+            pages: Some(2560),
+            max_db_size: Some(10_485_760),
+            max_readers: Some(512),
+            db_open_retries: Some(10),
+            trie_cache_size: Some(10_000),
+            warmup: Some(true),
+            warmup_depth: Some(8),
+            key_bloom: Some(true),
+            store_name: Some("testnet".to_string()),
+            audit_log: Some(PathBuf::from("/tmp/audit.log")),
+            use_payment_code: Some(true),
+            read_only: Some(true),
+            restore_from: Some(PathBuf::from("/tmp/backup")),
+            restore_genesis_hash: Some("00".to_string()),
+            no_sync: Some(true),
+            sync_policy: Some("interval:500".to_string()),
+            gas_config: Some(PathBuf::from("/tmp/gas.toml")),
+            max_wasm_size: Some(1_048_576),
+            max_memory_pages: Some(32),
+            wasm_stack_height: Some(1024),
+            execution_timeout_ms: Some(5000),
+            max_gas_limit: Some(100_000_000),
+            default_gas_limit: Some(1_000_000),
+            dedup_cache_size: Some(500),
+            exec_cache_size: Some(200),
+            wasm_cache_size: Some(50),
+            commit_retries: Some(3),
+            commit_group_window_ms: Some(2),
+            estimate_gas_max_trials: Some(8),
+            max_deploys_per_batch: Some(500),
+            max_query_depth: Some(16),
+            max_query_nodes: Some(5000),
+            max_rss_mb: Some(4096),
+            gas_profiling: Some(true),
+            metrics_addr: Some("127.0.0.1:9090".to_string()),
+            threads: Some(4),
+            max_concurrent_requests: Some(64),
+            socket_backlog: Some(1024),
+            drain_timeout: Some(60),
+            read_timeout: Some(120),
+            tls_cert: Some(PathBuf::from("/tmp/ee.crt")),
+            tls_key: Some(PathBuf::from("/tmp/ee.key")),
+        };
+
+        assert_eq!(Config::from_toml(input).expect("should parse"), expected);
+    }
+
+    #[test]
+    fn should_parse_empty_file() {
+        assert_eq!(
+            Config::from_toml("").expect("should parse"),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn should_parse_list_of_sockets() {
+        let config = Config::from_toml(r#"socket = ["/tmp/ee.sock", "tcp://127.0.0.1:7777"]"#)
+            .expect("should parse");
+        assert_eq!(
+            config.socket,
+            Some(vec![
+                "/tmp/ee.sock".to_string(),
+                "tcp://127.0.0.1:7777".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_settings() {
+        match Config::from_toml("sockett = \"/tmp/ee.sock\"") {
+            Err(Error::Parse(_)) => (),
+            other => panic!("expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn overrides_should_take_precedence() {
+        let file = Config {
+            socket: Some(vec!["/tmp/file.sock".to_string()]),
+            loglevel: Some("debug".to_string()),
+            pages: Some(10),
+            ..Default::default()
+        };
+
+        let cli = Config {
+            socket: Some(vec!["/tmp/cli.sock".to_string()]),
+            ..Default::default()
+        };
+
+        let merged = file.merge(cli);
+
+        assert_eq!(merged.socket, Some(vec!["/tmp/cli.sock".to_string()]));
+        assert_eq!(merged.loglevel, Some("debug".to_string()));
+        assert_eq!(merged.pages, Some(10));
+        assert_eq!(merged.data_dir, None);
+    }
+
+    #[test]
+    fn properties_should_list_given_settings_by_name() {
+        let config = Config {
+            socket: Some(vec![
+                "/tmp/ee.sock".to_string(),
+                "tcp://0.0.0.0:7777".to_string(),
+            ]),
+            data_dir: Some(PathBuf::from("/tmp/data")),
+            pages: Some(10),
+            read_only: Some(true),
+            tls_key: Some(PathBuf::from("/tmp/ee.key")),
+            ..Default::default()
+        };
+
+        let properties = config.properties();
+
+        let expected: Vec<(&str, &str)> = vec![
+            ("data-dir", "/tmp/data"),
+            ("pages", "10"),
+            ("read-only", "true"),
+            ("socket", "/tmp/ee.sock,tcp://0.0.0.0:7777"),
+            ("tls-key", "/tmp/ee.key"),
+        ];
+        let actual: Vec<(&str, &str)> = properties
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,300 @@
+//! Checks made on the data directory before global state is opened in it, and the restoring of a
+//! backup into it.
+//!
+//! A misconfigured directory, e.g. a volume mounted read-only or at the wrong path, would
+//! otherwise only show up as an LMDB error, or as a panic while creating the directory.  Each
+//! cause found here is reported on its own, with an exit code from `sysexits.h`.
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::process;
+
+/// Name of the file created and removed again to check that the data directory is writable.
+const WRITE_TEST_FILE_PREFIX: &str = ".write-test";
+
+/// Suffix of the name a backup's data file is copied to before it is renamed into place.
+const RESTORE_FILE_SUFFIX: &str = ".restoring";
+
+// exit codes from sysexits.h
+const EX_NOINPUT: i32 = 66;
+const EX_CANTCREAT: i32 = 73;
+const EX_IOERR: i32 = 74;
+const EX_NOPERM: i32 = 77;
+const EX_CONFIG: i32 = 78;
+
+#[derive(Debug)]
+pub enum DataDirError {
+    /// The directory does not exist and was not to be created.
+    NotFound(PathBuf),
+    /// The path, or one of its ancestors, exists but is not a directory.
+    NotADirectory(PathBuf),
+    /// The directory does not exist and cannot be created, as its nearest existing ancestor
+    /// `parent` is not writable.
+    ParentNotWritable {
+        path: PathBuf,
+        parent: PathBuf,
+    },
+    /// The directory exists, but files cannot be created in it.
+    PermissionDenied(PathBuf),
+    /// A backup was to be restored into the directory, but it already holds the data file at the
+    /// given path.
+    NotEmpty(PathBuf),
+    /// A backup was to be restored, but there is no data file at the given path.
+    BackupNotFound(PathBuf),
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+}
+
+impl DataDirError {
+    /// Returns the code the server exits with when failing with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DataDirError::NotFound(_) => EX_NOINPUT,
+            DataDirError::NotADirectory(_) => EX_CONFIG,
+            DataDirError::ParentNotWritable { .. } => EX_CANTCREAT,
+            DataDirError::PermissionDenied(_) => EX_NOPERM,
+            DataDirError::NotEmpty(_) => EX_CANTCREAT,
+            DataDirError::BackupNotFound(_) => EX_NOINPUT,
+            DataDirError::Io { .. } => EX_IOERR,
+        }
+    }
+}
+
+impl Display for DataDirError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DataDirError::NotFound(path) => {
+                write!(f, "data directory {} does not exist", path.display())
+            }
+            DataDirError::NotADirectory(path) => write!(
+                f,
+                "data directory path {} exists but is not a directory",
+                path.display()
+            ),
+            DataDirError::ParentNotWritable { path, parent } => write!(
+                f,
+                "cannot create data directory {}: {} is not writable",
+                path.display(),
+                parent.display()
+            ),
+            DataDirError::PermissionDenied(path) => write!(
+                f,
+                "permission denied: cannot create files in data directory {}",
+                path.display()
+            ),
+            DataDirError::NotEmpty(path) => write!(
+                f,
+                "refusing to restore over existing global state in {}",
+                path.display()
+            ),
+            DataDirError::BackupNotFound(path) => {
+                write!(f, "backup data file {} does not exist", path.display())
+            }
+            DataDirError::Io { path, error } => {
+                write!(f, "data directory {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+/// Makes sure `path` is a directory global state can be opened in.
+///
+/// If `writable` is set, the directory is created if it does not exist yet, and a file is created
+/// in it and removed again to check that it can be written to.  Otherwise it only has to exist.
+pub fn prepare(path: &Path, writable: bool) -> Result<(), DataDirError> {
+    let existing_ancestor = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or_else(|| Path::new("."));
+    if !existing_ancestor.is_dir() {
+        return Err(DataDirError::NotADirectory(existing_ancestor.to_path_buf()));
+    }
+
+    if existing_ancestor != path {
+        if !writable {
+            return Err(DataDirError::NotFound(path.to_path_buf()));
+        }
+        fs::create_dir_all(path).map_err(|error| match error.kind() {
+            io::ErrorKind::PermissionDenied => DataDirError::ParentNotWritable {
+                path: path.to_path_buf(),
+                parent: existing_ancestor.to_path_buf(),
+            },
+            _ => DataDirError::Io {
+                path: path.to_path_buf(),
+                error,
+            },
+        })?;
+    }
+
+    if writable {
+        check_writable(path)?;
+    }
+
+    Ok(())
+}
+
+/// Copies the data file called `file_name` from the backup in `backup_dir` into `data_dir`, which
+/// must not hold one yet, and returns its size in bytes.
+///
+/// The file is copied under a temporary name and then renamed, so that a data file found in
+/// `data_dir` is always a complete copy, even if the restore was interrupted.
+pub fn restore(backup_dir: &Path, data_dir: &Path, file_name: &str) -> Result<u64, DataDirError> {
+    let source = backup_dir.join(file_name);
+    let target = data_dir.join(file_name);
+    if target.exists() {
+        return Err(DataDirError::NotEmpty(target));
+    }
+    if !source.is_file() {
+        return Err(DataDirError::BackupNotFound(source));
+    }
+
+    let partial = data_dir.join(format!("{}{}", file_name, RESTORE_FILE_SUFFIX));
+    let size = fs::copy(&source, &partial)
+        .and_then(|size| fs::rename(&partial, &target).map(|_| size))
+        .map_err(|error| {
+            // best effort; the partial copy is overwritten by the next attempt anyway
+            let _ = fs::remove_file(&partial);
+            DataDirError::Io {
+                path: target,
+                error,
+            }
+        })?;
+    Ok(size)
+}
+
+/// Returns `true` if `name` can be used as the name of a directory directly under the data
+/// directory, i.e. it is not empty, has no path separators and does not refer to the data
+/// directory itself or its parent.
+pub fn is_valid_subdir_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.chars().any(path::is_separator)
+}
+
+/// Creates a file in the directory at `path` and removes it again.
+fn check_writable(path: &Path) -> Result<(), DataDirError> {
+    let io_error = |error: io::Error| match error.kind() {
+        io::ErrorKind::PermissionDenied => DataDirError::PermissionDenied(path.to_path_buf()),
+        _ => DataDirError::Io {
+            path: path.to_path_buf(),
+            error,
+        },
+    };
+
+    let test_file = path.join(format!("{}-{}", WRITE_TEST_FILE_PREFIX, process::id()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&test_file)
+        .map_err(io_error)?;
+    fs::remove_file(&test_file).map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn should_create_missing_directory() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("a").join("b");
+
+        prepare(&path, true).expect("should prepare");
+
+        assert!(path.is_dir());
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn should_not_create_missing_directory_when_not_writable() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("missing");
+
+        match prepare(&path, false) {
+            Err(DataDirError::NotFound(ref not_found)) if *not_found == path => (),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn should_restore_data_file_from_backup() {
+        let backup_dir = tempdir().unwrap();
+        let data_dir = tempdir().unwrap();
+        fs::write(backup_dir.path().join("data.mdb"), b"backup").unwrap();
+
+        let size = restore(backup_dir.path(), data_dir.path(), "data.mdb").expect("should restore");
+
+        assert_eq!(size, 6);
+        assert_eq!(
+            fs::read(data_dir.path().join("data.mdb")).unwrap(),
+            b"backup"
+        );
+        // nothing is left under the temporary name
+        assert_eq!(fs::read_dir(data_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn should_not_restore_over_existing_data_file() {
+        let backup_dir = tempdir().unwrap();
+        let data_dir = tempdir().unwrap();
+        fs::write(backup_dir.path().join("data.mdb"), b"backup").unwrap();
+        fs::write(data_dir.path().join("data.mdb"), b"existing").unwrap();
+
+        match restore(backup_dir.path(), data_dir.path(), "data.mdb") {
+            Err(error @ DataDirError::NotEmpty(_)) => assert_eq!(error.exit_code(), EX_CANTCREAT),
+            other => panic!("expected NotEmpty, got {:?}", other),
+        }
+        assert_eq!(
+            fs::read(data_dir.path().join("data.mdb")).unwrap(),
+            b"existing"
+        );
+    }
+
+    #[test]
+    fn should_fail_restore_from_directory_without_data_file() {
+        let backup_dir = tempdir().unwrap();
+        let data_dir = tempdir().unwrap();
+
+        match restore(backup_dir.path(), data_dir.path(), "data.mdb") {
+            Err(DataDirError::BackupNotFound(_)) => (),
+            other => panic!("expected BackupNotFound, got {:?}", other),
+        }
+        assert_eq!(fs::read_dir(data_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn should_accept_plain_subdir_names() {
+        assert!(is_valid_subdir_name("global_state"));
+        assert!(is_valid_subdir_name("state-v2.1"));
+    }
+
+    #[test]
+    fn should_reject_subdir_names_leaving_data_dir() {
+        for name in &["", ".", "..", "a/b", "/abs", "../up"] {
+            assert!(!is_valid_subdir_name(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn should_reject_file_in_path() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("file");
+        File::create(&file_path).unwrap();
+
+        for path in &[file_path.clone(), file_path.join("global_state")] {
+            match prepare(path, true) {
+                Err(error @ DataDirError::NotADirectory(_)) => {
+                    assert_eq!(error.exit_code(), EX_CONFIG)
+                }
+                other => panic!("expected NotADirectory, got {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Caps the number of requests the server handles at once.
+//!
+//! Requests which find every thread of the pool busy would otherwise queue up in the server
+//! without bound, each holding its message in memory.  Once a [`ConcurrencyLimit`] is reached,
+//! [`AdmissionGate`] instead rejects further requests straight away with an `Unavailable` status,
+//! so that the client can back off or try another server.  Every RPC of the engine service counts
+//! until its response is complete, or for a streaming response until its stream is done; health
+//! checks are served by another service and never rejected.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::hold_until_complete;
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+use super::metrics;
+
+/// The most requests to handle at once, if limited, and the number being handled.  Clones share
+/// the count, so that one limit can cover the servers of several sockets.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimit {
+    max: Option<usize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a limit of `max` requests at once, or one which admits every request if `None`.
+    pub fn new(max: Option<usize>) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            max,
+            in_flight: Default::default(),
+        }
+    }
+
+    /// Returns the number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Counts a request as in flight for as long as the returned permit is alive, or returns
+    /// `None` if that would take the number in flight above the limit.
+    pub fn try_acquire(&self) -> Option<Permit> {
+        let mut in_flight = self.in_flight();
+        loop {
+            if let Some(max) = self.max {
+                if in_flight >= max {
+                    metrics::REQUESTS_REJECTED.increment();
+                    return None;
+                }
+            }
+            match self.in_flight.compare_exchange(
+                in_flight,
+                in_flight + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(current) => in_flight = current,
+            }
+        }
+        metrics::REQUESTS_IN_FLIGHT.increment();
+        Some(Permit {
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+}
+
+/// Counts a request as in flight until dropped.
+#[derive(Debug)]
+pub struct Permit {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        metrics::REQUESTS_IN_FLIGHT.decrement();
+    }
+}
+
+fn limit_error(limit: &ConcurrencyLimit) -> grpc::Error {
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::Unavailable as i32,
+        grpc_message: format!(
+            "server is handling its maximum of {} concurrent requests; retry later",
+            limit.max.unwrap_or_default()
+        ),
+    })
+}
+
+/// Wraps an engine service so that its requests are rejected while `limit` is reached.
+pub struct AdmissionGate<S> {
+    service: S,
+    limit: ConcurrencyLimit,
+}
+
+impl<S> AdmissionGate<S> {
+    pub fn new(service: S, limit: ConcurrencyLimit) -> Self {
+        AdmissionGate { service, limit }
+    }
+}
+
+impl<S: ExecutionEngineService> ExecutionEngineService for AdmissionGate<S> {
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(self.service.exec(request_options, exec_request), permit)
+    }
+
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::StreamingResponse::err(limit_error(&self.limit)),
+        };
+        // the stream is produced after the handler returns, so it owns the permit until done
+        self.service
+            .exec_stream(request_options, exec_request)
+            .map_items(move |item| {
+                let _permit = &permit;
+                item
+            })
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(self.service.commit(request_options, commit_request), permit)
+    }
+
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .commit_batch(request_options, commit_batch_request),
+            permit,
+        )
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(self.service.query(request_options, query_request), permit)
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service.validate(request_options, validate_request),
+            permit,
+        )
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service.run_genesis(request_options, genesis_request),
+            permit,
+        )
+    }
+
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .get_store_stats(request_options, store_stats_request),
+            permit,
+        )
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .list_state_roots(request_options, list_state_roots_request),
+            permit,
+        )
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .backup_store(request_options, backup_store_request),
+            permit,
+        )
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .validate_deploy(request_options, validate_deploy_request),
+            permit,
+        )
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service.state_diff(request_options, state_diff_request),
+            permit,
+        )
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .flush_store(request_options, flush_store_request),
+            permit,
+        )
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .get_engine_version(request_options, get_engine_version_request),
+            permit,
+        )
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .get_genesis_hash(request_options, get_genesis_hash_request),
+            permit,
+        )
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .verify_store(request_options, verify_store_request),
+            permit,
+        )
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .prune_state(request_options, prune_state_request),
+            permit,
+        )
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .get_capabilities(request_options, get_capabilities_request),
+            permit,
+        )
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .estimate_gas(request_options, estimate_gas_request),
+            permit,
+        )
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::StreamingResponse::err(limit_error(&self.limit)),
+        };
+        // the stream is produced after the handler returns, so it owns the permit until done
+        self.service
+            .export_state(request_options, export_state_request)
+            .map_items(move |item| {
+                let _permit = &permit;
+                item
+            })
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        let permit = match self.limit.try_acquire() {
+            Some(permit) => permit,
+            None => return grpc::SingleResponse::err(limit_error(&self.limit)),
+        };
+        hold_until_complete(
+            self.service
+                .get_empty_state_root(request_options, get_empty_state_root_request),
+            permit,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_admit_up_to_limit() {
+        let limit = ConcurrencyLimit::new(Some(2));
+        let first = limit.try_acquire().expect("should admit first");
+        let _second = limit.try_acquire().expect("should admit second");
+        assert!(limit.try_acquire().is_none());
+        assert_eq!(limit.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(limit.in_flight(), 1);
+        assert!(limit.try_acquire().is_some());
+    }
+
+    #[test]
+    fn should_admit_every_request_without_limit() {
+        let limit = ConcurrencyLimit::new(None);
+        let permits: Vec<Permit> = (0..100).filter_map(|_| limit.try_acquire()).collect();
+        assert_eq!(permits.len(), 100);
+        assert_eq!(limit.in_flight(), 100);
+        drop(permits);
+        assert_eq!(limit.in_flight(), 0);
+    }
+
+    #[test]
+    fn clones_should_share_limit() {
+        let limit = ConcurrencyLimit::new(Some(1));
+        let _permit = limit.clone().try_acquire().expect("should admit");
+        assert!(limit.try_acquire().is_none());
+    }
+}
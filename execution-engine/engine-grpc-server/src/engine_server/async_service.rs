@@ -0,0 +1,318 @@
+//! Hands requests to the engine service off to a tokio thread pool.
+//!
+//! The gRPC server otherwise runs each handler on one of its own threads, so that requests beyond
+//! the number of threads wait for a handler to return before they are even looked at.
+//! [`AsyncService`] instead answers each request with a future, and runs its handler on a
+//! [`ThreadPool`] in a [`blocking`](tokio_threadpool::blocking) section: at most the pool's
+//! `max_blocking` handlers run at once, and requests waiting for their turn hold no thread.
+//!
+//! Only built with the `tokio-server` feature.
+
+use std::sync::Arc;
+
+use futures::future;
+use futures::{Future, Stream};
+use tokio_threadpool::{self, BlockingError, ThreadPool};
+
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+
+const THREAD_NAME_PREFIX: &str = "engine-handler-";
+
+/// Creates a pool running up to `max_handlers` handlers at once.
+pub fn new_pool(max_handlers: usize) -> ThreadPool {
+    tokio_threadpool::Builder::new()
+        .name_prefix(THREAD_NAME_PREFIX)
+        .max_blocking(max_handlers)
+        .build()
+}
+
+fn blocking_error(error: BlockingError) -> grpc::Error {
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::Internal as i32,
+        grpc_message: format!("request handler could not run: {}", error),
+    })
+}
+
+/// Returns a future which runs `handler` in a blocking section of the pool it is polled on, once
+/// the pool has room for another one.  Fails with an `Internal` status outside of a pool.
+fn run_blocking<R, F>(handler: F) -> impl Future<Item = R, Error = grpc::Error>
+where
+    F: FnOnce() -> R,
+{
+    let mut handler = Some(handler);
+    future::poll_fn(move || {
+        tokio_threadpool::blocking(|| {
+            let handler = handler.take().expect("handler should only run once");
+            handler()
+        })
+    })
+    .map_err(blocking_error)
+}
+
+/// Wraps an engine service so that its handlers run on `pool`.  Clones share the wrapped service
+/// and the pool, so that several servers can serve it.
+pub struct AsyncService<S> {
+    service: Arc<S>,
+    pool: Arc<ThreadPool>,
+}
+
+impl<S> AsyncService<S> {
+    pub fn new(service: S, pool: Arc<ThreadPool>) -> Self {
+        AsyncService {
+            service: Arc::new(service),
+            pool,
+        }
+    }
+}
+
+impl<S> Clone for AsyncService<S> {
+    fn clone(&self) -> Self {
+        AsyncService {
+            service: Arc::clone(&self.service),
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+impl<S: Send + Sync + 'static> AsyncService<S> {
+    /// Answers a request with the response `handler` gives on the pool.
+    fn spawn_single<T, F>(&self, handler: F) -> grpc::SingleResponse<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> grpc::SingleResponse<T> + Send + 'static,
+    {
+        let service = Arc::clone(&self.service);
+        let response =
+            run_blocking(move || handler(&service)).and_then(grpc::SingleResponse::drop_metadata);
+        grpc::SingleResponse::no_metadata(self.pool.spawn_handle(response))
+    }
+
+    /// Answers a request with the stream `handler` gives on the pool.
+    fn spawn_streaming<T, F>(&self, handler: F) -> grpc::StreamingResponse<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> grpc::StreamingResponse<T> + Send + 'static,
+    {
+        let service = Arc::clone(&self.service);
+        let stream =
+            run_blocking(move || handler(&service)).map(grpc::StreamingResponse::drop_metadata);
+        grpc::StreamingResponse::no_metadata(self.pool.spawn_handle(stream).flatten_stream())
+    }
+}
+
+impl<S: ExecutionEngineService + Send + Sync + 'static> ExecutionEngineService for AsyncService<S> {
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        self.spawn_single(move |service| service.exec(request_options, exec_request))
+    }
+
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        self.spawn_streaming(move |service| service.exec_stream(request_options, exec_request))
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        self.spawn_single(move |service| service.commit(request_options, commit_request))
+    }
+
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        self.spawn_single(move |service| {
+            service.commit_batch(request_options, commit_batch_request)
+        })
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        self.spawn_single(move |service| service.query(request_options, query_request))
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        self.spawn_single(move |service| service.validate(request_options, validate_request))
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        self.spawn_single(move |service| service.run_genesis(request_options, genesis_request))
+    }
+
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        self.spawn_single(move |service| {
+            service.get_store_stats(request_options, store_stats_request)
+        })
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        self.spawn_single(move |service| {
+            service.list_state_roots(request_options, list_state_roots_request)
+        })
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        self.spawn_single(move |service| {
+            service.backup_store(request_options, backup_store_request)
+        })
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        self.spawn_single(move |service| {
+            service.validate_deploy(request_options, validate_deploy_request)
+        })
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        self.spawn_single(move |service| service.state_diff(request_options, state_diff_request))
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        self.spawn_single(move |service| service.flush_store(request_options, flush_store_request))
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        self.spawn_single(move |service| {
+            service.get_engine_version(request_options, get_engine_version_request)
+        })
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        self.spawn_single(move |service| {
+            service.get_genesis_hash(request_options, get_genesis_hash_request)
+        })
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        self.spawn_single(move |service| {
+            service.verify_store(request_options, verify_store_request)
+        })
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        self.spawn_single(move |service| service.prune_state(request_options, prune_state_request))
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        self.spawn_single(move |service| {
+            service.get_capabilities(request_options, get_capabilities_request)
+        })
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        self.spawn_single(move |service| {
+            service.estimate_gas(request_options, estimate_gas_request)
+        })
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        self.spawn_streaming(move |service| {
+            service.export_state(request_options, export_state_request)
+        })
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        self.spawn_single(move |service| {
+            service.get_empty_state_root(request_options, get_empty_state_root_request)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_run_handler_on_pool() {
+        let pool = new_pool(1);
+        let result = pool.spawn_handle(run_blocking(|| 42)).wait();
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn should_fail_with_internal_status_outside_of_pool() {
+        match run_blocking(|| 42).wait() {
+            Err(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(error.grpc_status, grpc::GrpcStatus::Internal as i32);
+            }
+            other => panic!("expected an Internal status, got {:?}", other),
+        }
+    }
+}
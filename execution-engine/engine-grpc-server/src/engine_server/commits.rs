@@ -0,0 +1,179 @@
+//! Committing effects to global state, with retries on transient storage errors.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::thread;
+use std::time::Duration;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::account::PublicKey;
+use contract_ffi::value::U512;
+use engine_core::engine_state::error::Error as EngineError;
+use engine_core::engine_state::genesis::GenesisURefsSource;
+use engine_core::engine_state::{get_bonded_validators, EngineState, GetBondedValidatorsError};
+use engine_server::ipc::CommitResponse;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_shared::transform::Transform;
+use engine_storage::global_state::{CommitResult, History, WriteStats};
+
+use super::errors::out_of_space_error;
+use super::ipc;
+use super::mappings::grpc_response_from_commit_result;
+use super::metrics;
+
+/// Wait before the first retry of a commit which failed with a transient storage error; it
+/// doubles with every further retry, up to `COMMIT_RETRY_MAX_BACKOFF`.
+const COMMIT_RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+const COMMIT_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Runs `commit`, retrying it up to `retries` times while it fails with a transient storage
+/// error, with the wait before each retry twice as long as the one before.  Other errors are
+/// returned straight away.
+pub fn retry_commit<F>(retries: u32, mut commit: F) -> Result<CommitResult, EngineError>
+where
+    F: FnMut() -> Result<CommitResult, EngineError>,
+{
+    let mut backoff = COMMIT_RETRY_INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match commit() {
+            Err(ref error) if error.is_transient() && attempt < retries => {
+                attempt += 1;
+                log_debug!(&format!(
+                    "commit failed with transient error: {}; retry {} of {} in {:?}",
+                    error, attempt, retries, backoff
+                ));
+                thread::sleep(backoff);
+                backoff = cmp::min(backoff * 2, COMMIT_RETRY_MAX_BACKOFF);
+            }
+            Err(error) => {
+                if attempt > 0 {
+                    log_error!(&format!(
+                        "commit failed after {} retries: {}",
+                        attempt, error
+                    ));
+                }
+                return Err(error);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Applies each set of effects on top of the previous one, starting from `prestate_hash`, and
+/// returns the commit response.  Fails with a `ResourceExhausted` status if there was no room
+/// left for global state, in which case nothing was written.
+pub fn commit_effects_batch<H>(
+    engine_state: &EngineState<H>,
+    correlation_id: CorrelationId,
+    prestate_hash: Blake2bHash,
+    effects: Vec<HashMap<Key, Transform>>,
+) -> Result<ipc::CommitResponse, grpc::Error>
+where
+    H: History,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error> + Debug,
+{
+    let commit_result = retry_commit(engine_state.config().get_commit_retries(), || {
+        engine_state
+            .apply_effects_batch(correlation_id, prestate_hash, effects.clone())
+            .map_err(EngineError::from)
+    });
+    if let Err(EngineError::StorageError(ref error)) = commit_result {
+        if error.is_out_of_space() {
+            return Err(out_of_space_error(error));
+        }
+    }
+    if let Ok(CommitResult::Success {
+        state_root: poststate_hash,
+        stats,
+    }) = commit_result
+    {
+        metrics::COMMITS.increment();
+        let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
+        let bonded_validators_res = get_bonded_validators(
+            engine_state.state(),
+            poststate_hash,
+            &pos_key,
+            correlation_id,
+        );
+        Ok(bonded_validators_and_commit_result(
+            prestate_hash,
+            poststate_hash,
+            stats,
+            bonded_validators_res,
+        ))
+    } else {
+        // Commit unsuccessful; nothing was written.
+        Ok(grpc_response_from_commit_result(
+            prestate_hash,
+            commit_result,
+        ))
+    }
+}
+
+// TODO: Refactor.
+#[allow(clippy::implicit_hasher)]
+pub fn bonded_validators_and_commit_result<H>(
+    prestate_hash: Blake2bHash,
+    poststate_hash: Blake2bHash,
+    stats: WriteStats,
+    bonded_validators: Result<HashMap<PublicKey, U512>, GetBondedValidatorsError<H>>,
+) -> CommitResponse
+where
+    H: History,
+    H::Error: Into<EngineError> + std::fmt::Debug,
+{
+    match bonded_validators {
+        Ok(bonded_validators) => {
+            let mut grpc_response = grpc_response_from_commit_result::<H::Error>(
+                prestate_hash,
+                Ok(CommitResult::Success {
+                    state_root: poststate_hash,
+                    stats,
+                }),
+            );
+            let grpc_bonded_validators = bonded_validators
+                .iter()
+                .map(|(pk, bond)| {
+                    let mut ipc_bond = ipc::Bond::new();
+                    ipc_bond.set_stake((*bond).into());
+                    ipc_bond.set_validator_public_key(pk.value().to_vec());
+                    ipc_bond
+                })
+                .collect::<Vec<ipc::Bond>>()
+                .into();
+            grpc_response
+                .mut_success() // We know it's a success because of the check few lines earlier.
+                .set_bonded_validators(grpc_bonded_validators);
+            grpc_response
+        }
+        Err(GetBondedValidatorsError::StorageErrors(error)) => {
+            grpc_response_from_commit_result(poststate_hash, Err(error))
+        }
+        Err(GetBondedValidatorsError::PostStateHashNotFound(root_hash)) => {
+            // I am not sure how to parse this error. It would mean that most probably
+            // we have screwed up something in the trie store because `root_hash` was
+            // calculated by us just a moment ago. It [root_hash] is a `poststate_hash` we return to the node.
+            // There is no proper error variant in the `engine_storage::error::Error` for it though.
+            let error_message = format!(
+                "Post state hash not found {} when calculating bonded validators set.",
+                root_hash
+            );
+            log_error!(&error_message);
+            let mut commit_response = ipc::CommitResponse::new();
+            let mut err = ipc::PostEffectsError::new();
+            err.set_message(error_message);
+            commit_response.set_failed_transform(err);
+            commit_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            commit_response
+        }
+        Err(GetBondedValidatorsError::PoSNotFound(key)) => {
+            grpc_response_from_commit_result::<H::Error>(
+                poststate_hash,
+                Ok(CommitResult::KeyNotFound(key)),
+            )
+        }
+    }
+}
@@ -0,0 +1,518 @@
+//! Running, charging and caching the deploys of `exec`, `exec_stream`, `validate_deploy` and
+//! `estimate_gas` requests.
+
+use std::cmp;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug};
+use std::time::Instant;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::account::{BlockTime, PublicKey};
+use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
+use engine_core::engine_state::execution_result::ExecutionResult;
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_core::execution::{Error as ExecutionError, Executor, WasmiExecutor};
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::global_state::History;
+use engine_wasm_prep::{PreprocessingError, Preprocessor, WasmiPreprocessor};
+
+use super::errors::{
+    deadline_exceeded_error, invalid_argument_error, is_readers_full_failure, malformed_module,
+    malformed_module_error, malformed_payment_error, readers_full_error, root_not_found_error,
+    Failure,
+};
+use super::gas_estimate::{search_gas_limit, with_margin, GasSearch};
+use super::ipc;
+use super::mappings::{charge_gas_price, error_code};
+use super::request::{get_preprocessor, is_past_deadline, parse_state_hash};
+use super::state;
+
+const EXPECTED_PUBLIC_KEY_LENGTH: usize = 32;
+
+/// Returns the price `deploy` pays for each unit of gas: `request_gas_price`, the gas price of the
+/// request carrying it, if that is not 0, otherwise the deploy's own.  Both the gas limit of the
+/// deploy and its cost are worked out at this price.
+pub fn deploy_gas_price(request_gas_price: u64, deploy: &ipc::Deploy) -> u64 {
+    match request_gas_price {
+        0 => deploy.gas_price,
+        gas_price => gas_price,
+    }
+}
+
+/// Charges the result of the deploy at `index` at `gas_price`, or at 1 if it is 0, failing with
+/// an `InvalidArgument` status if its cost overflows.
+pub fn charge_deploy_result(
+    index: usize,
+    deploy_result: &mut ipc::DeployResult,
+    gas_price: u64,
+) -> Result<(), grpc::Error> {
+    charge_gas_price(deploy_result, cmp::max(gas_price, 1))
+        .map_err(|error| invalid_argument_error(format!("deploy {}: {}", index, error)))
+}
+
+/// Returns `true` if the cost of each deploy of `exec_request` should be broken down.
+pub fn is_gas_profiling(config: &EngineConfig, exec_request: &ipc::ExecRequest) -> bool {
+    config.is_gas_profiling() || exec_request.get_gas_profiling()
+}
+
+/// Returns the key of the account a deploy runs as, failing if its address is not a public key.
+pub fn deploy_account_key(deploy: &ipc::Deploy) -> Result<Key, EngineError> {
+    let address_len = deploy.address.len();
+    if address_len != EXPECTED_PUBLIC_KEY_LENGTH {
+        return Err(EngineError::InvalidPublicKeyLength {
+            expected: EXPECTED_PUBLIC_KEY_LENGTH,
+            actual: address_len,
+        });
+    }
+    let mut dest = [0; EXPECTED_PUBLIC_KEY_LENGTH];
+    dest.copy_from_slice(&deploy.address);
+    Ok(Key::Account(dest))
+}
+
+/// Parses the public keys a deploy was signed with.
+pub fn deploy_authorized_keys(deploy: &ipc::Deploy) -> Result<BTreeSet<PublicKey>, EngineError> {
+    deploy
+        .authorization_keys
+        .iter()
+        .map(|key_bytes| {
+            // Try to convert an element of bytes into a possibly
+            // valid PublicKey with error handling
+            PublicKey::try_from(key_bytes.as_slice()).map_err(|_| {
+                EngineError::InvalidPublicKeyLength {
+                    expected: EXPECTED_PUBLIC_KEY_LENGTH,
+                    actual: key_bytes.len(),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Why a deploy has no gas limit it may run with.
+#[derive(Debug)]
+pub enum GasLimitError {
+    /// the deploy runs at a gas price of 0, and no default gas limit is configured
+    NoDefaultGasLimit,
+    /// what the deploy pays for is above the configured maximum
+    AboveMaximum { gas_limit: u64, max_gas_limit: u64 },
+}
+
+impl GasLimitError {
+    /// Returns the deploy field to fix, as reported by `validate_deploy`.
+    fn field(&self) -> &'static str {
+        match self {
+            GasLimitError::NoDefaultGasLimit => "deploy.gas_price",
+            GasLimitError::AboveMaximum { .. } => "deploy.motes_transferred_in_payment",
+        }
+    }
+}
+
+impl fmt::Display for GasLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GasLimitError::NoDefaultGasLimit => write!(
+                f,
+                "gas_price must be greater than 0, as no default gas limit is configured"
+            ),
+            GasLimitError::AboveMaximum {
+                gas_limit,
+                max_gas_limit,
+            } => write!(
+                f,
+                "gas limit {} exceeds the maximum of {}",
+                gas_limit, max_gas_limit
+            ),
+        }
+    }
+}
+
+/// Returns the gas limit a deploy runs with: what it pays for at `gas_price`, or the configured
+/// default if `gas_price` is 0.  Fails if there is no limit, or if it is above the configured
+/// maximum.
+pub fn deploy_gas_limit(
+    config: &EngineConfig,
+    deploy: &ipc::Deploy,
+    gas_price: u64,
+) -> Result<u64, GasLimitError> {
+    let gas_limit = match gas_price {
+        0 => config
+            .get_default_gas_limit()
+            .ok_or(GasLimitError::NoDefaultGasLimit)?,
+        // TODO: is the rounding in this division ok?
+        gas_price => deploy.motes_transferred_in_payment / gas_price,
+    };
+    match config.get_max_gas_limit() {
+        Some(max_gas_limit) if gas_limit > max_gas_limit => Err(GasLimitError::AboveMaximum {
+            gas_limit,
+            max_gas_limit,
+        }),
+        _ => Ok(gas_limit),
+    }
+}
+
+/// Fails an exec request with an `InvalidArgument` status if it carries more deploys than the
+/// configured maximum, so that a single request cannot keep a worker busy for too long.
+pub fn check_deploy_count(config: &EngineConfig, deploy_count: usize) -> Result<(), grpc::Error> {
+    let max_deploys = config.get_max_deploys_per_batch();
+    if deploy_count > max_deploys {
+        return Err(invalid_argument_error(format!(
+            "request has {} deploys, more than the maximum of {}",
+            deploy_count, max_deploys
+        )));
+    }
+    Ok(())
+}
+
+pub fn validation_problem<M: ToString>(
+    field: &str,
+    error_code: ipc::ErrorCode,
+    message: M,
+) -> ipc::ValidateDeployResponse_Problem {
+    let mut problem = ipc::ValidateDeployResponse_Problem::new();
+    problem.set_field(field.to_string());
+    problem.set_message(message.to_string());
+    problem.set_error_code(error_code);
+    problem
+}
+
+/// Prepares the payment code of `deploy`, if it has any, and returns the error if it is too large
+/// or not a well-formed wasm module.  Payment code is not run yet, so it is not otherwise
+/// rejected.
+pub fn check_payment_code(
+    preprocessor: &WasmiPreprocessor,
+    deploy: &ipc::Deploy,
+) -> Result<(), PreprocessingError> {
+    let payment_code = &deploy.get_payment().code;
+    if payment_code.is_empty() {
+        return Ok(());
+    }
+    match preprocessor.preprocess(payment_code) {
+        Err(error @ PreprocessingError::ModuleTooLarge { .. })
+        | Err(error @ PreprocessingError::DeserializeError { .. }) => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_deploys<A, H, E, P>(
+    engine_state: &EngineState<H>,
+    executor: &E,
+    preprocessor: &P,
+    prestate_hash: Blake2bHash,
+    blocktime: BlockTime,
+    deploys: &[&ipc::Deploy],
+    request_gas_price: u64,
+    protocol_version: &state::ProtocolVersion,
+    correlation_id: CorrelationId,
+    deadline: Option<Instant>,
+) -> Result<Vec<ExecutionResult>, RootNotFound>
+where
+    H: History,
+    E: Executor<A>,
+    P: Preprocessor<A>,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error>,
+{
+    // We want to treat RootNotFound error differently b/c it should short-circuit
+    // the execution of ALL deploys within the block. This is because all of them share
+    // the same prestate and all of them would fail.
+    // Iterator (Result<_, _> + collect()) will short circuit the execution
+    // when run_deploy returns Err.
+    deploys
+        .iter()
+        .map(|deploy| {
+            if is_past_deadline(deadline) {
+                // the client has given up on the results, so the rest are not run
+                return Ok(ExecutionResult::precondition_failure(
+                    ExecutionError::Timeout.into(),
+                ));
+            }
+            run_deploy(
+                engine_state,
+                executor,
+                preprocessor,
+                prestate_hash,
+                blocktime,
+                deploy,
+                deploy_gas_price(request_gas_price, deploy),
+                protocol_version,
+                correlation_id,
+            )
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_deploy<A, H, E, P>(
+    engine_state: &EngineState<H>,
+    executor: &E,
+    preprocessor: &P,
+    prestate_hash: Blake2bHash,
+    blocktime: BlockTime,
+    deploy: &ipc::Deploy,
+    gas_price: u64,
+    protocol_version: &state::ProtocolVersion,
+    correlation_id: CorrelationId,
+) -> Result<ExecutionResult, RootNotFound>
+where
+    H: History,
+    E: Executor<A>,
+    P: Preprocessor<A>,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error>,
+{
+    let gas_limit = deploy_gas_limit(engine_state.config(), deploy, gas_price)
+        .expect("gas limits are checked before deploys are run");
+    run_deploy_with_gas_limit(
+        engine_state,
+        executor,
+        preprocessor,
+        prestate_hash,
+        blocktime,
+        deploy,
+        gas_limit,
+        protocol_version,
+        correlation_id,
+    )
+}
+
+/// Runs `deploy` with `gas_limit` in place of the limit it pays for.
+#[allow(clippy::too_many_arguments)]
+pub fn run_deploy_with_gas_limit<A, H, E, P>(
+    engine_state: &EngineState<H>,
+    executor: &E,
+    preprocessor: &P,
+    prestate_hash: Blake2bHash,
+    blocktime: BlockTime,
+    deploy: &ipc::Deploy,
+    gas_limit: u64,
+    protocol_version: &state::ProtocolVersion,
+    correlation_id: CorrelationId,
+) -> Result<ExecutionResult, RootNotFound>
+where
+    H: History,
+    E: Executor<A>,
+    P: Preprocessor<A>,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error>,
+{
+    let session_contract = deploy.get_session();
+    let module_bytes = &session_contract.code;
+    let args = &session_contract.args;
+    let address = match deploy_account_key(deploy) {
+        Ok(key) => key,
+        Err(error) => return Ok(ExecutionResult::precondition_failure(error)),
+    };
+
+    let authorized_keys = match deploy_authorized_keys(deploy) {
+        Ok(keys) => keys,
+        Err(error) => return Ok(ExecutionResult::precondition_failure(error)),
+    };
+
+    let nonce = deploy.nonce;
+    let protocol_version = protocol_version.value;
+    engine_state.run_deploy(
+        module_bytes,
+        args,
+        address,
+        authorized_keys,
+        blocktime,
+        nonce,
+        prestate_hash,
+        gas_limit,
+        protocol_version,
+        correlation_id,
+        executor,
+        preprocessor,
+    )
+}
+
+/// Runs `deploy` from `estimate_gas_request` with a series of gas limits, discarding the effects of
+/// each run, and returns the smallest limit it succeeded with.  Fails with a `NotFound` status if
+/// the parent state is not in global state, or with an `InvalidArgument` status if the deploy
+/// cannot be run.
+pub fn estimate_deploy_gas<H>(
+    engine_state: &EngineState<H>,
+    estimate_gas_request: &ipc::EstimateGasRequest,
+    correlation_id: CorrelationId,
+    deadline: Option<Instant>,
+) -> Result<ipc::EstimateGasResponse, grpc::Error>
+where
+    H: History,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error>,
+{
+    let config = engine_state.config();
+    let prestate_hash = parse_state_hash(estimate_gas_request.get_parent_state_hash())
+        .map_err(invalid_argument_error)?;
+    let blocktime = BlockTime(estimate_gas_request.get_block_time());
+    let deploy = estimate_gas_request.get_deploy();
+    let protocol_version = estimate_gas_request.get_protocol_version();
+
+    let max_gas_limit = match config.get_max_gas_limit() {
+        Some(max_gas_limit) => max_gas_limit,
+        None => deploy_gas_limit(config, deploy, deploy.gas_price)
+            .map_err(|error| invalid_argument_error(error.to_string()))?,
+    };
+    let preprocessor = get_preprocessor(config, protocol_version.value).ok_or_else(|| {
+        invalid_argument_error(format!(
+            "unsupported protocol version {}",
+            protocol_version.value
+        ))
+    })?;
+    check_payment_code(&preprocessor, deploy)
+        .map_err(|error| malformed_payment_error(0, &error))?;
+    // with the wasm cache on, the trials after the first skip preparing the module
+    let preprocessor = engine_state.caching_preprocessor(preprocessor, protocol_version.value);
+    let executor = WasmiExecutor::new()
+        .with_timeout(config.get_execution_timeout())
+        .with_deadline(deadline)
+        .with_max_memory_pages(config.get_max_memory_pages());
+
+    let trial = |gas_limit: u64| -> Result<ExecutionResult, grpc::Error> {
+        let execution_result = run_deploy_with_gas_limit(
+            engine_state,
+            &executor,
+            &preprocessor,
+            prestate_hash,
+            blocktime,
+            deploy,
+            gas_limit,
+            protocol_version,
+            correlation_id,
+        )
+        .map_err(|RootNotFound(root)| root_not_found_error(root))?;
+        if is_readers_full_failure(&execution_result) {
+            return Err(readers_full_error());
+        }
+        if let Some(error) = malformed_module(&execution_result) {
+            return Err(malformed_module_error(0, error));
+        }
+        Ok(execution_result)
+    };
+    let gas_search = search_gas_limit(max_gas_limit, config.get_estimate_gas_max_trials(), trial)?;
+
+    if is_past_deadline(deadline) {
+        return Err(deadline_exceeded_error());
+    }
+
+    let mut estimate_gas_response = ipc::EstimateGasResponse::new();
+    match gas_search {
+        GasSearch::Found { gas_limit, trials } => {
+            let mut estimate = ipc::EstimateGasResponse_Estimate::new();
+            estimate.set_min_gas_limit(gas_limit);
+            estimate.set_gas_limit(with_margin(gas_limit, config.get_max_gas_limit()));
+            estimate.set_trials(trials);
+            estimate_gas_response.set_success(estimate);
+        }
+        GasSearch::Failed(execution_result) => {
+            estimate_gas_response.set_failed_deploy(execution_result.into());
+        }
+    }
+    Ok(estimate_gas_response)
+}
+
+/// Returns the result of an earlier run of `deploy` against `prestate_hash`, if it has a deploy
+/// hash and the result is still cached.
+pub fn cached_deploy_result<H: History>(
+    engine_state: &EngineState<H>,
+    prestate_hash: Blake2bHash,
+    deploy: &ipc::Deploy,
+) -> Option<ipc::DeployResult> {
+    if deploy.get_deploy_hash().is_empty() {
+        return None;
+    }
+    let bytes = engine_state.get_deploy_result(deploy.get_deploy_hash(), prestate_hash)?;
+    let deploy_result = protobuf::parse_from_bytes::<ipc::DeployResult>(&bytes).ok()?;
+    log_debug!("returning the cached result of a repeated deploy");
+    Some(deploy_result)
+}
+
+/// Caches the result of running `deploy` against `prestate_hash`, if it has a deploy hash.
+pub fn cache_deploy_result<H: History>(
+    engine_state: &EngineState<H>,
+    prestate_hash: Blake2bHash,
+    deploy: &ipc::Deploy,
+    deploy_result: &ipc::DeployResult,
+) {
+    if deploy.get_deploy_hash().is_empty() {
+        return;
+    }
+    if let Ok(bytes) = protobuf::Message::write_to_bytes(deploy_result) {
+        engine_state.put_deploy_result(deploy.get_deploy_hash(), prestate_hash, bytes);
+    }
+}
+
+/// Returns the hash of everything which goes into running `deploy` as part of `exec_request`,
+/// apart from the prestate and the server's configuration, or `None` if the execution cache is
+/// off.  The deploy hash is left out, so that the same deploy sent by different clients under
+/// different hashes is recognised.
+pub fn deploy_content_hash<H: History>(
+    engine_state: &EngineState<H>,
+    exec_request: &ipc::ExecRequest,
+    gas_profiling: bool,
+    deploy: &ipc::Deploy,
+) -> Option<Blake2bHash> {
+    if engine_state.config().get_exec_cache_size() == 0 {
+        return None;
+    }
+    let mut bytes = if deploy.get_deploy_hash().is_empty() {
+        protobuf::Message::write_to_bytes(deploy).ok()?
+    } else {
+        let mut deploy = deploy.clone();
+        deploy.clear_deploy_hash();
+        protobuf::Message::write_to_bytes(&deploy).ok()?
+    };
+    // the deploy's own gas price is part of it, but the request's may replace it
+    bytes.extend_from_slice(&deploy_gas_price(exec_request.get_gas_price(), deploy).to_le_bytes());
+    bytes.extend_from_slice(&exec_request.get_block_time().to_le_bytes());
+    bytes.extend_from_slice(&exec_request.get_protocol_version().value.to_le_bytes());
+    bytes.push(gas_profiling as u8);
+    bytes.push(exec_request.get_execution_tracing() as u8);
+    Some(Blake2bHash::new(&bytes))
+}
+
+/// Returns the result stored for a deploy with `content_hash` run against `prestate_hash`, if
+/// there is one.
+pub fn cached_exec_result<H: History>(
+    engine_state: &EngineState<H>,
+    prestate_hash: Blake2bHash,
+    content_hash: Option<Blake2bHash>,
+) -> Option<ipc::DeployResult> {
+    let bytes = engine_state.get_exec_result(content_hash?, prestate_hash)?;
+    let deploy_result = protobuf::parse_from_bytes::<ipc::DeployResult>(&bytes).ok()?;
+    log_debug!("returning the cached result of a deploy with the same content");
+    Some(deploy_result)
+}
+
+/// Caches the result of running a deploy with `content_hash` against `prestate_hash`.
+pub fn cache_exec_result<H: History>(
+    engine_state: &EngineState<H>,
+    prestate_hash: Blake2bHash,
+    content_hash: Blake2bHash,
+    deploy_result: &ipc::DeployResult,
+) {
+    if let Ok(bytes) = protobuf::Message::write_to_bytes(deploy_result) {
+        engine_state.put_exec_result(content_hash, prestate_hash, bytes);
+    }
+}
+
+/// Returns `false` if running the same deploy against the same state again might give another
+/// result, as it failed on running out of time or on reading global state.
+pub fn is_reusable(execution_result: &ExecutionResult) -> bool {
+    match execution_result {
+        ExecutionResult::Failure {
+            error: EngineError::ExecError(ExecutionError::Timeout),
+            ..
+        }
+        | ExecutionResult::Failure {
+            error: EngineError::ExecError(ExecutionError::Storage(_)),
+            ..
+        }
+        | ExecutionResult::Failure {
+            error: EngineError::StorageError(_),
+            ..
+        } => false,
+        _ => true,
+    }
+}
@@ -0,0 +1,441 @@
+//! Lets the server finish the requests it is handling before it stops.
+//!
+//! Once a [`Drain`] is started, [`DrainGate`] rejects new `exec`, `exec_stream`, `commit`,
+//! `commit_batch` and `run_genesis` requests with an `Unavailable` status, so that a load
+//! balancer retries them on another server, while requests which were already admitted run to
+//! completion.  Other requests are still served.  [`Drain::wait`] returns once no request is in
+//! flight.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::hold_until_complete;
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+
+const DRAINING_MESSAGE: &str = "server is draining before shutdown";
+
+/// How often [`Drain::wait`] checks whether requests are still in flight.
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Whether the server is draining, and the number of requests in flight.  Clones share both, so
+/// the server can hand one to the [`DrainGate`] and keep another to start the drain.
+#[derive(Debug, Clone, Default)]
+pub struct Drain {
+    draining: Arc<AtomicBool>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drain {
+    pub fn new() -> Drain {
+        Default::default()
+    }
+
+    /// Starts rejecting requests which are not served while draining.
+    pub fn start(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no request is in flight, for at most `timeout`.  Returns `false` if requests
+    /// were still in flight when it ran out.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while self.in_flight() > 0 {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(WAIT_INTERVAL);
+        }
+        true
+    }
+
+    /// Counts a request as in flight for as long as the returned guard is alive.
+    pub fn track(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight {
+            in_flight: Arc::clone(&self.in_flight),
+        }
+    }
+
+    /// Like [`Drain::track`], but returns `None` once draining, for requests which are rejected
+    /// then.
+    pub fn admit(&self) -> Option<InFlight> {
+        // counted before checking, so that a drain started in between waits for the request
+        let in_flight = self.track();
+        if self.is_draining() {
+            return None;
+        }
+        Some(in_flight)
+    }
+}
+
+/// Counts a request as in flight until dropped.
+#[derive(Debug)]
+pub struct InFlight {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn draining_error() -> grpc::Error {
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::Unavailable as i32,
+        grpc_message: DRAINING_MESSAGE.to_string(),
+    })
+}
+
+/// Wraps an engine service so that its requests are counted by `drain`, and execution and
+/// commits are rejected once it is started.
+pub struct DrainGate<S> {
+    service: S,
+    drain: Drain,
+}
+
+impl<S> DrainGate<S> {
+    pub fn new(service: S, drain: Drain) -> Self {
+        DrainGate { service, drain }
+    }
+}
+
+impl<S: ExecutionEngineService> ExecutionEngineService for DrainGate<S> {
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        let in_flight = match self.drain.admit() {
+            Some(in_flight) => in_flight,
+            None => return grpc::SingleResponse::err(draining_error()),
+        };
+        hold_until_complete(self.service.exec(request_options, exec_request), in_flight)
+    }
+
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        let in_flight = match self.drain.admit() {
+            Some(in_flight) => in_flight,
+            None => return grpc::StreamingResponse::err(draining_error()),
+        };
+        // the deploys run after the handler returns, so the request is counted until the
+        // stream, which owns the guard, is done
+        self.service
+            .exec_stream(request_options, exec_request)
+            .map_items(move |item| {
+                let _in_flight = &in_flight;
+                item
+            })
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        let in_flight = match self.drain.admit() {
+            Some(in_flight) => in_flight,
+            None => return grpc::SingleResponse::err(draining_error()),
+        };
+        hold_until_complete(
+            self.service.commit(request_options, commit_request),
+            in_flight,
+        )
+    }
+
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        let in_flight = match self.drain.admit() {
+            Some(in_flight) => in_flight,
+            None => return grpc::SingleResponse::err(draining_error()),
+        };
+        hold_until_complete(
+            self.service
+                .commit_batch(request_options, commit_batch_request),
+            in_flight,
+        )
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service.query(request_options, query_request),
+            in_flight,
+        )
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service.validate(request_options, validate_request),
+            in_flight,
+        )
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        let in_flight = match self.drain.admit() {
+            Some(in_flight) => in_flight,
+            None => return grpc::SingleResponse::err(draining_error()),
+        };
+        hold_until_complete(
+            self.service.run_genesis(request_options, genesis_request),
+            in_flight,
+        )
+    }
+
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .get_store_stats(request_options, store_stats_request),
+            in_flight,
+        )
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .list_state_roots(request_options, list_state_roots_request),
+            in_flight,
+        )
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .backup_store(request_options, backup_store_request),
+            in_flight,
+        )
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .validate_deploy(request_options, validate_deploy_request),
+            in_flight,
+        )
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service.state_diff(request_options, state_diff_request),
+            in_flight,
+        )
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .flush_store(request_options, flush_store_request),
+            in_flight,
+        )
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .get_engine_version(request_options, get_engine_version_request),
+            in_flight,
+        )
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .get_genesis_hash(request_options, get_genesis_hash_request),
+            in_flight,
+        )
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .verify_store(request_options, verify_store_request),
+            in_flight,
+        )
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .prune_state(request_options, prune_state_request),
+            in_flight,
+        )
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .get_capabilities(request_options, get_capabilities_request),
+            in_flight,
+        )
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .estimate_gas(request_options, estimate_gas_request),
+            in_flight,
+        )
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        let in_flight = self.drain.track();
+        // the pairs are sent after the handler returns, so the request is counted until the
+        // stream, which owns the guard, is done
+        self.service
+            .export_state(request_options, export_state_request)
+            .map_items(move |item| {
+                let _in_flight = &in_flight;
+                item
+            })
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        let in_flight = self.drain.track();
+        hold_until_complete(
+            self.service
+                .get_empty_state_root(request_options, get_empty_state_root_request),
+            in_flight,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_count_requests_until_dropped() {
+        let drain = Drain::new();
+        let first = drain.track();
+        let second = drain.admit().expect("should admit before draining");
+        assert_eq!(drain.in_flight(), 2);
+        drop(first);
+        drop(second);
+        assert_eq!(drain.in_flight(), 0);
+    }
+
+    #[test]
+    fn should_reject_once_draining() {
+        let drain = Drain::new();
+        drain.start();
+        assert!(drain.admit().is_none());
+        assert_eq!(drain.in_flight(), 0);
+        let _tracked = drain.track();
+        assert_eq!(drain.in_flight(), 1);
+    }
+
+    #[test]
+    fn should_wait_for_requests_in_flight() {
+        let drain = Drain::new();
+        let in_flight = drain.track();
+        drain.start();
+        assert!(!drain.wait(Duration::from_millis(50)));
+
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(in_flight);
+        });
+        assert!(drain.wait(Duration::from_secs(10)));
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Errors returned by the engine server and the failures which map onto them.
+
+use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
+use engine_core::engine_state::execution_result::ExecutionResult;
+use engine_shared::newtypes::Blake2bHash;
+use engine_wasm_prep::PreprocessingError;
+
+use super::ipc;
+
+const READ_ONLY_MESSAGE: &str = "global state is read-only";
+const DEADLINE_EXCEEDED_MESSAGE: &str =
+    "request deadline exceeded before its deploys finished running; effects not committed";
+
+/// A failure to report in a response, with its category.
+pub type Failure = (ipc::ErrorCode, String);
+
+/// Reports a malformed request.
+pub fn invalid_argument_failure<M: ToString>(message: M) -> Failure {
+    (ipc::ErrorCode::INVALID_ARGUMENT, message.to_string())
+}
+
+/// Reports a storage error, or a request the store does not support.
+pub fn internal_failure<M: ToString>(message: M) -> Failure {
+    (ipc::ErrorCode::INTERNAL, message.to_string())
+}
+
+/// Rejects a request that would mutate global state with a `FailedPrecondition` status.
+pub fn read_only_error() -> grpc::Error {
+    log_warning!(READ_ONLY_MESSAGE);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::FailedPrecondition as i32,
+        grpc_message: READ_ONLY_MESSAGE.to_string(),
+    })
+}
+
+/// Fails a commit which found no room left for global state with a `ResourceExhausted` status.
+/// Nothing was written, so the client can retry once space has been freed.
+pub fn out_of_space_error(error: &engine_storage::error::Error) -> grpc::Error {
+    let message = error.to_string();
+    log_error!(&message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::ResourceExhausted as i32,
+        grpc_message: message,
+    })
+}
+
+/// Fails a request for a state root which is not in global state with a `NotFound` status naming
+/// the root.
+pub fn root_not_found_error(root: Blake2bHash) -> grpc::Error {
+    let message = engine_storage::error::Error::RootNotFound(root).to_string();
+    log_warning!(&message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::NotFound as i32,
+        grpc_message: message,
+    })
+}
+
+/// Returns `true` if `execution_result` is a failure to read global state because every LMDB
+/// reader slot was taken, rather than a result of the deploy itself.
+pub fn is_readers_full_failure(execution_result: &ExecutionResult) -> bool {
+    match execution_result {
+        ExecutionResult::Failure { error, .. } => error.is_readers_full(),
+        ExecutionResult::Success { .. } => false,
+    }
+}
+
+/// Fails a request which could not read global state because every LMDB reader slot was taken
+/// with an `Unavailable` status.  Slots are freed as other reads finish, so the client can retry.
+pub fn readers_full_error() -> grpc::Error {
+    let message = engine_storage::error::Error::ReadersFull.to_string();
+    log_warning!(&message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::Unavailable as i32,
+        grpc_message: message,
+    })
+}
+
+/// Returns why the session code of a deploy could not be parsed, if that is what it failed with.
+pub fn malformed_module(execution_result: &ExecutionResult) -> Option<&PreprocessingError> {
+    match execution_result {
+        ExecutionResult::Failure {
+            error: EngineError::WasmPreprocessingError(error),
+            ..
+        } => match error {
+            PreprocessingError::DeserializeError { .. } => Some(error),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn is_malformed_module(execution_result: &ExecutionResult) -> bool {
+    malformed_module(execution_result).is_some()
+}
+
+/// Fails an exec request with an `InvalidArgument` status if the session code of one of its
+/// deploys is not a well-formed wasm module.  The message names the deploy and says where and why
+/// parsing failed.
+pub fn malformed_module_error(deploy_index: usize, error: &PreprocessingError) -> grpc::Error {
+    invalid_argument_error(format!("deploy {}: {}", deploy_index, error))
+}
+
+/// Fails an exec request like [`malformed_module_error`], but for the payment code of a deploy.
+pub fn malformed_payment_error(deploy_index: usize, error: &PreprocessingError) -> grpc::Error {
+    invalid_argument_error(format!("deploy {}: payment code: {}", deploy_index, error))
+}
+
+/// Fails a request whose deadline passed while its deploys ran with a `DeadlineExceeded` status.
+/// Nothing was committed.
+pub fn deadline_exceeded_error() -> grpc::Error {
+    log_warning!(DEADLINE_EXCEEDED_MESSAGE);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::DeadlineExceeded as i32,
+        grpc_message: DEADLINE_EXCEEDED_MESSAGE.to_string(),
+    })
+}
+
+/// Rejects a malformed request with an `InvalidArgument` status.
+pub fn invalid_argument_error(message: String) -> grpc::Error {
+    log_warning!(&message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::InvalidArgument as i32,
+        grpc_message: message,
+    })
+}
@@ -0,0 +1,155 @@
+//! The search `estimate_gas` makes for the smallest gas limit a deploy succeeds with.
+
+use engine_core::engine_state::execution_result::ExecutionResult;
+
+/// Percentage of the smallest gas limit found which is added to it in the suggested gas limit,
+/// as the state a deploy runs against may have changed by the time it is run.
+pub const GAS_MARGIN_PERCENT: u64 = 10;
+
+/// Outcome of a search for the smallest gas limit a deploy succeeds with.
+#[derive(Debug)]
+pub enum GasSearch {
+    /// The deploy succeeded with `gas_limit`, the smallest limit found in `trials` runs.
+    Found { gas_limit: u64, trials: u32 },
+    /// The deploy failed even with the highest gas limit; the result of that run.
+    Failed(ExecutionResult),
+}
+
+/// Searches for the smallest gas limit, up to `max_gas_limit`, with which `trial` succeeds,
+/// calling it at most `max_trials` times, but always at least once.
+///
+/// `trial` runs the deploy with the given gas limit.  It is first run with `max_gas_limit`.  As a
+/// deploy never needs a higher limit than the gas it used, the search then narrows down between
+/// that and 0, trying one less than the gas used first, as that is usually the most a deploy
+/// fails with.  A failure with a lower limit is taken to mean the limit is too low, whatever the
+/// error.  If the trials run out, the smallest limit found so far is returned.
+pub fn search_gas_limit<F, E>(
+    max_gas_limit: u64,
+    max_trials: u32,
+    mut trial: F,
+) -> Result<GasSearch, E>
+where
+    F: FnMut(u64) -> Result<ExecutionResult, E>,
+{
+    let mut trials = 1;
+    let mut succeeded = match trial(max_gas_limit)? {
+        ExecutionResult::Success { cost, .. } => cost.min(max_gas_limit),
+        failure => return Ok(GasSearch::Failed(failure)),
+    };
+    // a deploy runs out of gas with a limit of 0, unless it costs nothing
+    let mut failed = 0;
+    let mut gas_limit = succeeded.saturating_sub(1);
+    while failed + 1 < succeeded && trials < max_trials {
+        trials += 1;
+        match trial(gas_limit)? {
+            ExecutionResult::Success { cost, .. } => succeeded = cost.min(gas_limit),
+            ExecutionResult::Failure { .. } => failed = gas_limit,
+        }
+        gas_limit = failed + succeeded.saturating_sub(failed) / 2;
+    }
+    Ok(GasSearch::Found {
+        gas_limit: succeeded,
+        trials,
+    })
+}
+
+/// Adds [`GAS_MARGIN_PERCENT`] to `gas_limit`, without going over `max_gas_limit` if there is one.
+pub fn with_margin(gas_limit: u64, max_gas_limit: Option<u64>) -> u64 {
+    let margin = u128::from(gas_limit) * u128::from(GAS_MARGIN_PERCENT) / 100;
+    let gas_limit = gas_limit.saturating_add(margin as u64);
+    match max_gas_limit {
+        Some(max_gas_limit) => gas_limit.min(max_gas_limit),
+        None => gas_limit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_core::execution::Error as ExecutionError;
+
+    use super::*;
+
+    /// Runs a deploy which succeeds with a limit of at least `needed`, reporting `cost` as the gas
+    /// it used, and counts the runs in `runs`.
+    fn deploy(
+        needed: u64,
+        cost: u64,
+        runs: &mut u32,
+    ) -> impl FnMut(u64) -> Result<ExecutionResult, ()> + '_ {
+        move |gas_limit| {
+            *runs += 1;
+            if gas_limit >= needed {
+                Ok(ExecutionResult::Success {
+                    effect: Default::default(),
+                    cost,
+                    gas_profile: None,
+                    trace: None,
+                    stats: Default::default(),
+                })
+            } else {
+                Ok(ExecutionResult::Failure {
+                    error: ExecutionError::OutOfGas {
+                        gas_limit,
+                        gas_used: gas_limit,
+                    }
+                    .into(),
+                    effect: Default::default(),
+                    cost: gas_limit,
+                    gas_profile: None,
+                    trace: None,
+                    stats: Default::default(),
+                })
+            }
+        }
+    }
+
+    fn found(gas_search: GasSearch) -> (u64, u32) {
+        match gas_search {
+            GasSearch::Found { gas_limit, trials } => (gas_limit, trials),
+            GasSearch::Failed(result) => panic!("search failed with {:?}", result),
+        }
+    }
+
+    #[test]
+    fn should_find_the_gas_used_in_two_trials() {
+        let mut runs = 0;
+        let gas_search = search_gas_limit(1_000_000, 16, deploy(1234, 1234, &mut runs)).unwrap();
+        assert_eq!(found(gas_search), (1234, 2));
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn should_search_below_the_gas_used_if_it_is_more_than_needed() {
+        let mut runs = 0;
+        let gas_search = search_gas_limit(1_000_000, 64, deploy(700, 1000, &mut runs)).unwrap();
+        assert_eq!(found(gas_search).0, 700);
+    }
+
+    #[test]
+    fn should_stop_after_max_trials() {
+        let mut runs = 0;
+        let gas_search = search_gas_limit(1_000_000, 4, deploy(1, 1_000_000, &mut runs)).unwrap();
+        let (gas_limit, trials) = found(gas_search);
+        assert_eq!(trials, 4);
+        assert_eq!(runs, 4);
+        // the smallest limit which succeeded in the trials made
+        assert!(gas_limit >= 1 && gas_limit < 1_000_000);
+    }
+
+    #[test]
+    fn should_return_failure_with_the_highest_gas_limit() {
+        let mut runs = 0;
+        match search_gas_limit(1000, 16, deploy(2000, 2000, &mut runs)).unwrap() {
+            GasSearch::Failed(ExecutionResult::Failure { cost, .. }) => assert_eq!(cost, 1000),
+            other => panic!("search should have failed, got {:?}", other),
+        }
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn should_add_margin_up_to_the_maximum() {
+        assert_eq!(with_margin(1000, None), 1100);
+        assert_eq!(with_margin(1000, Some(1050)), 1050);
+        assert_eq!(with_margin(u64::max_value(), None), u64::max_value());
+    }
+}
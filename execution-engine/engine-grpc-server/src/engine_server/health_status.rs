@@ -0,0 +1,123 @@
+//! The `grpc.health.v1.Health` service, which lets load balancers and orchestrators probe
+//! whether the server is ready for requests.
+//!
+//! See <https://github.com/grpc/grpc/blob/master/doc/health-checking.md>.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::health::{HealthCheckRequest, HealthCheckResponse, HealthCheckResponse_ServingStatus};
+use super::health_grpc::Health;
+
+/// Name under which the status of the engine service can be checked.  The empty name checks
+/// the server as a whole; both report the same status.
+pub const ENGINE_SERVICE_NAME: &str = "io.casperlabs.ipc.ExecutionEngineService";
+
+const UNKNOWN_SERVICE_MESSAGE: &str = "unknown service";
+
+/// Serving status reported by the health service.  Clones share the status, so the server
+/// can hand one to the service and keep another to update.
+///
+/// Starts out as `NOT_SERVING`.
+#[derive(Debug, Clone, Default)]
+pub struct HealthStatus {
+    serving: Arc<AtomicBool>,
+}
+
+impl HealthStatus {
+    pub fn new() -> HealthStatus {
+        Default::default()
+    }
+
+    pub fn set_serving(&self, serving: bool) {
+        self.serving.store(serving, Ordering::SeqCst);
+    }
+
+    pub fn is_serving(&self) -> bool {
+        self.serving.load(Ordering::SeqCst)
+    }
+
+    fn serving_status(&self) -> HealthCheckResponse_ServingStatus {
+        if self.is_serving() {
+            HealthCheckResponse_ServingStatus::SERVING
+        } else {
+            HealthCheckResponse_ServingStatus::NOT_SERVING
+        }
+    }
+}
+
+impl Health for HealthStatus {
+    fn check(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        health_check_request: HealthCheckRequest,
+    ) -> grpc::SingleResponse<HealthCheckResponse> {
+        match health_check_request.get_service() {
+            "" | ENGINE_SERVICE_NAME => {
+                let mut health_check_response = HealthCheckResponse::new();
+                health_check_response.set_status(self.serving_status());
+                grpc::SingleResponse::completed(health_check_response)
+            }
+            _ => grpc::SingleResponse::err(grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+                grpc_status: grpc::GrpcStatus::NotFound as i32,
+                grpc_message: UNKNOWN_SERVICE_MESSAGE.to_string(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grpc::RequestOptions;
+
+    use super::*;
+
+    fn check(
+        health_status: &HealthStatus,
+        service: &str,
+    ) -> Result<HealthCheckResponse_ServingStatus, grpc::Error> {
+        let mut health_check_request = HealthCheckRequest::new();
+        health_check_request.set_service(service.to_string());
+        health_status
+            .check(RequestOptions::new(), health_check_request)
+            .wait_drop_metadata()
+            .map(|health_check_response| health_check_response.get_status())
+    }
+
+    #[test]
+    fn should_report_serving_status() {
+        let health_status = HealthStatus::new();
+        let service_status = health_status.clone();
+
+        for service in &["", ENGINE_SERVICE_NAME] {
+            assert_eq!(
+                check(&service_status, service).unwrap(),
+                HealthCheckResponse_ServingStatus::NOT_SERVING
+            );
+        }
+
+        health_status.set_serving(true);
+        for service in &["", ENGINE_SERVICE_NAME] {
+            assert_eq!(
+                check(&service_status, service).unwrap(),
+                HealthCheckResponse_ServingStatus::SERVING
+            );
+        }
+
+        health_status.set_serving(false);
+        assert_eq!(
+            check(&service_status, "").unwrap(),
+            HealthCheckResponse_ServingStatus::NOT_SERVING
+        );
+    }
+
+    #[test]
+    fn should_fail_for_unknown_service() {
+        match check(&HealthStatus::new(), "unknown.Service") {
+            Err(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(error.grpc_status, grpc::GrpcStatus::NotFound as i32)
+            }
+            other => panic!("expected a NotFound status, got {:?}", other),
+        }
+    }
+}
@@ -12,16 +12,15 @@ use contract_ffi::value::account::{
 use contract_ffi::value::U512;
 use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
 use engine_core::engine_state::execution_effect::ExecutionEffect;
-use engine_core::engine_state::execution_result::ExecutionResult;
+use engine_core::engine_state::execution_result::{self, ExecutionResult};
 use engine_core::engine_state::op::Op;
-use engine_core::execution::Error as ExecutionError;
+use engine_core::execution::{Error as ExecutionError, HostCall};
 use engine_core::utils;
 use engine_server::{ipc, state};
-use engine_shared::logging;
 use engine_shared::logging::log_level;
 use engine_shared::newtypes::Blake2bHash;
 use engine_shared::transform::{self, TypeMismatch};
-use engine_storage::global_state::{CommitResult, History};
+use engine_storage::global_state::{CommitResult, KeyDiff, StoreStats};
 
 mod uint;
 
@@ -677,12 +676,59 @@ impl From<TypeMismatch> for ipc::TypeMismatch {
     }
 }
 
+impl From<StoreStats> for ipc::StoreStats {
+    fn from(stats: StoreStats) -> ipc::StoreStats {
+        let mut store_stats = ipc::StoreStats::new();
+        store_stats.set_page_size(stats.page_size);
+        store_stats.set_entries(stats.entries);
+        store_stats.set_used_pages(stats.used_pages);
+        store_stats.set_map_size(stats.map_size);
+        store_stats
+    }
+}
+
+impl From<KeyDiff<contract_ffi::key::Key, contract_ffi::value::Value>>
+    for ipc::StateDiffResponse_KeyDiff
+{
+    fn from(key_diff: KeyDiff<contract_ffi::key::Key, contract_ffi::value::Value>) -> Self {
+        let KeyDiff { key, before, after } = key_diff;
+        let mut ipc_key_diff = ipc::StateDiffResponse_KeyDiff::new();
+        ipc_key_diff.set_key((&key).into());
+        if let Some(value) = before {
+            ipc_key_diff.set_before(value.into());
+        }
+        if let Some(value) = after {
+            ipc_key_diff.set_after(value.into());
+        }
+        ipc_key_diff
+    }
+}
+
+impl From<HostCall> for ipc::HostCall {
+    fn from(host_call: HostCall) -> ipc::HostCall {
+        let mut ipc_host_call = ipc::HostCall::new();
+        ipc_host_call.set_function(host_call.function.to_string());
+        ipc_host_call.set_args(host_call.args);
+        ipc_host_call.set_results(host_call.results);
+        ipc_host_call.set_trapped(host_call.trapped);
+        ipc_host_call
+    }
+}
+
 impl From<ExecutionResult> for ipc::DeployResult {
     fn from(er: ExecutionResult) -> ipc::DeployResult {
-        match er {
+        let gas_profile = er.gas_profile().cloned();
+        let trace = er.trace().map(<[_]>::to_vec);
+        let stats = *er.stats();
+        let error_code = match er {
+            ExecutionResult::Success { .. } => ipc::ErrorCode::NO_ERROR,
+            ExecutionResult::Failure { ref error, .. } => error_code(error),
+        };
+        let mut deploy_result = match er {
             ExecutionResult::Success {
                 effect: effects,
                 cost,
+                ..
             } => {
                 let mut ipc_ee = effects.into();
                 let mut deploy_result = ipc::DeployResult::new();
@@ -696,6 +742,7 @@ impl From<ExecutionResult> for ipc::DeployResult {
                 error: err,
                 effect,
                 cost,
+                ..
             } => {
                 match err {
                     // TODO(mateusz.gorski): Fix error model for the storage errors.
@@ -716,12 +763,19 @@ impl From<ExecutionResult> for ipc::DeployResult {
                     error @ EngineError::AuthorizationError => {
                         precondition_failure(error.to_string())
                     }
+                    error @ EngineError::Overflow { .. } => precondition_failure(error.to_string()),
                     EngineError::ExecError(exec_error) => match exec_error {
-                        ExecutionError::GasLimit => {
+                        ExecutionError::OutOfGas {
+                            gas_limit,
+                            gas_used,
+                        } => {
                             let mut deploy_result = ipc::DeployResult::new();
                             let deploy_error = {
+                                let mut gas_error = ipc::DeployError_OutOfGasError::new();
+                                gas_error.set_gas_limit(gas_limit);
+                                gas_error.set_gas_used(gas_used);
                                 let mut tmp = ipc::DeployError::new();
-                                tmp.set_gas_error(ipc::DeployError_OutOfGasError::new());
+                                tmp.set_gas_error(gas_error);
                                 tmp
                             };
                             let exec_result = {
@@ -763,6 +817,25 @@ impl From<ExecutionResult> for ipc::DeployResult {
                             let error_msg = format!("Exit code: {}", status);
                             execution_error(error_msg, cost, effect)
                         }
+                        ExecutionError::MemoryLimitExceeded {
+                            requested_pages,
+                            max_pages,
+                        } => {
+                            let error_msg = format!(
+                                "Memory limit exceeded: grew to {} pages, the maximum is {}",
+                                requested_pages, max_pages
+                            );
+                            execution_error(error_msg, cost, effect)
+                        }
+                        ExecutionError::HostFunctionMisuse { function, mismatch } => {
+                            let error_msg =
+                                format!("Host function {} misused: {}", function, mismatch);
+                            execution_error(error_msg, cost, effect)
+                        }
+                        // A timed out deploy has no effects and may succeed when retried.
+                        ExecutionError::Timeout => precondition_failure(
+                            EngineError::ExecError(ExecutionError::Timeout).to_string(),
+                        ),
                         ExecutionError::Interpreter(error) => {
                             // If the error happens during contract execution it's mapped to HostError
                             // and wrapped in Interpreter error, so we may end up with InterpreterError(HostError(InterpreterError))).
@@ -802,28 +875,127 @@ impl From<ExecutionResult> for ipc::DeployResult {
                     },
                 }
             }
+        };
+        // Charged at a gas price of 1 until `charge_gas_price` is called.
+        if deploy_result.has_execution_result() {
+            let gas_used = deploy_result.get_execution_result().get_cost();
+            let ipc_execution_result = deploy_result.mut_execution_result();
+            ipc_execution_result.set_gas_used(gas_used);
+            ipc_execution_result.set_duration_micros(
+                stats.duration.as_micros().min(u128::from(u64::max_value())) as u64,
+            );
+            ipc_execution_result.set_peak_memory_pages(stats.peak_memory_pages);
+            ipc_execution_result.set_trie_reads(stats.trie_reads);
+        }
+        // Precondition failures and invalid nonces are not charged, so have nothing to break down.
+        if let Some(gas_profile) = gas_profile {
+            if deploy_result.has_execution_result() {
+                deploy_result
+                    .mut_execution_result()
+                    .mut_gas_profile()
+                    .extend(
+                        gas_profile
+                            .into_iter()
+                            .map(|(category, gas)| (category.name().to_string(), gas)),
+                    );
+            }
+        }
+        if let Some(trace) = trace {
+            if deploy_result.has_execution_result() {
+                deploy_result
+                    .mut_execution_result()
+                    .set_trace(trace.into_iter().map(Into::into).collect());
+            }
         }
+        deploy_result.set_error_code(error_code);
+        deploy_result
+    }
+}
+
+/// Sets the cost of `deploy_result` to its gas used times `gas_price`, or fails with
+/// [EngineError::Overflow] if that does not fit in a `u64`.  Results without an execution result
+/// were not charged, so are left as they are.
+pub fn charge_gas_price(
+    deploy_result: &mut ipc::DeployResult,
+    gas_price: u64,
+) -> Result<(), EngineError> {
+    if deploy_result.has_execution_result() {
+        let ipc_execution_result = deploy_result.mut_execution_result();
+        let cost = execution_result::cost(ipc_execution_result.get_gas_used(), gas_price)?;
+        ipc_execution_result.set_cost(cost);
     }
+    Ok(())
 }
 
-pub fn grpc_response_from_commit_result<H>(
+/// Returns the category of `error`, to be reported alongside it.
+pub fn error_code(error: &EngineError) -> ipc::ErrorCode {
+    match error {
+        EngineError::InvalidPublicKeyLength { .. } => ipc::ErrorCode::INVALID_ARGUMENT,
+        EngineError::WasmPreprocessingError(_) | EngineError::WasmSerializationError(_) => {
+            ipc::ErrorCode::INVALID_WASM
+        }
+        EngineError::ExecError(error) => execution_error_code(error),
+        EngineError::StorageError(error) => storage_error_code(error),
+        EngineError::AuthorizationError => ipc::ErrorCode::EXECUTION_ERROR,
+        EngineError::Overflow { .. } => ipc::ErrorCode::INVALID_ARGUMENT,
+    }
+}
+
+/// Returns the category of an error raised while running a deploy.
+fn execution_error_code(error: &ExecutionError) -> ipc::ErrorCode {
+    match error {
+        ExecutionError::OutOfGas { .. } => ipc::ErrorCode::OUT_OF_GAS,
+        ExecutionError::Timeout => ipc::ErrorCode::TIMEOUT,
+        ExecutionError::Storage(error) => storage_error_code(error),
+        ExecutionError::KeyNotFound(_)
+        | ExecutionError::AccountNotFound(_)
+        | ExecutionError::URefNotFound(_) => ipc::ErrorCode::STATE_NOT_FOUND,
+        ExecutionError::ParityWasm(_) | ExecutionError::FunctionNotFound(_) => {
+            ipc::ErrorCode::INVALID_WASM
+        }
+        ExecutionError::InvalidNonce { .. } => ipc::ErrorCode::INVALID_ARGUMENT,
+        ExecutionError::Rng(_) => ipc::ErrorCode::INTERNAL,
+        // errors raised by host functions reach the executor wrapped in interpreter errors
+        ExecutionError::Interpreter(error) => match error
+            .as_host_error()
+            .and_then(|host_error| host_error.downcast_ref::<ExecutionError>())
+        {
+            Some(error) => execution_error_code(error),
+            None => ipc::ErrorCode::EXECUTION_ERROR,
+        },
+        _ => ipc::ErrorCode::EXECUTION_ERROR,
+    }
+}
+
+/// Returns the category of a storage error; one caused by a lack of space is `STORAGE_FULL`.
+pub fn storage_error_code(error: &engine_storage::error::Error) -> ipc::ErrorCode {
+    if error.is_out_of_space() {
+        ipc::ErrorCode::STORAGE_FULL
+    } else if error.is_root_not_found() {
+        ipc::ErrorCode::STATE_NOT_FOUND
+    } else {
+        ipc::ErrorCode::INTERNAL
+    }
+}
+
+pub fn grpc_response_from_commit_result<E: std::fmt::Debug>(
     prestate_hash: Blake2bHash,
-    input: Result<CommitResult, H::Error>,
-) -> ipc::CommitResponse
-where
-    H: History,
-    H::Error: Into<EngineError> + std::fmt::Debug,
-{
+    input: Result<CommitResult, E>,
+) -> ipc::CommitResponse {
     match input {
         Ok(CommitResult::RootNotFound) => {
-            logging::log_warning("RootNotFound");
+            log_warning!("RootNotFound");
             let mut root = ipc::RootNotFound::new();
             root.set_hash(prestate_hash.to_vec());
             let mut tmp_res = ipc::CommitResponse::new();
             tmp_res.set_missing_prestate(root);
+            tmp_res.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
             tmp_res
         }
-        Ok(CommitResult::Success(post_state_hash)) => {
+        Ok(CommitResult::Success {
+            state_root: post_state_hash,
+            stats,
+        }) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
 
             properties.insert(
@@ -833,38 +1005,58 @@ where
 
             properties.insert("success".to_string(), true.to_string());
 
-            logging::log_details(
+            log_details!(
                 log_level::LogLevel::Info,
                 "effects applied; new state hash is: {post-state-hash}".to_owned(),
                 properties,
             );
 
+            let mut properties: BTreeMap<String, String> = BTreeMap::new();
+
+            properties.insert(
+                "post-state-hash".to_string(),
+                format!("{:?}", post_state_hash),
+            );
+            properties.insert("nodes-written".to_string(), stats.nodes.to_string());
+            properties.insert("bytes-written".to_string(), stats.bytes.to_string());
+
+            log_details!(
+                log_level::LogLevel::Debug,
+                "commit wrote {nodes-written} trie nodes, {bytes-written} bytes".to_owned(),
+                properties,
+            );
+
             let mut commit_result = ipc::CommitResult::new();
             let mut tmp_res = ipc::CommitResponse::new();
             commit_result.set_poststate_hash(post_state_hash.to_vec());
+            commit_result.set_nodes_written(stats.nodes);
+            commit_result.set_bytes_written(stats.bytes);
             tmp_res.set_success(commit_result);
             tmp_res
         }
         Ok(CommitResult::KeyNotFound(key)) => {
-            logging::log_warning("KeyNotFound");
+            log_warning!("KeyNotFound");
             let mut commit_response = ipc::CommitResponse::new();
             commit_response.set_key_not_found((&key).into());
+            commit_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
             commit_response
         }
         Ok(CommitResult::TypeMismatch(type_mismatch)) => {
-            logging::log_warning("TypeMismatch");
+            log_warning!("TypeMismatch");
             let mut commit_response = ipc::CommitResponse::new();
             commit_response.set_type_mismatch(type_mismatch.into());
+            commit_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
             commit_response
         }
         // TODO(mateusz.gorski): We should be more specific about errors here.
         Err(storage_error) => {
             let log_message = format!("storage error {:?} when applying effects", storage_error);
-            logging::log_error(&log_message);
+            log_error!(&log_message);
             let mut err = ipc::PostEffectsError::new();
             let mut tmp_res = ipc::CommitResponse::new();
             err.set_message(format!("{:?}", storage_error));
             tmp_res.set_failed_transform(err);
+            tmp_res.set_error_code(ipc::ErrorCode::INTERNAL);
             tmp_res
         }
     }
@@ -916,6 +1108,7 @@ pub fn to_domain_validators(bond: &ipc::Bond) -> Result<(PublicKey, U512), Strin
 mod tests {
     use std::collections::HashMap;
     use std::convert::TryInto;
+    use std::time::Duration;
 
     use proptest::prelude::*;
 
@@ -925,12 +1118,13 @@ mod tests {
     use engine_core::engine_state::error::Error::ExecError;
     use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
     use engine_core::engine_state::execution_effect::ExecutionEffect;
-    use engine_core::engine_state::execution_result::ExecutionResult;
+    use engine_core::engine_state::execution_result::{ExecutionResult, ExecutionStats};
     use engine_core::execution::Error;
     use engine_server::mappings::CommitTransforms;
     use engine_shared::newtypes::Blake2bHash;
     use engine_shared::transform::gens::transform_arb;
     use engine_shared::transform::Transform;
+    use engine_wasm_prep::gas_profile::{GasCategory, GasProfile};
 
     use super::execution_error;
     use super::ipc;
@@ -975,6 +1169,9 @@ mod tests {
         let execution_result: ExecutionResult = ExecutionResult::Success {
             effect: execution_effect,
             cost,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
         };
         let mut ipc_deploy_result: ipc::DeployResult = execution_result.into();
         assert!(ipc_deploy_result.has_execution_result());
@@ -994,11 +1191,56 @@ mod tests {
         assert_eq!(&input_transforms, &ipc_transforms);
     }
 
+    fn deploy_result_with_gas_used(gas_used: u64) -> ipc::DeployResult {
+        ExecutionResult::Success {
+            effect: Default::default(),
+            cost: gas_used,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn charge_gas_price_should_multiply_gas_used() {
+        let mut deploy_result = deploy_result_with_gas_used(123);
+        assert_eq!(deploy_result.get_execution_result().get_cost(), 123);
+
+        charge_gas_price(&mut deploy_result, 10).expect("should charge");
+        let execution_result = deploy_result.get_execution_result();
+        assert_eq!(execution_result.get_cost(), 1230);
+        assert_eq!(execution_result.get_gas_used(), 123);
+
+        // charging again starts from the gas used rather than from the last cost
+        charge_gas_price(&mut deploy_result, 2).expect("should charge");
+        assert_eq!(deploy_result.get_execution_result().get_cost(), 246);
+    }
+
+    #[test]
+    fn charge_gas_price_should_fail_on_overflow() {
+        let mut deploy_result = deploy_result_with_gas_used(u64::max_value() / 2 + 1);
+
+        match charge_gas_price(&mut deploy_result, 2) {
+            Err(EngineError::Overflow {
+                gas_used,
+                gas_price,
+            }) => {
+                assert_eq!(gas_used, u64::max_value() / 2 + 1);
+                assert_eq!(gas_price, 2);
+            }
+            other => panic!("expected an overflow, got {:?}", other),
+        }
+    }
+
     fn into_execution_failure<E: Into<EngineError>>(error: E, cost: u64) -> ExecutionResult {
         ExecutionResult::Failure {
             error: error.into(),
             effect: Default::default(),
             cost,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
         }
     }
 
@@ -1023,11 +1265,12 @@ mod tests {
     #[test]
     fn exec_err_has_cost() {
         let cost: u64 = 100;
-        // GasLimit error is treated differently at the moment so test separately
-        assert_eq!(
-            test_cost(cost, engine_core::execution::Error::GasLimit),
-            cost
-        );
+        // OutOfGas error is treated differently at the moment so test separately
+        let out_of_gas_error = engine_core::execution::Error::OutOfGas {
+            gas_limit: cost,
+            gas_used: cost,
+        };
+        assert_eq!(test_cost(cost, out_of_gas_error), cost);
         // for the time being all other execution errors are treated in the same way
         let forged_ref_error = engine_core::execution::Error::ForgedReference(URef::new(
             [1u8; 32],
@@ -1036,6 +1279,128 @@ mod tests {
         assert_eq!(test_cost(cost, forged_ref_error), cost);
     }
 
+    #[test]
+    fn memory_limit_exceeded_maps_to_exec_error() {
+        let memory_error = engine_core::execution::Error::MemoryLimitExceeded {
+            requested_pages: 65,
+            max_pages: 64,
+        };
+        let ipc_deploy_result: ipc::DeployResult = into_execution_failure(memory_error, 10).into();
+        let error = ipc_deploy_result.get_execution_result().get_error();
+        assert_eq!(
+            error.get_exec_error().get_message(),
+            "Memory limit exceeded: grew to 65 pages, the maximum is 64"
+        );
+    }
+
+    #[test]
+    fn host_function_misuse_maps_to_exec_error() {
+        let misuse_error = engine_core::execution::Error::HostFunctionMisuse {
+            function: "write".to_string(),
+            mismatch: "takes 4 arguments, but was imported taking 3".to_string(),
+        };
+        let ipc_deploy_result: ipc::DeployResult = into_execution_failure(misuse_error, 10).into();
+        let error = ipc_deploy_result.get_execution_result().get_error();
+        assert_eq!(
+            error.get_exec_error().get_message(),
+            "Host function write misused: takes 4 arguments, but was imported taking 3"
+        );
+    }
+
+    #[test]
+    fn out_of_gas_maps_to_gas_error() {
+        let out_of_gas_error = engine_core::execution::Error::OutOfGas {
+            gas_limit: 100,
+            gas_used: 90,
+        };
+        let ipc_deploy_result: ipc::DeployResult =
+            into_execution_failure(out_of_gas_error, 90).into();
+        let error = ipc_deploy_result.get_execution_result().get_error();
+        assert!(error.has_gas_error());
+        assert_eq!(error.get_gas_error().get_gas_limit(), 100);
+        assert_eq!(error.get_gas_error().get_gas_used(), 90);
+    }
+
+    #[test]
+    fn invalid_nonce_maps_by_direction() {
+        let invalid_nonce = |deploy_nonce| -> ipc::DeployResult {
+            let error = Error::InvalidNonce {
+                deploy_nonce,
+                expected_nonce: 2,
+            };
+            into_execution_failure(error, 0).into()
+        };
+
+        // a replayed deploy can never succeed
+        let too_low = invalid_nonce(1);
+        assert_eq!(
+            too_low.get_precondition_failure().get_message(),
+            "Deploy nonce: 1 was lower (or equal to) than expected nonce 2"
+        );
+
+        // a deploy sent ahead of its predecessor may succeed once that is committed
+        let too_high = invalid_nonce(5);
+        assert!(too_high.has_invalid_nonce());
+        assert_eq!(too_high.get_invalid_nonce().get_deploy_nonce(), 5);
+        assert_eq!(too_high.get_invalid_nonce().get_expected_nonce(), 2);
+    }
+
+    #[test]
+    fn deploy_result_carries_error_code() {
+        let error_code = |error: EngineError| {
+            let ipc_deploy_result: ipc::DeployResult = into_execution_failure(error, 0).into();
+            ipc_deploy_result.get_error_code()
+        };
+        let out_of_gas_error = Error::OutOfGas {
+            gas_limit: 100,
+            gas_used: 90,
+        };
+        assert_eq!(
+            error_code(out_of_gas_error.into()),
+            ipc::ErrorCode::OUT_OF_GAS
+        );
+        assert_eq!(error_code(Error::Timeout.into()), ipc::ErrorCode::TIMEOUT);
+        assert_eq!(
+            error_code(Error::Revert(1).into()),
+            ipc::ErrorCode::EXECUTION_ERROR
+        );
+        assert_eq!(
+            error_code(Error::KeyNotFound(Key::Hash([1u8; 32])).into()),
+            ipc::ErrorCode::STATE_NOT_FOUND
+        );
+        assert_eq!(
+            error_code(EngineError::StorageError(
+                engine_storage::error::Error::MapFull
+            )),
+            ipc::ErrorCode::STORAGE_FULL
+        );
+        assert_eq!(
+            error_code(EngineError::StorageError(
+                engine_storage::error::Error::RootNotFound([1u8; 32].into())
+            )),
+            ipc::ErrorCode::STATE_NOT_FOUND
+        );
+        assert_eq!(
+            error_code(EngineError::StorageError(
+                engine_storage::error::Error::PoisonError
+            )),
+            ipc::ErrorCode::INTERNAL
+        );
+    }
+
+    #[test]
+    fn successful_deploy_result_has_no_error_code() {
+        let execution_result = ExecutionResult::Success {
+            effect: Default::default(),
+            cost: 0,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+        assert_eq!(ipc_deploy_result.get_error_code(), ipc::ErrorCode::NO_ERROR);
+    }
+
     #[test]
     fn commit_effects_merges_transforms() {
         // Tests that transforms made to the same key are merged instead of lost.
@@ -1072,6 +1437,9 @@ mod tests {
             error: ExecError(revert_error),
             effect: Default::default(),
             cost: 10,
+            gas_profile: None,
+            trace: None,
+            stats: Default::default(),
         };
         let ipc_result: ipc::DeployResult = exec_result.into();
         assert!(ipc_result.has_execution_result());
@@ -1083,6 +1451,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn gas_profile_maps_to_named_categories() {
+        let mut gas_profile = GasProfile::new();
+        gas_profile.insert(GasCategory::Regular, 7);
+        gas_profile.insert(GasCategory::CalledContracts, 3);
+        let exec_result = ExecutionResult::Success {
+            effect: Default::default(),
+            cost: 10,
+            gas_profile: Some(gas_profile),
+            trace: None,
+            stats: Default::default(),
+        };
+        let ipc_result: ipc::DeployResult = exec_result.into();
+        let ipc_gas_profile = ipc_result.get_execution_result().get_gas_profile();
+        assert_eq!(ipc_gas_profile.len(), 2);
+        assert_eq!(ipc_gas_profile.get("regular"), Some(&7));
+        assert_eq!(ipc_gas_profile.get("called_contracts"), Some(&3));
+    }
+
+    #[test]
+    fn stats_map_to_execution_result() {
+        let exec_result = ExecutionResult::Failure {
+            error: ExecError(Error::Revert(1)),
+            effect: Default::default(),
+            cost: 10,
+            gas_profile: None,
+            trace: None,
+            stats: ExecutionStats {
+                duration: Duration::from_millis(3),
+                peak_memory_pages: 17,
+                trie_reads: 5,
+            },
+        };
+        let ipc_result: ipc::DeployResult = exec_result.into();
+        let ipc_execution_result = ipc_result.get_execution_result();
+        assert_eq!(ipc_execution_result.get_duration_micros(), 3000);
+        assert_eq!(ipc_execution_result.get_peak_memory_pages(), 17);
+        assert_eq!(ipc_execution_result.get_trie_reads(), 5);
+    }
+
     proptest! {
         #[test]
         fn key_roundtrip(key in key_arb()) {
@@ -0,0 +1,361 @@
+//! Keeps the server from starting more deploys while the process uses too much memory.
+//!
+//! The memory of each deploy is limited, but many deploys running at once can still exhaust the
+//! host's.  A [`MemoryGuard`] samples the resident set size of the process on a background thread,
+//! and while it is above the limit, [`MemoryGate`] rejects new `exec`, `exec_stream` and
+//! `estimate_gas` requests with a `ResourceExhausted` status.  Requests already running carry on,
+//! as finishing them is what frees memory, and other requests are still served.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use engine_shared::os;
+
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+
+/// How often [`MemoryGuard::start_sampling`] reads the resident set size.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+const SAMPLER_THREAD_NAME: &str = "rss-sampler";
+const RSS_READ_ERROR: &str = "failed to read resident set size";
+
+const BYTES_PER_MIB: u64 = 1024 * 1024;
+
+/// Whether the resident set size of the process was above `max_rss` bytes when last sampled.
+/// Clones share the latest sample, so the server can hand one to the [`MemoryGate`] and keep
+/// another to sample with.
+#[derive(Debug, Clone)]
+pub struct MemoryGuard {
+    max_rss: u64,
+    over_limit: Arc<AtomicBool>,
+}
+
+impl MemoryGuard {
+    /// Creates a guard for a limit of `max_rss` bytes, starting out under it.
+    pub fn new(max_rss: u64) -> MemoryGuard {
+        MemoryGuard {
+            max_rss,
+            over_limit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_over_limit(&self) -> bool {
+        self.over_limit.load(Ordering::SeqCst)
+    }
+
+    /// Records `rss`, a sample of the resident set size in bytes, logging when it crosses the
+    /// limit in either direction.
+    pub fn update(&self, rss: u64) {
+        let over_limit = rss > self.max_rss;
+        if self.over_limit.swap(over_limit, Ordering::SeqCst) == over_limit {
+            return;
+        }
+        if over_limit {
+            log_warning!(&format!(
+                "resident set size of {} MiB exceeds the limit of {} MiB; rejecting execution",
+                rss / BYTES_PER_MIB,
+                self.max_rss / BYTES_PER_MIB
+            ));
+        } else {
+            log_info!(&format!(
+                "resident set size back down to {} MiB; accepting execution",
+                rss / BYTES_PER_MIB
+            ));
+        }
+    }
+
+    /// Samples the resident set size now, then every `interval` on a background thread for as
+    /// long as the process runs.  Fails if it cannot be read, or the thread cannot be started.
+    ///
+    /// A sample which fails later is logged, and the previous one stands.
+    pub fn start_sampling(&self, interval: Duration) -> Result<(), io::Error> {
+        self.update(os::resident_set_size()?);
+        let memory_guard = self.clone();
+        thread::Builder::new()
+            .name(SAMPLER_THREAD_NAME.to_string())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                match os::resident_set_size() {
+                    Ok(rss) => memory_guard.update(rss),
+                    Err(error) => {
+                        log_warning!(&format!("{}: {}", RSS_READ_ERROR, error));
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
+
+fn over_limit_error(memory_guard: &MemoryGuard) -> grpc::Error {
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::ResourceExhausted as i32,
+        grpc_message: format!(
+            "server memory use is above its limit of {} MiB; retry later",
+            memory_guard.max_rss / BYTES_PER_MIB
+        ),
+    })
+}
+
+/// Wraps an engine service so that execution is rejected while `memory_guard`, if there is one,
+/// is over its limit.
+pub struct MemoryGate<S> {
+    service: S,
+    memory_guard: Option<MemoryGuard>,
+}
+
+impl<S> MemoryGate<S> {
+    pub fn new(service: S, memory_guard: Option<MemoryGuard>) -> Self {
+        MemoryGate {
+            service,
+            memory_guard,
+        }
+    }
+
+    /// Returns the error to reject execution with, if it is rejected now.
+    fn check(&self) -> Option<grpc::Error> {
+        match self.memory_guard {
+            Some(ref memory_guard) if memory_guard.is_over_limit() => {
+                Some(over_limit_error(memory_guard))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<S: ExecutionEngineService> ExecutionEngineService for MemoryGate<S> {
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        if let Some(error) = self.check() {
+            return grpc::SingleResponse::err(error);
+        }
+        self.service.exec(request_options, exec_request)
+    }
+
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        if let Some(error) = self.check() {
+            return grpc::StreamingResponse::err(error);
+        }
+        self.service.exec_stream(request_options, exec_request)
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        self.service.commit(request_options, commit_request)
+    }
+
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        self.service
+            .commit_batch(request_options, commit_batch_request)
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        self.service.query(request_options, query_request)
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        self.service.validate(request_options, validate_request)
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        self.service.run_genesis(request_options, genesis_request)
+    }
+
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        self.service
+            .get_store_stats(request_options, store_stats_request)
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        self.service
+            .list_state_roots(request_options, list_state_roots_request)
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        self.service
+            .backup_store(request_options, backup_store_request)
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        self.service
+            .validate_deploy(request_options, validate_deploy_request)
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        self.service.state_diff(request_options, state_diff_request)
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        self.service
+            .flush_store(request_options, flush_store_request)
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        self.service
+            .get_engine_version(request_options, get_engine_version_request)
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        self.service
+            .get_genesis_hash(request_options, get_genesis_hash_request)
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        self.service
+            .verify_store(request_options, verify_store_request)
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        self.service
+            .prune_state(request_options, prune_state_request)
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        self.service
+            .get_capabilities(request_options, get_capabilities_request)
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        if let Some(error) = self.check() {
+            return grpc::SingleResponse::err(error);
+        }
+        self.service
+            .estimate_gas(request_options, estimate_gas_request)
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        self.service
+            .export_state(request_options, export_state_request)
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        self.service
+            .get_empty_state_root(request_options, get_empty_state_root_request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_be_over_limit_only_while_above_max_rss() {
+        let memory_guard = MemoryGuard::new(100 * BYTES_PER_MIB);
+        assert!(!memory_guard.is_over_limit());
+        memory_guard.update(100 * BYTES_PER_MIB);
+        assert!(!memory_guard.is_over_limit());
+        memory_guard.update(100 * BYTES_PER_MIB + 1);
+        assert!(memory_guard.clone().is_over_limit());
+        memory_guard.update(50 * BYTES_PER_MIB);
+        assert!(!memory_guard.is_over_limit());
+    }
+
+    #[test]
+    fn should_reject_with_resource_exhausted_while_over_limit() {
+        let memory_guard = MemoryGuard::new(100 * BYTES_PER_MIB);
+        let memory_gate = MemoryGate::new((), Some(memory_guard.clone()));
+        assert!(memory_gate.check().is_none());
+
+        memory_guard.update(200 * BYTES_PER_MIB);
+        match memory_gate.check() {
+            Some(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(
+                    error.grpc_status,
+                    grpc::GrpcStatus::ResourceExhausted as i32
+                );
+                assert_eq!(
+                    error.grpc_message,
+                    "server memory use is above its limit of 100 MiB; retry later"
+                );
+            }
+            other => panic!("expected a ResourceExhausted status, got {:?}", other),
+        }
+
+        assert!(MemoryGate::new((), None).check().is_none());
+    }
+}
@@ -0,0 +1,387 @@
+//! Counters, gauges and histograms updated by the RPC handlers and served in the Prometheus text
+//! exposition format.
+//!
+//! See <https://prometheus.io/docs/instrumenting/exposition_formats/>.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use engine_shared::logging;
+
+use super::ipc;
+
+pub const METRICS_PATH: &str = "/metrics";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// A monotonically increasing count.
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicUsize,
+}
+
+impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Counter {
+        Counter {
+            name,
+            help,
+            value: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn increment(&self) {
+        self.add(1)
+    }
+
+    pub fn add(&self, amount: usize) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> usize {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn write_to(&self, output: &mut String) -> std::fmt::Result {
+        write_counter(output, self.name, self.help, self.get())
+    }
+}
+
+fn write_counter(output: &mut String, name: &str, help: &str, value: usize) -> std::fmt::Result {
+    writeln!(output, "# HELP {} {}", name, help)?;
+    writeln!(output, "# TYPE {} counter", name)?;
+    writeln!(output, "{} {}", name, value)
+}
+
+/// A count which goes up and down.
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicUsize,
+}
+
+impl Gauge {
+    pub const fn new(name: &'static str, help: &'static str) -> Gauge {
+        Gauge {
+            name,
+            help,
+            value: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn increment(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn decrement(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> usize {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn write_to(&self, output: &mut String) -> std::fmt::Result {
+        writeln!(output, "# HELP {} {}", self.name, self.help)?;
+        writeln!(output, "# TYPE {} gauge", self.name)?;
+        writeln!(output, "{} {}", self.name, self.get())
+    }
+}
+
+/// The most buckets a [Histogram] may have, besides the implicit `+Inf` one.
+const MAX_BUCKETS: usize = 12;
+
+/// A distribution of observed values, counted into buckets with fixed upper bounds.
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    /// The inclusive upper bound of each bucket, ascending; at most [MAX_BUCKETS] of them.
+    bounds: &'static [u64],
+    /// Observations per bucket, not cumulative; values above every bound are only in `count`.
+    buckets: [AtomicUsize; MAX_BUCKETS],
+    count: AtomicUsize,
+    sum: AtomicUsize,
+}
+
+impl Histogram {
+    pub const fn new(name: &'static str, help: &'static str, bounds: &'static [u64]) -> Histogram {
+        Histogram {
+            name,
+            help,
+            bounds,
+            buckets: [
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+            ],
+            count: AtomicUsize::new(0),
+            sum: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value as usize, Ordering::Relaxed);
+    }
+
+    fn write_to(&self, output: &mut String) -> std::fmt::Result {
+        writeln!(output, "# HELP {} {}", self.name, self.help)?;
+        writeln!(output, "# TYPE {} histogram", self.name)?;
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            writeln!(
+                output,
+                "{}_bucket{{le=\"{}\"}} {}",
+                self.name, bound, cumulative
+            )?;
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        writeln!(output, "{}_bucket{{le=\"+Inf\"}} {}", self.name, count)?;
+        writeln!(
+            output,
+            "{}_sum {}",
+            self.name,
+            self.sum.load(Ordering::Relaxed)
+        )?;
+        writeln!(output, "{}_count {}", self.name, count)
+    }
+}
+
+const LOG_LINES_DROPPED_NAME: &str = "casperlabs_engine_log_lines_dropped_total";
+const LOG_LINES_DROPPED_HELP: &str = "Log lines dropped because the async logging queue was full.";
+
+pub static DEPLOYS_EXECUTED: Counter = Counter::new(
+    "casperlabs_engine_deploys_executed_total",
+    "Deploys executed, whether or not they succeeded.",
+);
+
+pub static GAS_CONSUMED: Counter = Counter::new(
+    "casperlabs_engine_gas_consumed_total",
+    "Gas charged for executed deploys.",
+);
+
+pub static EXECUTION_ERRORS: Counter = Counter::new(
+    "casperlabs_engine_execution_errors_total",
+    "Deploys that failed a precondition or ended with an execution error.",
+);
+
+pub static COMMITS: Counter = Counter::new(
+    "casperlabs_engine_commits_total",
+    "Successful commit and commit_batch calls.",
+);
+
+pub static REQUESTS_REJECTED: Counter = Counter::new(
+    "casperlabs_engine_requests_rejected_total",
+    "Requests rejected because max-concurrent-requests were already in flight.",
+);
+
+pub static REQUESTS_IN_FLIGHT: Gauge = Gauge::new(
+    "casperlabs_engine_requests_in_flight",
+    "Requests being handled, counted against max-concurrent-requests.",
+);
+
+static COUNTERS: [&Counter; 5] = [
+    &DEPLOYS_EXECUTED,
+    &GAS_CONSUMED,
+    &EXECUTION_ERRORS,
+    &COMMITS,
+    &REQUESTS_REJECTED,
+];
+
+static GAUGES: [&Gauge; 1] = [&REQUESTS_IN_FLIGHT];
+
+pub static DEPLOY_DURATION: Histogram = Histogram::new(
+    "casperlabs_engine_deploy_duration_microseconds",
+    "Wall-clock time spent executing each deploy.",
+    &[
+        100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+    ],
+);
+
+pub static DEPLOY_PEAK_MEMORY_PAGES: Histogram = Histogram::new(
+    "casperlabs_engine_deploy_peak_memory_pages",
+    "The most pages of wasm linear memory held by each deploy.",
+    &[1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1_024],
+);
+
+pub static DEPLOY_TRIE_READS: Histogram = Histogram::new(
+    "casperlabs_engine_deploy_trie_reads",
+    "Values read from global state by each deploy.",
+    &[1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000],
+);
+
+static HISTOGRAMS: [&Histogram; 3] = [
+    &DEPLOY_DURATION,
+    &DEPLOY_PEAK_MEMORY_PAGES,
+    &DEPLOY_TRIE_READS,
+];
+
+/// Updates the deploy counters and histograms from the results of an `exec` call.
+pub fn record_deploy_results(deploy_results: &[ipc::DeployResult]) {
+    for deploy_result in deploy_results {
+        if deploy_result.has_execution_result() {
+            let execution_result = deploy_result.get_execution_result();
+            DEPLOYS_EXECUTED.increment();
+            GAS_CONSUMED.add(execution_result.get_gas_used() as usize);
+            DEPLOY_DURATION.observe(execution_result.get_duration_micros());
+            DEPLOY_PEAK_MEMORY_PAGES.observe(u64::from(execution_result.get_peak_memory_pages()));
+            DEPLOY_TRIE_READS.observe(execution_result.get_trie_reads());
+            if execution_result.has_error() {
+                EXECUTION_ERRORS.increment();
+            }
+        } else if deploy_result.has_precondition_failure() {
+            EXECUTION_ERRORS.increment();
+        }
+    }
+}
+
+/// Renders all counters, gauges and histograms in the Prometheus text format.
+pub fn render() -> String {
+    let mut output = String::new();
+    for counter in COUNTERS.iter() {
+        counter
+            .write_to(&mut output)
+            .expect("writing to a String should not fail");
+    }
+    for gauge in GAUGES.iter() {
+        gauge
+            .write_to(&mut output)
+            .expect("writing to a String should not fail");
+    }
+    for histogram in HISTOGRAMS.iter() {
+        histogram
+            .write_to(&mut output)
+            .expect("writing to a String should not fail");
+    }
+    // counted by the logger, which lives in engine-shared
+    write_counter(
+        &mut output,
+        LOG_LINES_DROPPED_NAME,
+        LOG_LINES_DROPPED_HELP,
+        logging::dropped_log_records(),
+    )
+    .expect("writing to a String should not fail");
+    output
+}
+
+/// Answers `GET /metrics` on `listener` with the rendered counters, and any other request with
+/// 404.  Requests are handled one at a time; this blocks for as long as the listener is open.
+pub fn serve(listener: TcpListener) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(handle_request);
+        if let Err(error) = result {
+            log_warning!(&format!("metrics request failed: {}", error));
+        }
+    }
+}
+
+fn handle_request(mut stream: TcpStream) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(METRICS_PATH)) => ("200 OK", render()),
+        _ => ("404 Not Found", String::new()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        CONTENT_TYPE,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_counters_in_text_format() {
+        let counter = Counter::new("test_total", "A test counter.");
+        counter.add(2);
+        counter.increment();
+
+        let mut output = String::new();
+        counter.write_to(&mut output).unwrap();
+
+        assert_eq!(
+            output,
+            "# HELP test_total A test counter.\n# TYPE test_total counter\ntest_total 3\n"
+        );
+    }
+
+    #[test]
+    fn should_render_gauges_in_text_format() {
+        let gauge = Gauge::new("test_in_flight", "A test gauge.");
+        gauge.increment();
+        gauge.increment();
+        gauge.decrement();
+
+        let mut output = String::new();
+        gauge.write_to(&mut output).unwrap();
+
+        assert_eq!(
+            output,
+            "# HELP test_in_flight A test gauge.\n# TYPE test_in_flight gauge\ntest_in_flight 1\n"
+        );
+    }
+
+    #[test]
+    fn should_render_histograms_in_text_format() {
+        let histogram = Histogram::new("test_reads", "A test histogram.", &[1, 10]);
+        histogram.observe(0);
+        histogram.observe(5);
+        histogram.observe(10);
+        histogram.observe(11);
+
+        let mut output = String::new();
+        histogram.write_to(&mut output).unwrap();
+
+        assert_eq!(
+            output,
+            "# HELP test_reads A test histogram.\n\
+             # TYPE test_reads histogram\n\
+             test_reads_bucket{le=\"1\"} 1\n\
+             test_reads_bucket{le=\"10\"} 3\n\
+             test_reads_bucket{le=\"+Inf\"} 4\n\
+             test_reads_sum 26\n\
+             test_reads_count 4\n"
+        );
+    }
+
+    #[test]
+    fn should_render_all_counters() {
+        let output = render();
+        for counter in COUNTERS.iter() {
+            assert!(output.contains(&format!("# TYPE {} counter", counter.name)));
+        }
+        for gauge in GAUGES.iter() {
+            assert!(output.contains(&format!("# TYPE {} gauge", gauge.name)));
+        }
+        for histogram in HISTOGRAMS.iter() {
+            assert!(output.contains(&format!("# TYPE {} histogram", histogram.name)));
+            assert!(histogram.bounds.len() <= MAX_BUCKETS);
+        }
+        assert!(output.contains(&format!("# TYPE {} counter", LOG_LINES_DROPPED_NAME)));
+    }
+}
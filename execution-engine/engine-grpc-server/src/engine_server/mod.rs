@@ -1,51 +1,160 @@
-use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::convert::TryInto;
-use std::fmt::Debug;
-use std::io::ErrorKind;
+use std::fmt::{self, Debug};
 use std::marker::{Send, Sync};
+use std::path::Path;
+#[cfg(feature = "tokio-server")]
+use std::sync::Arc;
+use std::thread;
 use std::time::Instant;
 
 use contract_ffi::key::Key;
-use contract_ffi::value::account::{BlockTime, PublicKey};
+use contract_ffi::value::account::BlockTime;
 use contract_ffi::value::U512;
-use engine_core::engine_state::error::Error as EngineError;
+use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
 use engine_core::engine_state::execution_result::ExecutionResult;
 use engine_core::engine_state::genesis::GenesisURefsSource;
 use engine_core::engine_state::{
-    genesis::GenesisResult, get_bonded_validators, EngineState, GetBondedValidatorsError,
+    genesis::GenesisResult, get_bonded_validators, EngineConfig, EngineState,
 };
-use engine_core::execution::{Executor, WasmiExecutor};
-use engine_core::tracking_copy::QueryResult;
+use engine_core::execution::WasmiExecutor;
+use engine_core::tracking_copy::{QueryLimits, QueryResult};
 use engine_server::ipc::CommitResponse;
 use engine_shared::logging;
-use engine_shared::logging::{log_duration, log_info};
-use engine_shared::newtypes::{Blake2bHash, CorrelationId};
-use engine_storage::global_state::{CommitResult, History};
-use engine_wasm_prep::wasm_costs::WasmCosts;
-use engine_wasm_prep::{Preprocessor, WasmiPreprocessor};
-
+use engine_shared::newtypes::Blake2bHash;
+use engine_shared::socket::Socket;
+use engine_storage::global_state::{CommitResult, DiffResult, History, PruneResult, VerifyResult};
+use engine_wasm_prep::{GasProfilingPreprocessor, Preprocessor, WasmiPreprocessor};
+use futures::sync::mpsc;
+use futures::{Future, Stream};
+use openssl::error::ErrorStack;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+use self::admission::{AdmissionGate, ConcurrencyLimit};
+#[cfg(feature = "tokio-server")]
+use self::async_service::AsyncService;
+use self::commits::{commit_effects_batch, retry_commit};
+use self::deploys::{
+    cache_deploy_result, cache_exec_result, cached_deploy_result, cached_exec_result,
+    charge_deploy_result, check_deploy_count, check_payment_code, deploy_account_key,
+    deploy_authorized_keys, deploy_content_hash, deploy_gas_limit, deploy_gas_price,
+    estimate_deploy_gas, is_gas_profiling, is_reusable, run_deploys, validation_problem,
+};
+use self::drain::{Drain, DrainGate};
+use self::errors::{
+    deadline_exceeded_error, internal_failure, invalid_argument_error, invalid_argument_failure,
+    is_malformed_module, is_readers_full_failure, malformed_module, malformed_module_error,
+    malformed_payment_error, out_of_space_error, read_only_error, readers_full_error,
+    root_not_found_error,
+};
+use self::health_status::HealthStatus;
 use self::ipc_grpc::ExecutionEngineService;
 use self::mappings::*;
-
+use self::memory_guard::{MemoryGate, MemoryGuard};
+use self::panic_boundary::PanicBoundary;
+use self::request::{
+    get_preprocessor, is_past_deadline, parse_state_hash, request_correlation_id, request_deadline,
+};
+use self::streams::{stream_deploys, stream_pairs};
+
+pub use self::commits::bonded_validators_and_commit_result;
+pub use self::server_config::{ServerConfig, TlsConfig, DEFAULT_SOCKET_BACKLOG};
+
+pub mod admission;
+#[cfg(feature = "tokio-server")]
+pub mod async_service;
+mod commits;
+mod deploys;
+pub mod drain;
+mod errors;
+pub mod gas_estimate;
+pub mod health;
+pub mod health_grpc;
+pub mod health_status;
 pub mod ipc;
 pub mod ipc_grpc;
 pub mod mappings;
+pub mod memory_guard;
+pub mod metrics;
+pub mod panic_boundary;
+mod request;
+mod server_config;
 pub mod state;
+mod streams;
+
+const EXPECTED_STATE_HASH_LENGTH: usize = 32;
 
-const EXPECTED_PUBLIC_KEY_LENGTH: usize = 32;
+/// Version of the IPC protocol reported by `get_engine_version`.  Bump it on every change to
+/// `ipc.proto` which old clients cannot handle.
+pub const IPC_PROTOCOL_VERSION: u32 = 1;
 
 const METRIC_DURATION_COMMIT: &str = "commit_duration";
+const METRIC_DURATION_COMMIT_BATCH: &str = "commit_batch_duration";
 const METRIC_DURATION_EXEC: &str = "exec_duration";
+const METRIC_DURATION_EXEC_STREAM: &str = "exec_stream_duration";
 const METRIC_DURATION_QUERY: &str = "query_duration";
 const METRIC_DURATION_VALIDATE: &str = "validate_duration";
 const METRIC_DURATION_GENESIS: &str = "genesis_duration";
+const METRIC_DURATION_STORE_STATS: &str = "store_stats_duration";
+const METRIC_DURATION_LIST_STATE_ROOTS: &str = "list_state_roots_duration";
+const METRIC_DURATION_BACKUP_STORE: &str = "backup_store_duration";
+const METRIC_DURATION_VALIDATE_DEPLOY: &str = "validate_deploy_duration";
+const METRIC_DURATION_STATE_DIFF: &str = "state_diff_duration";
+const METRIC_DURATION_FLUSH_STORE: &str = "flush_store_duration";
+const METRIC_DURATION_GET_ENGINE_VERSION: &str = "get_engine_version_duration";
+const METRIC_DURATION_GET_GENESIS_HASH: &str = "get_genesis_hash_duration";
+const METRIC_DURATION_VERIFY_STORE: &str = "verify_store_duration";
+const METRIC_DURATION_PRUNE_STATE: &str = "prune_state_duration";
+const METRIC_DURATION_GET_CAPABILITIES: &str = "get_capabilities_duration";
+const METRIC_DURATION_ESTIMATE_GAS: &str = "estimate_gas_duration";
+const METRIC_DURATION_EXPORT_STATE: &str = "export_state_duration";
+const METRIC_DURATION_GET_EMPTY_STATE_ROOT: &str = "get_empty_state_root_duration";
 
 const TAG_RESPONSE_COMMIT: &str = "commit_response";
+const TAG_RESPONSE_COMMIT_BATCH: &str = "commit_batch_response";
 const TAG_RESPONSE_EXEC: &str = "exec_response";
+const TAG_RESPONSE_EXEC_STREAM: &str = "exec_stream_response";
 const TAG_RESPONSE_QUERY: &str = "query_response";
 const TAG_RESPONSE_VALIDATE: &str = "validate_response";
 const TAG_RESPONSE_GENESIS: &str = "genesis_response";
+const TAG_RESPONSE_STORE_STATS: &str = "store_stats_response";
+const TAG_RESPONSE_LIST_STATE_ROOTS: &str = "list_state_roots_response";
+const TAG_RESPONSE_BACKUP_STORE: &str = "backup_store_response";
+const TAG_RESPONSE_VALIDATE_DEPLOY: &str = "validate_deploy_response";
+const TAG_RESPONSE_STATE_DIFF: &str = "state_diff_response";
+const TAG_RESPONSE_FLUSH_STORE: &str = "flush_store_response";
+const TAG_RESPONSE_GET_ENGINE_VERSION: &str = "get_engine_version_response";
+const TAG_RESPONSE_GET_GENESIS_HASH: &str = "get_genesis_hash_response";
+const TAG_RESPONSE_VERIFY_STORE: &str = "verify_store_response";
+const TAG_RESPONSE_PRUNE_STATE: &str = "prune_state_response";
+const TAG_RESPONSE_GET_CAPABILITIES: &str = "get_capabilities_response";
+const TAG_RESPONSE_ESTIMATE_GAS: &str = "estimate_gas_response";
+const TAG_RESPONSE_EXPORT_STATE: &str = "export_state_response";
+const TAG_RESPONSE_GET_EMPTY_STATE_ROOT: &str = "get_empty_state_root_response";
+
+const STORE_STATS_UNSUPPORTED_MESSAGE: &str = "global state store does not keep statistics";
+const STATE_ROOTS_UNSUPPORTED_MESSAGE: &str =
+    "global state store does not keep a history of state roots";
+const BACKUP_UNSUPPORTED_MESSAGE: &str = "global state store cannot be copied";
+const BACKUP_PATH_REQUIRED_MESSAGE: &str = "backup path is required";
+const PRUNE_UNSUPPORTED_MESSAGE: &str = "global state store cannot be pruned";
+const PRUNE_RETAIN_REQUIRED_MESSAGE: &str = "at least one state root to retain is required";
+const GENESIS_HASH_UNKNOWN_MESSAGE: &str = "nothing has been committed to global state yet";
+
+/// Most state roots returned by a single `list_state_roots` call.
+const MAX_LIST_STATE_ROOTS_LIMIT: u64 = 1000;
+
+/// Most keys returned by a single `state_diff` call.
+const MAX_STATE_DIFF_LIMIT: u64 = 1000;
+
+/// Most deploy results an `exec_stream` call holds while waiting for the client to read them.
+const EXEC_STREAM_BUFFER_SIZE: usize = 16;
+
+/// Most pairs sent in a single `export_state` chunk.
+const MAX_EXPORT_STATE_CHUNK_SIZE: u32 = 1000;
+
+/// Most chunks an `export_state` call holds while waiting for the client to read them.
+const EXPORT_STATE_BUFFER_SIZE: usize = 4;
 
 // Idea is that Engine will represent the core of the execution engine project.
 // It will act as an entry point for execution of Wasm binaries.
@@ -53,56 +162,64 @@ const TAG_RESPONSE_GENESIS: &str = "genesis_response";
 // This way core won't depend on casperlabs-engine-grpc-server (outer layer) leading to cleaner design.
 impl<H> ipc_grpc::ExecutionEngineService for EngineState<H>
 where
-    H: History,
+    H: History + Send + Sync + 'static,
     EngineError: From<H::Error>,
-    H::Error: Into<engine_core::execution::Error> + Debug,
+    H::Error: Into<engine_core::execution::Error> + Clone + Debug,
 {
     fn query(
         &self,
-        _request_options: ::grpc::RequestOptions,
+        request_options: ::grpc::RequestOptions,
         query_request: ipc::QueryRequest,
     ) -> grpc::SingleResponse<ipc::QueryResponse> {
         let start = Instant::now();
-        let correlation_id = CorrelationId::new();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
         // TODO: don't unwrap
         let state_hash: Blake2bHash = query_request.get_state_hash().try_into().unwrap();
 
-        let mut tracking_copy = match self.tracking_copy(state_hash) {
-            Err(storage_error) => {
-                let mut result = ipc::QueryResponse::new();
-                let error = format!("Error during checkout out Trie: {:?}", storage_error);
-                logging::log_error(&error);
-                result.set_failure(error);
-                log_duration(
+        let mut tracking_copy = match self.existing_tracking_copy(state_hash) {
+            Err(ref error) if error.is_readers_full() => {
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_QUERY,
-                    "tracking_copy_error",
+                    "tracking_copy_readers_full",
                     start.elapsed(),
                 );
-                return grpc::SingleResponse::completed(result);
+                return grpc::SingleResponse::err(readers_full_error());
             }
-            Ok(None) => {
+            Err(ref error) if error.is_root_not_found() => {
+                log_duration!(
+                    correlation_id,
+                    METRIC_DURATION_QUERY,
+                    "tracking_copy_root_not_found",
+                    start.elapsed(),
+                );
+                return grpc::SingleResponse::err(root_not_found_error(state_hash));
+            }
+            Err(storage_error) => {
                 let mut result = ipc::QueryResponse::new();
-                let error = format!("Root not found: {:?}", state_hash);
-                logging::log_warning(&error);
+                let error = format!("Error during checkout out Trie: {:?}", storage_error);
+                log_error!(&error);
+                result.set_error_code(error_code(&storage_error));
                 result.set_failure(error);
-                log_duration(
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_QUERY,
-                    "tracking_copy_root_not_found",
+                    "tracking_copy_error",
                     start.elapsed(),
                 );
                 return grpc::SingleResponse::completed(result);
             }
-            Ok(Some(tracking_copy)) => tracking_copy,
+            Ok(tracking_copy) => tracking_copy,
         };
 
         let key = match query_request.get_base_key().try_into() {
             Err(ParsingError(err_msg)) => {
-                logging::log_error(&err_msg);
+                log_error!(&err_msg);
                 let mut result = ipc::QueryResponse::new();
                 result.set_failure(err_msg);
-                log_duration(
+                result.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_QUERY,
                     "key_parsing_error",
@@ -114,20 +231,44 @@ where
         };
 
         let path = query_request.get_path();
+        let limits = QueryLimits {
+            max_depth: self.config().get_max_query_depth(),
+            max_nodes: self.config().get_max_query_nodes(),
+        };
 
-        let response = match tracking_copy.query(correlation_id, key, path) {
+        let response = match tracking_copy.query(correlation_id, key, path, limits) {
             Err(err) => {
-                let mut result = ipc::QueryResponse::new();
                 let error = format!("{:?}", err);
-                logging::log_error(&error);
+                let err = EngineError::from(err);
+                if err.is_readers_full() {
+                    log_duration!(
+                        correlation_id,
+                        METRIC_DURATION_QUERY,
+                        TAG_RESPONSE_QUERY,
+                        start.elapsed(),
+                    );
+                    return grpc::SingleResponse::err(readers_full_error());
+                }
+                let mut result = ipc::QueryResponse::new();
+                log_error!(&error);
                 result.set_failure(error);
+                result.set_error_code(error_code(&err));
                 result
             }
             Ok(QueryResult::ValueNotFound(full_path)) => {
                 let mut result = ipc::QueryResponse::new();
                 let error = format!("Value not found: {:?}", full_path);
-                logging::log_warning(&error);
+                log_warning!(&error);
+                result.set_failure(error);
+                result.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+                result
+            }
+            Ok(QueryResult::QueryTooComplex(reason)) => {
+                let mut result = ipc::QueryResponse::new();
+                let error = format!("Query too complex: {}", reason);
+                log_warning!(&error);
                 result.set_failure(error);
+                result.set_error_code(ipc::ErrorCode::QUERY_TOO_COMPLEX);
                 result
             }
             Ok(QueryResult::Success(value)) => {
@@ -137,7 +278,7 @@ where
             }
         };
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_QUERY,
             TAG_RESPONSE_QUERY,
@@ -149,11 +290,13 @@ where
 
     fn exec(
         &self,
-        _request_options: ::grpc::RequestOptions,
+        request_options: ::grpc::RequestOptions,
         exec_request: ipc::ExecRequest,
     ) -> grpc::SingleResponse<ipc::ExecResponse> {
         let start = Instant::now();
-        let correlation_id = CorrelationId::new();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+        let deadline = request_deadline(&request_options, start);
 
         let protocol_version = exec_request.get_protocol_version();
 
@@ -162,28 +305,185 @@ where
 
         let blocktime = BlockTime(exec_request.get_block_time());
 
+        let deploys = exec_request.get_deploys();
+
+        if let Err(error) = check_deploy_count(self.config(), deploys.len()) {
+            log_duration!(
+                correlation_id,
+                METRIC_DURATION_EXEC,
+                TAG_RESPONSE_EXEC,
+                start.elapsed(),
+            );
+            return grpc::SingleResponse::err(error);
+        }
+
         // TODO: don't unwrap
-        let wasm_costs = WasmCosts::from_version(protocol_version.value).unwrap();
+        let preprocessor: WasmiPreprocessor =
+            get_preprocessor(self.config(), protocol_version.value).unwrap();
 
-        let deploys = exec_request.get_deploys();
+        for (index, deploy) in deploys.iter().enumerate() {
+            if let Err(error) = check_payment_code(&preprocessor, deploy) {
+                log_duration!(
+                    correlation_id,
+                    METRIC_DURATION_EXEC,
+                    TAG_RESPONSE_EXEC,
+                    start.elapsed(),
+                );
+                return grpc::SingleResponse::err(malformed_payment_error(index, &error));
+            }
+            let gas_price = deploy_gas_price(exec_request.get_gas_price(), deploy);
+            if let Err(error) = deploy_gas_limit(self.config(), deploy, gas_price) {
+                log_duration!(
+                    correlation_id,
+                    METRIC_DURATION_EXEC,
+                    TAG_RESPONSE_EXEC,
+                    start.elapsed(),
+                );
+                return grpc::SingleResponse::err(invalid_argument_error(format!(
+                    "deploy {}: {}",
+                    index, error
+                )));
+            }
+        }
 
-        let preprocessor: WasmiPreprocessor = WasmiPreprocessor::new(wasm_costs);
+        let executor = WasmiExecutor::new()
+            .with_timeout(self.config().get_execution_timeout())
+            .with_deadline(deadline)
+            .with_max_memory_pages(self.config().get_max_memory_pages())
+            .with_tracing(exec_request.get_execution_tracing());
 
-        let executor = WasmiExecutor;
+        let gas_profiling = is_gas_profiling(self.config(), &exec_request);
 
-        let deploys_result: Result<Vec<ipc::DeployResult>, ipc::RootNotFound> = run_deploys(
-            &self,
-            &executor,
-            &preprocessor,
-            prestate_hash,
-            blocktime,
-            deploys,
-            protocol_version,
-            correlation_id,
-        );
+        // deploys run before against the same prestate are answered from the dedup cache, or
+        // from the execution cache if the same content was run before
+        let content_hashes: Vec<Option<Blake2bHash>> = deploys
+            .iter()
+            .map(|deploy| deploy_content_hash(&self, &exec_request, gas_profiling, deploy))
+            .collect();
+        let cached_results: Vec<Option<ipc::DeployResult>> = deploys
+            .iter()
+            .zip(&content_hashes)
+            .map(|(deploy, content_hash)| {
+                cached_deploy_result(&self, prestate_hash, deploy)
+                    .or_else(|| cached_exec_result(&self, prestate_hash, *content_hash))
+            })
+            .collect();
+        let (uncached_deploys, uncached_content_hashes): (Vec<&ipc::Deploy>, Vec<_>) = deploys
+            .iter()
+            .zip(content_hashes)
+            .zip(&cached_results)
+            .filter(|(_, cached_result)| cached_result.is_none())
+            .map(|(deploy_and_content_hash, _)| deploy_and_content_hash)
+            .unzip();
+
+        let deploys_result: Result<Vec<ExecutionResult>, RootNotFound> = if gas_profiling {
+            run_deploys(
+                &self,
+                &executor,
+                &GasProfilingPreprocessor::new(preprocessor),
+                prestate_hash,
+                blocktime,
+                &uncached_deploys,
+                exec_request.get_gas_price(),
+                protocol_version,
+                correlation_id,
+                deadline,
+            )
+        } else {
+            run_deploys(
+                &self,
+                &executor,
+                &self.caching_preprocessor(preprocessor, protocol_version.value),
+                prestate_hash,
+                blocktime,
+                &uncached_deploys,
+                exec_request.get_gas_price(),
+                protocol_version,
+                correlation_id,
+                deadline,
+            )
+        };
+
+        if is_past_deadline(deadline) {
+            log_duration!(
+                correlation_id,
+                METRIC_DURATION_EXEC,
+                TAG_RESPONSE_EXEC,
+                start.elapsed(),
+            );
+            return grpc::SingleResponse::err(deadline_exceeded_error());
+        }
 
         let exec_response = match deploys_result {
-            Ok(deploy_results) => {
+            Ok(ref execution_results) if execution_results.iter().any(is_readers_full_failure) => {
+                log_duration!(
+                    correlation_id,
+                    METRIC_DURATION_EXEC,
+                    TAG_RESPONSE_EXEC,
+                    start.elapsed(),
+                );
+                return grpc::SingleResponse::err(readers_full_error());
+            }
+            Ok(ref execution_results) if execution_results.iter().any(is_malformed_module) => {
+                // the results line up with the deploys which were not answered from a cache
+                let (index, error) = cached_results
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, cached_result)| cached_result.is_none())
+                    .map(|(index, _)| index)
+                    .zip(execution_results)
+                    .find_map(|(index, execution_result)| {
+                        malformed_module(execution_result).map(|error| (index, error))
+                    })
+                    .expect("a result was malformed");
+                log_duration!(
+                    correlation_id,
+                    METRIC_DURATION_EXEC,
+                    TAG_RESPONSE_EXEC,
+                    start.elapsed(),
+                );
+                return grpc::SingleResponse::err(malformed_module_error(index, error));
+            }
+            Ok(execution_results) => {
+                let reusable: Vec<bool> = execution_results.iter().map(is_reusable).collect();
+                let executed_results: Vec<ipc::DeployResult> =
+                    execution_results.into_iter().map(Into::into).collect();
+                metrics::record_deploy_results(&executed_results);
+                for (((deploy, content_hash), deploy_result), reusable) in uncached_deploys
+                    .iter()
+                    .zip(uncached_content_hashes)
+                    .zip(&executed_results)
+                    .zip(reusable)
+                {
+                    // a resent deploy which failed this way is run again rather than answered
+                    if !reusable {
+                        continue;
+                    }
+                    cache_deploy_result(&self, prestate_hash, deploy, deploy_result);
+                    if let Some(content_hash) = content_hash {
+                        cache_exec_result(&self, prestate_hash, content_hash, deploy_result);
+                    }
+                }
+                let mut executed_results = executed_results.into_iter();
+                let mut deploy_results: Vec<ipc::DeployResult> = cached_results
+                    .into_iter()
+                    .filter_map(|cached_result| cached_result.or_else(|| executed_results.next()))
+                    .collect();
+                // results are cached at a gas price of 1, as the price may differ between requests
+                for (index, (deploy, deploy_result)) in
+                    deploys.iter().zip(deploy_results.iter_mut()).enumerate()
+                {
+                    let gas_price = deploy_gas_price(exec_request.get_gas_price(), deploy);
+                    if let Err(error) = charge_deploy_result(index, deploy_result, gas_price) {
+                        log_duration!(
+                            correlation_id,
+                            METRIC_DURATION_EXEC,
+                            TAG_RESPONSE_EXEC,
+                            start.elapsed(),
+                        );
+                        return grpc::SingleResponse::err(error);
+                    }
+                }
                 let mut exec_response = ipc::ExecResponse::new();
                 let mut exec_result = ipc::ExecResult::new();
                 exec_result.set_deploy_results(protobuf::RepeatedField::from_vec(deploy_results));
@@ -191,14 +491,15 @@ where
                 exec_response
             }
             Err(error) => {
-                logging::log_error("deploy results error: RootNotFound");
+                log_error!("deploy results error: RootNotFound");
                 let mut exec_response = ipc::ExecResponse::new();
-                exec_response.set_missing_parent(error);
+                exec_response.set_missing_parent(error.into());
+                exec_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
                 exec_response
             }
         };
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_EXEC,
             TAG_RESPONSE_EXEC,
@@ -208,13 +509,106 @@ where
         grpc::SingleResponse::completed(exec_response)
     }
 
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        let deadline = request_deadline(&request_options, Instant::now());
+        if self.config().is_read_only() {
+            return grpc::StreamingResponse::err(read_only_error());
+        }
+
+        if let Err(error) = check_deploy_count(self.config(), exec_request.get_deploys().len()) {
+            return grpc::StreamingResponse::err(error);
+        }
+
+        let protocol_version = exec_request.get_protocol_version().value;
+        let preprocessor = match get_preprocessor(self.config(), protocol_version) {
+            Some(preprocessor) => preprocessor,
+            None => {
+                return grpc::StreamingResponse::err(invalid_argument_error(format!(
+                    "unsupported protocol version {}",
+                    protocol_version
+                )))
+            }
+        };
+
+        for (index, deploy) in exec_request.get_deploys().iter().enumerate() {
+            if let Err(error) = check_payment_code(&preprocessor, deploy) {
+                return grpc::StreamingResponse::err(malformed_payment_error(index, &error));
+            }
+            let gas_price = deploy_gas_price(exec_request.get_gas_price(), deploy);
+            if let Err(error) = deploy_gas_limit(self.config(), deploy, gas_price) {
+                return grpc::StreamingResponse::err(invalid_argument_error(format!(
+                    "deploy {}: {}",
+                    index, error
+                )));
+            }
+        }
+
+        let prestate_hash = match parse_state_hash(exec_request.get_parent_state_hash()) {
+            Ok(prestate_hash) => prestate_hash,
+            Err(message) => return grpc::StreamingResponse::err(invalid_argument_error(message)),
+        };
+
+        let correlation_id = request_correlation_id(&request_options);
+
+        // The deploys are run on their own thread so that each result can be sent while the
+        // next deploy runs.  The channel is bounded, so a slow client holds up execution rather
+        // than letting results pile up in memory.
+        let (sender, receiver) = mpsc::channel(EXEC_STREAM_BUFFER_SIZE);
+        let engine_state = self.clone();
+        if is_gas_profiling(self.config(), &exec_request) {
+            let preprocessor = GasProfilingPreprocessor::new(preprocessor);
+            thread::spawn(move || {
+                panic_boundary::catch_stream_panic("exec_stream", sender, |sender| {
+                    stream_deploys(
+                        &engine_state,
+                        &preprocessor,
+                        prestate_hash,
+                        &exec_request,
+                        correlation_id,
+                        deadline,
+                        sender,
+                    )
+                })
+            });
+        } else {
+            let preprocessor = self.caching_preprocessor(preprocessor, protocol_version);
+            thread::spawn(move || {
+                panic_boundary::catch_stream_panic("exec_stream", sender, |sender| {
+                    stream_deploys(
+                        &engine_state,
+                        &preprocessor,
+                        prestate_hash,
+                        &exec_request,
+                        correlation_id,
+                        deadline,
+                        sender,
+                    )
+                })
+            });
+        }
+
+        grpc::StreamingResponse::no_metadata(receiver.then(|item| match item {
+            Ok(exec_stream_response) => exec_stream_response,
+            Err(()) => Err(grpc::Error::Other("exec stream closed unexpectedly")),
+        }))
+    }
+
     fn commit(
         &self,
-        _request_options: ::grpc::RequestOptions,
+        request_options: ::grpc::RequestOptions,
         commit_request: ipc::CommitRequest,
     ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        if self.config().is_read_only() {
+            return grpc::SingleResponse::err(read_only_error());
+        }
+
         let start = Instant::now();
-        let correlation_id = CorrelationId::new();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
 
         // TODO: don't unwrap
         let prestate_hash: Blake2bHash = commit_request.get_prestate_hash().try_into().unwrap();
@@ -224,20 +618,38 @@ where
 
         let commit_response = match effects_result {
             Err(ParsingError(error_message)) => {
-                logging::log_error(&error_message);
+                log_error!(&error_message);
                 let mut commit_response = ipc::CommitResponse::new();
                 let mut err = ipc::PostEffectsError::new();
                 err.set_message(error_message);
                 commit_response.set_failed_transform(err);
+                commit_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
                 commit_response
             }
 
             Ok(effects) => {
-                let commit_result =
-                    self.apply_effect(correlation_id, prestate_hash, effects.value());
-                if let Ok(engine_storage::global_state::CommitResult::Success(poststate_hash)) =
-                    commit_result
+                let effects = effects.value();
+                let commit_result = retry_commit(self.config().get_commit_retries(), || {
+                    self.apply_effect(correlation_id, prestate_hash, effects.clone())
+                        .map_err(EngineError::from)
+                });
+                if let Err(EngineError::StorageError(ref error)) = commit_result {
+                    if error.is_out_of_space() {
+                        log_duration!(
+                            correlation_id,
+                            METRIC_DURATION_COMMIT,
+                            TAG_RESPONSE_COMMIT,
+                            start.elapsed(),
+                        );
+                        return grpc::SingleResponse::err(out_of_space_error(error));
+                    }
+                }
+                if let Ok(CommitResult::Success {
+                    state_root: poststate_hash,
+                    stats,
+                }) = commit_result
                 {
+                    metrics::COMMITS.increment();
                     let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
                     let bonded_validators_res = get_bonded_validators(
                         self.state(),
@@ -248,17 +660,17 @@ where
                     bonded_validators_and_commit_result(
                         prestate_hash,
                         poststate_hash,
-                        commit_result,
+                        stats,
                         bonded_validators_res,
                     )
                 } else {
                     // Commit unsuccessful.
-                    grpc_response_from_commit_result::<H>(prestate_hash, commit_result)
+                    grpc_response_from_commit_result(prestate_hash, commit_result)
                 }
             }
         };
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_COMMIT,
             TAG_RESPONSE_COMMIT,
@@ -268,13 +680,75 @@ where
         grpc::SingleResponse::completed(commit_response)
     }
 
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        if self.config().is_read_only() {
+            return grpc::SingleResponse::err(read_only_error());
+        }
+
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        // TODO: don't unwrap
+        let prestate_hash: Blake2bHash =
+            commit_batch_request.get_prestate_hash().try_into().unwrap();
+
+        let effects_result: Result<Vec<CommitTransforms>, ParsingError> = commit_batch_request
+            .get_deploy_effects()
+            .iter()
+            .map(|deploy_effects| deploy_effects.get_effects().try_into())
+            .collect();
+
+        let commit_response = match effects_result {
+            Err(ParsingError(error_message)) => {
+                log_error!(&error_message);
+                let mut commit_response = ipc::CommitResponse::new();
+                let mut err = ipc::PostEffectsError::new();
+                err.set_message(error_message);
+                commit_response.set_failed_transform(err);
+                commit_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
+                commit_response
+            }
+
+            Ok(effects) => {
+                let effects = effects.into_iter().map(CommitTransforms::value).collect();
+                match commit_effects_batch(self, correlation_id, prestate_hash, effects) {
+                    Ok(commit_response) => commit_response,
+                    Err(error) => {
+                        log_duration!(
+                            correlation_id,
+                            METRIC_DURATION_COMMIT_BATCH,
+                            TAG_RESPONSE_COMMIT_BATCH,
+                            start.elapsed(),
+                        );
+                        return grpc::SingleResponse::err(error);
+                    }
+                }
+            }
+        };
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_COMMIT_BATCH,
+            TAG_RESPONSE_COMMIT_BATCH,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(commit_response)
+    }
+
     fn validate(
         &self,
-        _request_options: ::grpc::RequestOptions,
+        request_options: ::grpc::RequestOptions,
         validate_request: ipc::ValidateRequest,
     ) -> grpc::SingleResponse<ipc::ValidateResponse> {
         let start = Instant::now();
-        let correlation_id = CorrelationId::new();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
 
         let pay_mod = wabt::Module::read_binary(
             validate_request.payment_code,
@@ -282,7 +756,7 @@ where
         )
         .and_then(|x| x.validate());
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_VALIDATE,
             "pay_mod",
@@ -295,7 +769,7 @@ where
         )
         .and_then(|x| x.validate());
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_VALIDATE,
             "ses_mod",
@@ -310,15 +784,16 @@ where
             }
             Err(cause) => {
                 let cause_msg = cause.to_string();
-                logging::log_error(&cause_msg);
+                log_error!(&cause_msg);
 
                 let mut validate_result = ipc::ValidateResponse::new();
                 validate_result.set_failure(cause_msg);
+                validate_result.set_error_code(ipc::ErrorCode::INVALID_WASM);
                 validate_result
             }
         };
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_VALIDATE,
             TAG_RESPONSE_VALIDATE,
@@ -331,25 +806,31 @@ where
     #[allow(dead_code)]
     fn run_genesis(
         &self,
-        _request_options: ::grpc::RequestOptions,
+        request_options: ::grpc::RequestOptions,
         genesis_request: ipc::GenesisRequest,
     ) -> ::grpc::SingleResponse<ipc::GenesisResponse> {
+        if self.config().is_read_only() {
+            return grpc::SingleResponse::err(read_only_error());
+        }
+
         let start = Instant::now();
-        let correlation_id = CorrelationId::new();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
 
         let genesis_account_addr = {
             let address = genesis_request.get_address();
             if address.len() != 32 {
                 let err_msg =
                     "genesis account public key has to be exactly 32 bytes long.".to_string();
-                logging::log_error(&err_msg);
+                log_error!(&err_msg);
 
                 let mut genesis_response = ipc::GenesisResponse::new();
                 let mut genesis_deploy_error = ipc::GenesisDeployError::new();
                 genesis_deploy_error.set_message(err_msg);
                 genesis_response.set_failed_deploy(genesis_deploy_error);
+                genesis_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
 
-                log_duration(
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_GENESIS,
                     TAG_RESPONSE_GENESIS,
@@ -368,14 +849,15 @@ where
             Ok(initial_tokens) => initial_tokens,
             Err(err) => {
                 let err_msg = format!("{:?}", err);
-                logging::log_error(&err_msg);
+                log_error!(&err_msg);
 
                 let mut genesis_response = ipc::GenesisResponse::new();
                 let mut genesis_deploy_error = ipc::GenesisDeployError::new();
                 genesis_deploy_error.set_message(err_msg);
                 genesis_response.set_failed_deploy(genesis_deploy_error);
+                genesis_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
 
-                log_duration(
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_GENESIS,
                     TAG_RESPONSE_GENESIS,
@@ -395,7 +877,7 @@ where
             .iter()
             .map(|bond| {
                 to_domain_validators(bond).map_err(|err_msg| {
-                    logging::log_error(&err_msg);
+                    log_error!(&err_msg);
                     let mut genesis_deploy_error = ipc::GenesisDeployError::new();
                     genesis_deploy_error.set_message(err_msg);
                     genesis_deploy_error
@@ -408,8 +890,9 @@ where
             Err(genesis_error) => {
                 let mut genesis_response = ipc::GenesisResponse::new();
                 genesis_response.set_failed_deploy(genesis_error);
+                genesis_response.set_error_code(ipc::ErrorCode::INVALID_ARGUMENT);
 
-                log_duration(
+                log_duration!(
                     correlation_id,
                     METRIC_DURATION_GENESIS,
                     TAG_RESPONSE_GENESIS,
@@ -436,7 +919,7 @@ where
                 effect,
             }) => {
                 let success_message = format!("run_genesis successful: {}", post_state_hash);
-                log_info(&success_message);
+                log_info!(&success_message);
 
                 let mut genesis_response = ipc::GenesisResponse::new();
                 let mut genesis_result = ipc::GenesisResult::new();
@@ -447,27 +930,29 @@ where
             }
             Ok(genesis_result) => {
                 let err_msg = genesis_result.to_string();
-                logging::log_error(&err_msg);
+                log_error!(&err_msg);
 
                 let mut genesis_response = ipc::GenesisResponse::new();
                 let mut genesis_deploy_error = ipc::GenesisDeployError::new();
                 genesis_deploy_error.set_message(err_msg);
                 genesis_response.set_failed_deploy(genesis_deploy_error);
+                genesis_response.set_error_code(ipc::ErrorCode::INTERNAL);
                 genesis_response
             }
             Err(err) => {
                 let err_msg = err.to_string();
-                logging::log_error(&err_msg);
+                log_error!(&err_msg);
 
                 let mut genesis_response = ipc::GenesisResponse::new();
                 let mut genesis_deploy_error = ipc::GenesisDeployError::new();
                 genesis_deploy_error.set_message(err_msg);
                 genesis_response.set_failed_deploy(genesis_deploy_error);
+                genesis_response.set_error_code(error_code(&err));
                 genesis_response
             }
         };
 
-        log_duration(
+        log_duration!(
             correlation_id,
             METRIC_DURATION_GENESIS,
             TAG_RESPONSE_GENESIS,
@@ -476,174 +961,785 @@ where
 
         grpc::SingleResponse::completed(genesis_response)
     }
-}
 
-#[allow(clippy::too_many_arguments)]
-fn run_deploys<A, H, E, P>(
-    engine_state: &EngineState<H>,
-    executor: &E,
-    preprocessor: &P,
-    prestate_hash: Blake2bHash,
-    blocktime: BlockTime,
-    deploys: &[ipc::Deploy],
-    protocol_version: &state::ProtocolVersion,
-    correlation_id: CorrelationId,
-) -> Result<Vec<ipc::DeployResult>, ipc::RootNotFound>
-where
-    H: History,
-    E: Executor<A>,
-    P: Preprocessor<A>,
-    EngineError: From<H::Error>,
-    H::Error: Into<engine_core::execution::Error>,
-{
-    // We want to treat RootNotFound error differently b/c it should short-circuit
-    // the execution of ALL deploys within the block. This is because all of them share
-    // the same prestate and all of them would fail.
-    // Iterator (Result<_, _> + collect()) will short circuit the execution
-    // when run_deploy returns Err.
-    deploys
-        .iter()
-        .map(|deploy| {
-            let session_contract = deploy.get_session();
-            let module_bytes = &session_contract.code;
-            let args = &session_contract.args;
-            let address = {
-                let address_len = deploy.address.len();
-                if address_len != EXPECTED_PUBLIC_KEY_LENGTH {
-                    let err = EngineError::InvalidPublicKeyLength {
-                        expected: EXPECTED_PUBLIC_KEY_LENGTH,
-                        actual: address_len,
-                    };
-                    let failure = ExecutionResult::precondition_failure(err);
-                    return Ok(failure.into());
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut store_stats_response = ipc::GetStoreStatsResponse::new();
+
+        match self.store_stats() {
+            Ok(Some(stats)) => store_stats_response.set_success(stats.into()),
+            Ok(None) => {
+                log_warning!(STORE_STATS_UNSUPPORTED_MESSAGE);
+                store_stats_response.set_failure(STORE_STATS_UNSUPPORTED_MESSAGE.to_string());
+                store_stats_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+            Err(err) => {
+                let err_msg = err.to_string();
+                log_error!(&err_msg);
+                store_stats_response.set_failure(err_msg);
+                store_stats_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_STORE_STATS,
+            TAG_RESPONSE_STORE_STATS,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(store_stats_response)
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let offset = list_state_roots_request.get_offset();
+        let limit = match list_state_roots_request.get_limit() {
+            0 => MAX_LIST_STATE_ROOTS_LIMIT,
+            limit => limit.min(MAX_LIST_STATE_ROOTS_LIMIT),
+        };
+
+        let mut list_state_roots_response = ipc::ListStateRootsResponse::new();
+
+        match self.state_roots(offset, limit) {
+            Ok(Some(state_roots)) => {
+                let mut success = ipc::ListStateRootsResponse_StateRoots::new();
+                success.set_state_hashes(
+                    state_roots
+                        .into_iter()
+                        .map(|state_root| state_root.to_vec())
+                        .collect(),
+                );
+                list_state_roots_response.set_success(success);
+            }
+            Ok(None) => {
+                log_warning!(STATE_ROOTS_UNSUPPORTED_MESSAGE);
+                list_state_roots_response.set_failure(STATE_ROOTS_UNSUPPORTED_MESSAGE.to_string());
+                list_state_roots_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+            Err(err) => {
+                let err_msg = err.to_string();
+                log_error!(&err_msg);
+                list_state_roots_response.set_failure(err_msg);
+                list_state_roots_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_LIST_STATE_ROOTS,
+            TAG_RESPONSE_LIST_STATE_ROOTS,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(list_state_roots_response)
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut backup_store_response = ipc::BackupStoreResponse::new();
+
+        let path = backup_store_request.get_path();
+        let result = if path.is_empty() {
+            Err(invalid_argument_failure(BACKUP_PATH_REQUIRED_MESSAGE))
+        } else {
+            match self.copy_store_to(Path::new(path), backup_store_request.get_compact()) {
+                Ok(Some(size)) => Ok(size),
+                Ok(None) => Err(internal_failure(BACKUP_UNSUPPORTED_MESSAGE)),
+                Err(err) => Err(internal_failure(err)),
+            }
+        };
+
+        match result {
+            Ok(size) => {
+                log_info!(&format!(
+                    "backed up global state to {} ({} bytes)",
+                    path, size
+                ));
+                let mut backup_result = ipc::BackupStoreResponse_BackupResult::new();
+                backup_result.set_size(size);
+                backup_store_response.set_success(backup_result);
+            }
+            Err((error_code, err_msg)) => {
+                log_error!(&err_msg);
+                backup_store_response.set_failure(err_msg);
+                backup_store_response.set_error_code(error_code);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_BACKUP_STORE,
+            TAG_RESPONSE_BACKUP_STORE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(backup_store_response)
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut flush_store_response = ipc::FlushStoreResponse::new();
+
+        match self.flush_store() {
+            Ok(()) => {
+                log_info!("flushed global state to disk");
+                flush_store_response.set_success(ipc::FlushStoreResponse_Flushed::new());
+            }
+            Err(err) => {
+                let err_msg = err.to_string();
+                log_error!(&err_msg);
+                flush_store_response.set_failure(err_msg);
+                flush_store_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_FLUSH_STORE,
+            TAG_RESPONSE_FLUSH_STORE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(flush_store_response)
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut get_engine_version_response = ipc::GetEngineVersionResponse::new();
+        get_engine_version_response.set_version(env!("CARGO_PKG_VERSION").to_string());
+        get_engine_version_response.set_git_commit_hash(env!("GIT_COMMIT_HASH").to_string());
+        get_engine_version_response.set_protocol_version(IPC_PROTOCOL_VERSION);
+        get_engine_version_response
+            .set_max_deploys_per_batch(self.config().get_max_deploys_per_batch() as u64);
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_GET_ENGINE_VERSION,
+            TAG_RESPONSE_GET_ENGINE_VERSION,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(get_engine_version_response)
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut get_genesis_hash_response = ipc::GetGenesisHashResponse::new();
+
+        match self.genesis_hash() {
+            Some(genesis_hash) => get_genesis_hash_response.set_genesis_hash(genesis_hash.to_vec()),
+            None => {
+                log_warning!(GENESIS_HASH_UNKNOWN_MESSAGE);
+                get_genesis_hash_response.set_failure(GENESIS_HASH_UNKNOWN_MESSAGE.to_string());
+                get_genesis_hash_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_GET_GENESIS_HASH,
+            TAG_RESPONSE_GET_GENESIS_HASH,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(get_genesis_hash_response)
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let deploy = validate_deploy_request.get_deploy();
+        let mut problems = Vec::new();
+
+        if let Err(error) = deploy_account_key(deploy) {
+            problems.push(validation_problem(
+                "deploy.address",
+                ipc::ErrorCode::INVALID_ARGUMENT,
+                error,
+            ));
+        }
+
+        if let Err(error) = deploy_authorized_keys(deploy) {
+            problems.push(validation_problem(
+                "deploy.authorization_keys",
+                ipc::ErrorCode::INVALID_ARGUMENT,
+                error,
+            ));
+        }
+
+        if let Err(error) = deploy_gas_limit(self.config(), deploy, deploy.gas_price) {
+            problems.push(validation_problem(
+                error.field(),
+                ipc::ErrorCode::INVALID_ARGUMENT,
+                error,
+            ));
+        }
+
+        let protocol_version = validate_deploy_request.get_protocol_version().value;
+        match get_preprocessor(self.config(), protocol_version) {
+            Some(preprocessor) => {
+                if let Err(error) = preprocessor.preprocess(&deploy.get_session().code) {
+                    problems.push(validation_problem(
+                        "deploy.session.code",
+                        ipc::ErrorCode::INVALID_WASM,
+                        error,
+                    ));
                 }
-                let mut dest = [0; EXPECTED_PUBLIC_KEY_LENGTH];
-                dest.copy_from_slice(&deploy.address);
-                Key::Account(dest)
-            };
-
-            // Parse all authorization keys from IPC into a vector
-            let authorized_keys: BTreeSet<PublicKey> = {
-                let maybe_keys: Result<BTreeSet<_>, EngineError> = deploy
-                    .authorization_keys
-                    .iter()
-                    .map(|key_bytes| {
-                        // Try to convert an element of bytes into a possibly
-                        // valid PublicKey with error handling
-                        PublicKey::try_from(key_bytes.as_slice()).map_err(|_| {
-                            EngineError::InvalidPublicKeyLength {
-                                expected: EXPECTED_PUBLIC_KEY_LENGTH,
-                                actual: key_bytes.len(),
-                            }
-                        })
-                    })
-                    .collect();
+            }
+            None => problems.push(validation_problem(
+                "protocol_version",
+                ipc::ErrorCode::INVALID_ARGUMENT,
+                format!("unsupported protocol version {}", protocol_version),
+            )),
+        }
 
-                match maybe_keys {
-                    Ok(keys) => keys,
-                    Err(error) => return Ok(ExecutionResult::precondition_failure(error).into()),
+        let parent_state_hash = validate_deploy_request.get_parent_state_hash();
+        match Blake2bHash::try_from(parent_state_hash) {
+            Err(_) => problems.push(validation_problem(
+                "parent_state_hash",
+                ipc::ErrorCode::INVALID_ARGUMENT,
+                format!(
+                    "expected {} bytes, got {}",
+                    EXPECTED_STATE_HASH_LENGTH,
+                    parent_state_hash.len()
+                ),
+            )),
+            Ok(prestate_hash) => match self.tracking_copy(prestate_hash) {
+                Ok(Some(_)) => (),
+                Ok(None) => problems.push(validation_problem(
+                    "parent_state_hash",
+                    ipc::ErrorCode::STATE_NOT_FOUND,
+                    format!("root not found: {}", prestate_hash),
+                )),
+                Err(error) => {
+                    log_error!(&error.to_string());
+                    problems.push(validation_problem(
+                        "parent_state_hash",
+                        error_code(&error),
+                        error,
+                    ));
                 }
-            };
-
-            let nonce = deploy.nonce;
-            // TODO: is the rounding in this division ok?
-            let gas_limit =
-                (deploy.motes_transferred_in_payment as u64) / (deploy.gas_price as u64);
-            let protocol_version = protocol_version.value;
-            engine_state
-                .run_deploy(
-                    module_bytes,
-                    args,
-                    address,
-                    authorized_keys,
-                    blocktime,
-                    nonce,
-                    prestate_hash,
-                    gas_limit,
-                    protocol_version,
+            },
+        }
+
+        let mut validate_deploy_response = ipc::ValidateDeployResponse::new();
+        validate_deploy_response.set_problems(protobuf::RepeatedField::from_vec(problems));
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_VALIDATE_DEPLOY,
+            TAG_RESPONSE_VALIDATE_DEPLOY,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(validate_deploy_response)
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let limit = match state_diff_request.get_limit() {
+            0 => MAX_STATE_DIFF_LIMIT,
+            limit => limit.min(MAX_STATE_DIFF_LIMIT),
+        };
+
+        let mut state_diff_response = ipc::StateDiffResponse::new();
+
+        let result = parse_state_hash(state_diff_request.get_before_state_hash())
+            .and_then(|before_root| {
+                let after_root = parse_state_hash(state_diff_request.get_after_state_hash())?;
+                Ok((before_root, after_root))
+            })
+            .map_err(invalid_argument_failure)
+            .and_then(|(before_root, after_root)| {
+                self.diff_states(correlation_id, before_root, after_root, limit as usize)
+                    .map_err(internal_failure)
+            });
+
+        match result {
+            Ok(DiffResult::Diff { diffs, truncated }) => {
+                let mut success = ipc::StateDiffResponse_StateDiff::new();
+                success.set_diffs(diffs.into_iter().map(Into::into).collect());
+                success.set_truncated(truncated);
+                state_diff_response.set_success(success);
+            }
+            Ok(DiffResult::RootNotFound(missing_root)) => {
+                log_warning!(&format!("Root not found: {}", missing_root));
+                state_diff_response.set_missing_state(RootNotFound(missing_root).into());
+                state_diff_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            }
+            Err((error_code, err_msg)) => {
+                log_error!(&err_msg);
+                state_diff_response.set_failure(err_msg);
+                state_diff_response.set_error_code(error_code);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_STATE_DIFF,
+            TAG_RESPONSE_STATE_DIFF,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(state_diff_response)
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut verify_store_response = ipc::VerifyStoreResponse::new();
+
+        let result = parse_state_hash(verify_store_request.get_state_hash())
+            .map_err(invalid_argument_failure)
+            .and_then(|root| {
+                self.verify_store(correlation_id, root)
+                    .map_err(internal_failure)
+            });
+
+        match result {
+            Ok(VerifyResult::Verified { node_count }) => {
+                let mut success = ipc::VerifyStoreResponse_Verified::new();
+                success.set_node_count(node_count);
+                verify_store_response.set_success(success);
+            }
+            Ok(VerifyResult::RootNotFound(missing_root)) => {
+                log_warning!(&format!("Root not found: {}", missing_root));
+                verify_store_response.set_missing_state(RootNotFound(missing_root).into());
+                verify_store_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            }
+            Ok(VerifyResult::MissingNode { parent, hash }) => {
+                let message = format!("Node {} is missing; it is referenced by {}", hash, parent);
+                log_error!(&message);
+                let mut corrupted = ipc::VerifyStoreResponse_Corrupted::new();
+                corrupted.set_node_hash(hash.to_vec());
+                corrupted.set_parent_hash(parent.to_vec());
+                corrupted.set_message(message);
+                verify_store_response.set_corrupted(corrupted);
+                verify_store_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+            Ok(VerifyResult::HashMismatch { hash, actual }) => {
+                let message = format!("Node stored at {} hashes to {}", hash, actual);
+                log_error!(&message);
+                let mut corrupted = ipc::VerifyStoreResponse_Corrupted::new();
+                corrupted.set_node_hash(hash.to_vec());
+                corrupted.set_message(message);
+                verify_store_response.set_corrupted(corrupted);
+                verify_store_response.set_error_code(ipc::ErrorCode::INTERNAL);
+            }
+            Err((error_code, err_msg)) => {
+                log_error!(&err_msg);
+                verify_store_response.set_failure(err_msg);
+                verify_store_response.set_error_code(error_code);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_VERIFY_STORE,
+            TAG_RESPONSE_VERIFY_STORE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(verify_store_response)
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        if self.config().is_read_only() {
+            return grpc::SingleResponse::err(read_only_error());
+        }
+
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut prune_state_response = ipc::PruneStateResponse::new();
+
+        let retain_state_hashes = prune_state_request.get_retain_state_hashes();
+        let result = if retain_state_hashes.is_empty() {
+            Err(invalid_argument_failure(PRUNE_RETAIN_REQUIRED_MESSAGE))
+        } else {
+            retain_state_hashes
+                .iter()
+                .map(|state_hash| parse_state_hash(state_hash))
+                .collect::<Result<Vec<Blake2bHash>, String>>()
+                .map_err(invalid_argument_failure)
+                .and_then(|retain| match self.prune_store(correlation_id, &retain) {
+                    Ok(Some(prune_result)) => Ok(prune_result),
+                    Ok(None) => Err(internal_failure(PRUNE_UNSUPPORTED_MESSAGE)),
+                    Err(err) => Err(internal_failure(err)),
+                })
+        };
+
+        match result {
+            Ok(PruneResult::Pruned {
+                nodes_kept,
+                nodes_deleted,
+                bytes_deleted,
+            }) => {
+                log_info!(&format!(
+                    "pruned global state: deleted {} trie nodes ({} bytes), kept {}",
+                    nodes_deleted, bytes_deleted, nodes_kept
+                ));
+                let mut pruned = ipc::PruneStateResponse_Pruned::new();
+                pruned.set_nodes_kept(nodes_kept);
+                pruned.set_nodes_deleted(nodes_deleted);
+                pruned.set_bytes_deleted(bytes_deleted);
+                prune_state_response.set_success(pruned);
+            }
+            Ok(PruneResult::RootNotFound(missing_root)) => {
+                log_warning!(&format!("Root not found: {}", missing_root));
+                prune_state_response.set_missing_state(RootNotFound(missing_root).into());
+                prune_state_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            }
+            Err((error_code, err_msg)) => {
+                log_error!(&err_msg);
+                prune_state_response.set_failure(err_msg);
+                prune_state_response.set_error_code(error_code);
+            }
+        }
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_PRUNE_STATE,
+            TAG_RESPONSE_PRUNE_STATE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(prune_state_response)
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let get_capabilities_response = capabilities(self.config());
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_GET_CAPABILITIES,
+            TAG_RESPONSE_GET_CAPABILITIES,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(get_capabilities_response)
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+        let deadline = request_deadline(&request_options, start);
+
+        let estimate_gas_result =
+            estimate_deploy_gas(&self, &estimate_gas_request, correlation_id, deadline);
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_ESTIMATE_GAS,
+            TAG_RESPONSE_ESTIMATE_GAS,
+            start.elapsed(),
+        );
+
+        match estimate_gas_result {
+            Ok(estimate_gas_response) => grpc::SingleResponse::completed(estimate_gas_response),
+            Err(error) => grpc::SingleResponse::err(error),
+        }
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        let root = match parse_state_hash(export_state_request.get_state_hash()) {
+            Ok(root) => root,
+            Err(message) => return grpc::StreamingResponse::err(invalid_argument_error(message)),
+        };
+
+        let chunk_size = match export_state_request.get_chunk_size() {
+            0 => MAX_EXPORT_STATE_CHUNK_SIZE,
+            chunk_size => chunk_size.min(MAX_EXPORT_STATE_CHUNK_SIZE),
+        };
+
+        let correlation_id = request_correlation_id(&request_options);
+
+        // The pairs are read on their own thread and sent a chunk at a time.  The channel is
+        // bounded, so a slow client holds up reading rather than letting chunks pile up in memory.
+        let (sender, receiver) = mpsc::channel(EXPORT_STATE_BUFFER_SIZE);
+        let engine_state = self.clone();
+        thread::spawn(move || {
+            panic_boundary::catch_stream_panic("export_state", sender, |sender| {
+                stream_pairs(
+                    &engine_state,
+                    root,
+                    chunk_size as usize,
                     correlation_id,
-                    executor,
-                    preprocessor,
+                    sender,
                 )
-                .map(Into::into)
-                .map_err(Into::into)
-        })
-        .collect()
+            })
+        });
+
+        grpc::StreamingResponse::no_metadata(receiver.then(|item| match item {
+            Ok(export_state_response) => export_state_response,
+            Err(()) => Err(grpc::Error::Other("export stream closed unexpectedly")),
+        }))
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        _get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        let start = Instant::now();
+        let correlation_id = request_correlation_id(&request_options);
+        let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+        let mut get_empty_state_root_response = ipc::GetEmptyStateRootResponse::new();
+        get_empty_state_root_response.set_empty_state_hash(self.empty_state_root().to_vec());
+
+        log_duration!(
+            correlation_id,
+            METRIC_DURATION_GET_EMPTY_STATE_ROOT,
+            TAG_RESPONSE_GET_EMPTY_STATE_ROOT,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(get_empty_state_root_response)
+    }
+}
+
+/// Lists the optional features enabled by `config` and the limits it sets on requests.
+fn capabilities(config: &EngineConfig) -> ipc::GetCapabilitiesResponse {
+    let features = [
+        (ipc::Feature::TLS, config.is_tls_enabled()),
+        (ipc::Feature::METRICS, config.is_metrics_enabled()),
+        (ipc::Feature::PRUNING, !config.is_read_only()),
+        (ipc::Feature::READ_ONLY, config.is_read_only()),
+        (ipc::Feature::GAS_PROFILING, config.is_gas_profiling()),
+        (ipc::Feature::DEDUP_CACHE, config.get_dedup_cache_size() > 0),
+        (ipc::Feature::EXEC_CACHE, config.get_exec_cache_size() > 0),
+        (ipc::Feature::WASM_CACHE, config.get_wasm_cache_size() > 0),
+    ];
+
+    let mut response = ipc::GetCapabilitiesResponse::new();
+    for (feature, enabled) in &features {
+        if *enabled {
+            response.mut_features().push(*feature);
+        }
+    }
+    response.set_protocol_version(IPC_PROTOCOL_VERSION);
+    response.set_max_gas_limit(config.get_max_gas_limit().unwrap_or(0));
+    response.set_default_gas_limit(config.get_default_gas_limit().unwrap_or(0));
+    response.set_max_deploys_per_batch(config.get_max_deploys_per_batch() as u64);
+    response.set_max_wasm_size(config.get_max_wasm_size() as u64);
+    response.set_max_memory_pages(config.get_max_memory_pages());
+    response.set_execution_timeout_ms(
+        config
+            .get_execution_timeout()
+            .map_or(0, |timeout| timeout.as_millis() as u64),
+    );
+    response
 }
 
-// TODO: Refactor.
-#[allow(clippy::implicit_hasher)]
-pub fn bonded_validators_and_commit_result<H>(
-    prestate_hash: Blake2bHash,
-    poststate_hash: Blake2bHash,
-    commit_result: Result<CommitResult, H::Error>,
-    bonded_validators: Result<HashMap<PublicKey, U512>, GetBondedValidatorsError<H>>,
-) -> CommitResponse
+/// Keeps `guard` until `response` is complete, rather than only while the handler which returned
+/// it runs, as the response may still be waiting for a thread to run on.
+fn hold_until_complete<T, G>(response: grpc::SingleResponse<T>, guard: G) -> grpc::SingleResponse<T>
 where
-    H: History,
-    H::Error: Into<EngineError> + std::fmt::Debug,
+    T: Send + 'static,
+    G: Send + 'static,
 {
-    match bonded_validators {
-        Ok(bonded_validators) => {
-            let mut grpc_response =
-                grpc_response_from_commit_result::<H>(prestate_hash, commit_result);
-            let grpc_bonded_validators = bonded_validators
-                .iter()
-                .map(|(pk, bond)| {
-                    let mut ipc_bond = ipc::Bond::new();
-                    ipc_bond.set_stake((*bond).into());
-                    ipc_bond.set_validator_public_key(pk.value().to_vec());
-                    ipc_bond
-                })
-                .collect::<Vec<ipc::Bond>>()
-                .into();
-            grpc_response
-                .mut_success() // We know it's a success because of the check few lines earlier.
-                .set_bonded_validators(grpc_bonded_validators);
-            grpc_response
-        }
-        Err(GetBondedValidatorsError::StorageErrors(error)) => {
-            grpc_response_from_commit_result::<H>(poststate_hash, Err(error))
-        }
-        Err(GetBondedValidatorsError::PostStateHashNotFound(root_hash)) => {
-            // I am not sure how to parse this error. It would mean that most probably
-            // we have screwed up something in the trie store because `root_hash` was
-            // calculated by us just a moment ago. It [root_hash] is a `poststate_hash` we return to the node.
-            // There is no proper error variant in the `engine_storage::error::Error` for it though.
-            let error_message = format!(
-                "Post state hash not found {} when calculating bonded validators set.",
-                root_hash
-            );
-            logging::log_error(&error_message);
-            let mut commit_response = ipc::CommitResponse::new();
-            let mut err = ipc::PostEffectsError::new();
-            err.set_message(error_message);
-            commit_response.set_failed_transform(err);
-            commit_response
-        }
-        Err(GetBondedValidatorsError::PoSNotFound(key)) => grpc_response_from_commit_result::<H>(
-            poststate_hash,
-            Ok(CommitResult::KeyNotFound(key)),
-        ),
+    grpc::SingleResponse::no_metadata(response.drop_metadata().then(move |result| {
+        drop(guard);
+        result
+    }))
+}
+
+#[derive(Debug)]
+pub enum ServerError {
+    Tls(ErrorStack),
+    Grpc(grpc::Error),
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServerError::Tls(error) => write!(f, "TLS setup failed: {}", error),
+            ServerError::Grpc(error) => write!(f, "gRPC server setup failed: {:?}", error),
+        }
     }
 }
 
-// Helper method which returns single DeployResult that is set to be a WasmError.
+/// Builds a gRPC server listening on each of `sockets`, configured according to `server_config`.
+/// All of them serve the same `e`.
+///
+/// Besides `e`, each server hosts the `grpc.health.v1.Health` service, which reports
+/// `health_status`.  Requests to `e` are counted by `drain`, and rejected as it describes once it
+/// is started.  They are also rejected while the server's max_concurrent_requests are in flight,
+/// over all of `sockets`, and execution while `memory_guard`, if given, is over its limit.
+///
+/// With the `tokio-server` feature, requests to `e` are handled on a tokio thread pool shared by
+/// all of `sockets`, running up to the server's number of threads handlers at once.  Requests
+/// waiting for a thread count towards max_concurrent_requests and hold up a drain.
 pub fn new<E: ExecutionEngineService + Sync + Send + 'static>(
-    socket: &str,
+    sockets: &[Socket],
+    server_config: &ServerConfig,
     e: E,
-) -> grpc::ServerBuilder {
-    let socket_path = std::path::Path::new(socket);
+    health_status: HealthStatus,
+    drain: Drain,
+    memory_guard: Option<MemoryGuard>,
+) -> Result<Vec<grpc::Server>, ServerError> {
+    let e = PanicBoundary::new(e);
+    let limit = ConcurrencyLimit::new(server_config.get_max_concurrent_requests());
+    #[cfg(feature = "tokio-server")]
+    let pool = Arc::new(async_service::new_pool(server_config.threads));
+    sockets
+        .iter()
+        .map(|socket| {
+            let e = MemoryGate::new(e.clone(), memory_guard.clone());
+            // only the handlers run on the pool: a request is admitted or rejected as soon as it
+            // arrives, and counted until its response is complete, waiting for a thread included
+            #[cfg(feature = "tokio-server")]
+            let e = AsyncService::new(e, Arc::clone(&pool));
+            let e = DrainGate::new(AdmissionGate::new(e, limit.clone()), drain.clone());
+            new_server(socket, server_config, e, health_status.clone())
+        })
+        .collect()
+}
 
-    if let Err(e) = std::fs::remove_file(socket_path) {
-        if e.kind() != ErrorKind::NotFound {
-            panic!("failed to remove old socket file: {:?}", e);
+fn new_server<E: ExecutionEngineService + Sync + Send + 'static>(
+    socket: &Socket,
+    server_config: &ServerConfig,
+    e: E,
+    health_status: HealthStatus,
+) -> Result<grpc::Server, ServerError> {
+    match server_config.tls_config {
+        None => {
+            let mut server = grpc::ServerBuilder::new_plain();
+            configure(&mut server, socket, server_config, e, health_status);
+            server.build().map_err(ServerError::Grpc)
+        }
+        Some(ref tls_config) => {
+            let acceptor = tls_acceptor(tls_config).map_err(ServerError::Tls)?;
+            let mut server = grpc::ServerBuilder::<tls_api_openssl::TlsAcceptor>::new();
+            server.http.set_tls(acceptor);
+            configure(&mut server, socket, server_config, e, health_status);
+            server.build().map_err(ServerError::Grpc)
         }
     }
+}
 
-    let mut server = grpc::ServerBuilder::new_plain();
-    server.http.set_unix_addr(socket.to_owned()).unwrap();
-    server.http.set_cpu_pool_threads(1);
+fn configure<A, E>(
+    server: &mut grpc::ServerBuilder<A>,
+    socket: &Socket,
+    server_config: &ServerConfig,
+    e: E,
+    health_status: HealthStatus,
+) where
+    A: tls_api::TlsAcceptor,
+    E: ExecutionEngineService + Sync + Send + 'static,
+{
+    match socket {
+        Socket::Unix(path) => {
+            if let Err(e) = socket.remove_file() {
+                panic!("failed to remove old socket file: {:?}", e);
+            }
+            server.http.set_unix_addr(path.to_owned()).unwrap();
+        }
+        Socket::Abstract(name) => {
+            // a leading null byte places the name in the abstract namespace
+            server.http.set_unix_addr(format!("\0{}", name)).unwrap();
+        }
+        Socket::Tcp(address) => {
+            server.http.set_addr(address.as_str()).unwrap();
+        }
+    }
+    // passed to listen(); the kernel caps it at net.core.somaxconn
+    server.http.conf.backlog =
+        Some(server_config.socket_backlog.min(i32::max_value() as u32) as i32);
+    server.http.set_cpu_pool_threads(server_config.threads);
     server.add_service(ipc_grpc::ExecutionEngineServiceServer::new_service_def(e));
-    server
+    server.add_service(health_grpc::HealthServer::new_service_def(health_status));
+}
+
+fn tls_acceptor(tls_config: &TlsConfig) -> Result<tls_api_openssl::TlsAcceptor, ErrorStack> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_certificate_chain_file(&tls_config.cert_path)?;
+    builder.set_private_key_file(&tls_config.key_path, SslFiletype::PEM)?;
+    builder.check_private_key()?;
+    Ok(tls_api_openssl::TlsAcceptor(builder.build()))
 }
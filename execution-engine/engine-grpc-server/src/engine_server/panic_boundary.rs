@@ -0,0 +1,368 @@
+//! Keeps a panic while handling one request from taking down the whole server.
+//!
+//! [`PanicBoundary`] wraps the engine service and runs each of its handlers under
+//! [`panic::catch_unwind`].  A handler which panics fails its own request with an `Internal`
+//! status carrying the panic message, and every other request carries on.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use futures::sync::mpsc;
+use futures::{Future, Sink};
+
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+
+thread_local! {
+    static CATCHING_PANICS: Cell<bool> = Cell::new(false);
+}
+
+/// Returns `true` if a panic on the current thread would be caught by a [`PanicBoundary`], so
+/// that it fails a single request rather than the server.
+pub fn is_catching_panics() -> bool {
+    CATCHING_PANICS.with(Cell::get)
+}
+
+/// Runs `handler`, turning a panic into an `Internal` status naming `method`.
+///
+/// State shared between requests is left as the panicking handler left it: LMDB transactions
+/// are aborted when dropped, and the engine's locks are not poisoned by a panic.
+pub(crate) fn catch_panic<R, F>(method: &str, handler: F) -> Result<R, grpc::Error>
+where
+    F: FnOnce() -> R,
+{
+    let previous = CATCHING_PANICS.with(|catching_panics| catching_panics.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(handler));
+    CATCHING_PANICS.with(|catching_panics| catching_panics.set(previous));
+
+    result.map_err(|payload| {
+        grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+            grpc_status: grpc::GrpcStatus::Internal as i32,
+            grpc_message: format!("{} panicked: {}", method, panic_message(&*payload)),
+        })
+    })
+}
+
+/// Runs the work of a streaming handler, which goes on after the handler itself has returned,
+/// like [`catch_panic`].  If it panics, the stream ends with the `Internal` status.
+pub(crate) fn catch_stream_panic<R, F>(
+    method: &str,
+    sender: mpsc::Sender<Result<R, grpc::Error>>,
+    handler: F,
+) where
+    F: FnOnce(mpsc::Sender<Result<R, grpc::Error>>),
+{
+    let panic_sender = sender.clone();
+    if let Err(error) = catch_panic(method, || handler(sender)) {
+        // the client may have gone away already
+        let _ = panic_sender.send(Err(error)).wait();
+    }
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        *message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// Wraps an engine service so that a panic in any of its handlers fails only the request being
+/// handled.  Clones share the wrapped service, so that several servers can serve it.
+pub struct PanicBoundary<S> {
+    service: Arc<S>,
+}
+
+impl<S> PanicBoundary<S> {
+    pub fn new(service: S) -> Self {
+        PanicBoundary {
+            service: Arc::new(service),
+        }
+    }
+}
+
+impl<S> Clone for PanicBoundary<S> {
+    fn clone(&self) -> Self {
+        PanicBoundary {
+            service: Arc::clone(&self.service),
+        }
+    }
+}
+
+impl<S: ExecutionEngineService> ExecutionEngineService for PanicBoundary<S> {
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        catch_panic("exec", || self.service.exec(request_options, exec_request))
+            .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn exec_stream(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::StreamingResponse<ipc::ExecStreamResponse> {
+        catch_panic("exec_stream", || {
+            self.service.exec_stream(request_options, exec_request)
+        })
+        .unwrap_or_else(grpc::StreamingResponse::err)
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        catch_panic("commit", || {
+            self.service.commit(request_options, commit_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn commit_batch(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_batch_request: ipc::CommitBatchRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        catch_panic("commit_batch", || {
+            self.service
+                .commit_batch(request_options, commit_batch_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        catch_panic("query", || {
+            self.service.query(request_options, query_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        catch_panic("validate", || {
+            self.service.validate(request_options, validate_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        catch_panic("run_genesis", || {
+            self.service.run_genesis(request_options, genesis_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn get_store_stats(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        store_stats_request: ipc::GetStoreStatsRequest,
+    ) -> grpc::SingleResponse<ipc::GetStoreStatsResponse> {
+        catch_panic("get_store_stats", || {
+            self.service
+                .get_store_stats(request_options, store_stats_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn list_state_roots(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        list_state_roots_request: ipc::ListStateRootsRequest,
+    ) -> grpc::SingleResponse<ipc::ListStateRootsResponse> {
+        catch_panic("list_state_roots", || {
+            self.service
+                .list_state_roots(request_options, list_state_roots_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn backup_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        backup_store_request: ipc::BackupStoreRequest,
+    ) -> grpc::SingleResponse<ipc::BackupStoreResponse> {
+        catch_panic("backup_store", || {
+            self.service
+                .backup_store(request_options, backup_store_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn validate_deploy(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_deploy_request: ipc::ValidateDeployRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateDeployResponse> {
+        catch_panic("validate_deploy", || {
+            self.service
+                .validate_deploy(request_options, validate_deploy_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn state_diff(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        state_diff_request: ipc::StateDiffRequest,
+    ) -> grpc::SingleResponse<ipc::StateDiffResponse> {
+        catch_panic("state_diff", || {
+            self.service.state_diff(request_options, state_diff_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn flush_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        flush_store_request: ipc::FlushStoreRequest,
+    ) -> grpc::SingleResponse<ipc::FlushStoreResponse> {
+        catch_panic("flush_store", || {
+            self.service
+                .flush_store(request_options, flush_store_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn get_engine_version(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_engine_version_request: ipc::GetEngineVersionRequest,
+    ) -> grpc::SingleResponse<ipc::GetEngineVersionResponse> {
+        catch_panic("get_engine_version", || {
+            self.service
+                .get_engine_version(request_options, get_engine_version_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn get_genesis_hash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_genesis_hash_request: ipc::GetGenesisHashRequest,
+    ) -> grpc::SingleResponse<ipc::GetGenesisHashResponse> {
+        catch_panic("get_genesis_hash", || {
+            self.service
+                .get_genesis_hash(request_options, get_genesis_hash_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn verify_store(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        verify_store_request: ipc::VerifyStoreRequest,
+    ) -> grpc::SingleResponse<ipc::VerifyStoreResponse> {
+        catch_panic("verify_store", || {
+            self.service
+                .verify_store(request_options, verify_store_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn prune_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        prune_state_request: ipc::PruneStateRequest,
+    ) -> grpc::SingleResponse<ipc::PruneStateResponse> {
+        catch_panic("prune_state", || {
+            self.service
+                .prune_state(request_options, prune_state_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn get_capabilities(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_capabilities_request: ipc::GetCapabilitiesRequest,
+    ) -> grpc::SingleResponse<ipc::GetCapabilitiesResponse> {
+        catch_panic("get_capabilities", || {
+            self.service
+                .get_capabilities(request_options, get_capabilities_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn estimate_gas(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        estimate_gas_request: ipc::EstimateGasRequest,
+    ) -> grpc::SingleResponse<ipc::EstimateGasResponse> {
+        catch_panic("estimate_gas", || {
+            self.service
+                .estimate_gas(request_options, estimate_gas_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+
+    fn export_state(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        export_state_request: ipc::ExportStateRequest,
+    ) -> grpc::StreamingResponse<ipc::ExportStateResponse> {
+        catch_panic("export_state", || {
+            self.service
+                .export_state(request_options, export_state_request)
+        })
+        .unwrap_or_else(grpc::StreamingResponse::err)
+    }
+
+    fn get_empty_state_root(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        get_empty_state_root_request: ipc::GetEmptyStateRootRequest,
+    ) -> grpc::SingleResponse<ipc::GetEmptyStateRootResponse> {
+        catch_panic("get_empty_state_root", || {
+            self.service
+                .get_empty_state_root(request_options, get_empty_state_root_request)
+        })
+        .unwrap_or_else(grpc::SingleResponse::err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_return_result_of_handler_which_does_not_panic() {
+        assert_eq!(catch_panic("test", || 42).unwrap(), 42);
+        assert!(!is_catching_panics());
+    }
+
+    #[test]
+    fn should_turn_panic_into_internal_status() {
+        let result: Result<(), grpc::Error> = catch_panic("test", || {
+            assert!(is_catching_panics());
+            panic!("bad request {}", 1)
+        });
+
+        match result {
+            Err(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(error.grpc_status, grpc::GrpcStatus::Internal as i32);
+                assert_eq!(error.grpc_message, "test panicked: bad request 1");
+            }
+            other => panic!("expected an Internal status, got {:?}", other),
+        }
+        assert!(!is_catching_panics());
+    }
+}
@@ -0,0 +1,91 @@
+//! Parsing of the parts common to several requests: state hashes, preprocessors, correlation ids
+//! and deadlines.
+
+use std::convert::TryFrom;
+use std::str;
+use std::time::{Duration, Instant};
+
+use engine_core::engine_state::EngineConfig;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_wasm_prep::wasm_costs::WasmCosts;
+use engine_wasm_prep::WasmiPreprocessor;
+
+use super::EXPECTED_STATE_HASH_LENGTH;
+
+/// Metadata key under which clients may pass the correlation id of a request.
+const CORRELATION_ID_METADATA_KEY: &str = "correlation-id";
+/// Metadata key under which gRPC clients pass the time left until their deadline.
+const GRPC_TIMEOUT_METADATA_KEY: &str = "grpc-timeout";
+
+/// Parses a state root hash from a request, failing with a message if it has the wrong length.
+pub fn parse_state_hash(state_hash: &[u8]) -> Result<Blake2bHash, String> {
+    Blake2bHash::try_from(state_hash).map_err(|_| {
+        format!(
+            "expected {} bytes for state hash, got {}",
+            EXPECTED_STATE_HASH_LENGTH,
+            state_hash.len()
+        )
+    })
+}
+
+/// Builds the preprocessor which deploys' session code is run through, using the configured wasm
+/// costs or else those of `protocol_version`.  Returns `None` if there are none for it.
+pub fn get_preprocessor(config: &EngineConfig, protocol_version: u64) -> Option<WasmiPreprocessor> {
+    let wasm_costs = match config.get_wasm_costs() {
+        Some(wasm_costs) => wasm_costs.clone(),
+        None => WasmCosts::from_version(protocol_version)?,
+    };
+    Some(
+        WasmiPreprocessor::new(wasm_costs)
+            .with_max_module_size(config.get_max_wasm_size())
+            .with_max_memory_pages(config.get_max_memory_pages()),
+    )
+}
+
+/// Returns the correlation id the client passed in the request's metadata, or a new one if it
+/// passed none or it is not a UUID.
+pub fn request_correlation_id(request_options: &grpc::RequestOptions) -> CorrelationId {
+    request_options
+        .metadata
+        .get(CORRELATION_ID_METADATA_KEY)
+        .and_then(|value| str::from_utf8(value).ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(CorrelationId::new)
+}
+
+/// Returns the instant by which the client, which sent its request at `start`, wants a response,
+/// or `None` if it set no deadline or passed a malformed `grpc-timeout`.
+pub fn request_deadline(request_options: &grpc::RequestOptions, start: Instant) -> Option<Instant> {
+    request_options
+        .metadata
+        .get(GRPC_TIMEOUT_METADATA_KEY)
+        .and_then(|value| str::from_utf8(value).ok())
+        .and_then(parse_grpc_timeout)
+        .map(|timeout| start + timeout)
+}
+
+/// Parses the value of a `grpc-timeout` header: at most 8 digits followed by a unit, one of `H`,
+/// `M`, `S`, `m`, `u` or `n`.
+pub fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 || !value.is_char_boundary(value.len() - 1) {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+pub fn is_past_deadline(deadline: Option<Instant>) -> bool {
+    deadline.map_or(false, |deadline| Instant::now() >= deadline)
+}
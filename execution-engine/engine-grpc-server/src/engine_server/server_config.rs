@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+/// The listen backlog of each socket unless configured otherwise.
+pub const DEFAULT_SOCKET_BACKLOG: u32 = 128;
+
+/// PEM encoded certificate chain and private key used to serve gRPC over TLS.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// The transport configuration of the gRPC server
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub(super) tls_config: Option<TlsConfig>,
+    pub(super) threads: usize,
+    pub(super) max_concurrent_requests: Option<usize>,
+    pub(super) socket_backlog: u32,
+}
+
+impl ServerConfig {
+    /// Creates a new server configuration with default parameters.
+    pub fn new() -> ServerConfig {
+        Default::default()
+    }
+
+    /// Sets the `tls_config` field to the given arg.  The server uses TLS when it is `Some`.
+    pub fn tls_config(mut self, arg: Option<TlsConfig>) -> ServerConfig {
+        self.tls_config = arg;
+        self
+    }
+
+    /// Sets the `threads` field to the given arg; the size of the pool requests are handled on.
+    pub fn threads(mut self, arg: usize) -> ServerConfig {
+        self.threads = arg;
+        self
+    }
+
+    /// Returns the size of the pool requests are handled on.
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets the `max_concurrent_requests` field to the given arg.
+    pub fn max_concurrent_requests(mut self, arg: Option<usize>) -> ServerConfig {
+        self.max_concurrent_requests = arg;
+        self
+    }
+
+    /// Returns the most requests handled at once, over all sockets, if limited.
+    pub fn get_max_concurrent_requests(&self) -> Option<usize> {
+        self.max_concurrent_requests
+    }
+
+    /// Sets the `socket_backlog` field to the given arg; how many connections each socket queues
+    /// before they are accepted.
+    pub fn socket_backlog(mut self, arg: u32) -> ServerConfig {
+        self.socket_backlog = arg;
+        self
+    }
+
+    /// Returns how many connections each socket queues before they are accepted.
+    pub fn get_socket_backlog(&self) -> u32 {
+        self.socket_backlog
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            tls_config: None,
+            threads: 1,
+            max_concurrent_requests: None,
+            socket_backlog: DEFAULT_SOCKET_BACKLOG,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Streaming responses of `exec_stream` and `export_state`.
+
+use std::fmt::Debug;
+use std::mem;
+use std::slice;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use contract_ffi::value::account::BlockTime;
+use engine_core::engine_state::error::{Error as EngineError, RootNotFound};
+use engine_core::engine_state::EngineState;
+use engine_core::execution::{Executor, WasmiExecutor};
+use engine_shared::logging;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::global_state::History;
+use engine_wasm_prep::Preprocessor;
+use futures::sink::Wait;
+use futures::sync::mpsc;
+use futures::Sink;
+
+use super::commits::commit_effects_batch;
+use super::deploys::{charge_deploy_result, deploy_gas_price, run_deploy};
+use super::errors::{
+    deadline_exceeded_error, internal_failure, is_malformed_module, is_readers_full_failure,
+    malformed_module, malformed_module_error, readers_full_error,
+};
+use super::ipc;
+use super::mappings::error_code;
+use super::metrics;
+use super::request::is_past_deadline;
+use super::{
+    METRIC_DURATION_EXEC_STREAM, METRIC_DURATION_EXPORT_STATE, TAG_RESPONSE_EXEC_STREAM,
+    TAG_RESPONSE_EXPORT_STATE,
+};
+
+const EXEC_STREAM_CLOSED_MESSAGE: &str = "exec stream closed by client; effects not committed";
+const EXPORT_STREAM_CLOSED_MESSAGE: &str = "export stream closed by client; export abandoned";
+const STREAM_READ_TIMEOUT_MESSAGE: &str = "client took nothing from the stream for";
+/// How often a streaming response with a read timeout checks whether the client has made room
+/// for its next message.
+const STREAM_SEND_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Runs the deploys of `exec_request` against `prestate_hash`, sending each one's result to
+/// `sender` as soon as it is known, then commits all of their effects in order on top of
+/// `prestate_hash` and sends the commit response.  Stops without committing if the client goes
+/// away or `deadline` passes.
+pub fn stream_deploys<A, H, P>(
+    engine_state: &EngineState<H>,
+    preprocessor: &P,
+    prestate_hash: Blake2bHash,
+    exec_request: &ipc::ExecRequest,
+    correlation_id: CorrelationId,
+    deadline: Option<Instant>,
+    sender: mpsc::Sender<Result<ipc::ExecStreamResponse, grpc::Error>>,
+) where
+    H: History,
+    P: Preprocessor<A>,
+    WasmiExecutor: Executor<A>,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error> + Debug,
+{
+    let start = Instant::now();
+    let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+    let read_timeout = engine_state.config().get_read_timeout();
+    let mut sender = sender.wait();
+
+    let executor = WasmiExecutor::new()
+        .with_timeout(engine_state.config().get_execution_timeout())
+        .with_deadline(deadline)
+        .with_max_memory_pages(engine_state.config().get_max_memory_pages())
+        .with_tracing(exec_request.get_execution_tracing());
+    let blocktime = BlockTime(exec_request.get_block_time());
+    let protocol_version = exec_request.get_protocol_version();
+
+    let mut effects = Vec::new();
+    let mut missing_parent = None;
+    for (index, deploy) in exec_request.get_deploys().iter().enumerate() {
+        let mut exec_stream_response = ipc::ExecStreamResponse::new();
+        let gas_price = deploy_gas_price(exec_request.get_gas_price(), deploy);
+        let deploy_result = run_deploy(
+            engine_state,
+            &executor,
+            preprocessor,
+            prestate_hash,
+            blocktime,
+            deploy,
+            gas_price,
+            protocol_version,
+            correlation_id,
+        );
+        if is_past_deadline(deadline) {
+            // nothing is committed, and the client no longer waits for the rest
+            if send_to_stream(&mut sender, Err(deadline_exceeded_error()), read_timeout).is_err() {
+                log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+            }
+            return;
+        }
+        match deploy_result {
+            Ok(ref execution_result) if is_readers_full_failure(execution_result) => {
+                // nothing is committed, so the client can run the deploys again
+                if send_to_stream(&mut sender, Err(readers_full_error()), read_timeout).is_err() {
+                    log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+                }
+                return;
+            }
+            Ok(ref execution_result) if is_malformed_module(execution_result) => {
+                // nothing is committed, as `exec` would have run none of the deploys
+                let error = malformed_module(execution_result).expect("the result was malformed");
+                let error = malformed_module_error(index, error);
+                if send_to_stream(&mut sender, Err(error), read_timeout).is_err() {
+                    log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+                }
+                return;
+            }
+            Ok(execution_result) => {
+                effects.push(execution_result.effect().transforms.clone());
+                let mut deploy_result: ipc::DeployResult = execution_result.into();
+                metrics::record_deploy_results(slice::from_ref(&deploy_result));
+                if let Err(error) = charge_deploy_result(index, &mut deploy_result, gas_price) {
+                    // nothing is committed, as the client could not be charged for this deploy
+                    if send_to_stream(&mut sender, Err(error), read_timeout).is_err() {
+                        log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+                    }
+                    return;
+                }
+                exec_stream_response.set_deploy_result(deploy_result);
+            }
+            Err(error) => {
+                // all deploys share the missing prestate, so the rest would fail too
+                log_error!("deploy results error: RootNotFound");
+                missing_parent = Some(error);
+                break;
+            }
+        }
+        if send_to_stream(&mut sender, Ok(exec_stream_response), read_timeout).is_err() {
+            log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+            return;
+        }
+    }
+
+    let last_response = match missing_parent {
+        Some(error) => {
+            let mut exec_stream_response = ipc::ExecStreamResponse::new();
+            exec_stream_response.set_missing_parent(error.into());
+            exec_stream_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            Ok(exec_stream_response)
+        }
+        None => commit_effects_batch(engine_state, correlation_id, prestate_hash, effects).map(
+            |commit_response| {
+                let mut exec_stream_response = ipc::ExecStreamResponse::new();
+                exec_stream_response.set_commit(commit_response);
+                exec_stream_response
+            },
+        ),
+    };
+    if send_to_stream(&mut sender, last_response, read_timeout).is_err() {
+        log_warning!(EXEC_STREAM_CLOSED_MESSAGE);
+    }
+
+    log_duration!(
+        correlation_id,
+        METRIC_DURATION_EXEC_STREAM,
+        TAG_RESPONSE_EXEC_STREAM,
+        start.elapsed(),
+    );
+}
+
+/// Sends the pairs under `root` to `sender` in chunks of up to `chunk_size`, then the number of
+/// pairs sent.  Stops reading as soon as the client goes away.
+pub fn stream_pairs<H>(
+    engine_state: &EngineState<H>,
+    root: Blake2bHash,
+    chunk_size: usize,
+    correlation_id: CorrelationId,
+    sender: mpsc::Sender<Result<ipc::ExportStateResponse, grpc::Error>>,
+) where
+    H: History,
+    EngineError: From<H::Error>,
+    H::Error: Into<engine_core::execution::Error> + Debug,
+{
+    let start = Instant::now();
+    let _correlation_id_guard = logging::set_correlation_id(correlation_id);
+
+    // blocks whenever the channel is full, until the client has read enough or the read timeout
+    // passes
+    let read_timeout = engine_state.config().get_read_timeout();
+    let mut sender = sender.wait();
+    let mut pairs = Vec::with_capacity(chunk_size);
+    let mut pair_count: u64 = 0;
+    let export_result = engine_state.export_state(correlation_id, root, |key, value| {
+        let mut pair = ipc::ExportStateResponse_Pair::new();
+        pair.set_key((&key).into());
+        pair.set_value(value.into());
+        pairs.push(pair);
+        pair_count += 1;
+        if pairs.len() < chunk_size {
+            return true;
+        }
+        let chunk = mem::replace(&mut pairs, Vec::with_capacity(chunk_size));
+        send_to_stream(&mut sender, Ok(chunk_response(chunk)), read_timeout).is_ok()
+    });
+
+    let last_response = match export_result {
+        Ok(Some(true)) => {
+            if !pairs.is_empty()
+                && send_to_stream(&mut sender, Ok(chunk_response(pairs)), read_timeout).is_err()
+            {
+                log_warning!(EXPORT_STREAM_CLOSED_MESSAGE);
+                return;
+            }
+            let mut done = ipc::ExportStateResponse_Done::new();
+            done.set_pair_count(pair_count);
+            let mut export_state_response = ipc::ExportStateResponse::new();
+            export_state_response.set_done(done);
+            export_state_response
+        }
+        Ok(Some(false)) => {
+            log_warning!(EXPORT_STREAM_CLOSED_MESSAGE);
+            return;
+        }
+        Ok(None) => {
+            log_warning!(&format!("Root not found: {}", root));
+            let mut export_state_response = ipc::ExportStateResponse::new();
+            export_state_response.set_missing_state(RootNotFound(root).into());
+            export_state_response.set_error_code(ipc::ErrorCode::STATE_NOT_FOUND);
+            export_state_response
+        }
+        Err(error) => {
+            let (error_code, err_msg) = internal_failure(error);
+            log_error!(&err_msg);
+            let mut export_state_response = ipc::ExportStateResponse::new();
+            export_state_response.set_failure(err_msg);
+            export_state_response.set_error_code(error_code);
+            export_state_response
+        }
+    };
+    if send_to_stream(&mut sender, Ok(last_response), read_timeout).is_err() {
+        log_warning!(EXPORT_STREAM_CLOSED_MESSAGE);
+    }
+
+    log_duration!(
+        correlation_id,
+        METRIC_DURATION_EXPORT_STATE,
+        TAG_RESPONSE_EXPORT_STATE,
+        start.elapsed(),
+    );
+}
+
+/// Why a message could not be sent on a streaming response.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamSendError {
+    /// The client went away.
+    Closed,
+    /// The client took nothing from the stream for the read timeout.
+    TimedOut,
+}
+
+/// Sends `item` on a streaming response, blocking while its channel is full.  With a
+/// `read_timeout`, gives up once the client has taken nothing from the stream for that long, so
+/// that a hung client does not hold the global state reader of the stream forever; each message
+/// taken starts the timeout again.
+pub fn send_to_stream<T>(
+    sender: &mut Wait<mpsc::Sender<T>>,
+    item: T,
+    read_timeout: Option<Duration>,
+) -> Result<(), StreamSendError> {
+    let read_timeout = match read_timeout {
+        Some(read_timeout) => read_timeout,
+        None => return sender.send(item).map_err(|_| StreamSendError::Closed),
+    };
+    let timeout_at = Instant::now() + read_timeout;
+    let mut item = item;
+    loop {
+        match sender.get_mut().try_send(item) {
+            Ok(()) => return Ok(()),
+            Err(ref error) if error.is_disconnected() => return Err(StreamSendError::Closed),
+            Err(error) => {
+                if Instant::now() >= timeout_at {
+                    log_warning!(&format!(
+                        "{} {:?}",
+                        STREAM_READ_TIMEOUT_MESSAGE, read_timeout
+                    ));
+                    return Err(StreamSendError::TimedOut);
+                }
+                item = error.into_inner();
+                thread::sleep(STREAM_SEND_RETRY_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Wraps `pairs` in a chunk of the `export_state` response.
+pub fn chunk_response(pairs: Vec<ipc::ExportStateResponse_Pair>) -> ipc::ExportStateResponse {
+    let mut chunk = ipc::ExportStateResponse_Chunk::new();
+    chunk.set_pairs(pairs.into());
+    let mut export_state_response = ipc::ExportStateResponse::new();
+    export_state_response.set_chunk(chunk);
+    export_state_response
+}
@@ -0,0 +1,97 @@
+//! Wasm gas costs loaded from a TOML file.
+//!
+//! Each entry overrides the field of the same name in
+//! [`WasmCosts`](engine_wasm_prep::wasm_costs::WasmCosts); missing entries keep the built-in
+//! value.
+//!
+//! # Example
+//!
+//! ```toml
+//! regular = 1
+//! div = 16
+//! mul = 4
+//! mem = 2
+//! grow-mem = 8192
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use toml;
+
+use engine_wasm_prep::wasm_costs::WasmCosts;
+
+use config::Error;
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GasConfig {
+    pub regular: Option<u32>,
+    pub div: Option<u32>,
+    pub mul: Option<u32>,
+    pub mem: Option<u32>,
+    pub initial_mem: Option<u32>,
+    pub grow_mem: Option<u32>,
+    pub memcpy: Option<u32>,
+    pub max_stack_height: Option<u32>,
+    pub opcodes_mul: Option<u32>,
+    pub opcodes_div: Option<u32>,
+}
+
+impl GasConfig {
+    /// Reads and deserializes the TOML file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<GasConfig, Error> {
+        let contents = fs::read_to_string(path)?;
+        GasConfig::from_toml(&contents)
+    }
+
+    pub fn from_toml(input: &str) -> Result<GasConfig, Error> {
+        toml::from_str(input).map_err(Into::into)
+    }
+
+    /// Returns `defaults` with every cost present in `self` replaced.
+    pub fn apply(&self, defaults: WasmCosts) -> WasmCosts {
+        WasmCosts {
+            regular: self.regular.unwrap_or(defaults.regular),
+            div: self.div.unwrap_or(defaults.div),
+            mul: self.mul.unwrap_or(defaults.mul),
+            mem: self.mem.unwrap_or(defaults.mem),
+            initial_mem: self.initial_mem.unwrap_or(defaults.initial_mem),
+            grow_mem: self.grow_mem.unwrap_or(defaults.grow_mem),
+            memcpy: self.memcpy.unwrap_or(defaults.memcpy),
+            max_stack_height: self.max_stack_height.unwrap_or(defaults.max_stack_height),
+            opcodes_mul: self.opcodes_mul.unwrap_or(defaults.opcodes_mul),
+            opcodes_div: self.opcodes_div.unwrap_or(defaults.opcodes_div),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine_wasm_prep::wasm_costs::WasmCosts;
+
+    use super::GasConfig;
+
+    #[test]
+    fn missing_entries_should_fall_back_to_defaults() {
+        let input = r#"
+            regular = 2
+            grow-mem = 100
+        "#;
+
+        let gas_config = GasConfig::from_toml(input).expect("should parse");
+        let defaults = WasmCosts::from_version(1).expect("should have costs for version 1");
+        let wasm_costs = gas_config.apply(defaults.clone());
+
+        assert_eq!(wasm_costs.regular, 2);
+        assert_eq!(wasm_costs.grow_mem, 100);
+        assert_eq!(wasm_costs.div, defaults.div);
+        assert_eq!(wasm_costs.mul, defaults.mul);
+        assert_eq!(wasm_costs.max_stack_height, defaults.max_stack_height);
+    }
+
+    #[test]
+    fn should_reject_unknown_costs() {
+        assert!(GasConfig::from_toml("regullar = 2").is_err());
+    }
+}
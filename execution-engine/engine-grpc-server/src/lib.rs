@@ -1,12 +1,19 @@
 extern crate contract_ffi;
 extern crate engine_core;
+#[macro_use]
 extern crate engine_shared;
 extern crate engine_storage;
 extern crate engine_wasm_prep;
+extern crate futures;
 extern crate grpc;
 extern crate lmdb;
+extern crate openssl;
 extern crate proptest;
 extern crate protobuf;
+extern crate tls_api;
+extern crate tls_api_openssl;
+#[cfg(feature = "tokio-server")]
+extern crate tokio_threadpool;
 extern crate wabt;
 
 #[cfg(test)]
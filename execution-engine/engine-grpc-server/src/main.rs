@@ -1,37 +1,93 @@
+extern crate base16;
 extern crate clap;
+extern crate contract_ffi;
 extern crate ctrlc;
 extern crate dirs;
 extern crate grpc;
 #[macro_use]
 extern crate lazy_static;
 extern crate lmdb;
+extern crate num_cpus;
+extern crate parity_wasm;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg(test)]
+extern crate tempfile;
+extern crate toml;
+extern crate wabt;
 
 extern crate casperlabs_engine_grpc_server;
 extern crate engine_core;
 extern crate engine_shared;
 extern crate engine_storage;
+extern crate engine_wasm_prep;
 
+use std::cmp;
 use std::collections::btree_map::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use dirs::home_dir;
+use engine_core::engine_state::engine_config::{
+    DEFAULT_DEDUP_CACHE_SIZE, DEFAULT_ESTIMATE_GAS_MAX_TRIALS, DEFAULT_MAX_DEPLOYS_PER_BATCH,
+    DEFAULT_MAX_QUERY_DEPTH, DEFAULT_MAX_QUERY_NODES,
+};
 use engine_core::engine_state::{EngineConfig, EngineState};
 use lmdb::DatabaseFlags;
 
-use engine_shared::logging::log_settings::{LogLevelFilter, LogSettings};
+use engine_shared::logging::log_file::LogFileSettings;
+use engine_shared::logging::log_settings::{
+    LogFormat, LogLevelFilter, LogSettings, TimestampFormat,
+};
+use engine_shared::logging::syslog::{SyslogFacility, SyslogSettings};
 use engine_shared::logging::{log_level, log_settings};
-use engine_shared::os::get_page_size;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_shared::os::{self, get_page_size};
 use engine_shared::{logging, socket};
+use engine_storage::error::Error as StorageError;
+use engine_storage::global_state::audit_log::AuditLog;
 use engine_storage::global_state::lmdb::LmdbGlobalState;
-use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+use engine_storage::global_state::{History, WarmUpProgress};
+use engine_storage::trie_store::lmdb::{
+    LmdbEnvironment, LmdbTrieStore, SyncPolicy, DEFAULT_MAX_READERS,
+};
+use engine_wasm_prep::wasm_costs::WasmCosts;
+use engine_wasm_prep::{DEFAULT_MAX_MODULE_SIZE, MEM_PAGES};
 
 use casperlabs_engine_grpc_server::engine_server;
+use casperlabs_engine_grpc_server::engine_server::drain::Drain;
+use casperlabs_engine_grpc_server::engine_server::health_status::HealthStatus;
+use casperlabs_engine_grpc_server::engine_server::memory_guard::{self, MemoryGuard};
+use casperlabs_engine_grpc_server::engine_server::metrics;
+use casperlabs_engine_grpc_server::engine_server::panic_boundary;
+use casperlabs_engine_grpc_server::engine_server::{
+    ServerConfig, TlsConfig, DEFAULT_SOCKET_BACKLOG,
+};
+
+use config::Config;
+use gas_config::GasConfig;
+use restore::{check_restored_genesis_hash, restore_backup};
+use subcommands::export::export_global_state;
+use subcommands::import::import_global_state;
+use subcommands::replay::replay_audit_log;
+use subcommands::self_test::self_test;
+use subcommands::validate::validate_wasm;
+
+mod config;
+mod data_dir;
+mod gas_config;
+mod restore;
+mod subcommands;
 
 // exe / proc
 const PROC_NAME: &str = "casperlabs-engine-grpc-server";
@@ -40,6 +96,15 @@ const SERVER_START_MESSAGE: &str = "starting Execution Engine Server";
 const SERVER_LISTENING_TEMPLATE: &str = "{listener} is listening on socket: {socket}";
 const SERVER_START_EXPECT: &str = "failed to start Execution Engine Server";
 const SERVER_STOP_MESSAGE: &str = "stopping Execution Engine Server";
+const SERVER_CONFIG_MESSAGE: &str = "starting with configuration";
+
+// config
+const ARG_CONFIG: &str = "config";
+const ARG_CONFIG_SHORT: &str = "c";
+const ARG_CONFIG_VALUE: &str = "FILE";
+const ARG_CONFIG_HELP: &str =
+    "Sets a TOML file to read settings from; command line arguments take precedence";
+const CONFIG_FILE_ERROR: &str = "failed to load config file";
 
 // data-dir / lmdb
 const ARG_DATA_DIR: &str = "data-dir";
@@ -49,10 +114,20 @@ const ARG_DATA_DIR_HELP: &str = "Sets the data directory";
 const DEFAULT_DATA_DIR_RELATIVE: &str = ".casperlabs";
 const GLOBAL_STATE_DIR: &str = "global_state";
 const GET_HOME_DIR_EXPECT: &str = "Could not get home directory";
-const CREATE_DATA_DIR_EXPECT: &str = "Could not create directory";
+
+// state-subdir
+const ARG_STATE_SUBDIR: &str = "state-subdir";
+const ARG_STATE_SUBDIR_VALUE: &str = "NAME";
+const ARG_STATE_SUBDIR_HELP: &str =
+    "Sets the name of the directory under the data directory holding global state, so that \
+     several configurations can share a data directory [default: global_state]";
+const STATE_SUBDIR_INVALID_MESSAGE: &str =
+    "state-subdir must be a plain directory name, without path separators";
 const LMDB_ENVIRONMENT_EXPECT: &str = "Could not create LmdbEnvironment";
 const LMDB_TRIE_STORE_EXPECT: &str = "Could not create LmdbTrieStore";
 const LMDB_GLOBAL_STATE_EXPECT: &str = "Could not create LmdbGlobalState";
+const LMDB_SYNC_ERROR: &str = "Could not flush LmdbEnvironment";
+const GENESIS_HASH_ERROR: &str = "Could not read genesis hash from global state";
 
 // pages / lmdb
 const ARG_PAGES: &str = "pages";
@@ -65,78 +140,888 @@ const GET_PAGES_EXPECT: &str = "Could not parse pages argument";
 // 805306368000 / 4096 = 196608000
 const DEFAULT_PAGES: usize = 196_608_000;
 
+// max-db-size / lmdb
+const ARG_MAX_DB_SIZE: &str = "max-db-size";
+const ARG_MAX_DB_SIZE_VALUE: &str = "BYTES";
+const ARG_MAX_DB_SIZE_HELP: &str =
+    "Sets the max size in bytes of lmdb's mmap, rounded up to a multiple of the page size; \
+     alternative to --pages";
+const GET_MAX_DB_SIZE_EXPECT: &str = "Could not parse max-db-size argument";
+const LMDB_MAP_FULL_MESSAGE: &str =
+    "global state does not fit in lmdb's mmap; increase --pages or --max-db-size";
+
+// max-readers
+const ARG_MAX_READERS: &str = "max-readers";
+const ARG_MAX_READERS_VALUE: &str = "NUM";
+const ARG_MAX_READERS_HELP: &str =
+    "Sets the max number of read transactions lmdb can have open at once [default: 4 per \
+     thread, and at least 126]";
+const GET_MAX_READERS_EXPECT: &str = "Could not parse max-readers argument";
+const MAX_READERS_ZERO_MESSAGE: &str = "max-readers must be greater than 0";
+const MAX_READERS_BELOW_THREADS_WARNING: &str =
+    "max-readers is lower than the number of threads; reads may fail with Unavailable under load";
+/// Reader slots allowed per gRPC thread unless max-readers is given
+const READERS_PER_THREAD: u32 = 4;
+
+// db-open-retries
+const ARG_DB_OPEN_RETRIES: &str = "db-open-retries";
+const ARG_DB_OPEN_RETRIES_VALUE: &str = "NUM";
+const ARG_DB_OPEN_RETRIES_HELP: &str = "Retries opening global state up to this many times, \
+                                        half a second apart, while another process may still be \
+                                        creating it [default: 0]";
+const GET_DB_OPEN_RETRIES_EXPECT: &str = "Could not parse db-open-retries argument";
+const DB_OPEN_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+const DB_OPEN_RETRY_MESSAGE: &str =
+    "global state could not be opened, as another process may still be creating it; retrying";
+
+// trie-cache-size
+const ARG_TRIE_CACHE_SIZE: &str = "trie-cache-size";
+const ARG_TRIE_CACHE_SIZE_VALUE: &str = "NUM";
+const ARG_TRIE_CACHE_SIZE_HELP: &str =
+    "Keeps up to this many recently read trie nodes in memory [default: no cache]";
+const GET_TRIE_CACHE_SIZE_EXPECT: &str = "Could not parse trie-cache-size argument";
+const TRIE_CACHE_SIZE_ZERO_MESSAGE: &str = "trie-cache-size must be greater than 0";
+
+// warmup
+const ARG_WARMUP: &str = "warmup";
+const ARG_WARMUP_HELP: &str =
+    "Reads the latest committed trie before serving, so that its pages are in the OS page cache";
+const WARMUP_NOTHING_COMMITTED_MESSAGE: &str = "no state root committed yet; skipping warmup";
+const WARMUP_ERROR: &str = "warmup failed";
+
+// warmup-depth
+const ARG_WARMUP_DEPTH: &str = "warmup-depth";
+const ARG_WARMUP_DEPTH_VALUE: &str = "DEPTH";
+const ARG_WARMUP_DEPTH_HELP: &str =
+    "Stops warmup this many levels below the state root, bounding startup time [default: no limit]";
+const GET_WARMUP_DEPTH_EXPECT: &str = "Could not parse warmup-depth argument";
+
+// key-bloom
+const ARG_KEY_BLOOM: &str = "key-bloom";
+const ARG_KEY_BLOOM_HELP: &str =
+    "Keeps bloom filters of the keys of recent state roots, so reads of missing keys skip the trie";
+const KEY_BLOOM_ERROR: &str = "failed to build key bloom filter";
+
+// store-name
+const ARG_STORE_NAME: &str = "store-name";
+const ARG_STORE_NAME_VALUE: &str = "NAME";
+const ARG_STORE_NAME_HELP: &str =
+    "Keeps global state in the LMDB sub-database with this name, so that independent states can \
+     share a data directory [default: the unnamed database]";
+const STORE_NAME_EMPTY_MESSAGE: &str = "store-name must not be empty";
+const STORE_NOT_FOUND_HINT: &str = "it is only created when the server runs without --read-only";
+
+// audit-log
+const ARG_AUDIT_LOG: &str = "audit-log";
+const ARG_AUDIT_LOG_VALUE: &str = "FILE";
+const ARG_AUDIT_LOG_HELP: &str =
+    "Appends a record of every commit to this file, from which global state can be rebuilt with \
+     the replay subcommand";
+const AUDIT_LOG_ERROR: &str = "failed to open audit log";
+
 // socket
 const ARG_SOCKET: &str = "socket";
-const ARG_SOCKET_HELP: &str = "socket file";
-const ARG_SOCKET_EXPECT: &str = "socket required";
+const ARG_SOCKET_HELP: &str = "socket file, or tcp://<host>:<port> to listen on TCP";
+const ARG_SOCKETS: &str = "sockets";
+const ARG_SOCKETS_VALUE: &str = "SOCKET";
+const ARG_SOCKETS_HELP: &str =
+    "Also listens on this socket file or tcp://<host>:<port>; may be repeated";
+const ARG_SOCKET_REQUIRED: &str = "socket required";
+const DUPLICATE_SOCKET_MESSAGE: &str = "socket given more than once";
 const REMOVING_SOCKET_FILE_MESSAGE: &str = "removing old socket file";
 const REMOVING_SOCKET_FILE_EXPECT: &str = "failed to remove old socket file";
 
+// pid-file
+const ARG_PID_FILE: &str = "pid-file";
+const ARG_PID_FILE_VALUE: &str = "FILE";
+const ARG_PID_FILE_HELP: &str =
+    "Writes the process id to a file, removed on shutdown; fails if it names a live process";
+const PID_FILE_WRITE_ERROR: &str = "failed to write pid file";
+const PID_FILE_REMOVE_ERROR: &str = "failed to remove pid file";
+
+// socket-mode
+const ARG_SOCKET_MODE: &str = "socket-mode";
+const ARG_SOCKET_MODE_VALUE: &str = "MODE";
+const ARG_SOCKET_MODE_HELP: &str =
+    "Sets the octal permission bits of the socket file, e.g. 0660; ignored for TCP sockets";
+const GET_SOCKET_MODE_EXPECT: &str = "Could not parse socket-mode argument as octal";
+const SET_SOCKET_MODE_ERROR: &str = "failed to set socket file mode";
+
+// abstract-socket
+const ARG_ABSTRACT_SOCKET: &str = "abstract-socket";
+const ARG_ABSTRACT_SOCKET_HELP: &str =
+    "Treats sockets other than TCP ones as names of Linux abstract namespace sockets, e.g. \
+     @casperlabs-engine";
+const ABSTRACT_SOCKET_UNSUPPORTED_MESSAGE: &str =
+    "abstract-socket is only supported on Linux; give the path of a socket file instead";
+
+// metrics-addr
+const ARG_METRICS_ADDR: &str = "metrics-addr";
+const ARG_METRICS_ADDR_VALUE: &str = "HOST:PORT";
+const ARG_METRICS_ADDR_HELP: &str =
+    "Serves counters in the Prometheus text format at http://HOST:PORT/metrics [default: off]";
+const METRICS_BIND_ERROR: &str = "failed to bind metrics address";
+const METRICS_THREAD_NAME: &str = "metrics";
+
+// threads
+const ARG_THREADS: &str = "threads";
+const ARG_THREADS_SHORT: &str = "t";
+const ARG_THREADS_VALUE: &str = "NUM";
+const ARG_THREADS_HELP: &str =
+    "Sets the number of threads handling gRPC requests [default: number of logical CPUs]";
+const GET_THREADS_EXPECT: &str = "Could not parse threads argument";
+const THREADS_ZERO_MESSAGE: &str = "threads must be greater than 0";
+
+// max-concurrent-requests
+const ARG_MAX_CONCURRENT_REQUESTS: &str = "max-concurrent-requests";
+const ARG_MAX_CONCURRENT_REQUESTS_VALUE: &str = "NUM";
+const ARG_MAX_CONCURRENT_REQUESTS_HELP: &str =
+    "Rejects requests with Unavailable while this many are being handled, over all sockets";
+const GET_MAX_CONCURRENT_REQUESTS_EXPECT: &str = "Could not parse max-concurrent-requests argument";
+const MAX_CONCURRENT_REQUESTS_ZERO_MESSAGE: &str = "max-concurrent-requests must be greater than 0";
+
+// socket-backlog
+const ARG_SOCKET_BACKLOG: &str = "socket-backlog";
+const ARG_SOCKET_BACKLOG_VALUE: &str = "NUM";
+const ARG_SOCKET_BACKLOG_HELP: &str =
+    "Sets how many connections each socket queues before they are accepted [default: 128]";
+const GET_SOCKET_BACKLOG_EXPECT: &str = "Could not parse socket-backlog argument";
+const SOCKET_BACKLOG_ZERO_MESSAGE: &str = "socket-backlog must be greater than 0";
+
+// drain-timeout
+const ARG_DRAIN_TIMEOUT: &str = "drain-timeout";
+const ARG_DRAIN_TIMEOUT_VALUE: &str = "SECONDS";
+const ARG_DRAIN_TIMEOUT_HELP: &str =
+    "Sets how long to wait for requests in flight after SIGUSR1 before stopping [default: 30]";
+const GET_DRAIN_TIMEOUT_EXPECT: &str = "Could not parse drain-timeout argument";
+const DEFAULT_DRAIN_TIMEOUT_SECONDS: u64 = 30;
+const SIGUSR1_HANDLER_ERROR: &str = "Error setting SIGUSR1 handler, server cannot be drained";
+const DRAIN_START_MESSAGE: &str = "draining; rejecting new exec and commit requests";
+const DRAIN_TIMEOUT_MESSAGE: &str = "drain timed out; aborting requests still in flight";
+
+// read-timeout
+const ARG_READ_TIMEOUT: &str = "read-timeout";
+const ARG_READ_TIMEOUT_VALUE: &str = "SECONDS";
+const ARG_READ_TIMEOUT_HELP: &str = "Abandons a streaming response once the client has taken \
+                                     nothing from it for this long [default: no limit]";
+const GET_READ_TIMEOUT_EXPECT: &str = "Could not parse read-timeout argument";
+const READ_TIMEOUT_ZERO_MESSAGE: &str = "read-timeout must be greater than 0";
+
+// tls
+const ARG_TLS_CERT: &str = "tls-cert";
+const ARG_TLS_CERT_VALUE: &str = "FILE";
+const ARG_TLS_CERT_HELP: &str = "Sets the PEM encoded certificate chain used to serve over TLS";
+const ARG_TLS_KEY: &str = "tls-key";
+const ARG_TLS_KEY_VALUE: &str = "FILE";
+const ARG_TLS_KEY_HELP: &str = "Sets the PEM encoded private key used to serve over TLS";
+const TLS_INCOMPLETE_MESSAGE: &str = "both --tls-cert and --tls-key are required to enable TLS";
+
 // loglevel
 const ARG_LOG_LEVEL: &str = "loglevel";
 const ARG_LOG_LEVEL_VALUE: &str = "LOGLEVEL";
-const ARG_LOG_LEVEL_HELP: &str = "[ fatal | error | warning | info | debug ]";
+const ARG_LOG_LEVEL_HELP: &str = "[ fatal | error | warning | info | debug ], optionally followed \
+                                  by comma separated MODULE=LEVEL overrides, e.g. \
+                                  info,casperlabs_engine_storage=warning";
+const LOG_LEVEL_SEPARATOR: char = ',';
+// read when --loglevel is not given
+const ENV_LOG_LEVEL: &str = "CASPERLABS_LOG_LEVEL";
+const LOG_LEVEL_MODULE_SEPARATOR: char = '=';
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+// log format
+const ARG_LOG_FORMAT: &str = "log-format";
+const ARG_LOG_FORMAT_VALUE: &str = "FORMAT";
+const ARG_LOG_FORMAT_HELP: &str = "[ text | json ]";
+
+// log timestamp format
+const ARG_LOG_TIMESTAMP_FORMAT: &str = "log-timestamp-format";
+const ARG_LOG_TIMESTAMP_FORMAT_VALUE: &str = "FORMAT";
+const ARG_LOG_TIMESTAMP_FORMAT_HELP: &str =
+    "[ rfc3339-utc | rfc3339-local ] Format and timezone of log timestamps";
+
+// deterministic timestamps, for tests only
+const ARG_DETERMINISTIC_TIMESTAMPS: &str = "deterministic-timestamps";
+const ARG_DETERMINISTIC_TIMESTAMPS_HELP: &str =
+    "Stamps log lines with a synthetic clock starting at the Unix epoch, for reproducible logs";
+const DETERMINISTIC_TIMESTAMPS_RELEASE_MESSAGE: &str =
+    "deterministic-timestamps is for tests and only available in debug builds";
+
+// log file
+const ARG_LOG_FILE: &str = "log-file";
+const ARG_LOG_FILE_VALUE: &str = "FILE";
+const ARG_LOG_FILE_HELP: &str = "Writes log lines to a rotating file instead of stdout";
+const ARG_LOG_FILE_MAX_SIZE: &str = "log-file-max-size";
+const ARG_LOG_FILE_MAX_SIZE_VALUE: &str = "BYTES";
+const ARG_LOG_FILE_MAX_SIZE_HELP: &str =
+    "Sets the size at which the log file is rotated [default: 10485760]";
+const GET_LOG_FILE_MAX_SIZE_EXPECT: &str = "Could not parse log-file-max-size argument";
+const ARG_LOG_FILE_COUNT: &str = "log-file-count";
+const ARG_LOG_FILE_COUNT_VALUE: &str = "NUM";
+const ARG_LOG_FILE_COUNT_HELP: &str = "Sets the number of rotated log files to keep [default: 5]";
+const GET_LOG_FILE_COUNT_EXPECT: &str = "Could not parse log-file-count argument";
+const INITIALIZE_LOGGER_ERROR: &str = "failed to open log file";
+
+// log-target
+const ARG_LOG_TARGET: &str = "log-target";
+const ARG_LOG_TARGET_VALUE: &str = "TARGET";
+const ARG_LOG_TARGET_HELP: &str =
+    "[ stdout | syslog ] Where log lines are written [default: stdout]";
+const LOG_TARGET_STDOUT: &str = "stdout";
+const LOG_TARGET_SYSLOG: &str = "syslog";
+const LOG_TARGET_INVALID_MESSAGE: &str = "log-target must be stdout or syslog";
+const LOG_FILE_WITH_SYSLOG_MESSAGE: &str = "log-file cannot be used with log-target syslog";
+
+// syslog-facility
+const ARG_SYSLOG_FACILITY: &str = "syslog-facility";
+const ARG_SYSLOG_FACILITY_VALUE: &str = "FACILITY";
+const ARG_SYSLOG_FACILITY_HELP: &str =
+    "[ user | daemon | local0 .. local7 ] Facility of syslog messages [default: user]";
+const SYSLOG_FACILITY_INVALID_MESSAGE: &str =
+    "syslog-facility must be user, daemon or one of local0 to local7";
+
+// syslog-ident
+const ARG_SYSLOG_IDENT: &str = "syslog-ident";
+const ARG_SYSLOG_IDENT_VALUE: &str = "IDENT";
+const ARG_SYSLOG_IDENT_HELP: &str =
+    "Prefixes syslog messages with this identifier [default: casperlabs-engine-grpc-server]";
+
+// async-logging
+const ARG_ASYNC_LOGGING: &str = "async-logging";
+const ARG_ASYNC_LOGGING_HELP: &str =
+    "Writes log lines on a background thread, dropping the least important ones if it falls behind";
+
+// log-include-thread-id
+const ARG_LOG_INCLUDE_THREAD_ID: &str = "log-include-thread-id";
+const ARG_LOG_INCLUDE_THREAD_ID_HELP: &str =
+    "Tags every log line with the id and name of the thread which logged it";
 
 // use-payment-code feature flag
 const ARG_USE_PAYMENT_CODE: &str = "use-payment-code";
 const ARG_USE_PAYMENT_CODE_SHORT: &str = "x";
 const ARG_USE_PAYMENT_CODE_HELP: &str = "Enables the use of payment code";
 
+// read-only
+const ARG_READ_ONLY: &str = "read-only";
+const ARG_READ_ONLY_HELP: &str =
+    "Opens existing global state read-only and rejects commit and genesis requests";
+
+// init-db-only
+const ARG_INIT_DB_ONLY: &str = "init-db-only";
+const ARG_INIT_DB_ONLY_HELP: &str =
+    "Creates the data directory and opens global state in it, then exits without serving";
+const INIT_DB_ONLY_READ_ONLY_MESSAGE: &str =
+    "init-db-only creates global state, so it cannot run with read-only";
+const INIT_DB_DONE_MESSAGE: &str = "global state initialized";
+
+// restore-from
+const ARG_RESTORE_FROM: &str = "restore-from";
+const ARG_RESTORE_FROM_VALUE: &str = "DIR";
+const ARG_RESTORE_FROM_HELP: &str =
+    "Copies global state from a backup_store directory into the data directory, which must not \
+     hold any yet, before opening it";
+const ARG_RESTORE_GENESIS_HASH: &str = "restore-genesis-hash";
+const ARG_RESTORE_GENESIS_HASH_VALUE: &str = "HASH";
+const ARG_RESTORE_GENESIS_HASH_HELP: &str =
+    "Hex encoded genesis hash the restored global state must have";
+
+// no-sync
+const ARG_NO_SYNC: &str = "no-sync";
+const ARG_NO_SYNC_HELP: &str = "Same as --sync-policy never";
+const NO_SYNC_WITH_SYNC_POLICY_MESSAGE: &str = "no-sync cannot be combined with sync-policy";
+
+// sync-policy
+const ARG_SYNC_POLICY: &str = "sync-policy";
+const ARG_SYNC_POLICY_VALUE: &str = "POLICY";
+const ARG_SYNC_POLICY_HELP: &str = "When commits are flushed to disk: always, by each commit; \
+                                    interval:<MS>, every MS milliseconds on a background thread; \
+                                    or never, until flush_store is called or the server stops \
+                                    [default: always]";
+const SYNC_POLICY_INTERVAL_PREFIX: &str = "interval:";
+const SYNC_POLICY_INVALID_MESSAGE: &str =
+    "sync-policy must be always, never or interval:<MS> with MS above 0";
+const SYNC_POLICY_INTERVAL_WARNING: &str = "durability is reduced, as a system crash may lose \
+                                            the commits made since the last flush";
+const SYNC_POLICY_NEVER_WARNING: &str = "durability is reduced, as commits are not flushed to \
+                                         disk until flush_store is called or the server stops";
+const SYNC_THREAD_NAME: &str = "lmdb-sync";
+const SYNC_THREAD_EXPECT: &str = "failed to start the thread syncing global state";
+
+// gas-config
+const ARG_GAS_CONFIG: &str = "gas-config";
+const ARG_GAS_CONFIG_VALUE: &str = "FILE";
+const ARG_GAS_CONFIG_HELP: &str =
+    "Sets a TOML file of wasm gas costs; missing entries keep their built-in values";
+const GAS_CONFIG_FILE_ERROR: &str = "failed to load gas config file";
+// costs of this protocol version are the fallback for entries missing from the gas config
+const GAS_CONFIG_DEFAULTS_PROTOCOL_VERSION: u64 = 1;
+
+// max-wasm-size
+const ARG_MAX_WASM_SIZE: &str = "max-wasm-size";
+const ARG_MAX_WASM_SIZE_VALUE: &str = "BYTES";
+const ARG_MAX_WASM_SIZE_HELP: &str =
+    "Sets the size in bytes above which wasm modules are rejected [default: 4194304]";
+const GET_MAX_WASM_SIZE_EXPECT: &str = "Could not parse max-wasm-size argument";
+const MAX_WASM_SIZE_ZERO_MESSAGE: &str = "max-wasm-size must be greater than 0";
+
+// max-memory-pages
+const ARG_MAX_MEMORY_PAGES: &str = "max-memory-pages";
+const ARG_MAX_MEMORY_PAGES_VALUE: &str = "NUM";
+const ARG_MAX_MEMORY_PAGES_HELP: &str =
+    "Sets the most 64 KiB pages of linear memory a wasm module may use [default: 64]";
+const GET_MAX_MEMORY_PAGES_EXPECT: &str = "Could not parse max-memory-pages argument";
+const MAX_MEMORY_PAGES_ZERO_MESSAGE: &str = "max-memory-pages must be greater than 0";
+
+// execution-timeout-ms
+const ARG_EXECUTION_TIMEOUT: &str = "execution-timeout-ms";
+const ARG_EXECUTION_TIMEOUT_VALUE: &str = "MILLISECONDS";
+const ARG_EXECUTION_TIMEOUT_HELP: &str =
+    "Aborts the execution of a deploy that runs longer than this [default: no limit]";
+const GET_EXECUTION_TIMEOUT_EXPECT: &str = "Could not parse execution-timeout-ms argument";
+const EXECUTION_TIMEOUT_ZERO_MESSAGE: &str = "execution-timeout-ms must be greater than 0";
+
+// max-gas-limit / default-gas-limit
+const ARG_MAX_GAS_LIMIT: &str = "max-gas-limit";
+const ARG_MAX_GAS_LIMIT_VALUE: &str = "GAS";
+const ARG_MAX_GAS_LIMIT_HELP: &str =
+    "Rejects exec requests with a deploy whose gas limit is higher than this [default: no limit]";
+const GET_MAX_GAS_LIMIT_EXPECT: &str = "Could not parse max-gas-limit argument";
+const MAX_GAS_LIMIT_ZERO_MESSAGE: &str = "max-gas-limit must be greater than 0";
+const ARG_DEFAULT_GAS_LIMIT: &str = "default-gas-limit";
+const ARG_DEFAULT_GAS_LIMIT_VALUE: &str = "GAS";
+const ARG_DEFAULT_GAS_LIMIT_HELP: &str =
+    "Sets the gas limit of deploys which give no gas price [default: such deploys are rejected]";
+const GET_DEFAULT_GAS_LIMIT_EXPECT: &str = "Could not parse default-gas-limit argument";
+const DEFAULT_GAS_LIMIT_ZERO_MESSAGE: &str = "default-gas-limit must be greater than 0";
+const DEFAULT_GAS_LIMIT_ABOVE_MAX_MESSAGE: &str =
+    "default-gas-limit must not be greater than max-gas-limit";
+
+// dedup-cache-size
+const ARG_DEDUP_CACHE_SIZE: &str = "dedup-cache-size";
+const ARG_DEDUP_CACHE_SIZE_VALUE: &str = "NUM";
+const ARG_DEDUP_CACHE_SIZE_HELP: &str =
+    "Keeps the results of this many deploys with a hash, to answer repeats of them [default: 1000]";
+const GET_DEDUP_CACHE_SIZE_EXPECT: &str = "Could not parse dedup-cache-size argument";
+
+// exec-cache-size
+const ARG_EXEC_CACHE_SIZE: &str = "exec-cache-size";
+const ARG_EXEC_CACHE_SIZE_VALUE: &str = "NUM";
+const ARG_EXEC_CACHE_SIZE_HELP: &str =
+    "Keeps the results of this many deploys, to answer deploys with the same content run against \
+     the same state [default: 0, no caching]";
+const GET_EXEC_CACHE_SIZE_EXPECT: &str = "Could not parse exec-cache-size argument";
+
+// wasm-cache-size
+const ARG_WASM_CACHE_SIZE: &str = "wasm-cache-size";
+const ARG_WASM_CACHE_SIZE_VALUE: &str = "NUM";
+const ARG_WASM_CACHE_SIZE_HELP: &str =
+    "Keeps this many prepared session modules, to run the same bytes again without validating \
+     and instrumenting them [default: 0, no caching]";
+const GET_WASM_CACHE_SIZE_EXPECT: &str = "Could not parse wasm-cache-size argument";
+
+// commit-retries
+const ARG_COMMIT_RETRIES: &str = "commit-retries";
+const ARG_COMMIT_RETRIES_VALUE: &str = "NUM";
+const ARG_COMMIT_RETRIES_HELP: &str =
+    "Retries a commit failing with a transient storage error this many times [default: 0]";
+const GET_COMMIT_RETRIES_EXPECT: &str = "Could not parse commit-retries argument";
+
+// commit-group-window-ms
+const ARG_COMMIT_GROUP_WINDOW: &str = "commit-group-window-ms";
+const ARG_COMMIT_GROUP_WINDOW_VALUE: &str = "MILLISECONDS";
+const ARG_COMMIT_GROUP_WINDOW_HELP: &str =
+    "Writes commits arriving this close together in one transaction [default: 0, no grouping]";
+const GET_COMMIT_GROUP_WINDOW_EXPECT: &str = "Could not parse commit-group-window-ms argument";
+
+// estimate-gas-max-trials
+const ARG_ESTIMATE_GAS_MAX_TRIALS: &str = "estimate-gas-max-trials";
+const ARG_ESTIMATE_GAS_MAX_TRIALS_VALUE: &str = "NUM";
+const ARG_ESTIMATE_GAS_MAX_TRIALS_HELP: &str =
+    "Runs a deploy at most this many times to estimate its gas limit [default: 16]";
+const GET_ESTIMATE_GAS_MAX_TRIALS_EXPECT: &str = "Could not parse estimate-gas-max-trials argument";
+
+// max-deploys-per-batch
+const ARG_MAX_DEPLOYS_PER_BATCH: &str = "max-deploys-per-batch";
+const ARG_MAX_DEPLOYS_PER_BATCH_VALUE: &str = "NUM";
+const ARG_MAX_DEPLOYS_PER_BATCH_HELP: &str =
+    "Rejects exec and exec_stream requests with more deploys than this [default: 10000]";
+const GET_MAX_DEPLOYS_PER_BATCH_EXPECT: &str = "Could not parse max-deploys-per-batch argument";
+const MAX_DEPLOYS_PER_BATCH_ZERO_MESSAGE: &str = "max-deploys-per-batch must be greater than 0";
+
+// max-query-depth
+const ARG_MAX_QUERY_DEPTH: &str = "max-query-depth";
+const ARG_MAX_QUERY_DEPTH_VALUE: &str = "NUM";
+const ARG_MAX_QUERY_DEPTH_HELP: &str =
+    "Rejects queries which follow more names than this from their base key [default: 64]";
+const GET_MAX_QUERY_DEPTH_EXPECT: &str = "Could not parse max-query-depth argument";
+
+// max-query-nodes
+const ARG_MAX_QUERY_NODES: &str = "max-query-nodes";
+const ARG_MAX_QUERY_NODES_VALUE: &str = "NUM";
+const ARG_MAX_QUERY_NODES_HELP: &str =
+    "Stops queries which visit more trie nodes than this [default: 10000]";
+const GET_MAX_QUERY_NODES_EXPECT: &str = "Could not parse max-query-nodes argument";
+const MAX_QUERY_NODES_ZERO_MESSAGE: &str = "max-query-nodes must be greater than 0";
+
+// max-rss-mb
+const ARG_MAX_RSS: &str = "max-rss-mb";
+const ARG_MAX_RSS_VALUE: &str = "MIB";
+const ARG_MAX_RSS_HELP: &str =
+    "Rejects exec, exec_stream and estimate_gas requests while the process's resident memory \
+     exceeds this many MiB";
+const GET_MAX_RSS_EXPECT: &str = "Could not parse max-rss-mb argument";
+const MAX_RSS_ZERO_MESSAGE: &str = "max-rss-mb must be greater than 0";
+const RSS_SAMPLING_ERROR: &str = "max-rss-mb is set, but memory use cannot be sampled";
+
+// gas-profiling
+const ARG_GAS_PROFILING: &str = "gas-profiling";
+const ARG_GAS_PROFILING_HELP: &str =
+    "Breaks down the cost of every deploy by what its gas was spent on, which slows execution";
+
+// wasm-stack-height
+const ARG_WASM_STACK_HEIGHT: &str = "wasm-stack-height";
+const ARG_WASM_STACK_HEIGHT_VALUE: &str = "NUM";
+const ARG_WASM_STACK_HEIGHT_HELP: &str =
+    "Sets the max wasm stack height, in value stack slots, before a call traps [default: 65536]";
+const GET_WASM_STACK_HEIGHT_EXPECT: &str = "Could not parse wasm-stack-height argument";
+const WASM_STACK_HEIGHT_ZERO_MESSAGE: &str = "wasm-stack-height must be greater than 0";
+
+// export / import
+const SUBCOMMAND_EXPORT: &str = "export";
+const SUBCOMMAND_EXPORT_ABOUT: &str =
+    "Writes the key/value pairs under a state root to a file and exits";
+const ARG_STATE_ROOT: &str = "state-root";
+const ARG_STATE_ROOT_VALUE: &str = "HASH";
+const ARG_STATE_ROOT_HELP: &str = "Hex encoded hash of the state root to export";
+const ARG_OUT: &str = "out";
+const ARG_OUT_VALUE: &str = "FILE";
+const ARG_OUT_HELP: &str = "Sets the file to write the pairs to";
+const SUBCOMMAND_IMPORT: &str = "import";
+const SUBCOMMAND_IMPORT_ABOUT: &str =
+    "Loads the pairs of an exported file into an empty data directory and exits";
+const ARG_IN: &str = "in";
+const ARG_IN_VALUE: &str = "FILE";
+const ARG_IN_HELP: &str = "Sets the file to read the pairs from";
+
+// replay
+const SUBCOMMAND_REPLAY: &str = "replay";
+const SUBCOMMAND_REPLAY_ABOUT: &str =
+    "Re-applies the commits of an audit log to an empty data directory, checking the root each \
+     one yields, and exits";
+const ARG_REPLAY_IN_HELP: &str = "Sets the audit log to replay";
+
+// validate
+const SUBCOMMAND_VALIDATE: &str = "validate";
+const SUBCOMMAND_VALIDATE_ABOUT: &str =
+    "Checks that a wasm module passes preprocessing with the configured costs and exits";
+const ARG_WASM: &str = "wasm";
+const ARG_WASM_VALUE: &str = "FILE";
+const ARG_WASM_HELP: &str = "Sets the wasm module to validate";
+
+// self-test
+const SUBCOMMAND_SELF_TEST: &str = "self-test";
+const SUBCOMMAND_SELF_TEST_ABOUT: &str =
+    "Executes, commits and queries back a built-in deploy against the data directory, reporting \
+     how long each step took, and exits";
+
 // runnable
-const SIGINT_HANDLE_EXPECT: &str = "Error setting Ctrl-C handler";
+const TERMINATION_HANDLE_EXPECT: &str = "Error setting SIGINT/SIGTERM handler";
 const RUNNABLE_CHECK_INTERVAL_SECONDS: u64 = 3;
+const SIGHUP_HANDLER_ERROR: &str = "Error setting SIGHUP handler, log level cannot be reloaded";
+const SIGNAL_WAKER_ERROR: &str = "Error waking up on signals, SIGHUP and SIGUSR1 are handled late";
+const LOG_LEVEL_RELOADED_MESSAGE: &str = "reloaded log level";
 
 // Command line arguments instance
 lazy_static! {
     static ref ARG_MATCHES: clap::ArgMatches<'static> = get_args();
 }
 
+// Settings merged from the config file and the command line arguments
+lazy_static! {
+    static ref CONFIG: Config = get_config();
+}
+
 // LogSettings instance to be used within this application
 lazy_static! {
     static ref LOG_SETTINGS: log_settings::LogSettings = get_log_settings();
 }
 
+// LogSettings instance used when reporting errors that occur before LOG_SETTINGS is available
+lazy_static! {
+    static ref FALLBACK_LOG_SETTINGS: log_settings::LogSettings =
+        LogSettings::new(PROC_NAME, LogLevelFilter::DEFAULT);
+}
+
 fn main() {
     set_panic_hook();
 
-    log_settings::set_log_settings_provider(&*LOG_SETTINGS);
+    if let Some(matches) = ARG_MATCHES.subcommand_matches(SUBCOMMAND_EXPORT) {
+        initialize_logger();
+        export_global_state(&*CONFIG, matches);
+        return;
+    }
+
+    if let Some(matches) = ARG_MATCHES.subcommand_matches(SUBCOMMAND_IMPORT) {
+        initialize_logger();
+        import_global_state(&*CONFIG, matches);
+        return;
+    }
+
+    if let Some(matches) = ARG_MATCHES.subcommand_matches(SUBCOMMAND_REPLAY) {
+        initialize_logger();
+        replay_audit_log(&*CONFIG, matches);
+        return;
+    }
+
+    if let Some(matches) = ARG_MATCHES.subcommand_matches(SUBCOMMAND_VALIDATE) {
+        initialize_logger();
+        validate_wasm(&*CONFIG, matches);
+        return;
+    }
+
+    if ARG_MATCHES
+        .subcommand_matches(SUBCOMMAND_SELF_TEST)
+        .is_some()
+    {
+        initialize_logger();
+        self_test(&*CONFIG);
+        return;
+    }
+
+    if ARG_MATCHES.is_present(ARG_INIT_DB_ONLY) {
+        initialize_logger();
+        init_db(&*CONFIG, get_threads(&*CONFIG));
+        return;
+    }
+
+    let pid_file = write_pid_file(&*CONFIG);
+
+    initialize_logger();
 
     logging::log_info(SERVER_START_MESSAGE);
 
-    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+    let config: &Config = &*CONFIG;
 
-    let socket = get_socket(matches);
+    let sockets = get_sockets(config);
 
-    match socket.remove_file() {
-        Err(e) => panic!("{}: {:?}", REMOVING_SOCKET_FILE_EXPECT, e),
-        Ok(_) => logging::log_info(REMOVING_SOCKET_FILE_MESSAGE),
-    };
+    for socket in &sockets {
+        if socket.get_path().is_some() {
+            match socket.remove_file() {
+                Err(e) => panic!("{}: {:?}", REMOVING_SOCKET_FILE_EXPECT, e),
+                Ok(_) => logging::log_info(REMOVING_SOCKET_FILE_MESSAGE),
+            };
+        }
+    }
+
+    let threads = get_threads(config);
+
+    logging::log_info(&format!("using {} gRPC server threads", threads));
+
+    let server_config = get_server_config(config, threads);
+
+    let engine_config: EngineConfig = get_engine_config(config);
+
+    let data_dir = get_data_dir(config, engine_config.is_read_only());
+
+    let restore_from = config.restore_from.as_ref().map(PathBuf::as_path);
+
+    if let Some(backup_dir) = restore_from {
+        restore_backup(backup_dir, &data_dir, &engine_config);
+    }
+
+    let map_size = get_map_size(config);
+
+    // each listener has its own pool of threads
+    let max_readers = get_max_readers(config, threads * sockets.len());
+
+    let sync_policy = get_sync_policy(config);
+
+    log_startup_config(
+        config,
+        &sockets,
+        &data_dir,
+        map_size,
+        &server_config,
+        max_readers,
+        sync_policy,
+    );
+
+    let environment = get_lmdb_environment(
+        data_dir,
+        map_size,
+        max_readers,
+        engine_config.is_read_only(),
+        sync_policy,
+        get_db_open_retries(config),
+    );
+
+    // before the servers are built, so that no request reaches a store which fails the check
+    if restore_from.is_some() {
+        let engine_state = get_engine_state(
+            Arc::clone(&environment),
+            get_store_name(config),
+            None,
+            None,
+            false,
+            None,
+            engine_config.clone(),
+        );
+        check_restored_genesis_hash(config, engine_state.genesis_hash(), &data_dir);
+    }
+
+    if let SyncPolicy::Interval(interval) = sync_policy {
+        if !engine_config.is_read_only() {
+            start_periodic_sync(Arc::clone(&environment), interval);
+        }
+    }
+
+    let store_name = get_store_name(config);
+
+    let trie_cache_size = get_trie_cache_size(config);
+
+    let warmup_depth = get_warmup_depth(config);
 
-    let data_dir = get_data_dir(matches);
+    let key_bloom = config.key_bloom.unwrap_or(false);
 
-    let map_size = get_map_size(matches);
+    let audit_log = config.audit_log.as_ref().map(PathBuf::as_path);
 
-    let engine_config: EngineConfig = get_engine_config(matches);
+    let health_status = HealthStatus::new();
 
-    let _server = get_grpc_server(&socket, data_dir, map_size, engine_config);
+    let drain = Drain::new();
 
-    log_listening_message(&socket);
+    let servers = get_grpc_servers(
+        &sockets,
+        &server_config,
+        Arc::clone(&environment),
+        store_name,
+        trie_cache_size,
+        warmup_depth,
+        key_bloom,
+        audit_log,
+        engine_config,
+        health_status.clone(),
+        drain.clone(),
+        get_memory_guard(config),
+    );
+
+    // global state is open once the server is built
+    health_status.set_serving(true);
+
+    for socket in &sockets {
+        set_socket_mode(socket, config);
+
+        log_listening_message(socket);
+    }
+
+    start_metrics_server(config);
 
     let interval = Duration::from_secs(RUNNABLE_CHECK_INTERVAL_SECONDS);
 
-    let runnable = get_sigint_handle();
+    let runnable = get_termination_handle();
+
+    // after the termination handle, which would otherwise stop the server on SIGHUP
+    if let Err(error) = os::catch_sighup() {
+        logging::log_warning(&format!("{}: {}", SIGHUP_HANDLER_ERROR, error));
+    }
+
+    if let Err(error) = os::catch_sigusr1() {
+        logging::log_warning(&format!("{}: {}", SIGUSR1_HANDLER_ERROR, error));
+    }
+
+    // without it, signals are still acted on, but only once the interval is up
+    if let Err(error) = os::wake_on_signals(std::thread::current()) {
+        logging::log_warning(&format!("{}: {}", SIGNAL_WAKER_ERROR, error));
+    }
 
     while runnable.load(Ordering::SeqCst) {
         std::thread::park_timeout(interval);
+        if os::take_sighup() {
+            reload_log_level();
+        }
+        if os::take_sigusr1() {
+            drain_requests(&health_status, &drain, get_drain_timeout(config));
+            break;
+        }
     }
 
     logging::log_info(SERVER_STOP_MESSAGE);
+
+    shutdown(
+        &health_status,
+        servers,
+        &sockets,
+        &environment,
+        pid_file.as_ref().map(PathBuf::as_path),
+    );
+
+    logging::flush();
+}
+
+/// Creates the data directory and opens global state in it as the server would, then flushes it
+/// to disk.  A server started on it afterwards finds the state as left here, including pairs
+/// loaded by the `import` subcommand.  Genesis itself is only run by a `run_genesis` request.
+fn init_db(config: &Config, threads: usize) {
+    let engine_config = get_engine_config(config);
+    if engine_config.is_read_only() {
+        exit_with_fatal(INIT_DB_ONLY_READ_ONLY_MESSAGE);
+    }
+
+    let data_dir = get_data_dir(config, false);
+
+    let restore_from = config.restore_from.as_ref().map(PathBuf::as_path);
+
+    if let Some(backup_dir) = restore_from {
+        restore_backup(backup_dir, &data_dir, &engine_config);
+    }
+
+    let environment = get_lmdb_environment(
+        data_dir.clone(),
+        get_map_size(config),
+        get_max_readers(config, threads),
+        false,
+        get_sync_policy(config),
+        get_db_open_retries(config),
+    );
+
+    let engine_state = get_engine_state(
+        Arc::clone(&environment),
+        get_store_name(config),
+        None,
+        None,
+        false,
+        None,
+        engine_config,
+    );
+
+    if restore_from.is_some() {
+        check_restored_genesis_hash(config, engine_state.genesis_hash(), &data_dir);
+    }
+
+    if let Err(e) = environment.sync() {
+        exit_with_fatal(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+    }
+
+    let mut properties: BTreeMap<String, String> = BTreeMap::new();
+    properties.insert("data_dir".to_string(), data_dir.display().to_string());
+    properties.insert(
+        "genesis_hash".to_string(),
+        engine_state
+            .genesis_hash()
+            .map_or_else(|| "none".to_string(), |hash| format!("{:?}", hash)),
+    );
+    logging::log_details(
+        log_level::LogLevel::Info,
+        format!(
+            "{} in {{data_dir}}; genesis: {{genesis_hash}}",
+            INIT_DB_DONE_MESSAGE
+        ),
+        properties,
+    );
+}
+
+/// Reports `NOT_SERVING` to health checks and rejects new exec and commit requests, then waits up
+/// to `timeout` for the requests in flight to complete.  Any still running when it runs out are
+/// aborted as the process exits; the commits among them are rolled back.
+fn drain_requests(health_status: &HealthStatus, drain: &Drain, timeout: Duration) {
+    health_status.set_serving(false);
+    drain.start();
+    logging::log_info(DRAIN_START_MESSAGE);
+
+    if !drain.wait(timeout) {
+        logging::log_warning(&format!("{}: {}", DRAIN_TIMEOUT_MESSAGE, drain.in_flight()));
+    }
+}
+
+/// Gets value of drain-timeout setting
+fn get_drain_timeout(config: &Config) -> Duration {
+    Duration::from_secs(
+        config
+            .drain_timeout
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECONDS),
+    )
+}
+
+/// Installs [`LOG_SETTINGS`] and opens the log output
+fn initialize_logger() {
+    log_settings::set_log_settings_provider(&*LOG_SETTINGS);
+
+    if let Err(error) = logging::initialize_logger(&*LOG_SETTINGS) {
+        exit_with_fatal(&format!("{}: {}", INITIALIZE_LOGGER_ERROR, error));
+    }
+}
+
+/// Reports `NOT_SERVING` to health checks, stops the gRPC servers, removes the socket and pid
+/// files and flushes the LMDB environment to disk
+fn shutdown(
+    health_status: &HealthStatus,
+    servers: Vec<grpc::Server>,
+    sockets: &[socket::Socket],
+    environment: &LmdbEnvironment,
+    pid_file: Option<&Path>,
+) {
+    health_status.set_serving(false);
+
+    drop(servers);
+
+    for socket in sockets {
+        if let Err(e) = socket.remove_file() {
+            logging::log_error(&format!("{}: {:?}", REMOVING_SOCKET_FILE_EXPECT, e));
+        }
+    }
+
+    if let Err(e) = environment.sync() {
+        logging::log_error(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+    }
+
+    if let Some(path) = pid_file {
+        if let Err(e) = fs::remove_file(path) {
+            logging::log_error(&format!("{}: {:?}", PID_FILE_REMOVE_ERROR, e));
+        }
+    }
+}
+
+/// Writes the id of this process to the pid-file, if one was given, and returns its path.
+///
+/// Exits if the file already holds the id of a live process, as that is most likely another
+/// instance using the same data directory.
+fn write_pid_file(config: &Config) -> Option<PathBuf> {
+    let path = config.pid_file.clone()?;
+
+    let existing_pid = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| u32::from_str(contents.trim()).ok());
+
+    if let Some(pid) = existing_pid {
+        if pid != process::id() && os::is_process_alive(pid) {
+            exit_with_fatal(&format!(
+                "pid file {} belongs to running process {}",
+                path.display(),
+                pid
+            ));
+        }
+    }
+
+    if let Err(error) = fs::write(&path, format!("{}\n", process::id())) {
+        exit_with_fatal(&format!(
+            "{} {}: {}",
+            PID_FILE_WRITE_ERROR,
+            path.display(),
+            error
+        ));
+    }
+
+    Some(path)
 }
 
-/// Sets panic hook for logging panic info
+/// Sets panic hook for logging panic info.  A panic while handling a request only fails that
+/// request, so it is logged as an error rather than as fatal.
 fn set_panic_hook() {
     let hook: Box<dyn Fn(&std::panic::PanicInfo) + 'static + Sync + Send> =
         Box::new(move |panic_info| {
+            if panic_boundary::is_catching_panics() {
+                logging::log_error(&format!("{:?}", panic_info));
+                return;
+            }
+
             match panic_info.payload().downcast_ref::<&str>() {
                 Some(s) => {
                     let panic_message = format!("{:?}", s);
@@ -149,6 +1034,7 @@ fn set_panic_hook() {
             }
 
             logging::log_info(SERVER_STOP_MESSAGE);
+            logging::flush();
         });
     std::panic::set_hook(hook);
 }
@@ -156,6 +1042,14 @@ fn set_panic_hook() {
 /// Gets command line arguments
 fn get_args() -> ArgMatches<'static> {
     App::new(APP_NAME)
+        .arg(
+            Arg::with_name(ARG_CONFIG)
+                .short(ARG_CONFIG_SHORT)
+                .long(ARG_CONFIG)
+                .value_name(ARG_CONFIG_VALUE)
+                .help(ARG_CONFIG_HELP)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARG_LOG_LEVEL)
                 .required(false)
@@ -164,6 +1058,79 @@ fn get_args() -> ArgMatches<'static> {
                 .value_name(ARG_LOG_LEVEL_VALUE)
                 .help(ARG_LOG_LEVEL_HELP),
         )
+        .arg(
+            Arg::with_name(ARG_LOG_FORMAT)
+                .long(ARG_LOG_FORMAT)
+                .value_name(ARG_LOG_FORMAT_VALUE)
+                .help(ARG_LOG_FORMAT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_TIMESTAMP_FORMAT)
+                .long(ARG_LOG_TIMESTAMP_FORMAT)
+                .value_name(ARG_LOG_TIMESTAMP_FORMAT_VALUE)
+                .help(ARG_LOG_TIMESTAMP_FORMAT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_DETERMINISTIC_TIMESTAMPS)
+                .long(ARG_DETERMINISTIC_TIMESTAMPS)
+                .help(ARG_DETERMINISTIC_TIMESTAMPS_HELP)
+                .conflicts_with(ARG_LOG_TIMESTAMP_FORMAT)
+                .hidden(true),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_FILE)
+                .long(ARG_LOG_FILE)
+                .value_name(ARG_LOG_FILE_VALUE)
+                .help(ARG_LOG_FILE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_FILE_MAX_SIZE)
+                .long(ARG_LOG_FILE_MAX_SIZE)
+                .value_name(ARG_LOG_FILE_MAX_SIZE_VALUE)
+                .help(ARG_LOG_FILE_MAX_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_FILE_COUNT)
+                .long(ARG_LOG_FILE_COUNT)
+                .value_name(ARG_LOG_FILE_COUNT_VALUE)
+                .help(ARG_LOG_FILE_COUNT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_TARGET)
+                .long(ARG_LOG_TARGET)
+                .value_name(ARG_LOG_TARGET_VALUE)
+                .help(ARG_LOG_TARGET_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_SYSLOG_FACILITY)
+                .long(ARG_SYSLOG_FACILITY)
+                .value_name(ARG_SYSLOG_FACILITY_VALUE)
+                .help(ARG_SYSLOG_FACILITY_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_SYSLOG_IDENT)
+                .long(ARG_SYSLOG_IDENT)
+                .value_name(ARG_SYSLOG_IDENT_VALUE)
+                .help(ARG_SYSLOG_IDENT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_ASYNC_LOGGING)
+                .long(ARG_ASYNC_LOGGING)
+                .help(ARG_ASYNC_LOGGING_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_LOG_INCLUDE_THREAD_ID)
+                .long(ARG_LOG_INCLUDE_THREAD_ID)
+                .help(ARG_LOG_INCLUDE_THREAD_ID_HELP),
+        )
         .arg(
             Arg::with_name(ARG_DATA_DIR)
                 .short(ARG_DATA_DIR_SHORT)
@@ -172,6 +1139,13 @@ fn get_args() -> ArgMatches<'static> {
                 .help(ARG_DATA_DIR_HELP)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(ARG_STATE_SUBDIR)
+                .long(ARG_STATE_SUBDIR)
+                .value_name(ARG_STATE_SUBDIR_VALUE)
+                .help(ARG_STATE_SUBDIR_HELP)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARG_PAGES)
                 .short(ARG_PAGES_SHORT)
@@ -180,6 +1154,66 @@ fn get_args() -> ArgMatches<'static> {
                 .help(ARG_PAGES_HELP)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(ARG_MAX_DB_SIZE)
+                .long(ARG_MAX_DB_SIZE)
+                .value_name(ARG_MAX_DB_SIZE_VALUE)
+                .help(ARG_MAX_DB_SIZE_HELP)
+                .takes_value(true)
+                .conflicts_with(ARG_PAGES),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_READERS)
+                .long(ARG_MAX_READERS)
+                .value_name(ARG_MAX_READERS_VALUE)
+                .help(ARG_MAX_READERS_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_DB_OPEN_RETRIES)
+                .long(ARG_DB_OPEN_RETRIES)
+                .value_name(ARG_DB_OPEN_RETRIES_VALUE)
+                .help(ARG_DB_OPEN_RETRIES_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_TRIE_CACHE_SIZE)
+                .long(ARG_TRIE_CACHE_SIZE)
+                .value_name(ARG_TRIE_CACHE_SIZE_VALUE)
+                .help(ARG_TRIE_CACHE_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_WARMUP)
+                .long(ARG_WARMUP)
+                .help(ARG_WARMUP_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_WARMUP_DEPTH)
+                .long(ARG_WARMUP_DEPTH)
+                .value_name(ARG_WARMUP_DEPTH_VALUE)
+                .help(ARG_WARMUP_DEPTH_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_KEY_BLOOM)
+                .long(ARG_KEY_BLOOM)
+                .help(ARG_KEY_BLOOM_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_STORE_NAME)
+                .long(ARG_STORE_NAME)
+                .value_name(ARG_STORE_NAME_VALUE)
+                .help(ARG_STORE_NAME_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_AUDIT_LOG)
+                .long(ARG_AUDIT_LOG)
+                .value_name(ARG_AUDIT_LOG_VALUE)
+                .help(ARG_AUDIT_LOG_HELP)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARG_USE_PAYMENT_CODE)
                 .short(ARG_USE_PAYMENT_CODE_SHORT)
@@ -187,107 +1221,1424 @@ fn get_args() -> ArgMatches<'static> {
                 .help(ARG_USE_PAYMENT_CODE_HELP),
         )
         .arg(
-            Arg::with_name(ARG_SOCKET)
-                .required(true)
-                .help(ARG_SOCKET_HELP)
-                .index(1),
+            Arg::with_name(ARG_GAS_CONFIG)
+                .long(ARG_GAS_CONFIG)
+                .value_name(ARG_GAS_CONFIG_VALUE)
+                .help(ARG_GAS_CONFIG_HELP)
+                .takes_value(true),
         )
-        .get_matches()
-}
-
-/// Gets SIGINT handle to allow clean exit
-fn get_sigint_handle() -> Arc<AtomicBool> {
-    let handle = Arc::new(AtomicBool::new(true));
-    let h = handle.clone();
-    ctrlc::set_handler(move || {
-        h.store(false, Ordering::SeqCst);
-    })
-    .expect(SIGINT_HANDLE_EXPECT);
-    handle
-}
-
-/// Gets value of socket argument
-fn get_socket(matches: &ArgMatches) -> socket::Socket {
-    let socket = matches.value_of(ARG_SOCKET).expect(ARG_SOCKET_EXPECT);
-
-    socket::Socket::new(socket.to_owned())
-}
-
-/// Gets value of data-dir argument
-fn get_data_dir(matches: &ArgMatches) -> PathBuf {
-    let mut buf = matches.value_of(ARG_DATA_DIR).map_or(
-        {
-            let mut dir = home_dir().expect(GET_HOME_DIR_EXPECT);
-            dir.push(DEFAULT_DATA_DIR_RELATIVE);
-            dir
-        },
-        PathBuf::from,
-    );
-    buf.push(GLOBAL_STATE_DIR);
-    fs::create_dir_all(&buf).unwrap_or_else(|_| panic!("{}: {:?}", CREATE_DATA_DIR_EXPECT, buf));
-    buf
-}
-
-///  Parses pages argument and returns map size
-fn get_map_size(matches: &ArgMatches) -> usize {
-    let page_size = get_page_size().unwrap();
-    let pages = matches
-        .value_of(ARG_PAGES)
-        .map_or(Ok(DEFAULT_PAGES), usize::from_str)
-        .expect(GET_PAGES_EXPECT);
-    page_size * pages
+        .arg(
+            Arg::with_name(ARG_EXECUTION_TIMEOUT)
+                .long(ARG_EXECUTION_TIMEOUT)
+                .value_name(ARG_EXECUTION_TIMEOUT_VALUE)
+                .help(ARG_EXECUTION_TIMEOUT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_GAS_LIMIT)
+                .long(ARG_MAX_GAS_LIMIT)
+                .value_name(ARG_MAX_GAS_LIMIT_VALUE)
+                .help(ARG_MAX_GAS_LIMIT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_DEFAULT_GAS_LIMIT)
+                .long(ARG_DEFAULT_GAS_LIMIT)
+                .value_name(ARG_DEFAULT_GAS_LIMIT_VALUE)
+                .help(ARG_DEFAULT_GAS_LIMIT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_DEDUP_CACHE_SIZE)
+                .long(ARG_DEDUP_CACHE_SIZE)
+                .value_name(ARG_DEDUP_CACHE_SIZE_VALUE)
+                .help(ARG_DEDUP_CACHE_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_EXEC_CACHE_SIZE)
+                .long(ARG_EXEC_CACHE_SIZE)
+                .value_name(ARG_EXEC_CACHE_SIZE_VALUE)
+                .help(ARG_EXEC_CACHE_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_WASM_CACHE_SIZE)
+                .long(ARG_WASM_CACHE_SIZE)
+                .value_name(ARG_WASM_CACHE_SIZE_VALUE)
+                .help(ARG_WASM_CACHE_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_COMMIT_RETRIES)
+                .long(ARG_COMMIT_RETRIES)
+                .value_name(ARG_COMMIT_RETRIES_VALUE)
+                .help(ARG_COMMIT_RETRIES_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_COMMIT_GROUP_WINDOW)
+                .long(ARG_COMMIT_GROUP_WINDOW)
+                .value_name(ARG_COMMIT_GROUP_WINDOW_VALUE)
+                .help(ARG_COMMIT_GROUP_WINDOW_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_ESTIMATE_GAS_MAX_TRIALS)
+                .long(ARG_ESTIMATE_GAS_MAX_TRIALS)
+                .value_name(ARG_ESTIMATE_GAS_MAX_TRIALS_VALUE)
+                .help(ARG_ESTIMATE_GAS_MAX_TRIALS_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_RSS)
+                .long(ARG_MAX_RSS)
+                .value_name(ARG_MAX_RSS_VALUE)
+                .help(ARG_MAX_RSS_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_DEPLOYS_PER_BATCH)
+                .long(ARG_MAX_DEPLOYS_PER_BATCH)
+                .value_name(ARG_MAX_DEPLOYS_PER_BATCH_VALUE)
+                .help(ARG_MAX_DEPLOYS_PER_BATCH_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_QUERY_DEPTH)
+                .long(ARG_MAX_QUERY_DEPTH)
+                .value_name(ARG_MAX_QUERY_DEPTH_VALUE)
+                .help(ARG_MAX_QUERY_DEPTH_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_QUERY_NODES)
+                .long(ARG_MAX_QUERY_NODES)
+                .value_name(ARG_MAX_QUERY_NODES_VALUE)
+                .help(ARG_MAX_QUERY_NODES_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_GAS_PROFILING)
+                .long(ARG_GAS_PROFILING)
+                .help(ARG_GAS_PROFILING_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_WASM_SIZE)
+                .long(ARG_MAX_WASM_SIZE)
+                .value_name(ARG_MAX_WASM_SIZE_VALUE)
+                .help(ARG_MAX_WASM_SIZE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_MEMORY_PAGES)
+                .long(ARG_MAX_MEMORY_PAGES)
+                .value_name(ARG_MAX_MEMORY_PAGES_VALUE)
+                .help(ARG_MAX_MEMORY_PAGES_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_WASM_STACK_HEIGHT)
+                .long(ARG_WASM_STACK_HEIGHT)
+                .value_name(ARG_WASM_STACK_HEIGHT_VALUE)
+                .help(ARG_WASM_STACK_HEIGHT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_READ_ONLY)
+                .long(ARG_READ_ONLY)
+                .help(ARG_READ_ONLY_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_INIT_DB_ONLY)
+                .long(ARG_INIT_DB_ONLY)
+                .help(ARG_INIT_DB_ONLY_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_RESTORE_FROM)
+                .long(ARG_RESTORE_FROM)
+                .value_name(ARG_RESTORE_FROM_VALUE)
+                .help(ARG_RESTORE_FROM_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_RESTORE_GENESIS_HASH)
+                .long(ARG_RESTORE_GENESIS_HASH)
+                .value_name(ARG_RESTORE_GENESIS_HASH_VALUE)
+                .help(ARG_RESTORE_GENESIS_HASH_HELP)
+                .requires(ARG_RESTORE_FROM)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_NO_SYNC)
+                .long(ARG_NO_SYNC)
+                .help(ARG_NO_SYNC_HELP)
+                .conflicts_with(ARG_SYNC_POLICY),
+        )
+        .arg(
+            Arg::with_name(ARG_SYNC_POLICY)
+                .long(ARG_SYNC_POLICY)
+                .value_name(ARG_SYNC_POLICY_VALUE)
+                .help(ARG_SYNC_POLICY_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_PID_FILE)
+                .long(ARG_PID_FILE)
+                .value_name(ARG_PID_FILE_VALUE)
+                .help(ARG_PID_FILE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_SOCKET_MODE)
+                .long(ARG_SOCKET_MODE)
+                .value_name(ARG_SOCKET_MODE_VALUE)
+                .help(ARG_SOCKET_MODE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_ABSTRACT_SOCKET)
+                .long(ARG_ABSTRACT_SOCKET)
+                .help(ARG_ABSTRACT_SOCKET_HELP),
+        )
+        .arg(
+            Arg::with_name(ARG_METRICS_ADDR)
+                .long(ARG_METRICS_ADDR)
+                .value_name(ARG_METRICS_ADDR_VALUE)
+                .help(ARG_METRICS_ADDR_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_THREADS)
+                .short(ARG_THREADS_SHORT)
+                .long(ARG_THREADS)
+                .value_name(ARG_THREADS_VALUE)
+                .help(ARG_THREADS_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_CONCURRENT_REQUESTS)
+                .long(ARG_MAX_CONCURRENT_REQUESTS)
+                .value_name(ARG_MAX_CONCURRENT_REQUESTS_VALUE)
+                .help(ARG_MAX_CONCURRENT_REQUESTS_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_SOCKET_BACKLOG)
+                .long(ARG_SOCKET_BACKLOG)
+                .value_name(ARG_SOCKET_BACKLOG_VALUE)
+                .help(ARG_SOCKET_BACKLOG_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_DRAIN_TIMEOUT)
+                .long(ARG_DRAIN_TIMEOUT)
+                .value_name(ARG_DRAIN_TIMEOUT_VALUE)
+                .help(ARG_DRAIN_TIMEOUT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_READ_TIMEOUT)
+                .long(ARG_READ_TIMEOUT)
+                .value_name(ARG_READ_TIMEOUT_VALUE)
+                .help(ARG_READ_TIMEOUT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_TLS_CERT)
+                .long(ARG_TLS_CERT)
+                .value_name(ARG_TLS_CERT_VALUE)
+                .help(ARG_TLS_CERT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_TLS_KEY)
+                .long(ARG_TLS_KEY)
+                .value_name(ARG_TLS_KEY_VALUE)
+                .help(ARG_TLS_KEY_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_SOCKET)
+                .required(false)
+                .help(ARG_SOCKET_HELP)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name(ARG_SOCKETS)
+                .long(ARG_SOCKET)
+                .value_name(ARG_SOCKETS_VALUE)
+                .help(ARG_SOCKETS_HELP)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_EXPORT)
+                .about(SUBCOMMAND_EXPORT_ABOUT)
+                .arg(
+                    Arg::with_name(ARG_STATE_ROOT)
+                        .long(ARG_STATE_ROOT)
+                        .value_name(ARG_STATE_ROOT_VALUE)
+                        .help(ARG_STATE_ROOT_HELP)
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARG_OUT)
+                        .long(ARG_OUT)
+                        .value_name(ARG_OUT_VALUE)
+                        .help(ARG_OUT_HELP)
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_IMPORT)
+                .about(SUBCOMMAND_IMPORT_ABOUT)
+                .arg(
+                    Arg::with_name(ARG_IN)
+                        .long(ARG_IN)
+                        .value_name(ARG_IN_VALUE)
+                        .help(ARG_IN_HELP)
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_REPLAY)
+                .about(SUBCOMMAND_REPLAY_ABOUT)
+                .arg(
+                    Arg::with_name(ARG_IN)
+                        .long(ARG_IN)
+                        .value_name(ARG_IN_VALUE)
+                        .help(ARG_REPLAY_IN_HELP)
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND_VALIDATE)
+                .about(SUBCOMMAND_VALIDATE_ABOUT)
+                .arg(
+                    Arg::with_name(ARG_WASM)
+                        .long(ARG_WASM)
+                        .value_name(ARG_WASM_VALUE)
+                        .help(ARG_WASM_HELP)
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(SubCommand::with_name(SUBCOMMAND_SELF_TEST).about(SUBCOMMAND_SELF_TEST_ABOUT))
+        .get_matches()
 }
 
-/// Parses `use-payment-code` argument and returns an [`EngineConfig`].
-fn get_engine_config(matches: &ArgMatches) -> EngineConfig {
-    let use_payment_code = matches.is_present(ARG_USE_PAYMENT_CODE);
-    EngineConfig::new().use_payment_code(use_payment_code)
+/// Gets SIGINT / SIGTERM handle to allow clean exit.  The calling thread is woken up when the
+/// handle is cleared.
+fn get_termination_handle() -> Arc<AtomicBool> {
+    let handle = Arc::new(AtomicBool::new(true));
+    let h = handle.clone();
+    let main_thread = std::thread::current();
+    ctrlc::set_handler(move || {
+        h.store(false, Ordering::SeqCst);
+        main_thread.unpark();
+    })
+    .expect(TERMINATION_HANDLE_EXPECT);
+    handle
 }
 
-/// Builds and returns a gRPC server.
-fn get_grpc_server(
-    socket: &socket::Socket,
-    data_dir: PathBuf,
-    map_size: usize,
+/// Loads the config file, if one was given, and applies command line arguments over it
+fn get_config() -> Config {
+    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+
+    let file_config = match matches.value_of(ARG_CONFIG) {
+        Some(path) => Config::from_file(path).unwrap_or_else(|error| {
+            exit_with_fatal(&format!("{} {}: {}", CONFIG_FILE_ERROR, path, error))
+        }),
+        None => Config::default(),
+    };
+
+    file_config
+        .merge(get_env_config())
+        .merge(get_arg_config(matches))
+}
+
+/// Re-reads the loglevel setting, as on startup, and applies its global level to
+/// [`LOG_SETTINGS`].  Keeps the current level if the config file cannot be read.
+fn reload_log_level() {
+    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+
+    let file_config = match matches.value_of(ARG_CONFIG) {
+        Some(path) => match Config::from_file(path) {
+            Ok(file_config) => file_config,
+            Err(error) => {
+                logging::log_warning(&format!("{} {}: {}", CONFIG_FILE_ERROR, path, error));
+                return;
+            }
+        },
+        None => Config::default(),
+    };
+
+    let config = file_config
+        .merge(get_env_config())
+        .merge(get_arg_config(matches));
+
+    let log_level_filter = get_log_level_filter(&config);
+
+    LOG_SETTINGS.set_log_level_filter(log_level_filter);
+
+    logging::log_info(&format!(
+        "{}: {}",
+        LOG_LEVEL_RELOADED_MESSAGE,
+        log_level_filter.as_log_level().to_uppercase()
+    ));
+}
+
+/// Collects the settings given as environment variables
+fn get_env_config() -> Config {
+    Config {
+        loglevel: env::var(ENV_LOG_LEVEL).ok(),
+        ..Default::default()
+    }
+}
+
+/// Collects the settings given as command line arguments
+fn get_arg_config(matches: &ArgMatches) -> Config {
+    Config {
+        socket: get_arg_sockets(matches),
+        socket_mode: matches.value_of(ARG_SOCKET_MODE).map(str::to_owned),
+        abstract_socket: if matches.is_present(ARG_ABSTRACT_SOCKET) {
+            Some(true)
+        } else {
+            None
+        },
+        pid_file: matches.value_of(ARG_PID_FILE).map(PathBuf::from),
+        data_dir: matches.value_of(ARG_DATA_DIR).map(PathBuf::from),
+        state_subdir: matches.value_of(ARG_STATE_SUBDIR).map(str::to_owned),
+        loglevel: matches.value_of(ARG_LOG_LEVEL).map(str::to_owned),
+        log_format: matches.value_of(ARG_LOG_FORMAT).map(str::to_owned),
+        log_timestamp_format: matches
+            .value_of(ARG_LOG_TIMESTAMP_FORMAT)
+            .map(str::to_owned),
+        log_file: matches.value_of(ARG_LOG_FILE).map(PathBuf::from),
+        log_file_max_size: matches
+            .value_of(ARG_LOG_FILE_MAX_SIZE)
+            .map(|size| u64::from_str(size).expect(GET_LOG_FILE_MAX_SIZE_EXPECT)),
+        log_file_count: matches
+            .value_of(ARG_LOG_FILE_COUNT)
+            .map(|count| usize::from_str(count).expect(GET_LOG_FILE_COUNT_EXPECT)),
+        log_target: matches.value_of(ARG_LOG_TARGET).map(str::to_owned),
+        syslog_facility: matches.value_of(ARG_SYSLOG_FACILITY).map(str::to_owned),
+        syslog_ident: matches.value_of(ARG_SYSLOG_IDENT).map(str::to_owned),
+        async_logging: if matches.is_present(ARG_ASYNC_LOGGING) {
+            Some(true)
+        } else {
+            None
+        },
+        log_include_thread_id: if matches.is_present(ARG_LOG_INCLUDE_THREAD_ID) {
+            Some(true)
+        } else {
+            None
+        },
+        pages: matches
+            .value_of(ARG_PAGES)
+            .map(|pages| usize::from_str(pages).expect(GET_PAGES_EXPECT)),
+        max_db_size: matches
+            .value_of(ARG_MAX_DB_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_MAX_DB_SIZE_EXPECT)),
+        max_readers: matches
+            .value_of(ARG_MAX_READERS)
+            .map(|max_readers| u32::from_str(max_readers).expect(GET_MAX_READERS_EXPECT)),
+        db_open_retries: matches
+            .value_of(ARG_DB_OPEN_RETRIES)
+            .map(|retries| u32::from_str(retries).expect(GET_DB_OPEN_RETRIES_EXPECT)),
+        trie_cache_size: matches
+            .value_of(ARG_TRIE_CACHE_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_TRIE_CACHE_SIZE_EXPECT)),
+        warmup: if matches.is_present(ARG_WARMUP) {
+            Some(true)
+        } else {
+            None
+        },
+        warmup_depth: matches
+            .value_of(ARG_WARMUP_DEPTH)
+            .map(|depth| u32::from_str(depth).expect(GET_WARMUP_DEPTH_EXPECT)),
+        key_bloom: if matches.is_present(ARG_KEY_BLOOM) {
+            Some(true)
+        } else {
+            None
+        },
+        store_name: matches.value_of(ARG_STORE_NAME).map(str::to_owned),
+        audit_log: matches.value_of(ARG_AUDIT_LOG).map(PathBuf::from),
+        use_payment_code: if matches.is_present(ARG_USE_PAYMENT_CODE) {
+            Some(true)
+        } else {
+            None
+        },
+        read_only: if matches.is_present(ARG_READ_ONLY) {
+            Some(true)
+        } else {
+            None
+        },
+        restore_from: matches.value_of(ARG_RESTORE_FROM).map(PathBuf::from),
+        restore_genesis_hash: matches
+            .value_of(ARG_RESTORE_GENESIS_HASH)
+            .map(str::to_owned),
+        no_sync: if matches.is_present(ARG_NO_SYNC) {
+            Some(true)
+        } else {
+            None
+        },
+        sync_policy: matches.value_of(ARG_SYNC_POLICY).map(str::to_owned),
+        gas_config: matches.value_of(ARG_GAS_CONFIG).map(PathBuf::from),
+        max_wasm_size: matches
+            .value_of(ARG_MAX_WASM_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_MAX_WASM_SIZE_EXPECT)),
+        max_memory_pages: matches
+            .value_of(ARG_MAX_MEMORY_PAGES)
+            .map(|pages| u32::from_str(pages).expect(GET_MAX_MEMORY_PAGES_EXPECT)),
+        wasm_stack_height: matches
+            .value_of(ARG_WASM_STACK_HEIGHT)
+            .map(|height| u32::from_str(height).expect(GET_WASM_STACK_HEIGHT_EXPECT)),
+        execution_timeout_ms: matches
+            .value_of(ARG_EXECUTION_TIMEOUT)
+            .map(|timeout| u64::from_str(timeout).expect(GET_EXECUTION_TIMEOUT_EXPECT)),
+        max_gas_limit: matches
+            .value_of(ARG_MAX_GAS_LIMIT)
+            .map(|gas| u64::from_str(gas).expect(GET_MAX_GAS_LIMIT_EXPECT)),
+        default_gas_limit: matches
+            .value_of(ARG_DEFAULT_GAS_LIMIT)
+            .map(|gas| u64::from_str(gas).expect(GET_DEFAULT_GAS_LIMIT_EXPECT)),
+        dedup_cache_size: matches
+            .value_of(ARG_DEDUP_CACHE_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_DEDUP_CACHE_SIZE_EXPECT)),
+        exec_cache_size: matches
+            .value_of(ARG_EXEC_CACHE_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_EXEC_CACHE_SIZE_EXPECT)),
+        wasm_cache_size: matches
+            .value_of(ARG_WASM_CACHE_SIZE)
+            .map(|size| usize::from_str(size).expect(GET_WASM_CACHE_SIZE_EXPECT)),
+        commit_retries: matches
+            .value_of(ARG_COMMIT_RETRIES)
+            .map(|retries| u32::from_str(retries).expect(GET_COMMIT_RETRIES_EXPECT)),
+        commit_group_window_ms: matches
+            .value_of(ARG_COMMIT_GROUP_WINDOW)
+            .map(|window| u64::from_str(window).expect(GET_COMMIT_GROUP_WINDOW_EXPECT)),
+        estimate_gas_max_trials: matches
+            .value_of(ARG_ESTIMATE_GAS_MAX_TRIALS)
+            .map(|trials| u32::from_str(trials).expect(GET_ESTIMATE_GAS_MAX_TRIALS_EXPECT)),
+        max_deploys_per_batch: matches
+            .value_of(ARG_MAX_DEPLOYS_PER_BATCH)
+            .map(|max| usize::from_str(max).expect(GET_MAX_DEPLOYS_PER_BATCH_EXPECT)),
+        max_query_depth: matches
+            .value_of(ARG_MAX_QUERY_DEPTH)
+            .map(|max| usize::from_str(max).expect(GET_MAX_QUERY_DEPTH_EXPECT)),
+        max_query_nodes: matches
+            .value_of(ARG_MAX_QUERY_NODES)
+            .map(|max| usize::from_str(max).expect(GET_MAX_QUERY_NODES_EXPECT)),
+        max_rss_mb: matches
+            .value_of(ARG_MAX_RSS)
+            .map(|max| u64::from_str(max).expect(GET_MAX_RSS_EXPECT)),
+        gas_profiling: if matches.is_present(ARG_GAS_PROFILING) {
+            Some(true)
+        } else {
+            None
+        },
+        metrics_addr: matches.value_of(ARG_METRICS_ADDR).map(str::to_owned),
+        threads: matches
+            .value_of(ARG_THREADS)
+            .map(|threads| usize::from_str(threads).expect(GET_THREADS_EXPECT)),
+        max_concurrent_requests: matches
+            .value_of(ARG_MAX_CONCURRENT_REQUESTS)
+            .map(|max| usize::from_str(max).expect(GET_MAX_CONCURRENT_REQUESTS_EXPECT)),
+        socket_backlog: matches
+            .value_of(ARG_SOCKET_BACKLOG)
+            .map(|backlog| u32::from_str(backlog).expect(GET_SOCKET_BACKLOG_EXPECT)),
+        drain_timeout: matches
+            .value_of(ARG_DRAIN_TIMEOUT)
+            .map(|timeout| u64::from_str(timeout).expect(GET_DRAIN_TIMEOUT_EXPECT)),
+        read_timeout: matches
+            .value_of(ARG_READ_TIMEOUT)
+            .map(|timeout| u64::from_str(timeout).expect(GET_READ_TIMEOUT_EXPECT)),
+        tls_cert: matches.value_of(ARG_TLS_CERT).map(PathBuf::from),
+        tls_key: matches.value_of(ARG_TLS_KEY).map(PathBuf::from),
+    }
+}
+
+/// Logs a fatal message and exits the process.
+///
+/// Usable before the configured log settings are available, in which case the default log
+/// settings are installed first.
+fn exit_with_fatal(message: &str) -> ! {
+    exit_with_fatal_code(message, 1)
+}
+
+/// Like [`exit_with_fatal`], but exits with the given code.
+fn exit_with_fatal_code(message: &str, code: i32) -> ! {
+    log_settings::set_log_settings_provider(&*FALLBACK_LOG_SETTINGS);
+    logging::log_fatal(message);
+    logging::log_info(SERVER_STOP_MESSAGE);
+    logging::flush();
+    process::exit(code)
+}
+
+/// Collects the positional socket argument followed by any `--socket` options
+fn get_arg_sockets(matches: &ArgMatches) -> Option<Vec<String>> {
+    let sockets: Vec<String> = matches
+        .values_of(ARG_SOCKET)
+        .into_iter()
+        .chain(matches.values_of(ARG_SOCKETS))
+        .flatten()
+        .map(str::to_owned)
+        .collect();
+    if sockets.is_empty() {
+        None
+    } else {
+        Some(sockets)
+    }
+}
+
+/// Gets values of socket setting, exiting if there are none or the same socket is given twice
+fn get_sockets(config: &Config) -> Vec<socket::Socket> {
+    let sockets = match config.socket {
+        Some(ref sockets) if !sockets.is_empty() => sockets,
+        _ => exit_with_fatal(ARG_SOCKET_REQUIRED),
+    };
+
+    let sockets: Vec<socket::Socket> = sockets
+        .iter()
+        .map(|socket| get_socket(config, socket))
+        .collect();
+    for (index, socket) in sockets.iter().enumerate() {
+        if sockets[..index].contains(socket) {
+            exit_with_fatal(&format!("{}: {}", DUPLICATE_SOCKET_MESSAGE, socket.value()));
+        }
+    }
+    sockets
+}
+
+/// Parses `socket`, as an abstract socket if the abstract-socket setting is on and it is not a
+/// TCP socket
+fn get_socket(config: &Config, socket: &str) -> socket::Socket {
+    if config.abstract_socket.unwrap_or(false) && !socket.starts_with(socket::TCP_SCHEME) {
+        if !cfg!(target_os = "linux") {
+            exit_with_fatal(ABSTRACT_SOCKET_UNSUPPORTED_MESSAGE);
+        }
+        return validate_socket(socket::Socket::new_abstract(socket.to_owned()));
+    }
+
+    validate_socket(socket::Socket::new(socket.to_owned()))
+}
+
+/// Exits if the socket path is too long to bind, rather than failing obscurely on bind
+fn validate_socket(socket: socket::Socket) -> socket::Socket {
+    if let Err(error) = socket.validate() {
+        exit_with_fatal(&format!("{}: {}", socket.value(), error));
+    }
+    socket
+}
+
+/// Gets values of socket backlog and tls settings and returns a [`ServerConfig`] with `threads`
+fn get_server_config(config: &Config, threads: usize) -> ServerConfig {
+    ServerConfig::new()
+        .threads(threads)
+        .max_concurrent_requests(get_max_concurrent_requests(config))
+        .socket_backlog(get_socket_backlog(config))
+        .tls_config(get_tls_config(config))
+}
+
+/// Gets value of max-concurrent-requests setting, which must not be 0
+fn get_max_concurrent_requests(config: &Config) -> Option<usize> {
+    let max_concurrent_requests = config.max_concurrent_requests?;
+    if max_concurrent_requests == 0 {
+        exit_with_fatal(MAX_CONCURRENT_REQUESTS_ZERO_MESSAGE);
+    }
+    Some(max_concurrent_requests)
+}
+
+/// Gets value of socket-backlog setting, which must not be 0
+fn get_socket_backlog(config: &Config) -> u32 {
+    let socket_backlog = config.socket_backlog.unwrap_or(DEFAULT_SOCKET_BACKLOG);
+    if socket_backlog == 0 {
+        exit_with_fatal(SOCKET_BACKLOG_ZERO_MESSAGE);
+    }
+    socket_backlog
+}
+
+/// Applies the socket-mode setting, if given, to the bound socket file
+fn set_socket_mode(socket: &socket::Socket, config: &Config) {
+    let mode = match config.socket_mode {
+        Some(ref mode) => u32::from_str_radix(mode, 8)
+            .unwrap_or_else(|_| exit_with_fatal(&format!("{}: {}", GET_SOCKET_MODE_EXPECT, mode))),
+        None => return,
+    };
+
+    if socket.get_path().is_none() {
+        logging::log_debug(&format!(
+            "ignoring socket mode {:o} for socket without a file",
+            mode
+        ));
+        return;
+    }
+
+    if let Err(error) = socket.set_mode(mode) {
+        exit_with_fatal(&format!("{}: {}", SET_SOCKET_MODE_ERROR, error));
+    }
+}
+
+/// Serves the metrics endpoint on a background thread if metrics-addr is given
+fn start_metrics_server(config: &Config) {
+    let metrics_addr = match config.metrics_addr {
+        Some(ref metrics_addr) => metrics_addr,
+        None => return,
+    };
+
+    let listener = TcpListener::bind(metrics_addr).unwrap_or_else(|error| {
+        exit_with_fatal(&format!(
+            "{} {}: {}",
+            METRICS_BIND_ERROR, metrics_addr, error
+        ))
+    });
+
+    if let Err(error) = std::thread::Builder::new()
+        .name(METRICS_THREAD_NAME.to_owned())
+        .spawn(move || metrics::serve(listener))
+    {
+        exit_with_fatal(&format!("{}: {}", METRICS_BIND_ERROR, error));
+    }
+
+    logging::log_info(&format!(
+        "serving metrics on http://{}{}",
+        metrics_addr,
+        metrics::METRICS_PATH
+    ));
+}
+
+/// Gets value of threads setting
+fn get_threads(config: &Config) -> usize {
+    let threads = config.threads.unwrap_or_else(num_cpus::get);
+    if threads == 0 {
+        exit_with_fatal(THREADS_ZERO_MESSAGE);
+    }
+    threads
+}
+
+/// Gets values of tls-cert and tls-key settings
+fn get_tls_config(config: &Config) -> Option<TlsConfig> {
+    match (config.tls_cert.clone(), config.tls_key.clone()) {
+        (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+            cert_path,
+            key_path,
+        }),
+        (None, None) => None,
+        _ => exit_with_fatal(TLS_INCOMPLETE_MESSAGE),
+    }
+}
+
+/// Gets value of data-dir setting, and makes sure global state can be opened there.  Unless
+/// global state is opened `read_only`, the directory is created if it is missing and must be
+/// writable.
+fn get_data_dir(config: &Config, read_only: bool) -> PathBuf {
+    let mut buf = config.data_dir.clone().unwrap_or_else(|| {
+        let mut dir = home_dir().expect(GET_HOME_DIR_EXPECT);
+        dir.push(DEFAULT_DATA_DIR_RELATIVE);
+        dir
+    });
+    buf.push(get_state_subdir(config));
+    if let Err(error) = data_dir::prepare(&buf, !read_only) {
+        exit_with_fatal_code(&error.to_string(), error.exit_code());
+    }
+    buf
+}
+
+/// Gets value of state-subdir setting
+fn get_state_subdir(config: &Config) -> &str {
+    let state_subdir = config
+        .state_subdir
+        .as_ref()
+        .map(String::as_str)
+        .unwrap_or(GLOBAL_STATE_DIR);
+    if !data_dir::is_valid_subdir_name(state_subdir) {
+        exit_with_fatal(&format!(
+            "{}: {:?}",
+            STATE_SUBDIR_INVALID_MESSAGE, state_subdir
+        ));
+    }
+    state_subdir
+}
+
+///  Gets value of max-db-size or pages setting and returns map size
+fn get_map_size(config: &Config) -> usize {
+    let page_size = get_page_size().unwrap();
+    match config.max_db_size {
+        Some(max_db_size) => {
+            let pages = (max_db_size + page_size - 1) / page_size;
+            page_size * pages
+        }
+        None => {
+            let pages = config.pages.unwrap_or(DEFAULT_PAGES);
+            page_size * pages
+        }
+    }
+}
+
+/// Gets value of trie-cache-size setting
+fn get_trie_cache_size(config: &Config) -> Option<usize> {
+    let trie_cache_size = config.trie_cache_size?;
+    if trie_cache_size == 0 {
+        exit_with_fatal(TRIE_CACHE_SIZE_ZERO_MESSAGE);
+    }
+    Some(trie_cache_size)
+}
+
+/// Gets the number of levels of the trie to warm up, if warmup is on; by default there is no limit
+fn get_warmup_depth(config: &Config) -> Option<u32> {
+    if config.warmup.unwrap_or(false) {
+        Some(config.warmup_depth.unwrap_or_else(u32::max_value))
+    } else {
+        None
+    }
+}
+
+/// Gets value of max-readers setting, by default allowing for a few concurrent reads on each of
+/// `threads` gRPC threads
+fn get_max_readers(config: &Config, threads: usize) -> u32 {
+    match config.max_readers {
+        Some(0) => exit_with_fatal(MAX_READERS_ZERO_MESSAGE),
+        Some(max_readers) => {
+            if (max_readers as usize) < threads {
+                logging::log_warning(MAX_READERS_BELOW_THREADS_WARNING);
+            }
+            max_readers
+        }
+        None => {
+            let threads = u32::try_from(threads).unwrap_or_else(|_| u32::max_value());
+            cmp::max(
+                DEFAULT_MAX_READERS,
+                threads.saturating_mul(READERS_PER_THREAD),
+            )
+        }
+    }
+}
+
+/// Gets value of store-name setting
+fn get_store_name(config: &Config) -> Option<&str> {
+    let store_name = config.store_name.as_ref()?;
+    if store_name.is_empty() {
+        exit_with_fatal(STORE_NAME_EMPTY_MESSAGE);
+    }
+    Some(store_name)
+}
+
+/// Gets values of the settings consumed by the engine and returns an [`EngineConfig`].
+fn get_engine_config(config: &Config) -> EngineConfig {
+    let use_payment_code = config.use_payment_code.unwrap_or(false);
+    let read_only = config.read_only.unwrap_or(false);
+    let gas_profiling = config.gas_profiling.unwrap_or(false);
+    let (max_gas_limit, default_gas_limit) = get_gas_limits(config);
+    EngineConfig::new()
+        .use_payment_code(use_payment_code)
+        .read_only(read_only)
+        .wasm_costs(get_wasm_costs(config))
+        .max_wasm_size(get_max_wasm_size(config))
+        .max_memory_pages(get_max_memory_pages(config))
+        .execution_timeout(get_execution_timeout(config))
+        .read_timeout(get_read_timeout(config))
+        .gas_profiling(gas_profiling)
+        .max_gas_limit(max_gas_limit)
+        .default_gas_limit(default_gas_limit)
+        .dedup_cache_size(get_dedup_cache_size(config))
+        .exec_cache_size(config.exec_cache_size.unwrap_or(0))
+        .wasm_cache_size(config.wasm_cache_size.unwrap_or(0))
+        .commit_retries(config.commit_retries.unwrap_or(0))
+        .commit_group_window(Duration::from_millis(
+            config.commit_group_window_ms.unwrap_or(0),
+        ))
+        .estimate_gas_max_trials(
+            config
+                .estimate_gas_max_trials
+                .unwrap_or(DEFAULT_ESTIMATE_GAS_MAX_TRIALS),
+        )
+        .max_deploys_per_batch(get_max_deploys_per_batch(config))
+        .max_query_depth(config.max_query_depth.unwrap_or(DEFAULT_MAX_QUERY_DEPTH))
+        .max_query_nodes(get_max_query_nodes(config))
+        .tls(get_tls_config(config).is_some())
+        .metrics(config.metrics_addr.is_some())
+}
+
+/// Gets value of max-deploys-per-batch setting, which must not be 0
+fn get_max_deploys_per_batch(config: &Config) -> usize {
+    let max_deploys_per_batch = config
+        .max_deploys_per_batch
+        .unwrap_or(DEFAULT_MAX_DEPLOYS_PER_BATCH);
+    if max_deploys_per_batch == 0 {
+        exit_with_fatal(MAX_DEPLOYS_PER_BATCH_ZERO_MESSAGE);
+    }
+    max_deploys_per_batch
+}
+
+/// Gets value of max-query-nodes setting, which must not be 0, as every query visits a node
+fn get_max_query_nodes(config: &Config) -> usize {
+    let max_query_nodes = config.max_query_nodes.unwrap_or(DEFAULT_MAX_QUERY_NODES);
+    if max_query_nodes == 0 {
+        exit_with_fatal(MAX_QUERY_NODES_ZERO_MESSAGE);
+    }
+    max_query_nodes
+}
+
+/// Gets value of max-rss-mb setting, which must not be 0, and starts sampling memory use against
+/// it; exits if memory use cannot be sampled, rather than running without the limit
+fn get_memory_guard(config: &Config) -> Option<MemoryGuard> {
+    let max_rss_mb = config.max_rss_mb?;
+    if max_rss_mb == 0 {
+        exit_with_fatal(MAX_RSS_ZERO_MESSAGE);
+    }
+    let memory_guard = MemoryGuard::new(max_rss_mb.saturating_mul(1024 * 1024));
+    if let Err(error) = memory_guard.start_sampling(memory_guard::SAMPLE_INTERVAL) {
+        exit_with_fatal(&format!("{}: {}", RSS_SAMPLING_ERROR, error));
+    }
+    Some(memory_guard)
+}
+
+/// Gets value of dedup-cache-size setting; 0 turns deduplication off
+fn get_dedup_cache_size(config: &Config) -> usize {
+    config.dedup_cache_size.unwrap_or(DEFAULT_DEDUP_CACHE_SIZE)
+}
+
+/// Gets value of execution-timeout-ms setting
+fn get_execution_timeout(config: &Config) -> Option<Duration> {
+    let execution_timeout_ms = config.execution_timeout_ms?;
+    if execution_timeout_ms == 0 {
+        exit_with_fatal(EXECUTION_TIMEOUT_ZERO_MESSAGE);
+    }
+    Some(Duration::from_millis(execution_timeout_ms))
+}
+
+/// Gets value of read-timeout setting, which must not be 0 if given
+fn get_read_timeout(config: &Config) -> Option<Duration> {
+    let read_timeout = config.read_timeout?;
+    if read_timeout == 0 {
+        exit_with_fatal(READ_TIMEOUT_ZERO_MESSAGE);
+    }
+    Some(Duration::from_secs(read_timeout))
+}
+
+/// Gets values of max-gas-limit and default-gas-limit settings
+fn get_gas_limits(config: &Config) -> (Option<u64>, Option<u64>) {
+    if config.max_gas_limit == Some(0) {
+        exit_with_fatal(MAX_GAS_LIMIT_ZERO_MESSAGE);
+    }
+    if config.default_gas_limit == Some(0) {
+        exit_with_fatal(DEFAULT_GAS_LIMIT_ZERO_MESSAGE);
+    }
+    if let (Some(max_gas_limit), Some(default_gas_limit)) =
+        (config.max_gas_limit, config.default_gas_limit)
+    {
+        if default_gas_limit > max_gas_limit {
+            exit_with_fatal(DEFAULT_GAS_LIMIT_ABOVE_MAX_MESSAGE);
+        }
+    }
+    (config.max_gas_limit, config.default_gas_limit)
+}
+
+/// Gets value of max-wasm-size setting
+fn get_max_wasm_size(config: &Config) -> usize {
+    let max_wasm_size = config.max_wasm_size.unwrap_or(DEFAULT_MAX_MODULE_SIZE);
+    if max_wasm_size == 0 {
+        exit_with_fatal(MAX_WASM_SIZE_ZERO_MESSAGE);
+    }
+    max_wasm_size
+}
+
+/// Gets value of max-memory-pages setting
+fn get_max_memory_pages(config: &Config) -> u32 {
+    let max_memory_pages = config.max_memory_pages.unwrap_or(MEM_PAGES);
+    if max_memory_pages == 0 {
+        exit_with_fatal(MAX_MEMORY_PAGES_ZERO_MESSAGE);
+    }
+    max_memory_pages
+}
+
+/// Applies the gas-config file and the wasm-stack-height setting, if either was given, over the
+/// built-in wasm costs
+fn get_wasm_costs(config: &Config) -> Option<WasmCosts> {
+    if config.gas_config.is_none() && config.wasm_stack_height.is_none() {
+        return None;
+    }
+
+    let mut wasm_costs = WasmCosts::from_version(GAS_CONFIG_DEFAULTS_PROTOCOL_VERSION)
+        .expect("should have built-in wasm costs");
+
+    if let Some(ref path) = config.gas_config {
+        let gas_config = GasConfig::from_file(path).unwrap_or_else(|error| {
+            exit_with_fatal(&format!(
+                "{} {}: {}",
+                GAS_CONFIG_FILE_ERROR,
+                path.display(),
+                error
+            ))
+        });
+        wasm_costs = gas_config.apply(wasm_costs);
+    }
+
+    if let Some(max_stack_height) = config.wasm_stack_height {
+        if max_stack_height == 0 {
+            exit_with_fatal(WASM_STACK_HEIGHT_ZERO_MESSAGE);
+        }
+        wasm_costs.max_stack_height = max_stack_height;
+    }
+
+    logging::log_debug(&format!("using wasm costs: {:?}", wasm_costs));
+
+    Some(wasm_costs)
+}
+
+/// Builds and returns a gRPC server for each socket, all sharing one engine state.
+#[allow(clippy::too_many_arguments)]
+fn get_grpc_servers(
+    sockets: &[socket::Socket],
+    server_config: &ServerConfig,
+    environment: Arc<LmdbEnvironment>,
+    store_name: Option<&str>,
+    trie_cache_size: Option<usize>,
+    warmup_depth: Option<u32>,
+    key_bloom: bool,
+    audit_log: Option<&Path>,
     engine_config: EngineConfig,
-) -> grpc::Server {
-    let engine_state = get_engine_state(data_dir, map_size, engine_config);
+    health_status: HealthStatus,
+    drain: Drain,
+    memory_guard: Option<MemoryGuard>,
+) -> Vec<grpc::Server> {
+    let engine_state = get_engine_state(
+        environment,
+        store_name,
+        trie_cache_size,
+        warmup_depth,
+        key_bloom,
+        audit_log,
+        engine_config,
+    );
 
-    engine_server::new(socket.as_str(), engine_state)
-        .build()
-        .expect(SERVER_START_EXPECT)
+    match engine_server::new(
+        sockets,
+        server_config,
+        engine_state,
+        health_status,
+        drain,
+        memory_guard,
+    ) {
+        Ok(servers) => servers,
+        Err(error) => exit_with_fatal(&format!("{}: {}", SERVER_START_EXPECT, error)),
+    }
 }
 
-/// Builds and returns engine global state
-fn get_engine_state(
+/// Gets value of sync-policy setting, or of no-sync, which stands for the `never` policy
+fn get_sync_policy(config: &Config) -> SyncPolicy {
+    let sync_policy = match config.sync_policy {
+        Some(ref sync_policy) => sync_policy,
+        None if config.no_sync.unwrap_or(false) => return SyncPolicy::Never,
+        None => return SyncPolicy::Always,
+    };
+    if config.no_sync.unwrap_or(false) {
+        exit_with_fatal(NO_SYNC_WITH_SYNC_POLICY_MESSAGE);
+    }
+    match sync_policy.as_str() {
+        "always" => SyncPolicy::Always,
+        "never" => SyncPolicy::Never,
+        other => other
+            .trim_start_matches(SYNC_POLICY_INTERVAL_PREFIX)
+            .parse::<u64>()
+            .ok()
+            .filter(|interval_ms| {
+                other.starts_with(SYNC_POLICY_INTERVAL_PREFIX) && *interval_ms > 0
+            })
+            .map(|interval_ms| SyncPolicy::Interval(Duration::from_millis(interval_ms)))
+            .unwrap_or_else(|| exit_with_fatal(SYNC_POLICY_INVALID_MESSAGE)),
+    }
+}
+
+/// Gets value of db-open-retries setting
+fn get_db_open_retries(config: &Config) -> u32 {
+    config.db_open_retries.unwrap_or(0)
+}
+
+/// Builds and returns the LMDB environment backing global state, logging a warning if
+/// `sync_policy` is weaker than `always`.  `sync_policy` has no effect on a read-only
+/// environment, which is never written to.  Opening is retried up to `open_retries` times while
+/// another process may still be creating the environment.
+fn get_lmdb_environment(
     data_dir: PathBuf,
     map_size: usize,
+    max_readers: u32,
+    read_only: bool,
+    sync_policy: SyncPolicy,
+    open_retries: u32,
+) -> Arc<LmdbEnvironment> {
+    if !read_only {
+        let warning = match sync_policy {
+            SyncPolicy::Always => None,
+            SyncPolicy::Interval(_) => Some(SYNC_POLICY_INTERVAL_WARNING),
+            SyncPolicy::Never => Some(SYNC_POLICY_NEVER_WARNING),
+        };
+        if let Some(warning) = warning {
+            logging::log_warning(&format!("sync-policy is {}: {}", sync_policy, warning));
+        }
+    }
+    let mut attempts = 0;
+    let ret = LmdbEnvironment::open_with_retries(open_retries, DB_OPEN_RETRY_INTERVAL, || {
+        if attempts > 0 {
+            logging::log_warning(&format!(
+                "{} ({} of {})",
+                DB_OPEN_RETRY_MESSAGE, attempts, open_retries
+            ));
+        }
+        attempts += 1;
+        if read_only {
+            LmdbEnvironment::new_read_only(&data_dir, map_size, max_readers)
+        } else {
+            LmdbEnvironment::new_with_sync_policy(&data_dir, map_size, max_readers, sync_policy)
+        }
+    });
+    Arc::new(ret.expect(LMDB_ENVIRONMENT_EXPECT))
+}
+
+/// Flushes `environment` to disk every `interval` on a background thread, for as long as the
+/// server runs.
+fn start_periodic_sync(environment: Arc<LmdbEnvironment>, interval: Duration) {
+    if let Err(error) = std::thread::Builder::new()
+        .name(SYNC_THREAD_NAME.to_owned())
+        .spawn(move || loop {
+            std::thread::sleep(interval);
+            if let Err(e) = environment.sync() {
+                logging::log_error(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+            }
+        })
+    {
+        exit_with_fatal(&format!("{}: {}", SYNC_THREAD_EXPECT, error));
+    }
+}
+
+/// Builds and returns engine global state.  Unless it is read-only, commits are recorded in the
+/// audit log at `audit_log`, if one is given.  If `warmup_depth` is given, the latest committed
+/// trie is read down to that depth first.  With `key_bloom`, bloom filters of the keys under
+/// committed roots are kept.
+fn get_engine_state(
+    environment: Arc<LmdbEnvironment>,
+    store_name: Option<&str>,
+    trie_cache_size: Option<usize>,
+    warmup_depth: Option<u32>,
+    key_bloom: bool,
+    audit_log: Option<&Path>,
     engine_config: EngineConfig,
 ) -> EngineState<LmdbGlobalState> {
-    let environment = {
-        let ret = LmdbEnvironment::new(&data_dir, map_size).expect(LMDB_ENVIRONMENT_EXPECT);
-        Arc::new(ret)
-    };
+    let read_only = engine_config.is_read_only();
 
     let trie_store = {
-        let ret = LmdbTrieStore::new(&environment, None, DatabaseFlags::empty())
-            .expect(LMDB_TRIE_STORE_EXPECT);
-        Arc::new(ret)
+        let ret = get_trie_store(&environment, store_name, read_only);
+        match trie_cache_size {
+            Some(capacity) => Arc::new(ret.with_cache(capacity)),
+            None => Arc::new(ret),
+        }
+    };
+
+    let global_state_result = if read_only {
+        LmdbGlobalState::read_only(Arc::clone(&environment), Arc::clone(&trie_store))
+    } else {
+        LmdbGlobalState::empty(Arc::clone(&environment), Arc::clone(&trie_store))
+    };
+
+    let global_state = match global_state_result {
+        Ok(global_state) => global_state,
+        Err(StorageError::MapFull) => exit_with_fatal(LMDB_MAP_FULL_MESSAGE),
+        Err(e) => panic!("{}: {:?}", LMDB_GLOBAL_STATE_EXPECT, e),
+    };
+
+    if let Some(max_depth) = warmup_depth {
+        warm_up_global_state(&global_state, max_depth);
+    }
+
+    let global_state = match audit_log {
+        Some(path) if !read_only => global_state.with_audit_log(open_audit_log(path)),
+        _ => global_state,
+    };
+
+    let global_state = if key_bloom {
+        global_state
+            .with_key_bloom(CorrelationId::new())
+            .unwrap_or_else(|error| panic!("{}: {:?}", KEY_BLOOM_ERROR, error))
+    } else {
+        global_state
+    };
+
+    let genesis_hash = get_genesis_hash(&global_state);
+    let engine_state = EngineState::new(global_state, engine_config);
+    if let Some(genesis_hash) = genesis_hash {
+        engine_state.set_genesis_hash(genesis_hash);
+    }
+    engine_state
+}
+
+/// Gets the post state root of the last successful genesis recorded in the store; `None` if
+/// genesis has not run against it
+fn get_genesis_hash(global_state: &LmdbGlobalState) -> Option<Blake2bHash> {
+    match global_state.genesis_root() {
+        Ok(genesis_root) => genesis_root,
+        Err(error) => {
+            logging::log_warning(&format!("{}: {:?}", GENESIS_HASH_ERROR, error));
+            None
+        }
+    }
+}
+
+/// Reads the trie at the latest committed state root, down to `max_depth` levels below it, so that
+/// the OS page cache holds it before requests are accepted.  Progress is logged after each level.
+/// Failing to warm up is only logged, as the server works without it.
+fn warm_up_global_state(global_state: &LmdbGlobalState, max_depth: u32) {
+    let root = match global_state.latest_root() {
+        Ok(Some(root)) => root,
+        Ok(None) => {
+            logging::log_info(WARMUP_NOTHING_COMMITTED_MESSAGE);
+            return;
+        }
+        Err(error) => {
+            logging::log_warning(&format!("{}: {:?}", WARMUP_ERROR, error));
+            return;
+        }
     };
+    logging::log_info(&format!("warming up state root {:x}", root));
 
-    let global_state = LmdbGlobalState::empty(Arc::clone(&environment), Arc::clone(&trie_store))
-        .expect(LMDB_GLOBAL_STATE_EXPECT);
+    let start = Instant::now();
+    let on_level = |progress: &WarmUpProgress| {
+        logging::log_info(&format!(
+            "warmup read {} trie nodes down to depth {}",
+            progress.node_count, progress.depth
+        ))
+    };
+    match global_state.warm_up(CorrelationId::new(), root, max_depth, on_level) {
+        Ok(Some(progress)) => logging::log_info(&format!(
+            "warmup {} after reading {} trie nodes in {}ms",
+            if progress.complete {
+                "complete"
+            } else {
+                "stopped at warmup-depth"
+            },
+            progress.node_count,
+            start.elapsed().as_millis()
+        )),
+        Ok(None) => logging::log_warning(&format!("{}: root {:x} not found", WARMUP_ERROR, root)),
+        Err(error) => logging::log_warning(&format!("{}: {:?}", WARMUP_ERROR, error)),
+    }
+}
+
+/// Opens the audit log at `path` for appending, creating it if it does not exist
+fn open_audit_log(path: &Path) -> AuditLog {
+    let audit_log = AuditLog::open(path).unwrap_or_else(|error| {
+        exit_with_fatal(&format!(
+            "{} {}: {:?}",
+            AUDIT_LOG_ERROR,
+            path.display(),
+            error
+        ))
+    });
+    logging::log_info(&format!(
+        "appending commits to audit log {}",
+        path.display()
+    ));
+    audit_log
+}
 
-    EngineState::new(global_state, engine_config)
+/// Opens the trie store called `store_name`, or the one in the unnamed database if there is no
+/// name.  A read-only store must already exist.
+fn get_trie_store(
+    environment: &LmdbEnvironment,
+    store_name: Option<&str>,
+    read_only: bool,
+) -> LmdbTrieStore {
+    let ret = if read_only {
+        LmdbTrieStore::open(environment, store_name)
+    } else {
+        LmdbTrieStore::new(environment, store_name, DatabaseFlags::empty())
+    };
+    match ret {
+        Ok(trie_store) => trie_store,
+        Err(error @ StorageError::StoreNotFound(_)) => {
+            exit_with_fatal(&format!("{}; {}", error, STORE_NOT_FOUND_HINT))
+        }
+        Err(error @ StorageError::ReservedStoreName(_)) => exit_with_fatal(&error.to_string()),
+        Err(error @ StorageError::UnsupportedStoreVersion { .. }) => {
+            exit_with_fatal(&error.to_string())
+        }
+        Err(error) => panic!("{}: {:?}", LMDB_TRIE_STORE_EXPECT, error),
+    }
 }
 
 /// Builds and returns log_settings
 fn get_log_settings() -> log_settings::LogSettings {
-    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+    let config: &Config = &*CONFIG;
+
+    let log_level_filter = get_log_level_filter(config);
+
+    let log_format = get_log_format(config);
+
+    let timestamp_format = get_log_timestamp_format(config);
+
+    let log_settings = get_module_log_level_filters(config).into_iter().fold(
+        LogSettings::new(PROC_NAME, log_level_filter)
+            .with_log_format(log_format)
+            .with_timestamp_format(timestamp_format)
+            .with_async_logging(config.async_logging.unwrap_or(false))
+            .with_thread_id(config.log_include_thread_id.unwrap_or(false)),
+        |log_settings, (module_prefix, module_log_level_filter)| {
+            log_settings.with_module_log_level_filter(&module_prefix, module_log_level_filter)
+        },
+    );
+
+    if let Some(syslog_settings) = get_syslog_settings(config) {
+        return log_settings.with_syslog(syslog_settings);
+    }
+
+    match get_log_file_settings(config) {
+        Some(log_file_settings) => log_settings.with_log_file(log_file_settings),
+        None => log_settings,
+    }
+}
+
+/// Gets values of syslog settings if log-target is syslog
+fn get_syslog_settings(config: &Config) -> Option<SyslogSettings> {
+    match config.log_target.as_ref().map(String::as_str) {
+        None | Some(LOG_TARGET_STDOUT) => return None,
+        Some(LOG_TARGET_SYSLOG) => (),
+        Some(_) => exit_with_fatal(LOG_TARGET_INVALID_MESSAGE),
+    }
+
+    if config.log_file.is_some() {
+        exit_with_fatal(LOG_FILE_WITH_SYSLOG_MESSAGE);
+    }
+
+    let ident = config
+        .syslog_ident
+        .clone()
+        .unwrap_or_else(|| PROC_NAME.to_owned());
+    let mut syslog_settings = SyslogSettings::new(ident);
+    if let Some(ref facility) = config.syslog_facility {
+        let facility = SyslogFacility::from_input(facility)
+            .unwrap_or_else(|| exit_with_fatal(SYSLOG_FACILITY_INVALID_MESSAGE));
+        syslog_settings = syslog_settings.with_facility(facility);
+    }
+    Some(syslog_settings)
+}
+
+/// Gets value of log-format setting
+fn get_log_format(config: &Config) -> LogFormat {
+    LogFormat::from_input(config.log_format.as_ref().map(String::as_str))
+}
+
+/// Gets value of log-timestamp-format setting, or the synthetic clock if deterministic-timestamps
+/// is given
+fn get_log_timestamp_format(config: &Config) -> TimestampFormat {
+    if ARG_MATCHES.is_present(ARG_DETERMINISTIC_TIMESTAMPS) {
+        if !cfg!(debug_assertions) {
+            exit_with_fatal(DETERMINISTIC_TIMESTAMPS_RELEASE_MESSAGE);
+        }
+        return TimestampFormat::Deterministic;
+    }
+    TimestampFormat::from_input(config.log_timestamp_format.as_ref().map(String::as_str))
+}
+
+/// Gets values of log-file settings
+fn get_log_file_settings(config: &Config) -> Option<LogFileSettings> {
+    let path = config.log_file.clone()?;
+    let mut log_file_settings = LogFileSettings::new(path);
+    if let Some(max_size) = config.log_file_max_size {
+        log_file_settings = log_file_settings.with_max_size(max_size);
+    }
+    if let Some(count) = config.log_file_count {
+        log_file_settings = log_file_settings.with_max_files(count);
+    }
+    Some(log_file_settings)
+}
+
+/// Gets the global level of loglevel setting, i.e. the item without a module prefix
+fn get_log_level_filter(config: &Config) -> LogLevelFilter {
+    let global_level = config.loglevel.as_ref().and_then(|loglevel| {
+        loglevel
+            .split(LOG_LEVEL_SEPARATOR)
+            .map(str::trim)
+            .filter(|item| !item.contains(LOG_LEVEL_MODULE_SEPARATOR))
+            .last()
+    });
+    LogLevelFilter::from_input(global_level)
+}
+
+/// Gets the `MODULE=LEVEL` overrides of loglevel setting
+fn get_module_log_level_filters(config: &Config) -> Vec<(String, LogLevelFilter)> {
+    let loglevel = match config.loglevel {
+        Some(ref loglevel) => loglevel,
+        None => return Vec::new(),
+    };
+    loglevel
+        .split(LOG_LEVEL_SEPARATOR)
+        .filter_map(|item| {
+            let mut parts = item.splitn(2, LOG_LEVEL_MODULE_SEPARATOR);
+            let module_prefix = parts.next()?.trim();
+            let level = parts.next()?.trim();
+            Some((
+                module_prefix.to_owned(),
+                LogLevelFilter::from_input(Some(level)),
+            ))
+        })
+        .collect()
+}
 
-    let log_level_filter = LogLevelFilter::from_input(matches.value_of(ARG_LOG_LEVEL));
+/// Logs the settings the server starts with in a single record: every setting given, by its
+/// name, and the effective values of the main ones, defaults included.  Files such as the TLS key
+/// are listed by path only.
+fn log_startup_config(
+    config: &Config,
+    sockets: &[socket::Socket],
+    data_dir: &Path,
+    map_size: usize,
+    server_config: &ServerConfig,
+    max_readers: u32,
+    sync_policy: SyncPolicy,
+) {
+    let mut properties = config.properties();
 
-    LogSettings::new(PROC_NAME, log_level_filter)
+    let sockets: Vec<String> = sockets.iter().map(socket::Socket::value).collect();
+    properties.insert(ARG_SOCKET.to_string(), sockets.join(","));
+    properties.insert(ARG_DATA_DIR.to_string(), data_dir.display().to_string());
+    properties
+        .entry(ARG_LOG_LEVEL.to_string())
+        .or_insert_with(|| DEFAULT_LOG_LEVEL.to_string());
+    properties.insert(ARG_MAX_DB_SIZE.to_string(), map_size.to_string());
+    properties.insert(
+        ARG_THREADS.to_string(),
+        server_config.get_threads().to_string(),
+    );
+    properties.insert(
+        ARG_SOCKET_BACKLOG.to_string(),
+        server_config.get_socket_backlog().to_string(),
+    );
+    properties.insert(ARG_MAX_READERS.to_string(), max_readers.to_string());
+    properties.insert(ARG_SYNC_POLICY.to_string(), sync_policy.to_string());
+    for (flag, value) in &[
+        (ARG_ABSTRACT_SOCKET, config.abstract_socket),
+        (ARG_ASYNC_LOGGING, config.async_logging),
+        (ARG_LOG_INCLUDE_THREAD_ID, config.log_include_thread_id),
+        (ARG_WARMUP, config.warmup),
+        (ARG_KEY_BLOOM, config.key_bloom),
+        (ARG_USE_PAYMENT_CODE, config.use_payment_code),
+        (ARG_READ_ONLY, config.read_only),
+        (ARG_GAS_PROFILING, config.gas_profiling),
+    ] {
+        properties.insert(flag.to_string(), value.unwrap_or(false).to_string());
+    }
+
+    logging::log_details(
+        log_level::LogLevel::Info,
+        SERVER_CONFIG_MESSAGE.to_string(),
+        properties,
+    );
 }
 
 /// Logs listening on socket message
@@ -0,0 +1,90 @@
+//! Restoring global state from a backup taken with the `backup_store` RPC, before the server or
+//! `--init-db-only` opens it.
+
+use std::collections::btree_map::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use base16;
+use engine_core::engine_state::EngineConfig;
+use engine_shared::logging::{self, log_level};
+use engine_shared::newtypes::Blake2bHash;
+use engine_storage::trie_store::lmdb::DATA_FILE_NAME;
+
+use config::Config;
+use data_dir;
+use {exit_with_fatal, exit_with_fatal_code};
+
+const RESTORE_GENESIS_HASH_INVALID_MESSAGE: &str =
+    "restore-genesis-hash must be a 32 byte hex encoded hash";
+const RESTORE_READ_ONLY_MESSAGE: &str =
+    "restore-from writes to the data directory, so it cannot run with read-only";
+const RESTORE_NO_GENESIS_MESSAGE: &str = "restored global state has no genesis hash";
+const RESTORE_GENESIS_MISMATCH_MESSAGE: &str =
+    "restored global state does not have the expected genesis hash";
+const RESTORE_REMOVED_MESSAGE: &str =
+    "removed the restored data file, so that restore can be rerun";
+const RESTORE_REMOVE_ERROR: &str = "could not remove the restored data file";
+const RESTORE_DONE_MESSAGE: &str = "global state restored";
+const RESTORE_OPENED_MESSAGE: &str = "restored global state opened";
+
+/// Copies the data file of the backup in `backup_dir`, as written by the `backup_store` RPC, into
+/// `data_dir`, exiting if `data_dir` already holds global state or the copy fails
+pub fn restore_backup(backup_dir: &Path, data_dir: &Path, engine_config: &EngineConfig) {
+    if engine_config.is_read_only() {
+        exit_with_fatal(RESTORE_READ_ONLY_MESSAGE);
+    }
+
+    let size = data_dir::restore(backup_dir, data_dir, DATA_FILE_NAME)
+        .unwrap_or_else(|error| exit_with_fatal_code(&error.to_string(), error.exit_code()));
+
+    let mut properties: BTreeMap<String, String> = BTreeMap::new();
+    properties.insert("backup_dir".to_string(), backup_dir.display().to_string());
+    properties.insert("data_dir".to_string(), data_dir.display().to_string());
+    properties.insert("size".to_string(), size.to_string());
+    logging::log_details(
+        log_level::LogLevel::Info,
+        format!(
+            "{} from {{backup_dir}} to {{data_dir}}; {{size}} bytes",
+            RESTORE_DONE_MESSAGE
+        ),
+        properties,
+    );
+}
+
+/// Checks that global state restored into `data_dir` has a genesis hash, and that it is the one
+/// given with restore-genesis-hash, if any.  Otherwise the restored data file is removed, so that
+/// the restore can be run again, and the process exits.
+pub fn check_restored_genesis_hash(
+    config: &Config,
+    genesis_hash: Option<Blake2bHash>,
+    data_dir: &Path,
+) {
+    let expected = config.restore_genesis_hash.as_ref().map(|hash| {
+        base16::decode(hash)
+            .ok()
+            .and_then(|bytes| Blake2bHash::try_from(bytes.as_slice()).ok())
+            .unwrap_or_else(|| exit_with_fatal(RESTORE_GENESIS_HASH_INVALID_MESSAGE))
+    });
+
+    let message = match (genesis_hash, expected) {
+        (None, _) => RESTORE_NO_GENESIS_MESSAGE.to_string(),
+        (Some(genesis_hash), Some(expected)) if genesis_hash != expected => format!(
+            "{}: found {:?}, expected {:?}",
+            RESTORE_GENESIS_MISMATCH_MESSAGE, genesis_hash, expected
+        ),
+        (Some(genesis_hash), _) => {
+            logging::log_info(&format!(
+                "{}; genesis: {:?}",
+                RESTORE_OPENED_MESSAGE, genesis_hash
+            ));
+            return;
+        }
+    };
+
+    match fs::remove_file(data_dir.join(DATA_FILE_NAME)) {
+        Ok(()) => exit_with_fatal(&format!("{}; {}", message, RESTORE_REMOVED_MESSAGE)),
+        Err(error) => exit_with_fatal(&format!("{}; {}: {}", message, RESTORE_REMOVE_ERROR, error)),
+    }
+}
@@ -0,0 +1,84 @@
+//! The `export` subcommand.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+
+use base16;
+use clap::ArgMatches;
+use engine_shared::logging;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::error::Error as StorageError;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::pairs_file;
+use engine_storage::trie_store::lmdb::{SyncPolicy, DEFAULT_MAX_READERS};
+
+use config::Config;
+use {
+    exit_with_fatal, get_data_dir, get_db_open_retries, get_lmdb_environment, get_map_size,
+    get_store_name, get_trie_store, ARG_OUT, ARG_STATE_ROOT, LMDB_GLOBAL_STATE_EXPECT,
+};
+
+const STATE_ROOT_INVALID_MESSAGE: &str = "state-root must be a 32 byte hex encoded hash";
+const STATE_ROOT_NOT_FOUND_MESSAGE: &str = "state root not found";
+const EXPORT_ERROR: &str = "failed to export global state";
+
+/// Writes the pairs under the given state root to the given file
+pub fn export_global_state(config: &Config, matches: &ArgMatches) {
+    let state_root = get_state_root(matches);
+    let out = Path::new(matches.value_of(ARG_OUT).expect("out is required"));
+
+    let data_dir = get_data_dir(config, true);
+    let environment = get_lmdb_environment(
+        data_dir,
+        get_map_size(config),
+        DEFAULT_MAX_READERS,
+        true,
+        SyncPolicy::Always,
+        get_db_open_retries(config),
+    );
+    let trie_store = get_trie_store(&environment, get_store_name(config), true);
+    let global_state = LmdbGlobalState::read_only(environment, Arc::new(trie_store))
+        .expect(LMDB_GLOBAL_STATE_EXPECT);
+
+    let pairs = match global_state.read_all(CorrelationId::new(), state_root) {
+        Ok(Some(pairs)) => pairs,
+        Ok(None) => exit_with_fatal(&format!(
+            "{}: {:x}",
+            STATE_ROOT_NOT_FOUND_MESSAGE, state_root
+        )),
+        Err(error) => exit_with_fatal(&format!("{}: {:?}", EXPORT_ERROR, error)),
+    };
+
+    let result = File::create(out)
+        .map_err(StorageError::from)
+        .and_then(|file| pairs_file::write_pairs(&mut BufWriter::new(file), &pairs));
+    if let Err(error) = result {
+        exit_with_fatal(&format!(
+            "{} to {}: {:?}",
+            EXPORT_ERROR,
+            out.display(),
+            error
+        ));
+    }
+
+    logging::log_info(&format!(
+        "exported {} pairs under {:x} to {}",
+        pairs.len(),
+        state_root,
+        out.display()
+    ));
+}
+
+/// Gets value of the state-root argument of the export subcommand
+fn get_state_root(matches: &ArgMatches) -> Blake2bHash {
+    let state_root = matches
+        .value_of(ARG_STATE_ROOT)
+        .expect("state-root is required");
+    base16::decode(state_root)
+        .ok()
+        .and_then(|bytes| Blake2bHash::try_from(bytes.as_slice()).ok())
+        .unwrap_or_else(|| exit_with_fatal(STATE_ROOT_INVALID_MESSAGE))
+}
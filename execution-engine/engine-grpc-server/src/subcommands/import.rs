@@ -0,0 +1,83 @@
+//! The `import` subcommand.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use engine_shared::logging;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::error::Error as StorageError;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::{pairs_file, History};
+use engine_storage::trie_store::lmdb::{DATA_FILE_NAME, DEFAULT_MAX_READERS};
+
+use config::Config;
+use {
+    exit_with_fatal, get_data_dir, get_db_open_retries, get_lmdb_environment, get_map_size,
+    get_store_name, get_sync_policy, get_trie_store, ARG_IN, LMDB_MAP_FULL_MESSAGE,
+    LMDB_SYNC_ERROR,
+};
+
+const IMPORT_ERROR: &str = "failed to import global state";
+const IMPORT_DATA_DIR_NOT_EMPTY_MESSAGE: &str =
+    "import requires a data directory without existing global state";
+
+/// Loads the pairs of the given file into the data directory, which must not hold global state
+pub fn import_global_state(config: &Config, matches: &ArgMatches) {
+    let input = Path::new(matches.value_of(ARG_IN).expect("in is required"));
+
+    let data_dir = get_data_dir(config, false);
+    if data_dir.join(DATA_FILE_NAME).exists() {
+        exit_with_fatal(&format!(
+            "{}: {}",
+            IMPORT_DATA_DIR_NOT_EMPTY_MESSAGE,
+            data_dir.display()
+        ));
+    }
+
+    let pairs = File::open(input)
+        .map_err(StorageError::from)
+        .and_then(|file| pairs_file::read_pairs(&mut BufReader::new(file)))
+        .unwrap_or_else(|error| {
+            exit_with_fatal(&format!(
+                "{} from {}: {:?}",
+                IMPORT_ERROR,
+                input.display(),
+                error
+            ))
+        });
+
+    let environment = get_lmdb_environment(
+        data_dir,
+        get_map_size(config),
+        DEFAULT_MAX_READERS,
+        false,
+        get_sync_policy(config),
+        get_db_open_retries(config),
+    );
+    let trie_store = get_trie_store(&environment, get_store_name(config), false);
+
+    let global_state = match LmdbGlobalState::from_pairs(
+        Arc::clone(&environment),
+        Arc::new(trie_store),
+        CorrelationId::new(),
+        &pairs,
+    ) {
+        Ok(global_state) => global_state,
+        Err(StorageError::MapFull) => exit_with_fatal(LMDB_MAP_FULL_MESSAGE),
+        Err(error) => exit_with_fatal(&format!("{}: {:?}", IMPORT_ERROR, error)),
+    };
+
+    if let Err(e) = environment.sync() {
+        logging::log_error(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+    }
+
+    logging::log_info(&format!(
+        "imported {} pairs from {} under {:x}",
+        pairs.len(),
+        input.display(),
+        global_state.current_root()
+    ));
+}
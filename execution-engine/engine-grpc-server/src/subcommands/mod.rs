@@ -0,0 +1,8 @@
+//! The subcommands of the server, each of which does its work against the data directory and
+//! exits instead of serving.
+
+pub mod export;
+pub mod import;
+pub mod replay;
+pub mod self_test;
+pub mod validate;
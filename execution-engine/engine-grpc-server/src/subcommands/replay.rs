@@ -0,0 +1,104 @@
+//! The `replay` subcommand.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::ArgMatches;
+use engine_shared::logging;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::error::Error as StorageError;
+use engine_storage::global_state::audit_log;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::{CommitResult, History};
+use engine_storage::trie_store::lmdb::{DATA_FILE_NAME, DEFAULT_MAX_READERS};
+
+use config::Config;
+use {
+    exit_with_fatal, get_data_dir, get_db_open_retries, get_lmdb_environment, get_map_size,
+    get_store_name, get_sync_policy, get_trie_store, ARG_IN, LMDB_MAP_FULL_MESSAGE,
+    LMDB_SYNC_ERROR,
+};
+
+const REPLAY_ERROR: &str = "failed to replay audit log";
+const REPLAY_DATA_DIR_NOT_EMPTY_MESSAGE: &str =
+    "replay requires a data directory without existing global state";
+
+/// Re-applies the commits of the given audit log to the data directory, which must not hold
+/// global state, exiting with an error at the first commit which does not yield its recorded root
+pub fn replay_audit_log(config: &Config, matches: &ArgMatches) {
+    let input = Path::new(matches.value_of(ARG_IN).expect("in is required"));
+
+    let data_dir = get_data_dir(config, false);
+    if data_dir.join(DATA_FILE_NAME).exists() {
+        exit_with_fatal(&format!(
+            "{}: {}",
+            REPLAY_DATA_DIR_NOT_EMPTY_MESSAGE,
+            data_dir.display()
+        ));
+    }
+
+    let records = File::open(input)
+        .map_err(StorageError::from)
+        .and_then(|file| audit_log::read_records(&mut BufReader::new(file)))
+        .unwrap_or_else(|error| {
+            exit_with_fatal(&format!(
+                "{} {}: {:?}",
+                REPLAY_ERROR,
+                input.display(),
+                error
+            ))
+        });
+
+    let environment = get_lmdb_environment(
+        data_dir,
+        get_map_size(config),
+        DEFAULT_MAX_READERS,
+        false,
+        get_sync_policy(config),
+        get_db_open_retries(config),
+    );
+    let trie_store = get_trie_store(&environment, get_store_name(config), false);
+    let mut global_state =
+        match LmdbGlobalState::empty(Arc::clone(&environment), Arc::new(trie_store)) {
+            Ok(global_state) => global_state,
+            Err(StorageError::MapFull) => exit_with_fatal(LMDB_MAP_FULL_MESSAGE),
+            Err(error) => exit_with_fatal(&format!("{}: {:?}", REPLAY_ERROR, error)),
+        };
+
+    let correlation_id = CorrelationId::new();
+    let record_count = records.len();
+    for (index, record) in records.into_iter().enumerate() {
+        match global_state.commit_batch(correlation_id, record.prestate_hash, record.effects) {
+            Ok(CommitResult::Success { state_root, .. }) if state_root == record.state_root => (),
+            Ok(CommitResult::Success { state_root, .. }) => exit_with_fatal(&format!(
+                "{}: record {} on top of {:x} yielded {:x} instead of {:x}",
+                REPLAY_ERROR, index, record.prestate_hash, state_root, record.state_root
+            )),
+            Ok(CommitResult::RootNotFound) => exit_with_fatal(&format!(
+                "{}: record {} applies to {:x}, which is not a replayed root",
+                REPLAY_ERROR, index, record.prestate_hash
+            )),
+            Ok(commit_result) => exit_with_fatal(&format!(
+                "{}: record {}: {}",
+                REPLAY_ERROR, index, commit_result
+            )),
+            Err(StorageError::MapFull) => exit_with_fatal(LMDB_MAP_FULL_MESSAGE),
+            Err(error) => {
+                exit_with_fatal(&format!("{}: record {}: {:?}", REPLAY_ERROR, index, error))
+            }
+        }
+    }
+
+    if let Err(e) = environment.sync() {
+        logging::log_error(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+    }
+
+    logging::log_info(&format!(
+        "replayed {} commits from {} up to {:x}",
+        record_count,
+        input.display(),
+        global_state.current_root()
+    ));
+}
@@ -0,0 +1,268 @@
+//! The `self-test` subcommand.
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::time::Instant;
+
+use contract_ffi::bytesrepr::ToBytes;
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use wabt;
+
+use engine_shared::init::mocked_account;
+use engine_shared::logging;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::History;
+use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    CommitRequest, Deploy, DeployCode, DeployResult_ExecutionResult, ExecRequest, QueryRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::state::ProtocolVersion;
+
+use config::Config;
+use {
+    exit_with_fatal, get_data_dir, get_engine_config, get_map_size, get_store_name,
+    get_sync_policy, LMDB_SYNC_ERROR,
+};
+
+const SELF_TEST_READ_ONLY_MESSAGE: &str =
+    "self-test writes to global state, so it cannot run with read-only";
+const SELF_TEST_SCRATCH_DIR: &str = "self-test";
+const SELF_TEST_CLEANUP_ERROR: &str = "Could not remove self-test environment at";
+const SELF_TEST_ACCOUNT_ADDRESS: [u8; 32] = [0x5e; 32];
+const SELF_TEST_LOCAL_KEY: &[u8] = b"self-test";
+const SELF_TEST_VALUE: i32 = 42;
+const SELF_TEST_GAS_LIMIT: u64 = 1_000_000;
+const SELF_TEST_PROTOCOL_VERSION: u64 = 1;
+
+/// Runs a built-in deploy, which writes a value under a local key of a test account, through
+/// execution, commit and a query for the value against the data directory, printing how long each
+/// step took, or the error and exiting with a nonzero code at the first step which fails.  The
+/// test account and the value are committed under roots of their own, which nothing else refers
+/// to.
+pub fn self_test(config: &Config) {
+    if config.read_only.unwrap_or(false) {
+        exit_with_fatal(SELF_TEST_READ_ONLY_MESSAGE);
+    }
+    let start = Instant::now();
+
+    let data_dir = get_data_dir(config, false);
+    let scratch_dir = data_dir.join(format!("{}-{}", SELF_TEST_SCRATCH_DIR, process::id()));
+    let passed = run_self_test(config, &scratch_dir).is_ok();
+    if let Err(error) = fs::remove_dir_all(&scratch_dir) {
+        logging::log_warning(&format!(
+            "{} {}: {}",
+            SELF_TEST_CLEANUP_ERROR,
+            scratch_dir.display(),
+            error
+        ));
+    }
+    if !passed {
+        process::exit(1);
+    }
+
+    println!(
+        "self-test: passed in {} ms against {}",
+        start.elapsed().as_millis(),
+        data_dir.display()
+    );
+}
+
+/// Runs the self-test steps against a new environment at `scratch_dir`, so that neither the
+/// served store nor its state root index is written to
+fn run_self_test(config: &Config, scratch_dir: &Path) -> Result<(), ()> {
+    let engine_config = get_engine_config(config);
+    let gas_limit = cmp::min(
+        SELF_TEST_GAS_LIMIT,
+        engine_config
+            .get_max_gas_limit()
+            .unwrap_or(SELF_TEST_GAS_LIMIT),
+    );
+
+    let (environment, global_state) = self_test_step("setup", || {
+        fs::create_dir(scratch_dir).map_err(|error| error.to_string())?;
+        let environment = LmdbEnvironment::new_with_sync_policy(
+            &scratch_dir.to_path_buf(),
+            get_map_size(config),
+            DEFAULT_MAX_READERS,
+            get_sync_policy(config),
+        )
+        .map(Arc::new)
+        .map_err(|error| format!("{:?}", error))?;
+        let trie_store =
+            LmdbTrieStore::new(&environment, get_store_name(config), DatabaseFlags::empty())
+                .map_err(|error| format!("{:?}", error))?;
+        LmdbGlobalState::from_pairs(
+            Arc::clone(&environment),
+            Arc::new(trie_store),
+            CorrelationId::new(),
+            &mocked_account(SELF_TEST_ACCOUNT_ADDRESS),
+        )
+        .map(|global_state| (environment, global_state))
+        .map_err(|error| format!("{:?}", error))
+    })?;
+    let prestate_hash = global_state.current_root();
+    let engine_state = EngineState::new(global_state, engine_config);
+
+    let execution_result = self_test_step("exec", || {
+        self_test_exec(&engine_state, prestate_hash, gas_limit)
+    })?;
+    let poststate_hash = self_test_step("commit", || {
+        self_test_commit(&engine_state, prestate_hash, execution_result)
+    })?;
+    self_test_step("query", || self_test_query(&engine_state, poststate_hash))?;
+
+    if let Err(e) = environment.sync() {
+        logging::log_error(&format!("{}: {:?}", LMDB_SYNC_ERROR, e));
+    }
+    Ok(())
+}
+
+/// Runs one step of the self-test, printing how long it took, or the error if it fails
+fn self_test_step<T, F: FnOnce() -> Result<T, String>>(step: &str, f: F) -> Result<T, ()> {
+    let start = Instant::now();
+    match f() {
+        Ok(ret) => {
+            println!(
+                "self-test: {} ok in {} ms",
+                step,
+                start.elapsed().as_millis()
+            );
+            Ok(ret)
+        }
+        Err(error) => {
+            eprintln!("self-test: {} failed: {}", step, error);
+            Err(())
+        }
+    }
+}
+
+/// Returns the session code of the self-test deploy, which writes `SELF_TEST_VALUE` under
+/// `SELF_TEST_LOCAL_KEY`
+fn self_test_wasm() -> Result<Vec<u8>, String> {
+    let escape =
+        |bytes: &[u8]| -> String { bytes.iter().map(|byte| format!("\\{:02x}", byte)).collect() };
+    let value_bytes = Value::Int32(SELF_TEST_VALUE)
+        .to_bytes()
+        .map_err(|error| format!("{:?}", error))?;
+    let wat = format!(
+        r#"
+        (module
+            (import "env" "memory" (memory 16 64))
+            (import "env" "write_local" (func $write_local (param i32 i32 i32 i32)))
+            (data (i32.const 0) "{}")
+            (data (i32.const 64) "{}")
+            (func (export "call")
+                (call $write_local (i32.const 0) (i32.const {}) (i32.const 64) (i32.const {}))))
+        "#,
+        escape(SELF_TEST_LOCAL_KEY),
+        escape(&value_bytes),
+        SELF_TEST_LOCAL_KEY.len(),
+        value_bytes.len()
+    );
+    wabt::wat2wasm(wat).map_err(|error| format!("{:?}", error))
+}
+
+/// Executes the self-test deploy on top of `prestate_hash`
+fn self_test_exec(
+    engine_state: &EngineState<LmdbGlobalState>,
+    prestate_hash: Blake2bHash,
+    gas_limit: u64,
+) -> Result<DeployResult_ExecutionResult, String> {
+    let mut session = DeployCode::new();
+    session.set_code(self_test_wasm()?);
+
+    let mut deploy = Deploy::new();
+    deploy.set_address(SELF_TEST_ACCOUNT_ADDRESS.to_vec());
+    deploy.set_session(session);
+    deploy.set_motes_transferred_in_payment(gas_limit);
+    deploy.set_gas_price(1);
+    deploy.set_nonce(1);
+    deploy
+        .mut_authorization_keys()
+        .push(SELF_TEST_ACCOUNT_ADDRESS.to_vec());
+
+    let mut protocol_version = ProtocolVersion::new();
+    protocol_version.set_value(SELF_TEST_PROTOCOL_VERSION);
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(prestate_hash.to_vec());
+    exec_request.set_protocol_version(protocol_version);
+
+    let mut exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .map_err(|error| format!("{:?}", error))?;
+    if !exec_response.has_success() {
+        return Err(format!("{:?}", exec_response));
+    }
+    let mut deploy_result = exec_response
+        .take_success()
+        .take_deploy_results()
+        .pop()
+        .ok_or_else(|| "no deploy result".to_string())?;
+    if !deploy_result.has_execution_result() {
+        return Err(format!("{:?}", deploy_result));
+    }
+    let execution_result = deploy_result.take_execution_result();
+    if execution_result.has_error() {
+        return Err(format!("{:?}", execution_result.get_error()));
+    }
+    Ok(execution_result)
+}
+
+/// Commits the effects of the self-test deploy on top of `prestate_hash`, returning the post state
+/// hash
+fn self_test_commit(
+    engine_state: &EngineState<LmdbGlobalState>,
+    prestate_hash: Blake2bHash,
+    mut execution_result: DeployResult_ExecutionResult,
+) -> Result<Blake2bHash, String> {
+    let mut commit_request = CommitRequest::new();
+    commit_request.set_prestate_hash(prestate_hash.to_vec());
+    commit_request.set_effects(execution_result.take_effects().take_transform_map());
+
+    let commit_response = engine_state
+        .commit(RequestOptions::new(), commit_request)
+        .wait_drop_metadata()
+        .map_err(|error| format!("{:?}", error))?;
+    if !commit_response.has_success() {
+        return Err(format!("{:?}", commit_response));
+    }
+    Blake2bHash::try_from(commit_response.get_success().get_poststate_hash())
+        .map_err(|_| "invalid post state hash".to_string())
+}
+
+/// Queries the value written by the self-test deploy under `poststate_hash`
+fn self_test_query(
+    engine_state: &EngineState<LmdbGlobalState>,
+    poststate_hash: Blake2bHash,
+) -> Result<(), String> {
+    let base_key = Key::local(SELF_TEST_ACCOUNT_ADDRESS, SELF_TEST_LOCAL_KEY);
+
+    let mut query_request = QueryRequest::new();
+    query_request.set_state_hash(poststate_hash.to_vec());
+    query_request.set_base_key((&base_key).into());
+
+    let query_response = engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .map_err(|error| format!("{:?}", error))?;
+    if !query_response.has_success()
+        || query_response.get_success().get_int_value() != SELF_TEST_VALUE
+    {
+        return Err(format!("{:?}", query_response));
+    }
+    Ok(())
+}
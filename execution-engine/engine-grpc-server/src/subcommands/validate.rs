@@ -0,0 +1,58 @@
+//! The `validate` subcommand.
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use clap::ArgMatches;
+use engine_wasm_prep::wasm_costs::WasmCosts;
+use engine_wasm_prep::{Preprocessor, WasmiPreprocessor};
+use parity_wasm;
+
+use config::Config;
+use {
+    get_max_memory_pages, get_max_wasm_size, get_wasm_costs, ARG_WASM,
+    GAS_CONFIG_DEFAULTS_PROTOCOL_VERSION,
+};
+
+/// Runs the given wasm module through the same preprocessing as deploys, printing the size of the
+/// instrumented module, or the error and exiting with a nonzero code if it is rejected
+pub fn validate_wasm(config: &Config, matches: &ArgMatches) {
+    let path = Path::new(matches.value_of(ARG_WASM).expect("wasm is required"));
+
+    let module_bytes = fs::read(path).unwrap_or_else(|error| {
+        eprintln!("{}: could not read file: {}", path.display(), error);
+        process::exit(1)
+    });
+
+    let wasm_costs = get_wasm_costs(config).unwrap_or_else(|| {
+        WasmCosts::from_version(GAS_CONFIG_DEFAULTS_PROTOCOL_VERSION)
+            .expect("should have built-in wasm costs")
+    });
+    let preprocessor = WasmiPreprocessor::new(wasm_costs)
+        .with_max_module_size(get_max_wasm_size(config))
+        .with_max_memory_pages(get_max_memory_pages(config));
+
+    let module = preprocessor
+        .preprocess(&module_bytes)
+        .unwrap_or_else(|error| {
+            eprintln!("{}: invalid: {}", path.display(), error);
+            process::exit(1)
+        });
+
+    let instrumented_bytes = parity_wasm::serialize(module).unwrap_or_else(|error| {
+        eprintln!(
+            "{}: could not serialize instrumented module: {}",
+            path.display(),
+            error
+        );
+        process::exit(1)
+    });
+
+    println!(
+        "{}: valid, {} bytes instrumented to {} bytes",
+        path.display(),
+        module_bytes.len(),
+        instrumented_bytes.len()
+    );
+}
@@ -0,0 +1,76 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate lmdb;
+extern crate tempfile;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use tempfile::tempdir;
+
+use contract_ffi::key::Key;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::History;
+use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+use engine_storage::trie_store::TransactionSource;
+
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+#[test]
+fn should_query_while_a_commit_is_in_progress() {
+    let temp_dir = tempdir().unwrap();
+    let environment =
+        Arc::new(LmdbEnvironment::new(&temp_dir.path().to_path_buf(), 1024 * 4096, 126).unwrap());
+    let trie_store =
+        Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = LmdbGlobalState::from_pairs(
+        Arc::clone(&environment),
+        trie_store,
+        CorrelationId::new(),
+        &mocked_account,
+    )
+    .unwrap();
+    let root_hash = global_state.current_root().to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+    let base_key = Key::Account(test_support::MOCKED_ACCOUNT_ADDRESS);
+
+    // a commit holds the state lock and a write transaction until it is done
+    let state = engine_state.state();
+    let _state_guard = state.lock();
+    let _txn = environment.create_read_write_txn().unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let reader = engine_state.clone();
+    thread::spawn(move || {
+        let query_request = test_support::create_query_request(root_hash, &base_key, vec![]);
+        let query_response = reader
+            .query(RequestOptions::new(), query_request)
+            .wait_drop_metadata();
+        sender.send(query_response).unwrap();
+    });
+
+    let query_response = receiver
+        .recv_timeout(Duration::from_secs(10))
+        .expect("query should not wait for the commit")
+        .expect("should query");
+    assert!(
+        query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+}
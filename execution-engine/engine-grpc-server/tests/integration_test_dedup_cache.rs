@@ -0,0 +1,117 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate wabt;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::time::Duration;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{Deploy, ExecRequest, ExecResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::metrics::DEPLOYS_EXECUTED;
+
+const DEPLOY_HASH: [u8; 32] = [7u8; 32];
+
+fn exec(
+    engine_state: &EngineState<InMemoryGlobalState>,
+    parent_state_hash: Vec<u8>,
+    deploy: Deploy,
+) -> (ExecResponse, usize) {
+    let deploys_executed = DEPLOYS_EXECUTED.get();
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(parent_state_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+
+    (exec_response, DEPLOYS_EXECUTED.get() - deploys_executed)
+}
+
+fn mocked_global_state() -> InMemoryGlobalState {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap()
+}
+
+fn hashed_deploy() -> Deploy {
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_deploy_hash(DEPLOY_HASH.to_vec());
+    deploy
+}
+
+/// A deploy with a hash whose session never returns, so that it runs until it times out.
+fn hashed_looping_deploy() -> Deploy {
+    let mut deploy = hashed_deploy();
+    deploy.set_motes_transferred_in_payment(1_000_000_000_000);
+    let session = wabt::wat2wasm(r#"(module (func (export "call") (loop (br 0))))"#).unwrap();
+    deploy.mut_session().set_code(session);
+    deploy
+}
+
+// a single test, as the executed deploys counter is shared by the whole test binary
+#[test]
+fn should_return_cached_result_of_repeated_deploy() {
+    let global_state = mocked_global_state();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, EngineConfig::new());
+
+    let (first_response, executed) = exec(&engine_state, root_hash.clone(), hashed_deploy());
+    assert_eq!(executed, 1);
+    assert_eq!(first_response.get_success().get_deploy_results().len(), 1);
+
+    let (repeated_response, executed) = exec(&engine_state, root_hash.clone(), hashed_deploy());
+    assert_eq!(executed, 0, "repeated deploy should not run again");
+    assert_eq!(repeated_response, first_response);
+
+    // without a hash, a deploy always runs
+    let (_, executed) = exec(
+        &engine_state,
+        root_hash.clone(),
+        test_support::get_mock_deploy(),
+    );
+    assert_eq!(executed, 1);
+
+    // the same deploy against another state is not answered from the cache
+    let (other_state_response, _) = exec(&engine_state, vec![1u8; 32], hashed_deploy());
+    assert!(other_state_response.has_missing_parent());
+
+    // a cache without capacity turns deduplication off
+    let engine_state = EngineState::new(
+        mocked_global_state(),
+        EngineConfig::new().dedup_cache_size(0),
+    );
+    exec(&engine_state, root_hash.clone(), hashed_deploy());
+    let (_, executed) = exec(&engine_state, root_hash.clone(), hashed_deploy());
+    assert_eq!(executed, 1);
+
+    // a deploy which timed out may finish when resent, so it runs again
+    let engine_state = EngineState::new(
+        mocked_global_state(),
+        EngineConfig::new().execution_timeout(Some(Duration::from_millis(50))),
+    );
+    let (timed_out_response, _) = exec(&engine_state, root_hash.clone(), hashed_looping_deploy());
+    assert!(
+        timed_out_response.get_success().get_deploy_results()[0].has_precondition_failure(),
+        "unexpected response: {:?}",
+        timed_out_response
+    );
+    let (_, executed) = exec(&engine_state, root_hash, hashed_looping_deploy());
+    assert_eq!(executed, 1, "timed out deploy should run again");
+}
@@ -0,0 +1,67 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate lmdb;
+extern crate tempfile;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use tempfile::tempdir;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::os::get_page_size;
+use engine_shared::transform::Transform;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::History;
+use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
+
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+#[test]
+fn should_fail_commit_with_resource_exhausted_when_map_is_full() {
+    let temp_dir = tempdir().unwrap();
+    let map_size = get_page_size().unwrap() * 64;
+    let environment = Arc::new(
+        LmdbEnvironment::new(
+            &temp_dir.path().to_path_buf(),
+            map_size,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap(),
+    );
+    let trie_store =
+        Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+    let global_state = LmdbGlobalState::empty(environment, trie_store).unwrap();
+    let empty_root_hash = global_state.current_root().to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let large_value = Value::String("a".repeat(16 * 1024));
+    let effects: HashMap<Key, Transform> = (0..64u8)
+        .map(|i| (Key::Account([i; 32]), Transform::Write(large_value.clone())))
+        .collect();
+    let commit_request = test_support::create_commit_request(&empty_root_hash, &effects);
+
+    let result = engine_state
+        .commit(RequestOptions::new(), commit_request)
+        .wait_drop_metadata();
+
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => assert_eq!(
+            error.grpc_status,
+            grpc::GrpcStatus::ResourceExhausted as i32
+        ),
+        other => panic!("expected a ResourceExhausted status, got {:?}", other),
+    }
+}
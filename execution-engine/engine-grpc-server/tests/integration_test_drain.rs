@@ -0,0 +1,110 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::drain::{Drain, DrainGate};
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    CommitRequest, ExecRequest, GetEngineVersionRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn setup() -> (
+    DrainGate<EngineState<InMemoryGlobalState>>,
+    Drain,
+    ExecRequest,
+) {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+
+    let mut exec_request = ExecRequest::new();
+    exec_request
+        .mut_deploys()
+        .push(test_support::get_mock_deploy());
+    exec_request.set_parent_state_hash(global_state.root_hash.to_vec());
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let drain = Drain::new();
+    let engine_service = DrainGate::new(
+        EngineState::new(global_state, Default::default()),
+        drain.clone(),
+    );
+    (engine_service, drain, exec_request)
+}
+
+fn assert_unavailable<T: std::fmt::Debug>(result: Result<T, grpc::Error>) {
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::Unavailable as i32)
+        }
+        other => panic!("expected an Unavailable status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_exec_and_commit_with_unavailable_once_draining() {
+    let (engine_service, drain, exec_request) = setup();
+    drain.start();
+
+    assert_unavailable(
+        engine_service
+            .exec(RequestOptions::new(), exec_request.clone())
+            .wait_drop_metadata(),
+    );
+    assert_unavailable(
+        engine_service
+            .exec_stream(RequestOptions::new(), exec_request)
+            .wait_drop_metadata()
+            .collect::<Result<Vec<_>, _>>(),
+    );
+    assert_unavailable(
+        engine_service
+            .commit(RequestOptions::new(), CommitRequest::new())
+            .wait_drop_metadata(),
+    );
+
+    engine_service
+        .get_engine_version(RequestOptions::new(), GetEngineVersionRequest::new())
+        .wait_drop_metadata()
+        .expect("should still serve other requests");
+    assert_eq!(drain.in_flight(), 0);
+}
+
+#[test]
+fn should_count_exec_stream_until_its_stream_is_done() {
+    let (engine_service, drain, exec_request) = setup();
+
+    let exec_stream_response = engine_service.exec_stream(RequestOptions::new(), exec_request);
+    assert_eq!(drain.in_flight(), 1);
+
+    drain.start();
+    let responses: Vec<_> = exec_stream_response.wait_drop_metadata().collect();
+    assert!(responses.iter().all(Result::is_ok), "{:?}", responses);
+    assert_eq!(drain.in_flight(), 0);
+}
+
+#[test]
+fn should_count_exec_until_its_response_is_done() {
+    let (engine_service, drain, exec_request) = setup();
+
+    let exec_response = engine_service.exec(RequestOptions::new(), exec_request);
+    assert_eq!(drain.in_flight(), 1);
+
+    exec_response
+        .wait_drop_metadata()
+        .expect("should answer exec");
+    assert_eq!(drain.in_flight(), 0);
+}
@@ -0,0 +1,77 @@
+extern crate casperlabs_engine_grpc_server;
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+use std::collections::HashMap;
+
+use grpc::RequestOptions;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::GetEmptyStateRootRequest;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_shared::transform::Transform;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+#[allow(unused)]
+mod test_support;
+
+fn get_empty_state_root(engine_state: &EngineState<InMemoryGlobalState>) -> Vec<u8> {
+    engine_state
+        .get_empty_state_root(RequestOptions::new(), GetEmptyStateRootRequest::new())
+        .wait_drop_metadata()
+        .expect("should get empty state root response")
+        .take_empty_state_hash()
+}
+
+#[test]
+fn should_report_same_empty_root_whatever_the_current_state() {
+    let empty_engine_state =
+        EngineState::new(InMemoryGlobalState::empty().unwrap(), Default::default());
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let seeded_global_state =
+        InMemoryGlobalState::from_pairs(CorrelationId::new(), &mocked_account).unwrap();
+    let expected = seeded_global_state.empty_root_hash.to_vec();
+    let seeded_engine_state = EngineState::new(seeded_global_state, Default::default());
+
+    assert_eq!(get_empty_state_root(&empty_engine_state), expected);
+    assert_eq!(get_empty_state_root(&seeded_engine_state), expected);
+}
+
+#[test]
+fn should_commit_against_empty_root() {
+    let engine_state = EngineState::new(InMemoryGlobalState::empty().unwrap(), Default::default());
+    let empty_state_root = get_empty_state_root(&engine_state);
+
+    let key = Key::Hash([1u8; 32]);
+    let mut effects = HashMap::new();
+    effects.insert(key, Transform::Write(Value::Int32(7)));
+    let commit_request = test_support::create_commit_request(&empty_state_root, &effects);
+    let commit_response = engine_state
+        .commit(RequestOptions::new(), commit_request)
+        .wait_drop_metadata()
+        .expect("should commit");
+    assert!(
+        commit_response.has_success(),
+        "unexpected response: {:?}",
+        commit_response
+    );
+
+    let post_state_hash = commit_response.get_success().get_poststate_hash().to_vec();
+    let query_request = test_support::create_query_request(post_state_hash, &key, vec![]);
+    let query_response = engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .expect("should query");
+    assert!(
+        query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+}
@@ -0,0 +1,113 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    ErrorCode, ExecRequest, QueryRequest, StateDiffRequest, ValidateRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const MISSING_STATE_HASH: [u8; 32] = [1u8; 32];
+
+fn setup() -> (EngineState<InMemoryGlobalState>, Vec<u8>) {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    (
+        EngineState::new(global_state, Default::default()),
+        root_hash,
+    )
+}
+
+#[test]
+fn should_report_missing_parent_as_state_not_found() {
+    let (engine_state, _) = setup();
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.set_parent_state_hash(MISSING_STATE_HASH.to_vec());
+    exec_request
+        .mut_deploys()
+        .push(test_support::get_mock_deploy());
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+
+    assert!(exec_response.has_missing_parent());
+    assert_eq!(exec_response.get_error_code(), ErrorCode::STATE_NOT_FOUND);
+}
+
+#[test]
+fn should_fail_query_of_unknown_root_with_not_found() {
+    let (engine_state, _) = setup();
+
+    let mut query_request = QueryRequest::new();
+    query_request.set_state_hash(MISSING_STATE_HASH.to_vec());
+
+    match engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+    {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::NotFound as i32);
+            assert_eq!(
+                error.grpc_message,
+                engine_storage::error::Error::RootNotFound(MISSING_STATE_HASH.into()).to_string()
+            );
+        }
+        other => panic!("expected a NotFound status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_report_malformed_module_as_invalid_wasm() {
+    let (engine_state, _) = setup();
+
+    let mut validate_request = ValidateRequest::new();
+    validate_request.set_session_code(vec![0u8; 8]);
+    validate_request.set_payment_code(vec![0u8; 8]);
+
+    let validate_response = engine_state
+        .validate(RequestOptions::new(), validate_request)
+        .wait_drop_metadata()
+        .expect("should validate");
+
+    assert!(validate_response.has_failure());
+    assert_eq!(validate_response.get_error_code(), ErrorCode::INVALID_WASM);
+}
+
+#[test]
+fn should_report_malformed_state_hash_as_invalid_argument() {
+    let (engine_state, root_hash) = setup();
+
+    let mut state_diff_request = StateDiffRequest::new();
+    state_diff_request.set_before_state_hash(root_hash);
+    state_diff_request.set_after_state_hash(vec![1u8; 3]);
+
+    let state_diff_response = engine_state
+        .state_diff(RequestOptions::new(), state_diff_request)
+        .wait_drop_metadata()
+        .expect("should diff states");
+
+    assert!(state_diff_response.has_failure());
+    assert_eq!(
+        state_diff_response.get_error_code(),
+        ErrorCode::INVALID_ARGUMENT
+    );
+}
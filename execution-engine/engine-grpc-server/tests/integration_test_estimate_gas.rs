@@ -0,0 +1,68 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    Deploy, EstimateGasRequest, EstimateGasResponse,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const MISSING_STATE_HASH: [u8; 32] = [1u8; 32];
+
+fn estimate_gas(
+    parent_state_hash: Option<[u8; 32]>,
+    deploy: Deploy,
+) -> Result<EstimateGasResponse, grpc::Error> {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, EngineConfig::new());
+
+    let mut estimate_gas_request = EstimateGasRequest::new();
+    estimate_gas_request.set_parent_state_hash(
+        parent_state_hash
+            .map(|hash| hash.to_vec())
+            .unwrap_or(root_hash),
+    );
+    estimate_gas_request.set_deploy(deploy);
+    estimate_gas_request.set_protocol_version(test_support::get_protocol_version());
+
+    engine_state
+        .estimate_gas(RequestOptions::new(), estimate_gas_request)
+        .wait_drop_metadata()
+}
+
+fn assert_status(result: Result<EstimateGasResponse, grpc::Error>, status: grpc::GrpcStatus) {
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => assert_eq!(error.grpc_status, status as i32),
+        other => panic!("expected a {:?} status, got {:?}", status, other),
+    }
+}
+
+#[test]
+fn should_fail_estimate_against_unknown_root_with_not_found() {
+    assert_status(
+        estimate_gas(Some(MISSING_STATE_HASH), test_support::get_mock_deploy()),
+        grpc::GrpcStatus::NotFound,
+    );
+}
+
+#[test]
+fn should_fail_estimate_of_malformed_module_with_invalid_argument() {
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.mut_session().set_code(vec![0u8; 8]);
+
+    assert_status(
+        estimate_gas(None, deploy),
+        grpc::GrpcStatus::InvalidArgument,
+    );
+}
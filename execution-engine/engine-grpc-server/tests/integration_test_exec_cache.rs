@@ -0,0 +1,95 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{Deploy, ExecRequest, ExecResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::metrics::DEPLOYS_EXECUTED;
+
+fn exec(
+    engine_state: &EngineState<InMemoryGlobalState>,
+    parent_state_hash: Vec<u8>,
+    block_time: u64,
+    deploy: Deploy,
+) -> (ExecResponse, usize) {
+    let deploys_executed = DEPLOYS_EXECUTED.get();
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(parent_state_hash);
+    exec_request.set_block_time(block_time);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+
+    (exec_response, DEPLOYS_EXECUTED.get() - deploys_executed)
+}
+
+fn mocked_global_state() -> InMemoryGlobalState {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap()
+}
+
+fn deploy_with_hash(deploy_hash: u8) -> Deploy {
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_deploy_hash(vec![deploy_hash; 32]);
+    deploy
+}
+
+// a single test, as the executed deploys counter is shared by the whole test binary
+#[test]
+fn should_return_cached_result_of_deploy_with_same_content() {
+    let global_state = mocked_global_state();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(
+        global_state,
+        EngineConfig::new().dedup_cache_size(0).exec_cache_size(10),
+    );
+
+    let (first_response, executed) = exec(&engine_state, root_hash.clone(), 0, deploy_with_hash(1));
+    assert_eq!(executed, 1);
+    assert_eq!(first_response.get_success().get_deploy_results().len(), 1);
+
+    // the deploy hash is not part of the content
+    let (same_content_response, executed) =
+        exec(&engine_state, root_hash.clone(), 0, deploy_with_hash(2));
+    assert_eq!(
+        executed, 0,
+        "deploy with the same content should not run again"
+    );
+    assert_eq!(same_content_response, first_response);
+
+    // another block time may give another result
+    let (_, executed) = exec(&engine_state, root_hash.clone(), 1, deploy_with_hash(1));
+    assert_eq!(executed, 1);
+
+    // nor is the result reused against another state
+    let (other_state_response, _) = exec(&engine_state, vec![1u8; 32], 0, deploy_with_hash(1));
+    assert!(other_state_response.has_missing_parent());
+
+    // the cache is off by default
+    let engine_state = EngineState::new(
+        mocked_global_state(),
+        EngineConfig::new().dedup_cache_size(0),
+    );
+    exec(&engine_state, root_hash.clone(), 0, deploy_with_hash(1));
+    let (_, executed) = exec(&engine_state, root_hash, 0, deploy_with_hash(1));
+    assert_eq!(executed, 1);
+}
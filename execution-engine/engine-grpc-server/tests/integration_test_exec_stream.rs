@@ -0,0 +1,76 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{ExecRequest, ExecStreamResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn exec_stream(
+    config: EngineConfig,
+    deploy_count: usize,
+) -> Vec<Result<ExecStreamResponse, grpc::Error>> {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, config);
+
+    let mut exec_request = ExecRequest::new();
+    for _ in 0..deploy_count {
+        exec_request
+            .mut_deploys()
+            .push(test_support::get_mock_deploy());
+    }
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    engine_state
+        .exec_stream(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .collect()
+}
+
+#[test]
+fn should_stream_each_deploy_result_then_commit() {
+    let responses = exec_stream(Default::default(), 3);
+
+    assert_eq!(responses.len(), 4);
+    for response in &responses[..3] {
+        let response = response.as_ref().expect("should have a deploy result");
+        assert!(response.has_deploy_result(), "unexpected: {:?}", response);
+    }
+    let last_response = responses[3]
+        .as_ref()
+        .expect("should have a commit response");
+    assert!(
+        last_response.has_commit(),
+        "unexpected: {:?}",
+        last_response
+    );
+}
+
+#[test]
+fn should_reject_exec_stream_when_read_only() {
+    let responses = exec_stream(EngineConfig::new().read_only(true), 1);
+
+    match responses.as_slice() {
+        [Err(grpc::Error::GrpcMessage(error))] => assert_eq!(
+            error.grpc_status,
+            grpc::GrpcStatus::FailedPrecondition as i32
+        ),
+        other => panic!("expected a FailedPrecondition status, got {:?}", other),
+    }
+}
@@ -0,0 +1,96 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate protobuf;
+extern crate wabt;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+use protobuf::Message;
+
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    DeployResult_ExecutionResult, ExecRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const BLOCKTIME_CONTRACT: &str = r#"
+    (module
+        (import "env" "memory" (memory 16 64))
+        (import "env" "protocol_version" (func $protocol_version (result i64)))
+        (import "env" "get_blocktime" (func $get_blocktime (param i32)))
+        (func (export "call")
+            (drop (call $protocol_version))
+            (call $get_blocktime (i32.const 8))))
+"#;
+
+fn exec(execution_tracing: bool) -> DeployResult_ExecutionResult {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut deploy = test_support::get_mock_deploy();
+    deploy
+        .mut_session()
+        .set_code(wabt::wat2wasm(BLOCKTIME_CONTRACT).expect("should compile wat"));
+    deploy
+        .mut_authorization_keys()
+        .push(test_support::MOCKED_ACCOUNT_ADDRESS.to_vec());
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+    exec_request.set_execution_tracing(execution_tracing);
+
+    let mut exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+    let mut deploy_result = exec_response.mut_success().take_deploy_results().remove(0);
+    assert!(
+        deploy_result.has_execution_result(),
+        "unexpected deploy result: {:?}",
+        deploy_result
+    );
+    deploy_result.take_execution_result()
+}
+
+#[test]
+fn should_not_trace_by_default() {
+    assert!(exec(false).get_trace().is_empty());
+}
+
+#[test]
+fn should_trace_host_calls_when_requested() {
+    let execution_result = exec(true);
+
+    let trace = execution_result.get_trace();
+    assert_eq!(trace.len(), 2, "unexpected trace: {:?}", trace);
+    assert_eq!(trace[0].get_function(), "protocol_version");
+    assert_eq!(trace[0].get_results(), &[1]);
+    assert_eq!(trace[1].get_function(), "get_blocktime");
+    assert_eq!(trace[1].get_args(), &[8]);
+    assert!(!trace[1].get_trapped());
+
+    // runs of the same deploy against the same state yield byte-identical traces
+    let trace_bytes = |execution_result: &DeployResult_ExecutionResult| -> Vec<Vec<u8>> {
+        execution_result
+            .get_trace()
+            .iter()
+            .map(|host_call| host_call.write_to_bytes().unwrap())
+            .collect()
+    };
+    assert_eq!(trace_bytes(&execution_result), trace_bytes(&exec(true)));
+}
@@ -0,0 +1,130 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::thread;
+use std::time::Duration;
+
+use grpc::RequestOptions;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    ErrorCode, ExportStateRequest, ExportStateResponse,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn export_state(
+    state_hash: Option<Vec<u8>>,
+    chunk_size: u32,
+) -> Vec<Result<ExportStateResponse, grpc::Error>> {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut export_state_request = ExportStateRequest::new();
+    export_state_request.set_state_hash(state_hash.unwrap_or(root_hash));
+    export_state_request.set_chunk_size(chunk_size);
+
+    engine_state
+        .export_state(RequestOptions::new(), export_state_request)
+        .wait_drop_metadata()
+        .collect()
+}
+
+#[test]
+fn should_stream_chunks_of_pairs_then_count() {
+    let responses = export_state(None, 1);
+
+    assert!(responses.len() > 1);
+    let (last_response, chunks) = responses.split_last().unwrap();
+    for response in chunks {
+        let response = response.as_ref().expect("should have a chunk");
+        assert_eq!(response.get_chunk().get_pairs().len(), 1);
+    }
+    let last_response = last_response.as_ref().expect("should have a count");
+    assert!(last_response.has_done(), "unexpected: {:?}", last_response);
+    assert_eq!(
+        last_response.get_done().get_pair_count(),
+        chunks.len() as u64
+    );
+}
+
+#[test]
+fn should_send_every_pair_in_one_chunk_by_default() {
+    let chunked = export_state(None, 1);
+    let responses = export_state(None, 0);
+
+    assert_eq!(responses.len(), 2);
+    let chunk = responses[0].as_ref().expect("should have a chunk");
+    assert_eq!(chunk.get_chunk().get_pairs().len(), chunked.len() - 1);
+}
+
+#[test]
+fn should_report_unknown_root() {
+    let responses = export_state(Some(vec![1u8; 32]), 0);
+
+    match responses.as_slice() {
+        [Ok(response)] => {
+            assert!(response.has_missing_state(), "unexpected: {:?}", response);
+            assert_eq!(response.get_error_code(), ErrorCode::STATE_NOT_FOUND);
+        }
+        other => panic!("expected a missing state, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_malformed_state_hash() {
+    let responses = export_state(Some(vec![1u8; 3]), 0);
+
+    match responses.as_slice() {
+        [Err(grpc::Error::GrpcMessage(error))] => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::InvalidArgument as i32)
+        }
+        other => panic!("expected an InvalidArgument status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_abandon_export_when_client_stops_reading() {
+    let correlation_id = CorrelationId::new();
+    let pairs: Vec<(Key, Value)> = (0..100u8)
+        .map(|index| (Key::Hash([index; 32]), Value::Int32(i32::from(index))))
+        .collect();
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &pairs).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_config = EngineConfig::new().read_timeout(Some(Duration::from_millis(100)));
+    let engine_state = EngineState::new(global_state, engine_config);
+
+    let mut export_state_request = ExportStateRequest::new();
+    export_state_request.set_state_hash(root_hash);
+    export_state_request.set_chunk_size(1);
+
+    let mut responses = engine_state
+        .export_state(RequestOptions::new(), export_state_request)
+        .wait_drop_metadata();
+    let first_response = responses.next().expect("should have a response");
+    assert!(first_response.expect("should have a chunk").has_chunk());
+
+    // the export fills its buffer, then gives up waiting for the client
+    thread::sleep(Duration::from_millis(500));
+    let rest: Vec<ExportStateResponse> = responses
+        .map(|response| response.expect("should have a chunk"))
+        .collect();
+    assert!(rest.len() < pairs.len() - 1);
+    assert!(rest.iter().all(ExportStateResponse::has_chunk));
+}
@@ -0,0 +1,30 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::FlushStoreRequest;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+#[test]
+fn should_flush_store() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let response = engine_state
+        .flush_store(RequestOptions::new(), FlushStoreRequest::new())
+        .wait_drop_metadata()
+        .expect("should flush store");
+
+    assert!(
+        response.has_success(),
+        "unexpected response: {:?}",
+        response
+    );
+}
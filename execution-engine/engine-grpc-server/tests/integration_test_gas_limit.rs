@@ -0,0 +1,73 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{Deploy, ExecRequest, ExecResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn exec(engine_config: EngineConfig, deploy: Deploy) -> Result<ExecResponse, grpc::Error> {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, engine_config);
+
+    let mut exec_request = ExecRequest::new();
+    exec_request
+        .mut_deploys()
+        .push(test_support::get_mock_deploy());
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+}
+
+fn assert_invalid_argument(result: Result<ExecResponse, grpc::Error>, expected_message: &str) {
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::InvalidArgument as i32);
+            assert_eq!(error.grpc_message, expected_message);
+        }
+        other => panic!("expected an InvalidArgument status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_fail_exec_with_invalid_argument_when_gas_limit_exceeds_max() {
+    let engine_config = EngineConfig::new().max_gas_limit(Some(1000));
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_motes_transferred_in_payment(1001);
+
+    assert_invalid_argument(
+        exec(engine_config, deploy),
+        "deploy 1: gas limit 1001 exceeds the maximum of 1000",
+    );
+}
+
+#[test]
+fn should_use_default_gas_limit_when_deploy_gives_no_gas_price() {
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_gas_price(0);
+
+    assert_invalid_argument(
+        exec(EngineConfig::new(), deploy.clone()),
+        "deploy 1: gas_price must be greater than 0, as no default gas limit is configured",
+    );
+
+    let engine_config = EngineConfig::new()
+        .max_gas_limit(Some(1000))
+        .default_gas_limit(Some(1000));
+    let exec_response = exec(engine_config, deploy).expect("should exec");
+    assert_eq!(exec_response.get_success().get_deploy_results().len(), 2);
+}
@@ -0,0 +1,101 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate wabt;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    DeployResult_ExecutionResult, ExecRequest, ExecResponse,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const MULTIPLYING_CONTRACT: &str = r#"
+    (module
+        (import "env" "memory" (memory 16 64))
+        (func (export "call")
+            (drop (i32.mul (i32.const 2) (i32.const 3)))))
+"#;
+
+/// enough for the contract at any of the gas prices below but the last
+const PAYMENT: u64 = 1_000_000;
+
+fn exec(gas_price: u64) -> Result<ExecResponse, grpc::Error> {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_motes_transferred_in_payment(PAYMENT);
+    deploy
+        .mut_session()
+        .set_code(wabt::wat2wasm(MULTIPLYING_CONTRACT).expect("should compile wat"));
+    deploy
+        .mut_authorization_keys()
+        .push(test_support::MOCKED_ACCOUNT_ADDRESS.to_vec());
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+    exec_request.set_gas_price(gas_price);
+
+    engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+}
+
+fn execution_result(gas_price: u64) -> DeployResult_ExecutionResult {
+    let mut exec_response = exec(gas_price).expect("should exec");
+    let mut deploy_result = exec_response.mut_success().take_deploy_results().remove(0);
+    assert!(
+        deploy_result.has_execution_result(),
+        "unexpected deploy result: {:?}",
+        deploy_result
+    );
+    deploy_result.take_execution_result()
+}
+
+#[test]
+fn should_charge_gas_used_when_request_gives_no_gas_price() {
+    let execution_result = execution_result(0);
+
+    assert!(execution_result.get_gas_used() > 0);
+    assert_eq!(execution_result.get_cost(), execution_result.get_gas_used());
+}
+
+#[test]
+fn should_charge_gas_used_times_gas_price() {
+    let execution_result = execution_result(7);
+
+    assert!(execution_result.get_gas_used() > 0);
+    assert_eq!(
+        execution_result.get_cost(),
+        execution_result.get_gas_used() * 7
+    );
+}
+
+#[test]
+fn should_work_out_gas_limit_at_gas_price() {
+    let execution_result = execution_result(PAYMENT);
+
+    let gas_error = execution_result.get_error().get_gas_error();
+    assert_eq!(gas_error.get_gas_limit(), 1);
+    assert_eq!(
+        execution_result.get_cost(),
+        execution_result.get_gas_used() * PAYMENT
+    );
+}
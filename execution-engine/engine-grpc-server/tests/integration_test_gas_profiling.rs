@@ -0,0 +1,95 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate wabt;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    DeployResult_ExecutionResult, ExecRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const MULTIPLYING_CONTRACT: &str = r#"
+    (module
+        (import "env" "memory" (memory 16 64))
+        (func (export "call")
+            (drop (i32.mul (i32.const 2) (i32.const 3)))))
+"#;
+
+fn exec(config: EngineConfig, gas_profiling: bool) -> DeployResult_ExecutionResult {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, config);
+
+    let mut deploy = test_support::get_mock_deploy();
+    deploy
+        .mut_session()
+        .set_code(wabt::wat2wasm(MULTIPLYING_CONTRACT).expect("should compile wat"));
+    deploy
+        .mut_authorization_keys()
+        .push(test_support::MOCKED_ACCOUNT_ADDRESS.to_vec());
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+    exec_request.set_gas_profiling(gas_profiling);
+
+    let mut exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+    let mut deploy_result = exec_response.mut_success().take_deploy_results().remove(0);
+    assert!(
+        deploy_result.has_execution_result(),
+        "unexpected deploy result: {:?}",
+        deploy_result
+    );
+    deploy_result.take_execution_result()
+}
+
+fn assert_profiled(execution_result: &DeployResult_ExecutionResult) {
+    let gas_profile = execution_result.get_gas_profile();
+    assert_eq!(
+        gas_profile.values().sum::<u64>(),
+        execution_result.get_cost()
+    );
+    assert_eq!(gas_profile.get("mul"), Some(&4));
+    assert_eq!(gas_profile.get("unattributed"), None);
+}
+
+#[test]
+fn should_not_profile_gas_by_default() {
+    let execution_result = exec(Default::default(), false);
+
+    assert!(execution_result.get_cost() > 0);
+    assert!(execution_result.get_gas_profile().is_empty());
+}
+
+#[test]
+fn should_profile_gas_when_requested() {
+    let execution_result = exec(Default::default(), true);
+
+    assert_profiled(&execution_result);
+}
+
+#[test]
+fn should_profile_gas_when_configured() {
+    let execution_result = exec(EngineConfig::new().gas_profiling(true), false);
+
+    assert_profiled(&execution_result);
+}
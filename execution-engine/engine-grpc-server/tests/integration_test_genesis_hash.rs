@@ -0,0 +1,72 @@
+extern crate casperlabs_engine_grpc_server;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+use std::collections::HashMap;
+
+use grpc::RequestOptions;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::GetGenesisHashRequest;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use engine_core::engine_state::EngineState;
+use engine_shared::newtypes::Blake2bHash;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+#[allow(unused)]
+mod test_support;
+
+const GENESIS_ADDR: [u8; 32] = [6u8; 32];
+
+#[test]
+fn should_fail_before_genesis() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let response = engine_state
+        .get_genesis_hash(RequestOptions::new(), GetGenesisHashRequest::new())
+        .wait_drop_metadata()
+        .expect("should get genesis hash response");
+
+    assert!(response.has_failure());
+}
+
+#[test]
+fn should_replace_genesis_hash_with_later_one() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, Default::default());
+    let second = Blake2bHash::new(b"second");
+    engine_state.set_genesis_hash(Blake2bHash::new(b"first"));
+    engine_state.set_genesis_hash(second);
+
+    let response = engine_state
+        .get_genesis_hash(RequestOptions::new(), GetGenesisHashRequest::new())
+        .wait_drop_metadata()
+        .expect("should get genesis hash response");
+
+    assert_eq!(response.get_genesis_hash(), second.to_vec().as_slice());
+}
+
+#[ignore]
+#[test]
+fn should_report_root_of_genesis() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let (genesis_request, _) = test_support::create_genesis_request(GENESIS_ADDR, HashMap::new());
+    let genesis_response = engine_state
+        .run_genesis(RequestOptions::new(), genesis_request)
+        .wait_drop_metadata()
+        .expect("should run genesis");
+
+    let response = engine_state
+        .get_genesis_hash(RequestOptions::new(), GetGenesisHashRequest::new())
+        .wait_drop_metadata()
+        .expect("should get genesis hash response");
+
+    assert_eq!(
+        response.get_genesis_hash(),
+        genesis_response.get_success().get_poststate_hash()
+    );
+}
@@ -0,0 +1,85 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+use std::time::Duration;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    Feature, GetCapabilitiesRequest, GetCapabilitiesResponse,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::IPC_PROTOCOL_VERSION;
+
+fn get_capabilities(engine_config: EngineConfig) -> GetCapabilitiesResponse {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, engine_config);
+    engine_state
+        .get_capabilities(RequestOptions::new(), GetCapabilitiesRequest::new())
+        .wait_drop_metadata()
+        .expect("should get capabilities")
+}
+
+#[test]
+fn should_report_default_capabilities() {
+    let engine_config = EngineConfig::new();
+    let response = get_capabilities(engine_config.clone());
+
+    assert_eq!(
+        response.get_features(),
+        &[Feature::PRUNING, Feature::DEDUP_CACHE]
+    );
+    assert_eq!(response.get_protocol_version(), IPC_PROTOCOL_VERSION);
+    assert_eq!(response.get_max_gas_limit(), 0);
+    assert_eq!(response.get_default_gas_limit(), 0);
+    assert_eq!(
+        response.get_max_deploys_per_batch(),
+        engine_config.get_max_deploys_per_batch() as u64
+    );
+    assert_eq!(
+        response.get_max_wasm_size(),
+        engine_config.get_max_wasm_size() as u64
+    );
+    assert_eq!(
+        response.get_max_memory_pages(),
+        engine_config.get_max_memory_pages()
+    );
+    assert_eq!(response.get_execution_timeout_ms(), 0);
+}
+
+#[test]
+fn should_report_configured_capabilities() {
+    let engine_config = EngineConfig::new()
+        .tls(true)
+        .metrics(true)
+        .read_only(true)
+        .gas_profiling(true)
+        .dedup_cache_size(0)
+        .exec_cache_size(10)
+        .max_gas_limit(Some(1_000_000))
+        .default_gas_limit(Some(1_000))
+        .max_deploys_per_batch(50)
+        .execution_timeout(Some(Duration::from_secs(2)));
+    let response = get_capabilities(engine_config);
+
+    assert_eq!(
+        response.get_features(),
+        &[
+            Feature::TLS,
+            Feature::METRICS,
+            Feature::READ_ONLY,
+            Feature::GAS_PROFILING,
+            Feature::EXEC_CACHE,
+        ]
+    );
+    assert_eq!(response.get_max_gas_limit(), 1_000_000);
+    assert_eq!(response.get_default_gas_limit(), 1_000);
+    assert_eq!(response.get_max_deploys_per_batch(), 50);
+    assert_eq!(response.get_execution_timeout_ms(), 2_000);
+}
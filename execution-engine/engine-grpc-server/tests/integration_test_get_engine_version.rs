@@ -0,0 +1,29 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::GetEngineVersionRequest;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::IPC_PROTOCOL_VERSION;
+
+#[test]
+fn should_report_engine_version() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let response = engine_state
+        .get_engine_version(RequestOptions::new(), GetEngineVersionRequest::new())
+        .wait_drop_metadata()
+        .expect("should get engine version");
+
+    assert_eq!(response.get_version(), env!("CARGO_PKG_VERSION"));
+    assert!(!response.get_git_commit_hash().is_empty());
+    assert_eq!(response.get_protocol_version(), IPC_PROTOCOL_VERSION);
+}
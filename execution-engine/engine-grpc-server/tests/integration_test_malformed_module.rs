@@ -0,0 +1,84 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::EngineState;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::ExecRequest;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+#[test]
+fn should_fail_exec_with_invalid_argument_when_module_is_malformed() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut malformed_deploy = test_support::get_mock_deploy();
+    malformed_deploy
+        .mut_session()
+        .set_code(b"\0asm\x02\0\0\0".to_vec());
+
+    let mut exec_request = ExecRequest::new();
+    exec_request
+        .mut_deploys()
+        .push(test_support::get_mock_deploy());
+    exec_request.mut_deploys().push(malformed_deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let result = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata();
+
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::InvalidArgument as i32);
+            assert_eq!(
+                error.grpc_message,
+                "deploy 1: malformed module at byte 8: unsupported wasm version 2, expected 1"
+            );
+        }
+        other => panic!("expected an InvalidArgument status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_fail_exec_with_invalid_argument_when_payment_module_is_malformed() {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut malformed_deploy = test_support::get_mock_deploy();
+    malformed_deploy
+        .mut_payment()
+        .set_code(b"\0asm\x02\0\0\0".to_vec());
+
+    let mut exec_request = ExecRequest::new();
+    exec_request.mut_deploys().push(malformed_deploy);
+    exec_request.set_parent_state_hash(root_hash);
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+
+    let result = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata();
+
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::InvalidArgument as i32);
+            assert_eq!(
+                error.grpc_message,
+                "deploy 0: payment code: malformed module at byte 8: unsupported wasm version 2, \
+                 expected 1"
+            );
+        }
+        other => panic!("expected an InvalidArgument status, got {:?}", other),
+    }
+}
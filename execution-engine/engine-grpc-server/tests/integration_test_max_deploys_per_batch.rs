@@ -0,0 +1,82 @@
+extern crate engine_core;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    ExecRequest, ExecResponse, GetEngineVersionRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn exec_request(
+    engine_state: &EngineState<InMemoryGlobalState>,
+    deploy_count: usize,
+) -> ExecRequest {
+    let mut exec_request = ExecRequest::new();
+    for _ in 0..deploy_count {
+        exec_request
+            .mut_deploys()
+            .push(test_support::get_mock_deploy());
+    }
+    exec_request.set_parent_state_hash(engine_state.state().lock().root_hash.to_vec());
+    exec_request.set_protocol_version(test_support::get_protocol_version());
+    exec_request
+}
+
+fn engine_state(max_deploys_per_batch: usize) -> EngineState<InMemoryGlobalState> {
+    let global_state = InMemoryGlobalState::empty().unwrap();
+    let engine_config = EngineConfig::new().max_deploys_per_batch(max_deploys_per_batch);
+    EngineState::new(global_state, engine_config)
+}
+
+#[test]
+fn should_fail_exec_with_invalid_argument_when_deploys_exceed_max() {
+    let engine_state = engine_state(2);
+    let exec_request = exec_request(&engine_state, 3);
+
+    let result: Result<ExecResponse, grpc::Error> = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata();
+    match result {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::InvalidArgument as i32);
+            assert_eq!(
+                error.grpc_message,
+                "request has 3 deploys, more than the maximum of 2"
+            );
+        }
+        other => panic!("expected an InvalidArgument status, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_exec_as_many_deploys_as_the_max() {
+    let engine_state = engine_state(2);
+    let exec_request = exec_request(&engine_state, 2);
+
+    let exec_response = engine_state
+        .exec(RequestOptions::new(), exec_request)
+        .wait_drop_metadata()
+        .expect("should exec");
+    assert_eq!(exec_response.get_success().get_deploy_results().len(), 2);
+}
+
+#[test]
+fn should_report_max_deploys_per_batch_in_engine_version() {
+    let engine_state = engine_state(2);
+
+    let response = engine_state
+        .get_engine_version(RequestOptions::new(), GetEngineVersionRequest::new())
+        .wait_drop_metadata()
+        .expect("should get engine version");
+    assert_eq!(response.get_max_deploys_per_batch(), 2);
+}
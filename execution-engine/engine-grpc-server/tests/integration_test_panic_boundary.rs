@@ -0,0 +1,82 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use contract_ffi::bytesrepr::ToBytes;
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+use engine_storage::trie::{Pointer, Trie};
+use engine_storage::trie_store::{Transaction, TransactionSource, TrieStore};
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    GetEngineVersionRequest, StateDiffRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::panic_boundary::PanicBoundary;
+
+/// Returns global state holding the mocked account, its root, and the root of a corrupted trie
+/// whose only child is missing from the store.
+fn setup() -> (InMemoryGlobalState, Blake2bHash, Blake2bHash) {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash;
+
+    let missing_hash: Blake2bHash = [9u8; 32].into();
+    let corrupted_root: Trie<Key, Value> = Trie::node(&[(0, Pointer::NodePointer(missing_hash))]);
+    let corrupted_root_hash = Blake2bHash::new(&corrupted_root.to_bytes().unwrap());
+    {
+        let mut txn = global_state.environment.create_read_write_txn().unwrap();
+        global_state
+            .store
+            .put(&mut txn, &corrupted_root_hash, &corrupted_root)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    (global_state, root_hash, corrupted_root_hash)
+}
+
+#[test]
+fn should_fail_request_which_panics_with_internal_status() {
+    let (global_state, root_hash, corrupted_root_hash) = setup();
+    let engine_service = PanicBoundary::new(EngineState::new(global_state, Default::default()));
+
+    let mut state_diff_request = StateDiffRequest::new();
+    state_diff_request.set_before_state_hash(root_hash.to_vec());
+    state_diff_request.set_after_state_hash(corrupted_root_hash.to_vec());
+
+    match engine_service
+        .state_diff(RequestOptions::new(), state_diff_request)
+        .wait_drop_metadata()
+    {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::Internal as i32);
+            assert!(
+                error.grpc_message.starts_with("state_diff panicked: "),
+                "unexpected message: {}",
+                error.grpc_message
+            );
+        }
+        other => panic!("expected an Internal status, got {:?}", other),
+    }
+
+    // the service keeps handling requests
+    engine_service
+        .get_engine_version(RequestOptions::new(), GetEngineVersionRequest::new())
+        .wait_drop_metadata()
+        .expect("should get engine version");
+}
@@ -0,0 +1,157 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate lmdb;
+extern crate tempfile;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use tempfile::tempdir;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_shared::transform::Transform;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::History;
+use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{PruneStateRequest, PruneStateResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn prune_state(retain_state_hashes: Vec<Vec<u8>>) -> PruneStateResponse {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash;
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut prune_state_request = PruneStateRequest::new();
+    prune_state_request.set_retain_state_hashes(
+        retain_state_hashes
+            .into_iter()
+            .map(|state_hash| {
+                if state_hash.is_empty() {
+                    root_hash.to_vec()
+                } else {
+                    state_hash
+                }
+            })
+            .collect(),
+    );
+
+    engine_state
+        .prune_state(RequestOptions::new(), prune_state_request)
+        .wait_drop_metadata()
+        .expect("should prune state")
+}
+
+#[test]
+fn should_fail_to_prune_in_memory_state() {
+    // An empty hash stands for the root of the mocked state.
+    let response = prune_state(vec![vec![]]);
+
+    assert_eq!(
+        response.get_failure(),
+        "global state store cannot be pruned"
+    );
+}
+
+#[test]
+fn should_fail_to_prune_without_roots_to_retain() {
+    let response = prune_state(vec![]);
+
+    assert_eq!(
+        response.get_failure(),
+        "at least one state root to retain is required"
+    );
+}
+
+#[test]
+fn should_fail_to_prune_with_malformed_state_hash() {
+    let response = prune_state(vec![vec![1u8; 3]]);
+
+    assert!(
+        response.has_failure(),
+        "unexpected response: {:?}",
+        response
+    );
+}
+
+#[test]
+fn should_prune_lmdb_state_in_default_store() {
+    let temp_dir = tempdir().unwrap();
+    let environment =
+        Arc::new(LmdbEnvironment::new(&temp_dir.path().to_path_buf(), 1024 * 4096, 16).unwrap());
+    // the unnamed database also holds the names of the state root index and store version
+    let trie_store =
+        Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = LmdbGlobalState::from_pairs(
+        environment,
+        trie_store,
+        CorrelationId::new(),
+        &mocked_account,
+    )
+    .unwrap();
+    let root_hash = global_state.current_root().to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let key = Key::Hash([1u8; 32]);
+    let mut effects = HashMap::new();
+    effects.insert(key, Transform::Write(Value::Int32(7)));
+    let commit_request = test_support::create_commit_request(&root_hash, &effects);
+    let commit_response = engine_state
+        .commit(RequestOptions::new(), commit_request)
+        .wait_drop_metadata()
+        .expect("should commit");
+    let post_state_hash = commit_response.get_success().get_poststate_hash().to_vec();
+
+    let mut prune_state_request = PruneStateRequest::new();
+    prune_state_request.set_retain_state_hashes(vec![post_state_hash.clone()].into());
+    let response = engine_state
+        .prune_state(RequestOptions::new(), prune_state_request)
+        .wait_drop_metadata()
+        .expect("should prune state");
+    assert!(
+        response.has_success(),
+        "unexpected response: {:?}",
+        response
+    );
+    assert!(response.get_success().get_nodes_deleted() > 0);
+
+    let query_request = test_support::create_query_request(post_state_hash, &key, vec![]);
+    let query_response = engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .expect("should query");
+    assert!(
+        query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+
+    let query_request = test_support::create_query_request(root_hash, &key, vec![]);
+    let query_response = engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .expect("should query");
+    assert!(
+        !query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+}
@@ -0,0 +1,73 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use contract_ffi::key::Key;
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{ErrorCode, QueryResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn query(engine_config: EngineConfig, path: Vec<String>) -> QueryResponse {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, engine_config);
+
+    let base_key = Key::Account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let query_request = test_support::create_query_request(root_hash, &base_key, path);
+    engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .expect("should query")
+}
+
+#[test]
+fn should_answer_query_within_limits() {
+    let query_response = query(EngineConfig::new(), vec![]);
+
+    assert!(
+        query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+}
+
+#[test]
+fn should_fail_query_with_path_over_max_depth() {
+    let path = vec!["a".to_string(), "b".to_string()];
+    let query_response = query(EngineConfig::new().max_query_depth(1), path);
+
+    assert_eq!(
+        query_response.get_error_code(),
+        ErrorCode::QUERY_TOO_COMPLEX
+    );
+    assert!(
+        query_response.get_failure().contains("limit of 1"),
+        "unexpected failure: {}",
+        query_response.get_failure()
+    );
+}
+
+#[test]
+fn should_fail_query_visiting_more_than_max_nodes() {
+    let query_response = query(EngineConfig::new().max_query_nodes(1), vec![]);
+
+    assert_eq!(
+        query_response.get_error_code(),
+        ErrorCode::QUERY_TOO_COMPLEX
+    );
+}
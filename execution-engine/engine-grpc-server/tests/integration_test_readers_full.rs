@@ -0,0 +1,77 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+extern crate lmdb;
+extern crate tempfile;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::sync::Arc;
+
+use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use tempfile::tempdir;
+
+use contract_ffi::key::Key;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::lmdb::LmdbGlobalState;
+use engine_storage::global_state::History;
+use engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+use engine_storage::trie_store::TransactionSource;
+
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+#[test]
+fn should_fail_query_with_unavailable_when_reader_slots_are_taken() {
+    let temp_dir = tempdir().unwrap();
+    let environment =
+        Arc::new(LmdbEnvironment::new(&temp_dir.path().to_path_buf(), 1024 * 4096, 1).unwrap());
+    let trie_store =
+        Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = LmdbGlobalState::from_pairs(
+        Arc::clone(&environment),
+        trie_store,
+        CorrelationId::new(),
+        &mocked_account,
+    )
+    .unwrap();
+    let root_hash = global_state.current_root().to_vec();
+    let engine_state = EngineState::new(global_state, Default::default());
+    let base_key = Key::Account(test_support::MOCKED_ACCOUNT_ADDRESS);
+
+    {
+        let _txn = environment.create_read_txn().unwrap();
+
+        let query_request =
+            test_support::create_query_request(root_hash.clone(), &base_key, vec![]);
+        match engine_state
+            .query(RequestOptions::new(), query_request)
+            .wait_drop_metadata()
+        {
+            Err(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(error.grpc_status, grpc::GrpcStatus::Unavailable as i32)
+            }
+            other => panic!("expected an Unavailable status, got {:?}", other),
+        }
+    }
+
+    // the same query succeeds once the slot is free again
+    let query_request = test_support::create_query_request(root_hash, &base_key, vec![]);
+    let query_response = engine_state
+        .query(RequestOptions::new(), query_request)
+        .wait_drop_metadata()
+        .expect("should query");
+    assert!(
+        query_response.has_success(),
+        "unexpected response: {:?}",
+        query_response
+    );
+}
@@ -0,0 +1,136 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use grpc::RequestOptions;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_shared::transform::Transform;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+use engine_storage::global_state::CommitResult;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{StateDiffRequest, StateDiffResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+const WRITTEN_KEYS: [Key; 3] = [
+    Key::Hash([1u8; 32]),
+    Key::Hash([2u8; 32]),
+    Key::Hash([3u8; 32]),
+];
+
+/// Returns an engine state with a state root holding the mocked account and a second one which
+/// also holds `WRITTEN_KEYS`.
+fn setup() -> (EngineState<InMemoryGlobalState>, Blake2bHash, Blake2bHash) {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let before_root = global_state.root_hash;
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let effects: HashMap<Key, Transform> = WRITTEN_KEYS
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (*key, Transform::Write(Value::Int32(index as i32))))
+        .collect();
+    let after_root = match engine_state
+        .apply_effect(correlation_id, before_root, effects)
+        .unwrap()
+    {
+        CommitResult::Success { state_root, .. } => state_root,
+        other => panic!("commit failed: {:?}", other),
+    };
+
+    (engine_state, before_root, after_root)
+}
+
+fn state_diff(
+    engine_state: &EngineState<InMemoryGlobalState>,
+    before_state_hash: Vec<u8>,
+    after_state_hash: Vec<u8>,
+    limit: u64,
+) -> StateDiffResponse {
+    let mut state_diff_request = StateDiffRequest::new();
+    state_diff_request.set_before_state_hash(before_state_hash);
+    state_diff_request.set_after_state_hash(after_state_hash);
+    state_diff_request.set_limit(limit);
+
+    engine_state
+        .state_diff(RequestOptions::new(), state_diff_request)
+        .wait_drop_metadata()
+        .expect("should diff states")
+}
+
+#[test]
+fn should_list_written_keys() {
+    let (engine_state, before_root, after_root) = setup();
+
+    let response = state_diff(&engine_state, before_root.to_vec(), after_root.to_vec(), 0);
+
+    assert!(
+        response.has_success(),
+        "unexpected response: {:?}",
+        response
+    );
+    let state_diff = response.get_success();
+    assert!(!state_diff.get_truncated());
+    let keys: Vec<Key> = state_diff
+        .get_diffs()
+        .iter()
+        .map(|key_diff| key_diff.get_key().try_into().expect("should parse key"))
+        .collect();
+    assert_eq!(keys, WRITTEN_KEYS.to_vec());
+    for key_diff in state_diff.get_diffs() {
+        assert!(!key_diff.has_before());
+        assert!(key_diff.has_after());
+    }
+}
+
+#[test]
+fn should_truncate_at_limit() {
+    let (engine_state, before_root, after_root) = setup();
+
+    let response = state_diff(&engine_state, after_root.to_vec(), before_root.to_vec(), 2);
+
+    let state_diff = response.get_success();
+    assert!(state_diff.get_truncated());
+    assert_eq!(state_diff.get_diffs().len(), 2);
+    for key_diff in state_diff.get_diffs() {
+        assert!(key_diff.has_before());
+        assert!(!key_diff.has_after());
+    }
+}
+
+#[test]
+fn should_report_missing_state() {
+    let (engine_state, before_root, _) = setup();
+
+    let response = state_diff(&engine_state, before_root.to_vec(), vec![1u8; 32], 0);
+
+    assert_eq!(response.get_missing_state().get_hash(), &[1u8; 32][..]);
+}
+
+#[test]
+fn should_fail_with_malformed_state_hash() {
+    let (engine_state, before_root, _) = setup();
+
+    let response = state_diff(&engine_state, before_root.to_vec(), vec![1u8; 31], 0);
+
+    assert_eq!(
+        response.get_failure(),
+        "expected 32 bytes for state hash, got 31"
+    );
+}
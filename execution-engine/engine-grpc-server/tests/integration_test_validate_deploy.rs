@@ -0,0 +1,107 @@
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use engine_core::engine_state::{EngineConfig, EngineState};
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::CorrelationId;
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{Deploy, ValidateDeployRequest};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+fn validate_deploy(parent_state_hash: Vec<u8>, deploy: Deploy) -> Vec<(String, String)> {
+    validate_deploy_with_config(Default::default(), parent_state_hash, deploy)
+}
+
+fn validate_deploy_with_config(
+    engine_config: EngineConfig,
+    parent_state_hash: Vec<u8>,
+    deploy: Deploy,
+) -> Vec<(String, String)> {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash.to_vec();
+    let engine_state = EngineState::new(global_state, engine_config);
+
+    let mut validate_deploy_request = ValidateDeployRequest::new();
+    validate_deploy_request.set_parent_state_hash(if parent_state_hash.is_empty() {
+        root_hash
+    } else {
+        parent_state_hash
+    });
+    validate_deploy_request.set_deploy(deploy);
+    validate_deploy_request.set_protocol_version(test_support::get_protocol_version());
+
+    engine_state
+        .validate_deploy(RequestOptions::new(), validate_deploy_request)
+        .wait_drop_metadata()
+        .expect("should validate deploy")
+        .get_problems()
+        .iter()
+        .map(|problem| {
+            (
+                problem.get_field().to_string(),
+                problem.get_message().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn should_find_no_problems_with_valid_deploy() {
+    let problems = validate_deploy(Vec::new(), test_support::get_mock_deploy());
+    assert!(problems.is_empty(), "unexpected problems: {:?}", problems);
+}
+
+#[test]
+fn should_list_every_problem_with_invalid_deploy() {
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_address(vec![1; 31]);
+    deploy.mut_session().set_code(b"\0asm\x02\0\0\0".to_vec());
+
+    let problems = validate_deploy(vec![1; 32], deploy);
+
+    let fields: Vec<&str> = problems.iter().map(|(field, _)| field.as_str()).collect();
+    assert_eq!(
+        fields,
+        vec!["deploy.address", "deploy.session.code", "parent_state_hash"]
+    );
+    assert_eq!(
+        problems[1].1,
+        "malformed module at byte 8: unsupported wasm version 2, expected 1"
+    );
+}
+
+#[test]
+fn should_report_gas_limit_problems_under_the_failing_field() {
+    let engine_config = EngineConfig::new().max_gas_limit(Some(1000));
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_motes_transferred_in_payment(1001);
+    assert_eq!(
+        validate_deploy_with_config(engine_config, Vec::new(), deploy),
+        vec![(
+            "deploy.motes_transferred_in_payment".to_string(),
+            "gas limit 1001 exceeds the maximum of 1000".to_string()
+        )]
+    );
+
+    let mut deploy = test_support::get_mock_deploy();
+    deploy.set_gas_price(0);
+    assert_eq!(
+        validate_deploy(Vec::new(), deploy),
+        vec![(
+            "deploy.gas_price".to_string(),
+            "gas_price must be greater than 0, as no default gas limit is configured".to_string()
+        )]
+    );
+}
@@ -0,0 +1,106 @@
+extern crate contract_ffi;
+extern crate engine_core;
+extern crate engine_shared;
+extern crate engine_storage;
+extern crate grpc;
+
+extern crate casperlabs_engine_grpc_server;
+
+#[allow(unused)]
+mod test_support;
+
+use grpc::RequestOptions;
+
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use engine_core::engine_state::EngineState;
+use engine_shared::init::mocked_account;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
+use engine_storage::global_state::in_memory::InMemoryGlobalState;
+use engine_storage::trie::Trie;
+use engine_storage::trie_store::{Transaction, TransactionSource, TrieStore};
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{VerifyStoreRequest, VerifyStoreResponse};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+
+/// Returns global state holding the mocked account, and its root.
+fn setup() -> (InMemoryGlobalState, Blake2bHash) {
+    let correlation_id = CorrelationId::new();
+    let mocked_account = mocked_account(test_support::MOCKED_ACCOUNT_ADDRESS);
+    let global_state = InMemoryGlobalState::from_pairs(correlation_id, &mocked_account).unwrap();
+    let root_hash = global_state.root_hash;
+    (global_state, root_hash)
+}
+
+fn verify_store(global_state: InMemoryGlobalState, state_hash: Vec<u8>) -> VerifyStoreResponse {
+    let engine_state = EngineState::new(global_state, Default::default());
+
+    let mut verify_store_request = VerifyStoreRequest::new();
+    verify_store_request.set_state_hash(state_hash);
+
+    engine_state
+        .verify_store(RequestOptions::new(), verify_store_request)
+        .wait_drop_metadata()
+        .expect("should verify store")
+}
+
+#[test]
+fn should_verify_intact_state() {
+    let (global_state, root_hash) = setup();
+
+    let response = verify_store(global_state, root_hash.to_vec());
+
+    assert!(
+        response.get_success().get_node_count() > 1,
+        "unexpected response: {:?}",
+        response
+    );
+}
+
+#[test]
+fn should_report_node_stored_at_wrong_hash() {
+    let (global_state, root_hash) = setup();
+    {
+        let mut txn = global_state.environment.create_read_write_txn().unwrap();
+        let empty_node: Trie<Key, Value> = Trie::node(&[]);
+        global_state
+            .store
+            .put(&mut txn, &root_hash, &empty_node)
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    let response = verify_store(global_state, root_hash.to_vec());
+
+    assert!(
+        response.has_corrupted(),
+        "unexpected response: {:?}",
+        response
+    );
+    assert_eq!(
+        response.get_corrupted().get_node_hash(),
+        &root_hash.to_vec()[..]
+    );
+    assert!(response.get_corrupted().get_parent_hash().is_empty());
+}
+
+#[test]
+fn should_report_missing_state() {
+    let (global_state, _) = setup();
+
+    let response = verify_store(global_state, vec![1u8; 32]);
+
+    assert_eq!(response.get_missing_state().get_hash(), &[1u8; 32][..]);
+}
+
+#[test]
+fn should_fail_with_malformed_state_hash() {
+    let (global_state, _) = setup();
+
+    let response = verify_store(global_state, vec![1u8; 31]);
+
+    assert_eq!(
+        response.get_failure(),
+        "expected 32 bytes for state hash, got 31"
+    );
+}
@@ -0,0 +1,179 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// 10 MiB
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// container for log file settings
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LogFileSettings {
+    pub path: PathBuf,
+    /// the current file is rotated once writing a line would grow it past this many bytes
+    pub max_size: u64,
+    /// number of rotated files (`file.1` .. `file.N`) to keep
+    pub max_files: usize,
+}
+
+impl LogFileSettings {
+    pub fn new(path: PathBuf) -> LogFileSettings {
+        LogFileSettings {
+            path,
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+
+    pub fn with_max_size(mut self, max_size: u64) -> LogFileSettings {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_max_files(mut self, max_files: usize) -> LogFileSettings {
+        self.max_files = max_files;
+        self
+    }
+}
+
+/// Appends lines to a file, renaming it to `file.1`, `file.2`, etc. when it grows past the
+/// configured size.  The oldest file beyond `max_files` is discarded.
+pub struct RotatingFileWriter {
+    settings: LogFileSettings,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(settings: LogFileSettings) -> io::Result<RotatingFileWriter> {
+        let file = open_append(&settings.path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFileWriter {
+            settings,
+            file,
+            size,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_size = line.len() as u64 + 1;
+
+        if self.size > 0 && self.size + line_size > self.settings.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.size += line_size;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let path = &self.settings.path;
+
+        if self.settings.max_files == 0 {
+            fs::remove_file(path)?;
+        } else {
+            for index in (1..self.settings.max_files).rev() {
+                let from = rotated_path(path, index);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(path, index + 1))?;
+                }
+            }
+            fs::rename(path, rotated_path(path, 1))?;
+        }
+
+        self.file = open_append(path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Returns `path` with `.index` appended, e.g. `ee.log` -> `ee.log.1`
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut rotated: OsString = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn temp_log_path() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("ee-log-file-tests-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("should create temp dir");
+        dir.push("ee.log");
+        dir
+    }
+
+    #[test]
+    fn should_append_rotated_index() {
+        let path = PathBuf::from("/var/log/ee.log");
+        assert_eq!(rotated_path(&path, 3), PathBuf::from("/var/log/ee.log.3"));
+    }
+
+    #[test]
+    fn should_rotate_when_max_size_exceeded() {
+        let path = temp_log_path();
+        let settings = LogFileSettings::new(path.clone())
+            .with_max_size(8)
+            .with_max_files(2);
+        let mut writer = RotatingFileWriter::new(settings).expect("should open log file");
+
+        for line in &["first", "second", "third", "fourth"] {
+            writer.write_line(line).expect("should write line");
+        }
+        writer.flush().expect("should flush");
+
+        let read = |p: &PathBuf| fs::read_to_string(p).expect("should read log file");
+
+        assert_eq!(read(&path), "fourth\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "third\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "second\n");
+        assert!(
+            !rotated_path(&path, 3).exists(),
+            "oldest file should be discarded"
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).expect("should clean up");
+    }
+
+    #[test]
+    fn should_not_rotate_below_max_size() {
+        let path = temp_log_path();
+        let settings = LogFileSettings::new(path.clone());
+        let mut writer = RotatingFileWriter::new(settings).expect("should open log file");
+
+        writer.write_line("first").expect("should write line");
+        writer.write_line("second").expect("should write line");
+        writer.flush().expect("should flush");
+
+        assert_eq!(
+            fs::read_to_string(&path).expect("should read log file"),
+            "first\nsecond\n"
+        );
+        assert!(!rotated_path(&path, 1).exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).expect("should clean up");
+    }
+}
@@ -44,6 +44,18 @@ impl LogLevel {
         self as u8
     }
 
+    /// inverse of `value`; values between levels map to the next less severe level
+    pub fn from_value(value: u8) -> LogLevel {
+        match value {
+            0 => LogLevel::Fatal,
+            1..=3 => LogLevel::Error,
+            4 => LogLevel::Warning,
+            5 => LogLevel::Info,
+            6 => LogLevel::Metric,
+            _ => LogLevel::Debug,
+        }
+    }
+
     pub fn to_uppercase(self) -> String {
         (format!("{:?}", self)).to_uppercase()
     }
@@ -2,15 +2,27 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
 use serde::Serialize;
+use serde_json::json;
 
+use crate::logging::current_correlation_id;
 use crate::logging::log_level::{LogLevel, LogPriority};
-use crate::logging::log_settings::{HostName, LogSettingsProvider, ProcessId, ProcessName};
+use crate::logging::log_settings::{
+    HostName, LogSettingsProvider, ProcessId, ProcessName, TimestampFormat,
+};
 use crate::semver::SemVer;
 
 const MESSAGE_TYPE: &str = "ee-structured";
+const CORRELATION_ID_KEY: &str = "correlation_id";
+const THREAD_ID_KEY: &str = "thread_id";
+const THREAD_NAME_KEY: &str = "thread_name";
+
+/// Milliseconds since the Unix epoch of the next deterministic timestamp.
+static DETERMINISTIC_MILLIS: AtomicUsize = AtomicUsize::new(0);
 
 /// container for log message data
 #[derive(Clone, Debug, Serialize)]
@@ -24,6 +36,11 @@ pub struct LogMessage {
     pub message_type: MessageType,
     pub message_type_version: SemVer,
     pub message_id: MessageId,
+    pub correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_name: Option<String>,
     pub description: String,
     pub properties: MessageProperties,
 }
@@ -42,12 +59,27 @@ impl LogMessage {
         properties
             .entry(MESSAGE_TEMPLATE_KEY.to_string())
             .or_insert_with(|| message_template.clone());
+        if let Some(correlation_id) = current_correlation_id() {
+            properties
+                .entry(CORRELATION_ID_KEY.to_string())
+                .or_insert_with(|| correlation_id.to_string());
+        }
+        let correlation_id = properties.get(CORRELATION_ID_KEY).cloned();
+        if log_settings_provider.get_include_thread_id() {
+            let thread = thread::current();
+            properties.insert(THREAD_ID_KEY.to_string(), thread_id(&thread));
+            if let Some(thread_name) = thread.name() {
+                properties.insert(THREAD_NAME_KEY.to_string(), thread_name.to_string());
+            }
+        }
+        let thread_id = properties.get(THREAD_ID_KEY).cloned();
+        let thread_name = properties.get(THREAD_NAME_KEY).cloned();
         let message_type = MessageType::new(MESSAGE_TYPE.to_string());
         let message_type_version = SemVer::V1_0_0;
         let process_id = log_settings_provider.get_process_id();
         let process_name = log_settings_provider.get_process_name();
         let host_name = log_settings_provider.get_host_name();
-        let timestamp = TimestampRfc3999::default();
+        let timestamp = TimestampRfc3999::new(log_settings_provider.get_timestamp_format());
         let priority = LogPriority::new(log_level);
         let properties = MessageProperties::new(properties);
         let description = properties.get_formatted_message(&message_template);
@@ -78,6 +110,9 @@ impl LogMessage {
             message_type,
             message_type_version,
             message_id,
+            correlation_id,
+            thread_id,
+            thread_name,
             description,
             properties,
         }
@@ -104,6 +139,41 @@ impl LogMessage {
     }
 }
 
+impl LogMessage {
+    /// Renders the message as a single line JSON object
+    pub fn to_json_line(&self) -> String {
+        let mut line = json!({
+            "timestamp": self.timestamp,
+            "level": self.log_level.to_uppercase(),
+            "priority": self.priority,
+            "process_id": self.process_id,
+            "process_name": self.process_name,
+            "host_name": self.host_name,
+            "message_id": self.message_id,
+            "correlation_id": self.correlation_id,
+            "message": self.description,
+            "properties": self.properties,
+        });
+        // left out unless enabled, to keep lines short
+        if let Some(ref thread_id) = self.thread_id {
+            line["thread_id"] = json!(thread_id);
+        }
+        if let Some(ref thread_name) = self.thread_name {
+            line["thread_name"] = json!(thread_name);
+        }
+        line.to_string()
+    }
+}
+
+/// Returns the number std gives `thread`, which only its `Debug` output exposes.
+fn thread_id(thread: &thread::Thread) -> String {
+    let thread_id = format!("{:?}", thread.id());
+    thread_id
+        .trim_start_matches("ThreadId(")
+        .trim_end_matches(')')
+        .to_string()
+}
+
 impl fmt::Display for LogMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -121,10 +191,31 @@ impl fmt::Display for LogMessage {
 #[derive(Clone, Debug, Hash, Serialize)]
 pub struct TimestampRfc3999(String);
 
+impl TimestampRfc3999 {
+    /// Returns the current time rendered in `timestamp_format`
+    pub fn new(timestamp_format: TimestampFormat) -> Self {
+        match timestamp_format {
+            TimestampFormat::Rfc3339Utc => {
+                let now: DateTime<Utc> = Utc::now();
+                TimestampRfc3999(now.to_rfc3339_opts(SecondsFormat::Millis, true))
+            }
+            TimestampFormat::Rfc3339Local => {
+                let now: DateTime<Local> = Local::now();
+                TimestampRfc3999(now.to_rfc3339_opts(SecondsFormat::Millis, false))
+            }
+            TimestampFormat::Deterministic => {
+                let millis = DETERMINISTIC_MILLIS.fetch_add(1, Ordering::SeqCst) as u64;
+                let synthetic: DateTime<Utc> =
+                    Utc.timestamp((millis / 1000) as i64, (millis % 1000) as u32 * 1_000_000);
+                TimestampRfc3999(synthetic.to_rfc3339_opts(SecondsFormat::Millis, true))
+            }
+        }
+    }
+}
+
 impl Default for TimestampRfc3999 {
     fn default() -> Self {
-        let now: DateTime<Utc> = Utc::now();
-        TimestampRfc3999(now.to_rfc3339_opts(SecondsFormat::Millis, true))
+        TimestampRfc3999::new(TimestampFormat::Rfc3339Utc)
     }
 }
 
@@ -233,6 +324,46 @@ mod tests {
     use crate::logging::log_settings;
     use crate::logging::log_settings::LogLevelFilter;
 
+    #[test]
+    fn should_include_thread_id_and_name_when_enabled() {
+        let log_settings =
+            log_settings::LogSettings::new("test", LogLevelFilter::DEFAULT).with_thread_id(true);
+
+        let message = thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(move || LogMessage::new_msg(&log_settings, LogLevel::Info, "hello".to_string()))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let thread_id = message.thread_id.clone().expect("should have thread id");
+        assert!(
+            thread_id.parse::<u64>().is_ok(),
+            "not a number: {}",
+            thread_id
+        );
+        assert_eq!(message.thread_name, Some("worker".to_string()));
+        let properties = message.properties.value();
+        assert_eq!(properties.get("thread_id"), Some(&thread_id));
+        assert_eq!(properties.get("thread_name"), Some(&"worker".to_string()));
+
+        let line: serde_json::Value = serde_json::from_str(&message.to_json_line()).unwrap();
+        assert_eq!(line["thread_id"], json!(thread_id));
+        assert_eq!(line["thread_name"], json!("worker"));
+    }
+
+    #[test]
+    fn should_leave_out_thread_id_by_default() {
+        let log_settings = log_settings::LogSettings::new("test", LogLevelFilter::DEFAULT);
+
+        let message = LogMessage::new_msg(&log_settings, LogLevel::Info, "hello".to_string());
+
+        assert_eq!(message.thread_id, None);
+        assert_eq!(message.properties.value().get("thread_id"), None);
+        let line: serde_json::Value = serde_json::from_str(&message.to_json_line()).unwrap();
+        assert!(line.get("thread_id").is_none());
+    }
+
     #[test]
     fn should_format_message_template_default_use_case() {
         let mut properties: BTreeMap<String, String> = BTreeMap::new();
@@ -383,6 +514,67 @@ mod tests {
         assert!(should_have_description(&l), "description required");
     }
 
+    #[test]
+    fn should_render_timestamp_in_configured_format() {
+        let utc = TimestampRfc3999::new(TimestampFormat::Rfc3339Utc);
+        assert!(utc.0.ends_with('Z'), "expected UTC timestamp: {}", utc);
+
+        let local = TimestampRfc3999::new(TimestampFormat::Rfc3339Local);
+        let parsed = DateTime::parse_from_rfc3339(&local.0).expect("should parse");
+        assert_eq!(
+            parsed.offset().local_minus_utc(),
+            Local::now().offset().local_minus_utc(),
+            "expected local timestamp: {}",
+            local
+        );
+    }
+
+    #[test]
+    fn should_render_deterministic_timestamps_in_sequence() {
+        let first = TimestampRfc3999::new(TimestampFormat::Deterministic);
+        let second = TimestampRfc3999::new(TimestampFormat::Deterministic);
+
+        let first = DateTime::parse_from_rfc3339(&first.0).expect("should parse");
+        let second = DateTime::parse_from_rfc3339(&second.0).expect("should parse");
+        assert_eq!(
+            first.timestamp(),
+            0,
+            "expected a time near the epoch: {}",
+            first
+        );
+        assert!(second > first, "expected {} to follow {}", second, first);
+    }
+
+    #[test]
+    fn should_render_json_line() {
+        let settings = log_settings::LogSettings::new(
+            "log_message_tests",
+            LogLevelFilter::new(LogLevel::Error),
+        );
+
+        let mut properties: BTreeMap<String, String> = BTreeMap::new();
+        properties.insert("socket".to_string(), "/tmp/ee.sock".to_string());
+
+        let l = super::LogMessage::new_props(
+            &settings,
+            LogLevel::Info,
+            "listening on {socket}".to_owned(),
+            properties,
+        );
+
+        let line = l.to_json_line();
+
+        assert!(!line.contains('\n'), "should be a single line");
+
+        let value: serde_json::Value = serde_json::from_str(&line).expect("should be json");
+
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["process_name"], "log_message_tests");
+        assert_eq!(value["message"], "listening on /tmp/ee.sock");
+        assert_eq!(value["properties"]["socket"], "/tmp/ee.sock");
+        assert_eq!(value["timestamp"], l.timestamp.0.as_str());
+    }
+
     fn should_have_rfc3339_timestamp(l: &super::LogMessage) -> bool {
         // ISO 8601 / RFC 3339
         // rfc3339 = "YYYY-MM-DDTHH:mm:ss+00:00"
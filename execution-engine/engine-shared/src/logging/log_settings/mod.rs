@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
 use std::process;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 
+use crate::logging::log_file::LogFileSettings;
 use crate::logging::log_level::*;
+use crate::logging::syslog::SyslogSettings;
 
 static mut LOG_SETTINGS_PROVIDER: &'static LogSettingsProvider = &NopLogSettingsProvider;
 
@@ -76,12 +79,26 @@ pub(crate) fn get_log_settings_provider() -> &'static LogSettingsProvider {
 /// container for logsettings from the host
 #[derive(Clone, Debug, Serialize)]
 pub struct LogSettings {
-    pub log_level_filter: LogLevelFilter,
+    /// can be replaced while the settings are in use, see `set_log_level_filter`
+    pub log_level_filter: AtomicLogLevelFilter,
     pub process_id: ProcessId,
     /// contains a string identifying the running process
     /// by convention should be a single token without whitespace
     pub process_name: ProcessName,
     pub host_name: HostName,
+    /// when set, log lines are written to this rotating file instead of StdOut
+    pub log_file: Option<LogFileSettings>,
+    /// when set, log lines are sent to syslog instead of StdOut or a log file
+    pub syslog: Option<SyslogSettings>,
+    pub log_format: LogFormat,
+    pub timestamp_format: TimestampFormat,
+    /// when set, log lines are queued and written on a background thread
+    pub async_logging: bool,
+    /// when set, messages carry the id and name of the thread which logged them
+    pub include_thread_id: bool,
+    /// module path prefix -> filter used in place of `log_level_filter` for messages logged from
+    /// modules under that prefix
+    pub module_log_level_filters: BTreeMap<String, LogLevelFilter>,
 }
 
 impl LogSettings {
@@ -93,25 +110,129 @@ impl LogSettings {
     ///       log level will be logged
     pub fn new(process_name: &str, log_level_filter: LogLevelFilter) -> LogSettings {
         LogSettings {
-            log_level_filter,
+            log_level_filter: AtomicLogLevelFilter::new(log_level_filter),
             process_id: ProcessId::new(*PID),
             process_name: ProcessName::new(process_name.to_owned()),
             host_name: HostName::new(HOSTNAME.clone()),
+            log_file: None,
+            syslog: None,
+            log_format: LogFormat::Text,
+            timestamp_format: TimestampFormat::Rfc3339Utc,
+            async_logging: false,
+            include_thread_id: false,
+            module_log_level_filters: BTreeMap::new(),
         }
     }
 
+    /// # Arguments
+    ///
+    /// * `log_format` - Format log lines should be rendered in
+    pub fn with_log_format(mut self, log_format: LogFormat) -> LogSettings {
+        self.log_format = log_format;
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `timestamp_format` - Format and timezone of log line timestamps
+    pub fn with_timestamp_format(mut self, timestamp_format: TimestampFormat) -> LogSettings {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `async_logging` - Whether log lines are written on a background thread
+    pub fn with_async_logging(mut self, async_logging: bool) -> LogSettings {
+        self.async_logging = async_logging;
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `include_thread_id` - Whether messages carry the id and name of the thread logging them
+    pub fn with_thread_id(mut self, include_thread_id: bool) -> LogSettings {
+        self.include_thread_id = include_thread_id;
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `log_file` - Settings of the rotating file log lines should be written to
+    pub fn with_log_file(mut self, log_file: LogFileSettings) -> LogSettings {
+        self.log_file = Some(log_file);
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `syslog` - Facility and ident log lines should be sent to syslog with
+    pub fn with_syslog(mut self, syslog: SyslogSettings) -> LogSettings {
+        self.syslog = Some(syslog);
+        self
+    }
+
+    /// # Arguments
+    ///
+    /// * `module_prefix` - Module path prefix, e.g. `casperlabs_engine_storage::trie_store`
+    /// * `log_level_filter` - Filter for messages logged from modules under `module_prefix`
+    pub fn with_module_log_level_filter(
+        mut self,
+        module_prefix: &str,
+        log_level_filter: LogLevelFilter,
+    ) -> LogSettings {
+        self.module_log_level_filters
+            .insert(module_prefix.to_owned(), log_level_filter);
+        self
+    }
+
+    /// Replaces the filter applied to messages from modules without a filter of their own; takes
+    /// effect for messages logged after it returns, on all threads
+    ///
+    /// # Arguments
+    ///
+    /// * `log_level_filter` - Only log messages with priority >= to this log level will be logged
+    pub fn set_log_level_filter(&self, log_level_filter: LogLevelFilter) {
+        self.log_level_filter.store(log_level_filter)
+    }
+
     /// if lvl is less than settings loglevel, associated msg should be filtered out
     pub fn filter(&self, log_level: LogLevel) -> bool {
-        log_level < self.log_level_filter.0
+        log_level < self.log_level_filter.load().0
     }
+
+    /// like `filter`, but applies the filter of the longest module prefix matching
+    /// `module_path`, if any
+    pub fn filter_module(&self, module_path: &str, log_level: LogLevel) -> bool {
+        let log_level_filter = self
+            .module_log_level_filters
+            .iter()
+            .filter(|(prefix, _)| is_module_prefix(prefix, module_path))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.log_level_filter.load(), |(_, log_level_filter)| {
+                *log_level_filter
+            });
+        log_level < log_level_filter.0
+    }
+}
+
+/// `prefix` matches `module_path` itself and the modules nested in it, but not sibling modules
+/// sharing a name prefix
+fn is_module_prefix(prefix: &str, module_path: &str) -> bool {
+    module_path.starts_with(prefix)
+        && (module_path.len() == prefix.len() || module_path[prefix.len()..].starts_with("::"))
 }
 
 pub trait LogSettingsProvider {
     fn filter(&self, log_level: LogLevel) -> bool;
+    fn filter_module(&self, module_path: &str, log_level: LogLevel) -> bool;
     fn get_process_id(&self) -> ProcessId;
     fn get_process_name(&self) -> ProcessName;
     fn get_host_name(&self) -> HostName;
     fn get_log_level_filter(&self) -> LogLevelFilter;
+    fn get_log_format(&self) -> LogFormat;
+    fn get_timestamp_format(&self) -> TimestampFormat;
+    fn get_include_thread_id(&self) -> bool;
 }
 
 impl LogSettingsProvider for LogSettings {
@@ -119,6 +240,10 @@ impl LogSettingsProvider for LogSettings {
         self.filter(log_level)
     }
 
+    fn filter_module(&self, module_path: &str, log_level: LogLevel) -> bool {
+        self.filter_module(module_path, log_level)
+    }
+
     fn get_process_id(&self) -> ProcessId {
         self.process_id
     }
@@ -132,7 +257,19 @@ impl LogSettingsProvider for LogSettings {
     }
 
     fn get_log_level_filter(&self) -> LogLevelFilter {
-        self.log_level_filter
+        self.log_level_filter.load()
+    }
+
+    fn get_log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    fn get_timestamp_format(&self) -> TimestampFormat {
+        self.timestamp_format
+    }
+
+    fn get_include_thread_id(&self) -> bool {
+        self.include_thread_id
     }
 }
 
@@ -143,6 +280,10 @@ impl LogSettingsProvider for NopLogSettingsProvider {
         true
     }
 
+    fn filter_module(&self, _module_path: &str, _log_level: LogLevel) -> bool {
+        true
+    }
+
     fn get_process_id(&self) -> ProcessId {
         ProcessId::new(-1)
     }
@@ -158,6 +299,18 @@ impl LogSettingsProvider for NopLogSettingsProvider {
     fn get_log_level_filter(&self) -> LogLevelFilter {
         LogLevelFilter::new(LogLevel::Info)
     }
+
+    fn get_log_format(&self) -> LogFormat {
+        LogFormat::Text
+    }
+
+    fn get_timestamp_format(&self) -> TimestampFormat {
+        TimestampFormat::Rfc3339Utc
+    }
+
+    fn get_include_thread_id(&self) -> bool {
+        false
+    }
 }
 
 /// newtype for LogLevel when used to filter out messages of lesser priority
@@ -173,8 +326,7 @@ impl LogLevelFilter {
         LogLevelFilter(log_level)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn as_log_level(self) -> LogLevel {
+    pub fn as_log_level(self) -> LogLevel {
         self.0
     }
 
@@ -211,6 +363,80 @@ impl Into<log::Level> for LogLevelFilter {
     }
 }
 
+/// LogLevelFilter which can be replaced while other threads filter messages with it
+#[derive(Debug)]
+pub struct AtomicLogLevelFilter(AtomicUsize);
+
+impl AtomicLogLevelFilter {
+    pub fn new(log_level_filter: LogLevelFilter) -> AtomicLogLevelFilter {
+        AtomicLogLevelFilter(AtomicUsize::new(log_level_filter.0.value().into()))
+    }
+
+    pub fn load(&self) -> LogLevelFilter {
+        LogLevelFilter(LogLevel::from_value(self.0.load(Ordering::SeqCst) as u8))
+    }
+
+    pub fn store(&self, log_level_filter: LogLevelFilter) {
+        self.0
+            .store(log_level_filter.0.value().into(), Ordering::SeqCst)
+    }
+}
+
+impl Clone for AtomicLogLevelFilter {
+    fn clone(&self) -> Self {
+        AtomicLogLevelFilter::new(self.load())
+    }
+}
+
+impl Serialize for AtomicLogLevelFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.load().serialize(serializer)
+    }
+}
+
+/// rendering of log lines
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize)]
+pub enum LogFormat {
+    /// human readable prefix followed by a JSON payload
+    Text,
+    /// one JSON object per line
+    Json,
+}
+
+impl LogFormat {
+    /// Gets LogFormat
+    pub fn from_input(input: Option<&str>) -> LogFormat {
+        match input {
+            Some("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// rendering of log line timestamps; all variants have millisecond precision
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize)]
+pub enum TimestampFormat {
+    /// RFC 3339 in UTC, e.g. `2019-07-01T12:00:00.000Z`
+    Rfc3339Utc,
+    /// RFC 3339 in the local timezone, with its offset, e.g. `2019-07-01T14:00:00.000+02:00`
+    Rfc3339Local,
+    /// RFC 3339 in UTC, but of a synthetic clock which starts at the Unix epoch and moves on by a
+    /// millisecond with every message rather than of the wall clock, e.g.
+    /// `1970-01-01T00:00:00.000Z`, then `1970-01-01T00:00:00.001Z`.  Makes log output
+    /// reproducible for tests; not for production use.
+    Deterministic,
+}
+
+impl TimestampFormat {
+    /// Gets TimestampFormat
+    pub fn from_input(input: Option<&str>) -> TimestampFormat {
+        match input {
+            Some("rfc3339-local") => TimestampFormat::Rfc3339Local,
+            _ => TimestampFormat::Rfc3339Utc,
+        }
+    }
+}
+
 /// newtype to encapsulate process_id / PID
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize)]
 pub struct ProcessId(i32);
@@ -285,6 +511,36 @@ mod tests {
         assert!(host_name.len() > 0, "host_name should have chars")
     }
 
+    #[test]
+    fn should_apply_most_specific_module_filter() {
+        let log_settings = LogSettings::new("test", LogLevelFilter::new(LogLevel::Info))
+            .with_module_log_level_filter("storage", LogLevelFilter::new(LogLevel::Error))
+            .with_module_log_level_filter("storage::trie", LogLevelFilter::new(LogLevel::Debug));
+
+        assert!(!log_settings.filter_module("storage::trie::ops", LogLevel::Debug));
+        assert!(log_settings.filter_module("storage::global", LogLevel::Info));
+        assert!(!log_settings.filter_module("storage", LogLevel::Error));
+        assert!(!log_settings.filter_module("storage_extra", LogLevel::Info));
+        assert!(log_settings.filter_module("core", LogLevel::Debug));
+    }
+
+    #[test]
+    fn should_filter_with_replaced_log_level_filter() {
+        let log_settings = LogSettings::new("test", LogLevelFilter::new(LogLevel::Info))
+            .with_module_log_level_filter("storage", LogLevelFilter::new(LogLevel::Error));
+        assert!(log_settings.filter(LogLevel::Debug));
+
+        log_settings.set_log_level_filter(LogLevelFilter::new(LogLevel::Debug));
+
+        assert!(!log_settings.filter(LogLevel::Debug));
+        assert!(!log_settings.filter_module("core", LogLevel::Debug));
+        assert!(log_settings.filter_module("storage", LogLevel::Info));
+        assert_eq!(
+            log_settings.get_log_level_filter(),
+            LogLevelFilter::new(LogLevel::Debug)
+        );
+    }
+
     #[test]
     fn should_get_process_id() {
         let pid = *super::PID;
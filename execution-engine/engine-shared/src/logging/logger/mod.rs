@@ -1,10 +1,17 @@
-use std::collections::BTreeMap;
-use std::sync::{Mutex, Once};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use log::{Metadata, Record};
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::logging::log_file::{LogFileSettings, RotatingFileWriter};
+use crate::logging::syslog::{SyslogLogger, SyslogSettings};
+
 pub(crate) static LOGGER_INIT: Once = Once::new();
 pub(crate) const LOG_MAX_LEVEL: log::LevelFilter = log::LevelFilter::Trace;
 pub(crate) const LOGGER_EXPECT: &str = "Logger should be set";
@@ -12,6 +19,19 @@ pub(crate) const LOGGER_EXPECT: &str = "Logger should be set";
 /// log lines are written to StdOut
 pub(crate) static TERMINAL_LOGGER: TerminalLogger = TerminalLogger;
 
+/// log lines queued by the async logger before the lowest-priority ones are dropped
+pub const ASYNC_LOG_QUEUE_CAPACITY: usize = 8192;
+const ASYNC_LOGGER_THREAD_NAME: &str = "async-logger";
+/// longest a flush waits for the async logger to write out its queue
+const ASYNC_LOG_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+static DROPPED_LOG_RECORDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of log lines dropped because the async logger's queue was full
+pub fn dropped_log_records() -> usize {
+    DROPPED_LOG_RECORDS.load(Ordering::Relaxed)
+}
+
 pub struct BufferedLogger {
     queue: Mutex<BTreeMap<String, LogLineItem>>,
 }
@@ -75,6 +95,227 @@ impl log::Log for TerminalLogger {
     fn flush(&self) {}
 }
 
+/// log lines are appended to a rotating log file
+pub struct FileLogger {
+    writer: Mutex<RotatingFileWriter>,
+}
+
+impl log::Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata
+            .target()
+            .starts_with("casperlabs_engine_shared::logging")
+            && metadata.level() <= log::Level::Trace
+    }
+
+    fn log(&self, record: &Record) {
+        let metadata = record.metadata();
+
+        // self.enabled caused 'unresolved ref' error
+        if Self::enabled(&self, metadata) {
+            let line = format!("{}", record.args());
+            // the lock serializes writers so lines from different threads don't interleave
+            if let Ok(mut writer) = self.writer.lock() {
+                let _ = writer.write_line(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// a log line waiting to be written by the async logger
+struct QueuedLine {
+    level: log::Level,
+    target: String,
+    line: String,
+}
+
+/// number of log levels, `Error` to `Trace`
+const LOG_LEVEL_COUNT: usize = 5;
+
+/// bounded queue of log lines waiting to be written by the async logger.  Lines are kept in one
+/// queue per level, so that the lowest-priority line to drop is found without scanning the queue.
+struct LogQueue {
+    /// queued lines of each level, `Error` first, with the sequence numbers they were queued under
+    levels: [VecDeque<(u64, QueuedLine)>; LOG_LEVEL_COUNT],
+    len: usize,
+    capacity: usize,
+    /// sequence number of the next line queued, so that lines of all levels are written in the
+    /// order they were queued
+    next_seq: u64,
+    /// set while the background thread writes a line taken off the queue
+    writing: bool,
+}
+
+/// index of the queue holding lines of `level`; log::Level numbers levels from 1 for `Error`
+fn level_index(level: log::Level) -> usize {
+    level as usize - 1
+}
+
+impl LogQueue {
+    fn new(capacity: usize) -> LogQueue {
+        LogQueue {
+            levels: Default::default(),
+            len: 0,
+            capacity,
+            next_seq: 0,
+            writing: false,
+        }
+    }
+
+    fn push_back(&mut self, queued_line: QueuedLine) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.levels[level_index(queued_line.level)].push_back((seq, queued_line));
+        self.len += 1;
+    }
+
+    /// Queues `line`.  If the queue is full, whichever of `line` and the lowest-priority queued
+    /// line has the lower priority is dropped, `line` on a tie.  Returns whether a line was
+    /// dropped.
+    fn push(&mut self, queued_line: QueuedLine) -> bool {
+        if self.len < self.capacity {
+            self.push_back(queued_line);
+            return false;
+        }
+
+        // the most verbose level below that of `line` with lines queued
+        let lowest = (level_index(queued_line.level) + 1..LOG_LEVEL_COUNT)
+            .rev()
+            .find(|&index| !self.levels[index].is_empty());
+
+        if let Some(index) = lowest {
+            self.levels[index].pop_back();
+            self.len -= 1;
+            self.push_back(queued_line);
+        }
+
+        true
+    }
+
+    /// Takes the earliest queued line off the queue.
+    fn pop_front(&mut self) -> Option<QueuedLine> {
+        let (_, index) = self
+            .levels
+            .iter()
+            .enumerate()
+            .filter_map(|(index, lines)| lines.front().map(|(seq, _)| (*seq, index)))
+            .min()?;
+        self.len -= 1;
+        self.levels[index]
+            .pop_front()
+            .map(|(_, queued_line)| queued_line)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.len == 0 && !self.writing
+    }
+}
+
+/// log lines are queued and written by `inner` on a background thread, so that logging does
+/// not block on output
+pub struct AsyncLogger {
+    inner: &'static dyn log::Log,
+    queue: Mutex<LogQueue>,
+    /// signalled when a line is queued
+    queued: Condvar,
+    /// signalled when the queue has been written out
+    idle: Condvar,
+}
+
+impl AsyncLogger {
+    /// Starts the background thread writing lines with `inner`, queueing up to `capacity` lines
+    pub fn start(
+        inner: &'static dyn log::Log,
+        capacity: usize,
+    ) -> io::Result<&'static AsyncLogger> {
+        let async_logger: &'static AsyncLogger = Box::leak(Box::new(AsyncLogger {
+            inner,
+            queue: Mutex::new(LogQueue::new(capacity)),
+            queued: Condvar::new(),
+            idle: Condvar::new(),
+        }));
+        thread::Builder::new()
+            .name(ASYNC_LOGGER_THREAD_NAME.to_owned())
+            .spawn(move || async_logger.run())?;
+        Ok(async_logger)
+    }
+
+    fn run(&self) {
+        while let Some(queued) = self.next_line() {
+            self.inner.log(
+                &Record::builder()
+                    .args(format_args!("{}", queued.line))
+                    .level(queued.level)
+                    .target(&queued.target)
+                    .build(),
+            );
+        }
+    }
+
+    /// Blocks until a line is queued and takes it off the queue; `None` if the lock is poisoned
+    fn next_line(&self) -> Option<QueuedLine> {
+        let mut queue = self.queue.lock().ok()?;
+        queue.writing = false;
+        loop {
+            if let Some(entry) = queue.pop_front() {
+                queue.writing = true;
+                return Some(entry);
+            }
+            self.idle.notify_all();
+            queue = self.queued.wait(queue).ok()?;
+        }
+    }
+}
+
+impl log::Log for AsyncLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.enabled(record.metadata()) {
+            let queued_line = QueuedLine {
+                level: record.level(),
+                target: record.target().to_owned(),
+                line: format!("{}", record.args()),
+            };
+            if let Ok(mut queue) = self.queue.lock() {
+                if queue.push(queued_line) {
+                    DROPPED_LOG_RECORDS.fetch_add(1, Ordering::Relaxed);
+                }
+                self.queued.notify_one();
+            }
+        }
+    }
+
+    /// Waits, for up to [`ASYNC_LOG_FLUSH_TIMEOUT`], until queued lines are written
+    fn flush(&self) {
+        // the background thread would wait for itself, e.g. when a write on it panics
+        if thread::current().name() != Some(ASYNC_LOGGER_THREAD_NAME) {
+            let deadline = Instant::now() + ASYNC_LOG_FLUSH_TIMEOUT;
+            if let Ok(mut queue) = self.queue.lock() {
+                while !queue.is_idle() {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    queue = match self.idle.wait_timeout(queue, deadline - now) {
+                        Ok((queue, _)) => queue,
+                        Err(_) => break,
+                    };
+                }
+            }
+        }
+        self.inner.flush();
+    }
+}
+
 pub trait LogBufferProvider {
     fn push(&self, line: LogLineItem);
     fn extract(&self, message_id: &str) -> Option<LogLineItem>;
@@ -200,6 +441,44 @@ pub fn initialize_terminal_logger() {
     });
 }
 
+/// opens a rotating file logger
+pub fn get_file_logger(log_file_settings: LogFileSettings) -> io::Result<&'static FileLogger> {
+    let writer = RotatingFileWriter::new(log_file_settings)?;
+    Ok(Box::leak(Box::new(FileLogger {
+        writer: Mutex::new(writer),
+    })))
+}
+
+/// set rotating file logger as application logger
+pub fn initialize_file_logger(log_file_settings: LogFileSettings) -> io::Result<()> {
+    let file_logger = get_file_logger(log_file_settings)?;
+    LOGGER_INIT.call_once(|| {
+        log::set_logger(file_logger).expect(LOGGER_EXPECT);
+        log::set_max_level(LOG_MAX_LEVEL);
+    });
+    Ok(())
+}
+
+/// set syslog logger as application logger
+pub fn initialize_syslog_logger(syslog_settings: &SyslogSettings) {
+    LOGGER_INIT.call_once(|| {
+        let syslog_logger: &'static SyslogLogger =
+            Box::leak(Box::new(SyslogLogger::open(syslog_settings)));
+        log::set_logger(syslog_logger).expect(LOGGER_EXPECT);
+        log::set_max_level(LOG_MAX_LEVEL);
+    });
+}
+
+/// set an async logger writing with `inner` as application logger
+pub fn initialize_async_logger(inner: &'static dyn log::Log) -> io::Result<()> {
+    let async_logger = AsyncLogger::start(inner, ASYNC_LOG_QUEUE_CAPACITY)?;
+    LOGGER_INIT.call_once(|| {
+        log::set_logger(async_logger).expect(LOGGER_EXPECT);
+        log::set_max_level(LOG_MAX_LEVEL);
+    });
+    Ok(())
+}
+
 /// set buffered logger as application logger
 pub fn initialize_buffered_logger() {
     LOGGER_INIT.call_once(|| {
@@ -234,6 +513,92 @@ mod tests {
         static ref LOG_SETTINGS_TESTS: LogSettings = get_log_settings(PROC_NAME);
     }
 
+    fn push_levels(queue: &mut LogQueue, levels: &[log::Level]) -> usize {
+        levels
+            .iter()
+            .filter(|level| {
+                queue.push(QueuedLine {
+                    level: **level,
+                    target: module_path!().to_owned(),
+                    line: level.to_string(),
+                })
+            })
+            .count()
+    }
+
+    fn queued_levels(queue: &LogQueue) -> Vec<log::Level> {
+        let mut queued: Vec<&(u64, QueuedLine)> = queue.levels.iter().flatten().collect();
+        queued.sort_by_key(|(seq, _)| *seq);
+        queued
+            .into_iter()
+            .map(|(_, queued_line)| queued_line.level)
+            .collect()
+    }
+
+    #[test]
+    fn should_drop_lowest_priority_lines_when_queue_is_full() {
+        let mut queue = LogQueue::new(3);
+
+        let dropped = push_levels(
+            &mut queue,
+            &[log::Level::Info, log::Level::Trace, log::Level::Error],
+        );
+        assert_eq!(dropped, 0);
+
+        // the queued metric line makes room for the warning
+        let dropped = push_levels(&mut queue, &[log::Level::Warn]);
+        assert_eq!(dropped, 1);
+        assert_eq!(
+            queued_levels(&queue),
+            vec![log::Level::Info, log::Level::Error, log::Level::Warn]
+        );
+
+        // nothing queued has lower priority than another info line
+        let dropped = push_levels(&mut queue, &[log::Level::Info, log::Level::Debug]);
+        assert_eq!(dropped, 2);
+        assert_eq!(
+            queued_levels(&queue),
+            vec![log::Level::Info, log::Level::Error, log::Level::Warn]
+        );
+    }
+
+    /// keeps written lines in memory
+    struct LineCollector(Mutex<Vec<String>>);
+
+    impl log::Log for LineCollector {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn should_write_queued_lines_on_flush() {
+        let inner: &'static LineCollector = Box::leak(Box::new(LineCollector(Mutex::new(vec![]))));
+        let async_logger =
+            AsyncLogger::start(inner, ASYNC_LOG_QUEUE_CAPACITY).expect("should start");
+
+        for index in 0..100 {
+            log::Log::log(
+                async_logger,
+                &Record::builder()
+                    .args(format_args!("line {}", index))
+                    .level(log::Level::Info)
+                    .build(),
+            );
+        }
+        log::Log::flush(async_logger);
+
+        let lines = inner.0.lock().unwrap();
+        assert_eq!(lines.len(), 100);
+        assert_eq!(lines[99], "line 99");
+    }
+
     #[test]
     #[ignore]
     fn should_log_structured_message() {
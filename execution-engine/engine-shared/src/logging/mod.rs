@@ -1,50 +1,167 @@
+use std::cell::Cell;
 use std::collections::btree_map::BTreeMap;
+use std::io;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::logging::log_level::LogLevel;
 use crate::logging::log_message::{LogMessage, MessageId};
-use crate::logging::logger::initialize_terminal_logger;
+use crate::logging::log_settings::{LogFormat, LogSettings};
+use crate::logging::logger::{
+    get_file_logger, initialize_async_logger, initialize_file_logger, initialize_syslog_logger,
+    initialize_terminal_logger, TERMINAL_LOGGER,
+};
+use crate::logging::syslog::{SyslogLogger, FATAL_TARGET};
 use crate::newtypes::CorrelationId;
 use crate::utils::jsonify;
 
+pub mod log_file;
 pub mod log_level;
 pub mod log_message;
 pub mod log_settings;
 #[macro_use]
 pub mod logger;
+pub mod syslog;
 
 #[cfg(test)]
 mod tests;
 
 pub const GAUGE: &str = "gauge";
 
+thread_local! {
+    static CORRELATION_ID: Cell<Option<CorrelationId>> = Cell::new(None);
+}
+
+/// Restores the correlation id messages were tagged with before [`set_correlation_id`] when
+/// dropped.
+#[must_use]
+pub struct CorrelationIdGuard {
+    previous: Option<CorrelationId>,
+}
+
+impl Drop for CorrelationIdGuard {
+    fn drop(&mut self) {
+        CORRELATION_ID.with(|correlation_id| correlation_id.set(self.previous));
+    }
+}
+
+/// Tags every message logged on the current thread with `correlation_id`, under the
+/// `correlation_id` property, until the returned guard is dropped.  Messages logged with a
+/// correlation id of their own, such as metrics, keep it.
+pub fn set_correlation_id(correlation_id: CorrelationId) -> CorrelationIdGuard {
+    let previous = CORRELATION_ID.with(|current| current.replace(Some(correlation_id)));
+    CorrelationIdGuard { previous }
+}
+
+/// Returns the correlation id messages logged on the current thread are tagged with, if any.
+pub fn current_correlation_id() -> Option<CorrelationId> {
+    CORRELATION_ID.with(Cell::get)
+}
+
+pub use crate::logging::logger::dropped_log_records;
+
+/// Sets the application logger; the syslog logger if `log_settings` carries syslog settings, a
+/// rotating file logger if it carries a log file, otherwise the terminal logger.  With async
+/// logging, lines are handed to that logger on a background thread.
+///
+/// Should be called once at startup, before anything is logged.
+pub fn initialize_logger(log_settings: &LogSettings) -> io::Result<()> {
+    if let Some(ref syslog) = log_settings.syslog {
+        if log_settings.async_logging {
+            let syslog_logger: &'static SyslogLogger =
+                Box::leak(Box::new(SyslogLogger::open(syslog)));
+            return initialize_async_logger(syslog_logger);
+        }
+        initialize_syslog_logger(syslog);
+        return Ok(());
+    }
+
+    if log_settings.async_logging {
+        return match log_settings.log_file {
+            Some(ref log_file) => initialize_async_logger(get_file_logger(log_file.clone())?),
+            None => initialize_async_logger(&TERMINAL_LOGGER),
+        };
+    }
+
+    match log_settings.log_file {
+        Some(ref log_file) => initialize_file_logger(log_file.clone()),
+        None => {
+            initialize_terminal_logger();
+            Ok(())
+        }
+    }
+}
+
+/// Flushes the application logger.  With async logging, waits for queued lines to be written,
+/// so should be called before the process exits.
+pub fn flush() {
+    log::logger().flush()
+}
+
+/// Hands `log_message` to the application logger, rendered in `log_format`
+#[inline]
+fn emit(log_format: LogFormat, log_message: &LogMessage) {
+    // fatal messages get a target of their own, as `log` has no level above error
+    let target = match log_message.log_level {
+        LogLevel::Fatal => FATAL_TARGET,
+        _ => module_path!(),
+    };
+
+    match log_format {
+        LogFormat::Text => {
+            let json = jsonify(log_message, false);
+
+            log::log!(
+                target: target,
+                log_message.log_level.into(),
+                "{timestamp} {loglevel} {priority} {hostname} {facility} payload={payload}",
+                timestamp = log_message.timestamp,
+                loglevel = log_message.log_level.to_uppercase(),
+                priority = log_message.priority.value(),
+                hostname = log_message.host_name.value(),
+                facility = log_message.process_name.value(),
+                payload = json
+            );
+        }
+        LogFormat::Json => {
+            log::log!(
+                target: target,
+                log_message.log_level.into(),
+                "{}",
+                log_message.to_json_line()
+            );
+        }
+    }
+}
+
 /// # Arguments
 ///
 /// * `log_level` - log level of the message to be logged
 /// * `log_message` - the message to be logged
 #[inline]
 pub fn log(log_level: LogLevel, log_message: &str) -> Option<MessageId> {
+    log_from("", log_level, log_message)
+}
+
+/// Like `log`, but filtered by the level configured for `module_path`, if any.  Usually called
+/// through the `log_*!` macros, which pass the caller's `module_path!()`.
+///
+/// # Arguments
+///
+/// * `module_path` - module path of the caller
+/// * `log_level` - log level of the message to be logged
+/// * `log_message` - the message to be logged
+#[inline]
+pub fn log_from(module_path: &str, log_level: LogLevel, log_message: &str) -> Option<MessageId> {
     initialize_terminal_logger();
     let log_settings_provider = log_settings::get_log_settings_provider();
 
-    if log_settings_provider.filter(log_level) {
+    if log_settings_provider.filter_module(module_path, log_level) {
         return None;
     }
 
     let log_message = LogMessage::new_msg(log_settings_provider, log_level, log_message.to_owned());
 
-    let json = jsonify(&log_message, false);
-
-    log::log!(
-        log_level.into(),
-        "{timestamp} {loglevel} {priority} {hostname} {facility} payload={payload}",
-        timestamp = log_message.timestamp,
-        loglevel = log_message.log_level.to_uppercase(),
-        priority = log_message.priority.value(),
-        hostname = log_message.host_name.value(),
-        facility = log_message.process_name.value(),
-        payload = json
-    );
+    emit(log_settings_provider.get_log_format(), &log_message);
 
     Some(log_message.message_id)
 }
@@ -59,11 +176,27 @@ pub fn log_details(
     log_level: LogLevel,
     message_format: String,
     properties: BTreeMap<String, String>,
+) -> Option<MessageId> {
+    log_details_from("", log_level, message_format, properties)
+}
+
+/// # Arguments
+///
+/// * `module_path` - module path of the caller
+/// * `log_level` - log level of the message to be logged
+/// * `message_format` - a message template to apply over properties by key
+/// * `properties` - a collection of machine readable key / value properties which will be logged
+#[inline]
+pub fn log_details_from(
+    module_path: &str,
+    log_level: LogLevel,
+    message_format: String,
+    properties: BTreeMap<String, String>,
 ) -> Option<MessageId> {
     initialize_terminal_logger();
     let log_settings_provider = log_settings::get_log_settings_provider();
 
-    if log_settings_provider.filter(log_level) {
+    if log_settings_provider.filter_module(module_path, log_level) {
         return None;
     }
 
@@ -74,18 +207,7 @@ pub fn log_details(
         properties.to_owned(),
     );
 
-    let json = jsonify(&log_message, false);
-
-    log::log!(
-        log_level.into(),
-        "{timestamp} {loglevel} {priority} {hostname} {facility} payload={payload}",
-        timestamp = log_message.timestamp,
-        loglevel = log_message.log_level.to_uppercase(),
-        priority = log_message.priority.value(),
-        hostname = log_message.host_name.value(),
-        facility = log_message.process_name.value(),
-        payload = json
-    );
+    emit(log_settings_provider.get_log_format(), &log_message);
 
     Some(log_message.message_id)
 }
@@ -102,11 +224,30 @@ pub fn log_duration(
     metric: &str,
     tag: &str,
     duration: Duration,
+) -> Option<MessageId> {
+    log_duration_from("", correlation_id, metric, tag, duration)
+}
+
+/// # Arguments
+///
+/// * `module_path` - module path of the caller
+/// * `correlation_id` - a shared identifier used to group metrics
+/// * `metric` - the name of the metric
+/// * `tag` - a grouping tag for the metric
+/// * `duration` - in seconds
+#[inline]
+pub fn log_duration_from(
+    module_path: &str,
+    correlation_id: CorrelationId,
+    metric: &str,
+    tag: &str,
+    duration: Duration,
 ) -> Option<MessageId> {
     initialize_terminal_logger();
     let duration_in_seconds: f64 = duration.as_float_secs();
 
-    log_metric(
+    log_metric_from(
+        module_path,
         correlation_id,
         metric,
         tag,
@@ -129,13 +270,33 @@ pub fn log_metric(
     tag: &str,
     metric_key: &str,
     metric_value: f64,
+) -> Option<MessageId> {
+    log_metric_from("", correlation_id, metric, tag, metric_key, metric_value)
+}
+
+/// # Arguments
+///
+/// * `module_path` - module path of the caller
+/// * `correlation_id` - a shared identifier used to group metrics
+/// * `metric` - the name of the metric
+/// * `tag` - a grouping tag for the metric
+/// * `metric_key` - property key for metric's value
+/// * `metric_value` - numeric value of metric
+#[inline]
+pub fn log_metric_from(
+    module_path: &str,
+    correlation_id: CorrelationId,
+    metric: &str,
+    tag: &str,
+    metric_key: &str,
+    metric_value: f64,
 ) -> Option<MessageId> {
     initialize_terminal_logger();
     let log_settings_provider = log_settings::get_log_settings_provider();
 
     const METRIC_LOG_LEVEL: LogLevel = LogLevel::Metric;
 
-    if log_settings_provider.filter(METRIC_LOG_LEVEL) {
+    if log_settings_provider.filter_module(module_path, METRIC_LOG_LEVEL) {
         return None;
     }
 
@@ -166,7 +327,7 @@ pub fn log_metric(
 
     let message_format = String::from("{message}");
 
-    log_details(METRIC_LOG_LEVEL, message_format, properties)
+    log_details_from(module_path, METRIC_LOG_LEVEL, message_format, properties)
 }
 
 /// # Arguments
@@ -207,3 +368,97 @@ pub fn log_info(log_message: &str) -> Option<MessageId> {
 pub fn log_debug(log_message: &str) -> Option<MessageId> {
     log(LogLevel::Debug, log_message)
 }
+
+/// Like `log_fatal`, filtered by the level configured for the calling module.  The other level
+/// macros below follow the same pattern.
+#[macro_export]
+macro_rules! log_fatal {
+    ($message:expr $(,)?) => {
+        $crate::logging::log_from(
+            module_path!(),
+            $crate::logging::log_level::LogLevel::Fatal,
+            $message,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($message:expr $(,)?) => {
+        $crate::logging::log_from(
+            module_path!(),
+            $crate::logging::log_level::LogLevel::Error,
+            $message,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_warning {
+    ($message:expr $(,)?) => {
+        $crate::logging::log_from(
+            module_path!(),
+            $crate::logging::log_level::LogLevel::Warning,
+            $message,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($message:expr $(,)?) => {
+        $crate::logging::log_from(
+            module_path!(),
+            $crate::logging::log_level::LogLevel::Info,
+            $message,
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($message:expr $(,)?) => {
+        $crate::logging::log_from(
+            module_path!(),
+            $crate::logging::log_level::LogLevel::Debug,
+            $message,
+        )
+    };
+}
+
+/// Like `log_details`, filtered by the level configured for the calling module.
+#[macro_export]
+macro_rules! log_details {
+    ($log_level:expr, $message_format:expr, $properties:expr $(,)?) => {
+        $crate::logging::log_details_from(module_path!(), $log_level, $message_format, $properties)
+    };
+}
+
+/// Like `log_duration`, filtered by the level configured for the calling module.
+#[macro_export]
+macro_rules! log_duration {
+    ($correlation_id:expr, $metric:expr, $tag:expr, $duration:expr $(,)?) => {
+        $crate::logging::log_duration_from(
+            module_path!(),
+            $correlation_id,
+            $metric,
+            $tag,
+            $duration,
+        )
+    };
+}
+
+/// Like `log_metric`, filtered by the level configured for the calling module.
+#[macro_export]
+macro_rules! log_metric {
+    ($correlation_id:expr, $metric:expr, $tag:expr, $metric_key:expr, $metric_value:expr $(,)?) => {
+        $crate::logging::log_metric_from(
+            module_path!(),
+            $correlation_id,
+            $metric,
+            $tag,
+            $metric_key,
+            $metric_value,
+        )
+    };
+}
@@ -0,0 +1,164 @@
+use std::ffi::CString;
+use std::os::raw::c_int;
+
+use log::{Metadata, Record};
+use serde::Serialize;
+
+/// target of records logged at [`LogLevel::Fatal`](crate::logging::log_level::LogLevel::Fatal),
+/// which the `log` crate otherwise can't tell apart from errors
+pub(crate) const FATAL_TARGET: &str = "casperlabs_engine_shared::logging::fatal";
+
+/// syslog facility log lines are sent with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum SyslogFacility {
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl SyslogFacility {
+    /// Gets SyslogFacility by its lowercase name, e.g. `daemon` or `local0`
+    pub fn from_input(input: &str) -> Option<SyslogFacility> {
+        let facility = match input {
+            "user" => SyslogFacility::User,
+            "daemon" => SyslogFacility::Daemon,
+            "local0" => SyslogFacility::Local0,
+            "local1" => SyslogFacility::Local1,
+            "local2" => SyslogFacility::Local2,
+            "local3" => SyslogFacility::Local3,
+            "local4" => SyslogFacility::Local4,
+            "local5" => SyslogFacility::Local5,
+            "local6" => SyslogFacility::Local6,
+            "local7" => SyslogFacility::Local7,
+            _ => return None,
+        };
+        Some(facility)
+    }
+
+    fn value(self) -> c_int {
+        match self {
+            SyslogFacility::User => libc::LOG_USER,
+            SyslogFacility::Daemon => libc::LOG_DAEMON,
+            SyslogFacility::Local0 => libc::LOG_LOCAL0,
+            SyslogFacility::Local1 => libc::LOG_LOCAL1,
+            SyslogFacility::Local2 => libc::LOG_LOCAL2,
+            SyslogFacility::Local3 => libc::LOG_LOCAL3,
+            SyslogFacility::Local4 => libc::LOG_LOCAL4,
+            SyslogFacility::Local5 => libc::LOG_LOCAL5,
+            SyslogFacility::Local6 => libc::LOG_LOCAL6,
+            SyslogFacility::Local7 => libc::LOG_LOCAL7,
+        }
+    }
+}
+
+/// container for syslog settings
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SyslogSettings {
+    pub facility: SyslogFacility,
+    /// prefixed to every message, by convention the name of the process
+    pub ident: String,
+}
+
+impl SyslogSettings {
+    pub fn new(ident: String) -> SyslogSettings {
+        SyslogSettings {
+            facility: SyslogFacility::User,
+            ident,
+        }
+    }
+
+    pub fn with_facility(mut self, facility: SyslogFacility) -> SyslogSettings {
+        self.facility = facility;
+        self
+    }
+}
+
+/// log lines are sent to the local syslog daemon
+pub struct SyslogLogger;
+
+impl SyslogLogger {
+    /// Opens the connection to syslog; should only be done once per process, as `openlog`
+    /// settings are global
+    pub fn open(settings: &SyslogSettings) -> SyslogLogger {
+        // openlog keeps the pointer, so the ident must live as long as the process
+        let ident = CString::new(settings.ident.replace('\0', "")).unwrap_or_default();
+        let ident: &'static CString = Box::leak(Box::new(ident));
+        unsafe { libc::openlog(ident.as_ptr(), libc::LOG_PID, settings.facility.value()) };
+        SyslogLogger
+    }
+}
+
+impl log::Log for SyslogLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata
+            .target()
+            .starts_with("casperlabs_engine_shared::logging")
+            && metadata.level() <= log::Level::Trace
+    }
+
+    fn log(&self, record: &Record) {
+        let metadata = record.metadata();
+
+        if Self::enabled(&self, metadata) {
+            let severity = severity(record.level(), record.target());
+            let line = format!("{}", record.args()).replace('\0', "");
+            if let Ok(line) = CString::new(line) {
+                // the line is passed as an argument so that `%` in it isn't read as a format
+                unsafe { libc::syslog(severity, b"%s\0".as_ptr() as *const _, line.as_ptr()) };
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Maps the level of a record to a syslog severity, following the mapping of
+/// [`LogLevel`](crate::logging::log_level::LogLevel), except that fatal messages are logged as
+/// critical rather than emergencies, which syslog broadcasts to every terminal
+fn severity(level: log::Level, target: &str) -> c_int {
+    match level {
+        log::Level::Error if target == FATAL_TARGET => libc::LOG_CRIT,
+        log::Level::Error => libc::LOG_ERR,
+        log::Level::Warn => libc::LOG_WARNING,
+        log::Level::Info => libc::LOG_NOTICE,
+        // metrics
+        log::Level::Trace => libc::LOG_INFO,
+        log::Level::Debug => libc::LOG_DEBUG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_levels_to_syslog_severities() {
+        let target = "casperlabs_engine_shared::logging";
+        assert_eq!(severity(log::Level::Error, FATAL_TARGET), libc::LOG_CRIT);
+        assert_eq!(severity(log::Level::Error, target), libc::LOG_ERR);
+        assert_eq!(severity(log::Level::Warn, target), libc::LOG_WARNING);
+        assert_eq!(severity(log::Level::Info, target), libc::LOG_NOTICE);
+        assert_eq!(severity(log::Level::Trace, target), libc::LOG_INFO);
+        assert_eq!(severity(log::Level::Debug, target), libc::LOG_DEBUG);
+    }
+
+    #[test]
+    fn should_parse_facility_names() {
+        assert_eq!(
+            SyslogFacility::from_input("daemon"),
+            Some(SyslogFacility::Daemon)
+        );
+        assert_eq!(
+            SyslogFacility::from_input("local7"),
+            Some(SyslogFacility::Local7)
+        );
+        assert_eq!(SyslogFacility::from_input("kern"), None);
+    }
+}
@@ -117,6 +117,29 @@ fn log_duration_should_log_correlation_id() {
     }
 }
 
+#[test]
+fn should_tag_messages_with_current_correlation_id() {
+    setup();
+
+    let correlation_id = CorrelationId::new();
+
+    let message_id = {
+        let _guard = set_correlation_id(correlation_id);
+        log(LogLevel::Info, "logged while handling a request").expect("expected message_id")
+    };
+    assert_eq!(current_correlation_id(), None);
+
+    let message = logger::BUFFERED_LOGGER
+        .extract(&message_id.value())
+        .expect("expected message");
+
+    assert_eq!(
+        message.properties.get(&"correlation_id".to_string()),
+        Some(&correlation_id.to_string()),
+        "correlation_id should match"
+    );
+}
+
 #[test]
 fn should_log_when_level_at_or_above_filter() {
     setup();
@@ -227,7 +250,7 @@ fn should_set_and_get_log_settings_provider() {
         );
 
         assert_eq!(
-            &log_settings.log_level_filter,
+            &log_settings.log_level_filter.load(),
             &log_settings_provider.get_log_level_filter(),
             "log_level_filter should be the same",
         );
@@ -3,6 +3,7 @@ use core::array::TryFromSliceError;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
+use std::str::FromStr;
 
 use blake2::digest::{Input, VariableOutput};
 use blake2::VarBlake2b;
@@ -149,6 +150,15 @@ impl CorrelationId {
     }
 }
 
+/// Parses a correlation id from its hyphenated form, as given by `Display`.
+impl FromStr for CorrelationId {
+    type Err = uuid::parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(CorrelationId)
+    }
+}
+
 impl fmt::Display for CorrelationId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self.0)
@@ -184,6 +194,19 @@ mod tests {
         )
     }
 
+    #[test]
+    fn should_parse_displayed_correlation_id() {
+        let correlation_id = CorrelationId::new();
+
+        let parsed: CorrelationId = correlation_id
+            .to_string()
+            .parse()
+            .expect("should parse correlation id");
+
+        assert_eq!(parsed, correlation_id);
+        assert!("not-a-uuid".parse::<CorrelationId>().is_err());
+    }
+
     #[test]
     fn should_support_to_string_no_type_encasement() {
         let correlation_id = CorrelationId::new();
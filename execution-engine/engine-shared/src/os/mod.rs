@@ -1,6 +1,21 @@
+use std::fs;
 use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread::{self, Thread};
 
-use libc::{c_long, sysconf, _SC_PAGESIZE};
+use libc::{
+    c_int, c_long, c_void, fcntl, kill, pid_t, pipe, read, sighandler_t, signal, sysconf, write,
+    EINTR, EPERM, F_GETFL, F_SETFL, O_NONBLOCK, SIGHUP, SIGUSR1, SIG_ERR, _SC_PAGESIZE,
+};
+
+const SIGNAL_WAKER_THREAD_NAME: &str = "signal-waker";
+
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+static SIGUSR1_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Write end of the pipe the signal handlers wake [`wake_on_signals`]'s thread through, or -1.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
 
 /// Returns OS page size
 pub fn get_page_size() -> Result<usize, io::Error> {
@@ -13,3 +28,123 @@ pub fn get_page_size() -> Result<usize, io::Error> {
 
     Ok(value as usize)
 }
+
+/// Returns the resident set size of this process in bytes, i.e. how much of its memory is in RAM.
+///
+/// Only supported on Linux, where it is read from `/proc/self/statm`; elsewhere this returns an
+/// error of kind [`io::ErrorKind::Other`].
+pub fn resident_set_size() -> Result<u64, io::Error> {
+    if !cfg!(target_os = "linux") {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "reading the resident set size is only supported on Linux",
+        ));
+    }
+    let statm = fs::read_to_string("/proc/self/statm")?;
+    // the second field is the number of resident pages
+    let resident_pages: u64 = statm
+        .split_whitespace()
+        .nth(1)
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected contents of /proc/self/statm: {:?}", statm),
+            )
+        })?;
+    Ok(resident_pages * get_page_size()? as u64)
+}
+
+/// Returns `true` if a process with the given id exists
+pub fn is_process_alive(pid: u32) -> bool {
+    // signal 0 performs the existence and permission checks without sending a signal; EPERM
+    // means the process exists but belongs to another user
+    let result = unsafe { kill(pid as pid_t, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(EPERM)
+}
+
+/// Writes a byte to the signal pipe, if there is one.  Async-signal-safe; the byte is dropped if
+/// the pipe is full, as the thread reading it has a wakeup pending anyway.
+fn notify_signal() {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        let byte = 0u8;
+        unsafe { write(fd, &byte as *const u8 as *const c_void, 1) };
+    }
+}
+
+extern "C" fn handle_sighup(_signal: c_int) {
+    // only async-signal-safe work is allowed here, so the signal is just recorded
+    SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+    notify_signal();
+}
+
+extern "C" fn handle_sigusr1(_signal: c_int) {
+    SIGUSR1_RECEIVED.store(true, Ordering::SeqCst);
+    notify_signal();
+}
+
+/// Sets `handler` to be called when `signum` is received, replacing any handler set before
+fn set_signal_handler(signum: c_int, handler: extern "C" fn(c_int)) -> Result<(), io::Error> {
+    if unsafe { signal(signum, handler as sighandler_t) } == SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Replaces the handling of SIGHUP, by default terminating the process, with recording it for
+/// [`take_sighup`].  Replaces any handler set before, so should be called after other signal
+/// handling is set up.
+pub fn catch_sighup() -> Result<(), io::Error> {
+    set_signal_handler(SIGHUP, handle_sighup)
+}
+
+/// Returns `true` if SIGHUP was received since [`catch_sighup`] or the previous call
+pub fn take_sighup() -> bool {
+    SIGHUP_RECEIVED.swap(false, Ordering::SeqCst)
+}
+
+/// Replaces the handling of SIGUSR1, by default terminating the process, with recording it for
+/// [`take_sigusr1`]
+pub fn catch_sigusr1() -> Result<(), io::Error> {
+    set_signal_handler(SIGUSR1, handle_sigusr1)
+}
+
+/// Returns `true` if SIGUSR1 was received since [`catch_sigusr1`] or the previous call
+pub fn take_sigusr1() -> bool {
+    SIGUSR1_RECEIVED.swap(false, Ordering::SeqCst)
+}
+
+/// Unparks `thread` whenever SIGHUP or SIGUSR1 is caught, so that it can act on
+/// [`take_sighup`] and [`take_sigusr1`] straight away rather than when it next wakes up.
+///
+/// The handlers cannot unpark a thread themselves, so they write to a pipe which a thread started
+/// here reads.  Should only be called once.
+pub fn wake_on_signals(thread: Thread) -> Result<(), io::Error> {
+    let mut fds: [c_int; 2] = [-1; 2];
+    if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+    // a handler must never block on a full pipe
+    let flags = unsafe { fcntl(write_fd, F_GETFL) };
+    if flags < 0 || unsafe { fcntl(write_fd, F_SETFL, flags | O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    thread::Builder::new()
+        .name(SIGNAL_WAKER_THREAD_NAME.to_string())
+        .spawn(move || {
+            let mut buffer = [0u8; 64];
+            loop {
+                let count =
+                    unsafe { read(read_fd, buffer.as_mut_ptr() as *mut c_void, buffer.len()) };
+                if count > 0 {
+                    thread.unpark();
+                } else if count == 0 || io::Error::last_os_error().raw_os_error() != Some(EINTR) {
+                    return;
+                }
+            }
+        })?;
+    SIGNAL_PIPE.store(write_fd, Ordering::SeqCst);
+    Ok(())
+}
@@ -1,35 +1,237 @@
+use std::fmt;
+use std::fs::{self, Permissions};
 use std::io;
+use std::mem;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
-pub struct Socket(String);
+/// Prefix selecting a TCP listener, e.g. `tcp://0.0.0.0:7777`.
+pub const TCP_SCHEME: &str = "tcp://";
+
+/// Prefix conventionally marking the name of an abstract socket, e.g. `@casperlabs-engine`.
+pub const ABSTRACT_PREFIX: &str = "@";
+
+/// Returns the longest socket file path, or abstract socket name, in bytes that fits in a
+/// `sockaddr_un` on this platform, e.g. 107 on Linux and 103 on macOS.
+///
+/// A path needs room for its NUL terminator, and an abstract name for its leading NUL byte.
+pub fn max_path_length() -> usize {
+    let address: libc::sockaddr_un = unsafe { mem::zeroed() };
+    address.sun_path.len() - 1
+}
+
+/// Error returned by [`Socket::validate`] when a socket path is longer than the platform allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathTooLong {
+    pub length: usize,
+    pub max_length: usize,
+}
+
+impl fmt::Display for PathTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "socket path is {} bytes long, exceeding the platform limit of {} bytes",
+            self.length, self.max_length
+        )
+    }
+}
+
+impl std::error::Error for PathTooLong {}
+
+/// The address the server listens on.
+///
+/// A bare path is a Unix domain socket file, while a value prefixed with [`TCP_SCHEME`] is a TCP
+/// `host:port` address.  An abstract socket is a Unix domain socket in Linux's abstract namespace,
+/// which has no file and disappears once the last reference to it is closed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Socket {
+    Unix(String),
+    /// Holds the name without the leading [`ABSTRACT_PREFIX`]
+    Abstract(String),
+    Tcp(String),
+}
 
 impl Socket {
     pub fn new(socket: String) -> Self {
-        Socket(socket)
+        if socket.starts_with(TCP_SCHEME) {
+            Socket::Tcp(socket[TCP_SCHEME.len()..].to_owned())
+        } else {
+            Socket::Unix(socket)
+        }
     }
 
+    /// Creates an abstract socket named `name`, which may start with [`ABSTRACT_PREFIX`].
+    pub fn new_abstract(name: String) -> Self {
+        if name.starts_with(ABSTRACT_PREFIX) {
+            Socket::Abstract(name[ABSTRACT_PREFIX.len()..].to_owned())
+        } else {
+            Socket::Abstract(name)
+        }
+    }
+
+    /// Returns the socket file path, the name prefixed with [`ABSTRACT_PREFIX`] for abstract
+    /// sockets, or the `host:port` form for TCP sockets.
     pub fn value(&self) -> String {
-        self.0.clone()
+        match self {
+            Socket::Abstract(name) => format!("{}{}", ABSTRACT_PREFIX, name),
+            _ => self.as_str().to_owned(),
+        }
     }
 
+    /// Returns the socket file path, the name of abstract sockets, or the `host:port` form for
+    /// TCP sockets.
     pub fn as_str(&self) -> &str {
-        self.0.as_str()
+        match self {
+            Socket::Unix(path) => path.as_str(),
+            Socket::Abstract(name) => name.as_str(),
+            Socket::Tcp(address) => address.as_str(),
+        }
+    }
+
+    /// Checks that the socket file path, or abstract socket name, fits within
+    /// [`max_path_length`], as binding a longer one fails with a confusing error.  TCP sockets
+    /// are always valid.
+    pub fn validate(&self) -> Result<(), PathTooLong> {
+        let length = match self {
+            Socket::Unix(path) => path.len(),
+            Socket::Abstract(name) => name.len(),
+            Socket::Tcp(_) => return Ok(()),
+        };
+        let max_length = max_path_length();
+        if length > max_length {
+            return Err(PathTooLong { length, max_length });
+        }
+        Ok(())
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        match self {
+            Socket::Unix(_) | Socket::Abstract(_) => false,
+            Socket::Tcp(_) => true,
+        }
     }
 
-    pub fn get_path(&self) -> &Path {
-        std::path::Path::new(&self.0)
+    /// Returns the socket file path, or `None` for abstract and TCP sockets.
+    pub fn get_path(&self) -> Option<&Path> {
+        match self {
+            Socket::Unix(path) => Some(Path::new(path)),
+            Socket::Abstract(_) | Socket::Tcp(_) => None,
+        }
+    }
+
+    /// Checks whether the socket file exists.  Always `false` for abstract and TCP sockets.
+    pub fn file_exists(&self) -> bool {
+        self.get_path().map_or(false, Path::exists)
     }
 
     /// Safely removes file pointed out by a path.
     ///
     /// In practice this file tries to remove file, and if
     /// the file does not exist, it ignores it, and propagates
-    /// any other error.
+    /// any other error.  This is a no-op for abstract and TCP sockets.
     pub fn remove_file(&self) -> io::Result<()> {
-        let path = self.get_path();
+        let path = match self.get_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
         match std::fs::remove_file(path) {
             Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
             result => result,
         }
     }
+
+    /// Sets the permission bits of the socket file, e.g. `0o660`.  This is a no-op for abstract
+    /// and TCP sockets.
+    pub fn set_mode(&self, mode: u32) -> io::Result<()> {
+        match self.get_path() {
+            Some(path) => fs::set_permissions(path, Permissions::from_mode(mode)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{max_path_length, PathTooLong, Socket};
+
+    #[test]
+    fn should_parse_bare_path_as_unix_socket() {
+        let socket = Socket::new("/tmp/ee.sock".to_string());
+        assert_eq!(socket, Socket::Unix("/tmp/ee.sock".to_string()));
+        assert!(!socket.is_tcp());
+        assert_eq!(socket.value(), "/tmp/ee.sock");
+    }
+
+    #[test]
+    fn should_parse_tcp_scheme_as_tcp_socket() {
+        let socket = Socket::new("tcp://0.0.0.0:7777".to_string());
+        assert_eq!(socket, Socket::Tcp("0.0.0.0:7777".to_string()));
+        assert!(socket.is_tcp());
+        assert_eq!(socket.value(), "0.0.0.0:7777");
+        assert!(socket.get_path().is_none());
+        assert!(!socket.file_exists());
+        assert!(socket.remove_file().is_ok());
+        assert!(socket.set_mode(0o660).is_ok());
+    }
+
+    #[test]
+    fn should_parse_abstract_socket_with_or_without_prefix() {
+        for name in &["@casperlabs-engine", "casperlabs-engine"] {
+            let socket = Socket::new_abstract(name.to_string());
+            assert_eq!(socket, Socket::Abstract("casperlabs-engine".to_string()));
+            assert!(!socket.is_tcp());
+            assert_eq!(socket.value(), "@casperlabs-engine");
+            assert!(socket.get_path().is_none());
+            assert!(!socket.file_exists());
+            assert!(socket.remove_file().is_ok());
+            assert!(socket.set_mode(0o660).is_ok());
+        }
+    }
+
+    #[test]
+    fn should_validate_socket_path_length() {
+        let max_length = max_path_length();
+        let path = format!("/{}", "a".repeat(max_length - 1));
+        assert!(Socket::new(path.clone()).validate().is_ok());
+        assert!(Socket::new_abstract(path.clone()).validate().is_ok());
+
+        let too_long = format!("{}a", path);
+        let expected_error = PathTooLong {
+            length: max_length + 1,
+            max_length,
+        };
+        assert_eq!(
+            Socket::new(too_long.clone()).validate(),
+            Err(expected_error)
+        );
+        assert_eq!(
+            Socket::new_abstract(too_long.clone()).validate(),
+            Err(expected_error)
+        );
+        assert!(Socket::new(format!("tcp://{}", too_long))
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn should_set_mode_of_socket_file() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("ee-socket-tests-{}.sock", uuid::Uuid::new_v4()));
+        fs::write(&path, b"").expect("should create file");
+
+        let socket = Socket::new(path.to_string_lossy().into_owned());
+        socket.set_mode(0o660).expect("should set mode");
+
+        let mode = fs::metadata(&path)
+            .expect("should read metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        socket.remove_file().expect("should remove file");
+    }
 }
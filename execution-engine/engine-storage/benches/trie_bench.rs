@@ -2,16 +2,27 @@
 extern crate casperlabs_engine_storage;
 extern crate contract_ffi;
 extern crate engine_shared;
+extern crate lmdb;
+extern crate tempfile;
 extern crate test;
 
+use std::sync::Arc;
+
+use lmdb::DatabaseFlags;
+use tempfile::{tempdir, TempDir};
 use test::black_box;
 use test::Bencher;
 
+use casperlabs_engine_storage::global_state::lmdb::LmdbGlobalState;
+use casperlabs_engine_storage::global_state::StateReader;
 use casperlabs_engine_storage::trie::{Pointer, PointerBlock, Trie};
+use casperlabs_engine_storage::trie_store::lmdb::{
+    LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS,
+};
 use contract_ffi::bytesrepr::{FromBytes, ToBytes};
 use contract_ffi::key::Key;
 use contract_ffi::value::Value;
-use engine_shared::newtypes::Blake2bHash;
+use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 
 #[bench]
 fn serialize_trie_leaf(b: &mut Bencher) {
@@ -70,3 +81,79 @@ fn deserialize_trie_node_pointer(b: &mut Bencher) {
 
     b.iter(|| u8::from_bytes(black_box(&node_bytes)))
 }
+
+// number of accounts in the state read by the cache benchmarks
+const ACCOUNTS: u16 = 4096;
+// number of accounts touched by each iteration, as by a run of related deploys
+const HOT_ACCOUNTS: u16 = 64;
+const TRIE_CACHE_SIZE: usize = 1024;
+const MAP_SIZE: usize = 4096 * 25600;
+
+fn account(index: u16) -> Key {
+    let mut addr = [0u8; 32];
+    addr[0] = (index >> 8) as u8;
+    addr[1] = index as u8;
+    Key::Account(addr)
+}
+
+fn create_state(trie_cache_size: Option<usize>) -> (TempDir, LmdbGlobalState, Arc<LmdbTrieStore>) {
+    let temp_dir = tempdir().unwrap();
+    let environment = Arc::new(
+        LmdbEnvironment::new(
+            &temp_dir.path().to_path_buf(),
+            MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap(),
+    );
+    let mut store = LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap();
+    if let Some(capacity) = trie_cache_size {
+        store = store.with_cache(capacity);
+    }
+    let store = Arc::new(store);
+    let pairs: Vec<(Key, Value)> = (0..ACCOUNTS)
+        .map(|index| (account(index), Value::Int32(i32::from(index))))
+        .collect();
+    let state = LmdbGlobalState::from_pairs(
+        environment,
+        Arc::clone(&store),
+        CorrelationId::new(),
+        &pairs,
+    )
+    .unwrap();
+    (temp_dir, state, store)
+}
+
+fn read_hot_accounts(b: &mut Bencher, trie_cache_size: Option<usize>) {
+    let (_temp_dir, state, store) = create_state(trie_cache_size);
+    let correlation_id = CorrelationId::new();
+    // spread the hot accounts over the whole trie
+    let keys: Vec<Key> = (0..HOT_ACCOUNTS)
+        .map(|index| account(index * (ACCOUNTS / HOT_ACCOUNTS)))
+        .collect();
+
+    b.iter(|| {
+        for key in &keys {
+            black_box(state.read(correlation_id, key).unwrap());
+        }
+    });
+
+    if let Some(stats) = store.cache_stats() {
+        eprintln!(
+            "trie cache: {} hits, {} misses, hit rate {:.3}",
+            stats.hits,
+            stats.misses,
+            stats.hit_rate()
+        );
+    }
+}
+
+#[bench]
+fn read_hot_accounts_without_trie_cache(b: &mut Bencher) {
+    read_hot_accounts(b, None)
+}
+
+#[bench]
+fn read_hot_accounts_with_trie_cache(b: &mut Bencher) {
+    read_hot_accounts(b, Some(TRIE_CACHE_SIZE))
+}
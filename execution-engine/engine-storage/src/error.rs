@@ -1,27 +1,124 @@
+use std::io;
+
+use libc;
 use lmdb;
 use wasmi;
 
 use contract_ffi::bytesrepr;
+use engine_shared::newtypes::Blake2bHash;
 
 use trie_store::in_memory;
 
-#[derive(Debug, Fail, PartialEq, Eq)]
+#[derive(Debug, Fail, PartialEq, Eq, Clone)]
 pub enum Error {
     #[fail(display = "{}", _0)]
     Lmdb(#[fail(cause)] lmdb::Error),
 
+    #[fail(display = "LMDB map is full; the map size is too small to hold global state")]
+    MapFull,
+
+    #[fail(display = "No space left on the device holding global state")]
+    DiskFull,
+
+    #[fail(display = "LMDB reader slots are exhausted; too many read transactions are open")]
+    ReadersFull,
+
     #[fail(display = "{}", _0)]
     BytesRepr(#[fail(cause)] bytesrepr::Error),
 
     #[fail(display = "Another thread panicked while holding a lock")]
     PoisonError,
+
+    #[fail(display = "I/O error: {}", _0)]
+    Io(String),
+
+    #[fail(display = "No trie store named {:?} in global state", _0)]
+    StoreNotFound(String),
+
+    #[fail(
+        display = "Trie store name {:?} is reserved for state root indexes, store versions and \
+                   genesis roots",
+        _0
+    )]
+    ReservedStoreName(String),
+
+    #[fail(
+        display = "Trie store is in format version {}, but this engine only reads version {}",
+        found, supported
+    )]
+    UnsupportedStoreVersion { found: u8, supported: u8 },
+
+    #[fail(display = "State root {} not found in global state", _0)]
+    RootNotFound(Blake2bHash),
+}
+
+impl Error {
+    /// Returns `true` if a write failed because global state has no room left, either in the map
+    /// or on disk.  Nothing was written in that case, so the write can be retried once space is
+    /// freed.
+    pub fn is_out_of_space(&self) -> bool {
+        match self {
+            Error::MapFull | Error::DiskFull => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if a read transaction could not be started because every LMDB reader slot
+    /// was taken.  Slots are freed as other reads finish, so the read can be retried.
+    pub fn is_readers_full(&self) -> bool {
+        match self {
+            Error::ReadersFull => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if a read asked for a state root which is not in global state.
+    pub fn is_root_not_found(&self) -> bool {
+        match self {
+            Error::RootNotFound(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if a write failed for a reason which is expected to clear up by itself, such
+    /// as contention for reader slots or another process resizing the map.  Nothing was written in
+    /// that case, so the write can be retried straight away.  Running out of space and corruption
+    /// are not transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::ReadersFull
+            | Error::Lmdb(lmdb::Error::MapResized)
+            | Error::Lmdb(lmdb::Error::Other(libc::EAGAIN))
+            | Error::Lmdb(lmdb::Error::Other(libc::EBUSY))
+            | Error::Lmdb(lmdb::Error::Other(libc::EINTR)) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if an environment could not be opened because another process may still be
+    /// creating it: its data file does not hold valid meta pages yet, or its lock file is busy.
+    /// Opening it again once the other process is done should succeed.  A missing or inaccessible
+    /// path and corruption are not transient.
+    pub fn is_transient_open(&self) -> bool {
+        match self {
+            Error::Lmdb(lmdb::Error::Invalid)
+            | Error::Lmdb(lmdb::Error::Other(libc::EAGAIN))
+            | Error::Lmdb(lmdb::Error::Other(libc::EBUSY)) => true,
+            _ => false,
+        }
+    }
 }
 
 impl wasmi::HostError for Error {}
 
 impl From<lmdb::Error> for Error {
     fn from(e: lmdb::Error) -> Self {
-        Error::Lmdb(e)
+        match e {
+            lmdb::Error::MapFull => Error::MapFull,
+            lmdb::Error::ReadersFull => Error::ReadersFull,
+            lmdb::Error::Other(libc::ENOSPC) => Error::DiskFull,
+            e => Error::Lmdb(e),
+        }
     }
 }
 
@@ -31,6 +128,13 @@ impl From<bytesrepr::Error> for Error {
     }
 }
 
+// io::Error is neither PartialEq nor Clone, so only its message is kept
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e.to_string())
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_e: std::sync::PoisonError<T>) -> Self {
         Error::PoisonError
@@ -45,3 +149,25 @@ impl From<in_memory::Error> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_treat_contention_as_transient() {
+        assert!(Error::ReadersFull.is_transient());
+        assert!(Error::from(lmdb::Error::MapResized).is_transient());
+        assert!(Error::from(lmdb::Error::Other(libc::EAGAIN)).is_transient());
+        assert!(Error::from(lmdb::Error::Other(libc::EBUSY)).is_transient());
+    }
+
+    #[test]
+    fn should_not_treat_lack_of_space_or_corruption_as_transient() {
+        assert!(!Error::from(lmdb::Error::MapFull).is_transient());
+        assert!(!Error::from(lmdb::Error::Other(libc::ENOSPC)).is_transient());
+        assert!(!Error::from(lmdb::Error::Corrupted).is_transient());
+        assert!(!Error::from(lmdb::Error::PageNotFound).is_transient());
+        assert!(!Error::PoisonError.is_transient());
+    }
+}
@@ -0,0 +1,323 @@
+//! An append-only log of the commits made to a global state, which can be replayed to rebuild it.
+//!
+//! The file starts with [`MAGIC`] and a little-endian `u32` [`VERSION`], followed by one record
+//! per commit.  A record holds four fields, each preceded by its length as a little-endian `u32`:
+//! the prestate hash, the resulting state root, the encoded effects, and the
+//! [`Blake2bHash`] of the encoded effects.  The effects of a commit are encoded as a `u32` count
+//! of effect sets, each a `u32` count of entries followed by the entries ordered by key bytes,
+//! so that the same effects always yield the same digest.  A plain commit has one effect set, and
+//! a batch commit one per set in the batch.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use parking_lot::Mutex;
+
+use contract_ffi::bytesrepr::{self, deserialize, FromBytes, ToBytes};
+use contract_ffi::key::Key;
+use contract_ffi::value::{Value, U128, U256, U512};
+use engine_shared::newtypes::Blake2bHash;
+use engine_shared::transform::{self, Transform, TypeMismatch};
+use error;
+use global_state::pairs_file::{invalid_data, read_field, read_u32, write_field};
+
+pub const MAGIC: [u8; 4] = *b"CLAL";
+
+pub const VERSION: u32 = 1;
+
+const TRANSFORM_IDENTITY: u8 = 0;
+const TRANSFORM_WRITE: u8 = 1;
+const TRANSFORM_ADD_INT32: u8 = 2;
+const TRANSFORM_ADD_UINT64: u8 = 3;
+const TRANSFORM_ADD_UINT128: u8 = 4;
+const TRANSFORM_ADD_UINT256: u8 = 5;
+const TRANSFORM_ADD_UINT512: u8 = 6;
+const TRANSFORM_ADD_KEYS: u8 = 7;
+const TRANSFORM_FAILURE: u8 = 8;
+
+/// A commit read back from an audit log.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub prestate_hash: Blake2bHash,
+    pub state_root: Blake2bHash,
+    pub effects: Vec<HashMap<Key, Transform>>,
+}
+
+/// An audit log file open for appending.
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, creating it if it does not exist.  Records are appended
+    /// after the existing ones, so the log of a data directory can be kept across restarts.
+    pub fn open(path: &Path) -> Result<Self, error::Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&MAGIC)?;
+            file.write_all(&VERSION.to_le_bytes())?;
+            file.sync_data()?;
+        } else {
+            read_header(&mut file)?;
+        }
+        Ok(AuditLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends a record of the commit of `effects_bytes`, as returned by [`encode_effects`], on
+    /// top of `prestate_hash`, yielding `state_root`.  Returns once the record is on disk.
+    pub fn append(
+        &self,
+        prestate_hash: Blake2bHash,
+        state_root: Blake2bHash,
+        effects_bytes: &[u8],
+    ) -> Result<(), error::Error> {
+        let digest = Blake2bHash::new(effects_bytes);
+        let mut record = Vec::new();
+        write_field(&mut record, &prestate_hash.to_bytes()?)?;
+        write_field(&mut record, &state_root.to_bytes()?)?;
+        write_field(&mut record, effects_bytes)?;
+        write_field(&mut record, &digest.to_bytes()?)?;
+
+        let mut file = self.file.lock();
+        file.write_all(&record)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Encodes the effect sets of a commit for [`AuditLog::append`].
+pub fn encode_effects(effects: &[&HashMap<Key, Transform>]) -> Result<Vec<u8>, error::Error> {
+    let mut ret = (effects.len() as u32).to_bytes()?;
+    for effect_set in effects {
+        let mut entries = effect_set
+            .iter()
+            .map(|(key, transform)| Ok((key.to_bytes()?, transform_to_bytes(transform)?)))
+            .collect::<Result<Vec<_>, bytesrepr::Error>>()?;
+        entries.sort();
+        ret.extend((entries.len() as u32).to_bytes()?);
+        for (key_bytes, transform_bytes) in entries {
+            ret.extend(key_bytes);
+            ret.extend(transform_bytes);
+        }
+    }
+    Ok(ret)
+}
+
+/// Reads the records appended to an audit log from `reader`, checking the digest of each.
+pub fn read_records<R: Read>(reader: &mut R) -> Result<Vec<AuditRecord>, error::Error> {
+    read_header(reader)?;
+
+    let mut records = Vec::new();
+    while let Some(prestate_bytes) = read_field(reader, true)? {
+        let mut next_field = || {
+            read_field(reader, false)
+                .map(|field| field.expect("read_field only returns None at end of file"))
+        };
+        let state_root_bytes = next_field()?;
+        let effects_bytes = next_field()?;
+        let digest_bytes = next_field()?;
+
+        let digest: Blake2bHash = deserialize(&digest_bytes)?;
+        if digest != Blake2bHash::new(&effects_bytes) {
+            return Err(invalid_data(&format!(
+                "digest of the effects of record {} does not match",
+                records.len()
+            ))
+            .into());
+        }
+        records.push(AuditRecord {
+            prestate_hash: deserialize(&prestate_bytes)?,
+            state_root: deserialize(&state_root_bytes)?,
+            effects: decode_effects(&effects_bytes)?,
+        });
+    }
+    Ok(records)
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<(), error::Error> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("not a global state audit log").into());
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported version {}", version)).into());
+    }
+    Ok(())
+}
+
+fn decode_effects(bytes: &[u8]) -> Result<Vec<HashMap<Key, Transform>>, bytesrepr::Error> {
+    let (set_count, mut stream): (u32, &[u8]) = FromBytes::from_bytes(bytes)?;
+    let mut ret = Vec::new();
+    for _ in 0..set_count {
+        let (entry_count, rem): (u32, &[u8]) = FromBytes::from_bytes(stream)?;
+        stream = rem;
+        let mut effect_set = HashMap::new();
+        for _ in 0..entry_count {
+            let (key, rem): (Key, &[u8]) = FromBytes::from_bytes(stream)?;
+            let (transform, rem) = transform_from_bytes(rem)?;
+            effect_set.insert(key, transform);
+            stream = rem;
+        }
+        ret.push(effect_set);
+    }
+    if !stream.is_empty() {
+        return Err(bytesrepr::Error::LeftOverBytes);
+    }
+    Ok(ret)
+}
+
+fn transform_to_bytes(transform: &Transform) -> Result<Vec<u8>, bytesrepr::Error> {
+    let (tag, mut payload) = match transform {
+        Transform::Identity => (TRANSFORM_IDENTITY, Vec::new()),
+        Transform::Write(value) => (TRANSFORM_WRITE, value.to_bytes()?),
+        Transform::AddInt32(i) => (TRANSFORM_ADD_INT32, i.to_bytes()?),
+        Transform::AddUInt64(u) => (TRANSFORM_ADD_UINT64, u.to_bytes()?),
+        Transform::AddUInt128(u) => (TRANSFORM_ADD_UINT128, u.to_bytes()?),
+        Transform::AddUInt256(u) => (TRANSFORM_ADD_UINT256, u.to_bytes()?),
+        Transform::AddUInt512(u) => (TRANSFORM_ADD_UINT512, u.to_bytes()?),
+        Transform::AddKeys(keys) => (TRANSFORM_ADD_KEYS, keys.to_bytes()?),
+        Transform::Failure(transform::Error::TypeMismatch(type_mismatch)) => {
+            let mut payload = type_mismatch.expected.to_bytes()?;
+            payload.extend(type_mismatch.found.to_bytes()?);
+            (TRANSFORM_FAILURE, payload)
+        }
+    };
+    let mut ret = vec![tag];
+    ret.append(&mut payload);
+    Ok(ret)
+}
+
+fn transform_from_bytes(bytes: &[u8]) -> Result<(Transform, &[u8]), bytesrepr::Error> {
+    let (tag, rem): (u8, &[u8]) = FromBytes::from_bytes(bytes)?;
+    match tag {
+        TRANSFORM_IDENTITY => Ok((Transform::Identity, rem)),
+        TRANSFORM_WRITE => {
+            let (value, rem): (Value, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::Write(value), rem))
+        }
+        TRANSFORM_ADD_INT32 => {
+            let (i, rem): (i32, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddInt32(i), rem))
+        }
+        TRANSFORM_ADD_UINT64 => {
+            let (u, rem): (u64, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddUInt64(u), rem))
+        }
+        TRANSFORM_ADD_UINT128 => {
+            let (u, rem): (U128, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddUInt128(u), rem))
+        }
+        TRANSFORM_ADD_UINT256 => {
+            let (u, rem): (U256, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddUInt256(u), rem))
+        }
+        TRANSFORM_ADD_UINT512 => {
+            let (u, rem): (U512, &[u8]) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddUInt512(u), rem))
+        }
+        TRANSFORM_ADD_KEYS => {
+            let (keys, rem) = FromBytes::from_bytes(rem)?;
+            Ok((Transform::AddKeys(keys), rem))
+        }
+        TRANSFORM_FAILURE => {
+            let (expected, rem): (String, &[u8]) = FromBytes::from_bytes(rem)?;
+            let (found, rem): (String, &[u8]) = FromBytes::from_bytes(rem)?;
+            let type_mismatch = TypeMismatch::new(expected, found);
+            Ok((Transform::Failure(type_mismatch.into()), rem))
+        }
+        _ => Err(bytesrepr::Error::FormattingError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn test_effects() -> HashMap<Key, Transform> {
+        let mut named_keys = BTreeMap::new();
+        named_keys.insert("three".to_string(), Key::Hash([3u8; 32]));
+        let mut effects = HashMap::new();
+        effects.insert(Key::Account([1u8; 32]), Transform::Write(Value::Int32(1)));
+        effects.insert(Key::Hash([2u8; 32]), Transform::AddUInt512(U512::from(2)));
+        effects.insert(Key::Hash([3u8; 32]), Transform::AddKeys(named_keys));
+        effects
+    }
+
+    #[test]
+    fn should_round_trip_records_across_reopening() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let first_root: Blake2bHash = [1u8; 32].into();
+        let second_root: Blake2bHash = [2u8; 32].into();
+        let third_root: Blake2bHash = [3u8; 32].into();
+        let effects = test_effects();
+
+        AuditLog::open(&path)
+            .unwrap()
+            .append(
+                first_root,
+                second_root,
+                &encode_effects(&[&effects]).unwrap(),
+            )
+            .unwrap();
+        AuditLog::open(&path)
+            .unwrap()
+            .append(
+                second_root,
+                third_root,
+                &encode_effects(&[&effects, &HashMap::new()]).unwrap(),
+            )
+            .unwrap();
+
+        let records = read_records(&mut File::open(&path).unwrap()).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                AuditRecord {
+                    prestate_hash: first_root,
+                    state_root: second_root,
+                    effects: vec![effects.clone()],
+                },
+                AuditRecord {
+                    prestate_hash: second_root,
+                    state_root: third_root,
+                    effects: vec![effects, HashMap::new()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reject_record_whose_effects_do_not_match_digest() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let effects_bytes = encode_effects(&[&test_effects()]).unwrap();
+        AuditLog::open(&path)
+            .unwrap()
+            .append([1u8; 32].into(), [2u8; 32].into(), &effects_bytes)
+            .unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let effects_start = bytes
+            .windows(effects_bytes.len())
+            .position(|window| window == effects_bytes.as_slice())
+            .unwrap();
+        bytes[effects_start + effects_bytes.len() - 1] ^= 0xff;
+
+        assert!(read_records(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! The post state root of the last successful genesis, kept in a named LMDB database next to the
+//! trie store, so that a node reopening its store knows which genesis it holds.  Each trie store
+//! of an environment has its own genesis root.
+//!
+//! The root is recorded explicitly rather than taken from the
+//! [`state_roots`](::global_state::state_roots) index, whose first entry may be the root of
+//! seeded or imported state, or missing on stores indexed after their genesis ran.
+
+use lmdb::{self, Database, DatabaseFlags, WriteFlags};
+
+use contract_ffi::bytesrepr::{deserialize, ToBytes};
+use engine_shared::newtypes::Blake2bHash;
+use error;
+use trie_store::lmdb::LmdbEnvironment;
+use trie_store::{Transaction, TransactionSource};
+
+/// Name of the LMDB database holding the genesis root of the trie store in the unnamed database.
+/// The genesis root of a named trie store is kept in a database whose name is this, followed by a
+/// slash and the trie store's name.
+pub const GENESIS_ROOT_DB_NAME: &str = "genesis-root";
+
+const GENESIS_ROOT_KEY: &[u8] = b"genesis-root";
+
+/// Returns `true` if `name` may be taken by the database of a genesis root, so that it cannot be
+/// used for a trie store.
+pub fn is_reserved_name(name: &str) -> bool {
+    name.starts_with(GENESIS_ROOT_DB_NAME)
+}
+
+/// Returns the name of the database holding the genesis root of the trie store called
+/// `store_name`.
+fn db_name(store_name: Option<&str>) -> String {
+    match store_name {
+        Some(store_name) => format!("{}/{}", GENESIS_ROOT_DB_NAME, store_name),
+        None => GENESIS_ROOT_DB_NAME.to_owned(),
+    }
+}
+
+/// Reads the recorded genesis root, if there is one.
+fn read_root<T: lmdb::Transaction>(
+    txn: &T,
+    genesis_root_db: Database,
+) -> Result<Option<Blake2bHash>, error::Error> {
+    match txn.get(genesis_root_db, &GENESIS_ROOT_KEY) {
+        Ok(bytes) => Ok(Some(deserialize(bytes)?)),
+        Err(lmdb::Error::NotFound) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Returns the genesis root recorded for the trie store called `store_name`, or `None` if none
+/// was recorded.  Nothing is written.
+///
+/// Suitable for environments opened with [`LmdbEnvironment::new_read_only`].
+pub(crate) fn read(
+    env: &LmdbEnvironment,
+    store_name: Option<&str>,
+) -> Result<Option<Blake2bHash>, error::Error> {
+    let genesis_root_db = match env.open_db(Some(&db_name(store_name))) {
+        Ok(genesis_root_db) => genesis_root_db,
+        Err(lmdb::Error::NotFound) => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let txn = env.create_read_txn()?;
+    let root = read_root(&txn, genesis_root_db)?;
+    txn.commit()?;
+    Ok(root)
+}
+
+/// Records `root` as the genesis root of the trie store called `store_name`, replacing any
+/// recorded before.
+pub(crate) fn write(
+    env: &LmdbEnvironment,
+    store_name: Option<&str>,
+    root: Blake2bHash,
+) -> Result<(), error::Error> {
+    let genesis_root_db = env.create_db(Some(&db_name(store_name)), DatabaseFlags::empty())?;
+    let mut txn = env.create_read_write_txn()?;
+    txn.put(
+        genesis_root_db,
+        &GENESIS_ROOT_KEY,
+        &root.to_bytes()?,
+        WriteFlags::empty(),
+    )?;
+    txn.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lmdb::DatabaseFlags;
+    use tempfile::{tempdir, TempDir};
+
+    use engine_shared::newtypes::Blake2bHash;
+    use error;
+    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
+
+    use super::*;
+
+    const TEST_MAP_SIZE: usize = 10 * 1024 * 1024;
+
+    fn create_environment() -> (TempDir, LmdbEnvironment) {
+        let temp_dir = tempdir().unwrap();
+        let environment = LmdbEnvironment::new(
+            &temp_dir.path().to_path_buf(),
+            TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
+        (temp_dir, environment)
+    }
+
+    #[test]
+    fn should_read_nothing_before_genesis() {
+        let (_temp_dir, environment) = create_environment();
+        LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap();
+        assert_eq!(read(&environment, None).unwrap(), None);
+    }
+
+    #[test]
+    fn should_replace_recorded_root() {
+        let (_temp_dir, environment) = create_environment();
+        let first_root: Blake2bHash = [1u8; 32].into();
+        let second_root: Blake2bHash = [2u8; 32].into();
+
+        write(&environment, Some("chain"), first_root).unwrap();
+        assert_eq!(read(&environment, Some("chain")).unwrap(), Some(first_root));
+
+        write(&environment, Some("chain"), second_root).unwrap();
+        assert_eq!(
+            read(&environment, Some("chain")).unwrap(),
+            Some(second_root)
+        );
+        // each store has its own genesis root
+        assert_eq!(read(&environment, None).unwrap(), None);
+    }
+
+    #[test]
+    fn genesis_root_names_should_be_reserved() {
+        let (_temp_dir, environment) = create_environment();
+        assert_eq!(
+            LmdbTrieStore::new(&environment, Some("genesis-root"), DatabaseFlags::empty())
+                .unwrap_err(),
+            error::Error::ReservedStoreName("genesis-root".to_string())
+        );
+    }
+}
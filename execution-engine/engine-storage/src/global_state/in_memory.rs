@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::Arc;
 
 use contract_ffi::key::Key;
@@ -8,16 +9,20 @@ use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 use engine_shared::transform::Transform;
 use error;
 use global_state::StateReader;
-use global_state::{commit, CommitResult, History};
+use global_state::{commit, commit_batch, commit_group, CommitResult, History, StoreStats};
 use trie::operations::create_hashed_empty_trie;
 use trie::Trie;
 use trie_store::in_memory::{
     self, InMemoryEnvironment, InMemoryReadTransaction, InMemoryTrieStore,
 };
-use trie_store::operations::{read, write, ReadResult, WriteResult};
+use trie_store::operations::{
+    diff, for_each_pair, read_with_node_count, verify, write, DiffResult, PruneResult, ReadResult,
+    VerifyResult, WriteResult,
+};
 use trie_store::{Transaction, TransactionSource, TrieStore};
 
-/// Represents a "view" of global state at a particular root hash.
+/// Represents a "view" of global state at a particular root hash.  Clones share the same store.
+#[derive(Clone)]
 pub struct InMemoryGlobalState {
     pub environment: Arc<InMemoryEnvironment>,
     pub store: Arc<InMemoryTrieStore>,
@@ -98,17 +103,32 @@ impl StateReader<Key, Value> for InMemoryGlobalState {
     type Error = error::Error;
 
     fn read(&self, correlation_id: CorrelationId, key: &Key) -> Result<Option<Value>, Self::Error> {
+        self.read_with_node_count(correlation_id, key)
+            .map(|(maybe_value, _)| maybe_value)
+    }
+
+    fn read_with_node_count(
+        &self,
+        correlation_id: CorrelationId,
+        key: &Key,
+    ) -> Result<(Option<Value>, usize), Self::Error> {
         let txn = self.environment.create_read_txn()?;
-        let ret = match read::<Key, Value, InMemoryReadTransaction, InMemoryTrieStore, Self::Error>(
+        let ret = match read_with_node_count::<
+            Key,
+            Value,
+            InMemoryReadTransaction,
+            InMemoryTrieStore,
+            Self::Error,
+        >(
             correlation_id,
             &txn,
             self.store.deref(),
             &self.root_hash,
             key,
         )? {
-            ReadResult::Found(value) => Some(value),
-            ReadResult::NotFound => None,
-            ReadResult::RootNotFound => panic!("InMemoryGlobalState has invalid root"),
+            (ReadResult::Found(value), nodes_visited) => (Some(value), nodes_visited),
+            (ReadResult::NotFound, nodes_visited) => (None, nodes_visited),
+            (ReadResult::RootNotFound, _) => panic!("InMemoryGlobalState has invalid root"),
         };
         txn.commit()?;
         Ok(ret)
@@ -146,12 +166,50 @@ impl History for InMemoryGlobalState {
             prestate_hash,
             effects,
         )?;
-        if let CommitResult::Success(root_hash) = commit_result {
-            self.root_hash = root_hash;
+        if let CommitResult::Success { state_root, .. } = commit_result {
+            self.root_hash = state_root;
         };
         Ok(commit_result)
     }
 
+    fn commit_batch(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: Vec<HashMap<Key, Transform>>,
+    ) -> Result<CommitResult, Self::Error> {
+        let commit_result = commit_batch::<InMemoryEnvironment, InMemoryTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            prestate_hash,
+            effects,
+        )?;
+        if let CommitResult::Success { state_root, .. } = commit_result {
+            self.root_hash = state_root;
+        };
+        Ok(commit_result)
+    }
+
+    fn commit_group(
+        &mut self,
+        correlation_id: CorrelationId,
+        commits: Vec<(Blake2bHash, HashMap<Key, Transform>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error> {
+        let commit_results = commit_group::<InMemoryEnvironment, InMemoryTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            commits,
+        )?;
+        for commit_result in &commit_results {
+            if let CommitResult::Success { state_root, .. } = commit_result {
+                self.root_hash = *state_root;
+            }
+        }
+        Ok(commit_results)
+    }
+
     fn current_root(&self) -> Blake2bHash {
         self.root_hash
     }
@@ -159,11 +217,105 @@ impl History for InMemoryGlobalState {
     fn empty_root(&self) -> Blake2bHash {
         self.empty_root_hash
     }
+
+    fn stats(&self) -> Result<Option<StoreStats>, Self::Error> {
+        Ok(None)
+    }
+
+    fn state_roots(
+        &self,
+        _offset: u64,
+        _limit: u64,
+    ) -> Result<Option<Vec<Blake2bHash>>, Self::Error> {
+        Ok(None)
+    }
+
+    fn genesis_root(&self) -> Result<Option<Blake2bHash>, Self::Error> {
+        Ok(None)
+    }
+
+    fn record_genesis_root(&mut self, _root: Blake2bHash) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn copy_to(&self, _path: &Path, _compact: bool) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+
+    fn flush(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn diff(
+        &self,
+        correlation_id: CorrelationId,
+        before_root: Blake2bHash,
+        after_root: Blake2bHash,
+        limit: usize,
+    ) -> Result<DiffResult<Key, Value>, Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let diff_result = diff::<Key, Value, _, InMemoryTrieStore, Self::Error>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &before_root,
+            &after_root,
+            limit,
+        )?;
+        txn.commit()?;
+        Ok(diff_result)
+    }
+
+    fn for_each_pair<F>(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+        f: F,
+    ) -> Result<Option<bool>, Self::Error>
+    where
+        F: FnMut(Key, Value) -> bool,
+    {
+        let txn = self.environment.create_read_txn()?;
+        let visited = for_each_pair::<Key, Value, _, InMemoryTrieStore, Self::Error, _>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &root,
+            f,
+        )?;
+        txn.commit()?;
+        Ok(visited)
+    }
+
+    fn verify(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<VerifyResult, Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let verify_result = verify::<Key, Value, _, InMemoryTrieStore, Self::Error>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &root,
+        )?;
+        txn.commit()?;
+        Ok(verify_result)
+    }
+
+    fn prune(
+        &mut self,
+        _correlation_id: CorrelationId,
+        _retain: &[Blake2bHash],
+    ) -> Result<Option<PruneResult>, Self::Error> {
+        Ok(None)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use engine_shared::init::mocked_account;
+    use trie_store::operations::KeyDiff;
 
     use super::*;
 
@@ -223,6 +375,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reads_count_the_trie_nodes_visited() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+        let checkout = state.checkout(state.root_hash).unwrap().unwrap();
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            let (maybe_value, nodes_visited) =
+                checkout.read_with_node_count(correlation_id, &key).unwrap();
+            assert_eq!(Some(value), maybe_value);
+            // at least the root and the leaf holding the value
+            assert!(nodes_visited >= 2, "visited {} nodes", nodes_visited);
+        }
+        let missing_key = Key::Account([3u8; 32]);
+        let (maybe_value, nodes_visited) = checkout
+            .read_with_node_count(correlation_id, &missing_key)
+            .unwrap();
+        assert_eq!(None, maybe_value);
+        assert!(nodes_visited >= 1, "visited {} nodes", nodes_visited);
+    }
+
     #[test]
     fn checkout_fails_if_unknown_hash_is_given() {
         let state = create_test_state();
@@ -231,6 +403,108 @@ mod tests {
         assert!(result.is_none());
     }
 
+    fn commit_updated_pairs(state: &mut InMemoryGlobalState) -> Blake2bHash {
+        let root_hash = state.root_hash;
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, Transform::Write(value)))
+            .collect();
+        match state
+            .commit(CorrelationId::new(), root_hash, effects)
+            .unwrap()
+        {
+            CommitResult::Success { state_root, .. } => state_root,
+            _ => panic!("commit failed"),
+        }
+    }
+
+    #[test]
+    fn diff_returns_changed_keys_with_values_before_and_after() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let updated_hash = commit_updated_pairs(&mut state);
+
+        let expected_diffs: Vec<KeyDiff<Key, Value>> = create_test_pairs_updated()
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| KeyDiff {
+                key,
+                before: TEST_PAIRS
+                    .iter()
+                    .find(|pair| pair.key == key)
+                    .map(|pair| pair.value.clone()),
+                after: Some(value),
+            })
+            .collect();
+
+        assert_eq!(
+            state
+                .diff(correlation_id, root_hash, updated_hash, 10)
+                .unwrap(),
+            DiffResult::Diff {
+                diffs: expected_diffs,
+                truncated: false,
+            }
+        );
+        assert_eq!(
+            state
+                .diff(correlation_id, root_hash, root_hash, 10)
+                .unwrap(),
+            DiffResult::Diff {
+                diffs: Vec::new(),
+                truncated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn diff_is_truncated_at_limit() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let updated_hash = commit_updated_pairs(&mut state);
+
+        match state
+            .diff(correlation_id, updated_hash, root_hash, 2)
+            .unwrap()
+        {
+            DiffResult::Diff { diffs, truncated } => {
+                assert_eq!(diffs.len(), 2);
+                assert_eq!(diffs[0].key, Key::Account([1u8; 32]));
+                assert_eq!(diffs[0].after, Some(Value::Int32(1)));
+                assert!(truncated);
+            }
+            other => panic!("expected a diff, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn diff_fails_if_unknown_hash_is_given() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+        let fake_hash: Blake2bHash = [1u8; 32].into();
+        assert_eq!(
+            state
+                .diff(correlation_id, state.root_hash, fake_hash, 10)
+                .unwrap(),
+            DiffResult::RootNotFound(fake_hash)
+        );
+    }
+
+    #[test]
+    fn verify_accepts_committed_state() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let updated_hash = commit_updated_pairs(&mut state);
+
+        match state.verify(correlation_id, updated_hash).unwrap() {
+            VerifyResult::Verified { node_count } => assert!(node_count > 1),
+            other => panic!("expected the state to verify, got {:?}", other),
+        }
+    }
+
     #[test]
     fn commit_updates_state() {
         let correlation_id = CorrelationId::new();
@@ -247,7 +521,7 @@ mod tests {
             .collect();
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success { state_root, .. } => state_root,
             _ => panic!("commit failed"),
         };
 
@@ -278,7 +552,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success { state_root, .. } => state_root,
             _ => panic!("commit failed"),
         };
 
@@ -0,0 +1,200 @@
+//! Bloom filters of the keys present under state roots, letting reads of keys which are not in
+//! the state return without walking the trie.
+//!
+//! A filter never reports a key it was given as missing, but may report a missing key as
+//! present, in which case the trie is walked as usual.  With [`BITS_PER_KEY`] bits and
+//! [`HASH_COUNT`] hashes per key, about 1% of missing keys are reported as present while a filter
+//! holds no more keys than its capacity.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use contract_ffi::key::Key;
+use engine_shared::newtypes::Blake2bHash;
+
+/// Number of bits of a filter per key of its capacity.
+pub const BITS_PER_KEY: usize = 10;
+
+/// Number of bits set for each key.
+pub const HASH_COUNT: u64 = 7;
+
+/// Smallest capacity a filter is created with, so that a small state can grow for a while before
+/// its filter needs to be rebuilt.
+pub const MIN_CAPACITY: usize = 1024;
+
+/// Number of roots [`KeyBlooms`] keeps the filters of.
+pub const DEFAULT_MAX_ROOTS: usize = 16;
+
+/// A bloom filter of keys.
+#[derive(Debug, Clone)]
+pub struct KeyBloom {
+    bits: Vec<u64>,
+    capacity: usize,
+    len: usize,
+}
+
+impl KeyBloom {
+    /// Creates an empty filter sized for `capacity` keys, or [`MIN_CAPACITY`] if that is more.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(MIN_CAPACITY);
+        let words = (capacity * BITS_PER_KEY + 63) / 64;
+        KeyBloom {
+            bits: vec![0; words],
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Creates a filter of `keys`, sized for twice as many so that it can take the keys written by
+    /// later commits.
+    pub fn from_keys<'a, I>(keys: I) -> Self
+    where
+        I: ExactSizeIterator<Item = &'a Key>,
+    {
+        let mut key_bloom = KeyBloom::with_capacity(keys.len() * 2);
+        for key in keys {
+            key_bloom.insert(key);
+        }
+        key_bloom
+    }
+
+    /// Adds `key` to the filter.
+    pub fn insert(&mut self, key: &Key) {
+        for index in self.bit_indices(key) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+        self.len += 1;
+    }
+
+    /// Returns `false` if `key` was definitely not added to the filter.
+    pub fn might_contain(&self, key: &Key) -> bool {
+        self.bit_indices(key)
+            .all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// Returns `true` if `count` more keys can be added without exceeding the number of keys the
+    /// filter was sized for; otherwise it should be rebuilt.  Keys added more than once are
+    /// counted each time.
+    pub fn has_room_for(&self, count: usize) -> bool {
+        self.len + count <= self.capacity
+    }
+
+    /// Returns the bits set for `key`, derived from a single 64-bit hash by double hashing.
+    fn bit_indices(&self, key: &Key) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let first = hash & u64::from(u32::max_value());
+        let step = (hash >> 32) | 1;
+        let bit_count = (self.bits.len() * 64) as u64;
+        (0..HASH_COUNT)
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(step)) % bit_count) as usize)
+    }
+}
+
+/// The filters of the most recently committed or rebuilt roots, shared by an
+/// [`LmdbGlobalState`](::global_state::lmdb::LmdbGlobalState) and its checkouts.
+#[derive(Debug)]
+pub struct KeyBlooms {
+    max_roots: usize,
+    blooms: Mutex<VecDeque<(Blake2bHash, Arc<KeyBloom>)>>,
+}
+
+impl KeyBlooms {
+    /// Creates an empty set keeping the filters of at most `max_roots` roots.
+    pub fn new(max_roots: usize) -> Self {
+        KeyBlooms {
+            max_roots,
+            blooms: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Returns the filter of the keys under `root`, if it is kept.
+    pub fn get(&self, root: &Blake2bHash) -> Option<Arc<KeyBloom>> {
+        self.blooms
+            .lock()
+            .iter()
+            .rev()
+            .find(|(bloom_root, _)| bloom_root == root)
+            .map(|(_, key_bloom)| Arc::clone(key_bloom))
+    }
+
+    /// Keeps `key_bloom` as the filter of `root`, dropping the filter of the root inserted the
+    /// longest time ago if more than the maximum are kept.
+    pub fn insert(&self, root: Blake2bHash, key_bloom: Arc<KeyBloom>) {
+        let mut blooms = self.blooms.lock();
+        blooms.retain(|(bloom_root, _)| *bloom_root != root);
+        blooms.push_back((root, key_bloom));
+        while blooms.len() > self.max_roots {
+            blooms.pop_front();
+        }
+    }
+}
+
+impl Default for KeyBlooms {
+    fn default() -> Self {
+        KeyBlooms::new(DEFAULT_MAX_ROOTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(range: ::std::ops::Range<u32>) -> Vec<Key> {
+        range
+            .map(|i| {
+                let mut addr = [0u8; 32];
+                addr[..4].copy_from_slice(&i.to_le_bytes());
+                Key::Account(addr)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_contain_every_inserted_key() {
+        let present = keys(0..10_000);
+        let key_bloom = KeyBloom::from_keys(present.iter());
+        for key in &present {
+            assert!(key_bloom.might_contain(key), "missing {:?}", key);
+        }
+    }
+
+    #[test]
+    fn should_report_few_missing_keys_as_present() {
+        let key_bloom = KeyBloom::from_keys(keys(0..10_000).iter());
+        let false_positives = keys(10_000..20_000)
+            .iter()
+            .filter(|key| key_bloom.might_contain(key))
+            .count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn should_have_room_for_capacity_keys() {
+        let mut key_bloom = KeyBloom::with_capacity(0);
+        assert!(key_bloom.has_room_for(MIN_CAPACITY));
+        assert!(!key_bloom.has_room_for(MIN_CAPACITY + 1));
+        for key in &keys(0..MIN_CAPACITY as u32) {
+            key_bloom.insert(key);
+        }
+        assert!(key_bloom.has_room_for(0));
+        assert!(!key_bloom.has_room_for(1));
+    }
+
+    #[test]
+    fn should_keep_filters_of_most_recent_roots() {
+        let key_blooms = KeyBlooms::new(2);
+        let roots: Vec<Blake2bHash> = (0..3u8).map(|i| [i; 32].into()).collect();
+        for root in &roots {
+            key_blooms.insert(*root, Arc::new(KeyBloom::with_capacity(0)));
+        }
+        assert!(key_blooms.get(&roots[0]).is_none());
+        assert!(key_blooms.get(&roots[1]).is_some());
+        assert!(key_blooms.get(&roots[2]).is_some());
+    }
+}
@@ -1,32 +1,112 @@
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::Arc;
 
 use lmdb;
+use parking_lot::Mutex;
 
 use contract_ffi::key::Key;
 use contract_ffi::value::Value;
 use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 use engine_shared::transform::Transform;
 use error;
+use global_state::audit_log::{encode_effects, AuditLog};
+use global_state::genesis_root;
+use global_state::key_bloom::{KeyBloom, KeyBlooms};
+use global_state::state_roots::LmdbStateRoots;
 use global_state::StateReader;
-use global_state::{commit, CommitResult, History};
+use global_state::{
+    apply_effects, apply_effects_batch, apply_effects_group, CommitResult, History, PruneResult,
+    StoreStats, VerifyResult, WarmUpProgress, WriteStats,
+};
 use trie::operations::create_hashed_empty_trie;
 use trie::Trie;
-use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
-use trie_store::operations::{read, ReadResult};
+use trie_store::lmdb::{LazyRoTransaction, LmdbEnvironment, LmdbTrieStore};
+use trie_store::operations::{
+    diff, for_each_pair, read_all, read_with_node_count, write_with_stats, DiffResult, ReadResult,
+    WriteResult,
+};
 use trie_store::{Transaction, TransactionSource, TrieStore};
 
+/// Roots of the states checked out of an [`LmdbGlobalState`], each with the number of its
+/// checkouts which are still alive.
+type CheckedOutRoots = Arc<Mutex<HashMap<Blake2bHash, usize>>>;
+
+/// Counts a checkout of `root` for as long as it is alive, so that pruning keeps the trie it reads.
+struct CheckoutGuard {
+    root: Blake2bHash,
+    checked_out: CheckedOutRoots,
+}
+
+impl CheckoutGuard {
+    fn new(root: Blake2bHash, checked_out: &CheckedOutRoots) -> Self {
+        *checked_out.lock().entry(root).or_insert(0) += 1;
+        CheckoutGuard {
+            root,
+            checked_out: Arc::clone(checked_out),
+        }
+    }
+}
+
+impl Drop for CheckoutGuard {
+    fn drop(&mut self) {
+        let mut checked_out = self.checked_out.lock();
+        let remaining = match checked_out.get_mut(&self.root) {
+            Some(count) => {
+                *count -= 1;
+                *count
+            }
+            None => return,
+        };
+        if remaining == 0 {
+            checked_out.remove(&self.root);
+        }
+    }
+}
+
 /// Represents a "view" of global state at a particular root hash.
 pub struct LmdbGlobalState {
     pub(super) environment: Arc<LmdbEnvironment>,
     pub(super) store: Arc<LmdbTrieStore>,
     pub(super) root_hash: Blake2bHash,
     pub(super) empty_root_hash: Blake2bHash,
+    pub(super) state_roots: Option<Arc<LmdbStateRoots>>,
+    pub(super) audit_log: Option<Arc<AuditLog>>,
+    key_blooms: Option<Arc<KeyBlooms>>,
+    /// Filter of the keys under `root_hash`, if one is kept
+    key_bloom: Option<Arc<KeyBloom>>,
+    checked_out: CheckedOutRoots,
+    _checkout_guard: Option<CheckoutGuard>,
+}
+
+/// Clones share the same store.  A clone of a checkout keeps its root from being pruned for as long
+/// as it is alive, like the checkout itself.
+impl Clone for LmdbGlobalState {
+    fn clone(&self) -> Self {
+        LmdbGlobalState {
+            environment: Arc::clone(&self.environment),
+            store: Arc::clone(&self.store),
+            root_hash: self.root_hash,
+            empty_root_hash: self.empty_root_hash,
+            state_roots: self.state_roots.clone(),
+            audit_log: self.audit_log.clone(),
+            key_blooms: self.key_blooms.clone(),
+            key_bloom: self.key_bloom.clone(),
+            checked_out: Arc::clone(&self.checked_out),
+            _checkout_guard: self
+                ._checkout_guard
+                .as_ref()
+                .map(|guard| CheckoutGuard::new(guard.root, &self.checked_out)),
+        }
+    }
 }
 
 impl LmdbGlobalState {
     /// Creates an empty state from an existing environment and store.
+    ///
+    /// Roots committed to the returned state are recorded in the environment's
+    /// [`state_roots`](::global_state::state_roots) index, which is created if it is missing.
     pub fn empty(
         environment: Arc<LmdbEnvironment>,
         store: Arc<LmdbTrieStore>,
@@ -38,14 +118,138 @@ impl LmdbGlobalState {
             txn.commit()?;
             root_hash
         };
+        let state_roots = LmdbStateRoots::new(&environment, store.name())?;
+        Ok(LmdbGlobalState::new(
+            environment,
+            store,
+            root_hash,
+            root_hash,
+            Some(Arc::new(state_roots)),
+        ))
+    }
+
+    /// Creates a state from an existing environment and store without writing to it.
+    ///
+    /// Unlike [`LmdbGlobalState::empty`], the empty root is not persisted, so this is suitable
+    /// for environments opened with [`LmdbEnvironment::new_read_only`].
+    pub fn read_only(
+        environment: Arc<LmdbEnvironment>,
+        store: Arc<LmdbTrieStore>,
+    ) -> Result<Self, error::Error> {
+        let (root_hash, _root) = create_hashed_empty_trie::<Key, Value>()?;
+        let state_roots = LmdbStateRoots::open(&environment, store.name())?;
         Ok(LmdbGlobalState::new(
             environment,
             store,
             root_hash,
             root_hash,
+            state_roots.map(Arc::new),
         ))
     }
 
+    /// Creates a state from a given set of [`Key`](contract_ffi::key::Key),
+    /// [`Value`](contract_ffi::value::Value) pairs in an existing environment and store.
+    ///
+    /// The resulting root is recorded in the state root index like a committed one.
+    pub fn from_pairs(
+        environment: Arc<LmdbEnvironment>,
+        store: Arc<LmdbTrieStore>,
+        correlation_id: CorrelationId,
+        pairs: &[(Key, Value)],
+    ) -> Result<Self, error::Error> {
+        let mut ret = LmdbGlobalState::empty(environment, store)?;
+        {
+            let mut txn = ret.environment.create_read_write_txn()?;
+            let mut current_root = ret.root_hash;
+            let mut total_stats = WriteStats::default();
+            for (key, value) in pairs {
+                let key = key.normalize();
+                let (write_result, stats) = write_with_stats::<_, _, _, LmdbTrieStore, error::Error>(
+                    correlation_id,
+                    &mut txn,
+                    &ret.store,
+                    &current_root,
+                    &key,
+                    value,
+                )?;
+                match write_result {
+                    WriteResult::Written(root_hash) => {
+                        current_root = root_hash;
+                    }
+                    WriteResult::AlreadyExists => (),
+                    WriteResult::RootNotFound => panic!("LmdbGlobalState has invalid root"),
+                }
+                total_stats += stats;
+            }
+            let commit_result = CommitResult::Success {
+                state_root: current_root,
+                stats: total_stats,
+            };
+            ret.finish_commit(txn, ret.root_hash, None, commit_result)?;
+            ret.root_hash = current_root;
+        }
+        Ok(ret)
+    }
+
+    /// Returns every key/value pair under `root`, ordered by key bytes, or `None` if `root` is
+    /// not in the store.
+    pub fn read_all(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<Option<Vec<(Key, Value)>>, error::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let pairs = read_all::<Key, Value, lmdb::RoTransaction, LmdbTrieStore, error::Error>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &root,
+        )?;
+        txn.commit()?;
+        Ok(pairs)
+    }
+
+    /// Returns the most recently committed root recorded in the state root index, or `None` if
+    /// nothing was committed yet or there is no index.
+    pub fn latest_root(&self) -> Result<Option<Blake2bHash>, error::Error> {
+        let state_roots = match self.state_roots {
+            Some(ref state_roots) => state_roots,
+            None => return Ok(None),
+        };
+        let txn = self.environment.create_read_txn()?;
+        let len = state_roots.len(&txn)?;
+        let roots = if len == 0 {
+            Vec::new()
+        } else {
+            state_roots.list(&txn, len - 1, 1)?
+        };
+        txn.commit()?;
+        Ok(roots.into_iter().next())
+    }
+
+    /// Reads the trie at `root`, down to at most `max_depth` levels below it, so that the first
+    /// requests after opening the state do not wait on disk reads.  Calls `on_level` with the
+    /// progress made at the end of each level, and returns the progress made once done, or
+    /// `None` if `root` is not in the store.
+    pub fn warm_up<F>(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+        max_depth: u32,
+        on_level: F,
+    ) -> Result<Option<WarmUpProgress>, error::Error>
+    where
+        F: FnMut(&WarmUpProgress),
+    {
+        self.store.warm_up::<Key, Value, _>(
+            &self.environment,
+            correlation_id,
+            &root,
+            max_depth,
+            on_level,
+        )
+    }
+
     /// Creates a state from an existing environment, store, and root_hash.
     /// Intended to be used for testing.
     pub(crate) fn new(
@@ -53,13 +257,151 @@ impl LmdbGlobalState {
         store: Arc<LmdbTrieStore>,
         root_hash: Blake2bHash,
         empty_root_hash: Blake2bHash,
+        state_roots: Option<Arc<LmdbStateRoots>>,
     ) -> Self {
         LmdbGlobalState {
             environment,
             store,
             root_hash,
             empty_root_hash,
+            state_roots,
+            audit_log: None,
+            key_blooms: None,
+            key_bloom: None,
+            checked_out: Default::default(),
+            _checkout_guard: None,
+        }
+    }
+
+    /// Appends a record of every successful commit to `audit_log` from now on.
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(Arc::new(audit_log));
+        self
+    }
+
+    /// Keeps [bloom filters](::global_state::key_bloom) of the keys under the most recently
+    /// committed roots from now on, so that reads of missing keys from checkouts of those roots
+    /// return without walking the trie.  The filter of the current root is built right away,
+    /// reading all of its keys.
+    pub fn with_key_bloom(mut self, correlation_id: CorrelationId) -> Result<Self, error::Error> {
+        self.key_blooms = Some(Arc::new(KeyBlooms::default()));
+        let root_hash = self.root_hash;
+        self.key_bloom = self.build_key_bloom(correlation_id, root_hash)?;
+        Ok(self)
+    }
+
+    /// Returns the keys written by `effects` if key filters are kept, to be added to the filter of
+    /// the committed root.
+    fn written_keys(&self, effects: &[&HashMap<Key, Transform>]) -> Option<Vec<Key>> {
+        self.key_blooms.as_ref().map(|_| {
+            effects
+                .iter()
+                .flat_map(|effects| effects.keys().cloned())
+                .collect()
+        })
+    }
+
+    /// Builds the filter of the keys under `root` from the trie and keeps it, returning `None` if
+    /// key filters are not kept or `root` is not in the store.
+    fn build_key_bloom(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<Option<Arc<KeyBloom>>, error::Error> {
+        let key_blooms = match self.key_blooms {
+            Some(ref key_blooms) => key_blooms,
+            None => return Ok(None),
+        };
+        let pairs = match self.read_all(correlation_id, root)? {
+            Some(pairs) => pairs,
+            None => return Ok(None),
+        };
+        let key_bloom = Arc::new(KeyBloom::from_keys(pairs.iter().map(|(key, _)| key)));
+        key_blooms.insert(root, Arc::clone(&key_bloom));
+        Ok(Some(key_bloom))
+    }
+
+    /// Makes `state_root`, just committed on top of `prestate_hash` by writing `written_keys`,
+    /// the current root, along with its key filter.  The filter is the one of `prestate_hash`
+    /// with `written_keys` added, or is rebuilt from the trie if there is no such filter or it has
+    /// no room for them.
+    fn set_committed_root(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        state_root: Blake2bHash,
+        written_keys: Option<Vec<Key>>,
+    ) {
+        self.root_hash = state_root;
+        let (key_blooms, written_keys) = match (self.key_blooms.clone(), written_keys) {
+            (Some(key_blooms), Some(written_keys)) => (key_blooms, written_keys),
+            _ => return,
+        };
+        self.key_bloom = match key_blooms.get(&prestate_hash) {
+            Some(ref prestate_bloom) if prestate_bloom.has_room_for(written_keys.len()) => {
+                let mut key_bloom = KeyBloom::clone(prestate_bloom);
+                for key in &written_keys {
+                    key_bloom.insert(key);
+                }
+                let key_bloom = Arc::new(key_bloom);
+                key_blooms.insert(state_root, Arc::clone(&key_bloom));
+                Some(key_bloom)
+            }
+            // the commit is durable already, so failing to read the new root is not reported;
+            // reads of it walk the trie as they would without a filter
+            _ => self
+                .build_key_bloom(correlation_id, state_root)
+                .unwrap_or(None),
+        };
+    }
+
+    /// Returns the encoded effects to record in the audit log, if there is one.
+    fn audit_effects(
+        &self,
+        effects: &[&HashMap<Key, Transform>],
+    ) -> Result<Option<Vec<u8>>, error::Error> {
+        match self.audit_log {
+            Some(_) => encode_effects(effects).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Commits `txn` if `commit_result` is a success, recording the new root in the same
+    /// transaction so that the state root index never refers to a root which was not committed.
+    /// Otherwise `txn` is dropped, rolling back any changes.
+    ///
+    /// If `audit_effects` are given, the commit is appended to the audit log before `txn` is
+    /// committed, so that every committed root can be replayed from the log.
+    fn finish_commit(
+        &self,
+        mut txn: lmdb::RwTransaction,
+        prestate_hash: Blake2bHash,
+        audit_effects: Option<Vec<u8>>,
+        commit_result: CommitResult,
+    ) -> Result<CommitResult, error::Error> {
+        if let CommitResult::Success { state_root, .. } = commit_result {
+            self.record_commit(&mut txn, prestate_hash, state_root, audit_effects)?;
+            txn.commit()?;
+        }
+        Ok(commit_result)
+    }
+
+    /// Records `state_root`, committed on top of `prestate_hash`, in the state root index within
+    /// `txn`, and appends it to the audit log if `audit_effects` are given.
+    fn record_commit(
+        &self,
+        txn: &mut lmdb::RwTransaction,
+        prestate_hash: Blake2bHash,
+        state_root: Blake2bHash,
+        audit_effects: Option<Vec<u8>>,
+    ) -> Result<(), error::Error> {
+        if let Some(ref state_roots) = self.state_roots {
+            state_roots.append(txn, state_root)?;
+        }
+        if let (Some(audit_log), Some(effects_bytes)) = (&self.audit_log, audit_effects) {
+            audit_log.append(prestate_hash, state_root, &effects_bytes)?;
         }
+        Ok(())
     }
 }
 
@@ -67,17 +409,38 @@ impl StateReader<Key, Value> for LmdbGlobalState {
     type Error = error::Error;
 
     fn read(&self, correlation_id: CorrelationId, key: &Key) -> Result<Option<Value>, Self::Error> {
-        let txn = self.environment.create_read_txn()?;
-        let ret = match read::<Key, Value, lmdb::RoTransaction, LmdbTrieStore, Self::Error>(
+        self.read_with_node_count(correlation_id, key)
+            .map(|(maybe_value, _)| maybe_value)
+    }
+
+    fn read_with_node_count(
+        &self,
+        correlation_id: CorrelationId,
+        key: &Key,
+    ) -> Result<(Option<Value>, usize), Self::Error> {
+        if let Some(ref key_bloom) = self.key_bloom {
+            if !key_bloom.might_contain(key) {
+                return Ok((None, 0));
+            }
+        }
+        // nodes in the trie cache are read without a transaction
+        let txn = LazyRoTransaction::new(&self.environment);
+        let ret = match read_with_node_count::<
+            Key,
+            Value,
+            LazyRoTransaction,
+            LmdbTrieStore,
+            Self::Error,
+        >(
             correlation_id,
             &txn,
             self.store.deref(),
             &self.root_hash,
             key,
         )? {
-            ReadResult::Found(value) => Some(value),
-            ReadResult::NotFound => None,
-            ReadResult::RootNotFound => panic!("LmdbGlobalState has invalid root"),
+            (ReadResult::Found(value), nodes_visited) => (Some(value), nodes_visited),
+            (ReadResult::NotFound, nodes_visited) => (None, nodes_visited),
+            (ReadResult::RootNotFound, _) => panic!("LmdbGlobalState has invalid root"),
         };
         txn.commit()?;
         Ok(ret)
@@ -90,13 +453,25 @@ impl History for LmdbGlobalState {
     type Reader = Self;
 
     fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
-        let txn = self.environment.create_read_txn()?;
+        // counted before the root is looked up, so that a prune either keeps the root or has
+        // already deleted it
+        let checkout_guard = CheckoutGuard::new(prestate_hash, &self.checked_out);
+        let txn = LazyRoTransaction::new(&self.environment);
         let maybe_root: Option<Trie<Key, Value>> = self.store.get(&txn, &prestate_hash)?;
         let maybe_state = maybe_root.map(|_| LmdbGlobalState {
             environment: Arc::clone(&self.environment),
             store: Arc::clone(&self.store),
             root_hash: prestate_hash,
             empty_root_hash: self.empty_root_hash,
+            state_roots: self.state_roots.clone(),
+            audit_log: self.audit_log.clone(),
+            key_blooms: self.key_blooms.clone(),
+            key_bloom: self
+                .key_blooms
+                .as_ref()
+                .and_then(|key_blooms| key_blooms.get(&prestate_hash)),
+            checked_out: Arc::clone(&self.checked_out),
+            _checkout_guard: Some(checkout_guard),
         });
         txn.commit()?;
         Ok(maybe_state)
@@ -108,19 +483,86 @@ impl History for LmdbGlobalState {
         prestate_hash: Blake2bHash,
         effects: HashMap<Key, Transform>,
     ) -> Result<CommitResult, Self::Error> {
-        let commit_result = commit::<LmdbEnvironment, LmdbTrieStore, _, Self::Error>(
-            &self.environment,
+        let audit_effects = self.audit_effects(&[&effects])?;
+        let written_keys = self.written_keys(&[&effects]);
+        let mut txn = self.environment.create_read_write_txn()?;
+        let commit_result = apply_effects::<_, LmdbTrieStore, _, Self::Error>(
+            correlation_id,
+            &mut txn,
             &self.store,
+            prestate_hash,
+            effects,
+        )?;
+        let commit_result = self.finish_commit(txn, prestate_hash, audit_effects, commit_result)?;
+        if let CommitResult::Success { state_root, .. } = commit_result {
+            self.set_committed_root(correlation_id, prestate_hash, state_root, written_keys);
+        };
+        Ok(commit_result)
+    }
+
+    fn commit_batch(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: Vec<HashMap<Key, Transform>>,
+    ) -> Result<CommitResult, Self::Error> {
+        let audit_effects = self.audit_effects(&effects.iter().collect::<Vec<_>>())?;
+        let written_keys = self.written_keys(&effects.iter().collect::<Vec<_>>());
+        let mut txn = self.environment.create_read_write_txn()?;
+        let commit_result = apply_effects_batch::<_, LmdbTrieStore, _, Self::Error>(
             correlation_id,
+            &mut txn,
+            &self.store,
             prestate_hash,
             effects,
         )?;
-        if let CommitResult::Success(root_hash) = commit_result {
-            self.root_hash = root_hash;
+        let commit_result = self.finish_commit(txn, prestate_hash, audit_effects, commit_result)?;
+        if let CommitResult::Success { state_root, .. } = commit_result {
+            self.set_committed_root(correlation_id, prestate_hash, state_root, written_keys);
         };
         Ok(commit_result)
     }
 
+    fn commit_group(
+        &mut self,
+        correlation_id: CorrelationId,
+        commits: Vec<(Blake2bHash, HashMap<Key, Transform>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error> {
+        let mut audit_effects = Vec::with_capacity(commits.len());
+        let mut written_keys = Vec::with_capacity(commits.len());
+        let mut prestate_hashes = Vec::with_capacity(commits.len());
+        for (prestate_hash, effects) in &commits {
+            audit_effects.push(self.audit_effects(&[effects])?);
+            written_keys.push(self.written_keys(&[effects]));
+            prestate_hashes.push(*prestate_hash);
+        }
+        let mut txn = self.environment.create_read_write_txn()?;
+        let commit_results = apply_effects_group::<_, LmdbTrieStore, _, Self::Error>(
+            correlation_id,
+            &mut txn,
+            &self.store,
+            commits,
+        )?;
+        for (commit_result, (prestate_hash, audit_effects)) in commit_results
+            .iter()
+            .zip(prestate_hashes.iter().zip(audit_effects))
+        {
+            if let CommitResult::Success { state_root, .. } = commit_result {
+                self.record_commit(&mut txn, *prestate_hash, *state_root, audit_effects)?;
+            }
+        }
+        txn.commit()?;
+        for (commit_result, (prestate_hash, written_keys)) in commit_results
+            .iter()
+            .zip(prestate_hashes.into_iter().zip(written_keys))
+        {
+            if let CommitResult::Success { state_root, .. } = commit_result {
+                self.set_committed_root(correlation_id, prestate_hash, *state_root, written_keys);
+            }
+        }
+        Ok(commit_results)
+    }
+
     fn current_root(&self) -> Blake2bHash {
         self.root_hash
     }
@@ -128,14 +570,136 @@ impl History for LmdbGlobalState {
     fn empty_root(&self) -> Blake2bHash {
         self.empty_root_hash
     }
+
+    fn stats(&self) -> Result<Option<StoreStats>, Self::Error> {
+        self.environment.stats(&self.store).map(Some)
+    }
+
+    fn state_roots(
+        &self,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Option<Vec<Blake2bHash>>, Self::Error> {
+        let state_roots = match self.state_roots {
+            Some(ref state_roots) => state_roots,
+            None => return Ok(None),
+        };
+        let txn = self.environment.create_read_txn()?;
+        let roots = state_roots.list(&txn, offset, limit)?;
+        txn.commit()?;
+        Ok(Some(roots))
+    }
+
+    fn genesis_root(&self) -> Result<Option<Blake2bHash>, Self::Error> {
+        genesis_root::read(&self.environment, self.store.name())
+    }
+
+    fn record_genesis_root(&mut self, root: Blake2bHash) -> Result<(), Self::Error> {
+        genesis_root::write(&self.environment, self.store.name(), root)
+    }
+
+    fn copy_to(&self, path: &Path, compact: bool) -> Result<Option<u64>, Self::Error> {
+        self.environment.copy_to(path, compact).map(Some)
+    }
+
+    fn flush(&self) -> Result<(), Self::Error> {
+        self.environment.sync()
+    }
+
+    fn diff(
+        &self,
+        correlation_id: CorrelationId,
+        before_root: Blake2bHash,
+        after_root: Blake2bHash,
+        limit: usize,
+    ) -> Result<DiffResult<Key, Value>, Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let diff_result = diff::<Key, Value, _, LmdbTrieStore, Self::Error>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &before_root,
+            &after_root,
+            limit,
+        )?;
+        txn.commit()?;
+        Ok(diff_result)
+    }
+
+    fn for_each_pair<F>(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+        f: F,
+    ) -> Result<Option<bool>, Self::Error>
+    where
+        F: FnMut(Key, Value) -> bool,
+    {
+        let txn = self.environment.create_read_txn()?;
+        let visited = for_each_pair::<Key, Value, _, LmdbTrieStore, Self::Error, _>(
+            correlation_id,
+            &txn,
+            self.store.deref(),
+            &root,
+            f,
+        )?;
+        txn.commit()?;
+        Ok(visited)
+    }
+
+    fn verify(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<VerifyResult, Self::Error> {
+        self.store
+            .verify::<Key, Value>(&self.environment, correlation_id, &root)
+    }
+
+    /// Prunes the trie store in a single transaction, also removing the deleted roots from the
+    /// state root index.  Checkouts wait until it is done, so that none starts reading a root as
+    /// it is deleted.
+    fn prune(
+        &mut self,
+        correlation_id: CorrelationId,
+        retain: &[Blake2bHash],
+    ) -> Result<Option<PruneResult>, Self::Error> {
+        let checked_out = self.checked_out.lock();
+        let mut roots = retain.to_vec();
+        roots.push(self.root_hash);
+        roots.push(self.empty_root_hash);
+        roots.extend(checked_out.keys());
+        roots.sort();
+        roots.dedup();
+
+        let mut txn = self.environment.create_read_write_txn()?;
+        let prune_result = self
+            .store
+            .prune::<Key, Value>(&mut txn, correlation_id, &roots)?;
+        if let PruneResult::Pruned { .. } = prune_result {
+            if let Some(ref state_roots) = self.state_roots {
+                state_roots.retain(&mut txn, |root| roots.binary_search(root).is_ok())?;
+            }
+            txn.commit()?;
+        }
+        Ok(Some(prune_result))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use libc;
     use lmdb::DatabaseFlags;
     use tempfile::tempdir;
 
-    use trie_store::operations::{write, WriteResult};
+    use engine_shared::os::get_page_size;
+    use global_state::{audit_log, pairs_file};
+    use trie_store::lmdb::DEFAULT_MAX_READERS;
+    use trie_store::operations::write;
     use TEST_MAP_SIZE;
 
     use super::*;
@@ -178,7 +742,12 @@ mod tests {
         let correlation_id = CorrelationId::new();
         let _temp_dir = tempdir().unwrap();
         let environment = Arc::new(
-            LmdbEnvironment::new(&_temp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap(),
+            LmdbEnvironment::new(
+                &_temp_dir.path().to_path_buf(),
+                *TEST_MAP_SIZE,
+                DEFAULT_MAX_READERS,
+            )
+            .unwrap(),
         );
         let store =
             Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
@@ -212,6 +781,572 @@ mod tests {
         ret
     }
 
+    #[test]
+    fn copy_should_hold_the_same_state() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+
+        for &compact in &[false, true] {
+            let backup_dir = tempdir().unwrap();
+            let size = state
+                .copy_to(backup_dir.path(), compact)
+                .unwrap()
+                .expect("should copy");
+            assert!(size > 0);
+
+            let environment = Arc::new(
+                LmdbEnvironment::new_read_only(
+                    &backup_dir.path().to_path_buf(),
+                    *TEST_MAP_SIZE,
+                    DEFAULT_MAX_READERS,
+                )
+                .unwrap(),
+            );
+            let store = Arc::new(LmdbTrieStore::open(&environment, None).unwrap());
+            let copy = LmdbGlobalState::read_only(environment, store).unwrap();
+
+            let checkout = copy.checkout(state.root_hash).unwrap().unwrap();
+            for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+                assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn exported_pairs_should_import_to_the_same_root() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+
+        let pairs = state
+            .read_all(correlation_id, state.root_hash)
+            .unwrap()
+            .expect("root should exist");
+        let mut file = Vec::new();
+        pairs_file::write_pairs(&mut file, &pairs).unwrap();
+
+        let imported_pairs = pairs_file::read_pairs(&mut file.as_slice()).unwrap();
+        let temp_dir = tempdir().unwrap();
+        let environment = Arc::new(
+            LmdbEnvironment::new(
+                &temp_dir.path().to_path_buf(),
+                *TEST_MAP_SIZE,
+                DEFAULT_MAX_READERS,
+            )
+            .unwrap(),
+        );
+        let store =
+            Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+        let imported =
+            LmdbGlobalState::from_pairs(environment, store, correlation_id, &imported_pairs)
+                .unwrap();
+
+        assert_eq!(imported.root_hash, state.root_hash);
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            assert_eq!(Some(value), imported.read(correlation_id, &key).unwrap());
+        }
+        assert_eq!(
+            imported.state_roots(0, 10).unwrap(),
+            Some(vec![state.root_hash])
+        );
+    }
+
+    #[test]
+    fn flushed_no_sync_state_should_reopen() {
+        let correlation_id = CorrelationId::new();
+        let pairs: Vec<(Key, Value)> = TEST_PAIRS
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, value))
+            .collect();
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+
+        let root_hash = {
+            let environment = Arc::new(
+                LmdbEnvironment::new_no_sync(&path, *TEST_MAP_SIZE, DEFAULT_MAX_READERS).unwrap(),
+            );
+            let store =
+                Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+            let state =
+                LmdbGlobalState::from_pairs(environment, store, correlation_id, &pairs).unwrap();
+            state.flush().unwrap();
+            state.root_hash
+        };
+
+        let environment = Arc::new(
+            LmdbEnvironment::new_read_only(&path, *TEST_MAP_SIZE, DEFAULT_MAX_READERS).unwrap(),
+        );
+        let store = Arc::new(LmdbTrieStore::open(&environment, None).unwrap());
+        let state = LmdbGlobalState::read_only(environment, store).unwrap();
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+    }
+
+    #[test]
+    fn environment_open_should_retry_transient_errors() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let mut attempts = 0;
+
+        let result = LmdbEnvironment::open_with_retries(3, Duration::from_millis(1), || {
+            attempts += 1;
+            if attempts < 3 {
+                return Err(error::Error::Lmdb(lmdb::Error::Invalid));
+            }
+            LmdbEnvironment::new(&path, *TEST_MAP_SIZE, DEFAULT_MAX_READERS)
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn environment_open_should_give_up_after_retries() {
+        let mut attempts = 0;
+
+        let result = LmdbEnvironment::open_with_retries(2, Duration::from_millis(1), || {
+            attempts += 1;
+            Err(error::Error::Lmdb(lmdb::Error::Other(libc::EAGAIN)))
+        });
+
+        assert_eq!(
+            result.err(),
+            Some(error::Error::Lmdb(lmdb::Error::Other(libc::EAGAIN)))
+        );
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn environment_open_should_not_retry_permanent_errors() {
+        let mut attempts = 0;
+
+        let result = LmdbEnvironment::open_with_retries(3, Duration::from_millis(1), || {
+            attempts += 1;
+            Err(error::Error::Lmdb(lmdb::Error::Corrupted))
+        });
+
+        assert_eq!(
+            result.err(),
+            Some(error::Error::Lmdb(lmdb::Error::Corrupted))
+        );
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn named_stores_should_hold_independent_states() {
+        let correlation_id = CorrelationId::new();
+        let pairs: Vec<(Key, Value)> = TEST_PAIRS
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, value))
+            .collect();
+        let temp_dir = tempdir().unwrap();
+        let environment = Arc::new(
+            LmdbEnvironment::new(
+                &temp_dir.path().to_path_buf(),
+                *TEST_MAP_SIZE,
+                DEFAULT_MAX_READERS,
+            )
+            .unwrap(),
+        );
+        let open_store = |name| {
+            Arc::new(LmdbTrieStore::new(&environment, Some(name), DatabaseFlags::empty()).unwrap())
+        };
+
+        let loaded = LmdbGlobalState::from_pairs(
+            Arc::clone(&environment),
+            open_store("loaded"),
+            correlation_id,
+            &pairs,
+        )
+        .unwrap();
+        let other = LmdbGlobalState::empty(Arc::clone(&environment), open_store("other")).unwrap();
+
+        assert!(loaded.checkout(loaded.root_hash).unwrap().is_some());
+        assert!(other.checkout(loaded.root_hash).unwrap().is_none());
+        assert_eq!(
+            loaded.state_roots(0, 10).unwrap(),
+            Some(vec![loaded.root_hash])
+        );
+        assert_eq!(other.state_roots(0, 10).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn opening_missing_named_store_should_fail() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        LmdbEnvironment::new(&path, *TEST_MAP_SIZE, DEFAULT_MAX_READERS).unwrap();
+
+        let environment =
+            LmdbEnvironment::new_read_only(&path, *TEST_MAP_SIZE, DEFAULT_MAX_READERS).unwrap();
+        assert_eq!(
+            LmdbTrieStore::open(&environment, Some("missing")).unwrap_err(),
+            error::Error::StoreNotFound("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn state_roots_names_should_be_reserved() {
+        let temp_dir = tempdir().unwrap();
+        let environment = LmdbEnvironment::new(
+            &temp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
+        assert_eq!(
+            LmdbTrieStore::new(&environment, Some("state-roots"), DatabaseFlags::empty())
+                .unwrap_err(),
+            error::Error::ReservedStoreName("state-roots".to_string())
+        );
+    }
+
+    #[test]
+    fn read_all_should_return_none_for_unknown_root() {
+        let state = create_test_state();
+        let fake_hash: Blake2bHash = [1u8; 32].into();
+        assert_eq!(
+            state.read_all(CorrelationId::new(), fake_hash).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn for_each_pair_should_stop_when_told() {
+        let state = create_test_state();
+        let mut keys = Vec::new();
+        let visited = state
+            .for_each_pair(CorrelationId::new(), state.root_hash, |key, _| {
+                keys.push(key);
+                false
+            })
+            .unwrap();
+        assert_eq!(visited, Some(false));
+
+        let all_pairs = state
+            .read_all(CorrelationId::new(), state.root_hash)
+            .unwrap()
+            .unwrap();
+        let all_keys: Vec<Key> = all_pairs.into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys[..], all_keys[..1]);
+    }
+
+    #[test]
+    fn stats_should_count_trie_entries() {
+        let state = create_test_state();
+        let stats = state.stats().unwrap().expect("should have stats");
+        // the empty root plus at least a leaf per pair
+        assert!(stats.entries > TEST_PAIRS.len() as u64);
+        assert!(stats.used_pages > 0);
+        assert_eq!(stats.map_size, *TEST_MAP_SIZE as u64);
+    }
+
+    #[test]
+    fn commit_records_state_roots_in_order() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(vec![]));
+
+        let mut expected = Vec::new();
+        for TestPair { key, value } in create_test_pairs_updated().iter().cloned() {
+            let mut effects = HashMap::new();
+            effects.insert(key, Transform::Write(value));
+            let root_hash = state.current_root();
+            match state.commit(correlation_id, root_hash, effects).unwrap() {
+                CommitResult::Success { state_root, .. } => expected.push(state_root),
+                _ => panic!("commit failed"),
+            }
+        }
+
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(expected.clone()));
+        assert_eq!(
+            state.state_roots(1, 1).unwrap(),
+            Some(expected[1..2].to_vec())
+        );
+        assert_eq!(state.state_roots(5, 10).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn commit_batch_applies_effects_in_order() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let key = Key::Account([1u8; 32]);
+
+        let effects: Vec<HashMap<Key, Transform>> = vec![
+            vec![(key, Transform::Write(Value::Int32(10)))]
+                .into_iter()
+                .collect(),
+            vec![(key, Transform::AddInt32(5))].into_iter().collect(),
+        ];
+
+        let updated_hash = match state
+            .commit_batch(correlation_id, root_hash, effects)
+            .unwrap()
+        {
+            CommitResult::Success { state_root, .. } => state_root,
+            _ => panic!("commit failed"),
+        };
+
+        let updated_checkout = state.checkout(updated_hash).unwrap().unwrap();
+        assert_eq!(
+            Some(Value::Int32(15)),
+            updated_checkout.read(correlation_id, &key).unwrap()
+        );
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(vec![updated_hash]));
+    }
+
+    #[test]
+    fn commit_group_commits_each_set_on_its_own_prestate() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let key = Key::Account([1u8; 32]);
+        let missing_key = Key::Account([4u8; 32]);
+        let effects = |transform: Transform| -> HashMap<Key, Transform> {
+            vec![(key, transform)].into_iter().collect()
+        };
+
+        let commits = vec![
+            (root_hash, effects(Transform::Write(Value::Int32(10)))),
+            (
+                root_hash,
+                vec![(missing_key, Transform::AddInt32(1))]
+                    .into_iter()
+                    .collect(),
+            ),
+            (root_hash, effects(Transform::Write(Value::Int32(20)))),
+        ];
+        let commit_results = state.commit_group(correlation_id, commits).unwrap();
+
+        assert_eq!(commit_results.len(), 3);
+        match commit_results[1] {
+            CommitResult::KeyNotFound(failed_key) => assert_eq!(failed_key, missing_key),
+            ref other => panic!("second commit should have failed, got {:?}", other),
+        }
+        let state_roots: Vec<Blake2bHash> = [&commit_results[0], &commit_results[2]]
+            .iter()
+            .map(|commit_result| match commit_result {
+                CommitResult::Success { state_root, .. } => *state_root,
+                other => panic!("commit failed: {:?}", other),
+            })
+            .collect();
+        for (state_root, expected) in state_roots.iter().zip(&[10, 20]) {
+            let checkout = state.checkout(*state_root).unwrap().unwrap();
+            assert_eq!(
+                Some(Value::Int32(*expected)),
+                checkout.read(correlation_id, &key).unwrap()
+            );
+        }
+        assert_eq!(state.current_root(), state_roots[1]);
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(state_roots));
+    }
+
+    #[test]
+    fn commit_reports_nodes_and_bytes_written() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let entries_before = state.stats().unwrap().unwrap().entries;
+        let effects: HashMap<Key, Transform> =
+            vec![(Key::Account([5u8; 32]), Transform::Write(Value::Int32(5)))]
+                .into_iter()
+                .collect();
+
+        let root_hash = state.current_root();
+        let (updated_hash, stats) = match state
+            .commit(correlation_id, root_hash, effects.clone())
+            .unwrap()
+        {
+            CommitResult::Success { state_root, stats } => (state_root, stats),
+            _ => panic!("commit failed"),
+        };
+
+        let entries_after = state.stats().unwrap().unwrap().entries;
+        assert_eq!(stats.nodes, entries_after - entries_before);
+        assert!(stats.bytes > stats.nodes);
+
+        // rewriting the same value leaves the trie as it is
+        match state.commit(correlation_id, updated_hash, effects).unwrap() {
+            CommitResult::Success { state_root, stats } => {
+                assert_eq!(state_root, updated_hash);
+                assert_eq!(stats, WriteStats::default());
+            }
+            _ => panic!("commit failed"),
+        }
+    }
+
+    #[test]
+    fn commit_batch_rolls_back_on_failure() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let written_key = Key::Account([3u8; 32]);
+        let missing_key = Key::Account([4u8; 32]);
+
+        let effects: Vec<HashMap<Key, Transform>> = vec![
+            vec![(written_key, Transform::Write(Value::Int32(3)))]
+                .into_iter()
+                .collect(),
+            vec![(missing_key, Transform::AddInt32(1))]
+                .into_iter()
+                .collect(),
+        ];
+
+        match state
+            .commit_batch(correlation_id, root_hash, effects)
+            .unwrap()
+        {
+            CommitResult::KeyNotFound(key) => assert_eq!(key, missing_key),
+            _ => panic!("commit should have failed"),
+        }
+
+        assert_eq!(state.current_root(), root_hash);
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn commit_appends_successful_commits_to_audit_log() {
+        let correlation_id = CorrelationId::new();
+        let audit_dir = tempdir().unwrap();
+        let audit_path = audit_dir.path().join("audit.log");
+        let mut state = create_test_state().with_audit_log(AuditLog::open(&audit_path).unwrap());
+        let root_hash = state.root_hash;
+        let effects: HashMap<Key, Transform> =
+            vec![(Key::Account([1u8; 32]), Transform::AddInt32(5))]
+                .into_iter()
+                .collect();
+
+        let updated_hash = match state
+            .commit(correlation_id, root_hash, effects.clone())
+            .unwrap()
+        {
+            CommitResult::Success { state_root, .. } => state_root,
+            _ => panic!("commit failed"),
+        };
+        let failing_effects = vec![vec![(Key::Account([4u8; 32]), Transform::AddInt32(1))]
+            .into_iter()
+            .collect()];
+        match state
+            .commit_batch(correlation_id, updated_hash, failing_effects)
+            .unwrap()
+        {
+            CommitResult::KeyNotFound(_) => (),
+            _ => panic!("commit should have failed"),
+        }
+
+        let records =
+            audit_log::read_records(&mut ::std::fs::File::open(&audit_path).unwrap()).unwrap();
+        assert_eq!(
+            records,
+            vec![audit_log::AuditRecord {
+                prestate_hash: root_hash,
+                state_root: updated_hash,
+                effects: vec![effects],
+            }]
+        );
+    }
+
+    #[test]
+    fn commit_fails_with_out_of_space_error_when_map_is_full() {
+        let correlation_id = CorrelationId::new();
+        let temp_dir = tempdir().unwrap();
+        let map_size = get_page_size().unwrap() * 64;
+        let environment = Arc::new(
+            LmdbEnvironment::new(
+                &temp_dir.path().to_path_buf(),
+                map_size,
+                DEFAULT_MAX_READERS,
+            )
+            .unwrap(),
+        );
+        let store =
+            Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+        let mut state = LmdbGlobalState::empty(environment, store).unwrap();
+        let root_hash = state.root_hash;
+
+        let large_value = Value::String("a".repeat(16 * 1024));
+        let effects: HashMap<Key, Transform> = (0..64u8)
+            .map(|i| (Key::Account([i; 32]), Transform::Write(large_value.clone())))
+            .collect();
+
+        match state.commit(correlation_id, root_hash, effects) {
+            Err(error) => assert!(error.is_out_of_space(), "unexpected error {:?}", error),
+            Ok(commit_result) => panic!("commit should have failed: {:?}", commit_result),
+        }
+        assert_eq!(state.current_root(), root_hash);
+
+        // a commit which fits still succeeds
+        let effects: HashMap<Key, Transform> =
+            vec![(Key::Account([1u8; 32]), Transform::Write(Value::Int32(1)))]
+                .into_iter()
+                .collect();
+        match state.commit(correlation_id, root_hash, effects).unwrap() {
+            CommitResult::Success { .. } => (),
+            commit_result => panic!("commit should have succeeded: {:?}", commit_result),
+        }
+    }
+
+    #[test]
+    fn checkout_fails_with_readers_full_error_when_reader_slots_are_taken() {
+        let temp_dir = tempdir().unwrap();
+        let environment = Arc::new(
+            LmdbEnvironment::new(&temp_dir.path().to_path_buf(), *TEST_MAP_SIZE, 1).unwrap(),
+        );
+        let store =
+            Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+        let state = LmdbGlobalState::empty(Arc::clone(&environment), store).unwrap();
+
+        {
+            let _txn = environment.create_read_txn().unwrap();
+            match state.checkout(state.root_hash) {
+                Err(error) => assert!(error.is_readers_full(), "unexpected error {:?}", error),
+                Ok(_) => panic!("checkout should have failed"),
+            }
+        }
+
+        // the slot is free again once the other read is done
+        assert!(state.checkout(state.root_hash).unwrap().is_some());
+    }
+
+    #[test]
+    fn cached_reads_do_not_need_a_reader_slot() {
+        let correlation_id = CorrelationId::new();
+        let temp_dir = tempdir().unwrap();
+        let environment = Arc::new(
+            LmdbEnvironment::new(&temp_dir.path().to_path_buf(), *TEST_MAP_SIZE, 1).unwrap(),
+        );
+        let store = Arc::new(
+            LmdbTrieStore::new(&environment, None, DatabaseFlags::empty())
+                .unwrap()
+                .with_cache(100),
+        );
+        let mut state = LmdbGlobalState::empty(Arc::clone(&environment), store).unwrap();
+        let empty_root_hash = state.root_hash;
+        let (key, value) = (Key::Account([1u8; 32]), Value::Int32(1));
+        let effects: HashMap<Key, Transform> = vec![(key, Transform::Write(value.clone()))]
+            .into_iter()
+            .collect();
+        let root_hash = match state
+            .commit(correlation_id, empty_root_hash, effects)
+            .unwrap()
+        {
+            CommitResult::Success { state_root, .. } => state_root,
+            commit_result => panic!("commit should have succeeded: {:?}", commit_result),
+        };
+        // the first checkout and read fill the cache
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        assert_eq!(
+            checkout.read(correlation_id, &key).unwrap(),
+            Some(value.clone())
+        );
+
+        let _txn = environment.create_read_txn().unwrap();
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        assert_eq!(checkout.read(correlation_id, &key).unwrap(), Some(value));
+    }
+
     #[test]
     fn reads_from_a_checkout_return_expected_values() {
         let correlation_id = CorrelationId::new();
@@ -247,7 +1382,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success { state_root, .. } => state_root,
             _ => panic!("commit failed"),
         };
 
@@ -278,7 +1413,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success { state_root, .. } => state_root,
             _ => panic!("commit failed"),
         };
 
@@ -304,4 +1439,205 @@ mod tests {
                 .unwrap()
         );
     }
+
+    fn commit_updated_pairs(state: &mut LmdbGlobalState) -> Blake2bHash {
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .map(|TestPair { key, value }| (*key, Transform::Write(value.to_owned())))
+            .collect();
+        let root_hash = state.root_hash;
+        match state
+            .commit(CorrelationId::new(), root_hash, effects)
+            .unwrap()
+        {
+            CommitResult::Success { state_root, .. } => state_root,
+            _ => panic!("commit failed"),
+        }
+    }
+
+    #[test]
+    fn key_bloom_reads_match_reads_without_it() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state().with_key_bloom(correlation_id).unwrap();
+        let root_before = state.root_hash;
+        let root_after = commit_updated_pairs(&mut state);
+        let without_key_bloom = LmdbGlobalState::new(
+            Arc::clone(&state.environment),
+            Arc::clone(&state.store),
+            state.root_hash,
+            state.empty_root_hash,
+            None,
+        );
+        let keys = [
+            Key::Account([1u8; 32]),
+            Key::Account([2u8; 32]),
+            Key::Account([3u8; 32]),
+            Key::Account([4u8; 32]),
+            Key::Hash([1u8; 32]),
+        ];
+
+        for (root, present_count) in &[(root_before, 2), (root_after, 3)] {
+            let checkout = state.checkout(*root).unwrap().unwrap();
+            assert!(checkout.key_bloom.is_some());
+            let expected_checkout = without_key_bloom.checkout(*root).unwrap().unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                let value = checkout.read(correlation_id, key).unwrap();
+                assert_eq!(value, expected_checkout.read(correlation_id, key).unwrap());
+                assert_eq!(value.is_some(), i < *present_count, "{:?}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn key_bloom_is_rebuilt_on_commit_to_root_without_one() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        state.key_blooms = Some(Arc::new(KeyBlooms::default()));
+        let root_hash = commit_updated_pairs(&mut state);
+
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        assert!(checkout.key_bloom.is_some());
+        for TestPair { key, value } in create_test_pairs_updated().iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+        assert_eq!(
+            None,
+            checkout
+                .read(correlation_id, &Key::Account([4u8; 32]))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn genesis_root_is_only_what_was_recorded() {
+        let mut state = create_test_state();
+        let updated_hash = commit_updated_pairs(&mut state);
+        // neither the seeded nor the committed root is taken for genesis
+        assert_eq!(state.genesis_root().unwrap(), None);
+
+        state.record_genesis_root(updated_hash).unwrap();
+        let reopened =
+            LmdbGlobalState::read_only(Arc::clone(&state.environment), Arc::clone(&state.store))
+                .unwrap();
+        assert_eq!(reopened.genesis_root().unwrap(), Some(updated_hash));
+    }
+
+    #[test]
+    fn prune_deletes_nodes_unreachable_from_kept_roots() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let updated_hash = commit_updated_pairs(&mut state);
+        let entries_before = state.stats().unwrap().unwrap().entries;
+
+        match state.prune(correlation_id, &[]).unwrap() {
+            Some(PruneResult::Pruned {
+                nodes_kept,
+                nodes_deleted,
+                bytes_deleted,
+            }) => {
+                assert!(nodes_deleted > 0);
+                assert!(bytes_deleted > 0);
+                assert_eq!(nodes_kept + nodes_deleted, entries_before);
+            }
+            other => panic!("expected the store to be pruned, got {:?}", other),
+        }
+
+        // the current root is kept, and no longer shares the store with the original one
+        assert!(state.checkout(root_hash).unwrap().is_none());
+        let updated_checkout = state.checkout(updated_hash).unwrap().unwrap();
+        for TestPair { key, value } in create_test_pairs_updated().iter().cloned() {
+            assert_eq!(
+                Some(value),
+                updated_checkout.read(correlation_id, &key).unwrap()
+            );
+        }
+        match state.verify(correlation_id, updated_hash).unwrap() {
+            VerifyResult::Verified { .. } => (),
+            other => panic!("expected the kept state to verify, got {:?}", other),
+        }
+        assert_eq!(state.state_roots(0, 10).unwrap(), Some(vec![updated_hash]));
+    }
+
+    #[test]
+    fn prune_keeps_roots_of_live_checkouts() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        commit_updated_pairs(&mut state);
+
+        state.prune(correlation_id, &[]).unwrap();
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+
+        drop(checkout);
+        state.prune(correlation_id, &[]).unwrap();
+        assert!(state.checkout(root_hash).unwrap().is_none());
+    }
+
+    #[test]
+    fn prune_deletes_nothing_if_a_kept_root_is_missing() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        commit_updated_pairs(&mut state);
+        let entries_before = state.stats().unwrap().unwrap().entries;
+
+        let fake_hash: Blake2bHash = [1u8; 32].into();
+        assert_eq!(
+            state
+                .prune(correlation_id, &[root_hash, fake_hash])
+                .unwrap(),
+            Some(PruneResult::RootNotFound(fake_hash))
+        );
+        assert_eq!(state.stats().unwrap().unwrap().entries, entries_before);
+        assert!(state.checkout(root_hash).unwrap().is_some());
+    }
+
+    #[test]
+    fn warm_up_reads_latest_committed_root() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let updated_hash = commit_updated_pairs(&mut state);
+        assert_eq!(state.latest_root().unwrap(), Some(updated_hash));
+
+        let node_count = match state.verify(correlation_id, updated_hash).unwrap() {
+            VerifyResult::Verified { node_count } => node_count,
+            other => panic!("expected the state to verify, got {:?}", other),
+        };
+        let mut levels = 0;
+        let progress = state
+            .warm_up(correlation_id, updated_hash, u32::max_value(), |_| {
+                levels += 1
+            })
+            .unwrap()
+            .unwrap();
+        assert!(progress.complete);
+        assert_eq!(progress.node_count, node_count);
+        assert_eq!(levels, progress.depth + 1);
+    }
+
+    #[test]
+    fn checkout_reads_while_a_write_transaction_is_open() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+        let root_hash = state.root_hash;
+        let reader = state.clone();
+        // stands in for a commit in progress
+        let txn = state.environment.create_read_write_txn().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let checkout = reader.checkout(root_hash).unwrap().unwrap();
+            let value = checkout.read(correlation_id, &TEST_PAIRS[0].key).unwrap();
+            sender.send(value).unwrap();
+        });
+        let value = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("read should not wait for the write transaction");
+        assert_eq!(value, Some(TEST_PAIRS[0].value.clone()));
+        drop(txn);
+    }
 }
@@ -1,19 +1,29 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::BuildHasher;
+use std::path::Path;
 use std::time::Instant;
 
 use contract_ffi::key::Key;
 use contract_ffi::value::Value;
-use engine_shared::logging::{log_duration, log_metric, GAUGE};
+use engine_shared::logging::GAUGE;
 use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 use engine_shared::transform::{self, Transform, TypeMismatch};
 use trie::Trie;
-use trie_store::operations::{read, write, ReadResult, WriteResult};
-use trie_store::{Transaction, TransactionSource, TrieStore};
+use trie_store::operations::{read, write_with_stats, ReadResult, WriteResult};
+use trie_store::{Readable, Transaction, TransactionSource, TrieStore, Writable};
 
+pub use trie_store::operations::{
+    DiffResult, KeyDiff, PruneResult, VerifyResult, WarmUpProgress, WriteStats,
+};
+
+pub mod audit_log;
+pub mod genesis_root;
 pub mod in_memory;
+pub mod key_bloom;
 pub mod lmdb;
+pub mod pairs_file;
+pub mod state_roots;
 
 /// A reader of state
 pub trait StateReader<K, V> {
@@ -22,12 +32,28 @@ pub trait StateReader<K, V> {
 
     /// Returns the state value from the corresponding key
     fn read(&self, correlation_id: CorrelationId, key: &K) -> Result<Option<V>, Self::Error>;
+
+    /// Like `read`, but also returns the number of trie nodes visited to find the value.  Readers
+    /// which do not walk a trie count every read as a single node.
+    fn read_with_node_count(
+        &self,
+        correlation_id: CorrelationId,
+        key: &K,
+    ) -> Result<(Option<V>, usize), Self::Error> {
+        self.read(correlation_id, key)
+            .map(|maybe_value| (maybe_value, 1))
+    }
 }
 
 #[derive(Debug)]
 pub enum CommitResult {
     RootNotFound,
-    Success(Blake2bHash),
+    /// The effects were applied, yielding the state at `state_root`, and `stats` describes the
+    /// trie nodes written to do so.
+    Success {
+        state_root: Blake2bHash,
+        stats: WriteStats,
+    },
     KeyNotFound(Key),
     TypeMismatch(TypeMismatch),
 }
@@ -36,7 +62,11 @@ impl fmt::Display for CommitResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
             CommitResult::RootNotFound => write!(f, "Root not found"),
-            CommitResult::Success(hash) => write!(f, "Success: {}", hash),
+            CommitResult::Success { state_root, stats } => write!(
+                f,
+                "Success: {} ({} nodes, {} bytes written)",
+                state_root, stats.nodes, stats.bytes
+            ),
             CommitResult::KeyNotFound(key) => write!(f, "Key not found: {}", key),
             CommitResult::TypeMismatch(type_mismatch) => {
                 write!(f, "Type mismatch: {:?}", type_mismatch)
@@ -55,6 +85,19 @@ impl From<transform::Error> for CommitResult {
     }
 }
 
+/// Size statistics of the store backing global state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreStats {
+    /// Size of a database page in bytes
+    pub page_size: u64,
+    /// Number of entries in the trie store
+    pub entries: u64,
+    /// Number of pages in use, including free pages not yet reclaimed
+    pub used_pages: u64,
+    /// Size of the memory map in bytes
+    pub map_size: u64,
+}
+
 pub trait History {
     type Error;
     type Reader: StateReader<Key, Value, Error = Self::Error>;
@@ -62,7 +105,8 @@ pub trait History {
     /// Checkouts to the post state of a specific block.
     fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error>;
 
-    /// Applies changes and returns a new post state hash.
+    /// Applies changes and returns a new post state hash, along with the number and total size of
+    /// the trie nodes written.
     /// block_hash is used for computing a deterministic and unique keys.
     fn commit(
         &mut self,
@@ -71,9 +115,100 @@ pub trait History {
         effects: HashMap<Key, Transform>,
     ) -> Result<CommitResult, Self::Error>;
 
+    /// Applies each set of changes on top of the post state of the previous one, starting from
+    /// `prestate_hash`, and returns the final post state hash.
+    ///
+    /// All sets are committed in one transaction: if any of them fails, none are.
+    fn commit_batch(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: Vec<HashMap<Key, Transform>>,
+    ) -> Result<CommitResult, Self::Error>;
+
+    /// Applies each set of changes on top of its own prestate hash and returns the result of each,
+    /// in order.
+    ///
+    /// All sets are committed in one transaction, but unlike with
+    /// [`commit_batch`](History::commit_batch) they are independent: a set which fails does not
+    /// stop the others from being committed.  Trie nodes written by a set before it failed are
+    /// kept, unreachable from any root, until they are pruned.
+    fn commit_group(
+        &mut self,
+        correlation_id: CorrelationId,
+        commits: Vec<(Blake2bHash, HashMap<Key, Transform>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error>;
+
     fn current_root(&self) -> Blake2bHash;
 
     fn empty_root(&self) -> Blake2bHash;
+
+    /// Returns size statistics of the backing store, or `None` if it does not keep any.
+    fn stats(&self) -> Result<Option<StoreStats>, Self::Error>;
+
+    /// Returns up to `limit` committed state roots in commit order, skipping the first `offset`,
+    /// or `None` if the backing store does not keep a history of roots.
+    fn state_roots(&self, offset: u64, limit: u64)
+        -> Result<Option<Vec<Blake2bHash>>, Self::Error>;
+
+    /// Returns the root recorded by [`record_genesis_root`](History::record_genesis_root), or
+    /// `None` if none was recorded or the backing store does not keep one.
+    fn genesis_root(&self) -> Result<Option<Blake2bHash>, Self::Error>;
+
+    /// Records `root` as the post state root of genesis, replacing any recorded before, if the
+    /// backing store keeps one.
+    fn record_genesis_root(&mut self, root: Blake2bHash) -> Result<(), Self::Error>;
+
+    /// Writes a consistent copy of the backing store to the directory at `path` and returns its
+    /// size in bytes, or `None` if the backing store cannot be copied.
+    fn copy_to(&self, path: &Path, compact: bool) -> Result<Option<u64>, Self::Error>;
+
+    /// Flushes everything committed so far to durable storage.
+    fn flush(&self) -> Result<(), Self::Error>;
+
+    /// Returns up to `limit` keys whose values differ between the states at `before_root` and
+    /// `after_root`, with their values in each.
+    fn diff(
+        &self,
+        correlation_id: CorrelationId,
+        before_root: Blake2bHash,
+        after_root: Blake2bHash,
+        limit: usize,
+    ) -> Result<DiffResult<Key, Value>, Self::Error>;
+
+    /// Calls `f` with every key/value pair of the state at `root`, ordered by key bytes, for as
+    /// long as it returns `true`.  Returns whether every pair was visited, or `None` if `root` is
+    /// not in the store.
+    ///
+    /// All pairs are read from one consistent view of the store, which is held until `f` returns
+    /// `false` or the last pair is visited.
+    fn for_each_pair<F>(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+        f: F,
+    ) -> Result<Option<bool>, Self::Error>
+    where
+        F: FnMut(Key, Value) -> bool;
+
+    /// Checks that every node of the trie at `root` is present and stored at its own hash, and
+    /// returns the first inconsistency found.
+    fn verify(
+        &self,
+        correlation_id: CorrelationId,
+        root: Blake2bHash,
+    ) -> Result<VerifyResult, Self::Error>;
+
+    /// Deletes every trie node which is not reachable from one of the `retain` roots, and returns
+    /// how many were deleted, or `None` if the backing store cannot be pruned.
+    ///
+    /// The current root, the empty root and the roots of checkouts which are still alive are
+    /// always kept, so that no state in use is deleted.
+    fn prune(
+        &mut self,
+        correlation_id: CorrelationId,
+        retain: &[Blake2bHash],
+    ) -> Result<Option<PruneResult>, Self::Error>;
 }
 
 const GLOBAL_STATE_COMMIT_READS: &str = "global_state_commit_reads";
@@ -98,9 +233,140 @@ where
     H: BuildHasher,
 {
     let mut txn = environment.create_read_write_txn()?;
+    let commit_result =
+        apply_effects::<_, _, _, E>(correlation_id, &mut txn, store, prestate_hash, effects)?;
+    if let CommitResult::Success { .. } = commit_result {
+        txn.commit()?;
+    }
+    Ok(commit_result)
+}
+
+pub fn commit_batch<'a, R, S, H, E>(
+    environment: &'a R,
+    store: &S,
+    correlation_id: CorrelationId,
+    prestate_hash: Blake2bHash,
+    effects: Vec<HashMap<Key, Transform, H>>,
+) -> Result<CommitResult, E>
+where
+    R: TransactionSource<'a, Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<R::Error>,
+    E: From<R::Error> + From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let mut txn = environment.create_read_write_txn()?;
+    let commit_result =
+        apply_effects_batch::<_, _, _, E>(correlation_id, &mut txn, store, prestate_hash, effects)?;
+    if let CommitResult::Success { .. } = commit_result {
+        txn.commit()?;
+    }
+    Ok(commit_result)
+}
+
+pub fn commit_group<'a, R, S, H, E>(
+    environment: &'a R,
+    store: &S,
+    correlation_id: CorrelationId,
+    commits: Vec<(Blake2bHash, HashMap<Key, Transform, H>)>,
+) -> Result<Vec<CommitResult>, E>
+where
+    R: TransactionSource<'a, Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<R::Error>,
+    E: From<R::Error> + From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let mut txn = environment.create_read_write_txn()?;
+    let commit_results =
+        apply_effects_group::<_, _, _, E>(correlation_id, &mut txn, store, commits)?;
+    txn.commit()?;
+    Ok(commit_results)
+}
+
+/// Applies each set of `effects` on top of its own prestate hash within `txn`, carrying on past
+/// failures.  Leaves it to the caller to commit the transaction.
+pub(crate) fn apply_effects_group<T, S, H, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    commits: Vec<(Blake2bHash, HashMap<Key, Transform, H>)>,
+) -> Result<Vec<CommitResult>, E>
+where
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    commits
+        .into_iter()
+        .map(|(prestate_hash, effects)| {
+            apply_effects::<_, _, _, E>(correlation_id, txn, store, prestate_hash, effects)
+        })
+        .collect()
+}
+
+/// Applies each set of `effects` on top of the root left by the previous one within `txn`,
+/// stopping at the first failure.  Leaves it to the caller to commit the transaction.
+pub(crate) fn apply_effects_batch<T, S, H, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    prestate_hash: Blake2bHash,
+    effects: Vec<HashMap<Key, Transform, H>>,
+) -> Result<CommitResult, E>
+where
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let maybe_root: Option<Trie<Key, Value>> = store.get(&*txn, &prestate_hash)?;
+
+    if maybe_root.is_none() {
+        return Ok(CommitResult::RootNotFound);
+    };
+
     let mut current_root = prestate_hash;
+    let mut total_stats = WriteStats::default();
 
-    let maybe_root: Option<Trie<Key, Value>> = store.get(&txn, &current_root)?;
+    for effects in effects {
+        match apply_effects::<_, _, _, E>(correlation_id, txn, store, current_root, effects)? {
+            CommitResult::Success { state_root, stats } => {
+                current_root = state_root;
+                total_stats += stats;
+            }
+            failure => return Ok(failure),
+        }
+    }
+
+    Ok(CommitResult::Success {
+        state_root: current_root,
+        stats: total_stats,
+    })
+}
+
+/// Applies `effects` on top of `prestate_hash` within `txn`, leaving it to the caller to commit
+/// the transaction.
+pub(crate) fn apply_effects<T, S, H, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    prestate_hash: Blake2bHash,
+    effects: HashMap<Key, Transform, H>,
+) -> Result<CommitResult, E>
+where
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let mut current_root = prestate_hash;
+
+    let maybe_root: Option<Trie<Key, Value>> = store.get(&*txn, &current_root)?;
 
     if maybe_root.is_none() {
         return Ok(CommitResult::RootNotFound);
@@ -109,11 +375,12 @@ where
     let start = Instant::now();
     let mut reads: i32 = 0;
     let mut writes: i32 = 0;
+    let mut total_stats = WriteStats::default();
 
     for (key, transform) in effects.into_iter() {
-        let read_result = read::<_, _, _, _, E>(correlation_id, &txn, store, &current_root, &key)?;
+        let read_result = read::<_, _, _, _, E>(correlation_id, &*txn, store, &current_root, &key)?;
 
-        log_duration(
+        log_duration!(
             correlation_id,
             GLOBAL_STATE_COMMIT_READ_DURATION,
             COMMIT,
@@ -134,10 +401,17 @@ where
             _x @ (ReadResult::RootNotFound, _) => panic!(stringify!(_x._1)),
         };
 
-        let write_result =
-            write::<_, _, _, _, E>(correlation_id, &mut txn, store, &current_root, &key, &value)?;
+        let (write_result, stats) = write_with_stats::<_, _, _, _, E>(
+            correlation_id,
+            txn,
+            store,
+            &current_root,
+            &key,
+            &value,
+        )?;
+        total_stats += stats;
 
-        log_duration(
+        log_duration!(
             correlation_id,
             GLOBAL_STATE_COMMIT_WRITE_DURATION,
             COMMIT,
@@ -154,16 +428,14 @@ where
         }
     }
 
-    txn.commit()?;
-
-    log_duration(
+    log_duration!(
         correlation_id,
         GLOBAL_STATE_COMMIT_DURATION,
         COMMIT,
         start.elapsed(),
     );
 
-    log_metric(
+    log_metric!(
         correlation_id,
         GLOBAL_STATE_COMMIT_READS,
         COMMIT,
@@ -171,7 +443,7 @@ where
         f64::from(reads),
     );
 
-    log_metric(
+    log_metric!(
         correlation_id,
         GLOBAL_STATE_COMMIT_WRITES,
         COMMIT,
@@ -179,5 +451,8 @@ where
         f64::from(writes),
     );
 
-    Ok(CommitResult::Success(current_root))
+    Ok(CommitResult::Success {
+        state_root: current_root,
+        stats: total_stats,
+    })
 }
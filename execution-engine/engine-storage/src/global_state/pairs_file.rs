@@ -0,0 +1,116 @@
+//! A portable file format for the key/value pairs of a global state.
+//!
+//! The file starts with [`MAGIC`] and a little-endian `u32` [`VERSION`], followed by one record
+//! per pair.  A record is the [`bytesrepr`](contract_ffi::bytesrepr) encoding of the key and then
+//! of the value, each preceded by its length as a little-endian `u32`.  The file ends after the
+//! last record.
+
+use std::io::{self, Read, Write};
+
+use contract_ffi::bytesrepr::{self, deserialize, ToBytes};
+use contract_ffi::key::Key;
+use contract_ffi::value::Value;
+use error;
+
+pub const MAGIC: [u8; 4] = *b"CLGS";
+
+pub const VERSION: u32 = 1;
+
+/// Writes the header followed by a record for each pair to `writer`.
+pub fn write_pairs<W: Write>(writer: &mut W, pairs: &[(Key, Value)]) -> Result<(), error::Error> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    for (key, value) in pairs {
+        write_field(writer, &key.to_bytes()?)?;
+        write_field(writer, &value.to_bytes()?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the pairs written by [`write_pairs`] from `reader`.
+pub fn read_pairs<R: Read>(reader: &mut R) -> Result<Vec<(Key, Value)>, error::Error> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data("not a global state pairs file").into());
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("unsupported version {}", version)).into());
+    }
+
+    let mut pairs = Vec::new();
+    while let Some(key_bytes) = read_field(reader, true)? {
+        let value_bytes =
+            read_field(reader, false)?.expect("read_field only returns None at end of file");
+        let key: Key = deserialize(&key_bytes)?;
+        let value: Value = deserialize(&value_bytes)?;
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+pub(super) fn write_field<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), error::Error> {
+    if bytes.len() > u32::max_value() as usize {
+        return Err(bytesrepr::Error::OutOfMemoryError.into());
+    }
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+/// Reads a length-prefixed field.  If `eof_allowed`, returns `None` when `reader` is exhausted
+/// before the length prefix.
+pub(super) fn read_field<R: Read>(
+    reader: &mut R,
+    eof_allowed: bool,
+) -> Result<Option<Vec<u8>>, io::Error> {
+    let mut length_bytes = [0u8; 4];
+    let mut filled = 0;
+    while filled < length_bytes.len() {
+        match reader.read(&mut length_bytes[filled..])? {
+            0 if filled == 0 && eof_allowed => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            read => filled += read,
+        }
+    }
+    let mut bytes = vec![0u8; u32::from_le_bytes(length_bytes) as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+pub(super) fn read_u32<R: Read>(reader: &mut R) -> Result<u32, io::Error> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(super) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_pairs() {
+        let pairs = vec![
+            (Key::Account([1u8; 32]), Value::Int32(1)),
+            (Key::Hash([2u8; 32]), Value::String("two".to_string())),
+        ];
+        let mut buffer = Vec::new();
+        write_pairs(&mut buffer, &pairs).unwrap();
+        assert_eq!(read_pairs(&mut buffer.as_slice()).unwrap(), pairs);
+    }
+
+    #[test]
+    fn should_reject_truncated_file() {
+        let pairs = vec![(Key::Account([1u8; 32]), Value::Int32(1))];
+        let mut buffer = Vec::new();
+        write_pairs(&mut buffer, &pairs).unwrap();
+        buffer.pop();
+        assert!(read_pairs(&mut buffer.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! An append-only index of committed state root hashes, kept in a named LMDB database next to
+//! the trie store.  Each trie store of an environment has its own index.
+//!
+//! The `n`th committed root is stored under `n` encoded as a big-endian `u64`, so the keys sort
+//! in commit order.
+
+use lmdb::{self, Database, DatabaseFlags, RwTransaction, WriteFlags};
+
+use contract_ffi::bytesrepr::{deserialize, ToBytes};
+use engine_shared::newtypes::Blake2bHash;
+use error;
+use trie_store::lmdb::{database_stat, LmdbEnvironment};
+
+/// Name of the LMDB database holding the index of the trie store in the unnamed database.  The
+/// index of a named trie store is kept in a database whose name is this, followed by a slash and
+/// the trie store's name.
+pub const STATE_ROOTS_DB_NAME: &str = "state-roots";
+
+/// Returns `true` if `name` may be taken by the database of an index, so that it cannot be used
+/// for a trie store.
+pub fn is_reserved_name(name: &str) -> bool {
+    name.starts_with(STATE_ROOTS_DB_NAME)
+}
+
+/// Returns the name of the database holding the index of the trie store called `store_name`.
+fn db_name(store_name: Option<&str>) -> String {
+    match store_name {
+        Some(store_name) => format!("{}/{}", STATE_ROOTS_DB_NAME, store_name),
+        None => STATE_ROOTS_DB_NAME.to_owned(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LmdbStateRoots {
+    db: Database,
+}
+
+impl LmdbStateRoots {
+    /// Opens the index of the trie store called `store_name`, creating an empty one if the
+    /// environment does not have it yet.
+    pub fn new(env: &LmdbEnvironment, store_name: Option<&str>) -> Result<Self, error::Error> {
+        let db = env.create_db(Some(&db_name(store_name)), DatabaseFlags::empty())?;
+        Ok(LmdbStateRoots { db })
+    }
+
+    /// Opens the existing index of the trie store called `store_name`, returning `None` if the
+    /// environment does not have one.
+    ///
+    /// Suitable for environments opened with [`LmdbEnvironment::new_read_only`].
+    pub fn open(
+        env: &LmdbEnvironment,
+        store_name: Option<&str>,
+    ) -> Result<Option<Self>, error::Error> {
+        match env.open_db(Some(&db_name(store_name))) {
+            Ok(db) => Ok(Some(LmdbStateRoots { db })),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Returns the number of recorded roots.
+    pub fn len<T: lmdb::Transaction>(&self, txn: &T) -> Result<u64, error::Error> {
+        database_stat(txn, self.db).map(|stat| stat.ms_entries as u64)
+    }
+
+    /// Records `root` as the most recently committed root.
+    pub fn append(&self, txn: &mut RwTransaction, root: Blake2bHash) -> Result<(), error::Error> {
+        let index = self.len(&*txn)?;
+        txn.put(
+            self.db,
+            &index.to_be_bytes(),
+            &root.to_bytes()?,
+            WriteFlags::APPEND,
+        )?;
+        Ok(())
+    }
+
+    /// Removes the roots for which `keep` returns `false`, keeping the others in commit order,
+    /// and returns the number removed.
+    pub fn retain<F>(&self, txn: &mut RwTransaction, keep: F) -> Result<u64, error::Error>
+    where
+        F: Fn(&Blake2bHash) -> bool,
+    {
+        let roots = self.list(&*txn, 0, u64::max_value())?;
+        let (kept, removed): (Vec<Blake2bHash>, Vec<Blake2bHash>) =
+            roots.into_iter().partition(|root| keep(root));
+        if !removed.is_empty() {
+            txn.clear_db(self.db)?;
+            for root in kept {
+                self.append(txn, root)?;
+            }
+        }
+        Ok(removed.len() as u64)
+    }
+
+    /// Returns up to `limit` roots in commit order, skipping the first `offset`.
+    pub fn list<T: lmdb::Transaction>(
+        &self,
+        txn: &T,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<Blake2bHash>, error::Error> {
+        let end = self.len(txn)?.min(offset.saturating_add(limit));
+        let mut roots = Vec::new();
+        for index in offset..end {
+            let bytes = txn.get(self.db, &index.to_be_bytes())?;
+            roots.push(deserialize(bytes)?);
+        }
+        Ok(roots)
+    }
+}
@@ -6,12 +6,15 @@ extern crate core;
 // third-party dependencies
 #[macro_use]
 extern crate failure;
+extern crate libc;
 extern crate lmdb;
+extern crate lmdb_sys;
 extern crate parking_lot;
 extern crate wasmi;
 
 // local dependencies
 extern crate contract_ffi;
+#[macro_use]
 extern crate engine_shared;
 
 // third-party dev-dependencies
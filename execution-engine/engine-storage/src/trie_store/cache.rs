@@ -0,0 +1,162 @@
+//! A least recently used cache of serialized [`Trie`](::trie::Trie) values, keyed by hash.
+//!
+//! Tries are content addressed, so a cached value can only go stale by belonging to a
+//! transaction which was never committed.  [`LmdbTrieStore`](super::lmdb::LmdbTrieStore) avoids
+//! this by only caching values read through read-only transactions, and by evicting values as
+//! they are written.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use engine_shared::newtypes::Blake2bHash;
+
+/// Lookup counts and size of a [`TrieCache`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TrieCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl TrieCacheStats {
+    /// Returns the fraction of lookups which were hits, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+/// Holds up to `capacity` values, evicting the least recently used one when full.
+#[derive(Debug)]
+pub struct TrieCache {
+    capacity: usize,
+    entries: HashMap<Blake2bHash, (Arc<Vec<u8>>, u64)>,
+    // maps the last use of each entry to its key, oldest first
+    recency: BTreeMap<u64, Blake2bHash>,
+    uses: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl TrieCache {
+    pub fn new(capacity: usize) -> Self {
+        TrieCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            uses: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the value at `key` and marks it as the most recently used one.
+    pub fn get(&mut self, key: &Blake2bHash) -> Option<Arc<Vec<u8>>> {
+        let next_use = self.next_use();
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                self.recency.remove(&entry.1);
+                self.recency.insert(next_use, *key);
+                entry.1 = next_use;
+                self.hits += 1;
+                Some(Arc::clone(&entry.0))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Inserts `value` at `key`, evicting the least recently used value if the cache is full.
+    pub fn insert(&mut self, key: Blake2bHash, value: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let next_use = self.next_use();
+        if let Some((_, last_use)) = self.entries.insert(key, (Arc::new(value), next_use)) {
+            self.recency.remove(&last_use);
+        }
+        self.recency.insert(next_use, key);
+        while self.entries.len() > self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(last_use) => *last_use,
+                None => break,
+            };
+            if let Some(oldest_key) = self.recency.remove(&oldest) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+    }
+
+    /// Removes the value at `key`, if any.
+    pub fn remove(&mut self, key: &Blake2bHash) {
+        if let Some((_, last_use)) = self.entries.remove(key) {
+            self.recency.remove(&last_use);
+        }
+    }
+
+    pub fn stats(&self) -> TrieCacheStats {
+        TrieCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> Blake2bHash {
+        [byte; 32].into()
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        let mut cache = TrieCache::new(2);
+        cache.insert(hash(1), vec![1]);
+        cache.insert(hash(2), vec![2]);
+        assert_eq!(cache.get(&hash(1)), Some(Arc::new(vec![1])));
+
+        cache.insert(hash(3), vec![3]);
+
+        assert_eq!(cache.get(&hash(2)), None);
+        assert_eq!(cache.get(&hash(1)), Some(Arc::new(vec![1])));
+        assert_eq!(cache.get(&hash(3)), Some(Arc::new(vec![3])));
+        assert_eq!(
+            cache.stats(),
+            TrieCacheStats {
+                hits: 3,
+                misses: 1,
+                entries: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn should_remove_entries() {
+        let mut cache = TrieCache::new(2);
+        cache.insert(hash(1), vec![1]);
+        cache.remove(&hash(1));
+        assert_eq!(cache.get(&hash(1)), None);
+        assert!(cache.recency.is_empty());
+    }
+
+    #[test]
+    fn should_not_hold_entries_without_capacity() {
+        let mut cache = TrieCache::new(0);
+        cache.insert(hash(1), vec![1]);
+        assert_eq!(cache.get(&hash(1)), None);
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -302,12 +302,18 @@ impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for InMemor
         }
     }
 
-    fn put<T>(&self, txn: &mut T, key: &Blake2bHash, value: &Trie<K, V>) -> Result<(), Self::Error>
+    fn put<T>(
+        &self,
+        txn: &mut T,
+        key: &Blake2bHash,
+        value: &Trie<K, V>,
+    ) -> Result<usize, Self::Error>
     where
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>,
     {
-        txn.write((), &key.to_bytes()?, &value.to_bytes()?)
-            .map_err(Into::into)
+        let bytes = value.to_bytes()?;
+        txn.write((), &key.to_bytes()?, &bytes)?;
+        Ok(bytes.len())
     }
 }
@@ -10,7 +10,9 @@
 //! # extern crate tempfile;
 //! use casperlabs_engine_storage::trie::{Pointer, PointerBlock, Trie};
 //! use casperlabs_engine_storage::trie_store::{Transaction, TransactionSource, TrieStore};
-//! use casperlabs_engine_storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+//! use casperlabs_engine_storage::trie_store::lmdb::{
+//!     LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS,
+//! };
 //! use contract_ffi::bytesrepr::ToBytes;
 //! use lmdb::DatabaseFlags;
 //! use engine_shared::newtypes::Blake2bHash;
@@ -41,7 +43,8 @@
 //! // transactions.
 //! let tmp_dir = tempdir().unwrap();
 //! let map_size = 4096 * 2560;  // map size should be a multiple of OS page size
-//! let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), map_size).unwrap();
+//! let env =
+//!     LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), map_size, DEFAULT_MAX_READERS).unwrap();
 //! let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
 //!
 //! // First let's create a read-write transaction, persist the values, but
@@ -106,14 +109,39 @@
 //! tmp_dir.close().unwrap();
 //! ```
 
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use lmdb::{
+    self, Cursor, Database, DatabaseFlags, Environment, EnvironmentFlags, RoTransaction,
+    RwTransaction, WriteFlags,
+};
+
+use lmdb_sys::{
+    mdb_env_copy2, mdb_env_info, mdb_stat, MDB_envinfo, MDB_stat, MDB_CP_COMPACT, MDB_SUCCESS,
+};
 
-use lmdb::{self, Database, DatabaseFlags, Environment, RoTransaction, RwTransaction, WriteFlags};
+use parking_lot::Mutex;
 
-use contract_ffi::bytesrepr::{deserialize, FromBytes, ToBytes};
+use contract_ffi::bytesrepr::{deserialize, FromBytes, ToBytes, N32, U32_SIZE};
 
+use engine_shared::newtypes::CorrelationId;
+
+use super::cache::{TrieCache, TrieCacheStats};
+use super::operations::{self, PruneResult, VerifyResult, WarmUpProgress};
+use super::store_version;
 use super::*;
 use error;
+use global_state::StoreStats;
+use global_state::{genesis_root, state_roots};
 
 impl<'a> Transaction for RoTransaction<'a> {
     type Error = lmdb::Error;
@@ -133,6 +161,10 @@ impl<'a> Readable for RoTransaction<'a> {
             Err(e) => Err(e),
         }
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 impl<'a> Transaction for RwTransaction<'a> {
@@ -162,25 +194,277 @@ impl<'a> Writable for RwTransaction<'a> {
     }
 }
 
+/// A read-only transaction which is only begun by the first read reaching LMDB, so that reads
+/// answered from the cache of an [`LmdbTrieStore`] neither take a reader slot nor wait for one.
+///
+/// Tries are content addressed, so cached values match whichever snapshot the transaction ends up
+/// reading.
+pub struct LazyRoTransaction<'a> {
+    env: &'a LmdbEnvironment,
+    txn: RefCell<Option<RoTransaction<'a>>>,
+}
+
+impl<'a> LazyRoTransaction<'a> {
+    pub fn new(env: &'a LmdbEnvironment) -> Self {
+        LazyRoTransaction {
+            env,
+            txn: RefCell::new(None),
+        }
+    }
+
+    /// Returns `true` if a read reached LMDB, so that the transaction was begun.
+    pub fn is_begun(&self) -> bool {
+        self.txn.borrow().is_some()
+    }
+}
+
+impl<'a> Transaction for LazyRoTransaction<'a> {
+    type Error = lmdb::Error;
+
+    type Handle = Database;
+
+    fn commit(self) -> Result<(), Self::Error> {
+        match self.txn.into_inner() {
+            Some(txn) => lmdb::Transaction::commit(txn),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Readable for LazyRoTransaction<'a> {
+    fn read(&self, handle: Self::Handle, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        let mut txn = self.txn.borrow_mut();
+        if txn.is_none() {
+            *txn = Some(self.env.env.begin_ro_txn()?);
+        }
+        txn.as_ref()
+            .expect("transaction should have been begun")
+            .read(handle, key)
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+/// Name of the file LMDB keeps an environment's data in, inside the environment's directory.
+pub const DATA_FILE_NAME: &str = "data.mdb";
+
+/// Number of named databases an environment can hold besides the unnamed one, i.e. named trie
+/// stores and the state root index, version and genesis root of each trie store.
+const MAX_NAMED_DBS: u32 = 32;
+
+/// Number of read transactions an environment can have open at once unless told otherwise, which
+/// is LMDB's own default.  Starting another one fails with [`error::Error::ReadersFull`].
+pub const DEFAULT_MAX_READERS: u32 = 126;
+
+/// Start of the key of every trie node, i.e. the length prefix of the serialized hash it is stored
+/// at.  Names of named databases cannot contain a NUL byte, so never start with it.
+const TRIE_KEY_PREFIX: [u8; U32_SIZE] = [N32 as u8, 0, 0, 0];
+
+/// The smallest key greater than the key of every trie node.
+const TRIE_KEYS_END: [u8; U32_SIZE] = [N32 as u8, 0, 0, 1];
+
+/// When commits to an [`LmdbEnvironment`] are flushed to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Every commit is flushed before it returns, so a crash loses nothing committed.
+    Always,
+    /// Commits are not flushed one by one, but the environment is meant to be synced with
+    /// [`LmdbEnvironment::sync`] at this interval, so a system crash loses at most the commits
+    /// made since the last sync.
+    Interval(Duration),
+    /// Neither commits nor the meta page are flushed until [`LmdbEnvironment::sync`] is called,
+    /// so a system crash may lose or corrupt any commit since.  Meant for tests and bulk loads.
+    Never,
+}
+
+impl SyncPolicy {
+    fn environment_flags(self) -> EnvironmentFlags {
+        match self {
+            SyncPolicy::Always => EnvironmentFlags::empty(),
+            SyncPolicy::Interval(_) => EnvironmentFlags::NO_SYNC,
+            SyncPolicy::Never => EnvironmentFlags::NO_SYNC | EnvironmentFlags::NO_META_SYNC,
+        }
+    }
+}
+
+impl fmt::Display for SyncPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncPolicy::Always => write!(f, "always"),
+            SyncPolicy::Interval(interval) => write!(f, "interval:{}", interval.as_millis()),
+            SyncPolicy::Never => write!(f, "never"),
+        }
+    }
+}
+
 /// The environment for an LMDB-backed trie store.
 ///
-/// Wraps [`lmdb::Environment`].
+/// Wraps [`lmdb::Environment`].  Besides the trie store in the unnamed database, the environment
+/// may hold trie stores in named databases, and holds a
+/// [`state_roots`](::global_state::state_roots) index, a [`store_version`] and a
+/// [`genesis_root`](::global_state::genesis_root) for each trie store.  The names of named
+/// databases are recorded as entries of the unnamed database.
 #[derive(Debug)]
 pub struct LmdbEnvironment {
     path: PathBuf,
     env: Environment,
+    read_only: bool,
 }
 
 impl LmdbEnvironment {
-    pub fn new(path: &PathBuf, map_size: usize) -> Result<Self, error::Error> {
-        let env = Environment::new().set_map_size(map_size).open(path)?;
+    pub fn new(path: &PathBuf, map_size: usize, max_readers: u32) -> Result<Self, error::Error> {
+        Self::new_with_sync_policy(path, map_size, max_readers, SyncPolicy::Always)
+    }
+
+    /// Opens the environment with the flags `sync_policy` calls for.  With
+    /// [`SyncPolicy::Interval`], syncing at that interval is left to the caller.
+    pub fn new_with_sync_policy(
+        path: &PathBuf,
+        map_size: usize,
+        max_readers: u32,
+        sync_policy: SyncPolicy,
+    ) -> Result<Self, error::Error> {
+        let env = Environment::new()
+            .set_flags(sync_policy.environment_flags())
+            .set_max_dbs(MAX_NAMED_DBS)
+            .set_map_size(map_size)
+            .set_max_readers(max_readers)
+            .open(path)?;
         let path = path.to_owned();
-        Ok(LmdbEnvironment { path, env })
+        Ok(LmdbEnvironment {
+            path,
+            env,
+            read_only: false,
+        })
+    }
+
+    /// Opens an existing environment with `MDB_RDONLY`.  Read-write transactions cannot be
+    /// created from the returned environment.
+    pub fn new_read_only(
+        path: &PathBuf,
+        map_size: usize,
+        max_readers: u32,
+    ) -> Result<Self, error::Error> {
+        let env = Environment::new()
+            .set_flags(EnvironmentFlags::READ_ONLY)
+            .set_max_dbs(MAX_NAMED_DBS)
+            .set_map_size(map_size)
+            .set_max_readers(max_readers)
+            .open(path)?;
+        let path = path.to_owned();
+        Ok(LmdbEnvironment {
+            path,
+            env,
+            read_only: true,
+        })
+    }
+
+    /// Opens the environment with [`SyncPolicy::Never`], so that commits are not flushed to disk.
+    /// A system crash may then lose or corrupt the last commits, until [`LmdbEnvironment::sync`]
+    /// is called.  Meant for bulk loads, which are quicker without a flush per commit.
+    pub fn new_no_sync(
+        path: &PathBuf,
+        map_size: usize,
+        max_readers: u32,
+    ) -> Result<Self, error::Error> {
+        Self::new_with_sync_policy(path, map_size, max_readers, SyncPolicy::Never)
+    }
+
+    /// Calls `open` until it returns an environment, retrying up to `retries` times, `interval`
+    /// apart, while it fails with an error for which [`error::Error::is_transient_open`] holds.
+    /// Meant for opening an environment which another process may still be creating; other
+    /// errors are returned straight away.
+    pub fn open_with_retries<F>(
+        retries: u32,
+        interval: Duration,
+        mut open: F,
+    ) -> Result<Self, error::Error>
+    where
+        F: FnMut() -> Result<Self, error::Error>,
+    {
+        let mut attempts = 0;
+        loop {
+            match open() {
+                Err(ref error) if error.is_transient_open() && attempts < retries => {
+                    attempts += 1;
+                    thread::sleep(interval);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
     }
 
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
+
+    /// Flushes the environment's data buffers to disk, even if it was opened with `MDB_NOSYNC`.
+    /// This is a no-op for read-only environments.
+    pub fn sync(&self) -> Result<(), error::Error> {
+        if self.read_only {
+            return Ok(());
+        }
+        self.env.sync(true).map_err(Into::into)
+    }
+
+    pub(crate) fn create_db(
+        &self,
+        name: Option<&str>,
+        flags: DatabaseFlags,
+    ) -> Result<Database, lmdb::Error> {
+        self.env.create_db(name, flags)
+    }
+
+    pub(crate) fn open_db(&self, name: Option<&str>) -> Result<Database, lmdb::Error> {
+        self.env.open_db(name)
+    }
+
+    /// Returns size statistics of the environment and the number of entries in `store`.
+    pub fn stats(&self, store: &LmdbTrieStore) -> Result<StoreStats, error::Error> {
+        let txn = self.env.begin_ro_txn()?;
+
+        let stat = database_stat(&txn, store.db)?;
+
+        let mut info: MDB_envinfo = unsafe { mem::zeroed() };
+        let code = unsafe { mdb_env_info(self.env.env(), &mut info) };
+        if code != MDB_SUCCESS {
+            return Err(lmdb::Error::from_err_code(code).into());
+        }
+
+        let other_entries = count_other_entries(&txn, store.db)?;
+
+        Ok(StoreStats {
+            page_size: u64::from(stat.ms_psize),
+            entries: stat.ms_entries as u64 - other_entries,
+            // page numbers start at 0
+            used_pages: info.me_last_pgno as u64 + 1,
+            map_size: info.me_mapsize as u64,
+        })
+    }
+
+    /// Writes a consistent copy of the environment to the directory at `path`, creating it if
+    /// needed, and returns the size of the copied data file in bytes.
+    ///
+    /// The copy is made within a read transaction, so writers are not blocked.  With `compact`,
+    /// free pages are omitted and the remaining pages renumbered, which yields a smaller file
+    /// but takes longer.
+    pub fn copy_to(&self, path: &Path, compact: bool) -> Result<u64, error::Error> {
+        fs::create_dir_all(path)?;
+        let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| lmdb::Error::Invalid)?;
+        let flags = if compact { MDB_CP_COMPACT } else { 0 };
+        let code = unsafe { mdb_env_copy2(self.env.env(), c_path.as_ptr(), flags) };
+        if code != MDB_SUCCESS {
+            return Err(lmdb::Error::from_err_code(code).into());
+        }
+        let metadata = fs::metadata(path.join(DATA_FILE_NAME))?;
+        Ok(metadata.len())
+    }
 }
 
 impl<'a> TransactionSource<'a> for LmdbEnvironment {
@@ -201,27 +485,265 @@ impl<'a> TransactionSource<'a> for LmdbEnvironment {
     }
 }
 
+/// Returns LMDB's statistics of `db` as seen by `txn`.
+pub(crate) fn database_stat<T: lmdb::Transaction>(
+    txn: &T,
+    db: Database,
+) -> Result<MDB_stat, error::Error> {
+    let mut stat: MDB_stat = unsafe { mem::zeroed() };
+    let code = unsafe { mdb_stat(txn.txn(), db.dbi(), &mut stat) };
+    if code != MDB_SUCCESS {
+        return Err(lmdb::Error::from_err_code(code).into());
+    }
+    Ok(stat)
+}
+
+/// Returns `true` if `key` is the key of a trie node rather than the name of a named database,
+/// which the unnamed database holds as well.
+fn is_trie_key(key: &[u8]) -> bool {
+    key.len() == U32_SIZE + N32 && key.starts_with(&TRIE_KEY_PREFIX)
+}
+
+/// Returns the number of entries of `db` which are not trie nodes, i.e. the names of the named
+/// databases if `db` is the unnamed one.  The keys of trie nodes sort together, so only the
+/// entries before and after them are read.
+fn count_other_entries<T: lmdb::Transaction>(txn: &T, db: Database) -> Result<u64, error::Error> {
+    let mut cursor = txn.open_ro_cursor(db)?;
+    let before = cursor
+        .iter_start()
+        .take_while(|&(key, _)| key < &TRIE_KEY_PREFIX[..])
+        .count();
+    let after = cursor.iter_from(&TRIE_KEYS_END[..]).count();
+    Ok((before + after) as u64)
+}
+
 /// An LMDB-backed trie store.
 ///
-/// Wraps [`lmdb::Database`].
+/// Wraps [`lmdb::Database`], optionally with a [`TrieCache`] in front of it.  Clones share the
+/// cache.
 #[derive(Debug, Clone)]
 pub struct LmdbTrieStore {
     db: Database,
+    name: Option<String>,
+    cache: Option<Arc<Mutex<TrieCache>>>,
 }
 
 impl LmdbTrieStore {
+    /// Opens the trie store in the database called `name`, or in the unnamed database if `name` is
+    /// `None`, creating it if the environment does not have it yet.
+    ///
+    /// Each named trie store is independent of the others, so several states can be kept in one
+    /// environment.
+    ///
+    /// A store written in an older [`store_version`] is upgraded to the current one, while one
+    /// in a newer version fails with [`error::Error::UnsupportedStoreVersion`].
     pub fn new(
         env: &LmdbEnvironment,
         name: Option<&str>,
         flags: DatabaseFlags,
     ) -> Result<Self, error::Error> {
+        check_store_name(name)?;
         let db = env.env.create_db(name, flags)?;
-        Ok(LmdbTrieStore { db })
+        store_version::check_and_migrate(env, name, db)?;
+        Ok(LmdbTrieStore {
+            db,
+            name: name.map(str::to_owned),
+            cache: None,
+        })
     }
 
+    /// Opens an existing trie store like [`LmdbTrieStore::new`], failing with
+    /// [`error::Error::StoreNotFound`] if the environment does not have it, and with
+    /// [`error::Error::UnsupportedStoreVersion`] unless it is in the current [`store_version`], as
+    /// it is not upgraded.
+    ///
+    /// Suitable for environments opened with [`LmdbEnvironment::new_read_only`].
     pub fn open(env: &LmdbEnvironment, name: Option<&str>) -> Result<Self, error::Error> {
-        let db = env.env.open_db(name)?;
-        Ok(LmdbTrieStore { db })
+        check_store_name(name)?;
+        let db = match env.env.open_db(name) {
+            Ok(db) => db,
+            Err(lmdb::Error::NotFound) => {
+                return Err(error::Error::StoreNotFound(
+                    name.unwrap_or_default().to_owned(),
+                ))
+            }
+            Err(error) => return Err(error.into()),
+        };
+        store_version::check(env, name)?;
+        Ok(LmdbTrieStore {
+            db,
+            name: name.map(str::to_owned),
+            cache: None,
+        })
+    }
+
+    /// Returns the name of the database holding the trie store, or `None` for the unnamed one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(String::as_str)
+    }
+
+    /// Keeps up to `capacity` of the most recently read values in memory, so that reading them
+    /// again does not touch LMDB.
+    ///
+    /// Only values read through read-only transactions are cached, as a read-write transaction
+    /// may see values which are later rolled back.  Values are evicted from the cache when they
+    /// are written.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Arc::new(Mutex::new(TrieCache::new(capacity))));
+        self
+    }
+
+    /// Returns the statistics of the cache, if there is one.
+    pub fn cache_stats(&self) -> Option<TrieCacheStats> {
+        self.cache.as_ref().map(|cache| cache.lock().stats())
+    }
+
+    /// Walks the trie at `root`, checking that every node reachable from it is present and hashes
+    /// to the hash it is stored at, and returns the first inconsistency found.
+    ///
+    /// Nodes are read straight from LMDB, bypassing the cache, so that it is what is on disk which
+    /// is checked.  Nothing is written.
+    pub fn verify<K, V>(
+        &self,
+        env: &LmdbEnvironment,
+        correlation_id: CorrelationId,
+        root: &Blake2bHash,
+    ) -> Result<VerifyResult, error::Error>
+    where
+        K: ToBytes + FromBytes,
+        V: ToBytes + FromBytes,
+    {
+        let uncached_store = LmdbTrieStore {
+            db: self.db,
+            name: self.name.clone(),
+            cache: None,
+        };
+        let txn = env.create_read_txn()?;
+        let verify_result = operations::verify::<K, V, _, _, error::Error>(
+            correlation_id,
+            &txn,
+            &uncached_store,
+            root,
+        )?;
+        txn.commit()?;
+        Ok(verify_result)
+    }
+
+    /// Reads every node reachable from `root`, down to at most `max_depth` levels below it, so
+    /// that the pages holding them are in the OS page cache.  Calls `on_level` with the progress
+    /// made at the end of each level.
+    ///
+    /// Nodes are read straight from LMDB, bypassing the cache, which would otherwise only keep
+    /// the last nodes read.  Nothing is written.
+    pub fn warm_up<K, V, F>(
+        &self,
+        env: &LmdbEnvironment,
+        correlation_id: CorrelationId,
+        root: &Blake2bHash,
+        max_depth: u32,
+        on_level: F,
+    ) -> Result<Option<WarmUpProgress>, error::Error>
+    where
+        K: FromBytes,
+        V: FromBytes,
+        F: FnMut(&WarmUpProgress),
+    {
+        let uncached_store = LmdbTrieStore {
+            db: self.db,
+            name: self.name.clone(),
+            cache: None,
+        };
+        let txn = env.create_read_txn()?;
+        let warm_up_progress = operations::warm_up::<K, V, _, _, error::Error, _>(
+            correlation_id,
+            &txn,
+            &uncached_store,
+            root,
+            max_depth,
+            on_level,
+        )?;
+        txn.commit()?;
+        Ok(warm_up_progress)
+    }
+
+    /// Deletes every node which is not reachable from one of the `retain` roots as part of `txn`,
+    /// and evicts the deleted nodes from the cache.  Nothing is deleted if one of the roots is not
+    /// in the store.
+    ///
+    /// Nodes are read straight from LMDB, bypassing the cache, as every node is read once anyway.
+    pub fn prune<K, V>(
+        &self,
+        txn: &mut RwTransaction,
+        correlation_id: CorrelationId,
+        retain: &[Blake2bHash],
+    ) -> Result<PruneResult, error::Error>
+    where
+        K: ToBytes + FromBytes,
+        V: ToBytes + FromBytes,
+    {
+        let uncached_store = LmdbTrieStore {
+            db: self.db,
+            name: self.name.clone(),
+            cache: None,
+        };
+        for root in retain {
+            let maybe_root: Option<Trie<K, V>> = uncached_store.get(&*txn, root)?;
+            if maybe_root.is_none() {
+                return Ok(PruneResult::RootNotFound(*root));
+            }
+        }
+        let reachable = operations::reachable::<K, V, _, _, error::Error>(
+            correlation_id,
+            &*txn,
+            &uncached_store,
+            retain,
+        )?;
+
+        let mut nodes_kept = 0;
+        let mut bytes_deleted = 0;
+        let mut unreachable = Vec::new();
+        {
+            let mut cursor = lmdb::Transaction::open_ro_cursor(&*txn, self.db)?;
+            for (key, value) in cursor.iter_start() {
+                if !is_trie_key(key) {
+                    continue;
+                }
+                let hash: Blake2bHash = deserialize(key)?;
+                if reachable.contains(&hash) {
+                    nodes_kept += 1;
+                } else {
+                    bytes_deleted += value.len() as u64;
+                    unreachable.push(hash);
+                }
+            }
+        }
+        for hash in &unreachable {
+            txn.del(self.db, &hash.to_bytes()?, None)?;
+            if let Some(ref cache) = self.cache {
+                cache.lock().remove(hash);
+            }
+        }
+
+        Ok(PruneResult::Pruned {
+            nodes_kept,
+            nodes_deleted: unreachable.len() as u64,
+            bytes_deleted,
+        })
+    }
+}
+
+/// Fails if `name` is taken by the databases of the [`state_roots`](::global_state::state_roots)
+/// indexes, of the [`store_version`]s or of the [`genesis_root`](::global_state::genesis_root)s.
+fn check_store_name(name: Option<&str>) -> Result<(), error::Error> {
+    match name {
+        Some(name)
+            if state_roots::is_reserved_name(name)
+                || store_version::is_reserved_name(name)
+                || genesis_root::is_reserved_name(name) =>
+        {
+            Err(error::Error::ReservedStoreName(name.to_owned()))
+        }
+        _ => Ok(()),
     }
 }
 
@@ -239,10 +761,21 @@ impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for LmdbTri
         T: Readable<Handle = Self::Handle>,
         Self::Error: From<T::Error>,
     {
+        if let Some(ref cache) = self.cache {
+            let cached = cache.lock().get(key);
+            if let Some(bytes) = cached {
+                return Ok(Some(deserialize(&bytes)?));
+            }
+        }
         match txn.read(self.db, &key.to_bytes()?)? {
             None => Ok(None),
             Some(bytes) => {
                 let trie = deserialize(&bytes)?;
+                if let Some(ref cache) = self.cache {
+                    if txn.is_read_only() {
+                        cache.lock().insert(*key, bytes);
+                    }
+                }
                 Ok(Some(trie))
             }
         }
@@ -253,12 +786,16 @@ impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for LmdbTri
         txn: &mut T,
         key: &Blake2bHash,
         value: &Trie<K, V>,
-    ) -> Result<(), Self::Error>
+    ) -> Result<usize, Self::Error>
     where
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>,
     {
-        txn.write(self.db, &key.to_bytes()?, &value.to_bytes()?)
-            .map_err(Into::into)
+        if let Some(ref cache) = self.cache {
+            cache.lock().remove(key);
+        }
+        let bytes = value.to_bytes()?;
+        txn.write(self.db, &key.to_bytes()?, &bytes)?;
+        Ok(bytes.len())
     }
 }
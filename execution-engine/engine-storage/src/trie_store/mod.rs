@@ -2,9 +2,11 @@
 //!
 //! See the [in_memory](in_memory/index.html#usage) and
 //! [lmdb](lmdb/index.html#usage) modules for usage examples.
+pub mod cache;
 pub mod in_memory;
 pub mod lmdb;
 pub(crate) mod operations;
+pub mod store_version;
 #[cfg(test)]
 mod tests;
 
@@ -36,6 +38,12 @@ pub trait Transaction: Sized {
 pub trait Readable: Transaction {
     /// Returns the value from the corresponding key from a given [`Transaction::Handle`].
     fn read(&self, handle: Self::Handle, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Returns `true` if the transaction only observes committed data, i.e. it cannot see
+    /// writes which may still be rolled back.
+    fn is_read_only(&self) -> bool {
+        false
+    }
 }
 
 /// A transaction with the capability to write to a given [`Handle`](Transaction::Handle).
@@ -83,8 +91,14 @@ pub trait TrieStore<K, V> {
         T: Readable<Handle = Self::Handle>,
         Self::Error: From<T::Error>;
 
-    /// Inserts a [`Trie`] value at a given hash.
-    fn put<T>(&self, txn: &mut T, key: &Blake2bHash, value: &Trie<K, V>) -> Result<(), Self::Error>
+    /// Inserts a [`Trie`] value at a given hash, and returns the size of its serialized form in
+    /// bytes.
+    fn put<T>(
+        &self,
+        txn: &mut T,
+        key: &Blake2bHash,
+        value: &Trie<K, V>,
+    ) -> Result<usize, Self::Error>
     where
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>;
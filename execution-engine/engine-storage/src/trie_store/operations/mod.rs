@@ -1,7 +1,10 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::AddAssign;
 use std::time::Instant;
 
 use contract_ffi::bytesrepr::{self, ToBytes};
-use engine_shared::logging::{log_duration, log_metric, GAUGE};
+use engine_shared::logging::GAUGE;
 use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 
 use trie::{self, Parents, Pointer, Trie};
@@ -12,13 +15,28 @@ mod tests;
 
 const TRIE_STORE_READ_DURATION: &str = "trie_store_read_duration";
 const TRIE_STORE_READ_GETS: &str = "trie_store_read_gets";
+const TRIE_STORE_READ_ALL_DURATION: &str = "trie_store_read_all_duration";
+const TRIE_STORE_READ_ALL_GETS: &str = "trie_store_read_all_gets";
+const TRIE_STORE_DIFF_DURATION: &str = "trie_store_diff_duration";
+const TRIE_STORE_DIFF_GETS: &str = "trie_store_diff_gets";
 const TRIE_STORE_SCAN_DURATION: &str = "trie_store_scan_duration";
 const TRIE_STORE_SCAN_GETS: &str = "trie_store_scan_gets";
+const TRIE_STORE_VERIFY_DURATION: &str = "trie_store_verify_duration";
+const TRIE_STORE_VERIFY_GETS: &str = "trie_store_verify_gets";
+const TRIE_STORE_REACHABLE_DURATION: &str = "trie_store_reachable_duration";
+const TRIE_STORE_REACHABLE_GETS: &str = "trie_store_reachable_gets";
+const TRIE_STORE_WARM_UP_DURATION: &str = "trie_store_warm_up_duration";
+const TRIE_STORE_WARM_UP_GETS: &str = "trie_store_warm_up_gets";
 const TRIE_STORE_WRITE_DURATION: &str = "trie_store_write_duration";
 const TRIE_STORE_WRITE_PUTS: &str = "trie_store_write_puts";
 const READ: &str = "read";
+const READ_ALL: &str = "read_all";
+const DIFF: &str = "diff";
 const GET: &str = "get";
 const SCAN: &str = "scan";
+const VERIFY: &str = "verify";
+const REACHABLE: &str = "reachable";
+const WARM_UP: &str = "warm_up";
 const WRITE: &str = "write";
 const PUT: &str = "put";
 
@@ -37,6 +55,27 @@ pub fn read<K, V, T, S, E>(
     root: &Blake2bHash,
     key: &K,
 ) -> Result<ReadResult<V>, E>
+where
+    K: ToBytes + Eq + std::fmt::Debug,
+    V: ToBytes,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+{
+    read_with_node_count::<K, V, T, S, E>(correlation_id, txn, store, root, key)
+        .map(|(result, _)| result)
+}
+
+/// Like [`read`], but also returns the number of trie nodes loaded from the store to find the
+/// value, counting the root
+pub fn read_with_node_count<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+    key: &K,
+) -> Result<(ReadResult<V>, usize), E>
 where
     K: ToBytes + Eq + std::fmt::Debug,
     V: ToBytes,
@@ -50,8 +89,9 @@ where
     let mut depth: usize = 0;
     let mut current: Trie<K, V> = match store.get(txn, root)? {
         Some(root) => root,
-        None => return Ok(ReadResult::RootNotFound),
+        None => return Ok((ReadResult::RootNotFound, 0)),
     };
+    let mut nodes_visited: usize = 1;
 
     let start = Instant::now();
     let mut get_counter: i32 = 0;
@@ -69,20 +109,20 @@ where
                     // a Node directly to a Leaf
                     ReadResult::NotFound
                 };
-                log_metric(
+                log_metric!(
                     correlation_id,
                     TRIE_STORE_READ_GETS,
                     GET,
                     GAUGE,
                     f64::from(get_counter),
                 );
-                log_duration(
+                log_duration!(
                     correlation_id,
                     TRIE_STORE_READ_DURATION,
                     READ,
                     start.elapsed(),
                 );
-                return Ok(result);
+                return Ok((result, nodes_visited));
             }
             Trie::Node { pointer_block } => {
                 let index: usize = {
@@ -98,18 +138,19 @@ where
                         Some(next) => {
                             get_counter += 1;
                             depth += 1;
+                            nodes_visited += 1;
                             current = next;
                         }
                         None => {
                             get_counter += 1;
-                            log_metric(
+                            log_metric!(
                                 correlation_id,
                                 TRIE_STORE_READ_GETS,
                                 GET,
                                 GAUGE,
                                 f64::from(get_counter),
                             );
-                            log_duration(
+                            log_duration!(
                                 correlation_id,
                                 TRIE_STORE_READ_DURATION,
                                 READ,
@@ -123,20 +164,20 @@ where
                         }
                     },
                     None => {
-                        log_metric(
+                        log_metric!(
                             correlation_id,
                             TRIE_STORE_READ_GETS,
                             GET,
                             GAUGE,
                             f64::from(get_counter),
                         );
-                        log_duration(
+                        log_duration!(
                             correlation_id,
                             TRIE_STORE_READ_DURATION,
                             READ,
                             start.elapsed(),
                         );
-                        return Ok(ReadResult::NotFound);
+                        return Ok((ReadResult::NotFound, nodes_visited));
                     }
                 }
             }
@@ -148,18 +189,19 @@ where
                         Some(next) => {
                             get_counter += 1;
                             depth += affix.len();
+                            nodes_visited += 1;
                             current = next;
                         }
                         None => {
                             get_counter += 1;
-                            log_metric(
+                            log_metric!(
                                 correlation_id,
                                 TRIE_STORE_READ_GETS,
                                 GET,
                                 GAUGE,
                                 f64::from(get_counter),
                             );
-                            log_duration(
+                            log_duration!(
                                 correlation_id,
                                 TRIE_STORE_READ_DURATION,
                                 READ,
@@ -173,24 +215,641 @@ where
                         }
                     }
                 } else {
-                    log_metric(
+                    log_metric!(
                         correlation_id,
                         TRIE_STORE_READ_GETS,
                         GET,
                         GAUGE,
                         f64::from(get_counter),
                     );
-                    log_duration(
+                    log_duration!(
                         correlation_id,
                         TRIE_STORE_READ_DURATION,
                         READ,
                         start.elapsed(),
                     );
-                    return Ok(ReadResult::NotFound);
+                    return Ok((ReadResult::NotFound, nodes_visited));
+                }
+            }
+        }
+    }
+}
+
+/// Returns every key/value pair reachable from a given root in a given store, ordered by key
+/// bytes, or `None` if the root is not in the store
+pub fn read_all<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+) -> Result<Option<Vec<(K, V)>>, E>
+where
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+{
+    let mut pairs = Vec::new();
+    let visited =
+        for_each_pair::<_, _, _, _, E, _>(correlation_id, txn, store, root, |key, value| {
+            pairs.push((key, value));
+            true
+        })?;
+    Ok(visited.map(|_| pairs))
+}
+
+/// Calls `f` with every key/value pair reachable from a given root in a given store, ordered by
+/// key bytes, for as long as it returns `true`.  Returns whether every pair was visited, or `None`
+/// if the root is not in the store
+pub fn for_each_pair<K, V, T, S, E, F>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+    mut f: F,
+) -> Result<Option<bool>, E>
+where
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+    F: FnMut(K, V) -> bool,
+{
+    let root_trie: Trie<K, V> = match store.get(txn, root)? {
+        Some(root_trie) => root_trie,
+        None => return Ok(None),
+    };
+
+    let start = Instant::now();
+
+    let (get_counter, completed) = visit_leaves::<_, _, _, _, E, _>(txn, store, root_trie, &mut f)?;
+
+    log_metric!(
+        correlation_id,
+        TRIE_STORE_READ_ALL_GETS,
+        GET,
+        GAUGE,
+        f64::from(get_counter),
+    );
+    log_duration!(
+        correlation_id,
+        TRIE_STORE_READ_ALL_DURATION,
+        READ_ALL,
+        start.elapsed(),
+    );
+
+    Ok(Some(completed))
+}
+
+/// Calls `f` with every key/value pair under `trie`, ordered by key bytes, until it returns
+/// `false`, and returns the number of store lookups made and whether every pair was visited
+fn visit_leaves<K, V, T, S, E, F>(
+    txn: &T,
+    store: &S,
+    trie: Trie<K, V>,
+    f: &mut F,
+) -> Result<(i32, bool), E>
+where
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+    F: FnMut(K, V) -> bool,
+{
+    let mut get_counter: i32 = 0;
+    let mut pending = vec![trie];
+
+    while let Some(current) = pending.pop() {
+        let pointers: Vec<Pointer> = match current {
+            Trie::Leaf { key, value } => {
+                if !f(key, value) {
+                    return Ok((get_counter, false));
                 }
+                continue;
+            }
+            // reversed so that the lowest index is popped first
+            Trie::Node { pointer_block } => (0..trie::RADIX)
+                .rev()
+                .filter_map(|index| pointer_block[index])
+                .collect(),
+            Trie::Extension { pointer, .. } => vec![pointer],
+        };
+        for pointer in pointers {
+            get_counter += 1;
+            match store.get(txn, pointer.hash())? {
+                Some(child) => pending.push(child),
+                None => panic!("No trie value at key: {:?}", pointer.hash()),
             }
         }
     }
+
+    Ok((get_counter, true))
+}
+
+/// A key whose value differs between two tries.  `before` is `None` if the key was added, and
+/// `after` is `None` if it was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDiff<K, V> {
+    pub key: K,
+    pub before: Option<V>,
+    pub after: Option<V>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DiffResult<K, V> {
+    /// The differing keys, ordered by key bytes.  `truncated` is set if there were more than the
+    /// requested limit, in which case only the first ones are returned.
+    Diff {
+        diffs: Vec<KeyDiff<K, V>>,
+        truncated: bool,
+    },
+    RootNotFound(Blake2bHash),
+}
+
+/// Returns up to `limit` keys whose values differ between the tries at `before_root` and
+/// `after_root` in a given store.
+///
+/// Subtries with the same hash are skipped without being read, so the cost depends on the size
+/// of the difference rather than on the size of the tries.
+pub fn diff<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    before_root: &Blake2bHash,
+    after_root: &Blake2bHash,
+    limit: usize,
+) -> Result<DiffResult<K, V>, E>
+where
+    K: ToBytes + Eq,
+    V: Eq,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<bytesrepr::Error>,
+{
+    let before_trie: Trie<K, V> = match store.get(txn, before_root)? {
+        Some(trie) => trie,
+        None => return Ok(DiffResult::RootNotFound(*before_root)),
+    };
+    let after_trie: Trie<K, V> = match store.get(txn, after_root)? {
+        Some(trie) => trie,
+        None => return Ok(DiffResult::RootNotFound(*after_root)),
+    };
+
+    let start = Instant::now();
+    let mut differ = TrieDiffer {
+        txn,
+        store,
+        limit,
+        diffs: Vec::new(),
+        get_counter: 0,
+    };
+    let complete =
+        before_root == after_root || differ.diff::<E>(Some(before_trie), Some(after_trie))?;
+
+    log_metric!(
+        correlation_id,
+        TRIE_STORE_DIFF_GETS,
+        GET,
+        GAUGE,
+        f64::from(differ.get_counter),
+    );
+    log_duration!(
+        correlation_id,
+        TRIE_STORE_DIFF_DURATION,
+        DIFF,
+        start.elapsed(),
+    );
+
+    Ok(DiffResult::Diff {
+        diffs: differ.diffs,
+        truncated: !complete,
+    })
+}
+
+struct TrieDiffer<'a, K, V, T: 'a, S: 'a> {
+    txn: &'a T,
+    store: &'a S,
+    limit: usize,
+    diffs: Vec<KeyDiff<K, V>>,
+    get_counter: i32,
+}
+
+impl<'a, K, V, T, S> TrieDiffer<'a, K, V, T, S>
+where
+    K: ToBytes + Eq,
+    V: Eq,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+{
+    /// Records the differences between two subtries, either of which may be missing.  Returns
+    /// `false` if the limit was reached before all of them were recorded.
+    fn diff<E>(&mut self, before: Option<Trie<K, V>>, after: Option<Trie<K, V>>) -> Result<bool, E>
+    where
+        E: From<S::Error> + From<bytesrepr::Error>,
+    {
+        match (before, after) {
+            (
+                Some(Trie::Node {
+                    pointer_block: before_block,
+                }),
+                Some(Trie::Node {
+                    pointer_block: after_block,
+                }),
+            ) => {
+                for index in 0..trie::RADIX {
+                    let before_pointer = before_block[index];
+                    let after_pointer = after_block[index];
+                    if before_pointer.map(|pointer| *pointer.hash())
+                        == after_pointer.map(|pointer| *pointer.hash())
+                    {
+                        continue;
+                    }
+                    let before_child = self.get::<E>(before_pointer)?;
+                    let after_child = self.get::<E>(after_pointer)?;
+                    if !self.diff::<E>(before_child, after_child)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (
+                Some(Trie::Extension {
+                    affix: ref before_affix,
+                    pointer: before_pointer,
+                }),
+                Some(Trie::Extension {
+                    affix: ref after_affix,
+                    pointer: after_pointer,
+                }),
+            ) if before_affix == after_affix => {
+                if before_pointer.hash() == after_pointer.hash() {
+                    return Ok(true);
+                }
+                let before_child = self.get::<E>(Some(before_pointer))?;
+                let after_child = self.get::<E>(Some(after_pointer))?;
+                self.diff::<E>(before_child, after_child)
+            }
+            // the subtries are shaped differently, so compare their leaves
+            (before, after) => {
+                let mut before_pairs = Vec::new();
+                if let Some(trie) = before {
+                    self.get_counter += collect_leaves::<_, _, _, _, E>(
+                        self.txn,
+                        self.store,
+                        trie,
+                        &mut before_pairs,
+                    )?;
+                }
+                let mut after_pairs = Vec::new();
+                if let Some(trie) = after {
+                    self.get_counter += collect_leaves::<_, _, _, _, E>(
+                        self.txn,
+                        self.store,
+                        trie,
+                        &mut after_pairs,
+                    )?;
+                }
+                self.diff_pairs::<E>(before_pairs, after_pairs)
+            }
+        }
+    }
+
+    /// Records the differences between two lists of pairs, each ordered by key bytes.
+    fn diff_pairs<E>(&mut self, before: Vec<(K, V)>, after: Vec<(K, V)>) -> Result<bool, E>
+    where
+        E: From<bytesrepr::Error>,
+    {
+        let mut before = before.into_iter().peekable();
+        let mut after = after.into_iter().peekable();
+        loop {
+            let ordering = match (before.peek(), after.peek()) {
+                (Some((before_key, _)), Some((after_key, _))) => {
+                    before_key.to_bytes()?.cmp(&after_key.to_bytes()?)
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return Ok(true),
+            };
+            let key_diff = match ordering {
+                Ordering::Less => {
+                    let (key, value) = before.next().expect("should have before pair");
+                    KeyDiff {
+                        key,
+                        before: Some(value),
+                        after: None,
+                    }
+                }
+                Ordering::Greater => {
+                    let (key, value) = after.next().expect("should have after pair");
+                    KeyDiff {
+                        key,
+                        before: None,
+                        after: Some(value),
+                    }
+                }
+                Ordering::Equal => {
+                    let (key, before_value) = before.next().expect("should have before pair");
+                    let (_, after_value) = after.next().expect("should have after pair");
+                    if before_value == after_value {
+                        continue;
+                    }
+                    KeyDiff {
+                        key,
+                        before: Some(before_value),
+                        after: Some(after_value),
+                    }
+                }
+            };
+            if self.diffs.len() == self.limit {
+                return Ok(false);
+            }
+            self.diffs.push(key_diff);
+        }
+    }
+
+    fn get<E>(&mut self, pointer: Option<Pointer>) -> Result<Option<Trie<K, V>>, E>
+    where
+        E: From<S::Error>,
+    {
+        let pointer = match pointer {
+            Some(pointer) => pointer,
+            None => return Ok(None),
+        };
+        self.get_counter += 1;
+        match self.store.get(self.txn, pointer.hash())? {
+            Some(trie) => Ok(Some(trie)),
+            None => panic!("No trie value at key: {:?}", pointer.hash()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyResult {
+    /// Every node reachable from the root is present and stored at its own hash.
+    Verified {
+        node_count: u64,
+    },
+    RootNotFound(Blake2bHash),
+    /// The node at `parent` points to `hash`, but nothing is stored there.
+    MissingNode {
+        parent: Blake2bHash,
+        hash: Blake2bHash,
+    },
+    /// The node stored at `hash` actually hashes to `actual`.
+    HashMismatch {
+        hash: Blake2bHash,
+        actual: Blake2bHash,
+    },
+}
+
+/// Walks the trie at `root` in a given store, checking that every node reachable from it is
+/// present and that its hash matches the hash it is stored at, and returns the first inconsistency
+/// found.
+///
+/// A node whose bytes cannot be deserialized at all fails with the store's error instead.
+pub fn verify<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+) -> Result<VerifyResult, E>
+where
+    K: ToBytes,
+    V: ToBytes,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<bytesrepr::Error>,
+{
+    let root_trie: Trie<K, V> = match store.get(txn, root)? {
+        Some(root_trie) => root_trie,
+        None => return Ok(VerifyResult::RootNotFound(*root)),
+    };
+
+    let start = Instant::now();
+    let mut get_counter: i32 = 1;
+    let mut node_count: u64 = 0;
+    let mut pending = vec![(*root, root_trie)];
+
+    let result = loop {
+        let (hash, current) = match pending.pop() {
+            Some(entry) => entry,
+            None => break VerifyResult::Verified { node_count },
+        };
+        node_count += 1;
+
+        let actual = Blake2bHash::new(&current.to_bytes()?);
+        if actual != hash {
+            break VerifyResult::HashMismatch { hash, actual };
+        }
+
+        let pointers: Vec<Pointer> = match current {
+            Trie::Leaf { .. } => continue,
+            // reversed so that the lowest index is checked first
+            Trie::Node { pointer_block } => (0..trie::RADIX)
+                .rev()
+                .filter_map(|index| pointer_block[index])
+                .collect(),
+            Trie::Extension { pointer, .. } => vec![pointer],
+        };
+        let mut missing = None;
+        for pointer in pointers {
+            get_counter += 1;
+            match store.get(txn, pointer.hash())? {
+                Some(child) => pending.push((*pointer.hash(), child)),
+                None => {
+                    missing = Some(*pointer.hash());
+                    break;
+                }
+            }
+        }
+        if let Some(missing) = missing {
+            break VerifyResult::MissingNode {
+                parent: hash,
+                hash: missing,
+            };
+        }
+    };
+
+    log_metric!(
+        correlation_id,
+        TRIE_STORE_VERIFY_GETS,
+        GET,
+        GAUGE,
+        f64::from(get_counter),
+    );
+    log_duration!(
+        correlation_id,
+        TRIE_STORE_VERIFY_DURATION,
+        VERIFY,
+        start.elapsed(),
+    );
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneResult {
+    /// Every node not reachable from one of the retained roots was deleted.
+    Pruned {
+        nodes_kept: u64,
+        nodes_deleted: u64,
+        /// Total size of the deleted nodes, in bytes
+        bytes_deleted: u64,
+    },
+    /// One of the roots to retain is not in the store, so nothing was deleted.
+    RootNotFound(Blake2bHash),
+}
+
+/// Returns the hashes of every node reachable from `roots` in a given store, including the roots.
+///
+/// Each node is read once, however many parents point to it.  Missing nodes are skipped, as there
+/// is nothing under them to find.
+pub fn reachable<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    roots: &[Blake2bHash],
+) -> Result<HashSet<Blake2bHash>, E>
+where
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+{
+    let start = Instant::now();
+    let mut get_counter: i32 = 0;
+    let mut reachable = HashSet::new();
+    let mut pending = roots.to_vec();
+
+    while let Some(hash) = pending.pop() {
+        if !reachable.insert(hash) {
+            continue;
+        }
+        get_counter += 1;
+        match store.get(txn, &hash)? {
+            Some(Trie::Node { pointer_block }) => pending.extend(
+                (0..trie::RADIX)
+                    .filter_map(|index| pointer_block[index])
+                    .map(|pointer| *pointer.hash())
+                    .filter(|hash| !reachable.contains(hash)),
+            ),
+            Some(Trie::Extension { pointer, .. }) => pending.push(*pointer.hash()),
+            Some(Trie::Leaf { .. }) | None => (),
+        }
+    }
+
+    log_metric!(
+        correlation_id,
+        TRIE_STORE_REACHABLE_GETS,
+        GET,
+        GAUGE,
+        f64::from(get_counter),
+    );
+    log_duration!(
+        correlation_id,
+        TRIE_STORE_REACHABLE_DURATION,
+        REACHABLE,
+        start.elapsed(),
+    );
+
+    Ok(reachable)
+}
+
+/// Progress of a [`warm_up`] walk, as of the end of a level of the trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmUpProgress {
+    /// Depth of the last level read, the root being at depth 0
+    pub depth: u32,
+    /// Number of nodes read so far
+    pub node_count: u64,
+    /// Whether every node reachable from the root has been read
+    pub complete: bool,
+}
+
+/// Reads every node reachable from `root` in a given store, level by level, down to at most
+/// `max_depth` levels below the root, so that later reads of the trie find it in memory.  Calls
+/// `on_level` with the progress made at the end of each level, and returns the progress made once
+/// done, or `None` if `root` is not in the store.
+///
+/// Each node is read once, however many parents point to it.  Missing nodes are skipped.
+pub fn warm_up<K, V, T, S, E, F>(
+    correlation_id: CorrelationId,
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+    max_depth: u32,
+    mut on_level: F,
+) -> Result<Option<WarmUpProgress>, E>
+where
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+    F: FnMut(&WarmUpProgress),
+{
+    let start = Instant::now();
+    let mut get_counter: i32 = 0;
+    let mut seen = HashSet::new();
+    seen.insert(*root);
+    let mut level = vec![*root];
+    let mut progress = WarmUpProgress {
+        depth: 0,
+        node_count: 0,
+        complete: false,
+    };
+
+    let result = loop {
+        let mut next_level = Vec::new();
+        for hash in &level {
+            get_counter += 1;
+            match store.get(txn, hash)? {
+                Some(Trie::Node { pointer_block }) => next_level.extend(
+                    (0..trie::RADIX)
+                        .filter_map(|index| pointer_block[index])
+                        .map(|pointer| *pointer.hash())
+                        .filter(|hash| seen.insert(*hash)),
+                ),
+                Some(Trie::Extension { pointer, .. }) => {
+                    if seen.insert(*pointer.hash()) {
+                        next_level.push(*pointer.hash());
+                    }
+                }
+                Some(Trie::Leaf { .. }) => (),
+                None => continue,
+            }
+            progress.node_count += 1;
+        }
+        if progress.node_count == 0 {
+            break None;
+        }
+
+        progress.complete = next_level.is_empty();
+        on_level(&progress);
+        if progress.complete || progress.depth >= max_depth {
+            break Some(progress);
+        }
+        progress.depth += 1;
+        level = next_level;
+    };
+
+    log_metric!(
+        correlation_id,
+        TRIE_STORE_WARM_UP_GETS,
+        GET,
+        GAUGE,
+        f64::from(get_counter),
+    );
+    log_duration!(
+        correlation_id,
+        TRIE_STORE_WARM_UP_DURATION,
+        WARM_UP,
+        start.elapsed(),
+    );
+
+    Ok(result)
 }
 
 struct TrieScan<K, V> {
@@ -235,14 +894,14 @@ where
     loop {
         match current {
             leaf @ Trie::Leaf { .. } => {
-                log_metric(
+                log_metric!(
                     correlation_id,
                     TRIE_STORE_SCAN_GETS,
                     GET,
                     GAUGE,
                     f64::from(get_counter),
                 );
-                log_duration(
+                log_duration!(
                     correlation_id,
                     TRIE_STORE_SCAN_DURATION,
                     SCAN,
@@ -263,14 +922,14 @@ where
                 let pointer = match maybe_pointer {
                     Some(pointer) => pointer,
                     None => {
-                        log_metric(
+                        log_metric!(
                             correlation_id,
                             TRIE_STORE_SCAN_GETS,
                             GET,
                             GAUGE,
                             f64::from(get_counter),
                         );
-                        log_duration(
+                        log_duration!(
                             correlation_id,
                             TRIE_STORE_SCAN_DURATION,
                             SCAN,
@@ -288,14 +947,14 @@ where
                     }
                     None => {
                         get_counter += 1;
-                        log_metric(
+                        log_metric!(
                             correlation_id,
                             TRIE_STORE_SCAN_GETS,
                             GET,
                             GAUGE,
                             f64::from(get_counter),
                         );
-                        log_duration(
+                        log_duration!(
                             correlation_id,
                             TRIE_STORE_SCAN_DURATION,
                             SCAN,
@@ -312,14 +971,14 @@ where
             Trie::Extension { affix, pointer } => {
                 let sub_path = &path[depth..depth + affix.len()];
                 if sub_path != affix.as_slice() {
-                    log_metric(
+                    log_metric!(
                         correlation_id,
                         TRIE_STORE_SCAN_GETS,
                         GET,
                         GAUGE,
                         f64::from(get_counter),
                     );
-                    log_duration(
+                    log_duration!(
                         correlation_id,
                         TRIE_STORE_SCAN_DURATION,
                         SCAN,
@@ -340,14 +999,14 @@ where
                     }
                     None => {
                         get_counter += 1;
-                        log_metric(
+                        log_metric!(
                             correlation_id,
                             TRIE_STORE_SCAN_GETS,
                             GET,
                             GAUGE,
                             f64::from(get_counter),
                         );
-                        log_duration(
+                        log_duration!(
                             correlation_id,
                             TRIE_STORE_SCAN_DURATION,
                             SCAN,
@@ -612,6 +1271,20 @@ pub enum WriteResult {
     RootNotFound,
 }
 
+/// Number of trie nodes put into a store, and their total size in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriteStats {
+    pub nodes: u64,
+    pub bytes: u64,
+}
+
+impl AddAssign for WriteStats {
+    fn add_assign(&mut self, other: WriteStats) {
+        self.nodes += other.nodes;
+        self.bytes += other.bytes;
+    }
+}
+
 pub fn write<K, V, T, S, E>(
     correlation_id: CorrelationId,
     txn: &mut T,
@@ -620,6 +1293,28 @@ pub fn write<K, V, T, S, E>(
     key: &K,
     value: &V,
 ) -> Result<WriteResult, E>
+where
+    K: ToBytes + Clone + Eq + std::fmt::Debug,
+    V: ToBytes + Clone + Eq,
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<contract_ffi::bytesrepr::Error>,
+{
+    write_with_stats::<K, V, T, S, E>(correlation_id, txn, store, root, key, value)
+        .map(|(write_result, _)| write_result)
+}
+
+/// Like [`write`], but also returns the number and total size of the trie nodes it put into
+/// `store`.
+pub fn write_with_stats<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    root: &Blake2bHash,
+    key: &K,
+    value: &V,
+) -> Result<(WriteResult, WriteStats), E>
 where
     K: ToBytes + Clone + Eq + std::fmt::Debug,
     V: ToBytes + Clone + Eq,
@@ -630,9 +1325,10 @@ where
 {
     let start = Instant::now();
     let mut put_counter: i32 = 0;
+    let mut stats = WriteStats::default();
 
     match store.get(txn, root)? {
-        None => Ok(WriteResult::RootNotFound),
+        None => Ok((WriteResult::RootNotFound, stats)),
         Some(current_root) => {
             let new_leaf = Trie::Leaf {
                 key: key.to_owned(),
@@ -693,34 +1389,35 @@ where
                 }
             };
             if new_elements.is_empty() {
-                log_duration(
+                log_duration!(
                     correlation_id,
                     TRIE_STORE_WRITE_DURATION,
                     WRITE,
                     start.elapsed(),
                 );
-                return Ok(WriteResult::AlreadyExists);
+                return Ok((WriteResult::AlreadyExists, stats));
             }
             let mut root_hash = root.to_owned();
             for (hash, element) in new_elements.iter() {
                 put_counter += 1;
-                store.put(txn, hash, element)?;
+                stats.nodes += 1;
+                stats.bytes += store.put(txn, hash, element)? as u64;
                 root_hash = *hash;
             }
-            log_metric(
+            log_metric!(
                 correlation_id,
                 TRIE_STORE_WRITE_PUTS,
                 PUT,
                 GAUGE,
                 f64::from(put_counter),
             );
-            log_duration(
+            log_duration!(
                 correlation_id,
                 TRIE_STORE_WRITE_DURATION,
                 WRITE,
                 start.elapsed(),
             );
-            Ok(WriteResult::Written(root_hash))
+            Ok((WriteResult::Written(root_hash), stats))
         }
     }
 }
@@ -8,7 +8,7 @@ use engine_shared::newtypes::{Blake2bHash, CorrelationId};
 use error;
 use trie::{Pointer, Trie};
 use trie_store::in_memory::{self, InMemoryEnvironment, InMemoryTrieStore};
-use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
 use trie_store::operations::{read, write, ReadResult, WriteResult};
 use trie_store::{Readable, Transaction, TransactionSource, TrieStore};
 use TEST_MAP_SIZE;
@@ -478,7 +478,11 @@ struct LmdbTestContext {
 impl LmdbTestContext {
     fn new(tries: &[HashedTestTrie]) -> Result<Self, failure::Error> {
         let _temp_dir = tempdir()?;
-        let environment = LmdbEnvironment::new(&_temp_dir.path().to_path_buf(), *TEST_MAP_SIZE)?;
+        let environment = LmdbEnvironment::new(
+            &_temp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )?;
         let store = LmdbTrieStore::new(&environment, None, DatabaseFlags::empty())?;
         put_tries::<_, _, error::Error>(&environment, &store, tries)?;
         Ok(LmdbTestContext {
@@ -865,6 +869,239 @@ mod scan {
     }
 }
 
+mod verify {
+    use super::*;
+    use error;
+    use trie_store::in_memory;
+    use trie_store::operations::{verify, VerifyResult};
+
+    fn check_verify<'a, R, S, E>(
+        environment: &'a R,
+        store: &S,
+        root_hash: &Blake2bHash,
+    ) -> Result<VerifyResult, E>
+    where
+        R: TransactionSource<'a, Handle = S::Handle>,
+        S: TrieStore<TestKey, TestValue>,
+        S::Error: From<R::Error>,
+        E: From<R::Error> + From<S::Error> + From<bytesrepr::Error>,
+    {
+        let correlation_id = CorrelationId::new();
+        let txn: R::ReadTransaction = environment.create_read_txn()?;
+        let result = verify::<_, _, _, _, E>(correlation_id, &txn, store, root_hash)?;
+        txn.commit()?;
+        Ok(result)
+    }
+
+    #[test]
+    fn lmdb_verifies_n_leaf_partial_trie() {
+        for generator in &TEST_TRIE_GENERATORS {
+            let (root_hash, tries) = generator().unwrap();
+            let context = LmdbTestContext::new(&tries).unwrap();
+
+            assert_eq!(
+                check_verify::<_, _, error::Error>(
+                    &context.environment,
+                    &context.store,
+                    &root_hash
+                )
+                .unwrap(),
+                VerifyResult::Verified {
+                    node_count: tries.len() as u64
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn in_memory_verifies_n_leaf_partial_trie() {
+        for generator in &TEST_TRIE_GENERATORS {
+            let (root_hash, tries) = generator().unwrap();
+            let context = InMemoryTestContext::new(&tries).unwrap();
+
+            assert_eq!(
+                check_verify::<_, _, in_memory::Error>(
+                    &context.environment,
+                    &context.store,
+                    &root_hash
+                )
+                .unwrap(),
+                VerifyResult::Verified {
+                    node_count: tries.len() as u64
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn lmdb_verify_reports_node_stored_at_wrong_hash() {
+        let (root_hash, tries) = create_2_leaf_trie().unwrap();
+        let context = LmdbTestContext::new(&tries).unwrap();
+        let corrupted_leaf = HashedTestTrie::new(TEST_LEAVES[5].clone()).unwrap();
+        context
+            .update(&[HashedTestTrie {
+                hash: tries[0].hash,
+                trie: corrupted_leaf.trie,
+            }])
+            .unwrap();
+
+        assert_eq!(
+            check_verify::<_, _, error::Error>(&context.environment, &context.store, &root_hash)
+                .unwrap(),
+            VerifyResult::HashMismatch {
+                hash: tries[0].hash,
+                actual: corrupted_leaf.hash,
+            }
+        );
+    }
+
+    #[test]
+    fn lmdb_verify_reports_missing_node() {
+        let (root_hash, tries) = create_2_leaf_trie().unwrap();
+        // the first leaf is left out; its parent is the last node of the trie
+        let context = LmdbTestContext::new(&tries[1..]).unwrap();
+
+        assert_eq!(
+            check_verify::<_, _, error::Error>(&context.environment, &context.store, &root_hash)
+                .unwrap(),
+            VerifyResult::MissingNode {
+                parent: tries[4].hash,
+                hash: tries[0].hash,
+            }
+        );
+    }
+
+    #[test]
+    fn lmdb_verify_reports_missing_root() {
+        let (_, tries) = create_2_leaf_trie().unwrap();
+        let context = LmdbTestContext::new(&tries).unwrap();
+        let fake_hash: Blake2bHash = [1u8; 32].into();
+
+        assert_eq!(
+            check_verify::<_, _, error::Error>(&context.environment, &context.store, &fake_hash)
+                .unwrap(),
+            VerifyResult::RootNotFound(fake_hash)
+        );
+    }
+
+    #[test]
+    fn lmdb_store_verify_bypasses_cache() {
+        let (root_hash, tries) = create_2_leaf_trie().unwrap();
+        let mut context = LmdbTestContext::new(&tries).unwrap();
+        context.store = context.store.clone().with_cache(16);
+        // reading through the cache first leaves the original leaf in it
+        check_verify::<_, _, error::Error>(&context.environment, &context.store, &root_hash)
+            .unwrap();
+        // corrupt the leaf behind the cache's back
+        let corrupted_leaf = HashedTestTrie::new(TEST_LEAVES[5].clone()).unwrap();
+        let uncached_store = LmdbTrieStore::open(&context.environment, None).unwrap();
+        put_tries::<_, _, error::Error>(
+            &context.environment,
+            &uncached_store,
+            &[HashedTestTrie {
+                hash: tries[0].hash,
+                trie: corrupted_leaf.trie.clone(),
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            context
+                .store
+                .verify::<TestKey, TestValue>(
+                    &context.environment,
+                    CorrelationId::new(),
+                    &root_hash
+                )
+                .unwrap(),
+            VerifyResult::HashMismatch {
+                hash: tries[0].hash,
+                actual: corrupted_leaf.hash,
+            }
+        );
+    }
+}
+
+mod warm_up {
+    use super::*;
+    use error;
+    use trie_store::operations::{warm_up, WarmUpProgress};
+
+    fn check_warm_up(
+        context: &LmdbTestContext,
+        root_hash: &Blake2bHash,
+        max_depth: u32,
+    ) -> (Option<WarmUpProgress>, Vec<WarmUpProgress>) {
+        let mut levels = Vec::new();
+        let txn = context.environment.create_read_txn().unwrap();
+        let result = warm_up::<TestKey, TestValue, _, _, error::Error, _>(
+            CorrelationId::new(),
+            &txn,
+            &context.store,
+            root_hash,
+            max_depth,
+            |progress| levels.push(*progress),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+        (result, levels)
+    }
+
+    #[test]
+    fn lmdb_warm_up_reads_every_node() {
+        for generator in &TEST_TRIE_GENERATORS {
+            let (root_hash, tries) = generator().unwrap();
+            let context = LmdbTestContext::new(&tries).unwrap();
+
+            let (result, levels) = check_warm_up(&context, &root_hash, u32::max_value());
+            let result = result.unwrap();
+            assert!(result.complete);
+            assert_eq!(result.node_count, tries.len() as u64);
+            assert_eq!(levels.last(), Some(&result));
+        }
+    }
+
+    #[test]
+    fn lmdb_warm_up_reports_each_level() {
+        let (root_hash, tries) = create_2_leaf_trie().unwrap();
+        let context = LmdbTestContext::new(&tries).unwrap();
+
+        let (_, levels) = check_warm_up(&context, &root_hash, u32::max_value());
+        let node_counts: Vec<(u32, u64)> = levels
+            .iter()
+            .map(|progress| (progress.depth, progress.node_count))
+            .collect();
+        // root, extension, node, then both leaves
+        assert_eq!(node_counts, vec![(0, 1), (1, 2), (2, 3), (3, 5)]);
+    }
+
+    #[test]
+    fn lmdb_warm_up_stops_at_max_depth() {
+        let (root_hash, tries) = create_2_leaf_trie().unwrap();
+        let context = LmdbTestContext::new(&tries).unwrap();
+
+        let (result, levels) = check_warm_up(&context, &root_hash, 1);
+        assert_eq!(
+            result,
+            Some(WarmUpProgress {
+                depth: 1,
+                node_count: 2,
+                complete: false,
+            })
+        );
+        assert_eq!(levels.len(), 2);
+    }
+
+    #[test]
+    fn lmdb_warm_up_reports_missing_root() {
+        let (_, tries) = create_2_leaf_trie().unwrap();
+        let context = LmdbTestContext::new(&tries).unwrap();
+        let fake_hash: Blake2bHash = [1u8; 32].into();
+
+        assert_eq!(check_warm_up(&context, &fake_hash, 0), (None, vec![]));
+    }
+}
+
 mod write {
     use super::*;
 
@@ -0,0 +1,279 @@
+//! The version of the encoding a trie store's nodes are written in, kept in a named LMDB database
+//! next to the trie store, so that a store written in an encoding this engine does not know is
+//! refused instead of misread.  Each trie store of an environment has its own version.
+//!
+//! Opening a store in an older version for writing upgrades it in place with the migrations in
+//! [`MIGRATIONS`].  A store written before versions were recorded is taken to be in version 1.
+
+use lmdb::{self, Database, DatabaseFlags, RwTransaction, WriteFlags};
+
+use error;
+use trie_store::lmdb::LmdbEnvironment;
+use trie_store::{Transaction, TransactionSource};
+
+/// Name of the LMDB database holding the version of the trie store in the unnamed database.  The
+/// version of a named trie store is kept in a database whose name is this, followed by a slash and
+/// the trie store's name.
+pub const STORE_VERSION_DB_NAME: &str = "store-version";
+
+/// Version of the encoding of trie nodes this engine reads and writes.
+pub const CURRENT_STORE_VERSION: u8 = 1;
+
+/// Version of a store which has none recorded.
+const UNVERSIONED_STORE_VERSION: u8 = 1;
+
+const VERSION_KEY: &[u8] = b"version";
+
+/// Upgrades the trie store held in the given database as part of the given transaction from one
+/// version to the next.
+pub type Migration = fn(&mut RwTransaction, Database) -> Result<(), error::Error>;
+
+/// The migration from version `n` to `n + 1` is at index `n - 1`, so there is one for each
+/// version before [`CURRENT_STORE_VERSION`].
+const MIGRATIONS: &[Migration] = &[];
+
+/// Returns `true` if `name` may be taken by the database of a store version, so that it cannot be
+/// used for a trie store.
+pub fn is_reserved_name(name: &str) -> bool {
+    name.starts_with(STORE_VERSION_DB_NAME)
+}
+
+/// Returns the name of the database holding the version of the trie store called `store_name`.
+fn db_name(store_name: Option<&str>) -> String {
+    match store_name {
+        Some(store_name) => format!("{}/{}", STORE_VERSION_DB_NAME, store_name),
+        None => STORE_VERSION_DB_NAME.to_owned(),
+    }
+}
+
+/// Reads the recorded version, if there is one.
+fn read_version<T: lmdb::Transaction>(
+    txn: &T,
+    version_db: Database,
+) -> Result<Option<u8>, error::Error> {
+    match txn.get(version_db, &VERSION_KEY) {
+        // an empty value is no version this engine ever wrote
+        Ok(bytes) => Ok(Some(bytes.first().cloned().unwrap_or(0))),
+        Err(lmdb::Error::NotFound) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Upgrades the trie store called `store_name`, whose nodes are in `trie_db`, to
+/// [`CURRENT_STORE_VERSION`] if it is in an older version, and records the version if it was not.
+///
+/// Fails with [`error::Error::UnsupportedStoreVersion`] if the store is in a newer version.
+pub(crate) fn check_and_migrate(
+    env: &LmdbEnvironment,
+    store_name: Option<&str>,
+    trie_db: Database,
+) -> Result<(), error::Error> {
+    migrate(env, store_name, trie_db, MIGRATIONS)
+}
+
+/// Like [`check_and_migrate`], with `migrations` in place of [`MIGRATIONS`].
+fn migrate(
+    env: &LmdbEnvironment,
+    store_name: Option<&str>,
+    trie_db: Database,
+    migrations: &[Migration],
+) -> Result<(), error::Error> {
+    let current_version = migrations.len() as u8 + 1;
+    let version_db = env.create_db(Some(&db_name(store_name)), DatabaseFlags::empty())?;
+    let mut txn = env.create_read_write_txn()?;
+    let recorded_version = read_version(&txn, version_db)?;
+    let version = recorded_version.unwrap_or(UNVERSIONED_STORE_VERSION);
+    if version == 0 || version > current_version {
+        return Err(error::Error::UnsupportedStoreVersion {
+            found: version,
+            supported: current_version,
+        });
+    }
+    if recorded_version == Some(current_version) {
+        return Ok(());
+    }
+    // the migrations and the new version are committed together, or not at all
+    for migration in &migrations[version as usize - 1..] {
+        migration(&mut txn, trie_db)?;
+    }
+    txn.put(
+        version_db,
+        &VERSION_KEY,
+        &[current_version],
+        WriteFlags::empty(),
+    )?;
+    txn.commit()?;
+    Ok(())
+}
+
+/// Checks that the trie store called `store_name` is in [`CURRENT_STORE_VERSION`] without writing
+/// anything, failing with [`error::Error::UnsupportedStoreVersion`] otherwise.
+///
+/// Suitable for environments opened with [`LmdbEnvironment::new_read_only`], where an older store
+/// cannot be upgraded.
+pub(crate) fn check(env: &LmdbEnvironment, store_name: Option<&str>) -> Result<(), error::Error> {
+    let recorded_version = match env.open_db(Some(&db_name(store_name))) {
+        Ok(version_db) => {
+            let txn = env.create_read_txn()?;
+            let recorded_version = read_version(&txn, version_db)?;
+            txn.commit()?;
+            recorded_version
+        }
+        Err(lmdb::Error::NotFound) => None,
+        Err(error) => return Err(error.into()),
+    };
+    let version = recorded_version.unwrap_or(UNVERSIONED_STORE_VERSION);
+    if version != CURRENT_STORE_VERSION {
+        return Err(error::Error::UnsupportedStoreVersion {
+            found: version,
+            supported: CURRENT_STORE_VERSION,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lmdb::{self, Database, DatabaseFlags, RwTransaction, WriteFlags};
+    use tempfile::{tempdir, TempDir};
+
+    use error;
+    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
+    use trie_store::{Transaction, TransactionSource};
+
+    use super::*;
+
+    const TEST_MAP_SIZE: usize = 10 * 1024 * 1024;
+
+    fn create_environment() -> (TempDir, LmdbEnvironment) {
+        let temp_dir = tempdir().unwrap();
+        let environment = LmdbEnvironment::new(
+            &temp_dir.path().to_path_buf(),
+            TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
+        (temp_dir, environment)
+    }
+
+    fn recorded_version(environment: &LmdbEnvironment, store_name: Option<&str>) -> Option<u8> {
+        let version_db = environment.open_db(Some(&db_name(store_name))).unwrap();
+        let txn = environment.create_read_txn().unwrap();
+        read_version(&txn, version_db).unwrap()
+    }
+
+    fn record_version(environment: &LmdbEnvironment, store_name: Option<&str>, version: u8) {
+        let version_db = environment
+            .create_db(Some(&db_name(store_name)), DatabaseFlags::empty())
+            .unwrap();
+        let mut txn = environment.create_read_write_txn().unwrap();
+        txn.put(version_db, &VERSION_KEY, &[version], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn migrations_should_lead_to_current_version() {
+        assert_eq!(MIGRATIONS.len() + 1, CURRENT_STORE_VERSION as usize);
+    }
+
+    #[test]
+    fn should_record_current_version_of_new_store() {
+        let (_temp_dir, environment) = create_environment();
+        LmdbTrieStore::new(&environment, Some("chain"), DatabaseFlags::empty()).unwrap();
+        assert_eq!(
+            recorded_version(&environment, Some("chain")),
+            Some(CURRENT_STORE_VERSION)
+        );
+        assert!(check(&environment, Some("chain")).is_ok());
+    }
+
+    #[test]
+    fn should_refuse_store_in_newer_version() {
+        let (_temp_dir, environment) = create_environment();
+        record_version(&environment, None, CURRENT_STORE_VERSION + 1);
+
+        let expected = error::Error::UnsupportedStoreVersion {
+            found: CURRENT_STORE_VERSION + 1,
+            supported: CURRENT_STORE_VERSION,
+        };
+        assert_eq!(
+            LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap_err(),
+            expected
+        );
+        assert_eq!(
+            LmdbTrieStore::open(&environment, None).unwrap_err(),
+            expected
+        );
+        // the store is left as it was
+        assert_eq!(
+            recorded_version(&environment, None),
+            Some(CURRENT_STORE_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn store_version_names_should_be_reserved() {
+        let (_temp_dir, environment) = create_environment();
+        assert_eq!(
+            LmdbTrieStore::new(
+                &environment,
+                Some("store-version/chain"),
+                DatabaseFlags::empty()
+            )
+            .unwrap_err(),
+            error::Error::ReservedStoreName("store-version/chain".to_string())
+        );
+    }
+
+    fn mark_migrated(txn: &mut RwTransaction, trie_db: Database) -> Result<(), error::Error> {
+        let count = match lmdb::Transaction::get(&*txn, trie_db, b"migrations") {
+            Ok(bytes) => bytes[0],
+            Err(_) => 0,
+        };
+        txn.put(trie_db, b"migrations", &[count + 1], WriteFlags::empty())?;
+        Ok(())
+    }
+
+    #[test]
+    fn should_run_migrations_from_recorded_version() {
+        let (_temp_dir, environment) = create_environment();
+        let trie_db = environment.create_db(None, DatabaseFlags::empty()).unwrap();
+        record_version(&environment, None, 2);
+
+        let migrations: &[Migration] = &[mark_migrated, mark_migrated, mark_migrated];
+        migrate(&environment, None, trie_db, migrations).unwrap();
+        assert_eq!(recorded_version(&environment, None), Some(4));
+
+        let txn = environment.create_read_txn().unwrap();
+        // only the migrations from version 2 to 3 and from 3 to 4 ran
+        assert_eq!(
+            lmdb::Transaction::get(&txn, trie_db, b"migrations").unwrap(),
+            &[2u8][..]
+        );
+        txn.commit().unwrap();
+
+        // nothing runs once the store is current
+        migrate(&environment, None, trie_db, migrations).unwrap();
+        let txn = environment.create_read_txn().unwrap();
+        assert_eq!(
+            lmdb::Transaction::get(&txn, trie_db, b"migrations").unwrap(),
+            &[2u8][..]
+        );
+    }
+
+    #[test]
+    fn should_take_unversioned_store_to_be_in_first_version() {
+        let (_temp_dir, environment) = create_environment();
+        let trie_db = environment.create_db(None, DatabaseFlags::empty()).unwrap();
+
+        let migrations: &[Migration] = &[mark_migrated];
+        migrate(&environment, None, trie_db, migrations).unwrap();
+        assert_eq!(recorded_version(&environment, None), Some(2));
+        let txn = environment.create_read_txn().unwrap();
+        assert_eq!(
+            lmdb::Transaction::get(&txn, trie_db, b"migrations").unwrap(),
+            &[1u8][..]
+        );
+    }
+}
@@ -113,7 +113,7 @@ mod simple {
     use error;
     use trie::Trie;
     use trie_store::in_memory::{self, InMemoryEnvironment, InMemoryTrieStore};
-    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
     use trie_store::tests::TEST_MAP_SIZE;
     use trie_store::{Transaction, TransactionSource, TrieStore};
 
@@ -148,7 +148,12 @@ mod simple {
     #[test]
     fn lmdb_put_succeeds() {
         let tmp_dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
         let data = &super::create_data()[0..1];
 
@@ -200,7 +205,12 @@ mod simple {
     #[test]
     fn lmdb_put_get_succeeds() {
         let tmp_dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
         let data = &super::create_data()[0..1];
 
@@ -241,7 +251,12 @@ mod simple {
     #[test]
     fn lmdb_put_get_many_succeeds() {
         let tmp_dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
         let data = super::create_data();
 
@@ -306,7 +321,12 @@ mod simple {
     #[test]
     fn lmdb_uncommitted_read_write_txn_does_not_persist() {
         let tmp_dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
         let data = super::create_data();
 
@@ -323,6 +343,72 @@ mod simple {
         tmp_dir.close().unwrap();
     }
 
+    #[test]
+    fn lmdb_cached_uncommitted_read_write_txn_does_not_persist() {
+        let tmp_dir = tempdir().unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
+        let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty())
+            .unwrap()
+            .with_cache(10);
+        let data = super::create_data();
+
+        assert_eq!(
+            None,
+            uncommitted_read_write_txn_does_not_persist::<_, _, _, _, error::Error>(
+                &store, &env, &data
+            )
+            .expect("uncommitted_read_write_txn_does_not_persist failed")
+            .into_iter()
+            .collect::<Option<Vec<Trie<Vec<u8>, Vec<u8>>>>>()
+        );
+        assert_eq!(store.cache_stats().map(|stats| stats.entries), Some(0));
+
+        tmp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn lmdb_cached_reads_are_served_from_cache() {
+        let tmp_dir = tempdir().unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
+        let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty())
+            .unwrap()
+            .with_cache(10);
+        let data = super::create_data();
+        let keys: Vec<&Blake2bHash> = data.iter().map(|TestData(k, _)| k).collect();
+        let expected: Vec<Option<Trie<Vec<u8>, Vec<u8>>>> = data
+            .iter()
+            .map(|TestData(_, v)| Some(v.to_owned()))
+            .collect();
+
+        let mut txn = env.create_read_write_txn().unwrap();
+        super::put_many::<_, _, _, _, error::Error>(&mut txn, &store, &data).unwrap();
+        txn.commit().unwrap();
+
+        for _ in 0..2 {
+            let txn = env.create_read_txn().unwrap();
+            let actual = super::get_many::<_, _, _, _, error::Error>(&txn, &store, &keys).unwrap();
+            txn.commit().unwrap();
+            assert_eq!(actual, expected);
+        }
+
+        let stats = store.cache_stats().unwrap();
+        assert_eq!(stats.misses, data.len() as u64);
+        assert_eq!(stats.hits, data.len() as u64);
+        assert_eq!(stats.entries, data.len());
+
+        tmp_dir.close().unwrap();
+    }
+
     fn read_write_transaction_does_not_block_read_transaction<'a, X, E>(
         transaction_source: &'a X,
     ) -> Result<(), E>
@@ -350,7 +436,12 @@ mod simple {
     #[test]
     fn lmdb_read_write_transaction_does_not_block_read_transaction() {
         let dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
 
         assert!(
             read_write_transaction_does_not_block_read_transaction::<_, error::Error>(&env).is_ok()
@@ -403,7 +494,12 @@ mod simple {
     #[test]
     fn lmdb_reads_are_isolated() {
         let dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
 
         assert!(reads_are_isolated::<_, _, error::Error>(&store, &env).is_ok())
@@ -459,7 +555,12 @@ mod simple {
     #[test]
     fn lmdb_reads_are_isolated_2() {
         let dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
 
         assert!(reads_are_isolated_2::<_, _, error::Error>(&store, &env).is_ok())
@@ -475,15 +576,21 @@ mod concurrent {
     use super::TestData;
     use trie::Trie;
     use trie_store::in_memory::{InMemoryEnvironment, InMemoryTrieStore};
-    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+    use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
     use trie_store::tests::TEST_MAP_SIZE;
     use trie_store::{Transaction, TransactionSource, TrieStore};
 
     #[test]
     fn lmdb_writer_mutex_does_not_collide_with_readers() {
         let dir = tempdir().unwrap();
-        let env =
-            Arc::new(LmdbEnvironment::new(&dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap());
+        let env = Arc::new(
+            LmdbEnvironment::new(
+                &dir.path().to_path_buf(),
+                *TEST_MAP_SIZE,
+                DEFAULT_MAX_READERS,
+            )
+            .unwrap(),
+        );
         let store = Arc::new(LmdbTrieStore::open(&env, None).unwrap());
         let num_threads = 10;
         let barrier = Arc::new(Barrier::new(num_threads + 1));
@@ -676,10 +783,15 @@ mod proptests {
 
     fn lmdb_roundtrip_succeeds(inputs: Vec<Trie<Key, Value>>) -> bool {
         use error;
-        use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
+        use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore, DEFAULT_MAX_READERS};
 
         let tmp_dir = tempdir().unwrap();
-        let env = LmdbEnvironment::new(&tmp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap();
+        let env = LmdbEnvironment::new(
+            &tmp_dir.path().to_path_buf(),
+            *TEST_MAP_SIZE,
+            DEFAULT_MAX_READERS,
+        )
+        .unwrap();
         let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
 
         let ret = roundtrip_succeeds::<_, _, error::Error>(&store, &env, inputs);
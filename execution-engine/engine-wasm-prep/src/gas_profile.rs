@@ -0,0 +1,442 @@
+//! Instrumentation which attributes the gas charged by a module to categories of instructions.
+//!
+//! The gas counter injected by `pwasm_utils` charges the cost of a whole metered block with a
+//! single call to `env::gas`.  To profile a module, each of those calls is given an extra
+//! argument identifying its call site and is redirected to `env::gas_profiled`, and the cost of
+//! each site is broken down by category up front.  The host then only has to add up the
+//! breakdowns of the sites it is called from.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use parity_wasm::elements::{
+    External, FunctionType, ImportEntry, Instruction, Module, Type, ValueType,
+};
+
+use wasm_costs::WasmCosts;
+
+/// Host function called by profiled modules in place of `env::gas`, with the amount to charge
+/// and the index of the [`GasSite`] calling it.
+pub const GAS_PROFILED_FUNCTION: &str = "gas_profiled";
+
+const GAS_FUNCTION: &str = "gas";
+
+/// What the gas charged during an execution was spent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GasCategory {
+    /// Instructions charged at the regular rate
+    Regular,
+    /// Loads and stores
+    Memory,
+    /// Integer divisions and remainders
+    Div,
+    /// Integer multiplications
+    Mul,
+    /// Pages added by `grow_memory`
+    GrowMemory,
+    /// Gas charged by stored contracts called during the execution
+    CalledContracts,
+    /// Gas charged by a block whose cost could not be broken down
+    Unattributed,
+}
+
+impl GasCategory {
+    /// Returns the name of the category, as reported in execution results.
+    pub fn name(self) -> &'static str {
+        match self {
+            GasCategory::Regular => "regular",
+            GasCategory::Memory => "memory",
+            GasCategory::Div => "div",
+            GasCategory::Mul => "mul",
+            GasCategory::GrowMemory => "grow_memory",
+            GasCategory::CalledContracts => "called_contracts",
+            GasCategory::Unattributed => "unattributed",
+        }
+    }
+}
+
+impl fmt::Display for GasCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Gas spent per category.
+pub type GasProfile = BTreeMap<GasCategory, u64>;
+
+/// Adds `amount` to the gas spent on `category`.
+pub fn add_gas(profile: &mut GasProfile, category: GasCategory, amount: u64) {
+    let total = profile.entry(category).or_insert(0);
+    *total = total.saturating_add(amount);
+}
+
+/// A call to the gas counter in a profiled module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GasSite {
+    /// Charges for a metered block, whose cost is broken down as given
+    Block(GasProfile),
+    /// Charges for the pages added by `grow_memory`, which are only known at run time
+    GrowMemory,
+}
+
+impl GasSite {
+    /// Adds the gas charged by a call from this site to `profile`.
+    pub fn record(&self, amount: u64, profile: &mut GasProfile) {
+        match self {
+            GasSite::Block(breakdown) => {
+                for (category, gas) in breakdown {
+                    add_gas(profile, *category, *gas);
+                }
+            }
+            GasSite::GrowMemory => add_gas(profile, GasCategory::GrowMemory, amount),
+        }
+    }
+}
+
+/// A module prepared for execution with gas profiling.
+#[derive(Debug, Clone)]
+pub struct ProfiledModule {
+    /// The module as it would have been prepared without profiling.  This is the one stored by
+    /// contracts, so that they are not profiled when called later.
+    pub module: Module,
+    /// The module to execute, which reports its gas to `env::gas_profiled`.
+    pub profiled_module: Module,
+    /// The gas counter call sites of `profiled_module`, by index.
+    pub sites: Vec<GasSite>,
+}
+
+/// A metered block being scanned, along with the cost of its instructions so far.
+#[derive(Default)]
+struct PendingBlock {
+    site: Option<usize>,
+    charged: u64,
+    profile: GasProfile,
+}
+
+/// Redirects the gas counter calls of a module which has been through `inject_gas_counter` to
+/// [`GAS_PROFILED_FUNCTION`], and returns it along with its gas call sites.
+///
+/// The breakdown of each block's cost mirrors the metering done by `pwasm_utils`.  Should it
+/// ever not add up to what the block is charged, the whole charge is recorded as
+/// [`GasCategory::Unattributed`] instead.
+pub fn instrument(mut module: Module, wasm_costs: &WasmCosts) -> (Module, Vec<GasSite>) {
+    let gas_function = match redirect_gas_import(&mut module) {
+        Some(gas_function) => gas_function,
+        None => return (module, Vec::new()),
+    };
+
+    let mut sites = Vec::new();
+    if let Some(code_section) = module.code_section_mut() {
+        for body in code_section.bodies_mut() {
+            let instructions = body.code_mut().elements_mut();
+            let instrumented =
+                instrument_function(instructions, gas_function, wasm_costs, &mut sites);
+            *instructions = instrumented;
+        }
+    }
+    (module, sites)
+}
+
+/// Changes the module's import of `env::gas` to [`GAS_PROFILED_FUNCTION`], taking the index of
+/// the call site as a second argument.  Returns the function index of the import, or `None` if
+/// the module has no gas counter.
+fn redirect_gas_import(module: &mut Module) -> Option<u32> {
+    // `inject_gas_counter` adds its import after every other imported function.
+    let (gas_function, entry_index) = {
+        let entries = module.import_section()?.entries();
+        let function_entries: Vec<usize> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| match entry.external() {
+                External::Function(_) => true,
+                _ => false,
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let entry_index = *function_entries.last()?;
+        if entries[entry_index].field() != GAS_FUNCTION {
+            return None;
+        }
+        (function_entries.len() as u32 - 1, entry_index)
+    };
+
+    let type_index = {
+        let types = module.type_section_mut()?.types_mut();
+        types.push(Type::Function(FunctionType::new(
+            vec![ValueType::I32, ValueType::I32],
+            None,
+        )));
+        types.len() as u32 - 1
+    };
+
+    let entry = &mut module.import_section_mut()?.entries_mut()[entry_index];
+    let module_name = entry.module().to_string();
+    *entry = ImportEntry::new(
+        module_name,
+        GAS_PROFILED_FUNCTION.to_string(),
+        External::Function(type_index),
+    );
+    Some(gas_function)
+}
+
+fn instrument_function(
+    instructions: &[Instruction],
+    gas_function: u32,
+    wasm_costs: &WasmCosts,
+    sites: &mut Vec<GasSite>,
+) -> Vec<Instruction> {
+    let mut instrumented = Vec::with_capacity(instructions.len());
+    let mut blocks = vec![PendingBlock::default()];
+
+    let mut cursor = 0;
+    while cursor < instructions.len() {
+        let instruction = &instructions[cursor];
+        match (instruction, instructions.get(cursor + 1)) {
+            (Instruction::I32Const(amount), Some(Instruction::Call(function)))
+                if *function == gas_function =>
+            {
+                // A block's charge comes first, so one found part way through a block starts a
+                // new metered block at the same depth.
+                if blocks.last().map_or(false, |block| block.site.is_some()) {
+                    let block = blocks.pop().unwrap_or_default();
+                    finish_block(block, sites);
+                    blocks.push(PendingBlock::default());
+                }
+                let site = sites.len();
+                sites.push(GasSite::Block(GasProfile::new()));
+                if let Some(block) = blocks.last_mut() {
+                    block.site = Some(site);
+                    block.charged = u64::from(*amount as u32);
+                }
+                instrumented.push(instruction.clone());
+                instrumented.push(Instruction::I32Const(site as i32));
+                instrumented.push(Instruction::Call(gas_function));
+                cursor += 2;
+                continue;
+            }
+            (Instruction::Call(function), _) if *function == gas_function => {
+                // Only the `grow_memory` counter computes its charge at run time.
+                instrumented.push(Instruction::I32Const(sites.len() as i32));
+                sites.push(GasSite::GrowMemory);
+            }
+            (Instruction::Block(_), _) | (Instruction::Loop(_), _) | (Instruction::If(_), _) => {
+                charge(&mut blocks, instruction, wasm_costs);
+                blocks.push(PendingBlock::default());
+            }
+            (Instruction::Else, _) => {
+                if let Some(block) = blocks.pop() {
+                    finish_block(block, sites);
+                }
+                blocks.push(PendingBlock::default());
+            }
+            (Instruction::End, _) => {
+                if let Some(block) = blocks.pop() {
+                    finish_block(block, sites);
+                }
+            }
+            _ => charge(&mut blocks, instruction, wasm_costs),
+        }
+        instrumented.push(instruction.clone());
+        cursor += 1;
+    }
+    instrumented
+}
+
+fn charge(blocks: &mut Vec<PendingBlock>, instruction: &Instruction, wasm_costs: &WasmCosts) {
+    if let Some(block) = blocks.last_mut() {
+        let (category, cost) = categorize(instruction, wasm_costs);
+        add_gas(&mut block.profile, category, u64::from(cost));
+    }
+}
+
+fn finish_block(block: PendingBlock, sites: &mut Vec<GasSite>) {
+    let site = match block.site {
+        Some(site) => site,
+        None => return,
+    };
+    let attributed: u64 = block.profile.values().sum();
+    let mut profile = block.profile;
+    if attributed != block.charged {
+        profile = GasProfile::new();
+        add_gas(&mut profile, GasCategory::Unattributed, block.charged);
+    }
+    profile.retain(|_, gas| *gas > 0);
+    sites[site] = GasSite::Block(profile);
+}
+
+/// Returns the category of `instruction` and its cost, following the rules given to
+/// `inject_gas_counter`.
+fn categorize(instruction: &Instruction, wasm_costs: &WasmCosts) -> (GasCategory, u32) {
+    use parity_wasm::elements::Instruction::*;
+
+    match instruction {
+        I32Load(..) | I64Load(..) | F32Load(..) | F64Load(..) | I32Load8S(..) | I32Load8U(..)
+        | I32Load16S(..) | I32Load16U(..) | I64Load8S(..) | I64Load8U(..) | I64Load16S(..)
+        | I64Load16U(..) | I64Load32S(..) | I64Load32U(..) | I32Store(..) | I64Store(..)
+        | F32Store(..) | F64Store(..) | I32Store8(..) | I32Store16(..) | I64Store8(..)
+        | I64Store16(..) | I64Store32(..) => (GasCategory::Memory, wasm_costs.mem),
+        I32DivS | I32DivU | I32RemS | I32RemU | I64DivS | I64DivU | I64RemS | I64RemU => {
+            (GasCategory::Div, wasm_costs.div)
+        }
+        I32Mul | I64Mul => (GasCategory::Mul, wasm_costs.mul),
+        _ => (GasCategory::Regular, wasm_costs.regular),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::elements::{
+        BlockType, CodeSection, Func, FuncBody, FunctionSection, ImportSection, Instructions,
+        Section, TypeSection,
+    };
+
+    use super::*;
+
+    fn costs() -> WasmCosts {
+        WasmCosts::from_version(1).unwrap()
+    }
+
+    /// Builds a module importing `env::gas` as its only function, with a single function made of
+    /// `instructions`.
+    fn module_with_gas_import(instructions: Vec<Instruction>) -> Module {
+        Module::new(vec![
+            Section::Type(TypeSection::with_types(vec![Type::Function(
+                FunctionType::new(vec![ValueType::I32], None),
+            )])),
+            Section::Import(ImportSection::with_entries(vec![ImportEntry::new(
+                "env".to_string(),
+                GAS_FUNCTION.to_string(),
+                External::Function(0),
+            )])),
+            Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+            Section::Code(CodeSection::with_bodies(vec![FuncBody::new(
+                Vec::new(),
+                Instructions::new(instructions),
+            )])),
+        ])
+    }
+
+    fn function_code(module: &Module) -> &[Instruction] {
+        module.code_section().unwrap().bodies()[0].code().elements()
+    }
+
+    #[test]
+    fn should_break_down_block_cost() {
+        let module = module_with_gas_import(vec![
+            Instruction::I32Const(26),
+            Instruction::Call(0),
+            Instruction::GetLocal(0),
+            Instruction::GetLocal(0),
+            Instruction::I32Mul,
+            Instruction::I32Load(2, 0),
+            Instruction::I32Const(3),
+            Instruction::I32DivU,
+            Instruction::Drop,
+            Instruction::End,
+        ]);
+
+        let (module, sites) = instrument(module, &costs());
+
+        let mut expected = GasProfile::new();
+        expected.insert(GasCategory::Regular, 4);
+        expected.insert(GasCategory::Mul, 4);
+        expected.insert(GasCategory::Memory, 2);
+        expected.insert(GasCategory::Div, 16);
+        assert_eq!(sites, vec![GasSite::Block(expected)]);
+        assert_eq!(
+            &function_code(&module)[..3],
+            &[
+                Instruction::I32Const(26),
+                Instruction::I32Const(0),
+                Instruction::Call(0)
+            ][..]
+        );
+    }
+
+    #[test]
+    fn should_charge_nested_blocks_to_their_own_sites() {
+        let module = module_with_gas_import(vec![
+            Instruction::I32Const(2),
+            Instruction::Call(0),
+            Instruction::Block(BlockType::NoResult),
+            Instruction::I32Const(7),
+            Instruction::Call(0),
+            Instruction::I32Const(1),
+            Instruction::I32Const(1),
+            Instruction::I32Mul,
+            Instruction::Drop,
+            Instruction::End,
+            Instruction::Nop,
+            Instruction::End,
+        ]);
+
+        let (_, sites) = instrument(module, &costs());
+
+        let mut outer = GasProfile::new();
+        outer.insert(GasCategory::Regular, 2);
+        let mut inner = GasProfile::new();
+        inner.insert(GasCategory::Regular, 3);
+        inner.insert(GasCategory::Mul, 4);
+        assert_eq!(sites, vec![GasSite::Block(outer), GasSite::Block(inner)]);
+    }
+
+    #[test]
+    fn should_leave_cost_unattributed_when_breakdown_does_not_add_up() {
+        let module = module_with_gas_import(vec![
+            Instruction::I32Const(7),
+            Instruction::Call(0),
+            Instruction::Nop,
+            Instruction::End,
+        ]);
+
+        let (_, sites) = instrument(module, &costs());
+
+        let mut expected = GasProfile::new();
+        expected.insert(GasCategory::Unattributed, 7);
+        assert_eq!(sites, vec![GasSite::Block(expected)]);
+    }
+
+    #[test]
+    fn should_record_grow_memory_charges_at_run_time() {
+        let module = module_with_gas_import(vec![
+            Instruction::GetLocal(0),
+            Instruction::GetLocal(0),
+            Instruction::I32Const(8),
+            Instruction::I32Mul,
+            Instruction::Call(0),
+            Instruction::GrowMemory(0),
+            Instruction::End,
+        ]);
+
+        let (module, sites) = instrument(module, &costs());
+
+        assert_eq!(sites, vec![GasSite::GrowMemory]);
+        let mut profile = GasProfile::new();
+        sites[0].record(24, &mut profile);
+        assert_eq!(profile.get(&GasCategory::GrowMemory), Some(&24));
+        assert_eq!(
+            &function_code(&module)[4..6],
+            &[Instruction::I32Const(0), Instruction::Call(0)][..]
+        );
+    }
+
+    #[test]
+    fn should_redirect_gas_import() {
+        let module = module_with_gas_import(vec![Instruction::End]);
+
+        let (module, _) = instrument(module, &costs());
+
+        let entry = &module.import_section().unwrap().entries()[0];
+        assert_eq!(entry.field(), GAS_PROFILED_FUNCTION);
+        match entry.external() {
+            External::Function(type_index) => assert_eq!(
+                module.type_section().unwrap().types()[*type_index as usize],
+                Type::Function(FunctionType::new(
+                    vec![ValueType::I32, ValueType::I32],
+                    None
+                ))
+            ),
+            other => panic!("expected a function import, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use parity_wasm::elements::{External, Module};
+
+use PreprocessingError::{self, InvalidImportsError};
+
+/// Name of the module host functions and memory are imported from.
+pub const HOST_MODULE: &str = "env";
+
+/// Name under which a module may import its linear memory.
+pub const MEMORY_IMPORT: &str = "memory";
+
+/// Host functions a contract may import.
+///
+/// Must be kept in sync with the functions resolved by `engine_core::resolvers`.  `gas` is
+/// listed because the gas counter injected during preparation imports it.
+pub const HOST_FUNCTIONS: &[&str] = &[
+    "read_value",
+    "read_value_local",
+    "serialize_function",
+    "serialize_known_urefs",
+    "write",
+    "write_local",
+    "get_read",
+    "get_function",
+    "add",
+    "new_uref",
+    "load_arg",
+    "get_arg",
+    "ret",
+    "call_contract",
+    "get_call_result",
+    "get_uref",
+    "has_uref_name",
+    "add_uref",
+    "gas",
+    "store_function",
+    "protocol_version",
+    "is_valid",
+    "revert",
+    "add_associated_key",
+    "remove_associated_key",
+    "update_associated_key",
+    "set_action_threshold",
+    "list_known_urefs",
+    "remove_uref",
+    "get_caller",
+    "get_blocktime",
+    "create_purse",
+    "transfer_to_account",
+    "transfer_from_purse_to_account",
+    "transfer_from_purse_to_purse",
+    "get_balance",
+];
+
+/// Checks that every entry of the module's import section is a known host function or the
+/// host memory, naming the first offending import otherwise.
+pub fn validate_imports(module: &Module) -> Result<(), PreprocessingError> {
+    let import_section = match module.import_section() {
+        Some(import_section) => import_section,
+        None => return Ok(()),
+    };
+
+    for entry in import_section.entries() {
+        let module_name = entry.module();
+        let field_name = entry.field();
+
+        if module_name != HOST_MODULE {
+            return Err(InvalidImportsError(format!(
+                "import {}::{} is not from the host module {}",
+                module_name, field_name, HOST_MODULE
+            )));
+        }
+
+        match entry.external() {
+            External::Function(_) if HOST_FUNCTIONS.contains(&field_name) => (),
+            External::Function(_) => {
+                return Err(InvalidImportsError(format!(
+                    "unknown host function {}::{}",
+                    module_name, field_name
+                )));
+            }
+            External::Memory(_) if field_name == MEMORY_IMPORT => (),
+            External::Memory(_) => {
+                return Err(InvalidImportsError(format!(
+                    "memory must be imported as {}::{}, not {}::{}",
+                    HOST_MODULE, MEMORY_IMPORT, module_name, field_name
+                )));
+            }
+            External::Table(_) | External::Global(_) => {
+                return Err(InvalidImportsError(format!(
+                    "host module does not export tables or globals, but {}::{} is imported",
+                    module_name, field_name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
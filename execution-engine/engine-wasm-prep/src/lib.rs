@@ -1,57 +1,203 @@
 extern crate parity_wasm;
 extern crate pwasm_utils;
 
+pub mod gas_profile;
+pub mod imports;
+pub mod memory_limit;
 pub mod wasm_costs;
 
-use parity_wasm::elements::{deserialize_buffer, Error as ParityWasmError, Module};
+use gas_profile::ProfiledModule;
+use parity_wasm::elements::{Deserialize, Error as ParityWasmError, Module};
 use pwasm_utils::{externalize_mem, inject_gas_counter, rules};
-use std::error::Error;
+use std::fmt;
+use std::io::Cursor;
 use wasm_costs::WasmCosts;
 
 //NOTE: size of Wasm memory page is 64 KiB
+/// Default limit on the size of a module's linear memory, in pages
 pub const MEM_PAGES: u32 = 64;
 
+/// Default limit on the size of a module's bytes: 4 MiB
+pub const DEFAULT_MAX_MODULE_SIZE: usize = 4 * 1024 * 1024;
+
 #[derive(Debug)]
 pub enum PreprocessingError {
+    /// The module imports something the host does not provide.
     InvalidImportsError(String),
     NoExportSection,
     NoImportSection,
-    DeserializeError(String),
+    /// The module's bytes are not a well-formed wasm module.
+    DeserializeError {
+        /// Offset in the module's bytes at which parsing stopped
+        offset: usize,
+        reason: String,
+    },
     OperationForbiddenByGasRules,
-    StackLimiterError,
+    /// The stack height limiter could not instrument the module, e.g. because a function's
+    /// type is missing.  Holds the limiter's description of the problem.
+    StackLimiterError(String),
+    /// The module's bytes exceed the configured limit.
+    ModuleTooLarge {
+        actual: usize,
+        max: usize,
+    },
+    /// The module's memory may grow past the configured limit.
+    MemoryLimitExceeded {
+        /// Declared maximum size of the memory, in pages
+        requested: u32,
+        max: u32,
+    },
+}
+
+impl fmt::Display for PreprocessingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidImportsError(message) => write!(f, "invalid imports: {}", message),
+            NoExportSection => write!(f, "module has no export section"),
+            NoImportSection => write!(f, "module has no import section"),
+            DeserializeError { offset, reason } => {
+                write!(f, "malformed module at byte {}: {}", offset, reason)
+            }
+            OperationForbiddenByGasRules => write!(f, "module uses floating point operations"),
+            StackLimiterError(message) => write!(f, "stack height limiter failed: {}", message),
+            ModuleTooLarge { actual, max } => write!(
+                f,
+                "module is {} bytes, more than the maximum of {}",
+                actual, max
+            ),
+            MemoryLimitExceeded { requested, max } => write!(
+                f,
+                "module memory may grow to {} pages, more than the maximum of {}",
+                requested, max
+            ),
+        }
+    }
 }
 
 use PreprocessingError::*;
 
+/// Parses `module_bytes` into a [`Module`], without validating or instrumenting it.
+///
+/// On failure, the error holds the offset at which parsing stopped, which is just past the
+/// offending item, and the reason.
+pub fn deserialize(module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
+    let mut cursor = Cursor::new(module_bytes);
+    let result = Module::deserialize(&mut cursor);
+    let offset = cursor.position() as usize;
+    match result {
+        Ok(_) if offset < module_bytes.len() => Err(DeserializeError {
+            offset,
+            reason: "section is truncated".to_string(),
+        }),
+        Ok(module) => Ok(module),
+        Err(error) => Err(DeserializeError {
+            offset,
+            reason: deserialize_error_reason(error),
+        }),
+    }
+}
+
+fn deserialize_error_reason(error: ParityWasmError) -> String {
+    match error {
+        ParityWasmError::UnexpectedEof => "module is truncated".to_string(),
+        ParityWasmError::InvalidMagic => {
+            "module does not start with the wasm magic number".to_string()
+        }
+        ParityWasmError::UnsupportedVersion(version) => {
+            format!("unsupported wasm version {}, expected 1", version)
+        }
+        ParityWasmError::InvalidSectionId(id) => format!("unknown section id {}", id),
+        other => other.to_string(),
+    }
+}
+
 pub trait Preprocessor<A> {
     fn preprocess(&self, module_bytes: &[u8]) -> Result<A, PreprocessingError>;
 }
 
 pub struct WasmiPreprocessor {
     wasm_costs: WasmCosts,
-    // Number of memory pages.
-    mem_pages: u32,
+    // Largest accepted memory, in pages.
+    max_memory_pages: u32,
+    // Largest accepted module, in bytes.
+    max_module_size: usize,
 }
 
 impl WasmiPreprocessor {
     pub fn new(wasm_costs: WasmCosts) -> WasmiPreprocessor {
         WasmiPreprocessor {
             wasm_costs,
-            mem_pages: MEM_PAGES,
+            max_memory_pages: MEM_PAGES,
+            max_module_size: DEFAULT_MAX_MODULE_SIZE,
+        }
+    }
+
+    /// Sets the largest module, in bytes, accepted by [`Preprocessor::preprocess`].
+    pub fn with_max_module_size(mut self, max_module_size: usize) -> WasmiPreprocessor {
+        self.max_module_size = max_module_size;
+        self
+    }
+
+    /// Sets the most pages of linear memory a module may declare or grow to.
+    pub fn with_max_memory_pages(mut self, max_memory_pages: u32) -> WasmiPreprocessor {
+        self.max_memory_pages = max_memory_pages;
+        self
+    }
+
+    /// Checks, parses and meters `module_bytes`, and routes its memory growth through the host,
+    /// stopping short of limiting the stack height.
+    fn inject_gas(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
+        if module_bytes.len() > self.max_module_size {
+            return Err(ModuleTooLarge {
+                actual: module_bytes.len(),
+                max: self.max_module_size,
+            });
         }
+        let deserialized_module = deserialize(module_bytes)?;
+        imports::validate_imports(&deserialized_module)?;
+        let ext_mod = externalize_mem(deserialized_module, None, self.max_memory_pages);
+        memory_limit::validate_memory(&ext_mod, self.max_memory_pages)?;
+        let gas_mod = inject_gas_counters(ext_mod, &self.wasm_costs)?;
+        Ok(memory_limit::instrument(gas_mod))
+    }
+
+    fn inject_stack_limiter(&self, module: Module) -> Result<Module, PreprocessingError> {
+        pwasm_utils::stack_height::inject_limiter(module, self.wasm_costs.max_stack_height)
+            .map_err(|error| StackLimiterError(format!("{:?}", error)))
     }
 }
 
 impl Preprocessor<Module> for WasmiPreprocessor {
     fn preprocess(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
-        let from_parity_err = |err: ParityWasmError| DeserializeError(err.description().to_owned());
-        let deserialized_module = deserialize_buffer(module_bytes).map_err(from_parity_err)?;
-        let ext_mod = externalize_mem(deserialized_module, None, self.mem_pages);
-        let gas_mod = inject_gas_counters(ext_mod, &self.wasm_costs)?;
-        let module =
-            pwasm_utils::stack_height::inject_limiter(gas_mod, self.wasm_costs.max_stack_height)
-                .map_err(|_| StackLimiterError)?;
-        Ok(module)
+        let gas_mod = self.inject_gas(module_bytes)?;
+        self.inject_stack_limiter(gas_mod)
+    }
+}
+
+/// Prepares modules like the wrapped [`WasmiPreprocessor`], and also instruments them to report
+/// what their gas is spent on.
+///
+/// The extra argument passed to the gas counter counts towards the stack height, so a profiled
+/// module can run out of stack slightly sooner than it would otherwise.
+pub struct GasProfilingPreprocessor(WasmiPreprocessor);
+
+impl GasProfilingPreprocessor {
+    pub fn new(preprocessor: WasmiPreprocessor) -> GasProfilingPreprocessor {
+        GasProfilingPreprocessor(preprocessor)
+    }
+}
+
+impl Preprocessor<ProfiledModule> for GasProfilingPreprocessor {
+    fn preprocess(&self, module_bytes: &[u8]) -> Result<ProfiledModule, PreprocessingError> {
+        let preprocessor = &self.0;
+        let gas_mod = preprocessor.inject_gas(module_bytes)?;
+        let (profiled_mod, sites) =
+            gas_profile::instrument(gas_mod.clone(), &preprocessor.wasm_costs);
+        Ok(ProfiledModule {
+            module: preprocessor.inject_stack_limiter(gas_mod)?,
+            profiled_module: preprocessor.inject_stack_limiter(profiled_mod)?,
+            sites,
+        })
     }
 }
 
@@ -86,3 +232,51 @@ fn inject_gas_counters(
 ) -> Result<Module, PreprocessingError> {
     inject_gas_counter(module, &gas_rules(wasm_costs)).map_err(|_| OperationForbiddenByGasRules)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_reject_module_over_max_size_before_parsing() {
+        let preprocessor = WasmiPreprocessor::new(WasmCosts::free()).with_max_module_size(4);
+        match preprocessor.preprocess(&[0u8; 5]) {
+            Err(ModuleTooLarge { actual: 5, max: 4 }) => (),
+            other => panic!("expected ModuleTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_report_offset_and_reason_of_parse_failures() {
+        let cases: &[(&[u8], usize, &str)] = &[
+            (
+                b"\0wasm\x01\0\0\0",
+                4,
+                "module does not start with the wasm magic number",
+            ),
+            (
+                b"\0asm\x02\0\0\0",
+                8,
+                "unsupported wasm version 2, expected 1",
+            ),
+            (b"\0asm\x01\0\0\0\x0d\0", 9, "unknown section id 13"),
+        ];
+        for (module_bytes, expected_offset, expected_reason) in cases {
+            match deserialize(module_bytes) {
+                Err(DeserializeError { offset, reason }) => {
+                    assert_eq!(offset, *expected_offset);
+                    assert_eq!(reason, *expected_reason);
+                }
+                other => panic!("expected DeserializeError, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn should_report_truncated_module() {
+        match deserialize(b"\0asm\x01\0") {
+            Err(DeserializeError { reason, .. }) => assert_eq!(reason, "module is truncated"),
+            other => panic!("expected DeserializeError, got {:?}", other),
+        }
+    }
+}
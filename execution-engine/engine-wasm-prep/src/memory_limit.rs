@@ -0,0 +1,237 @@
+//! Enforcement of the limit on the size of a module's linear memory.
+//!
+//! A module may only declare a memory whose maximum size is within the limit.
+//! Growing memory past the limit at run time is caught by the host instead: every `grow_memory`
+//! instruction is replaced with a call to [`GROW_MEMORY_FUNCTION`], which traps rather than
+//! growing the memory past the limit.
+
+use parity_wasm::elements::{
+    External, FunctionType, ImportEntry, Instruction, Internal, Module, Section, Type, ValueType,
+};
+
+use imports::{HOST_MODULE, MEMORY_IMPORT};
+use PreprocessingError::{self, MemoryLimitExceeded};
+
+/// Host function called by prepared modules in place of `grow_memory`, with the number of pages
+/// to grow memory by.  Returns the previous size in pages, or -1 if memory could not be grown.
+pub const GROW_MEMORY_FUNCTION: &str = "grow_memory";
+
+/// Most pages a wasm memory can have, which a memory without a declared maximum may grow to.
+const WASM_MAX_PAGES: u32 = 65_536;
+
+/// Checks that the module's imported memory may not grow larger than `max_pages`.  A memory
+/// without a declared maximum is rejected, as it could grow to the largest size wasm allows.
+pub fn validate_memory(module: &Module, max_pages: u32) -> Result<(), PreprocessingError> {
+    let import_section = match module.import_section() {
+        Some(import_section) => import_section,
+        None => return Ok(()),
+    };
+
+    for entry in import_section.entries() {
+        if let External::Memory(memory_type) = entry.external() {
+            if entry.field() != MEMORY_IMPORT {
+                continue;
+            }
+            let limits = memory_type.limits();
+            let requested = limits.maximum().unwrap_or(WASM_MAX_PAGES);
+            if requested > max_pages {
+                return Err(MemoryLimitExceeded {
+                    requested,
+                    max: max_pages,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces each `grow_memory` instruction of the module with a call to an import of
+/// [`GROW_MEMORY_FUNCTION`].  Modules which never grow their memory are left untouched.
+///
+/// The import is added before every other imported function, so it has index 0 and the index of
+/// every other function goes up by one.
+pub fn instrument(mut module: Module) -> Module {
+    let grows_memory = module.code_section().map_or(false, |code_section| {
+        code_section.bodies().iter().any(|body| {
+            body.code()
+                .elements()
+                .iter()
+                .any(|instruction| match instruction {
+                    Instruction::GrowMemory(_) => true,
+                    _ => false,
+                })
+        })
+    });
+    if !grows_memory || add_grow_memory_import(&mut module).is_none() {
+        return module;
+    }
+
+    for section in module.sections_mut() {
+        match section {
+            Section::Code(code_section) => {
+                for body in code_section.bodies_mut() {
+                    for instruction in body.code_mut().elements_mut() {
+                        let replacement = match instruction {
+                            Instruction::Call(function) => Instruction::Call(*function + 1),
+                            Instruction::GrowMemory(_) => Instruction::Call(0),
+                            _ => continue,
+                        };
+                        *instruction = replacement;
+                    }
+                }
+            }
+            Section::Export(export_section) => {
+                for entry in export_section.entries_mut() {
+                    if let Internal::Function(function) = entry.internal_mut() {
+                        *function += 1;
+                    }
+                }
+            }
+            Section::Element(elements_section) => {
+                for segment in elements_section.entries_mut() {
+                    for function in segment.members_mut() {
+                        *function += 1;
+                    }
+                }
+            }
+            Section::Start(function) => *function += 1,
+            _ => (),
+        }
+    }
+
+    module
+}
+
+/// Adds an import of [`GROW_MEMORY_FUNCTION`] before every other imported function.  Returns
+/// `None`, leaving the module unchanged, if it has no import or type section.
+fn add_grow_memory_import(module: &mut Module) -> Option<()> {
+    module.import_section()?;
+    let type_index = {
+        let types = module.type_section_mut()?.types_mut();
+        types.push(Type::Function(FunctionType::new(
+            vec![ValueType::I32],
+            Some(ValueType::I32),
+        )));
+        types.len() as u32 - 1
+    };
+
+    let entries = module.import_section_mut()?.entries_mut();
+    let position = entries
+        .iter()
+        .position(|entry| match entry.external() {
+            External::Function(_) => true,
+            _ => false,
+        })
+        .unwrap_or_else(|| entries.len());
+    entries.insert(
+        position,
+        ImportEntry::new(
+            HOST_MODULE.to_string(),
+            GROW_MEMORY_FUNCTION.to_string(),
+            External::Function(type_index),
+        ),
+    );
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::elements::{
+        CodeSection, ExportEntry, ExportSection, Func, FuncBody, FunctionSection, ImportSection,
+        Instructions, MemoryType, TypeSection,
+    };
+
+    use super::*;
+
+    fn memory_import(initial: u32, maximum: Option<u32>) -> ImportEntry {
+        ImportEntry::new(
+            HOST_MODULE.to_string(),
+            MEMORY_IMPORT.to_string(),
+            External::Memory(MemoryType::new(initial, maximum)),
+        )
+    }
+
+    /// Builds a module importing `env::gas` and memory, which exports a single function made of
+    /// `instructions`.
+    fn module(instructions: Vec<Instruction>) -> Module {
+        let gas_type = Type::Function(FunctionType::new(vec![ValueType::I32], None));
+        let call_type = Type::Function(FunctionType::new(vec![], None));
+        let gas_import = ImportEntry::new(
+            HOST_MODULE.to_string(),
+            "gas".to_string(),
+            External::Function(0),
+        );
+        Module::new(vec![
+            Section::Type(TypeSection::with_types(vec![gas_type, call_type])),
+            Section::Import(ImportSection::with_entries(vec![
+                memory_import(1, Some(16)),
+                gas_import,
+            ])),
+            Section::Function(FunctionSection::with_entries(vec![Func::new(1)])),
+            Section::Export(ExportSection::with_entries(vec![ExportEntry::new(
+                "call".to_string(),
+                Internal::Function(1),
+            )])),
+            Section::Code(CodeSection::with_bodies(vec![FuncBody::new(
+                vec![],
+                Instructions::new(instructions),
+            )])),
+        ])
+    }
+
+    #[test]
+    fn should_accept_memory_within_limit() {
+        let module = module(vec![Instruction::End]);
+        assert!(validate_memory(&module, 16).is_ok());
+    }
+
+    #[test]
+    fn should_reject_memory_declared_past_limit() {
+        let module = module(vec![Instruction::End]);
+        match validate_memory(&module, 8) {
+            Err(MemoryLimitExceeded {
+                requested: 16,
+                max: 8,
+            }) => (),
+            other => panic!("expected MemoryLimitExceeded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_route_grow_memory_through_host() {
+        let module = instrument(module(vec![
+            Instruction::I32Const(1),
+            Instruction::Call(0),
+            Instruction::I32Const(1),
+            Instruction::GrowMemory(0),
+            Instruction::Drop,
+            Instruction::End,
+        ]));
+
+        let imports = module.import_section().unwrap().entries();
+        assert_eq!(imports[1].field(), GROW_MEMORY_FUNCTION);
+        assert_eq!(imports[2].field(), "gas");
+        assert_eq!(
+            module.code_section().unwrap().bodies()[0].code().elements(),
+            &[
+                Instruction::I32Const(1),
+                Instruction::Call(1),
+                Instruction::I32Const(1),
+                Instruction::Call(0),
+                Instruction::Drop,
+                Instruction::End,
+            ][..]
+        );
+        match module.export_section().unwrap().entries()[0].internal() {
+            Internal::Function(2) => (),
+            other => panic!("expected the export to move to function 2, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_not_instrument_module_which_never_grows_memory() {
+        let original = module(vec![Instruction::End]);
+        assert_eq!(instrument(original.clone()), original);
+    }
+}
@@ -1,5 +1,11 @@
+/// Default for [`WasmCosts::max_stack_height`].
+///
+/// Measured in wasm value stack slots, as counted by `pwasm_utils::stack_height`, and shared by
+/// all frames of a call chain.
+pub const DEFAULT_MAX_STACK_HEIGHT: u32 = 64 * 1024;
+
 // Taken (partially) from parity-ethereum
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WasmCosts {
     /// Default opcode cost
     pub regular: u32,
@@ -15,7 +21,8 @@ pub struct WasmCosts {
     pub grow_mem: u32,
     /// Memory copy cost, per byte
     pub memcpy: u32,
-    /// Max stack height (native WebAssembly stack limiter)
+    /// Max stack height (native WebAssembly stack limiter).  Calls which would exceed it trap
+    /// with `unreachable`.
     pub max_stack_height: u32,
     /// Cost of wasm opcode is calculated as TABLE_ENTRY_COST * `opcodes_mul` / `opcodes_div`
     pub opcodes_mul: u32,
@@ -34,7 +41,7 @@ impl WasmCosts {
                 initial_mem: 4096,
                 grow_mem: 8192,
                 memcpy: 1,
-                max_stack_height: 64 * 1024,
+                max_stack_height: DEFAULT_MAX_STACK_HEIGHT,
                 opcodes_mul: 3,
                 opcodes_div: 8,
             }),
@@ -51,7 +58,7 @@ impl WasmCosts {
             initial_mem: 4096,
             grow_mem: 8192,
             memcpy: 0,
-            max_stack_height: 64 * 1024,
+            max_stack_height: DEFAULT_MAX_STACK_HEIGHT,
             opcodes_mul: 1,
             opcodes_div: 1,
         }